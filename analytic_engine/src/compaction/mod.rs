@@ -10,7 +10,7 @@ use snafu::{ensure, Backtrace, GenerateBacktrace, ResultExt, Snafu};
 use tokio::sync::oneshot;
 
 use crate::{
-    compaction::picker::{CommonCompactionPicker, CompactionPickerRef},
+    compaction::picker::{CommonCompactionPicker, CompactionPickerRef, LeveledCompactionPicker},
     instance::write_worker::CompactionNotifier,
     sst::file::{FileHandle, Level},
     table::data::TableDataRef,
@@ -62,6 +62,7 @@ pub enum CompactionStrategy {
     Default,
     TimeWindow(TimeWindowCompactionOptions),
     SizeTiered(SizeTieredCompactionOptions),
+    Leveled(LeveledCompactionOptions),
 }
 
 #[derive(Debug, Clone, Copy, Deserialize, PartialEq)]
@@ -80,6 +81,23 @@ pub struct TimeWindowCompactionOptions {
     pub timestamp_resolution: TimeUnit,
 }
 
+/// Options of the leveled compaction strategy.
+///
+/// Files in level 0 may overlap with each other, while files in level 1 and
+/// deeper levels have non-overlapping key ranges. It is designed for tables in
+/// overwrite mode.
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq)]
+pub struct LeveledCompactionOptions {
+    /// Number of files in level 0 to trigger a compaction into level 1.
+    pub level0_file_num_trigger: usize,
+    /// Max total size of level 1.
+    pub max_bytes_for_level_base: ReadableSize,
+    /// Max total size of level n+1 is `level_size_multiplier` times of level n.
+    pub level_size_multiplier: usize,
+    /// Target size of the files output by a compaction.
+    pub target_file_size: ReadableSize,
+}
+
 impl protobuf::Clear for SizeTieredCompactionOptions {
     fn clear(&mut self) {
         *self = SizeTieredCompactionOptions::default()
@@ -92,6 +110,12 @@ impl protobuf::Clear for TimeWindowCompactionOptions {
     }
 }
 
+impl protobuf::Clear for LeveledCompactionOptions {
+    fn clear(&mut self) {
+        *self = LeveledCompactionOptions::default()
+    }
+}
+
 impl Default for SizeTieredCompactionOptions {
     fn default() -> Self {
         Self {
@@ -113,6 +137,17 @@ impl Default for TimeWindowCompactionOptions {
     }
 }
 
+impl Default for LeveledCompactionOptions {
+    fn default() -> Self {
+        Self {
+            level0_file_num_trigger: 4,
+            max_bytes_for_level_base: ReadableSize::mb(256),
+            level_size_multiplier: 10,
+            target_file_size: ReadableSize::mb(64),
+        }
+    }
+}
+
 impl Default for CompactionStrategy {
     fn default() -> Self {
         CompactionStrategy::Default
@@ -125,9 +160,14 @@ const MIN_THRESHOLD_KEY: &str = "compaction_min_threshold";
 const MAX_THRESHOLD_KEY: &str = "compaction_max_threshold";
 const MIN_SSTABLE_SIZE_KEY: &str = "compaction_min_sstable_size";
 const TIMESTAMP_RESOLUTION_KEY: &str = "compaction_timestamp_resolution";
+const LEVEL0_FILE_NUM_TRIGGER_KEY: &str = "compaction_level0_file_num_trigger";
+const MAX_BYTES_FOR_LEVEL_BASE_KEY: &str = "compaction_max_bytes_for_level_base";
+const LEVEL_SIZE_MULTIPLIER_KEY: &str = "compaction_level_size_multiplier";
+const TARGET_FILE_SIZE_KEY: &str = "compaction_target_file_size";
 const DEFAULT_STRATEGY: &str = "default";
 const STC_STRATEGY: &str = "size_tiered";
 const TWC_STRATEGY: &str = "time_window";
+const LCS_STRATEGY: &str = "leveled";
 
 impl CompactionStrategy {
     pub(crate) fn parse_from(
@@ -142,6 +182,9 @@ impl CompactionStrategy {
             TWC_STRATEGY => Ok(CompactionStrategy::TimeWindow(
                 TimeWindowCompactionOptions::parse_from(options)?,
             )),
+            LCS_STRATEGY => Ok(CompactionStrategy::Leveled(
+                LeveledCompactionOptions::parse_from(options)?,
+            )),
             _ => ParseStrategy {
                 value: value.to_string(),
             }
@@ -165,6 +208,10 @@ impl CompactionStrategy {
                 m.insert(COMPACTION_STRATEGY.to_string(), TWC_STRATEGY.to_string());
                 opts.fill_raw_map(m);
             }
+            CompactionStrategy::Leveled(opts) => {
+                m.insert(COMPACTION_STRATEGY.to_string(), LCS_STRATEGY.to_string());
+                opts.fill_raw_map(m);
+            }
         }
     }
 }
@@ -304,14 +351,123 @@ impl TimeWindowCompactionOptions {
     }
 }
 
+impl LeveledCompactionOptions {
+    /// Max total size of the given `level`, level 0 is limited by the file
+    /// number instead of the size.
+    pub fn max_bytes_for_level(&self, level: Level) -> u64 {
+        let mut max_bytes = self.max_bytes_for_level_base.as_bytes();
+        for _ in 1..level {
+            max_bytes = max_bytes.saturating_mul(self.level_size_multiplier as u64);
+        }
+
+        max_bytes
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), Error> {
+        ensure!(
+            self.level0_file_num_trigger > 0,
+            InvalidOption {
+                error: format!("{} should be greater than 0", LEVEL0_FILE_NUM_TRIGGER_KEY),
+            }
+        );
+        ensure!(
+            self.level_size_multiplier > 1,
+            InvalidOption {
+                error: format!("{} should be greater than 1", LEVEL_SIZE_MULTIPLIER_KEY),
+            }
+        );
+        ensure!(
+            self.max_bytes_for_level_base.as_bytes() > 0 && self.target_file_size.as_bytes() > 0,
+            InvalidOption {
+                error: format!(
+                    "{} and {} should be greater than 0",
+                    MAX_BYTES_FOR_LEVEL_BASE_KEY, TARGET_FILE_SIZE_KEY
+                ),
+            }
+        );
+
+        Ok(())
+    }
+
+    fn fill_raw_map(&self, m: &mut HashMap<String, String>) {
+        m.insert(
+            LEVEL0_FILE_NUM_TRIGGER_KEY.to_string(),
+            format!("{}", self.level0_file_num_trigger),
+        );
+        m.insert(
+            MAX_BYTES_FOR_LEVEL_BASE_KEY.to_string(),
+            format!("{}", self.max_bytes_for_level_base.0),
+        );
+        m.insert(
+            LEVEL_SIZE_MULTIPLIER_KEY.to_string(),
+            format!("{}", self.level_size_multiplier),
+        );
+        m.insert(
+            TARGET_FILE_SIZE_KEY.to_string(),
+            format!("{}", self.target_file_size.0),
+        );
+    }
+
+    fn parse_size(
+        options: &HashMap<String, String>,
+        key: &str,
+    ) -> Result<Option<ReadableSize>, Error> {
+        options
+            .get(key)
+            .map(|v| {
+                v.parse::<ReadableSize>().map_err(|err| Error::ParseSize {
+                    key: key.to_string(),
+                    value: v.to_string(),
+                    error: err,
+                    backtrace: Backtrace::generate(),
+                })
+            })
+            .transpose()
+    }
+
+    pub(crate) fn parse_from(
+        options: &HashMap<String, String>,
+    ) -> Result<LeveledCompactionOptions, Error> {
+        let mut opts = LeveledCompactionOptions::default();
+        if let Some(v) = options.get(LEVEL0_FILE_NUM_TRIGGER_KEY) {
+            opts.level0_file_num_trigger = v.parse().context(ParseInt {
+                key: LEVEL0_FILE_NUM_TRIGGER_KEY,
+                value: v,
+            })?;
+        }
+        if let Some(v) = Self::parse_size(options, MAX_BYTES_FOR_LEVEL_BASE_KEY)? {
+            opts.max_bytes_for_level_base = v;
+        }
+        if let Some(v) = options.get(LEVEL_SIZE_MULTIPLIER_KEY) {
+            opts.level_size_multiplier = v.parse().context(ParseInt {
+                key: LEVEL_SIZE_MULTIPLIER_KEY,
+                value: v,
+            })?;
+        }
+        if let Some(v) = Self::parse_size(options, TARGET_FILE_SIZE_KEY)? {
+            opts.target_file_size = v;
+        }
+
+        opts.validate()?;
+
+        Ok(opts)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct CompactionInputFiles {
     /// Level of the files to be compacted.
     pub level: Level,
     /// Files to be compacted.
     pub files: Vec<FileHandle>,
+    /// Files in the output level whose key ranges overlap with `files`, they
+    /// are merged and rewritten together with `files`.
+    pub overlapping_files: Vec<FileHandle>,
     /// The output level of the merged file.
     pub output_level: Level,
+    /// Split the merged data into files of about this size, all the data is
+    /// written into a single file if it is None.
+    pub max_output_file_size: Option<u64>,
 }
 
 #[derive(Default, Clone)]
@@ -331,7 +487,7 @@ pub struct CompactionTask {
 impl CompactionTask {
     pub fn mark_files_being_compacted(&self, being_compacted: bool) {
         for input in &self.compaction_inputs {
-            for file in &input.files {
+            for file in input.files.iter().chain(&input.overlapping_files) {
                 file.set_being_compacted(being_compacted);
             }
         }
@@ -347,6 +503,7 @@ pub struct PickerManager {
     default_picker: CompactionPickerRef,
     time_window_picker: CompactionPickerRef,
     size_tiered_picker: CompactionPickerRef,
+    leveled_picker: CompactionPickerRef,
 }
 
 impl Default for PickerManager {
@@ -362,6 +519,7 @@ impl Default for PickerManager {
             default_picker: time_window_picker.clone(),
             size_tiered_picker,
             time_window_picker,
+            leveled_picker: Arc::new(LeveledCompactionPicker::default()),
         }
     }
 }
@@ -372,6 +530,7 @@ impl PickerManager {
             CompactionStrategy::Default => self.default_picker.clone(),
             CompactionStrategy::SizeTiered(_) => self.size_tiered_picker.clone(),
             CompactionStrategy::TimeWindow(_) => self.time_window_picker.clone(),
+            CompactionStrategy::Leveled(_) => self.leveled_picker.clone(),
         }
     }
 }
@@ -490,5 +649,42 @@ mod tests {
             c,
             CompactionStrategy::parse_from("time_window", &m).unwrap()
         );
+
+        let lcs_opts = LeveledCompactionOptions {
+            level0_file_num_trigger: 8,
+            target_file_size: ReadableSize(2048),
+            ..Default::default()
+        };
+        let c = CompactionStrategy::Leveled(lcs_opts);
+        let mut m = HashMap::new();
+        c.fill_raw_map(&mut m);
+
+        assert_eq!(5, m.len());
+        assert_eq!(m[COMPACTION_STRATEGY], "leveled");
+        assert_eq!(m[LEVEL0_FILE_NUM_TRIGGER_KEY], "8");
+        assert_eq!(m[MAX_BYTES_FOR_LEVEL_BASE_KEY], "268435456");
+        assert_eq!(m[LEVEL_SIZE_MULTIPLIER_KEY], "10");
+        assert_eq!(m[TARGET_FILE_SIZE_KEY], "2048");
+        assert_eq!(c, CompactionStrategy::parse_from("leveled", &m).unwrap());
+    }
+
+    #[test]
+    fn test_leveled_options() {
+        let opts = LeveledCompactionOptions {
+            max_bytes_for_level_base: ReadableSize(100),
+            ..Default::default()
+        };
+        assert_eq!(100, opts.max_bytes_for_level(1));
+        assert_eq!(1000, opts.max_bytes_for_level(2));
+        assert_eq!(10000, opts.max_bytes_for_level(3));
+
+        let mut m = HashMap::new();
+        m.insert(LEVEL_SIZE_MULTIPLIER_KEY.to_string(), "1".to_string());
+        assert!(LeveledCompactionOptions::parse_from(&m).is_err());
+
+        let mut m = HashMap::new();
+        m.insert(MAX_BYTES_FOR_LEVEL_BASE_KEY.to_string(), "1KB".to_string());
+        let opts = LeveledCompactionOptions::parse_from(&m).unwrap();
+        assert_eq!(1024, opts.max_bytes_for_level_base.as_bytes());
     }
 }
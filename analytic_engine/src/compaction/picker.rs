@@ -3,20 +3,21 @@
 //! Compaction picker.
 
 use std::{
+    cmp,
     collections::{BTreeSet, HashMap},
     sync::Arc,
     time::Duration,
 };
 
-use common_types::time::Timestamp;
+use common_types::{bytes::Bytes, time::Timestamp};
 use common_util::{config::TimeUnit, define_result};
 use log::{debug, info};
 use snafu::Snafu;
 
 use crate::{
     compaction::{
-        CompactionInputFiles, CompactionStrategy, CompactionTask, SizeTieredCompactionOptions,
-        TimeWindowCompactionOptions,
+        CompactionInputFiles, CompactionStrategy, CompactionTask, LeveledCompactionOptions,
+        SizeTieredCompactionOptions, TimeWindowCompactionOptions,
     },
     sst::{
         file::{FileHandle, Level},
@@ -51,6 +52,13 @@ impl PickerContext {
             _ => TimeWindowCompactionOptions::default(),
         }
     }
+
+    fn leveled_opts(&self) -> LeveledCompactionOptions {
+        match self.strategy {
+            CompactionStrategy::Leveled(opts) => opts,
+            _ => LeveledCompactionOptions::default(),
+        }
+    }
 }
 
 pub trait CompactionPicker {
@@ -86,9 +94,10 @@ pub struct CommonCompactionPicker {
 impl CommonCompactionPicker {
     pub fn new(strategy: CompactionStrategy) -> Self {
         let level_picker: LevelPickerRef = match strategy {
-            CompactionStrategy::SizeTiered(_) | CompactionStrategy::Default => {
-                Arc::new(SizeTieredPicker::default())
-            }
+            // The leveled strategy should use the [LeveledCompactionPicker] instead.
+            CompactionStrategy::SizeTiered(_)
+            | CompactionStrategy::Default
+            | CompactionStrategy::Leveled(_) => Arc::new(SizeTieredPicker::default()),
             CompactionStrategy::TimeWindow(_) => Arc::new(TimeWindowPicker::default()),
         };
         Self { level_picker }
@@ -112,8 +121,10 @@ impl CommonCompactionPicker {
                 return Some(CompactionInputFiles {
                     level,
                     files,
+                    overlapping_files: Vec::new(),
                     // Now, we always output to the same level.
                     output_level: level,
+                    max_output_file_size: None,
                 });
             }
         }
@@ -134,8 +145,9 @@ impl CompactionPicker for CommonCompactionPicker {
             ..Default::default()
         };
 
-        if let Some(input_files) =
-            self.pick_compact_candidates(&ctx, levels_controller, expire_time)
+        if let Some(input_files) = self
+            .pick_compact_candidates(&ctx, levels_controller, expire_time)
+            .or_else(|| pick_partially_expired_file(levels_controller, expire_time))
         {
             info!(
                 "Compaction strategy: {:?} picker pick files to compact, input_files:{:?}",
@@ -163,6 +175,57 @@ fn find_uncompact_files(
         .collect()
 }
 
+/// A partially expired file is rewritten only if at least this ratio of its
+/// time range is expired, otherwise the same file would be rewritten again and
+/// again as the expire time moves on.
+const MIN_EXPIRED_RATIO_TO_REWRITE: f64 = 0.5;
+
+/// Pick the partially expired file to rewrite, so its expired rows are dropped
+/// by the compaction.
+///
+/// Only one file is picked and it is rewritten to the same level, so the key
+/// ranges of the files in the level are unchanged.
+fn pick_partially_expired_file(
+    levels_controller: &LevelsController,
+    expire_time: Option<Timestamp>,
+) -> Option<CompactionInputFiles> {
+    let expire_time = expire_time?;
+    for level in 0..levels_controller.num_levels() {
+        let file = levels_controller
+            .iter_ssts_at_level(level)
+            .filter(|file| !file.being_compacted())
+            .filter_map(|file| {
+                let time_range = file.time_range();
+                let start = time_range.inclusive_start().as_i64();
+                let end = time_range.exclusive_end().as_i64();
+                let expire = expire_time.as_i64();
+                if start >= expire || end <= expire {
+                    // Not expired or totally expired.
+                    return None;
+                }
+                let ratio = (expire - start) as f64 / (end - start) as f64;
+                if ratio < MIN_EXPIRED_RATIO_TO_REWRITE {
+                    return None;
+                }
+                Some((ratio, file))
+            })
+            .max_by(|(r1, _), (r2, _)| r1.partial_cmp(r2).unwrap())
+            .map(|(_, file)| file.clone());
+
+        if let Some(file) = file {
+            return Some(CompactionInputFiles {
+                level,
+                files: vec![file],
+                overlapping_files: Vec::new(),
+                output_level: level,
+                max_output_file_size: None,
+            });
+        }
+    }
+
+    None
+}
+
 /// Size tiered compaction strategy
 /// See https://github.com/jeffjirsa/twcs/blob/master/src/main/java/com/jeffjirsa/cassandra/db/compaction/SizeTieredCompactionStrategy.java
 #[derive(Default)]
@@ -578,6 +641,182 @@ impl LevelPicker for TimeWindowPicker {
     }
 }
 
+/// Leveled compaction strategy
+/// See https://github.com/facebook/rocksdb/wiki/Leveled-Compaction
+///
+/// Files in level 0 are compacted into level 1 once the number of them
+/// reaches the trigger, and a file in level n (n >= 1) is compacted into level
+/// n+1 once the total size of level n exceeds its target. Files in level 1 and
+/// deeper levels never overlap with each other in key range, so only the files
+/// in the next level overlapping with the picked files need to be rewritten.
+#[derive(Default)]
+pub struct LeveledCompactionPicker {}
+
+impl CompactionPicker for LeveledCompactionPicker {
+    fn pick_compaction(
+        &self,
+        ctx: PickerContext,
+        levels_controller: &LevelsController,
+    ) -> Result<CompactionTask> {
+        let expire_time = ctx.ttl.map(Timestamp::expire_time);
+        let mut compaction_task = CompactionTask {
+            expired: levels_controller.expired_ssts(expire_time),
+            ..Default::default()
+        };
+
+        if let Some(input_files) =
+            Self::pick_compact_candidates(&ctx.leveled_opts(), levels_controller, expire_time)
+                .or_else(|| pick_partially_expired_file(levels_controller, expire_time))
+        {
+            info!(
+                "Compaction strategy: {:?} picker pick files to compact, input_files:{:?}",
+                ctx.strategy, input_files
+            );
+
+            compaction_task.compaction_inputs = vec![input_files];
+        }
+
+        Ok(compaction_task)
+    }
+}
+
+impl LeveledCompactionPicker {
+    fn pick_compact_candidates(
+        opts: &LeveledCompactionOptions,
+        levels_controller: &LevelsController,
+        expire_time: Option<Timestamp>,
+    ) -> Option<CompactionInputFiles> {
+        // The last level has no size limit and is never compacted into the next level.
+        let last_level = levels_controller.num_levels() - 1;
+        let mut level_scores: Vec<_> = (0..last_level)
+            .map(|level| {
+                let score = Self::level_score(opts, levels_controller, level, expire_time);
+                (level, score)
+            })
+            .filter(|(_, score)| *score >= 1.0)
+            .collect();
+        // The level with the highest score is compacted first.
+        level_scores.sort_unstable_by(|(_, s1), (_, s2)| s2.partial_cmp(s1).unwrap());
+
+        for (level, score) in level_scores {
+            let candidates = if level == 0 {
+                Self::pick_level0_files(levels_controller, expire_time)
+            } else {
+                Self::pick_file_at_level(levels_controller, level, expire_time)
+            };
+
+            if let Some((files, overlapping_files)) = candidates {
+                debug!(
+                    "Leveled picker pick files at level:{}, score:{}, files:{:?}",
+                    level, score, files
+                );
+                return Some(CompactionInputFiles {
+                    level,
+                    files,
+                    overlapping_files,
+                    output_level: level + 1,
+                    max_output_file_size: Some(opts.target_file_size.as_bytes()),
+                });
+            }
+        }
+
+        None
+    }
+
+    /// The level needs compaction if its score is not less than 1.
+    fn level_score(
+        opts: &LeveledCompactionOptions,
+        levels_controller: &LevelsController,
+        level: Level,
+        expire_time: Option<Timestamp>,
+    ) -> f64 {
+        let files = find_uncompact_files(levels_controller, level, expire_time);
+        if level == 0 {
+            files.len() as f64 / opts.level0_file_num_trigger as f64
+        } else {
+            let level_size: u64 = files.iter().map(FileHandle::size).sum();
+            level_size as f64 / opts.max_bytes_for_level(level) as f64
+        }
+    }
+
+    /// Pick all the files in level 0 as they may overlap with each other.
+    fn pick_level0_files(
+        levels_controller: &LevelsController,
+        expire_time: Option<Timestamp>,
+    ) -> Option<(Vec<FileHandle>, Vec<FileHandle>)> {
+        // Only one compaction of level 0 is allowed at the same time.
+        if levels_controller
+            .iter_ssts_at_level(0)
+            .any(FileHandle::being_compacted)
+        {
+            return None;
+        }
+
+        let files = find_uncompact_files(levels_controller, 0, expire_time);
+        let min_key = files.iter().map(FileHandle::min_key).min()?;
+        let max_key = files.iter().map(FileHandle::max_key).max()?;
+        let overlapping_files =
+            Self::overlapping_files(levels_controller, 1, &min_key, &max_key, expire_time)?;
+
+        Some((files, overlapping_files))
+    }
+
+    /// Pick the file whose overlapping files in the next level are the
+    /// smallest relative to its own size, which minimizes the write
+    /// amplification.
+    fn pick_file_at_level(
+        levels_controller: &LevelsController,
+        level: Level,
+        expire_time: Option<Timestamp>,
+    ) -> Option<(Vec<FileHandle>, Vec<FileHandle>)> {
+        find_uncompact_files(levels_controller, level, expire_time)
+            .into_iter()
+            .filter_map(|file| {
+                let overlapping_files = Self::overlapping_files(
+                    levels_controller,
+                    level + 1,
+                    &file.min_key(),
+                    &file.max_key(),
+                    expire_time,
+                )?;
+                let overlapping_size: u64 = overlapping_files.iter().map(FileHandle::size).sum();
+                let ratio = overlapping_size as f64 / cmp::max(file.size(), 1) as f64;
+                Some((ratio, file, overlapping_files))
+            })
+            .min_by(|(r1, ..), (r2, ..)| r1.partial_cmp(r2).unwrap())
+            .map(|(_, file, overlapping_files)| (vec![file], overlapping_files))
+    }
+
+    /// Find the files in `level` overlapping with the key range [min_key,
+    /// max_key], returns None if any of them is being compacted.
+    ///
+    /// The expired files are ignored as they will be deleted by the
+    /// compaction.
+    fn overlapping_files(
+        levels_controller: &LevelsController,
+        level: Level,
+        min_key: &Bytes,
+        max_key: &Bytes,
+        expire_time: Option<Timestamp>,
+    ) -> Option<Vec<FileHandle>> {
+        let mut overlapping_files = Vec::new();
+        for file in levels_controller.iter_ssts_at_level(level) {
+            if file.time_range().is_expired(expire_time)
+                || file.max_key() < *min_key
+                || file.min_key() > *max_key
+            {
+                continue;
+            }
+            if file.being_compacted() {
+                return None;
+            }
+            overlapping_files.push(file.clone());
+        }
+
+        Some(overlapping_files)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::time::Duration;
@@ -587,9 +826,12 @@ mod tests {
         tests::build_schema,
         time::{TimeRange, Timestamp},
     };
+    use common_util::config::ReadableSize;
 
     use crate::{
-        compaction::{picker::PickerContext, CompactionStrategy, PickerManager},
+        compaction::{
+            picker::PickerContext, CompactionStrategy, LeveledCompactionOptions, PickerManager,
+        },
         sst::{
             file::SstMetaData,
             manager::{tests::LevelsControllerMockBuilder, LevelsController},
@@ -605,6 +847,21 @@ mod tests {
             schema: build_schema(),
             size,
             row_num: 2,
+            sst_filter: None,
+            inverted_index: None,
+        }
+    }
+
+    fn build_sst_meta_data_with_key_range(
+        min_key: &'static [u8],
+        max_key: &'static [u8],
+        time_range: TimeRange,
+        size: u64,
+    ) -> SstMetaData {
+        SstMetaData {
+            min_key: Bytes::from_static(min_key),
+            max_key: Bytes::from_static(max_key),
+            ..build_sst_meta_data(time_range, size)
         }
     }
 
@@ -737,4 +994,161 @@ mod tests {
             assert!(task.expired[0].files.is_empty());
         }
     }
+
+    #[test]
+    fn test_pick_partially_expired_file() {
+        let picker_manager = PickerManager::default();
+        let twp = picker_manager.get_picker(CompactionStrategy::Default);
+        let ctx = PickerContext {
+            segment_duration: Duration::from_millis(1000),
+            ttl: Some(Duration::from_secs(10)),
+            strategy: CompactionStrategy::Default,
+        };
+        let now = Timestamp::now().as_i64();
+        let build_meta = |start, end| {
+            build_sst_meta_data(
+                TimeRange::new_unchecked(Timestamp::new(start), Timestamp::new(end)),
+                2,
+            )
+        };
+
+        // Less than half of file [0] is expired, most of file [1] is expired and file
+        // [2] is totally expired.
+        let lc = LevelsControllerMockBuilder::default()
+            .add_sst(vec![
+                build_meta(now - 15000, now - 1000),
+                build_meta(now - 30000, now - 5000),
+                build_meta(100, 200),
+            ])
+            .build();
+        let task = twp.pick_compaction(ctx.clone(), &lc).unwrap();
+        assert_eq!(task.expired[0].files.len(), 1);
+        assert_eq!(task.expired[0].files[0].id(), 2);
+        let input = &task.compaction_inputs[0];
+        assert_eq!(input.level, 0);
+        assert_eq!(input.output_level, 0);
+        assert_eq!(input.files.len(), 1);
+        assert_eq!(input.files[0].id(), 1);
+        assert!(input.overlapping_files.is_empty());
+
+        // The file being compacted can't be picked.
+        for file in lc.iter_ssts_at_level(0) {
+            if file.id() == 1 {
+                file.set_being_compacted(true);
+            }
+        }
+        let task = twp.pick_compaction(ctx, &lc).unwrap();
+        assert!(task.compaction_inputs.is_empty());
+    }
+
+    #[test]
+    fn test_leveled_picker() {
+        let picker_manager = PickerManager::default();
+        let opts = LeveledCompactionOptions {
+            level0_file_num_trigger: 2,
+            max_bytes_for_level_base: ReadableSize(100),
+            ..Default::default()
+        };
+        let strategy = CompactionStrategy::Leveled(opts);
+        let lcp = picker_manager.get_picker(strategy);
+        let ctx = PickerContext {
+            segment_duration: Duration::from_millis(1000),
+            ttl: None,
+            strategy,
+        };
+        let now = Timestamp::now().as_i64();
+        let time_range = TimeRange::new_unchecked(Timestamp::new(now - 1000), Timestamp::new(now));
+        let build_meta = |min_key, max_key, size| {
+            build_sst_meta_data_with_key_range(min_key, max_key, time_range, size)
+        };
+
+        // Level 0 files [0, 1] reach the trigger, and they overlap with level 1 file
+        // [2] but not [3].
+        {
+            let lc = LevelsControllerMockBuilder::default()
+                .add_sst(vec![
+                    build_meta(b"100", b"200", 10),
+                    build_meta(b"150", b"300", 10),
+                ])
+                .add_sst_to_level(
+                    1,
+                    vec![
+                        build_meta(b"250", b"400", 10),
+                        build_meta(b"500", b"600", 10),
+                    ],
+                )
+                .build();
+            let task = lcp.pick_compaction(ctx.clone(), &lc).unwrap();
+            let input = &task.compaction_inputs[0];
+            assert_eq!(input.level, 0);
+            assert_eq!(input.output_level, 1);
+            assert_eq!(input.files.len(), 2);
+            assert_eq!(input.files[0].id(), 0);
+            assert_eq!(input.files[1].id(), 1);
+            assert_eq!(input.overlapping_files.len(), 1);
+            assert_eq!(input.overlapping_files[0].id(), 2);
+            assert_eq!(
+                input.max_output_file_size,
+                Some(opts.target_file_size.as_bytes())
+            );
+        }
+
+        // Level 1 exceeds its max size, the file [2] overlaps with less data in level 2
+        // than the file [1].
+        let build_level1_case = || {
+            LevelsControllerMockBuilder::default()
+                .add_sst(vec![build_meta(b"100", b"200", 10)])
+                .add_sst_to_level(
+                    1,
+                    vec![
+                        build_meta(b"100", b"200", 60),
+                        build_meta(b"300", b"400", 60),
+                    ],
+                )
+                .add_sst_to_level(
+                    2,
+                    vec![
+                        build_meta(b"100", b"250", 500),
+                        build_meta(b"350", b"360", 10),
+                    ],
+                )
+                .build()
+        };
+        {
+            let lc = build_level1_case();
+            let task = lcp.pick_compaction(ctx.clone(), &lc).unwrap();
+            let input = &task.compaction_inputs[0];
+            assert_eq!(input.level, 1);
+            assert_eq!(input.output_level, 2);
+            assert_eq!(input.files.len(), 1);
+            assert_eq!(input.files[0].id(), 2);
+            assert_eq!(input.overlapping_files.len(), 1);
+            assert_eq!(input.overlapping_files[0].id(), 4);
+        }
+
+        // The file [4] is being compacted, so the file [2] can't be picked.
+        {
+            let lc = build_level1_case();
+            for file in lc.iter_ssts_at_level(2) {
+                if file.id() == 4 {
+                    file.set_being_compacted(true);
+                }
+            }
+            let task = lcp.pick_compaction(ctx.clone(), &lc).unwrap();
+            let input = &task.compaction_inputs[0];
+            assert_eq!(input.files[0].id(), 1);
+            assert_eq!(input.overlapping_files.len(), 1);
+            assert_eq!(input.overlapping_files[0].id(), 3);
+        }
+
+        // No level needs compaction.
+        {
+            let lc = LevelsControllerMockBuilder::default()
+                .add_sst(vec![build_meta(b"100", b"200", 10)])
+                .add_sst_to_level(1, vec![build_meta(b"100", b"200", 10)])
+                .build();
+            let task = lcp.pick_compaction(ctx, &lc).unwrap();
+            assert!(task.compaction_inputs.is_empty());
+        }
+    }
 }
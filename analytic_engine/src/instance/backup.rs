@@ -0,0 +1,561 @@
+// Copyright 2022 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Snapshot and restore logic of instance
+//!
+//! A snapshot of a table under the prefix of the backup store looks like:
+//! ```text
+//! {prefix}/MANIFEST
+//! {prefix}/{file_id}.sst
+//! ...
+//! ```
+//! The `MANIFEST` is written after all the sst files are copied, so a snapshot
+//! without it is incomplete and can't be restored.
+
+use std::collections::HashMap;
+
+use common_types::{bytes::BytesMut, schema::Schema, time::TimeRange, SequenceNumber};
+use common_util::define_result;
+use futures::TryStreamExt;
+use log::{info, warn};
+use object_store::{GetResult, ObjectStore, ObjectStoreError, Path};
+use snafu::{ensure, Backtrace, OptionExt, ResultExt, Snafu};
+use table_engine::table::{SnapshotStats, TableId};
+use tokio::sync::oneshot;
+use wal::manager::WalManager;
+
+use crate::{
+    instance::{
+        write_worker::{self, RestoreTableCommand, WorkerLocal},
+        Instance,
+    },
+    meta::{
+        meta_update::{MetaUpdate, TableSnapshot, VersionEditMeta},
+        Manifest,
+    },
+    space::SpaceAndTable,
+    sst::{
+        factory::Factory,
+        file::{FileHandle, FileMeta},
+        manager::FileId,
+    },
+    table::{data::TableData, sst_util, version_edit::AddFile},
+};
+
+/// Name of the manifest file in the snapshot.
+const BACKUP_MANIFEST_NAME: &str = "MANIFEST";
+/// Max times to retry pinning the sst files of the table.
+const MAX_PIN_FILES_RETRY: usize = 3;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Failed to load manifest, table:{}, err:{}", table, source))]
+    LoadManifest {
+        table: String,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+
+    #[snafu(display(
+        "Table not found in manifest, table:{}, table_id:{}.\nBacktrace:\n{}",
+        table,
+        table_id,
+        backtrace
+    ))]
+    TableNotInManifest {
+        table: String,
+        table_id: TableId,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "Sst files of the table keep changing during snapshot, table:{}, retry:{}.\nBacktrace:\n{}",
+        table,
+        retry,
+        backtrace
+    ))]
+    PinFiles {
+        table: String,
+        retry: usize,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("Failed to read object, path:{}, err:{}", path, source))]
+    ReadObject {
+        path: String,
+        source: ObjectStoreError,
+    },
+
+    #[snafu(display("Failed to write object, path:{}, err:{}", path, source))]
+    WriteObject {
+        path: String,
+        source: ObjectStoreError,
+    },
+
+    #[snafu(display("Failed to encode table backup, table:{}, err:{}", table, source))]
+    EncodeBackup {
+        table: String,
+        source: crate::meta::meta_update::Error,
+    },
+
+    #[snafu(display("Failed to decode table backup, path:{}, err:{}", path, source))]
+    DecodeBackup {
+        path: String,
+        source: crate::meta::meta_update::Error,
+    },
+
+    #[snafu(display(
+        "Table to restore is not empty, table:{}.\nBacktrace:\n{}",
+        table,
+        backtrace
+    ))]
+    TableNotEmpty { table: String, backtrace: Backtrace },
+
+    #[snafu(display(
+        "Schema of the snapshot is incompatible with the table, table:{}, msg:{}.\nBacktrace:\n{}",
+        table,
+        msg,
+        backtrace
+    ))]
+    IncompatibleSchema {
+        table: String,
+        msg: String,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("Failed to store version edit, table:{}, err:{}", table, source))]
+    StoreVersionEdit {
+        table: String,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+
+    #[snafu(display("Failed to operate by write worker, table:{}, err:{}", table, source))]
+    OperateByWriteWorker {
+        table: String,
+        source: crate::instance::write_worker::Error,
+    },
+}
+
+define_result!(Error);
+
+impl<Wal, Meta, Store, Fa> Instance<Wal, Meta, Store, Fa>
+where
+    Wal: WalManager + Send + Sync + 'static,
+    Meta: Manifest + Send + Sync + 'static,
+    Store: ObjectStore,
+    Fa: Factory + Send + Sync + 'static,
+{
+    /// Copy the sst files and the manifest of the table to `prefix` of the
+    /// `backup_store`.
+    ///
+    /// Only the flushed data is included, the caller should flush the table
+    /// before taking the snapshot.
+    pub async fn snapshot_table(
+        &self,
+        space_table: &SpaceAndTable,
+        backup_store: &dyn ObjectStore,
+        prefix: &str,
+    ) -> Result<SnapshotStats> {
+        let table_data = space_table.table_data();
+
+        // Keep the handles until all files are copied so they won't be purged by
+        // compaction.
+        let (backup, _pinned_files) = self.pin_table_backup(table_data).await?;
+
+        info!(
+            "Instance snapshot table begin, table:{}, prefix:{}, files:{}, tombstones:{}",
+            table_data.name,
+            prefix,
+            backup.version_edit.files_to_add.len(),
+            backup.version_edit.tombstones_to_add.len(),
+        );
+
+        let mut stats = SnapshotStats::default();
+        for add_file in &backup.version_edit.files_to_add {
+            let src = table_data.set_sst_file_path(add_file.file.id);
+            let dst = backup_path(prefix, &sst_util::sst_file_name(add_file.file.id));
+            let size = add_file.file.meta.size as usize;
+            copy_object(self.space_store.store_ref(), &src, backup_store, &dst, size).await?;
+
+            stats.num_files += 1;
+            stats.num_bytes += add_file.file.meta.size;
+            stats.num_rows += add_file.file.meta.row_num;
+        }
+
+        let manifest_path = backup_path(prefix, BACKUP_MANIFEST_NAME);
+        let manifest = backup.encode().context(EncodeBackup {
+            table: &table_data.name,
+        })?;
+        backup_store
+            .put(&manifest_path, manifest.into())
+            .await
+            .context(WriteObject {
+                path: manifest_path.to_string(),
+            })?;
+
+        info!(
+            "Instance snapshot table finished, table:{}, prefix:{}, stats:{:?}",
+            table_data.name, prefix, stats
+        );
+
+        Ok(stats)
+    }
+
+    /// Restore the snapshot under `prefix` of the `backup_store` into the
+    /// table, the table must be empty.
+    ///
+    /// The sst files are copied with newly allocated file ids, then the write
+    /// worker of the table checks the table is still empty and registers the
+    /// files by a version edit, so no write can happen between the check and
+    /// the edit. Restored rows and tombstones keep their original sequences
+    /// and the last sequence of the table is advanced to cover them. Rows
+    /// written later still get their sequences from the wal of the table,
+    /// which may be smaller than the restored sequences.
+    pub async fn restore_table(
+        &self,
+        space_table: &SpaceAndTable,
+        backup_store: &dyn ObjectStore,
+        prefix: &str,
+    ) -> Result<SnapshotStats> {
+        let table_data = space_table.table_data();
+
+        let manifest_path = backup_path(prefix, BACKUP_MANIFEST_NAME);
+        let manifest = read_object(backup_store, &manifest_path).await?;
+        let backup = TableSnapshot::decode(&manifest).context(DecodeBackup {
+            path: manifest_path.to_string(),
+        })?;
+
+        // Fail fast before copying the files, the write worker checks it again
+        // before applying the restored files.
+        ensure_table_empty(table_data)?;
+        check_schema_compatible(
+            &table_data.name,
+            &table_data.schema(),
+            &backup.table_meta.schema,
+        )?;
+
+        info!(
+            "Instance restore table begin, table:{}, prefix:{}, backup_table:{}, files:{}",
+            table_data.name,
+            prefix,
+            backup.table_meta.table_name,
+            backup.version_edit.files_to_add.len(),
+        );
+
+        let mut stats = SnapshotStats::default();
+        let mut files_to_add = Vec::with_capacity(backup.version_edit.files_to_add.len());
+        for add_file in backup.version_edit.files_to_add {
+            let src = backup_path(prefix, &sst_util::sst_file_name(add_file.file.id));
+            let file_id = table_data.alloc_file_id();
+            let dst = table_data.set_sst_file_path(file_id);
+            let size = add_file.file.meta.size as usize;
+            if let Err(e) =
+                copy_object(backup_store, &src, self.space_store.store_ref(), &dst, size).await
+            {
+                self.remove_restored_files(table_data, &files_to_add).await;
+                return Err(e);
+            }
+
+            stats.num_files += 1;
+            stats.num_bytes += add_file.file.meta.size;
+            stats.num_rows += add_file.file.meta.row_num;
+            files_to_add.push(AddFile {
+                level: add_file.level,
+                file: FileMeta {
+                    id: file_id,
+                    meta: add_file.file.meta,
+                },
+            });
+        }
+
+        let edit_meta = VersionEditMeta {
+            space_id: table_data.space_id,
+            table_id: table_data.id,
+            // The restored files are not flushed from the wal of this table, so the
+            // flushed sequence is not advanced, otherwise the unflushed entries in the
+            // wal may be deleted.
+            flushed_sequence: 0,
+            files_to_add,
+            files_to_delete: Vec::new(),
+            tombstones_to_add: backup.version_edit.tombstones_to_add,
+            tombstones_to_delete: Vec::new(),
+        };
+        let files_to_add = edit_meta.files_to_add.clone();
+        let (tx, rx) = oneshot::channel();
+        let cmd = RestoreTableCommand {
+            space_table: space_table.clone(),
+            edit_meta,
+            tx,
+        };
+        // Actual work is done in process_restore_table_command().
+        let res = write_worker::process_command_in_write_worker(cmd.into_command(), table_data, rx)
+            .await
+            .context(OperateByWriteWorker {
+                table: &table_data.name,
+            });
+        if let Err(e) = res {
+            self.remove_restored_files(table_data, &files_to_add).await;
+            return Err(e);
+        }
+
+        info!(
+            "Instance restore table finished, table:{}, prefix:{}, stats:{:?}",
+            table_data.name, prefix, stats
+        );
+
+        Ok(stats)
+    }
+
+    /// Apply the restored files and tombstones to the table, must be called by
+    /// the write worker of the table.
+    pub(crate) async fn process_restore_table_command(
+        &self,
+        _worker_local: &mut WorkerLocal,
+        space_table: &SpaceAndTable,
+        edit_meta: VersionEditMeta,
+    ) -> Result<()> {
+        let table_data = space_table.table_data();
+        ensure_table_empty(table_data)?;
+
+        let restored_sequence = restored_max_sequence(&edit_meta);
+        let edit = edit_meta.clone().into_version_edit();
+        self.space_store
+            .manifest
+            .store_update(MetaUpdate::VersionEdit(edit_meta))
+            .await
+            .map_err(|e| Box::new(e) as _)
+            .context(StoreVersionEdit {
+                table: &table_data.name,
+            })?;
+
+        table_data.current_version().apply_edit(edit);
+        if restored_sequence > table_data.last_sequence() {
+            table_data.set_last_sequence(restored_sequence);
+        }
+
+        Ok(())
+    }
+
+    /// Pin the sst files of the current version and load the checkpoint of the
+    /// table from manifest, retry if the files in the manifest are not pinned,
+    /// which means there are flush or compaction running concurrently.
+    async fn pin_table_backup(
+        &self,
+        table_data: &TableData,
+    ) -> Result<(TableSnapshot, HashMap<FileId, FileHandle>)> {
+        for retry in 0..MAX_PIN_FILES_RETRY {
+            let read_view = table_data
+                .current_version()
+                .pick_read_view(TimeRange::min_to_max());
+            let pinned_files: HashMap<_, _> = read_view
+                .leveled_ssts
+                .into_iter()
+                .flatten()
+                .map(|file| (file.id(), file))
+                .collect();
+
+            let manifest_data = self
+                .space_store
+                .manifest
+                .load_data(table_data.id, true)
+                .await
+                .map_err(|e| Box::new(e) as _)
+                .context(LoadManifest {
+                    table: &table_data.name,
+                })?
+                .context(TableNotInManifest {
+                    table: &table_data.name,
+                    table_id: table_data.id,
+                })?;
+
+            let backup = TableSnapshot::from(manifest_data);
+            if backup
+                .version_edit
+                .files_to_add
+                .iter()
+                .all(|add_file| pinned_files.contains_key(&add_file.file.id))
+            {
+                return Ok((backup, pinned_files));
+            }
+
+            warn!(
+                "Files in manifest are not pinned, table:{}, retry:{}",
+                table_data.name, retry
+            );
+        }
+
+        PinFiles {
+            table: &table_data.name,
+            retry: MAX_PIN_FILES_RETRY,
+        }
+        .fail()
+    }
+
+    /// Remove the files copied by a failed restore, errors are ignored.
+    async fn remove_restored_files(&self, table_data: &TableData, files: &[AddFile]) {
+        for add_file in files {
+            let path = table_data.set_sst_file_path(add_file.file.id);
+            if let Err(e) = self.space_store.store_ref().delete(&path).await {
+                warn!(
+                    "Failed to remove restored file, table:{}, path:{}, err:{}",
+                    table_data.name, path, e
+                );
+            }
+        }
+    }
+}
+
+fn backup_path(prefix: &str, name: &str) -> Path {
+    let prefix = prefix.trim_end_matches('/');
+    if prefix.is_empty() {
+        Path::from(name)
+    } else {
+        Path::from(format!("{}/{}", prefix, name))
+    }
+}
+
+async fn read_object<S: ObjectStore + ?Sized>(store: &S, path: &Path) -> Result<Vec<u8>> {
+    let get_result = store.get(path).await.context(ReadObject {
+        path: path.to_string(),
+    })?;
+    let bytes = get_result.bytes().await.context(ReadObject {
+        path: path.to_string(),
+    })?;
+
+    Ok(bytes.to_vec())
+}
+
+/// Copy the object of `size` bytes, the object is read as a stream into a
+/// buffer of the expected size as the object store only supports putting a
+/// whole object.
+async fn copy_object<S, T>(
+    src_store: &S,
+    src: &Path,
+    dst_store: &T,
+    dst: &Path,
+    size: usize,
+) -> Result<()>
+where
+    S: ObjectStore + ?Sized,
+    T: ObjectStore + ?Sized,
+{
+    let get_result = src_store.get(src).await.context(ReadObject {
+        path: src.to_string(),
+    })?;
+    let bytes = match get_result {
+        GetResult::Stream(stream) => stream
+            .try_fold(BytesMut::with_capacity(size), |mut buf, chunk| async move {
+                buf.extend_from_slice(&chunk);
+                Ok(buf)
+            })
+            .await
+            .context(ReadObject {
+                path: src.to_string(),
+            })?
+            .freeze(),
+        get_result => get_result.bytes().await.context(ReadObject {
+            path: src.to_string(),
+        })?,
+    };
+
+    dst_store.put(dst, bytes).await.context(WriteObject {
+        path: dst.to_string(),
+    })
+}
+
+/// Returns the max sequence of the restored files and tombstones.
+fn restored_max_sequence(edit_meta: &VersionEditMeta) -> SequenceNumber {
+    let file_sequences = edit_meta
+        .files_to_add
+        .iter()
+        .map(|add_file| add_file.file.meta.max_sequence);
+    let tombstone_sequences = edit_meta
+        .tombstones_to_add
+        .iter()
+        .map(|tombstone| tombstone.sequence);
+
+    file_sequences.chain(tombstone_sequences).max().unwrap_or(0)
+}
+
+fn ensure_table_empty(table_data: &TableData) -> Result<()> {
+    let read_view = table_data
+        .current_version()
+        .pick_read_view(TimeRange::min_to_max());
+    let sampling_empty = read_view
+        .sampling_mem
+        .map(|v| v.mem.min_key().is_none())
+        .unwrap_or(true);
+    let memtables_empty = read_view
+        .memtables
+        .iter()
+        .all(|v| v.mem.min_key().is_none());
+    let ssts_empty = read_view.leveled_ssts.iter().all(|files| files.is_empty());
+
+    ensure!(
+        sampling_empty && memtables_empty && ssts_empty && read_view.tombstones.is_empty(),
+        TableNotEmpty {
+            table: &table_data.name,
+        }
+    );
+
+    Ok(())
+}
+
+/// The schema of the snapshot is compatible if the table has the same key
+/// columns and contains all the columns of the snapshot with the same type.
+fn check_schema_compatible(table: &str, schema: &Schema, backup_schema: &Schema) -> Result<()> {
+    let key_names = |schema: &Schema| {
+        schema
+            .key_columns()
+            .iter()
+            .map(|column| column.name.clone())
+            .collect::<Vec<_>>()
+    };
+    ensure!(
+        key_names(schema) == key_names(backup_schema),
+        IncompatibleSchema {
+            table,
+            msg: "key columns are different",
+        }
+    );
+
+    for backup_column in backup_schema.columns() {
+        let column = schema
+            .column_with_name(&backup_column.name)
+            .with_context(|| IncompatibleSchema {
+                table,
+                msg: format!("column {} not found", backup_column.name),
+            })?;
+        ensure!(
+            column.data_type == backup_column.data_type,
+            IncompatibleSchema {
+                table,
+                msg: format!(
+                    "type of column {} is different, expect:{:?}, given:{:?}",
+                    column.name, column.data_type, backup_column.data_type
+                ),
+            }
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backup_path() {
+        assert_eq!(
+            Path::from("MANIFEST"),
+            backup_path("", BACKUP_MANIFEST_NAME)
+        );
+        assert_eq!(
+            Path::from("backup/t1/1.sst"),
+            backup_path("backup/t1/", "1.sst")
+        );
+        assert_eq!(
+            Path::from("backup/t1/MANIFEST"),
+            backup_path("backup/t1", BACKUP_MANIFEST_NAME)
+        );
+    }
+}
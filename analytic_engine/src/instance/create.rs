@@ -113,6 +113,7 @@ where
             table_name: table_data.name.clone(),
             schema: table_data.schema(),
             opts: table_data.table_options().as_ref().clone(),
+            partition_info: table_data.partition_info.clone(),
         });
         self.space_store
             .manifest
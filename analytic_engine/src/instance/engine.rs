@@ -71,6 +71,19 @@ pub enum Error {
         source: crate::instance::write::Error,
     },
 
+    #[snafu(display(
+        "Failed to apply log entry to tombstone, table:{}, table_id:{}, err:{}",
+        table,
+        table_id,
+        source
+    ))]
+    ApplyTombstone {
+        space_id: SpaceId,
+        table: String,
+        table_id: TableId,
+        source: crate::table::tombstone::Error,
+    },
+
     #[snafu(display(
         "Failed to operate table through write worker, space_id:{}, table:{}, table_id:{}, err:{}",
         space_id,
@@ -199,6 +212,7 @@ impl From<Error> for table_engine::engine::Error {
             | Error::RecoverTableData { .. }
             | Error::ReadWal { .. }
             | Error::ApplyMemTable { .. }
+            | Error::ApplyTombstone { .. }
             | Error::OperateByWriteWorker { .. }
             | Error::FlushTable { .. } => Self::Unexpected {
                 source: Box::new(err),
@@ -5,23 +5,28 @@
 use std::{cmp, collections::Bound, sync::Arc};
 
 use common_types::{
+    bytes::Bytes,
     projected_schema::ProjectedSchema,
     record_batch::{RecordBatchWithKey, RecordBatchWithKeyBuilder},
     request_id::RequestId,
-    row::RowViewOnBatch,
-    time::TimeRange,
+    row::{Row, RowGroupBuilder, RowViewOnBatch},
+    schema::Schema,
+    time::{TimeRange, Timestamp},
     SequenceNumber,
 };
-use common_util::{config::ReadableDuration, define_result, runtime::Runtime};
+use common_util::{codec::Encoder, config::ReadableDuration, define_result, runtime::Runtime};
 use futures::{
     channel::{mpsc, mpsc::channel},
-    future::try_join_all,
-    stream, SinkExt, TryStreamExt,
+    future::{self, try_join_all},
+    stream, SinkExt, StreamExt, TryStreamExt,
 };
-use log::{error, info};
+use log::{error, info, warn};
 use object_store::ObjectStore;
 use snafu::{Backtrace, OptionExt, ResultExt, Snafu};
-use table_engine::{predicate::Predicate, table::Result as TableResult};
+use table_engine::{
+    predicate::Predicate,
+    table::{Result as TableResult, WriteRequest},
+};
 use tokio::sync::oneshot;
 use wal::manager::{RegionId, WalManager};
 
@@ -30,10 +35,14 @@ use crate::{
         CompactionInputFiles, CompactionTask, ExpiredFiles, TableCompactionRequest, WaitError,
     },
     instance::{
+        rollup::{align_to_buckets, RollupAggregator},
         write_worker::{self, CompactTableCommand, FlushTableCommand, WorkerLocal},
         Instance, SpaceStore,
     },
-    memtable::{ColumnarIterPtr, MemTableRef, ScanContext, ScanRequest},
+    memtable::{
+        key::{ComparableInternalKey, KeySequence, RowIndex},
+        ColumnarIterPtr, MemTableRef, ScanContext, ScanRequest,
+    },
     meta::{
         meta_update::{AlterOptionsMeta, MetaUpdate, VersionEditMeta},
         Manifest,
@@ -42,16 +51,17 @@ use crate::{
         self,
         dedup::DedupIterator,
         merge::{MergeBuilder, MergeConfig},
-        IterOptions,
+        IterOptions, RecordBatchWithKeyIterator,
     },
     space::SpaceAndTable,
     sst::{
-        builder::RecordBatchStream,
+        builder::{RecordBatchStream, RecordBatchStreamItem},
         factory::{Factory, SstBuilderOptions, SstReaderOptions, SstType},
-        file::{self, FileMeta, SstMetaData},
+        file::{self, FileHandle, FileMeta, SstMetaData},
     },
     table::{
         data::{MemTableId, TableData, TableDataRef},
+        tombstone::Tombstone,
         version::{FlushableMemTables, MemTableState, SamplingMemTable},
         version_edit::{AddFile, DeleteFile, VersionEdit},
     },
@@ -130,6 +140,37 @@ pub enum Error {
 
     #[snafu(display("Runtime join error, source:{}", source))]
     RuntimeJoin { source: common_util::runtime::Error },
+
+    #[snafu(display("Failed to encode key of sst, err:{}", source))]
+    EncodeSstKey { source: crate::memtable::key::Error },
+
+    #[snafu(display("Failed to aggregate rows of rollup, table:{}, err:{}", table, source))]
+    AggregateRollup {
+        table: String,
+        source: crate::instance::rollup::Error,
+    },
+
+    #[snafu(display(
+        "Failed to read source rows of rollup, table:{}, err:{}",
+        table,
+        source
+    ))]
+    ReadRollupSource {
+        table: String,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+
+    #[snafu(display("Failed to build rows of rollup, table:{}, err:{}", table, source))]
+    BuildRollupRows {
+        table: String,
+        source: common_types::row::Error,
+    },
+
+    #[snafu(display("Failed to write rows of rollup, table:{}, err:{}", table, source))]
+    WriteRollup {
+        table: String,
+        source: crate::instance::write::Error,
+    },
 }
 
 define_result!(Error);
@@ -328,37 +369,33 @@ where
             worker_local.compaction_notifier(),
         );
         let instance = self.clone();
-
-        if opts.compact_after_flush {
-            // Schedule compaction if flush completed successfully.
-            let on_flush_success = async move {
+        let rollup_table_data = table_data.clone();
+        let compact_after_flush = opts.compact_after_flush;
+        let block_on_write_thread = opts.block_on_write_thread;
+
+        let on_flush_success = async move {
+            instance
+                .clone()
+                .update_rollups_after_flush(rollup_table_data, block_on_write_thread)
+                .await;
+
+            if compact_after_flush {
+                // Schedule compaction if flush completed successfully.
                 instance.schedule_table_compaction(compact_req).await;
-            };
+            }
+        };
 
-            worker_local
-                .flush_sequentially(
-                    table,
-                    &table_data.metrics,
-                    flush_job,
-                    on_flush_success,
-                    opts.block_on_write_thread,
-                    opts.res_sender,
-                )
-                .await
-                .context(BackgroundFlushFailed)
-        } else {
-            worker_local
-                .flush_sequentially(
-                    table,
-                    &table_data.metrics,
-                    flush_job,
-                    async {},
-                    opts.block_on_write_thread,
-                    opts.res_sender,
-                )
-                .await
-                .context(BackgroundFlushFailed)
-        }
+        worker_local
+            .flush_sequentially(
+                table,
+                &table_data.metrics,
+                flush_job,
+                on_flush_success,
+                block_on_write_thread,
+                opts.res_sender,
+            )
+            .await
+            .context(BackgroundFlushFailed)
     }
 
     /// Caller should guarantee flush of single table is sequential
@@ -433,6 +470,16 @@ where
         // Collect sst num metrics.
         local_metrics.observe_sst_num(sst_num);
 
+        // Record the flushed time ranges before the memtables are removed from the
+        // version, so the rollup watermark never covers the rows not aggregated yet.
+        if !table_data.table_options().rollups.is_empty() {
+            for add_file in &files_to_level0 {
+                table_data
+                    .rollup_state
+                    .add_pending(add_file.file.meta.time_range);
+            }
+        }
+
         info!(
             "Instance flush memtables to output, table:{}, table_id:{}, request_id:{}, mems_to_flush:{:?}, files_to_level0:{:?}, flushed_sequence:{}",
             table_data.name,
@@ -443,6 +490,12 @@ where
             flushed_sequence
         );
 
+        // Tombstones written before the flushed sequence are persisted with the
+        // flush result, so the log entries of them can be deleted.
+        let tombstones_to_add = table_data
+            .current_version()
+            .tombstones_to_persist(flushed_sequence);
+
         // Persist the flush result to manifest.
         let edit_meta = VersionEditMeta {
             space_id: table_data.space_id,
@@ -450,6 +503,8 @@ where
             flushed_sequence,
             files_to_add: files_to_level0.clone(),
             files_to_delete: Vec::new(),
+            tombstones_to_add: tombstones_to_add.clone(),
+            tombstones_to_delete: Vec::new(),
         };
         let meta_update = MetaUpdate::VersionEdit(edit_meta);
         self.space_store
@@ -466,6 +521,8 @@ where
             mems_to_remove,
             files_to_add: files_to_level0,
             files_to_delete: Vec::new(),
+            tombstones_to_add,
+            tombstones_to_delete: Vec::new(),
         };
         table_data.current_version().apply_edit(edit);
 
@@ -537,6 +594,8 @@ where
                 schema: table_data.schema(),
                 size: 0,
                 row_num: 0,
+                sst_filter: None,
+                inverted_index: None,
             };
 
             let store = self.space_store.clone();
@@ -572,6 +631,7 @@ where
                 // update sst metadata by built info.
                 sst_meta.row_num = sst_info.row_num as u64;
                 sst_meta.size = sst_info.file_size as u64;
+                sst_meta.inverted_index = sst_info.inverted_index;
                 Ok(sst_meta)
             });
 
@@ -640,6 +700,8 @@ where
             schema: table_data.schema(),
             size: 0,
             row_num: 0,
+            sst_filter: None,
+            inverted_index: None,
         };
 
         // Alloc file id for next sst file
@@ -683,6 +745,7 @@ where
         // update sst metadata by built info.
         sst_meta.row_num = sst_info.row_num as u64;
         sst_meta.size = sst_info.file_size as u64;
+        sst_meta.inverted_index = sst_info.inverted_index;
 
         Ok(Some(FileMeta {
             id: file_id,
@@ -690,6 +753,215 @@ where
         }))
     }
 
+    /// Update the rollups of the flushed time ranges after the flush.
+    ///
+    /// The update writes to the target tables and waits for their write
+    /// workers, so it is spawned to the background runtime if the flush
+    /// blocks the write thread.
+    async fn update_rollups_after_flush(
+        self: Arc<Self>,
+        table_data: TableDataRef,
+        block_on_write_thread: bool,
+    ) {
+        if table_data.table_options().rollups.is_empty() {
+            return;
+        }
+
+        let update = async move {
+            if let Err(e) = self.update_rollups(&table_data).await {
+                error!(
+                    "Failed to update rollups, table:{}, table_id:{}, err:{}",
+                    table_data.name, table_data.id, e
+                );
+            }
+        };
+
+        if block_on_write_thread {
+            self.runtimes.bg_runtime.spawn(update);
+        } else {
+            update.await;
+        }
+    }
+
+    /// Update the rollups of the time ranges flushed since the last update.
+    ///
+    /// The time buckets intersecting with the flushed time ranges are
+    /// recomputed from all the rows of the source table in these buckets,
+    /// and written through the write path of the target table, so the rows
+    /// get sequences of the target table. The target table overwrites the
+    /// rows with the same time bucket and tsid, so the complete aggregates
+    /// replace the older ones and retrying an update produces the same rows.
+    ///
+    /// The time ranges failed to update are retried by the next update. They
+    /// are only kept in memory, so the buckets are not updated until flushed
+    /// again if the server crashes before the update. Rows deleted after the
+    /// update are not reflected in the rollups either.
+    pub(crate) async fn update_rollups(&self, table_data: &TableData) -> Result<()> {
+        let _update_guard = table_data.rollup_state.lock_update().await;
+
+        let time_ranges = table_data.rollup_state.start_update();
+        let res = self
+            .update_rollups_of_ranges(table_data, &time_ranges)
+            .await;
+        table_data.rollup_state.finish_update(res.is_ok());
+
+        res
+    }
+
+    async fn update_rollups_of_ranges(
+        &self,
+        table_data: &TableData,
+        time_ranges: &[TimeRange],
+    ) -> Result<()> {
+        if time_ranges.is_empty() {
+            return Ok(());
+        }
+
+        let space = self
+            .space_store
+            .spaces
+            .read()
+            .unwrap()
+            .get_by_id(table_data.space_id)
+            .cloned();
+        let space = match space {
+            Some(v) => v,
+            None => return Ok(()),
+        };
+
+        let request_id = RequestId::next_id();
+        let table_options = table_data.table_options();
+        let source_schema = table_data.schema();
+        for rollup in &table_options.rollups {
+            let target = match space.find_table(&rollup.table) {
+                Some(v) if !v.is_dropped() => v,
+                _ => {
+                    warn!(
+                        "Target table of rollup not found, table:{}, rollup:{}",
+                        table_data.name, rollup
+                    );
+                    continue;
+                }
+            };
+            // The recomputed rows must replace the older rows of the same bucket.
+            if !target.table_options().need_dedup() {
+                warn!(
+                    "Target table of rollup is in append mode, table:{}, rollup:{}",
+                    table_data.name, rollup
+                );
+                continue;
+            }
+            let target_schema = target.schema();
+
+            for time_range in time_ranges {
+                let time_range = align_to_buckets(*time_range, rollup.period);
+                let mut aggregator =
+                    match RollupAggregator::new(rollup, &source_schema, &target_schema, time_range)
+                    {
+                        Some(v) => v,
+                        None => {
+                            warn!(
+                                "Schema of target table mismatches the rollup, table:{}, rollup:{}",
+                                table_data.name, rollup
+                            );
+                            break;
+                        }
+                    };
+
+                self.aggregate_rollup_source(table_data, request_id, time_range, &mut aggregator)
+                    .await?;
+
+                let rows = aggregator.into_rows();
+                if rows.is_empty() {
+                    continue;
+                }
+                let row_group = RowGroupBuilder::with_rows(target_schema.clone(), rows)
+                    .context(BuildRollupRows {
+                        table: &rollup.table,
+                    })?
+                    .build();
+
+                info!(
+                    "Instance update rollup, table:{}, rollup:{}, request_id:{}, time_range:{:?}, rows:{}",
+                    table_data.name,
+                    rollup,
+                    request_id,
+                    time_range,
+                    row_group.num_rows()
+                );
+
+                self.write_to_table(
+                    &SpaceAndTable::new(space.clone(), target.clone()),
+                    WriteRequest { row_group },
+                )
+                .await
+                .context(WriteRollup {
+                    table: &rollup.table,
+                })?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Aggregate all the visible rows of the table in `time_range`.
+    async fn aggregate_rollup_source(
+        &self,
+        table_data: &TableData,
+        request_id: RequestId,
+        time_range: TimeRange,
+        aggregator: &mut RollupAggregator,
+    ) -> Result<()> {
+        let table_options = table_data.table_options();
+        let projected_schema = ProjectedSchema::no_projection(table_data.schema());
+        let predicate = Arc::new(Predicate::new(time_range));
+        let read_view = table_data.current_version().pick_read_view(time_range);
+        let expire_time = table_options.ttl().map(|ttl| Timestamp::expire_time(ttl.0));
+        let iter_options = IterOptions::default();
+
+        let sst_reader_options = SstReaderOptions {
+            sst_type: table_data.sst_type,
+            read_batch_row_num: table_options.num_rows_per_row_group,
+            reverse: false,
+            projected_schema: projected_schema.clone(),
+            predicate: predicate.clone(),
+            meta_cache: self.meta_cache.clone(),
+            data_cache: self.data_cache.clone(),
+            runtime: self.read_runtime().clone(),
+        };
+        let merge_iter = MergeBuilder::new(MergeConfig {
+            request_id,
+            space_id: table_data.space_id,
+            table_id: table_data.id,
+            sequence: table_data.last_sequence(),
+            projected_schema,
+            predicate,
+            sst_factory: self.space_store.sst_factory.clone(),
+            sst_reader_options,
+            store: self.space_store.store_ref(),
+            merge_iter_options: iter_options.clone(),
+            need_dedup: table_options.need_dedup(),
+            reverse: false,
+        })
+        .sampling_mem(read_view.sampling_mem)
+        .memtables(read_view.memtables)
+        .ssts_of_level(read_view.leveled_ssts)
+        .tombstones(read_view.tombstones)
+        .expire_time(expire_time)
+        .build()
+        .await
+        .context(BuildMergeIterator {
+            table: table_data.name.clone(),
+        })?;
+
+        if table_options.need_dedup() {
+            let iter = DedupIterator::new(request_id, merge_iter, iter_options);
+            aggregate_rows(&table_data.name, iter, aggregator).await
+        } else {
+            aggregate_rows(&table_data.name, merge_iter, aggregator).await
+        }
+    }
+
     /// Schedule table compaction request to background workers and return
     /// immediately.
     pub async fn schedule_table_compaction(&self, compact_req: TableCompactionRequest) {
@@ -714,6 +986,8 @@ impl<Wal, Meta: Manifest, Store: ObjectStore, Fa: Factory> SpaceStore<Wal, Meta,
             // Use the number of compaction inputs as the estimated number of files to add.
             files_to_add: Vec::with_capacity(task.compaction_inputs.len()),
             files_to_delete: Vec::new(),
+            tombstones_to_add: Vec::new(),
+            tombstones_to_delete: Vec::new(),
         };
 
         if task.expired.is_empty() && task.compaction_inputs.is_empty() {
@@ -725,17 +999,27 @@ impl<Wal, Meta: Manifest, Store: ObjectStore, Fa: Factory> SpaceStore<Wal, Meta,
             self.delete_expired_files(table_data, request_id, files, &mut edit_meta);
         }
 
+        // Rows hidden by these tombstones are dropped by compaction.
+        let tombstones = table_data
+            .current_version()
+            .tombstones(TimeRange::min_to_max());
         for input in &task.compaction_inputs {
             self.compact_input_files(
                 runtime.clone(),
                 table_data,
                 request_id,
                 input,
+                &tombstones,
                 &mut edit_meta,
             )
             .await?;
         }
 
+        // Remove the tombstones no longer needed.
+        edit_meta.tombstones_to_delete = table_data
+            .current_version()
+            .obsolete_tombstones(&tombstones, &edit_meta.files_to_delete);
+
         let meta_update = MetaUpdate::VersionEdit(edit_meta.clone());
         self.manifest
             .store_update(meta_update)
@@ -756,12 +1040,21 @@ impl<Wal, Meta: Manifest, Store: ObjectStore, Fa: Factory> SpaceStore<Wal, Meta,
         table_data: &TableData,
         request_id: RequestId,
         input: &CompactionInputFiles,
+        tombstones: &[Tombstone],
         edit_meta: &mut VersionEditMeta,
     ) -> Result<()> {
         if input.files.is_empty() {
             return Ok(());
         }
 
+        // The overlapping files in the output level are merged and rewritten too.
+        let input_files: Vec<_> = input
+            .files
+            .iter()
+            .chain(&input.overlapping_files)
+            .cloned()
+            .collect();
+
         // metrics
         let _timer = table_data
             .metrics
@@ -769,10 +1062,10 @@ impl<Wal, Meta: Manifest, Store: ObjectStore, Fa: Factory> SpaceStore<Wal, Meta,
             .start_timer();
         table_data
             .metrics
-            .compaction_observe_sst_num(input.files.len());
+            .compaction_observe_sst_num(input_files.len());
         let mut sst_size = 0;
         let mut sst_row_num = 0;
-        for file in &input.files {
+        for file in &input_files {
             sst_size += file.size();
             sst_row_num += file.row_num();
         }
@@ -785,13 +1078,15 @@ impl<Wal, Meta: Manifest, Store: ObjectStore, Fa: Factory> SpaceStore<Wal, Meta,
 
         info!(
             "Instance try to compact table, table:{}, table_id:{}, request_id:{}, input_files:{:?}",
-            table_data.name, table_data.id, request_id, input.files,
+            table_data.name, table_data.id, request_id, input_files,
         );
 
         // The schema may be modified during compaction, so we acquire it first and use
         // the acquired schema as the compacted sst meta.
         let schema = table_data.schema();
         let table_options = table_data.table_options();
+        // Rows older than the ttl are dropped by compaction.
+        let expire_time = table_options.ttl().map(|ttl| Timestamp::expire_time(ttl.0));
 
         let iter_options = IterOptions::default();
         let merge_iter = {
@@ -827,6 +1122,13 @@ impl<Wal, Meta: Manifest, Store: ObjectStore, Fa: Factory> SpaceStore<Wal, Meta,
             builder
                 .mut_ssts_of_level(input.level)
                 .extend_from_slice(&input.files);
+            builder
+                .mut_ssts_of_level(input.output_level)
+                .extend_from_slice(&input.overlapping_files);
+            // Drop the deleted and expired rows.
+            let builder = builder
+                .tombstones(tombstones.to_vec())
+                .expire_time(expire_time);
             let merge_iter = builder.build().await.context(BuildMergeIterator {
                 table: table_data.name.clone(),
             })?;
@@ -842,20 +1144,105 @@ impl<Wal, Meta: Manifest, Store: ObjectStore, Fa: Factory> SpaceStore<Wal, Meta,
             row_iter::record_batch_with_key_iter_to_stream(merge_iter, &runtime)
         };
 
-        let mut sst_meta = file::merge_sst_meta(&input.files, schema);
-
-        // Alloc file id for the merged sst.
-        let file_id = table_data.alloc_file_id();
-        let sst_file_path = table_data.set_sst_file_path(file_id);
-
+        let mut sst_meta = file::merge_sst_meta(&input_files, schema);
+        if let Some(expire_time) = expire_time {
+            // The expired rows are dropped, so the output starts from the expire time.
+            let time_range = sst_meta.time_range;
+            if let Some(v) = TimeRange::new(
+                cmp::max(time_range.inclusive_start(), expire_time),
+                time_range.exclusive_end(),
+            ) {
+                sst_meta.time_range = v;
+            }
+        }
         let sst_builder_options = SstBuilderOptions {
             sst_type: table_data.sst_type,
             num_rows_per_row_group: table_options.num_rows_per_row_group,
             compression: table_options.compression,
         };
+
+        let output_files = match input.max_output_file_size {
+            Some(max_file_size) => {
+                // Estimate the row number of each output file by the average row size of the
+                // input files.
+                let max_rows_per_file =
+                    sst_row_num.saturating_mul(max_file_size) / cmp::max(sst_size, 1);
+                self.build_split_ssts(
+                    table_data,
+                    request_id,
+                    &sst_builder_options,
+                    &input_files,
+                    sst_meta,
+                    record_batch_stream,
+                    cmp::max(max_rows_per_file, 1) as usize,
+                )
+                .await?
+            }
+            None => {
+                let output_file = self
+                    .build_compacted_sst(
+                        table_data,
+                        request_id,
+                        &sst_builder_options,
+                        sst_meta,
+                        record_batch_stream,
+                    )
+                    .await?;
+                vec![output_file]
+            }
+        };
+
+        info!(
+            "Instance files compacted, table:{}, table_id:{}, request_id:{}, input_files:{:?}, output_files:{:?}",
+            table_data.name,
+            table_data.id,
+            request_id,
+            input_files,
+            output_files,
+        );
+
+        // Store updates to edit_meta.
+        edit_meta.files_to_delete.reserve(input_files.len());
+        // The compacted file can be deleted later.
+        for file in &input.files {
+            edit_meta.files_to_delete.push(DeleteFile {
+                level: input.level,
+                file_id: file.id(),
+            });
+        }
+        for file in &input.overlapping_files {
+            edit_meta.files_to_delete.push(DeleteFile {
+                level: input.output_level,
+                file_id: file.id(),
+            });
+        }
+        // Add the newly created files to meta.
+        for file in output_files {
+            edit_meta.files_to_add.push(AddFile {
+                level: input.output_level,
+                file,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Build a sst from the `record_batch_stream` of compaction.
+    async fn build_compacted_sst(
+        &self,
+        table_data: &TableData,
+        request_id: RequestId,
+        sst_builder_options: &SstBuilderOptions,
+        mut sst_meta: SstMetaData,
+        record_batch_stream: RecordBatchStream,
+    ) -> Result<FileMeta> {
+        // Alloc file id for the merged sst.
+        let file_id = table_data.alloc_file_id();
+        let sst_file_path = table_data.set_sst_file_path(file_id);
+
         let mut sst_builder = self
             .sst_factory
-            .new_sst_builder(&sst_builder_options, &sst_file_path, self.store_ref())
+            .new_sst_builder(sst_builder_options, &sst_file_path, self.store_ref())
             .context(InvalidSstType {
                 sst_type: table_data.sst_type,
             })?;
@@ -871,6 +1258,7 @@ impl<Wal, Meta: Manifest, Store: ObjectStore, Fa: Factory> SpaceStore<Wal, Meta,
         // update sst metadata by built info.
         sst_meta.row_num = sst_info.row_num as u64;
         sst_meta.size = sst_info.file_size as u64;
+        sst_meta.inverted_index = sst_info.inverted_index;
 
         table_data
             .metrics
@@ -879,35 +1267,143 @@ impl<Wal, Meta: Manifest, Store: ObjectStore, Fa: Factory> SpaceStore<Wal, Meta,
             .metrics
             .compaction_observe_output_sst_row_num(sst_meta.row_num);
 
-        info!(
-            "Instance files compacted, table:{}, table_id:{}, request_id:{}, output_path:{}, input_files:{:?}, sst_meta:{:?}",
-            table_data.name,
-            table_data.id,
-            request_id,
-            sst_file_path.to_string(),
-            input.files,
-            sst_meta
-        );
+        Ok(FileMeta {
+            id: file_id,
+            meta: sst_meta,
+        })
+    }
 
-        // Store updates to edit_meta.
-        edit_meta.files_to_delete.reserve(input.files.len());
-        // The compacted file can be deleted later.
-        for file in &input.files {
-            edit_meta.files_to_delete.push(DeleteFile {
-                level: input.level,
-                file_id: file.id(),
-            });
+    /// Build multiple ssts with about `max_rows_per_file` rows from the
+    /// `record_batch_stream` of compaction.
+    ///
+    /// The stream is sorted by key and the ssts are only split between rows of
+    /// different keys, so the rows of a key are always in the same sst and
+    /// the key ranges of the ssts don't overlap with each other.
+    ///
+    /// The time range of each sst is computed from its rows, and its max
+    /// sequence is the max sequence of the `input_files` overlapping with its
+    /// key range, as the rows carry no sequence.
+    #[allow(clippy::too_many_arguments)]
+    async fn build_split_ssts(
+        &self,
+        table_data: &TableData,
+        request_id: RequestId,
+        sst_builder_options: &SstBuilderOptions,
+        input_files: &[FileHandle],
+        sst_meta: SstMetaData,
+        record_batch_stream: RecordBatchStream,
+        max_rows_per_file: usize,
+    ) -> Result<Vec<FileMeta>> {
+        let mut record_batch_stream =
+            record_batch_stream.try_filter(|batch| future::ready(!batch.is_empty()));
+        let mut output_files = Vec::new();
+        // Rows of the batch split by the last sst, which starts the next sst.
+        let mut remaining = None;
+
+        loop {
+            let first_item = match remaining.take() {
+                Some(batch) => Ok(batch),
+                None => match record_batch_stream.next().await {
+                    Some(item) => item,
+                    None => break,
+                },
+            };
+            let (mut sender, receiver) = channel::<RecordBatchStreamItem>(DEFAULT_CHANNEL_SIZE);
+            // The first and last row of the sst.
+            let mut key_rows: Option<(Row, Row)> = None;
+            // The min and max timestamp of the sst.
+            let mut timestamps: Option<(Timestamp, Timestamp)> = None;
+            let timestamp_index = sst_meta.schema.timestamp_index();
+            let send_batches = async {
+                let mut num_rows = 0;
+                let mut next_item = Some(first_item);
+                while let Some(mut item) = next_item.take() {
+                    if let Ok(batch) = &item {
+                        let last_row = key_rows.as_ref().map(|(_, max_row)| max_row);
+                        let num_to_write =
+                            num_rows_to_write(batch, num_rows, last_row, max_rows_per_file);
+                        if num_to_write < batch.num_rows() {
+                            let num_remaining = batch.num_rows() - num_to_write;
+                            remaining = Some(batch.slice(num_to_write, num_remaining));
+                            if num_to_write == 0 {
+                                break;
+                            }
+                            item = Ok(batch.slice(0, num_to_write));
+                        }
+                    }
+
+                    if let Ok(batch) = &item {
+                        num_rows += batch.num_rows();
+                        let last_row = batch.clone_row_at(batch.num_rows() - 1);
+                        match &mut key_rows {
+                            Some((_, max_row)) => *max_row = last_row,
+                            None => key_rows = Some((batch.clone_row_at(0), last_row)),
+                        }
+
+                        let column = batch.column(timestamp_index);
+                        for row_idx in 0..batch.num_rows() {
+                            if let Some(ts) = column.datum(row_idx).as_timestamp() {
+                                timestamps = Some(match timestamps {
+                                    Some((min, max)) => (cmp::min(min, ts), cmp::max(max, ts)),
+                                    None => (ts, ts),
+                                });
+                            }
+                        }
+                    }
+                    // The builder is failed if the receiver is dropped.
+                    if sender.send(item).await.is_err() || remaining.is_some() {
+                        break;
+                    }
+                    next_item = record_batch_stream.next().await;
+                }
+                // Finish the stream of the builder.
+                sender.close_channel();
+            };
+
+            let (output_file, ()) = future::join(
+                self.build_compacted_sst(
+                    table_data,
+                    request_id,
+                    sst_builder_options,
+                    sst_meta.clone(),
+                    Box::new(receiver),
+                ),
+                send_batches,
+            )
+            .await;
+            let mut output_file = output_file?;
+
+            if let Some((min_row, max_row)) = key_rows {
+                // Internal keys are ordered by sequence desc, so the min row with the max
+                // sequence is the min internal key and the max row with the min sequence is
+                // the max internal key.
+                let schema = &sst_meta.schema;
+                let max_key = encode_sst_key(schema, KeySequence::new(0, 0), &max_row)?;
+                let lower_bound = encode_sst_key(
+                    schema,
+                    KeySequence::new(SequenceNumber::MAX, RowIndex::MAX),
+                    &min_row,
+                )?;
+                // Only the input files overlapping with the key range may provide rows.
+                let max_sequence = input_files
+                    .iter()
+                    .filter(|file| file.min_key() <= max_key && file.max_key() >= lower_bound)
+                    .map(|file| file.max_sequence())
+                    .max()
+                    .unwrap_or(sst_meta.max_sequence);
+                let min_key_sequence = KeySequence::new(max_sequence, RowIndex::MAX);
+                output_file.meta.min_key = encode_sst_key(schema, min_key_sequence, &min_row)?;
+                output_file.meta.max_key = max_key;
+                output_file.meta.max_sequence = max_sequence;
+            }
+            if let Some((min_ts, max_ts)) = timestamps {
+                let end = max_ts.checked_add_i64(1).unwrap_or(Timestamp::MAX);
+                output_file.meta.time_range = TimeRange::new_unchecked(min_ts, end);
+            }
+            output_files.push(output_file);
         }
-        // Add the newly created file to meta.
-        edit_meta.files_to_add.push(AddFile {
-            level: input.output_level,
-            file: FileMeta {
-                id: file_id,
-                meta: sst_meta,
-            },
-        });
 
-        Ok(())
+        Ok(output_files)
     }
 
     pub(crate) fn delete_expired_files(
@@ -935,6 +1431,61 @@ impl<Wal, Meta: Manifest, Store: ObjectStore, Fa: Factory> SpaceStore<Wal, Meta,
     }
 }
 
+/// Returns the number of the leading rows of the `batch` to write into the sst
+/// which already has `num_rows` rows and ends with `last_row`.
+///
+/// Once the sst has `max_rows_per_file` rows, only the rows with the same key
+/// as its last row are still written into it, so the rows of a key never span
+/// two ssts.
+fn num_rows_to_write(
+    batch: &RecordBatchWithKey,
+    num_rows: usize,
+    last_row: Option<&Row>,
+    max_rows_per_file: usize,
+) -> usize {
+    let last_row = match last_row {
+        Some(row) if num_rows >= max_rows_per_file => row,
+        _ => return batch.num_rows(),
+    };
+
+    let num_key_columns = batch.schema_with_key().num_key_columns();
+    (0..batch.num_rows())
+        .take_while(|&row_idx| {
+            (0..num_key_columns)
+                .all(|col_idx| batch.column(col_idx).datum(row_idx) == last_row[col_idx])
+        })
+        .count()
+}
+
+/// Encode the internal key of the `row` with given `sequence`.
+fn encode_sst_key(schema: &Schema, sequence: KeySequence, row: &Row) -> Result<Bytes> {
+    let encoder = ComparableInternalKey::new(sequence, schema);
+    let mut buf = Vec::with_capacity(encoder.estimate_encoded_size(row));
+    encoder.encode(&mut buf, row).context(EncodeSstKey)?;
+
+    Ok(buf.into())
+}
+
+/// Aggregate all the rows of the `iter` of `table` by the `aggregator`.
+async fn aggregate_rows(
+    table: &str,
+    mut iter: impl RecordBatchWithKeyIterator,
+    aggregator: &mut RollupAggregator,
+) -> Result<()> {
+    while let Some(record_batch) = iter
+        .next_batch()
+        .await
+        .map_err(|e| Box::new(e) as _)
+        .context(ReadRollupSource { table })?
+    {
+        aggregator
+            .aggregate(&record_batch)
+            .context(AggregateRollup { table })?;
+    }
+
+    Ok(())
+}
+
 fn split_record_batch_with_time_ranges(
     record_batch: RecordBatchWithKey,
     time_ranges: &[TimeRange],
@@ -984,6 +1535,12 @@ fn split_record_batch_with_time_ranges(
     Ok(ret)
 }
 
+/// Build the iterator to flush the memtable, the rows deleted by tombstones
+/// are removed.
+///
+/// All tombstones written before the last row of the immutable memtable are
+/// already in the version, so the sst only needs the tombstones written after
+/// its max sequence in read.
 fn build_mem_table_iter(memtable: MemTableRef, table_data: &TableData) -> Result<ColumnarIterPtr> {
     let scan_ctx = ScanContext::default();
     let scan_req = ScanRequest {
@@ -993,6 +1550,9 @@ fn build_mem_table_iter(memtable: MemTableRef, table_data: &TableData) -> Result
         projected_schema: ProjectedSchema::no_projection(table_data.schema()),
         need_dedup: table_data.dedup(),
         reverse: false,
+        tombstones: table_data
+            .current_version()
+            .tombstones(TimeRange::min_to_max()),
     };
     memtable
         .scan(scan_ctx, scan_req)
@@ -1010,7 +1570,9 @@ mod tests {
         time::TimeRange,
     };
 
-    use crate::instance::flush_compaction::split_record_batch_with_time_ranges;
+    use crate::instance::flush_compaction::{
+        num_rows_to_write, split_record_batch_with_time_ranges,
+    };
 
     #[test]
     fn test_split_record_batch_with_time_ranges() {
@@ -1042,4 +1604,45 @@ mod tests {
         check_record_batch_with_key_with_rows(&rets[1], rows1.len(), column_num, rows1);
         check_record_batch_with_key_with_rows(&rets[2], rows2.len(), column_num, rows2);
     }
+
+    #[test]
+    fn test_num_rows_to_write() {
+        let max_rows_per_file = 2;
+        let last_row = build_row(b"a", 1, 10.0, "v1");
+        let batch = build_record_batch_with_key_by_rows(vec![
+            build_row(b"a", 1, 10.0, "v1"),
+            build_row(b"a", 1, 10.0, "v1"),
+            build_row(b"b", 2, 10.0, "v2"),
+        ]);
+
+        // The sst is not full.
+        assert_eq!(3, num_rows_to_write(&batch, 0, None, max_rows_per_file));
+        assert_eq!(
+            3,
+            num_rows_to_write(&batch, 1, Some(&last_row), max_rows_per_file)
+        );
+
+        // The key of the last row spans the threshold, its rows are still
+        // written into the full sst.
+        assert_eq!(
+            2,
+            num_rows_to_write(&batch, 2, Some(&last_row), max_rows_per_file)
+        );
+        let batch = build_record_batch_with_key_by_rows(vec![
+            build_row(b"a", 1, 10.0, "v1"),
+            build_row(b"a", 1, 10.0, "v1"),
+        ]);
+        assert_eq!(
+            2,
+            num_rows_to_write(&batch, 4, Some(&last_row), max_rows_per_file)
+        );
+
+        // The full sst is cut before the next key.
+        let last_row = build_row(b"a", 0, 10.0, "v0");
+        let batch = build_record_batch_with_key_by_rows(vec![build_row(b"a", 1, 10.0, "v1")]);
+        assert_eq!(
+            0,
+            num_rows_to_write(&batch, 2, Some(&last_row), max_rows_per_file)
+        );
+    }
 }
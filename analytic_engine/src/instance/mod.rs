@@ -6,6 +6,7 @@
 //! divided into the sub crates
 
 mod alter;
+pub mod backup;
 mod close;
 mod create;
 mod drop;
@@ -14,6 +15,7 @@ pub mod flush_compaction;
 pub(crate) mod mem_collector;
 pub mod open;
 mod read;
+pub(crate) mod rollup;
 mod write;
 pub mod write_worker;
 
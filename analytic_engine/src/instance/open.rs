@@ -20,8 +20,8 @@ use crate::{
     context::OpenContext,
     instance::{
         engine::{
-            ApplyMemTable, FlushTable, OperateByWriteWorker, ReadMetaUpdate, ReadWal,
-            RecoverTableData, Result,
+            ApplyMemTable, ApplyTombstone, FlushTable, OperateByWriteWorker, ReadMetaUpdate,
+            ReadWal, RecoverTableData, Result,
         },
         mem_collector::MemUsageCollector,
         write_worker,
@@ -32,7 +32,10 @@ use crate::{
     payload::{ReadPayload, WalDecoder},
     space::{Space, SpaceId, SpaceRef},
     sst::{factory::Factory, file::FilePurger},
-    table::data::{TableData, TableDataRef},
+    table::{
+        data::{TableData, TableDataRef},
+        tombstone::Tombstone,
+    },
 };
 
 impl<Wal, Meta, Store, Fa> Instance<Wal, Meta, Store, Fa>
@@ -402,6 +405,24 @@ where
                             })?;
                     }
                 }
+                ReadPayload::Delete { request } => {
+                    trace!(
+                        "Instance replay delete, table:{}, request:{:?}",
+                        table_data.name,
+                        request
+                    );
+
+                    let tombstone =
+                        Tombstone::from_delete_request_pb(sequence, std::mem::take(request))
+                            .context(ApplyTombstone {
+                                space_id: table_data.space_id,
+                                table: &table_data.name,
+                                table_id: table_data.id,
+                            })?;
+                    table_data
+                        .current_version()
+                        .add_tombstone(worker_local, tombstone);
+                }
             }
         }
 
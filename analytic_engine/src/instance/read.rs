@@ -10,7 +10,7 @@ use std::{
 
 use common_types::{
     projected_schema::ProjectedSchema, record_batch::RecordBatch, schema::RecordSchema,
-    time::TimeRange,
+    time::Timestamp,
 };
 use common_util::{define_result, runtime::Runtime};
 use futures::stream::Stream;
@@ -18,6 +18,7 @@ use log::{debug, error, trace};
 use object_store::ObjectStore;
 use snafu::{ResultExt, Snafu};
 use table_engine::{
+    predicate::Predicate,
     stream::{
         self, ErrWithSource, PartitionedStreams, RecordBatchStream, SendableRecordBatchStream,
     },
@@ -76,6 +77,17 @@ fn need_merge_sort_streams(table_options: &TableOptions, read_request: &ReadRequ
     table_options.need_dedup() || read_request.order.is_in_order()
 }
 
+/// Returns false if the predicate of the `read_request` prunes all the
+/// partitions of the table, so nothing needs to be read.
+fn may_match_partitions(table_data: &TableData, read_request: &ReadRequest) -> bool {
+    match &table_data.partition_rule {
+        Some(partition_rule) => !partition_rule
+            .prune(&read_request.predicate.exprs)
+            .is_empty(),
+        None => true,
+    }
+}
+
 impl<Wal: WalManager + Send + Sync, Meta: Manifest, Store: ObjectStore, Fa: Factory>
     Instance<Wal, Meta, Store, Fa>
 {
@@ -99,6 +111,14 @@ impl<Wal: WalManager + Send + Sync, Meta: Manifest, Store: ObjectStore, Fa: Fact
         // Collect metrics.
         table_data.metrics.on_read_request_begin();
 
+        if !may_match_partitions(table_data, &request) {
+            debug!(
+                "No partition matches the predicate, table:{}, predicate:{:?}",
+                table_data.name, request.predicate
+            );
+            return self.build_partitioned_streams(&request, Vec::<ChainIterator>::new());
+        }
+
         let iter_options = IterOptions::default();
         let table_options = table_data.table_options();
 
@@ -168,9 +188,12 @@ impl<Wal: WalManager + Send + Sync, Meta: Manifest, Store: ObjectStore, Fa: Fact
             runtime: self.read_runtime().clone(),
         };
 
-        let time_range = request.predicate.time_range;
         let version = table_data.current_version();
-        let read_views = self.partition_ssts_and_memtables(time_range, version, &*table_options);
+        let read_views =
+            self.partition_ssts_and_memtables(&request.predicate, version, &*table_options);
+        // Rows older than the ttl may be still in the memtables or ssts, which must be
+        // filtered out.
+        let expire_time = table_options.ttl().map(|ttl| Timestamp::expire_time(ttl.0));
 
         let mut iters = Vec::with_capacity(read_views.len());
         for read_view in read_views {
@@ -193,6 +216,8 @@ impl<Wal: WalManager + Send + Sync, Meta: Manifest, Store: ObjectStore, Fa: Fact
                 .sampling_mem(read_view.sampling_mem)
                 .memtables(read_view.memtables)
                 .ssts_of_level(read_view.leveled_ssts)
+                .tombstones(read_view.tombstones)
+                .expire_time(expire_time)
                 .build()
                 .await
                 .context(BuildMergeIterator {
@@ -229,9 +254,12 @@ impl<Wal: WalManager + Send + Sync, Meta: Manifest, Store: ObjectStore, Fa: Fact
             runtime: self.read_runtime().clone(),
         };
 
-        let time_range = request.predicate.time_range;
         let version = table_data.current_version();
-        let read_views = self.partition_ssts_and_memtables(time_range, version, &*table_options);
+        let read_views =
+            self.partition_ssts_and_memtables(&request.predicate, version, &*table_options);
+        // Rows older than the ttl may be still in the memtables or ssts, which must be
+        // filtered out.
+        let expire_time = table_options.ttl().map(|ttl| Timestamp::expire_time(ttl.0));
 
         let mut iters = Vec::with_capacity(read_views.len());
         for read_view in read_views {
@@ -250,6 +278,8 @@ impl<Wal: WalManager + Send + Sync, Meta: Manifest, Store: ObjectStore, Fa: Fact
                 .sampling_mem(read_view.sampling_mem)
                 .memtables(read_view.memtables)
                 .ssts(read_view.leveled_ssts)
+                .tombstones(read_view.tombstones)
+                .expire_time(expire_time)
                 .build()
                 .await
                 .context(BuildChainIterator {
@@ -264,11 +294,15 @@ impl<Wal: WalManager + Send + Sync, Meta: Manifest, Store: ObjectStore, Fa: Fact
 
     fn partition_ssts_and_memtables(
         &self,
-        time_range: TimeRange,
+        predicate: &Predicate,
         version: &TableVersion,
         table_options: &TableOptions,
     ) -> Vec<ReadView> {
-        let read_view = version.pick_read_view(time_range);
+        let mut read_view = version.pick_read_view(predicate.time_range);
+        // Skip the ssts having no series matched by the predicate.
+        for ssts in &mut read_view.leveled_ssts {
+            ssts.retain(|file| file.may_match(predicate));
+        }
 
         let segment_duration = match table_options.segment_duration {
             Some(v) => v.0,
@@ -311,7 +345,13 @@ impl<Wal: WalManager + Send + Sync, Meta: Manifest, Store: ObjectStore, Fa: Fact
             entry.memtables.push(memtable);
         }
 
-        read_view_by_time.into_values().collect()
+        read_view_by_time
+            .into_values()
+            .map(|mut v| {
+                v.tombstones = read_view.tombstones.clone();
+                v
+            })
+            .collect()
     }
 }
 
@@ -0,0 +1,289 @@
+// Copyright 2022 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Aggregator of rollups maintained at flush time
+
+use std::{collections::BTreeMap, mem, sync::Mutex, time::Duration};
+
+use common_types::{
+    column_schema::ColumnId,
+    datum::{Datum, DatumKind},
+    record_batch::RecordBatchWithKey,
+    row::Row,
+    schema::Schema,
+    time::{TimeRange, Timestamp},
+};
+use common_util::{codec::compact::TsidBuilder, define_result};
+use snafu::{ResultExt, Snafu};
+use table_engine::rollup::{PartialAggregate, Rollup};
+use tokio::sync::{Mutex as AsyncMutex, MutexGuard as AsyncMutexGuard};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Failed to encode tsid of rollup, err:{}", source))]
+    EncodeTsid {
+        source: common_util::codec::compact::Error,
+    },
+}
+
+define_result!(Error);
+
+/// Partial aggregate of a column.
+struct PartialIndex {
+    /// Index of the aggregated column in the source schema.
+    source_index: usize,
+    /// Index of the partial column in the target schema.
+    target_index: usize,
+    partial: PartialAggregate,
+}
+
+/// Aggregates the rows of the source table into the rows of the target table
+/// of a rollup.
+///
+/// The rows are grouped by the time bucket and the tsid of the tags, which are
+/// also the primary key of the target table, so the output rows are sorted by
+/// the primary key.
+pub struct RollupAggregator {
+    /// Period of the time bucket.
+    period: Duration,
+    /// Only rows in this time range are aggregated.
+    time_range: TimeRange,
+    num_columns: usize,
+    /// Index of the timestamp column in the source schema.
+    source_timestamp_index: usize,
+    /// Index of the timestamp column in the target schema.
+    target_timestamp_index: usize,
+    /// Index of the tsid column in the target schema.
+    tsid_index: usize,
+    /// (index in source schema, index in target schema, column id in target
+    /// schema) of the tag columns.
+    tags: Vec<(usize, usize, ColumnId)>,
+    partials: Vec<PartialIndex>,
+    /// Aggregated rows keyed by (time bucket, tsid).
+    rows: BTreeMap<(i64, u64), Row>,
+    hash_bytes: Vec<u8>,
+}
+
+impl RollupAggregator {
+    /// Create an aggregator for the `rollup` which aggregates the rows in
+    /// `time_range`, returns `None` if the target schema doesn't match the
+    /// rollup.
+    pub fn new(
+        rollup: &Rollup,
+        source_schema: &Schema,
+        target_schema: &Schema,
+        time_range: TimeRange,
+    ) -> Option<Self> {
+        let tsid_index = target_schema.index_of_tsid()?;
+        let target_timestamp_index = target_schema.timestamp_index();
+        if target_schema.timestamp_name() != source_schema.timestamp_name() {
+            return None;
+        }
+
+        let mut tags = Vec::with_capacity(rollup.tags.len());
+        for tag in &rollup.tags {
+            let source_index = source_schema.index_of(tag)?;
+            let target_index = target_schema.index_of(tag)?;
+            let target_column = target_schema.column(target_index);
+            if !target_column.is_tag
+                || target_column.data_type != source_schema.column(source_index).data_type
+            {
+                return None;
+            }
+            tags.push((source_index, target_index, target_column.id));
+        }
+
+        let partial_columns = rollup.partial_columns();
+        let mut partials = Vec::with_capacity(partial_columns.len());
+        for partial_column in partial_columns {
+            let source_index = source_schema.index_of(&partial_column.column)?;
+            let target_index = target_schema.index_of(&partial_column.name)?;
+            let expect_kind = match partial_column.partial {
+                PartialAggregate::Count => DatumKind::UInt64,
+                PartialAggregate::Sum | PartialAggregate::Min | PartialAggregate::Max => {
+                    DatumKind::Double
+                }
+            };
+            if !source_schema
+                .column(source_index)
+                .data_type
+                .is_f64_castable()
+                || target_schema.column(target_index).data_type != expect_kind
+            {
+                return None;
+            }
+            partials.push(PartialIndex {
+                source_index,
+                target_index,
+                partial: partial_column.partial,
+            });
+        }
+
+        Some(Self {
+            period: rollup.period,
+            time_range,
+            num_columns: target_schema.num_columns(),
+            source_timestamp_index: source_schema.timestamp_index(),
+            target_timestamp_index,
+            tsid_index,
+            tags,
+            partials,
+            rows: BTreeMap::new(),
+            hash_bytes: Vec::new(),
+        })
+    }
+
+    /// Aggregate rows of the `record_batch` from the source table.
+    pub fn aggregate(&mut self, record_batch: &RecordBatchWithKey) -> Result<()> {
+        let timestamp_column = record_batch.column(self.source_timestamp_index);
+        for row_idx in 0..record_batch.num_rows() {
+            let timestamp = match timestamp_column.datum(row_idx).as_timestamp() {
+                Some(v) if self.time_range.contains(v) => v,
+                _ => continue,
+            };
+            let bucket = timestamp.truncate_by(self.period);
+
+            let mut tsid_builder = TsidBuilder::new(&mut self.hash_bytes);
+            let mut tag_datums = Vec::with_capacity(self.tags.len());
+            for (source_index, _, column_id) in &self.tags {
+                let datum = record_batch.column(*source_index).datum(row_idx);
+                tsid_builder
+                    .maybe_write_datum(*column_id, &datum)
+                    .context(EncodeTsid)?;
+                tag_datums.push(datum);
+            }
+            let tsid = tsid_builder.finish();
+
+            let (num_columns, timestamp_index, tsid_index) = (
+                self.num_columns,
+                self.target_timestamp_index,
+                self.tsid_index,
+            );
+            let (tags, partials) = (&self.tags, &self.partials);
+            let row = self.rows.entry((bucket.as_i64(), tsid)).or_insert_with(|| {
+                let mut datums = vec![Datum::Null; num_columns];
+                datums[timestamp_index] = Datum::Timestamp(bucket);
+                datums[tsid_index] = Datum::UInt64(tsid);
+                for ((_, target_index, _), datum) in tags.iter().zip(tag_datums) {
+                    datums[*target_index] = datum;
+                }
+                for partial in partials {
+                    if partial.partial == PartialAggregate::Count {
+                        datums[partial.target_index] = Datum::UInt64(0);
+                    }
+                }
+
+                Row::from_datums(datums)
+            });
+
+            for partial in &self.partials {
+                let value = match record_batch
+                    .column(partial.source_index)
+                    .datum(row_idx)
+                    .as_f64()
+                {
+                    Some(v) => v,
+                    // Null values are ignored like the aggregate functions of sql.
+                    None => continue,
+                };
+                let current = &mut row[partial.target_index];
+                *current = match (partial.partial, &*current) {
+                    (PartialAggregate::Count, Datum::UInt64(count)) => Datum::UInt64(count + 1),
+                    (PartialAggregate::Sum, Datum::Double(sum)) => Datum::Double(sum + value),
+                    (PartialAggregate::Min, Datum::Double(min)) => Datum::Double(min.min(value)),
+                    (PartialAggregate::Max, Datum::Double(max)) => Datum::Double(max.max(value)),
+                    _ => Datum::Double(value),
+                };
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the aggregated rows sorted by the primary key of the target
+    /// table.
+    pub fn into_rows(self) -> Vec<Row> {
+        self.rows.into_values().collect()
+    }
+}
+
+/// Returns the time range covering all the time buckets of `period` that
+/// intersect with `time_range`.
+pub fn align_to_buckets(time_range: TimeRange, period: Duration) -> TimeRange {
+    let start = time_range.inclusive_start().truncate_by(period);
+    let last = Timestamp::new(time_range.exclusive_end().as_i64() - 1).truncate_by(period);
+    let end = last
+        .checked_add_i64(period.as_millis() as i64)
+        .unwrap_or(Timestamp::MAX);
+
+    TimeRange::new_unchecked(start, end)
+}
+
+#[derive(Debug, Default)]
+struct UpdateRanges {
+    /// Time ranges waiting for the next update.
+    pending: Vec<TimeRange>,
+    /// Time ranges being updated.
+    running: Vec<TimeRange>,
+}
+
+/// State of the rollups of a source table.
+///
+/// The flush records the time ranges of the flushed rows, then the rollups of
+/// these time ranges are recomputed from the source table in background. The
+/// updates are serialized so a later update always reads newer data.
+#[derive(Debug, Default)]
+pub struct RollupState {
+    update_lock: AsyncMutex<()>,
+    ranges: Mutex<UpdateRanges>,
+}
+
+impl RollupState {
+    /// Record the time range of the flushed rows to update.
+    pub fn add_pending(&self, time_range: TimeRange) {
+        let mut ranges = self.ranges.lock().unwrap();
+        if !ranges.pending.contains(&time_range) {
+            ranges.pending.push(time_range);
+        }
+    }
+
+    /// Acquire the lock to update the rollups.
+    pub async fn lock_update(&self) -> AsyncMutexGuard<'_, ()> {
+        self.update_lock.lock().await
+    }
+
+    /// Take the pending time ranges to update.
+    ///
+    /// REQUIRE: Hold the lock returned by [RollupState::lock_update].
+    pub fn start_update(&self) -> Vec<TimeRange> {
+        let mut ranges = self.ranges.lock().unwrap();
+        let pending = mem::take(&mut ranges.pending);
+        ranges.running.extend_from_slice(&pending);
+
+        pending
+    }
+
+    /// Finish the update started by [RollupState::start_update], the time
+    /// ranges are updated again by the next update if `success` is false.
+    pub fn finish_update(&self, success: bool) {
+        let mut ranges = self.ranges.lock().unwrap();
+        let running = mem::take(&mut ranges.running);
+        if !success {
+            for time_range in running {
+                if !ranges.pending.contains(&time_range) {
+                    ranges.pending.push(time_range);
+                }
+            }
+        }
+    }
+
+    /// Returns the min start of the time ranges not updated yet.
+    pub fn min_pending_start(&self) -> Option<Timestamp> {
+        let ranges = self.ranges.lock().unwrap();
+        ranges
+            .pending
+            .iter()
+            .chain(ranges.running.iter())
+            .map(|time_range| time_range.inclusive_start())
+            .min()
+    }
+}
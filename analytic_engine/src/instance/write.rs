@@ -15,7 +15,7 @@ use object_store::ObjectStore;
 use proto::table_requests;
 use smallvec::SmallVec;
 use snafu::{ensure, Backtrace, ResultExt, Snafu};
-use table_engine::table::WriteRequest;
+use table_engine::table::{DeleteRequest, WriteRequest};
 use tokio::sync::oneshot;
 use wal::{
     log_batch::{LogWriteBatch, LogWriteEntry},
@@ -26,7 +26,7 @@ use crate::{
     instance::{
         flush_compaction::TableFlushOptions,
         write_worker,
-        write_worker::{BackgroundStatus, WorkerLocal, WriteTableCommand},
+        write_worker::{BackgroundStatus, DeleteTableCommand, WorkerLocal, WriteTableCommand},
         Instance,
     },
     memtable::{key::KeySequence, PutContext},
@@ -36,6 +36,7 @@ use crate::{
     sst::factory::Factory,
     table::{
         data::{TableData, TableDataRef},
+        tombstone::{self, Tombstone},
         version::MemTableForWrite,
     },
 };
@@ -96,6 +97,12 @@ pub enum Error {
         source: common_types::schema::CompatError,
     },
 
+    #[snafu(display("Failed to locate partition of row, table:{}, err:{}", table, source))]
+    LocatePartition {
+        table: String,
+        source: table_engine::partition::rule::Error,
+    },
+
     #[snafu(display("Failed to encode row group, err:{}", source))]
     EncodeRowGroup {
         source: common_util::codec::row::Error,
@@ -103,6 +110,11 @@ pub enum Error {
 
     #[snafu(display("Failed to update sequence of memtable, err:{}", source))]
     UpdateMemTableSequence { source: crate::memtable::Error },
+
+    #[snafu(display("Failed to encode series keys to delete, err:{}", source))]
+    EncodeSeriesKeys {
+        source: crate::table::tombstone::Error,
+    },
 }
 
 define_result!(Error);
@@ -245,6 +257,86 @@ where
         Ok(num_rows)
     }
 
+    /// Delete rows from the table under given space.
+    pub async fn delete_from_table(
+        &self,
+        space_table: &SpaceAndTable,
+        request: DeleteRequest,
+    ) -> Result<()> {
+        // Create a oneshot channel to send/receive delete result.
+        let (tx, rx) = oneshot::channel();
+        let cmd = DeleteTableCommand {
+            space_table: space_table.clone(),
+            request,
+            tx,
+        };
+
+        // Send delete request to write worker, actual works done in
+        // Self::process_delete_table_command().
+        write_worker::process_command_in_write_worker(
+            cmd.into_command(),
+            space_table.table_data(),
+            rx,
+        )
+        .await
+        .context(Write)
+    }
+
+    /// Do the actual delete, must called by write worker in write thread
+    /// sequentially.
+    ///
+    /// The delete is persisted in the wal and then added to the table version
+    /// as a tombstone, no memtable is flushed. The tombstone only deletes rows
+    /// with smaller sequence, the deleted rows are filtered out in read and
+    /// dropped in flush and compaction. The tombstone is persisted in the
+    /// manifest with the next flush.
+    pub(crate) async fn process_delete_table_command(
+        self: &Arc<Self>,
+        worker_local: &mut WorkerLocal,
+        space_table: &SpaceAndTable,
+        request: DeleteRequest,
+    ) -> Result<()> {
+        let table_data = space_table.table_data();
+        ensure!(
+            !table_data.is_dropped(),
+            WriteDroppedTable {
+                table: &table_data.name,
+            }
+        );
+
+        if let BackgroundStatus::FlushFailed(e) = &*worker_local.background_status() {
+            return BackgroundFlushFailed { msg: e.to_string() }.fail();
+        }
+
+        let series_keys = match &request.series_keys {
+            Some(keys) => Some(tombstone::encode_series_keys(keys).context(EncodeSeriesKeys)?),
+            None => None,
+        };
+
+        let mut tombstone = Tombstone {
+            sequence: 0,
+            time_range: request.time_range,
+            series_keys,
+        };
+        let delete_req_pb = tombstone.to_delete_request_pb();
+        let sequence = self
+            .write_payload_to_wal(table_data, WritePayload::Delete(&delete_req_pb))
+            .await?;
+        tombstone.sequence = sequence;
+
+        debug!(
+            "Instance delete finished, update sequence, space_table:{:?}, tombstone:{:?}",
+            space_table, tombstone
+        );
+
+        table_data
+            .current_version()
+            .add_tombstone(worker_local, tombstone);
+        table_data.set_last_sequence(sequence);
+
+        Ok(())
+    }
+
     /// Return Ok if the request is valid, this is done before entering the
     /// write thread.
     fn validate_before_write(
@@ -265,6 +357,7 @@ where
 
     /// Preprocess before write, check:
     ///  - whether table is dropped
+    ///  - whether rows belong to partitions of the table
     ///  - memtable capacity and maybe trigger flush
     ///
     /// Fills [common_types::schema::IndexInWriterSchema] in [EncodeContext]
@@ -293,6 +386,18 @@ where
             )
             .context(IncompatSchema)?;
 
+        // Rejects the rows not belonging to any partition, such as the rows out
+        // of all the ranges of the range partitions.
+        if let Some(partition_rule) = &table_data.partition_rule {
+            for row in &encode_ctx.row_group {
+                partition_rule
+                    .locate_partition_in_writer(row, &encode_ctx.index_in_writer)
+                    .context(LocatePartition {
+                        table: &table_data.name,
+                    })?;
+            }
+        }
+
         // TODO(yingwen): Allow write and retry flush.
         // Check background status, if background error occured, not allow to write
         // again.
@@ -351,11 +456,19 @@ where
         write_req_pb.set_schema(table_data.schema().into());
         write_req_pb.set_rows(encoded_rows.into());
 
+        self.write_payload_to_wal(table_data, WritePayload::Write(&write_req_pb))
+            .await
+    }
+
+    /// Write the payload into wal, return the sequence number of the payload.
+    async fn write_payload_to_wal(
+        &self,
+        table_data: &TableData,
+        payload: WritePayload<'_>,
+    ) -> Result<SequenceNumber> {
         let mut log_batch = LogWriteBatch::new(table_data.wal_region_id());
         // Now we only have one request, so no need to use with_capacity
-        log_batch.push(LogWriteEntry {
-            payload: WritePayload::Write(&write_req_pb),
-        });
+        log_batch.push(LogWriteEntry { payload });
 
         // Write to wal manager
         let write_ctx = WriteContext::default();
@@ -24,7 +24,8 @@ use snafu::{Backtrace, ResultExt, Snafu};
 use table_engine::{
     engine::{CloseTableRequest, DropTableRequest},
     table::{
-        AlterSchemaRequest, Error as TableError, Result as TableResult, TableId, WriteRequest,
+        AlterSchemaRequest, DeleteRequest, Error as TableError, Result as TableResult, TableId,
+        WriteRequest,
     },
 };
 use tokio::sync::{mpsc, oneshot, watch, watch::Ref, Mutex, Notify};
@@ -33,11 +34,11 @@ use wal::{log_batch::LogEntry, manager::WalManager};
 use crate::{
     compaction::{TableCompactionRequest, WaitResult},
     instance::{
-        engine,
+        backup, engine,
         flush_compaction::{self, TableFlushOptions},
         write, write_worker, InstanceRef,
     },
-    meta::Manifest,
+    meta::{meta_update::VersionEditMeta, Manifest},
     payload::ReadPayload,
     space::{SpaceAndTable, SpaceId, SpaceRef},
     sst::factory::Factory,
@@ -306,6 +307,21 @@ impl WriteTableCommand {
     }
 }
 
+/// Delete table command.
+pub struct DeleteTableCommand {
+    pub space_table: SpaceAndTable,
+    pub request: DeleteRequest,
+    /// Sender for the worker to return result of delete
+    pub tx: oneshot::Sender<write::Result<()>>,
+}
+
+impl DeleteTableCommand {
+    /// Convert into [Command]
+    pub fn into_command(self) -> Command {
+        Command::Delete(self)
+    }
+}
+
 /// Recover table command.
 pub struct RecoverTableCommand {
     pub space: SpaceRef,
@@ -429,11 +445,30 @@ impl CompactTableCommand {
     }
 }
 
+/// Restore table command.
+pub struct RestoreTableCommand {
+    pub space_table: SpaceAndTable,
+    /// Version edit to add the restored files and tombstones
+    pub edit_meta: VersionEditMeta,
+    /// Sender for the worker to return result of restore
+    pub tx: oneshot::Sender<backup::Result<()>>,
+}
+
+impl RestoreTableCommand {
+    /// Convert into [Command]
+    pub fn into_command(self) -> Command {
+        Command::Restore(self)
+    }
+}
+
 /// Command sent to write worker
 pub enum Command {
     /// Write to table
     Write(WriteTableCommand),
 
+    /// Delete from table
+    Delete(DeleteTableCommand),
+
     /// Drop table
     Create(CreateTableCommand),
 
@@ -458,6 +493,9 @@ pub enum Command {
     /// Compact table
     Compact(CompactTableCommand),
 
+    /// Restore table
+    Restore(RestoreTableCommand),
+
     /// Exit the worker
     Exit,
 }
@@ -738,6 +776,9 @@ impl<
                 Command::Write(cmd) => {
                     self.handle_write_table(cmd).await;
                 }
+                Command::Delete(cmd) => {
+                    self.handle_delete_table(cmd).await;
+                }
                 Command::Create(cmd) => {
                     self.handle_create_table(cmd).await;
                 }
@@ -762,6 +803,9 @@ impl<
                 Command::Compact(cmd) => {
                     self.handle_compact_table(cmd).await;
                 }
+                Command::Restore(cmd) => {
+                    self.handle_restore_table(cmd).await;
+                }
                 Command::Exit => {
                     info!(
                         "Write worker recv Command::Exit, exit, space_id:{}, id:{}",
@@ -799,6 +843,25 @@ impl<
         }
     }
 
+    async fn handle_delete_table(&mut self, cmd: DeleteTableCommand) {
+        let DeleteTableCommand {
+            space_table,
+            request,
+            tx,
+        } = cmd;
+
+        let delete_res = self
+            .instance
+            .process_delete_table_command(&mut self.local, &space_table, request)
+            .await;
+        if let Err(res) = tx.send(delete_res) {
+            error!(
+                "handle delete table failed to send result, delete_res:{:?}",
+                res
+            );
+        }
+    }
+
     async fn handle_recover_table(&mut self, cmd: RecoverTableCommand) {
         let RecoverTableCommand {
             space,
@@ -952,6 +1015,25 @@ impl<
         }
     }
 
+    async fn handle_restore_table(&mut self, cmd: RestoreTableCommand) {
+        let RestoreTableCommand {
+            space_table,
+            edit_meta,
+            tx,
+        } = cmd;
+
+        let restore_res = self
+            .instance
+            .process_restore_table_command(&mut self.local, &space_table, edit_meta)
+            .await;
+        if let Err(res) = tx.send(restore_res) {
+            error!(
+                "handle restore table failed to send result, restore_res:{:?}",
+                res
+            );
+        }
+    }
+
     #[inline]
     fn space_id(&self) -> SpaceId {
         self.local.data.space_id
@@ -14,7 +14,7 @@ mod sampler;
 pub mod setup;
 pub mod space;
 pub mod sst;
-mod storage_options;
+pub mod storage_options;
 pub mod table;
 pub mod table_options;
 
@@ -23,7 +23,9 @@ pub mod tests;
 
 use meta::details::Options as ManifestOptions;
 use serde_derive::Deserialize;
-use storage_options::{LocalOptions, StorageOptions};
+use storage_options::{
+    DiskCacheOptions, LocalOptions, ManifestStorageOptions, StorageOptions, WalStorageOptions,
+};
 
 pub use crate::{compaction::scheduler::SchedulerConfig, table_options::TableOptions};
 
@@ -36,6 +38,8 @@ pub struct Config {
 
     /// WAL path of the engine.
     pub wal_path: String,
+    /// Storage backend of the WAL.
+    pub wal_storage: WalStorageOptions,
 
     /// Batch size to read records from wal to replay.
     pub replay_batch_size: usize,
@@ -54,9 +58,14 @@ pub struct Config {
     pub sst_meta_cache_cap: Option<usize>,
     /// sst data cache capacity.
     pub sst_data_cache_cap: Option<usize>,
+    /// Disk cache of the sst files, which is useful when the sst files are
+    /// stored remotely.
+    pub sst_disk_cache: Option<DiskCacheOptions>,
 
     /// Manifest options.
     pub manifest: ManifestOptions,
+    /// Storage backend of the manifest.
+    pub manifest_storage: ManifestStorageOptions,
 
     // Global write buffer options:
     /// The maximum write buffer size used for single space.
@@ -73,6 +82,7 @@ impl Default for Config {
                 data_path: String::from("/tmp/ceresdb"),
             }),
             wal_path: String::from("/tmp/ceresdb"),
+            wal_storage: WalStorageOptions::default(),
             replay_batch_size: 500,
             max_replay_tables_per_batch: 64,
             write_group_worker_num: 8,
@@ -81,7 +91,9 @@ impl Default for Config {
             compaction_config: SchedulerConfig::default(),
             sst_meta_cache_cap: Some(1000),
             sst_data_cache_cap: Some(1000),
+            sst_disk_cache: None,
             manifest: ManifestOptions::default(),
+            manifest_storage: ManifestStorageOptions::default(),
             /// Zero means disabling this param, give a positive value to enable
             /// it.
             space_write_buffer_size: 0,
@@ -0,0 +1,34 @@
+// Copyright 2022 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Columnar memtable factory
+
+use std::sync::{
+    atomic::{AtomicU64, AtomicUsize},
+    Arc, Mutex,
+};
+
+use crate::memtable::{
+    columnar::{ColumnBuffer, ColumnarMemTable},
+    factory::{Factory, Options, Result},
+    MemTableRef,
+};
+
+/// Factory to create columnar memtable
+#[derive(Debug)]
+pub struct ColumnarMemTableFactory;
+
+impl Factory for ColumnarMemTableFactory {
+    fn create_memtable(&self, opts: Options) -> Result<MemTableRef> {
+        let buffer = ColumnBuffer::new(&opts.schema);
+        let memtable = Arc::new(ColumnarMemTable {
+            schema: opts.schema,
+            buffer: Mutex::new(buffer),
+            sorted_runs: Mutex::new(Vec::new()),
+            bytes_used: AtomicUsize::new(0),
+            collector: opts.collector,
+            last_sequence: AtomicU64::new(opts.creation_sequence),
+        });
+
+        Ok(memtable)
+    }
+}
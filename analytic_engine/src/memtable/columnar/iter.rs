@@ -0,0 +1,292 @@
+// Copyright 2022 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Columnar memtable iterator
+
+use std::{cmp::Ordering, collections::BinaryHeap, ops::Bound, sync::Arc};
+
+use common_types::{
+    bytes::ByteVec,
+    datum::Datum,
+    projected_schema::{ProjectedSchema, RowProjector},
+    record_batch::{RecordBatchWithKey, RecordBatchWithKeyBuilder},
+    row::Row,
+    schema::Schema,
+    SequenceNumber,
+};
+use log::trace;
+use snafu::ResultExt;
+
+use crate::{
+    memtable::{
+        columnar::Chunk, AppendRow, BuildRecordBatch, ProjectSchema, Result, ScanContext,
+        ScanRequest,
+    },
+    table::tombstone::TombstoneFilter,
+};
+
+/// Cursor of the rows in the key range of a sorted run.
+struct RunCursor {
+    chunk: Arc<Chunk>,
+    /// Index of the run in the memtable
+    run: usize,
+    /// Index of the next row to visit
+    next_index: usize,
+    /// Index of the row after the last row in range
+    end_index: usize,
+    num_key_columns: usize,
+}
+
+impl PartialEq for RunCursor {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for RunCursor {}
+
+impl PartialOrd for RunCursor {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for RunCursor {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed so the cursor with the smallest next row is popped first
+        // from the max heap.
+        other.chunk.compare_row(
+            other.next_index,
+            &self.chunk,
+            self.next_index,
+            self.num_key_columns,
+        )
+    }
+}
+
+/// Columnar iterator for [ColumnarMemTable](super::ColumnarMemTable)
+///
+/// The rows of each sorted run in the key range are located by binary search,
+/// so only the keys visited by the search need to be encoded, and the runs are
+/// merged by a heap of their cursors.
+pub struct ColumnarIterImpl {
+    /// Sorted runs of the memtable
+    runs: Vec<Arc<Chunk>>,
+    num_key_columns: usize,
+
+    // Schema related:
+    /// Projection of schema to read
+    projected_schema: ProjectedSchema,
+    projector: RowProjector,
+
+    // Options related:
+    batch_size: usize,
+    /// Max visible sequence
+    sequence: SequenceNumber,
+    /// Dedup rows with key
+    need_dedup: bool,
+
+    /// Cursors of the runs having rows to visit
+    cursors: BinaryHeap<RunCursor>,
+    /// Run and index of the last row this iterator returned
+    last_row: Option<(usize, usize)>,
+
+    /// Filter to remove rows deleted by tombstones
+    tombstone_filter: Option<TombstoneFilter>,
+    /// Sequences of rows in the batch to build
+    sequences: Vec<SequenceNumber>,
+}
+
+impl ColumnarIterImpl {
+    /// Create a new [ColumnarIterImpl]
+    pub(crate) fn new(
+        memtable_schema: &Schema,
+        runs: Vec<Arc<Chunk>>,
+        ctx: ScanContext,
+        request: ScanRequest,
+    ) -> Result<Self> {
+        // Create projection for the memtable schema
+        let projector = request
+            .projected_schema
+            .try_project_with_key(memtable_schema)
+            .context(ProjectSchema)?;
+
+        let num_key_columns = memtable_schema.num_key_columns();
+        let mut cursors = BinaryHeap::with_capacity(runs.len());
+        for (run, chunk) in runs.iter().enumerate() {
+            let next_index = match &request.start_user_key {
+                Bound::Included(user_key) => search(chunk, num_key_columns, user_key, false)?,
+                Bound::Excluded(user_key) => search(chunk, num_key_columns, user_key, true)?,
+                Bound::Unbounded => 0,
+            };
+            let end_index = match &request.end_user_key {
+                Bound::Included(user_key) => search(chunk, num_key_columns, user_key, true)?,
+                Bound::Excluded(user_key) => search(chunk, num_key_columns, user_key, false)?,
+                Bound::Unbounded => chunk.num_rows(),
+            };
+            if next_index < end_index {
+                cursors.push(RunCursor {
+                    chunk: chunk.clone(),
+                    run,
+                    next_index,
+                    end_index,
+                    num_key_columns,
+                });
+            }
+        }
+
+        let tombstone_filter = TombstoneFilter::new(
+            request.tombstones,
+            &request.projected_schema.to_record_schema_with_key(),
+            memtable_schema.timestamp_name(),
+        );
+
+        Ok(Self {
+            runs,
+            num_key_columns,
+            projected_schema: request.projected_schema,
+            projector,
+            batch_size: ctx.batch_size,
+            sequence: request.sequence,
+            need_dedup: request.need_dedup,
+            cursors,
+            last_row: None,
+            tombstone_filter,
+            sequences: Vec::new(),
+        })
+    }
+
+    /// Fetch next record batch, skip the batches whose rows are all deleted
+    fn fetch_next_record_batch(&mut self) -> Result<Option<RecordBatchWithKey>> {
+        while let Some(mut batch) = self.fetch_next_unfiltered_record_batch()? {
+            if let Some(filter) = &mut self.tombstone_filter {
+                filter
+                    .retain_rows(&mut batch, &self.sequences)
+                    .context(BuildRecordBatch)?;
+            }
+            if batch.num_rows() > 0 {
+                return Ok(Some(batch));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Fetch next record batch without applying tombstones
+    fn fetch_next_unfiltered_record_batch(&mut self) -> Result<Option<RecordBatchWithKey>> {
+        assert!(self.batch_size > 0);
+
+        self.sequences.clear();
+
+        let mut builder = RecordBatchWithKeyBuilder::with_capacity(
+            self.projected_schema.to_record_schema_with_key(),
+            self.batch_size,
+        );
+        let mut num_rows = 0;
+        while num_rows < self.batch_size {
+            let mut cursor = match self.cursors.pop() {
+                Some(cursor) => cursor,
+                None => break,
+            };
+            let (run, index) = (cursor.run, cursor.next_index);
+            cursor.next_index += 1;
+            if cursor.next_index < cursor.end_index {
+                self.cursors.push(cursor);
+            }
+            let chunk = &self.runs[run];
+
+            if self.need_dedup {
+                // Whether this user key is already returned
+                if let Some((last_run, last_index)) = self.last_row {
+                    if chunk
+                        .compare_key(
+                            index,
+                            &self.runs[last_run],
+                            last_index,
+                            self.num_key_columns,
+                        )
+                        .is_eq()
+                    {
+                        continue;
+                    }
+                }
+            }
+
+            // Check whether this row is visible
+            let sequence = chunk.sequences[index].sequence();
+            if sequence > self.sequence {
+                continue;
+            }
+
+            let row = self.project_row(run, index);
+            trace!("Column iterator fetch next row, row:{:?}", row);
+
+            builder.append_row(row).context(AppendRow)?;
+            self.sequences.push(sequence);
+            self.last_row = Some((run, index));
+            num_rows += 1;
+        }
+
+        if num_rows > 0 {
+            let batch = builder.build().context(BuildRecordBatch)?;
+            trace!("column iterator send one batch:{:?}", batch);
+
+            Ok(Some(batch))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn project_row(&self, run: usize, index: usize) -> Row {
+        let chunk = &self.runs[run];
+        let datums = self
+            .projector
+            .source_projection()
+            .iter()
+            .map(|source_index| match source_index {
+                Some(source_index) => chunk.columns[*source_index].datum(index),
+                // The column is not in the memtable, fill by null.
+                None => Datum::Null,
+            })
+            .collect();
+
+        Row::from_datums(datums)
+    }
+}
+
+impl Iterator for ColumnarIterImpl {
+    type Item = Result<RecordBatchWithKey>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.fetch_next_record_batch().transpose()
+    }
+}
+
+/// Returns index of the first row whose user key is greater than or equal to
+/// `user_key`, or greater than `user_key` if `skip_equal` is true.
+fn search(
+    chunk: &Chunk,
+    num_key_columns: usize,
+    user_key: &[u8],
+    skip_equal: bool,
+) -> Result<usize> {
+    let mut key_buf = ByteVec::new();
+    let (mut low, mut high) = (0, chunk.num_rows());
+    while low < high {
+        let mid = low + (high - low) / 2;
+        key_buf.clear();
+        chunk.encode_user_key(mid, num_key_columns, &mut key_buf)?;
+
+        let before = if skip_equal {
+            key_buf[..] <= *user_key
+        } else {
+            key_buf[..] < *user_key
+        };
+        if before {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+
+    Ok(low)
+}
@@ -0,0 +1,779 @@
+// Copyright 2022 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! MemTable based on columnar buffers
+//!
+//! Rows are appended into the arrow builders of each column without encoding
+//! the key and the value, and are sorted by the key and sequence lazily when
+//! the memtable is scanned or flushed, so the memtable is cheap to write but
+//! only suitable for tables in append mode.
+//!
+//! The rows appended since last sort are sorted into a new run, which is
+//! merged with the previous runs not much larger than it, so the sizes of the
+//! runs decrease geometrically and there are at most log2(rows) runs. The
+//! remaining runs are merged by the iterator.
+
+pub mod factory;
+pub mod iter;
+
+use std::{
+    cmp::Ordering,
+    mem,
+    sync::{
+        atomic::{self, AtomicU64, AtomicUsize},
+        Arc, Mutex,
+    },
+};
+
+use arena::CollectorRef;
+use common_types::{
+    bytes::{ByteVec, Bytes},
+    column::{ColumnBlock, ColumnBlockBuilder},
+    datum::Datum,
+    row::{self, Row},
+    schema::Schema,
+    SequenceNumber,
+};
+use common_util::codec::{memcomparable::MemComparable, Encoder};
+use log::{debug, trace};
+use snafu::{ensure, ResultExt};
+
+use crate::memtable::{
+    columnar::iter::ColumnarIterImpl,
+    key::{ComparableInternalKey, KeySequence},
+    skiplist::iter::ReversedColumnarIterator,
+    AppendDatum, ColumnarIterPtr, EncodeInternalKey, EncodeUserKey, InvalidPutSequence, InvalidRow,
+    MemTable, PutContext, Result, ScanContext, ScanRequest,
+};
+
+/// MemTable implementation based on columnar buffers
+pub struct ColumnarMemTable {
+    /// Schema of this memtable, is immutable.
+    schema: Schema,
+    /// Rows not sorted yet.
+    buffer: Mutex<ColumnBuffer>,
+    /// Sorted runs of the rows, the lock also serializes the sorts so a scan
+    /// always sees the rows taken from the buffer by the other scans.
+    sorted_runs: Mutex<Vec<Arc<Chunk>>>,
+    /// Approximate bytes used by the rows in this memtable.
+    bytes_used: AtomicUsize,
+    collector: CollectorRef,
+    /// The last sequence of the rows in this memtable. Update to this field
+    /// require external synchronization.
+    last_sequence: AtomicU64,
+}
+
+impl ColumnarMemTable {
+    /// Sort the rows appended since last sort into a new run and returns all
+    /// the sorted runs.
+    fn sorted_runs(&self) -> Vec<Arc<Chunk>> {
+        let mut sorted_runs = self.sorted_runs.lock().unwrap();
+        let unsorted = self.buffer.lock().unwrap().take_unsorted();
+        // The rows are sorted without holding the lock of the buffer, so the
+        // puts are not blocked.
+        if let Some(unsorted) = unsorted {
+            let num_key_columns = self.schema.num_key_columns();
+            let mut run = unsorted.sort(num_key_columns);
+            // Keep every run more than twice as large as the next one, so a
+            // lot of small sorts don't leave a lot of tiny runs.
+            while let Some(last) = sorted_runs.last() {
+                if last.num_rows() > 2 * run.num_rows() {
+                    break;
+                }
+                run = last.merge(&run, num_key_columns);
+                sorted_runs.pop();
+            }
+            sorted_runs.push(Arc::new(run));
+        }
+
+        sorted_runs.clone()
+    }
+
+    /// Returns the min or max internal key of the rows in the sorted runs and
+    /// the buffer, without changing the layout of the runs.
+    fn key_bound(&self, max: bool) -> Option<Bytes> {
+        // Hold the lock of the runs so no row is moved from the buffer to the
+        // runs by a concurrent sort.
+        let sorted_runs = self.sorted_runs.lock().unwrap();
+        let buffer_row = {
+            let buffer = self.buffer.lock().unwrap();
+            if max {
+                buffer.max_row.clone()
+            } else {
+                buffer.min_row.clone()
+            }
+        };
+
+        // The runs are never empty.
+        let run_keys = sorted_runs.iter().map(|chunk| {
+            let index = if max { chunk.num_rows() - 1 } else { 0 };
+            self.internal_key_at(chunk, index)
+        });
+        let buffer_key =
+            buffer_row.map(|(key_datums, sequence)| self.encode_internal_key(key_datums, sequence));
+        let keys = run_keys
+            .chain(buffer_key)
+            .map(|key| key.expect("should encode the key of rows in memtable"));
+
+        if max {
+            keys.max()
+        } else {
+            keys.min()
+        }
+    }
+
+    fn internal_key_at(&self, chunk: &Chunk, index: usize) -> Result<Bytes> {
+        let num_key_columns = self.schema.num_key_columns();
+        let key_datums = chunk.columns[..num_key_columns]
+            .iter()
+            .map(|column| column.datum(index))
+            .collect();
+
+        self.encode_internal_key(key_datums, chunk.sequences[index])
+    }
+
+    fn encode_internal_key(&self, key_datums: Vec<Datum>, sequence: KeySequence) -> Result<Bytes> {
+        let row = Row::from_datums(key_datums);
+        let key_encoder = ComparableInternalKey::new(sequence, &self.schema);
+        let mut key_buf = ByteVec::with_capacity(key_encoder.estimate_encoded_size(&row));
+        key_encoder
+            .encode(&mut key_buf, &row)
+            .context(EncodeInternalKey)?;
+
+        Ok(key_buf.into())
+    }
+}
+
+impl MemTable for ColumnarMemTable {
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn min_key(&self) -> Option<Bytes> {
+        self.key_bound(false)
+    }
+
+    fn max_key(&self) -> Option<Bytes> {
+        self.key_bound(true)
+    }
+
+    fn put(
+        &self,
+        ctx: &mut PutContext,
+        sequence: KeySequence,
+        row: &Row,
+        _schema: &Schema,
+    ) -> Result<()> {
+        trace!("columnar put row, sequence:{:?}, row:{:?}", sequence, row);
+
+        let null_datum = Datum::Null;
+        let mut datums = Vec::with_capacity(self.schema.num_columns());
+        // Check all the datums before appending, so the builders never contain
+        // a part of a row.
+        for (index_in_table, column) in self.schema.columns().iter().enumerate() {
+            let datum = match ctx.index_in_writer.column_index_in_writer(index_in_table) {
+                Some(writer_index) => &row[writer_index],
+                None => &null_datum,
+            };
+            row::check_datum_type(datum, column)
+                .map_err(|e| Box::new(e) as _)
+                .context(InvalidRow)?;
+            datums.push(datum);
+        }
+
+        let mut bytes = mem::size_of::<KeySequence>();
+        let mut buffer = self.buffer.lock().unwrap();
+        for (builder, datum) in buffer.builders.iter_mut().zip(&datums) {
+            bytes += estimate_datum_size(datum);
+            builder.append_view(datum.as_view()).context(AppendDatum)?;
+        }
+        buffer.sequences.push(sequence);
+        buffer.update_key_range(&datums[..self.schema.num_key_columns()], sequence);
+        drop(buffer);
+
+        self.bytes_used.fetch_add(bytes, atomic::Ordering::Relaxed);
+        self.collector.on_alloc(bytes);
+        self.collector.on_used(bytes);
+
+        Ok(())
+    }
+
+    fn scan(&self, ctx: ScanContext, request: ScanRequest) -> Result<ColumnarIterPtr> {
+        debug!(
+            "Scan columnar memtable, ctx:{:?}, request:{:?}",
+            ctx, request
+        );
+
+        let sorted_runs = self.sorted_runs();
+        let num_rows = sorted_runs.iter().map(|chunk| chunk.num_rows()).sum();
+        let (reverse, batch_size) = (request.reverse, ctx.batch_size);
+        let iter = ColumnarIterImpl::new(&self.schema, sorted_runs, ctx, request)?;
+        if reverse {
+            Ok(Box::new(ReversedColumnarIterator::new(
+                iter, num_rows, batch_size,
+            )))
+        } else {
+            Ok(Box::new(iter))
+        }
+    }
+
+    fn approximate_memory_usage(&self) -> usize {
+        self.bytes_used.load(atomic::Ordering::Relaxed)
+    }
+
+    fn set_last_sequence(&self, sequence: SequenceNumber) -> Result<()> {
+        let last = self.last_sequence();
+        ensure!(
+            sequence >= last,
+            InvalidPutSequence {
+                given: sequence,
+                last
+            }
+        );
+
+        self.last_sequence
+            .store(sequence, atomic::Ordering::Relaxed);
+
+        Ok(())
+    }
+
+    fn last_sequence(&self) -> SequenceNumber {
+        self.last_sequence.load(atomic::Ordering::Relaxed)
+    }
+}
+
+impl Drop for ColumnarMemTable {
+    fn drop(&mut self) {
+        let bytes = self.bytes_used.load(atomic::Ordering::Relaxed);
+        self.collector.on_free(bytes, bytes);
+    }
+}
+
+/// Rows buffered in columnar format.
+struct ColumnBuffer {
+    /// Builders of the rows not sorted yet, one builder for each column.
+    builders: Vec<ColumnBlockBuilder>,
+    /// Sequences of the rows not sorted yet.
+    sequences: Vec<KeySequence>,
+    /// Key datums and sequence of the min row not sorted yet.
+    min_row: Option<(Vec<Datum>, KeySequence)>,
+    /// Key datums and sequence of the max row not sorted yet.
+    max_row: Option<(Vec<Datum>, KeySequence)>,
+}
+
+impl ColumnBuffer {
+    fn new(schema: &Schema) -> Self {
+        let builders = schema
+            .columns()
+            .iter()
+            .map(|column| ColumnBlockBuilder::new(&column.data_type))
+            .collect();
+
+        Self {
+            builders,
+            sequences: Vec::new(),
+            min_row: None,
+            max_row: None,
+        }
+    }
+
+    /// Update the min and max rows by the row just appended.
+    fn update_key_range(&mut self, key_datums: &[&Datum], sequence: KeySequence) {
+        let is_min = match &self.min_row {
+            Some((min_datums, min_sequence)) => {
+                compare_key_row(key_datums, sequence, min_datums, *min_sequence) == Ordering::Less
+            }
+            None => true,
+        };
+        if is_min {
+            let datums = key_datums.iter().map(|&datum| datum.clone()).collect();
+            self.min_row = Some((datums, sequence));
+        }
+
+        let is_max = match &self.max_row {
+            Some((max_datums, max_sequence)) => {
+                compare_key_row(key_datums, sequence, max_datums, *max_sequence)
+                    == Ordering::Greater
+            }
+            None => true,
+        };
+        if is_max {
+            let datums = key_datums.iter().map(|&datum| datum.clone()).collect();
+            self.max_row = Some((datums, sequence));
+        }
+    }
+
+    /// Take the rows appended since last call, returns None if there is no
+    /// row.
+    fn take_unsorted(&mut self) -> Option<Chunk> {
+        if self.sequences.is_empty() {
+            return None;
+        }
+
+        self.min_row = None;
+        self.max_row = None;
+        Some(Chunk {
+            columns: self
+                .builders
+                .iter_mut()
+                .map(|builder| builder.build())
+                .collect(),
+            sequences: mem::take(&mut self.sequences),
+        })
+    }
+}
+
+/// Rows in columnar format, the order of the columns is the same as the schema
+/// of the memtable.
+///
+/// The rows of a sorted chunk are ordered by user key ascending and sequence
+/// descending, the same as the order of the internal keys.
+pub(crate) struct Chunk {
+    columns: Vec<ColumnBlock>,
+    sequences: Vec<KeySequence>,
+}
+
+impl Chunk {
+    #[inline]
+    fn num_rows(&self) -> usize {
+        self.sequences.len()
+    }
+
+    /// Returns the rows of this chunk sorted by the user key ascending and
+    /// sequence descending.
+    fn sort(&self, num_key_columns: usize) -> Chunk {
+        let mut indexes: Vec<_> = (0..self.num_rows()).collect();
+        indexes.sort_unstable_by(|&lhs, &rhs| self.compare_row(lhs, self, rhs, num_key_columns));
+
+        let positions: Vec<_> = indexes.into_iter().map(|index| (0, index)).collect();
+        Chunk::from_rows(&[self], &positions)
+    }
+
+    /// Merge the rows of this sorted chunk and the `other` sorted chunk into
+    /// a new sorted chunk.
+    fn merge(&self, other: &Chunk, num_key_columns: usize) -> Chunk {
+        // Positions of the merged rows, in (chunk index, row index).
+        let mut positions = Vec::with_capacity(self.num_rows() + other.num_rows());
+        let (mut index, mut other_index) = (0, 0);
+        while index < self.num_rows() && other_index < other.num_rows() {
+            if self.compare_row(index, other, other_index, num_key_columns) == Ordering::Greater {
+                positions.push((1, other_index));
+                other_index += 1;
+            } else {
+                positions.push((0, index));
+                index += 1;
+            }
+        }
+        positions.extend((index..self.num_rows()).map(|index| (0, index)));
+        positions.extend((other_index..other.num_rows()).map(|index| (1, index)));
+
+        Chunk::from_rows(&[self, other], &positions)
+    }
+
+    /// Build a chunk from the rows at `positions`, each position is the index
+    /// of the chunk in `chunks` and the index of the row in that chunk.
+    fn from_rows(chunks: &[&Chunk], positions: &[(usize, usize)]) -> Chunk {
+        let mut builders: Vec<_> = chunks[0]
+            .columns
+            .iter()
+            .map(|column| ColumnBlockBuilder::with_capacity(&column.datum_kind(), positions.len()))
+            .collect();
+        for (column_index, builder) in builders.iter_mut().enumerate() {
+            for &(chunk_index, index) in positions {
+                let column = &chunks[chunk_index].columns[column_index];
+                builder
+                    .append_view(column.datum_view(index))
+                    .expect("should append datum of the same kind");
+            }
+        }
+
+        Chunk {
+            columns: builders.iter_mut().map(|builder| builder.build()).collect(),
+            sequences: positions
+                .iter()
+                .map(|&(chunk_index, index)| chunks[chunk_index].sequences[index])
+                .collect(),
+        }
+    }
+
+    /// Compare the user key of the row at `index` with the row at
+    /// `other_index` of `other`.
+    fn compare_key(
+        &self,
+        index: usize,
+        other: &Chunk,
+        other_index: usize,
+        num_key_columns: usize,
+    ) -> Ordering {
+        for (column, other_column) in self.columns[..num_key_columns]
+            .iter()
+            .zip(&other.columns[..num_key_columns])
+        {
+            // The key columns can't be float so the views are always comparable.
+            let ordering = column
+                .datum_view(index)
+                .partial_cmp(&other_column.datum_view(other_index))
+                .unwrap_or(Ordering::Equal);
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+
+        Ordering::Equal
+    }
+
+    /// Compare the row at `index` with the row at `other_index` of `other`,
+    /// by the user key ascending and then the sequence descending.
+    fn compare_row(
+        &self,
+        index: usize,
+        other: &Chunk,
+        other_index: usize,
+        num_key_columns: usize,
+    ) -> Ordering {
+        self.compare_key(index, other, other_index, num_key_columns)
+            .then_with(|| {
+                let (lhs, rhs) = (self.sequences[index], other.sequences[other_index]);
+                (rhs.sequence(), rhs.row_index()).cmp(&(lhs.sequence(), lhs.row_index()))
+            })
+    }
+
+    /// Encode the user key of the row at `index` into `buf` in memcomparable
+    /// format.
+    fn encode_user_key(
+        &self,
+        index: usize,
+        num_key_columns: usize,
+        buf: &mut ByteVec,
+    ) -> Result<()> {
+        let encoder = MemComparable;
+        for column in &self.columns[..num_key_columns] {
+            encoder
+                .encode(buf, &column.datum(index))
+                .context(EncodeUserKey)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Compare the row with `key_datums` and `sequence` with the other row, by the
+/// user key ascending and then the sequence descending.
+fn compare_key_row(
+    key_datums: &[&Datum],
+    sequence: KeySequence,
+    other_datums: &[Datum],
+    other_sequence: KeySequence,
+) -> Ordering {
+    for (&datum, other_datum) in key_datums.iter().zip(other_datums) {
+        // The key columns can't be float so the datums are always comparable.
+        let ordering = datum.partial_cmp(other_datum).unwrap_or(Ordering::Equal);
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+
+    (other_sequence.sequence(), other_sequence.row_index())
+        .cmp(&(sequence.sequence(), sequence.row_index()))
+}
+
+/// Estimate the bytes used by the datum in the builder.
+fn estimate_datum_size(datum: &Datum) -> usize {
+    match datum {
+        Datum::Varbinary(v) => v.len() + mem::size_of::<i32>(),
+        Datum::String(v) => v.len() + mem::size_of::<i32>(),
+        _ => mem::size_of::<u64>(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Bound;
+
+    use arena::NoopCollector;
+    use common_types::{
+        projected_schema::ProjectedSchema,
+        record_batch::RecordBatchWithKey,
+        schema::IndexInWriterSchema,
+        tests::{build_row, build_schema},
+    };
+
+    use super::*;
+    use crate::memtable::{
+        columnar::factory::ColumnarMemTableFactory,
+        factory::{Factory, Options},
+        MemTableRef,
+    };
+
+    fn build_memtable(schema: &Schema) -> MemTableRef {
+        let memtable = ColumnarMemTableFactory
+            .create_memtable(Options {
+                schema: schema.clone(),
+                arena_block_size: 512,
+                creation_sequence: 1,
+                collector: Arc::new(NoopCollector {}),
+            })
+            .unwrap();
+
+        let mut ctx = PutContext::new(IndexInWriterSchema::for_same_schema(schema.num_columns()));
+        let input = vec![
+            (KeySequence::new(1, 1), build_row(b"d", 4, 10.0, "v4")),
+            (KeySequence::new(1, 2), build_row(b"b", 2, 10.0, "v2")),
+            (KeySequence::new(1, 3), build_row(b"a", 1, 10.0, "v1")),
+            (
+                KeySequence::new(1, 4),
+                build_row(b"c", 3, 10.0, "c in seq 1"),
+            ),
+        ];
+        for (seq, row) in input {
+            memtable.put(&mut ctx, seq, &row, schema).unwrap();
+        }
+        // Sort the rows written before.
+        assert_eq!(4, scan_rows(&memtable, new_request(schema, 1, false)).len());
+
+        let input = vec![
+            (
+                KeySequence::new(2, 1),
+                build_row(b"c", 3, 10.0, "c in seq 2"),
+            ),
+            (KeySequence::new(2, 2), build_row(b"f", 6, 10.0, "v6")),
+            (KeySequence::new(3, 1), build_row(b"e", 5, 10.0, "v5")),
+        ];
+        for (seq, row) in input {
+            memtable.put(&mut ctx, seq, &row, schema).unwrap();
+        }
+
+        memtable
+    }
+
+    fn scan_rows(memtable: &MemTableRef, request: ScanRequest) -> Vec<Row> {
+        let ctx = ScanContext { batch_size: 2 };
+        let batches: Vec<RecordBatchWithKey> = memtable
+            .scan(ctx, request)
+            .unwrap()
+            .map(|batch| batch.unwrap())
+            .collect();
+
+        batches
+            .iter()
+            .flat_map(|batch| (0..batch.num_rows()).map(move |i| batch.clone_row_at(i)))
+            .collect()
+    }
+
+    fn build_scan_key(c1: &str, c2: i64) -> Bytes {
+        let mut buf = ByteVec::new();
+        let encoder = MemComparable;
+        encoder.encode(&mut buf, &Datum::from(c1)).unwrap();
+        encoder.encode(&mut buf, &Datum::from(c2)).unwrap();
+
+        Bytes::from(buf)
+    }
+
+    fn new_request(schema: &Schema, sequence: SequenceNumber, need_dedup: bool) -> ScanRequest {
+        ScanRequest {
+            start_user_key: Bound::Unbounded,
+            end_user_key: Bound::Unbounded,
+            sequence,
+            projected_schema: ProjectedSchema::no_projection(schema.clone()),
+            need_dedup,
+            reverse: false,
+            tombstones: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_columnar_memtable_scan() {
+        let schema = build_schema();
+        let memtable = build_memtable(&schema);
+
+        let rows = scan_rows(&memtable, new_request(&schema, 3, false));
+        let expected = vec![
+            build_row(b"a", 1, 10.0, "v1"),
+            build_row(b"b", 2, 10.0, "v2"),
+            build_row(b"c", 3, 10.0, "c in seq 2"),
+            build_row(b"c", 3, 10.0, "c in seq 1"),
+            build_row(b"d", 4, 10.0, "v4"),
+            build_row(b"e", 5, 10.0, "v5"),
+            build_row(b"f", 6, 10.0, "v6"),
+        ];
+        assert_eq!(expected, rows);
+
+        // Limited by sequence and deduped.
+        let rows = scan_rows(&memtable, new_request(&schema, 1, true));
+        let expected = vec![
+            build_row(b"a", 1, 10.0, "v1"),
+            build_row(b"b", 2, 10.0, "v2"),
+            build_row(b"c", 3, 10.0, "c in seq 1"),
+            build_row(b"d", 4, 10.0, "v4"),
+        ];
+        assert_eq!(expected, rows);
+
+        // Limited by start/end key.
+        let mut request = new_request(&schema, 3, true);
+        request.start_user_key = Bound::Excluded(build_scan_key("a", 1));
+        request.end_user_key = Bound::Included(build_scan_key("e", 5));
+        let rows = scan_rows(&memtable, request);
+        let expected = vec![
+            build_row(b"b", 2, 10.0, "v2"),
+            build_row(b"c", 3, 10.0, "c in seq 2"),
+            build_row(b"d", 4, 10.0, "v4"),
+            build_row(b"e", 5, 10.0, "v5"),
+        ];
+        assert_eq!(expected, rows);
+
+        // Reversed.
+        let mut request = new_request(&schema, 3, true);
+        request.end_user_key = Bound::Excluded(build_scan_key("c", 3));
+        request.reverse = true;
+        let rows = scan_rows(&memtable, request);
+        let expected = vec![
+            build_row(b"b", 2, 10.0, "v2"),
+            build_row(b"a", 1, 10.0, "v1"),
+        ];
+        assert_eq!(expected, rows);
+    }
+
+    #[test]
+    fn test_columnar_memtable_sorted_runs() {
+        let schema = build_schema();
+        let memtable = build_memtable(&schema);
+        let memory_usage = memtable.approximate_memory_usage();
+
+        // Rows put after the scan are sorted into another run.
+        let request = new_request(&schema, 3, true);
+        assert_eq!(6, scan_rows(&memtable, request).len());
+        let mut ctx = PutContext::new(IndexInWriterSchema::for_same_schema(schema.num_columns()));
+        let row = build_row(b"c", 3, 10.0, "c in seq 4");
+        memtable
+            .put(&mut ctx, KeySequence::new(4, 1), &row, &schema)
+            .unwrap();
+        assert!(memtable.approximate_memory_usage() > memory_usage);
+
+        let rows = scan_rows(&memtable, new_request(&schema, 4, true));
+        let expected = vec![
+            build_row(b"a", 1, 10.0, "v1"),
+            build_row(b"b", 2, 10.0, "v2"),
+            build_row(b"c", 3, 10.0, "c in seq 4"),
+            build_row(b"d", 4, 10.0, "v4"),
+            build_row(b"e", 5, 10.0, "v5"),
+            build_row(b"f", 6, 10.0, "v6"),
+        ];
+        assert_eq!(expected, rows);
+    }
+
+    #[test]
+    fn test_columnar_memtable_key_range() {
+        let schema = build_schema();
+        let memtable = build_memtable(&schema);
+
+        let mut buf = ByteVec::new();
+        ComparableInternalKey::new(KeySequence::new(1, 3), &schema)
+            .encode(&mut buf, &build_row(b"a", 1, 10.0, "v1"))
+            .unwrap();
+        assert_eq!(Some(Bytes::from(buf)), memtable.min_key());
+
+        let mut buf = ByteVec::new();
+        ComparableInternalKey::new(KeySequence::new(2, 2), &schema)
+            .encode(&mut buf, &build_row(b"f", 6, 10.0, "v6"))
+            .unwrap();
+        assert_eq!(Some(Bytes::from(buf)), memtable.max_key());
+        assert!(memtable.approximate_memory_usage() > 0);
+    }
+
+    fn build_columnar_memtable(schema: &Schema) -> ColumnarMemTable {
+        ColumnarMemTable {
+            schema: schema.clone(),
+            buffer: Mutex::new(ColumnBuffer::new(schema)),
+            sorted_runs: Mutex::new(Vec::new()),
+            bytes_used: AtomicUsize::new(0),
+            collector: Arc::new(NoopCollector {}),
+            last_sequence: AtomicU64::new(1),
+        }
+    }
+
+    fn num_sorted_runs(memtable: &ColumnarMemTable) -> usize {
+        memtable.sorted_runs.lock().unwrap().len()
+    }
+
+    #[test]
+    fn test_columnar_memtable_merge_runs() {
+        let schema = build_schema();
+        let memtable = build_columnar_memtable(&schema);
+        let mut ctx = PutContext::new(IndexInWriterSchema::for_same_schema(schema.num_columns()));
+
+        // Sort after every put, in descending key order.
+        let num_rows = 100;
+        for i in 0..num_rows {
+            let key = num_rows - i;
+            let row = build_row(b"a", key, 10.0, "v");
+            memtable
+                .put(&mut ctx, KeySequence::new(i as u64 + 1, 0), &row, &schema)
+                .unwrap();
+            memtable.sorted_runs();
+
+            // At most log2(rows) + 1 runs.
+            let max_runs = 64 - (i as u64 + 1).leading_zeros() as usize;
+            assert!(num_sorted_runs(&memtable) <= max_runs);
+        }
+
+        let sorted_runs = memtable.sorted_runs();
+        assert_eq!(
+            num_rows as usize,
+            sorted_runs
+                .iter()
+                .map(|chunk| chunk.num_rows())
+                .sum::<usize>()
+        );
+        for chunk in &sorted_runs {
+            for index in 1..chunk.num_rows() {
+                let ordering = chunk.compare_row(index - 1, chunk, index, schema.num_key_columns());
+                assert_eq!(Ordering::Less, ordering);
+            }
+        }
+    }
+
+    #[test]
+    fn test_columnar_memtable_key_range_unsorted() {
+        let schema = build_schema();
+        let memtable = build_columnar_memtable(&schema);
+        let mut ctx = PutContext::new(IndexInWriterSchema::for_same_schema(schema.num_columns()));
+        assert!(memtable.min_key().is_none());
+        assert!(memtable.max_key().is_none());
+
+        let input = vec![
+            (KeySequence::new(1, 1), build_row(b"b", 2, 10.0, "v2")),
+            (KeySequence::new(1, 2), build_row(b"c", 3, 10.0, "v3")),
+        ];
+        for (seq, row) in input {
+            memtable.put(&mut ctx, seq, &row, &schema).unwrap();
+        }
+        memtable.sorted_runs();
+
+        // Rows in the buffer only.
+        let input = vec![
+            (
+                KeySequence::new(2, 1),
+                build_row(b"b", 2, 10.0, "v2 in seq 2"),
+            ),
+            (KeySequence::new(2, 2), build_row(b"a", 1, 10.0, "v1")),
+        ];
+        for (seq, row) in input {
+            memtable.put(&mut ctx, seq, &row, &schema).unwrap();
+        }
+
+        let encode_key = |sequence, row: &Row| {
+            let mut buf = ByteVec::new();
+            ComparableInternalKey::new(sequence, &schema)
+                .encode(&mut buf, row)
+                .unwrap();
+            Bytes::from(buf)
+        };
+        let min_key = encode_key(KeySequence::new(2, 2), &build_row(b"a", 1, 10.0, "v1"));
+        let max_key = encode_key(KeySequence::new(1, 2), &build_row(b"c", 3, 10.0, "v3"));
+        assert_eq!(Some(min_key), memtable.min_key());
+        assert_eq!(Some(max_key), memtable.max_key());
+
+        // The key range doesn't sort the buffer.
+        assert_eq!(1, num_sorted_runs(&memtable));
+        assert_eq!(2, memtable.buffer.lock().unwrap().sequences.len());
+    }
+}
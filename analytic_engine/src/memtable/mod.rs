@@ -2,6 +2,7 @@
 
 //! MemTable
 
+pub mod columnar;
 pub mod factory;
 pub mod key;
 pub mod skiplist;
@@ -19,7 +20,7 @@ use common_types::{
 use common_util::define_result;
 use snafu::{Backtrace, Snafu};
 
-use crate::memtable::key::KeySequence;
+use crate::{memtable::key::KeySequence, table::tombstone::Tombstone};
 
 const DEFAULT_SCAN_BATCH_SIZE: usize = 500;
 
@@ -29,6 +30,11 @@ pub enum Error {
     #[snafu(display("Failed to encode internal key, err:{}", source))]
     EncodeInternalKey { source: crate::memtable::key::Error },
 
+    #[snafu(display("Failed to encode user key, err:{}", source))]
+    EncodeUserKey {
+        source: common_util::codec::memcomparable::Error,
+    },
+
     #[snafu(display("Failed to decode internal key, err:{}", source))]
     DecodeInternalKey { source: crate::memtable::key::Error },
 
@@ -42,6 +48,9 @@ pub enum Error {
         source: common_types::record_batch::Error,
     },
 
+    #[snafu(display("Failed to append datum to column builder, err:{}", source))]
+    AppendDatum { source: common_types::column::Error },
+
     #[snafu(display("Failed to build record batch, err:{}", source,))]
     BuildRecordBatch {
         source: common_types::record_batch::Error,
@@ -128,6 +137,9 @@ pub struct ScanRequest {
     pub projected_schema: ProjectedSchema,
     pub need_dedup: bool,
     pub reverse: bool,
+    /// Tombstones to apply, rows deleted by a tombstone written after them are
+    /// not returned.
+    pub tombstones: Vec<Tombstone>,
 }
 
 /// In memory storage for table's data.
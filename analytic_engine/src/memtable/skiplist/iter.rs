@@ -18,11 +18,14 @@ use log::trace;
 use skiplist::{ArenaSlice, IterRef, Skiplist};
 use snafu::ResultExt;
 
-use crate::memtable::{
-    key::{self, KeySequence},
-    skiplist::{BytewiseComparator, SkiplistMemTable},
-    AppendRow, BuildRecordBatch, DecodeInternalKey, EncodeInternalKey, IterReverse, ProjectSchema,
-    Result, ScanContext, ScanRequest,
+use crate::{
+    memtable::{
+        key::{self, KeySequence},
+        skiplist::{BytewiseComparator, SkiplistMemTable},
+        AppendRow, BuildRecordBatch, DecodeInternalKey, EncodeInternalKey, IterReverse,
+        ProjectSchema, Result, ScanContext, ScanRequest,
+    },
+    table::tombstone::TombstoneFilter,
 };
 
 /// Iterator state
@@ -63,6 +66,11 @@ pub struct ColumnarIterImpl<A: Arena<Stats = BasicStats> + Clone + Sync + Send>
 
     /// Dedup rows with key
     need_dedup: bool,
+
+    /// Filter to remove rows deleted by tombstones
+    tombstone_filter: Option<TombstoneFilter>,
+    /// Sequences of rows in the batch to build
+    sequences: Vec<SequenceNumber>,
 }
 
 impl<A: Arena<Stats = BasicStats> + Clone + Sync + Send> ColumnarIterImpl<A> {
@@ -78,6 +86,12 @@ impl<A: Arena<Stats = BasicStats> + Clone + Sync + Send> ColumnarIterImpl<A> {
             .try_project_with_key(&memtable.schema)
             .context(ProjectSchema)?;
 
+        let tombstone_filter = TombstoneFilter::new(
+            request.tombstones,
+            &request.projected_schema.to_record_schema_with_key(),
+            memtable.schema.timestamp_name(),
+        );
+
         let iter = memtable.skiplist.iter();
         let mut columnar_iter = Self {
             iter,
@@ -91,6 +105,8 @@ impl<A: Arena<Stats = BasicStats> + Clone + Sync + Send> ColumnarIterImpl<A> {
             state: State::Uninitialized,
             last_internal_key: None,
             need_dedup: request.need_dedup,
+            tombstone_filter,
+            sequences: Vec::new(),
         };
 
         columnar_iter.init()?;
@@ -133,18 +149,41 @@ impl<A: Arena<Stats = BasicStats> + Clone + Sync + Send> ColumnarIterImpl<A> {
         Ok(())
     }
 
-    /// Fetch next record batch
+    /// Fetch next record batch, skip the batches whose rows are all deleted
     fn fetch_next_record_batch(&mut self) -> Result<Option<RecordBatchWithKey>> {
+        while self.state == State::Initialized {
+            let mut batch = match self.fetch_next_unfiltered_record_batch()? {
+                Some(v) => v,
+                None => return Ok(None),
+            };
+
+            if let Some(filter) = &mut self.tombstone_filter {
+                filter
+                    .retain_rows(&mut batch, &self.sequences)
+                    .context(BuildRecordBatch)?;
+            }
+            if batch.num_rows() > 0 {
+                return Ok(Some(batch));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Fetch next record batch without applying tombstones
+    fn fetch_next_unfiltered_record_batch(&mut self) -> Result<Option<RecordBatchWithKey>> {
         debug_assert_eq!(State::Initialized, self.state);
         assert!(self.batch_size > 0);
 
+        self.sequences.clear();
+
         let mut builder = RecordBatchWithKeyBuilder::with_capacity(
             self.projected_schema.to_record_schema_with_key(),
             self.batch_size,
         );
         let mut num_rows = 0;
         while self.iter.valid() && num_rows < self.batch_size {
-            if let Some(row) = self.fetch_next_row()? {
+            if let Some((row, sequence)) = self.fetch_next_row()? {
                 let row_reader = ContiguousRowReader::with_schema(&row, &self.memtable_schema);
                 let projected_row = ProjectedContiguousRow::new(row_reader, &self.projector);
 
@@ -153,6 +192,7 @@ impl<A: Arena<Stats = BasicStats> + Clone + Sync + Send> ColumnarIterImpl<A> {
                 builder
                     .append_projected_contiguous_row(&projected_row)
                     .context(AppendRow)?;
+                self.sequences.push(sequence.sequence());
                 num_rows += 1;
             } else {
                 // There is no more row to fetch
@@ -178,7 +218,7 @@ impl<A: Arena<Stats = BasicStats> + Clone + Sync + Send> ColumnarIterImpl<A> {
     /// will be considered
     ///
     /// REQUIRE: The iter is valid
-    fn fetch_next_row(&mut self) -> Result<Option<ArenaSlice<A>>> {
+    fn fetch_next_row(&mut self) -> Result<Option<(ArenaSlice<A>, KeySequence)>> {
         debug_assert_eq!(State::Initialized, self.state);
 
         // TODO(yingwen): Some operation like delete needs to be considered during
@@ -234,7 +274,7 @@ impl<A: Arena<Stats = BasicStats> + Clone + Sync + Send> ColumnarIterImpl<A> {
             // Move iter forward
             self.iter.next();
 
-            return Ok(Some(row));
+            return Ok(Some((row, sequence)));
         }
 
         // No more row in range, we can stop the iterator
@@ -180,14 +180,17 @@ mod tests {
         record_batch::RecordBatchWithKey,
         schema::IndexInWriterSchema,
         tests::{build_row, build_schema},
-        time::Timestamp,
+        time::{TimeRange, Timestamp},
     };
     use common_util::codec::memcomparable::MemComparable;
 
     use super::*;
-    use crate::memtable::{
-        factory::{Factory, Options},
-        skiplist::factory::SkiplistMemTableFactory,
+    use crate::{
+        memtable::{
+            factory::{Factory, Options},
+            skiplist::factory::SkiplistMemTableFactory,
+        },
+        table::tombstone::Tombstone,
     };
 
     fn test_memtable_scan_for_scan_request(
@@ -207,6 +210,7 @@ mod tests {
                     projected_schema: projected_schema.clone(),
                     need_dedup: true,
                     reverse: false,
+                    tombstones: Vec::new(),
                 },
                 vec![
                     build_row(b"a", 1, 10.0, "v1"),
@@ -217,6 +221,28 @@ mod tests {
                     build_row(b"f", 6, 10.0, "v6"),
                 ],
             ),
+            (
+                // rows written before the tombstone are deleted
+                ScanRequest {
+                    start_user_key: Bound::Unbounded,
+                    end_user_key: Bound::Unbounded,
+                    sequence: 3,
+                    projected_schema: projected_schema.clone(),
+                    need_dedup: true,
+                    reverse: false,
+                    tombstones: vec![Tombstone {
+                        sequence: 2,
+                        time_range: TimeRange::new_unchecked_for_test(1, 7),
+                        series_keys: None,
+                    }],
+                },
+                vec![
+                    build_row(b"d", 4, 10.0, "v4"),
+                    build_row(b"e", 5, 10.0, "v5"),
+                    build_row(b"f", 6, 10.0, "v6"),
+                    build_row(b"g", 7, 10.0, "v7"),
+                ],
+            ),
             (
                 // limited by sequence and start/end key
                 ScanRequest {
@@ -226,6 +252,7 @@ mod tests {
                     projected_schema: projected_schema.clone(),
                     need_dedup: true,
                     reverse: false,
+                    tombstones: Vec::new(),
                 },
                 vec![
                     build_row(b"a", 1, 10.0, "v1"),
@@ -244,6 +271,7 @@ mod tests {
                     projected_schema,
                     need_dedup: true,
                     reverse: false,
+                    tombstones: Vec::new(),
                 },
                 vec![
                     build_row(b"a", 1, 10.0, "v1"),
@@ -275,6 +303,7 @@ mod tests {
                 projected_schema,
                 need_dedup: true,
                 reverse: false,
+                tombstones: Vec::new(),
             },
             vec![
                 build_row_for_two_column(b"a", 1),
@@ -595,6 +595,8 @@ impl<S: MetaUpdateLogStore + Send + Sync> Snapshotter<S> {
                     flushed_sequence: version_meta.flushed_sequence,
                     files_to_add: version_meta.ordered_files(),
                     files_to_delete: Vec::new(),
+                    tombstones_to_add: version_meta.ordered_tombstones(),
+                    tombstones_to_delete: Vec::new(),
                 };
                 meta_updates.push(MetaUpdateLogEntry::Snapshot {
                     sequence: snapshot.end_seq,
@@ -742,6 +744,7 @@ mod tests {
                 table_name,
                 schema: common_types::tests::build_schema(),
                 opts: TableOptions::default(),
+                partition_info: None,
             })
         }
 
@@ -765,6 +768,8 @@ mod tests {
                 flushed_sequence: flushed_seq.unwrap_or(100),
                 files_to_add: Vec::new(),
                 files_to_delete: Vec::new(),
+                tombstones_to_add: Vec::new(),
+                tombstones_to_delete: Vec::new(),
             })
         }
 
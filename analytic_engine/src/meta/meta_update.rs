@@ -12,13 +12,17 @@ use common_types::{
 use common_util::define_result;
 use proto::{analytic_common, common as common_pb, meta_update as meta_pb};
 use protobuf::Message;
-use snafu::{Backtrace, ResultExt, Snafu};
-use table_engine::table::TableId;
+use snafu::{ensure, Backtrace, ResultExt, Snafu};
+use table_engine::{partition::PartitionInfo, table::TableId};
 use wal::log_batch::{Payload, PayloadDecoder};
 
 use crate::{
+    meta::meta_data::TableManifestData,
     space::SpaceId,
-    table::version_edit::{AddFile, DeleteFile, VersionEdit},
+    table::{
+        tombstone::Tombstone,
+        version_edit::{AddFile, DeleteFile, VersionEdit},
+    },
     TableOptions,
 };
 
@@ -49,6 +53,19 @@ pub enum Error {
     ConvertVersionEdit {
         source: crate::table::version_edit::Error,
     },
+
+    #[snafu(display("Failed to convert tombstone, err:{}", source))]
+    ConvertTombstone {
+        source: crate::table::tombstone::Error,
+    },
+
+    #[snafu(display("Failed to convert partition info, err:{}", source))]
+    ConvertPartitionInfo {
+        source: table_engine::partition::Error,
+    },
+
+    #[snafu(display("Empty table snapshot.\nBacktrace:\n{}", backtrace))]
+    EmptyTableSnapshot { backtrace: Backtrace },
 }
 
 define_result!(Error);
@@ -217,6 +234,8 @@ pub struct AddTableMeta {
     pub schema: Schema,
     // Options needed to persist
     pub opts: TableOptions,
+    /// Partition info of the table
+    pub partition_info: Option<PartitionInfo>,
 }
 
 impl AddTableMeta {
@@ -227,6 +246,9 @@ impl AddTableMeta {
         target.set_table_name(self.table_name);
         target.set_schema(common_pb::TableSchema::from(self.schema));
         target.set_options(analytic_common::TableOptions::from(self.opts));
+        if let Some(partition_info) = self.partition_info {
+            target.set_partition_info(common_pb::PartitionInfo::from(partition_info));
+        }
 
         target
     }
@@ -238,13 +260,22 @@ impl TryFrom<meta_pb::AddTableMeta> for AddTableMeta {
     fn try_from(mut src: meta_pb::AddTableMeta) -> Result<Self> {
         let table_schema = src.take_schema();
         let opts = src.take_options();
+        let schema = Schema::try_from(table_schema).context(ConvertSchema)?;
+        let partition_info = if src.has_partition_info() {
+            let partition_info = PartitionInfo::try_from_pb(src.take_partition_info(), &schema)
+                .context(ConvertPartitionInfo)?;
+            Some(partition_info)
+        } else {
+            None
+        };
 
         Ok(Self {
             space_id: src.space_id,
             table_id: TableId::from(src.table_id),
             table_name: src.table_name,
-            schema: Schema::try_from(table_schema).context(ConvertSchema)?,
+            schema,
             opts: TableOptions::from(opts),
+            partition_info,
         })
     }
 }
@@ -289,6 +320,8 @@ pub struct VersionEditMeta {
     pub flushed_sequence: SequenceNumber,
     pub files_to_add: Vec<AddFile>,
     pub files_to_delete: Vec<DeleteFile>,
+    pub tombstones_to_add: Vec<Tombstone>,
+    pub tombstones_to_delete: Vec<SequenceNumber>,
 }
 
 impl VersionEditMeta {
@@ -310,6 +343,13 @@ impl VersionEditMeta {
         }
         target.files_to_delete = files_to_delete.into();
 
+        let mut tombstones_to_add = Vec::with_capacity(self.tombstones_to_add.len());
+        for tombstone in self.tombstones_to_add {
+            tombstones_to_add.push(tombstone.into_pb());
+        }
+        target.tombstones_to_add = tombstones_to_add.into();
+        target.tombstones_to_delete = self.tombstones_to_delete;
+
         target
     }
 
@@ -321,6 +361,8 @@ impl VersionEditMeta {
             flushed_sequence: self.flushed_sequence,
             files_to_add: self.files_to_add,
             files_to_delete: self.files_to_delete,
+            tombstones_to_add: self.tombstones_to_add,
+            tombstones_to_delete: self.tombstones_to_delete,
         }
     }
 }
@@ -339,12 +381,80 @@ impl TryFrom<meta_pb::VersionEditMeta> for VersionEditMeta {
             files_to_delete.push(DeleteFile::try_from(file_meta).context(ConvertVersionEdit)?);
         }
 
+        let mut tombstones_to_add = Vec::with_capacity(src.tombstones_to_add.len());
+        for tombstone_meta in src.tombstones_to_add {
+            tombstones_to_add.push(Tombstone::try_from(tombstone_meta).context(ConvertTombstone)?);
+        }
+
         Ok(Self {
             space_id: src.space_id,
             table_id: TableId::from(src.table_id),
             flushed_sequence: src.flushed_sequence,
             files_to_add,
             files_to_delete,
+            tombstones_to_add,
+            tombstones_to_delete: src.tombstones_to_delete,
+        })
+    }
+}
+
+/// Snapshot of the manifest data of a table, persisted as the snapshot of the
+/// manifest in the object store and along with the sst files in the backup
+/// store.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableSnapshot {
+    pub table_meta: AddTableMeta,
+    /// Files and tombstones in the snapshot, the `flushed_sequence` is the
+    /// flushed sequence of the table when the snapshot is taken.
+    pub version_edit: VersionEditMeta,
+}
+
+impl From<TableManifestData> for TableSnapshot {
+    fn from(data: TableManifestData) -> Self {
+        let (flushed_sequence, files_to_add, tombstones_to_add) = match data.version_meta {
+            Some(version_meta) => (
+                version_meta.flushed_sequence,
+                version_meta.ordered_files(),
+                version_meta.ordered_tombstones(),
+            ),
+            None => (0, Vec::new(), Vec::new()),
+        };
+
+        Self {
+            version_edit: VersionEditMeta {
+                space_id: data.table_meta.space_id,
+                table_id: data.table_meta.table_id,
+                flushed_sequence,
+                files_to_add,
+                files_to_delete: Vec::new(),
+                tombstones_to_add,
+                tombstones_to_delete: Vec::new(),
+            },
+            table_meta: data.table_meta,
+        }
+    }
+}
+
+impl TableSnapshot {
+    pub fn encode(self) -> Result<Vec<u8>> {
+        let mut target = meta_pb::TableSnapshot::new();
+        target.set_table_meta(self.table_meta.into_pb());
+        target.set_version_edit(self.version_edit.into_pb());
+
+        target.write_to_bytes().context(EncodePayloadPb)
+    }
+
+    pub fn decode(buf: &[u8]) -> Result<Self> {
+        let mut snapshot_pb =
+            meta_pb::TableSnapshot::parse_from_bytes(buf).context(DecodePayloadPb)?;
+        ensure!(
+            snapshot_pb.has_table_meta() && snapshot_pb.has_version_edit(),
+            EmptyTableSnapshot
+        );
+
+        Ok(Self {
+            table_meta: AddTableMeta::try_from(snapshot_pb.take_table_meta())?,
+            version_edit: VersionEditMeta::try_from(snapshot_pb.take_version_edit())?,
         })
     }
 }
@@ -5,6 +5,7 @@
 pub mod details;
 pub mod meta_data;
 pub mod meta_update;
+pub mod object_store_impl;
 
 use std::fmt;
 
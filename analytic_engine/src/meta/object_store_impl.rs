@@ -0,0 +1,689 @@
+// Copyright 2022 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Implementation of Manifest based on the object store
+//!
+//! The manifest of each table is stored under its own directory:
+//! ```text
+//! {root}/{table_id}/snapshot/{sequence}
+//! {root}/{table_id}/log/{sequence}
+//! {root}/{table_id}/epoch/{epoch}
+//! ```
+//! Every meta update is stored as a log object with an increasing sequence. A
+//! snapshot object holds the manifest data after applying all the logs whose
+//! sequences are not greater than the sequence of the snapshot, so the logs and
+//! snapshots covered by it are deleted once it is stored. The snapshot of a
+//! dropped table is empty.
+//!
+//! Only one node is allowed to update the manifest of a table at the same time.
+//! A writer claims a larger epoch than all the existing ones before its first
+//! write, and checks no larger epoch is claimed before every write, so a stale
+//! writer is fenced once the table is opened by another node. The object store
+//! provides no conditional put, so a write racing with the claim of a new
+//! epoch may still be stored.
+
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use common_types::SequenceNumber;
+use common_util::define_result;
+use futures::TryStreamExt;
+use log::{info, warn};
+use object_store::{ObjectStoreError, ObjectStoreRef, Path};
+use proto::meta_update as meta_pb;
+use protobuf::Message;
+use snafu::{ensure, Backtrace, ResultExt, Snafu};
+use table_engine::table::TableId;
+use tokio::sync::Mutex as AsyncMutex;
+
+use crate::meta::{
+    details::Options,
+    meta_data::{TableManifestData, TableManifestDataBuilder},
+    meta_update::{MetaUpdate, TableSnapshot},
+    Manifest,
+};
+
+/// Root directory of the manifest in the object store.
+const MANIFEST_ROOT: &str = "manifest";
+const SNAPSHOT_DIR: &str = "snapshot";
+const LOG_DIR: &str = "log";
+const EPOCH_DIR: &str = "epoch";
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Failed to list objects, path:{}, err:{}", path, source))]
+    ListObjects {
+        path: String,
+        source: ObjectStoreError,
+    },
+
+    #[snafu(display("Failed to read object, path:{}, err:{}", path, source))]
+    ReadObject {
+        path: String,
+        source: ObjectStoreError,
+    },
+
+    #[snafu(display("Failed to write object, path:{}, err:{}", path, source))]
+    WriteObject {
+        path: String,
+        source: ObjectStoreError,
+    },
+
+    #[snafu(display("Failed to delete object, path:{}, err:{}", path, source))]
+    DeleteObject {
+        path: String,
+        source: ObjectStoreError,
+    },
+
+    #[snafu(display("Failed to encode meta update, err:{}", source))]
+    EncodeMetaUpdate {
+        source: protobuf::error::ProtobufError,
+    },
+
+    #[snafu(display("Failed to decode meta update, path:{}, err:{}", path, source))]
+    DecodeMetaUpdatePb {
+        path: String,
+        source: protobuf::error::ProtobufError,
+    },
+
+    #[snafu(display("Failed to convert meta update, path:{}, err:{}", path, source))]
+    ConvertMetaUpdate {
+        path: String,
+        source: crate::meta::meta_update::Error,
+    },
+
+    #[snafu(display("Failed to encode snapshot, table_id:{}, err:{}", table_id, source))]
+    EncodeSnapshot {
+        table_id: TableId,
+        source: crate::meta::meta_update::Error,
+    },
+
+    #[snafu(display("Failed to decode snapshot, path:{}, err:{}", path, source))]
+    DecodeSnapshot {
+        path: String,
+        source: crate::meta::meta_update::Error,
+    },
+
+    #[snafu(display("Failed to apply table meta update, err:{}", source))]
+    ApplyUpdate {
+        source: crate::meta::meta_data::Error,
+    },
+
+    #[snafu(display(
+        "Manifest writer is fenced by a larger epoch, table_id:{}, epoch:{}, \
+         current_epoch:{}.\nBacktrace:\n{}",
+        table_id,
+        epoch,
+        current_epoch,
+        backtrace
+    ))]
+    Fenced {
+        table_id: TableId,
+        epoch: u64,
+        current_epoch: u64,
+        backtrace: Backtrace,
+    },
+}
+
+define_result!(Error);
+
+/// Manifest state of a table.
+#[derive(Debug, Default)]
+struct TableState {
+    /// Sequence of the last log, `None` if the state is not loaded from the
+    /// store yet.
+    last_sequence: Option<SequenceNumber>,
+    /// Number of logs stored since last snapshot.
+    num_updates_since_snapshot: usize,
+    /// Epoch claimed by this writer, `None` if not claimed yet.
+    epoch: Option<u64>,
+}
+
+type TableStateRef = Arc<AsyncMutex<TableState>>;
+
+/// Sequences of the objects of a table in the store.
+#[derive(Debug, Default)]
+struct TableObjects {
+    /// Sequences of the snapshots in ascending order.
+    snapshots: Vec<SequenceNumber>,
+    /// Sequences of the logs in ascending order.
+    logs: Vec<SequenceNumber>,
+}
+
+impl TableObjects {
+    fn last_sequence(&self) -> SequenceNumber {
+        let last_snapshot = self.snapshots.last().copied().unwrap_or(0);
+        let last_log = self.logs.last().copied().unwrap_or(0);
+        last_snapshot.max(last_log)
+    }
+}
+
+/// Manifest data of a table loaded from the store.
+#[derive(Debug)]
+struct LoadedManifest {
+    data: Option<TableManifestData>,
+    objects: TableObjects,
+}
+
+/// The implementation of Manifest based on the object store, which makes the
+/// tables recoverable from the shared storage without the wal.
+#[derive(Debug)]
+pub struct ObjectStoreManifest {
+    opts: Options,
+    store: ObjectStoreRef,
+    /// States of the tables, the async mutex of each table serializes the
+    /// updates and snapshots of the table.
+    tables: Mutex<HashMap<TableId, TableStateRef>>,
+}
+
+impl ObjectStoreManifest {
+    pub fn new(store: ObjectStoreRef, opts: Options) -> Self {
+        Self {
+            opts,
+            store,
+            tables: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn table_state(&self, table_id: TableId) -> TableStateRef {
+        let mut tables = self.tables.lock().unwrap();
+        tables.entry(table_id).or_default().clone()
+    }
+
+    async fn load_table(&self, table_id: TableId) -> Result<LoadedManifest> {
+        let objects = self.list_table_objects(table_id).await?;
+
+        let mut builder = TableManifestDataBuilder::default();
+        let mut snapshot_seq = 0;
+        if let Some(seq) = objects.snapshots.last() {
+            snapshot_seq = *seq;
+            let path = snapshot_path(table_id, snapshot_seq);
+            let buf = self.read_object(&path).await?;
+            // The snapshot of a dropped table is empty.
+            if !buf.is_empty() {
+                let snapshot = TableSnapshot::decode(&buf).context(DecodeSnapshot {
+                    path: path.to_string(),
+                })?;
+                builder
+                    .apply_update(MetaUpdate::AddTable(snapshot.table_meta))
+                    .context(ApplyUpdate)?;
+                builder
+                    .apply_update(MetaUpdate::VersionEdit(snapshot.version_edit))
+                    .context(ApplyUpdate)?;
+            }
+        }
+
+        for seq in objects.logs.iter().filter(|seq| **seq > snapshot_seq) {
+            let path = log_path(table_id, *seq);
+            let buf = self.read_object(&path).await?;
+            let update_pb =
+                meta_pb::MetaUpdate::parse_from_bytes(&buf).context(DecodeMetaUpdatePb {
+                    path: path.to_string(),
+                })?;
+            let update = MetaUpdate::try_from(update_pb).context(ConvertMetaUpdate {
+                path: path.to_string(),
+            })?;
+            builder.apply_update(update).context(ApplyUpdate)?;
+        }
+
+        Ok(LoadedManifest {
+            data: builder.build(),
+            objects,
+        })
+    }
+
+    /// Store the snapshot of the loaded manifest, then delete the logs and
+    /// snapshots covered by it.
+    async fn store_snapshot(&self, table_id: TableId, loaded: &LoadedManifest) -> Result<()> {
+        let snapshot_seq = loaded.objects.last_sequence();
+        let buf = match &loaded.data {
+            Some(data) => TableSnapshot::from(data.clone())
+                .encode()
+                .context(EncodeSnapshot { table_id })?,
+            None => Vec::new(),
+        };
+
+        info!(
+            "Object store manifest store snapshot, table_id:{}, snapshot_seq:{}",
+            table_id, snapshot_seq
+        );
+
+        let path = snapshot_path(table_id, snapshot_seq);
+        self.store
+            .put(&path, buf.into())
+            .await
+            .context(WriteObject {
+                path: path.to_string(),
+            })?;
+
+        let covered_logs = loaded
+            .objects
+            .logs
+            .iter()
+            .map(|seq| log_path(table_id, *seq));
+        let old_snapshots = loaded
+            .objects
+            .snapshots
+            .iter()
+            .filter(|seq| **seq < snapshot_seq)
+            .map(|seq| snapshot_path(table_id, *seq));
+        for path in covered_logs.chain(old_snapshots) {
+            self.store.delete(&path).await.context(DeleteObject {
+                path: path.to_string(),
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Claim a new epoch if the writer hasn't claimed one, otherwise check the
+    /// writer is not fenced by a larger epoch.
+    async fn claim_or_check_epoch(&self, table_id: TableId, state: &mut TableState) -> Result<()> {
+        let epochs = self.list_sequences(&table_dir(table_id, EPOCH_DIR)).await?;
+        let current_epoch = epochs.last().copied().unwrap_or(0);
+        if let Some(epoch) = state.epoch {
+            ensure!(
+                epoch >= current_epoch,
+                Fenced {
+                    table_id,
+                    epoch,
+                    current_epoch,
+                }
+            );
+            return Ok(());
+        }
+
+        let epoch = current_epoch + 1;
+        info!(
+            "Object store manifest claim epoch, table_id:{}, epoch:{}",
+            table_id, epoch
+        );
+
+        let path = epoch_path(table_id, epoch);
+        self.store
+            .put(&path, Vec::new().into())
+            .await
+            .context(WriteObject {
+                path: path.to_string(),
+            })?;
+        state.epoch = Some(epoch);
+
+        // Only the largest epoch is needed to fence the stale writers.
+        for old_epoch in epochs {
+            let path = epoch_path(table_id, old_epoch);
+            self.store.delete(&path).await.context(DeleteObject {
+                path: path.to_string(),
+            })?;
+        }
+
+        Ok(())
+    }
+
+    async fn list_table_objects(&self, table_id: TableId) -> Result<TableObjects> {
+        Ok(TableObjects {
+            snapshots: self
+                .list_sequences(&table_dir(table_id, SNAPSHOT_DIR))
+                .await?,
+            logs: self.list_sequences(&table_dir(table_id, LOG_DIR)).await?,
+        })
+    }
+
+    /// List the sequences of the objects under `dir` in ascending order.
+    async fn list_sequences(&self, dir: &str) -> Result<Vec<SequenceNumber>> {
+        let prefix = Path::from(dir);
+        let objects: Vec<_> = self
+            .store
+            .list(Some(&prefix))
+            .await
+            .context(ListObjects { path: dir })?
+            .try_collect()
+            .await
+            .context(ListObjects { path: dir })?;
+
+        let mut sequences = Vec::with_capacity(objects.len());
+        for object in objects {
+            let location = object.location.to_string();
+            let seq = location
+                .strip_prefix(dir)
+                .and_then(|name| name.strip_prefix('/'))
+                .and_then(|name| name.parse::<SequenceNumber>().ok());
+            match seq {
+                Some(seq) => sequences.push(seq),
+                None => warn!("Ignore unknown object in manifest, path:{}", location),
+            }
+        }
+        sequences.sort_unstable();
+
+        Ok(sequences)
+    }
+
+    async fn read_object(&self, path: &Path) -> Result<Vec<u8>> {
+        let get_result = self.store.get(path).await.context(ReadObject {
+            path: path.to_string(),
+        })?;
+        let bytes = get_result.bytes().await.context(ReadObject {
+            path: path.to_string(),
+        })?;
+
+        Ok(bytes.to_vec())
+    }
+}
+
+#[async_trait]
+impl Manifest for ObjectStoreManifest {
+    type Error = Error;
+
+    async fn store_update(&self, update: MetaUpdate) -> Result<()> {
+        info!("Object store manifest store update, update:{:?}", update);
+
+        let table_id = update.table_id();
+        let state = self.table_state(table_id);
+        let mut state = state.lock().await;
+        self.claim_or_check_epoch(table_id, &mut state).await?;
+
+        let last_sequence = match state.last_sequence {
+            Some(seq) => seq,
+            None => self.list_table_objects(table_id).await?.last_sequence(),
+        };
+        let sequence = last_sequence + 1;
+        let path = log_path(table_id, sequence);
+        let buf = update
+            .into_pb()
+            .write_to_bytes()
+            .context(EncodeMetaUpdate)?;
+        self.store
+            .put(&path, buf.into())
+            .await
+            .context(WriteObject {
+                path: path.to_string(),
+            })?;
+
+        state.last_sequence = Some(sequence);
+        state.num_updates_since_snapshot += 1;
+        if state.num_updates_since_snapshot < self.opts.snapshot_every_n_updates {
+            return Ok(());
+        }
+
+        let loaded = self.load_table(table_id).await?;
+        self.claim_or_check_epoch(table_id, &mut state).await?;
+        self.store_snapshot(table_id, &loaded).await?;
+        state.num_updates_since_snapshot = 0;
+
+        Ok(())
+    }
+
+    async fn load_data(
+        &self,
+        table_id: TableId,
+        do_snapshot: bool,
+    ) -> Result<Option<TableManifestData>> {
+        let state = self.table_state(table_id);
+        let mut state = state.lock().await;
+        // The writer loading the manifest takes over the table, and fences the
+        // previous writers.
+        self.claim_or_check_epoch(table_id, &mut state).await?;
+
+        let loaded = self.load_table(table_id).await?;
+        state.last_sequence = Some(loaded.objects.last_sequence());
+        if do_snapshot && !loaded.objects.logs.is_empty() {
+            self.store_snapshot(table_id, &loaded).await?;
+            state.num_updates_since_snapshot = 0;
+        }
+
+        Ok(loaded.data)
+    }
+}
+
+fn table_dir(table_id: TableId, sub_dir: &str) -> String {
+    format!("{}/{}/{}", MANIFEST_ROOT, table_id.as_u64(), sub_dir)
+}
+
+fn snapshot_path(table_id: TableId, sequence: SequenceNumber) -> Path {
+    Path::from(format!(
+        "{}/{:020}",
+        table_dir(table_id, SNAPSHOT_DIR),
+        sequence
+    ))
+}
+
+fn log_path(table_id: TableId, sequence: SequenceNumber) -> Path {
+    Path::from(format!("{}/{:020}", table_dir(table_id, LOG_DIR), sequence))
+}
+
+fn epoch_path(table_id: TableId, epoch: u64) -> Path {
+    Path::from(format!("{}/{:020}", table_dir(table_id, EPOCH_DIR), epoch))
+}
+
+#[cfg(test)]
+mod tests {
+    use object_store::LocalFileSystem;
+    use table_engine::table::{SchemaId, TableSeqGenerator};
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::{
+        meta::meta_update::{AddTableMeta, AlterOptionsMeta, DropTableMeta, VersionEditMeta},
+        TableOptions,
+    };
+
+    struct TestContext {
+        _dir: TempDir,
+        store: ObjectStoreRef,
+        schema_id: SchemaId,
+        table_seq_gen: TableSeqGenerator,
+    }
+
+    impl TestContext {
+        fn new() -> Self {
+            let dir = tempfile::tempdir().unwrap();
+            let store = Arc::new(LocalFileSystem::new_with_prefix(dir.path()).unwrap());
+
+            Self {
+                _dir: dir,
+                store,
+                schema_id: SchemaId::new(0).unwrap(),
+                table_seq_gen: TableSeqGenerator::default(),
+            }
+        }
+
+        fn open_manifest(&self, snapshot_every_n_updates: usize) -> ObjectStoreManifest {
+            let opts = Options {
+                snapshot_every_n_updates,
+            };
+            ObjectStoreManifest::new(self.store.clone(), opts)
+        }
+
+        fn alloc_table_id(&self) -> TableId {
+            TableId::new(
+                self.schema_id,
+                self.table_seq_gen.alloc_table_seq().unwrap(),
+            )
+        }
+
+        fn add_table(&self, table_id: TableId) -> MetaUpdate {
+            MetaUpdate::AddTable(AddTableMeta {
+                space_id: self.schema_id.as_u32(),
+                table_id,
+                table_name: format!("table_{}", table_id),
+                schema: common_types::tests::build_schema(),
+                opts: TableOptions::default(),
+                partition_info: None,
+            })
+        }
+
+        fn version_edit(&self, table_id: TableId, flushed_sequence: SequenceNumber) -> MetaUpdate {
+            MetaUpdate::VersionEdit(VersionEditMeta {
+                space_id: self.schema_id.as_u32(),
+                table_id,
+                flushed_sequence,
+                files_to_add: Vec::new(),
+                files_to_delete: Vec::new(),
+                tombstones_to_add: Vec::new(),
+                tombstones_to_delete: Vec::new(),
+            })
+        }
+
+        fn alter_options(&self, table_id: TableId) -> MetaUpdate {
+            MetaUpdate::AlterOptions(AlterOptionsMeta {
+                space_id: self.schema_id.as_u32(),
+                table_id,
+                options: TableOptions {
+                    enable_ttl: false,
+                    ..Default::default()
+                },
+            })
+        }
+
+        fn drop_table(&self, table_id: TableId) -> MetaUpdate {
+            MetaUpdate::DropTable(DropTableMeta {
+                space_id: self.schema_id.as_u32(),
+                table_id,
+                table_name: format!("table_{}", table_id),
+            })
+        }
+
+        async fn store_updates(
+            &self,
+            manifest: &ObjectStoreManifest,
+            updates: Vec<MetaUpdate>,
+            builder: &mut TableManifestDataBuilder,
+        ) {
+            for update in updates {
+                manifest.store_update(update.clone()).await.unwrap();
+                builder.apply_update(update).unwrap();
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_store_and_load() {
+        let ctx = TestContext::new();
+        let table_id = ctx.alloc_table_id();
+        let mut builder = TableManifestDataBuilder::default();
+
+        let manifest = ctx.open_manifest(100);
+        assert!(manifest.load_data(table_id, false).await.unwrap().is_none());
+
+        let updates = vec![
+            ctx.add_table(table_id),
+            ctx.version_edit(table_id, 10),
+            ctx.alter_options(table_id),
+            ctx.version_edit(table_id, 20),
+        ];
+        ctx.store_updates(&manifest, updates, &mut builder).await;
+        let expect = builder.clone().build();
+        assert!(expect.is_some());
+        assert_eq!(expect, manifest.load_data(table_id, false).await.unwrap());
+
+        // Reopen the manifest and load from the store.
+        let manifest = ctx.open_manifest(100);
+        assert_eq!(expect, manifest.load_data(table_id, false).await.unwrap());
+
+        // Logs are appended after the logs stored before reopen.
+        let updates = vec![ctx.version_edit(table_id, 30)];
+        ctx.store_updates(&manifest, updates, &mut builder).await;
+        let manifest = ctx.open_manifest(100);
+        assert_eq!(
+            builder.build(),
+            manifest.load_data(table_id, false).await.unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_snapshot() {
+        let ctx = TestContext::new();
+        let table_id = ctx.alloc_table_id();
+        let mut builder = TableManifestDataBuilder::default();
+
+        let manifest = ctx.open_manifest(3);
+        let mut updates = vec![ctx.add_table(table_id)];
+        for seq in 1..8 {
+            updates.push(ctx.version_edit(table_id, seq));
+        }
+        ctx.store_updates(&manifest, updates, &mut builder).await;
+
+        // Snapshots are stored after every 3 updates, so 2 logs left.
+        let objects = manifest.list_table_objects(table_id).await.unwrap();
+        assert_eq!(vec![6], objects.snapshots);
+        assert_eq!(vec![7, 8], objects.logs);
+
+        let expect = builder.clone().build();
+        let manifest = ctx.open_manifest(3);
+        assert_eq!(expect, manifest.load_data(table_id, true).await.unwrap());
+        let objects = manifest.list_table_objects(table_id).await.unwrap();
+        assert_eq!(vec![8], objects.snapshots);
+        assert!(objects.logs.is_empty());
+
+        // Sequence continues from the snapshot.
+        let updates = vec![ctx.version_edit(table_id, 9)];
+        ctx.store_updates(&manifest, updates, &mut builder).await;
+        let objects = manifest.list_table_objects(table_id).await.unwrap();
+        assert_eq!(vec![9], objects.logs);
+        assert_eq!(
+            builder.build(),
+            manifest.load_data(table_id, false).await.unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_drop_table() {
+        let ctx = TestContext::new();
+        let table_id = ctx.alloc_table_id();
+        let other_table_id = ctx.alloc_table_id();
+        let mut builder = TableManifestDataBuilder::default();
+        let mut other_builder = TableManifestDataBuilder::default();
+
+        let manifest = ctx.open_manifest(100);
+        let updates = vec![ctx.add_table(table_id), ctx.version_edit(table_id, 10)];
+        ctx.store_updates(&manifest, updates, &mut builder).await;
+        let updates = vec![ctx.add_table(other_table_id)];
+        ctx.store_updates(&manifest, updates, &mut other_builder)
+            .await;
+
+        let updates = vec![ctx.drop_table(table_id)];
+        ctx.store_updates(&manifest, updates, &mut builder).await;
+        assert!(manifest.load_data(table_id, true).await.unwrap().is_none());
+
+        let manifest = ctx.open_manifest(100);
+        assert!(manifest.load_data(table_id, false).await.unwrap().is_none());
+        assert_eq!(
+            other_builder.build(),
+            manifest.load_data(other_table_id, false).await.unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fence_stale_writer() {
+        let ctx = TestContext::new();
+        let table_id = ctx.alloc_table_id();
+        let mut builder = TableManifestDataBuilder::default();
+
+        let stale_manifest = ctx.open_manifest(2);
+        let updates = vec![ctx.add_table(table_id), ctx.version_edit(table_id, 10)];
+        ctx.store_updates(&stale_manifest, updates, &mut builder)
+            .await;
+
+        // Another writer takes over the table.
+        let manifest = ctx.open_manifest(2);
+        let expect = builder.clone().build();
+        assert_eq!(expect, manifest.load_data(table_id, false).await.unwrap());
+
+        let res = stale_manifest
+            .store_update(ctx.version_edit(table_id, 20))
+            .await;
+        assert!(matches!(res, Err(Error::Fenced { .. })));
+        assert!(stale_manifest.load_data(table_id, true).await.is_err());
+        assert_eq!(expect, manifest.load_data(table_id, false).await.unwrap());
+
+        let updates = vec![ctx.version_edit(table_id, 30)];
+        ctx.store_updates(&manifest, updates, &mut builder).await;
+        let manifest = ctx.open_manifest(2);
+        assert_eq!(
+            builder.build(),
+            manifest.load_data(table_id, false).await.unwrap()
+        );
+    }
+}
@@ -60,6 +60,7 @@ define_result!(Error);
 #[derive(Clone, Copy)]
 enum Header {
     Write = 1,
+    Delete = 2,
 }
 
 impl Header {
@@ -70,6 +71,7 @@ impl Header {
     pub fn from_u8(value: u8) -> Option<Self> {
         match value {
             value if value == Self::Write as u8 => Some(Self::Write),
+            value if value == Self::Delete as u8 => Some(Self::Delete),
             _ => None,
         }
     }
@@ -87,6 +89,7 @@ const HEADER_SIZE: usize = 1;
 #[derive(Debug)]
 pub enum WritePayload<'a> {
     Write(&'a table_requests::WriteRequest),
+    Delete(&'a table_requests::DeleteRequest),
 }
 
 impl<'a> Payload for WritePayload<'a> {
@@ -95,6 +98,7 @@ impl<'a> Payload for WritePayload<'a> {
     fn encode_size(&self) -> usize {
         let body_size = match self {
             WritePayload::Write(req) => req.compute_size(),
+            WritePayload::Delete(req) => req.compute_size(),
         };
 
         HEADER_SIZE + body_size as usize
@@ -107,6 +111,11 @@ impl<'a> Payload for WritePayload<'a> {
                 let mut writer = Writer::new(buf);
                 req.write_to_writer(&mut writer).context(EncodeBody)?;
             }
+            WritePayload::Delete(req) => {
+                write_header(Header::Delete, buf)?;
+                let mut writer = Writer::new(buf);
+                req.write_to_writer(&mut writer).context(EncodeBody)?;
+            }
         }
 
         Ok(())
@@ -116,7 +125,12 @@ impl<'a> Payload for WritePayload<'a> {
 /// Payload decoded from wal
 #[derive(Debug)]
 pub enum ReadPayload {
-    Write { row_group: RowGroup },
+    Write {
+        row_group: RowGroup,
+    },
+    Delete {
+        request: table_requests::DeleteRequest,
+    },
 }
 
 /// Wal payload decoder
@@ -167,6 +181,12 @@ impl PayloadDecoder for WalDecoder {
 
                 ReadPayload::Write { row_group }
             }
+            Header::Delete => {
+                let request: table_requests::DeleteRequest =
+                    Message::parse_from_bytes(buf.remaining_slice()).context(DecodeBody)?;
+
+                ReadPayload::Delete { request }
+            }
         };
 
         Ok(payload)
@@ -5,7 +5,7 @@ use std::{fmt, time::Instant};
 use async_trait::async_trait;
 use common_types::{
     projected_schema::ProjectedSchema, record_batch::RecordBatchWithKey, request_id::RequestId,
-    schema::RecordSchemaWithKey,
+    schema::RecordSchemaWithKey, time::Timestamp,
 };
 use common_util::define_result;
 use futures::StreamExt;
@@ -24,7 +24,10 @@ use crate::{
         factory::{Factory, SstReaderOptions},
         file::FileHandle,
     },
-    table::version::{MemTableVec, SamplingMemTable},
+    table::{
+        tombstone::Tombstone,
+        version::{MemTableVec, SamplingMemTable},
+    },
 };
 
 #[derive(Debug, Snafu)]
@@ -72,6 +75,9 @@ pub struct Builder<'a, S, Fa> {
     sampling_mem: Option<SamplingMemTable>,
     memtables: MemTableVec,
     ssts: Vec<Vec<FileHandle>>,
+    tombstones: Vec<Tombstone>,
+    /// Rows older than the expire time are filtered out.
+    expire_time: Option<Timestamp>,
 }
 
 impl<'a, S, Fa> Builder<'a, S, Fa> {
@@ -81,6 +87,8 @@ impl<'a, S, Fa> Builder<'a, S, Fa> {
             sampling_mem: None,
             memtables: Vec::new(),
             ssts: Vec::new(),
+            tombstones: Vec::new(),
+            expire_time: None,
         }
     }
 
@@ -98,6 +106,16 @@ impl<'a, S, Fa> Builder<'a, S, Fa> {
         self.ssts = ssts;
         self
     }
+
+    pub fn tombstones(mut self, tombstones: Vec<Tombstone>) -> Self {
+        self.tombstones = tombstones;
+        self
+    }
+
+    pub fn expire_time(mut self, expire_time: Option<Timestamp>) -> Self {
+        self.expire_time = expire_time;
+        self
+    }
 }
 
 impl<'a, S: ObjectStore, Fa: Factory> Builder<'a, S, Fa> {
@@ -116,8 +134,14 @@ impl<'a, S: ObjectStore, Fa: Factory> Builder<'a, S, Fa> {
                 &v.mem,
                 false,
                 self.config.predicate.as_ref(),
+                &self.tombstones,
             )
             .context(BuildStreamFromMemtable)?;
+            let stream = record_batch_stream::filter_stream_by_expire_time(
+                stream,
+                &self.config.projected_schema,
+                self.expire_time,
+            );
             streams.push(stream);
         }
 
@@ -130,8 +154,14 @@ impl<'a, S: ObjectStore, Fa: Factory> Builder<'a, S, Fa> {
                 &memtable.mem,
                 false,
                 self.config.predicate.as_ref(),
+                &self.tombstones,
             )
             .context(BuildStreamFromMemtable)?;
+            let stream = record_batch_stream::filter_stream_by_expire_time(
+                stream,
+                &self.config.projected_schema,
+                self.expire_time,
+            );
             streams.push(stream);
         }
 
@@ -147,6 +177,17 @@ impl<'a, S: ObjectStore, Fa: Factory> Builder<'a, S, Fa> {
                 )
                 .await
                 .context(BuildStreamFromSst)?;
+                let stream = record_batch_stream::filter_stream_by_tombstones(
+                    stream,
+                    &self.config.projected_schema,
+                    sst.max_sequence(),
+                    &self.tombstones,
+                );
+                let stream = record_batch_stream::filter_stream_by_expire_time(
+                    stream,
+                    &self.config.projected_schema,
+                    self.expire_time,
+                );
                 streams.push(stream);
             }
         }
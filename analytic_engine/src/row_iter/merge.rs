@@ -16,6 +16,7 @@ use common_types::{
     request_id::RequestId,
     row::RowViewOnBatch,
     schema::RecordSchemaWithKey,
+    time::Timestamp,
     SequenceNumber,
 };
 use common_util::define_result;
@@ -37,7 +38,10 @@ use crate::{
         file::FileHandle,
         manager::{FileId, MAX_LEVEL},
     },
-    table::version::{MemTableVec, SamplingMemTable},
+    table::{
+        tombstone::Tombstone,
+        version::{MemTableVec, SamplingMemTable},
+    },
 };
 
 #[derive(Debug, Snafu)]
@@ -117,6 +121,10 @@ pub struct MergeBuilder<'a, S, Fa> {
     memtables: MemTableVec,
     /// Ssts to read of each level.
     ssts: Vec<Vec<FileHandle>>,
+    /// Tombstones to apply.
+    tombstones: Vec<Tombstone>,
+    /// Rows older than the expire time are filtered out.
+    expire_time: Option<Timestamp>,
 }
 
 impl<'a, S: ObjectStore, Fa: Factory> MergeBuilder<'a, S, Fa> {
@@ -126,6 +134,8 @@ impl<'a, S: ObjectStore, Fa: Factory> MergeBuilder<'a, S, Fa> {
             sampling_mem: None,
             memtables: Vec::new(),
             ssts: vec![Vec::new(); MAX_LEVEL],
+            tombstones: Vec::new(),
+            expire_time: None,
         }
     }
 
@@ -144,6 +154,16 @@ impl<'a, S: ObjectStore, Fa: Factory> MergeBuilder<'a, S, Fa> {
         self
     }
 
+    pub fn tombstones(mut self, tombstones: Vec<Tombstone>) -> Self {
+        self.tombstones = tombstones;
+        self
+    }
+
+    pub fn expire_time(mut self, expire_time: Option<Timestamp>) -> Self {
+        self.expire_time = expire_time;
+        self
+    }
+
     pub fn mut_memtables(&mut self) -> &mut MemTableVec {
         &mut self.memtables
     }
@@ -181,8 +201,14 @@ impl<'a, S: ObjectStore, Fa: Factory> MergeBuilder<'a, S, Fa> {
                 &v.mem,
                 self.config.reverse,
                 self.config.predicate.as_ref(),
+                &self.tombstones,
             )
             .context(BuildStreamFromMemtable)?;
+            let stream = record_batch_stream::filter_stream_by_expire_time(
+                stream,
+                &self.config.projected_schema,
+                self.expire_time,
+            );
             streams.push(stream);
         }
 
@@ -193,8 +219,14 @@ impl<'a, S: ObjectStore, Fa: Factory> MergeBuilder<'a, S, Fa> {
                 &memtable.mem,
                 self.config.reverse,
                 self.config.predicate.as_ref(),
+                &self.tombstones,
             )
             .context(BuildStreamFromMemtable)?;
+            let stream = record_batch_stream::filter_stream_by_expire_time(
+                stream,
+                &self.config.projected_schema,
+                self.expire_time,
+            );
             streams.push(stream);
         }
 
@@ -211,6 +243,17 @@ impl<'a, S: ObjectStore, Fa: Factory> MergeBuilder<'a, S, Fa> {
                 )
                 .await
                 .context(BuildStreamFromSst)?;
+                let stream = record_batch_stream::filter_stream_by_tombstones(
+                    stream,
+                    &self.config.projected_schema,
+                    f.max_sequence(),
+                    &self.tombstones,
+                );
+                let stream = record_batch_stream::filter_stream_by_expire_time(
+                    stream,
+                    &self.config.projected_schema,
+                    self.expire_time,
+                );
                 streams.push(stream);
                 sst_ids.push(f.id());
             }
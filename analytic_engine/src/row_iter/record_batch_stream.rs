@@ -3,7 +3,8 @@
 use std::ops::Bound;
 
 use common_types::{
-    projected_schema::ProjectedSchema, record_batch::RecordBatchWithKey, SequenceNumber,
+    projected_schema::ProjectedSchema, record_batch::RecordBatchWithKey, time::Timestamp,
+    SequenceNumber,
 };
 use common_util::define_result;
 use futures::stream::{self, Stream, StreamExt};
@@ -20,7 +21,10 @@ use crate::{
     space::SpaceId,
     sst,
     sst::{factory::SstReaderOptions, file::FileHandle},
-    table::sst_util,
+    table::{
+        sst_util,
+        tombstone::{Tombstone, TombstoneFilter},
+    },
 };
 
 #[derive(Debug, Snafu)]
@@ -154,6 +158,113 @@ pub fn filter_stream(
     Box::new(stream)
 }
 
+/// Filter out rows deleted by the `tombstones` from the sst stream whose max
+/// sequence is `sequence`. Only tombstones written after `sequence` are
+/// applied, the others are applied when the sst is flushed or compacted.
+pub fn filter_stream_by_tombstones(
+    origin_stream: SequencedRecordBatchStream,
+    projected_schema: &ProjectedSchema,
+    sequence: SequenceNumber,
+    tombstones: &[Tombstone],
+) -> SequencedRecordBatchStream {
+    let tombstones: Vec<_> = tombstones
+        .iter()
+        .filter(|v| v.applies_to(sequence))
+        .cloned()
+        .collect();
+    let mut filter = match TombstoneFilter::new(
+        tombstones,
+        &projected_schema.to_record_schema_with_key(),
+        projected_schema.table_schema().timestamp_name(),
+    ) {
+        Some(v) => v,
+        None => return origin_stream,
+    };
+
+    let mut selected_rows_buf = Vec::new();
+    let stream = origin_stream.filter_map(move |sequenced_record_batch| {
+        let v = match sequenced_record_batch {
+            Ok(mut v) => {
+                selected_rows_buf.resize(v.num_rows(), true);
+                let num_selected_rows =
+                    filter.filter(&v.record_batch, selected_rows_buf.as_mut_slice());
+                if num_selected_rows == 0 {
+                    None
+                } else if num_selected_rows == v.num_rows() {
+                    Some(Ok(v))
+                } else {
+                    // Deleted rows must be removed, so the error is returned.
+                    match v.record_batch.select_data(selected_rows_buf.as_slice()) {
+                        Ok(()) => Some(Ok(v)),
+                        Err(e) => Some(Err(Box::new(e) as _)),
+                    }
+                }
+            }
+            Err(e) => Some(Err(e)),
+        };
+
+        futures::future::ready(v)
+    });
+
+    Box::new(stream)
+}
+
+/// Filter out rows whose timestamp is older than the `expire_time` from the
+/// stream, so the expired rows of a partially expired memtable or sst are never
+/// returned.
+pub fn filter_stream_by_expire_time(
+    origin_stream: SequencedRecordBatchStream,
+    projected_schema: &ProjectedSchema,
+    expire_time: Option<Timestamp>,
+) -> SequencedRecordBatchStream {
+    let expire_time = match expire_time {
+        Some(v) => v,
+        None => return origin_stream,
+    };
+    // Timestamp column is always a key column.
+    let timestamp_index = match projected_schema
+        .to_record_schema_with_key()
+        .index_of(projected_schema.table_schema().timestamp_name())
+    {
+        Some(v) => v,
+        None => return origin_stream,
+    };
+
+    let mut selected_rows_buf = Vec::new();
+    let stream = origin_stream.filter_map(move |sequenced_record_batch| {
+        let v = match sequenced_record_batch {
+            Ok(mut v) => {
+                let timestamp_column = v.record_batch.column(timestamp_index);
+                selected_rows_buf.clear();
+                selected_rows_buf.extend((0..v.num_rows()).map(|row_idx| {
+                    timestamp_column
+                        .datum(row_idx)
+                        .as_timestamp()
+                        .map(|ts| !ts.is_expired(expire_time))
+                        .unwrap_or(true)
+                }));
+                let num_selected_rows = selected_rows_buf.iter().filter(|v| **v).count();
+                if num_selected_rows == 0 {
+                    None
+                } else if num_selected_rows == v.num_rows() {
+                    Some(Ok(v))
+                } else {
+                    // Expired rows must be removed, so the error is returned.
+                    match v.record_batch.select_data(selected_rows_buf.as_slice()) {
+                        Ok(()) => Some(Ok(v)),
+                        Err(e) => Some(Err(Box::new(e) as _)),
+                    }
+                }
+            }
+            Err(e) => Some(Err(e)),
+        };
+
+        futures::future::ready(v)
+    });
+
+    Box::new(stream)
+}
+
 /// Build filtered (by `predicate`) [SequencedRecordBatchStream] from a
 /// memtable.
 pub fn filtered_stream_from_memtable(
@@ -162,17 +273,20 @@ pub fn filtered_stream_from_memtable(
     memtable: &MemTableRef,
     reverse: bool,
     predicate: &Predicate,
+    tombstones: &[Tombstone],
 ) -> Result<SequencedRecordBatchStream> {
-    stream_from_memtable(projected_schema, need_dedup, memtable, reverse)
+    stream_from_memtable(projected_schema, need_dedup, memtable, reverse, tombstones)
         .map(|origin_stream| filter_stream(origin_stream, predicate))
 }
 
-/// Build [SequencedRecordBatchStream] from a memtable.
+/// Build [SequencedRecordBatchStream] from a memtable, rows deleted by the
+/// `tombstones` are removed by the memtable iterator.
 pub fn stream_from_memtable(
     projected_schema: ProjectedSchema,
     need_dedup: bool,
     memtable: &MemTableRef,
     reverse: bool,
+    tombstones: &[Tombstone],
 ) -> Result<SequencedRecordBatchStream> {
     let scan_ctx = ScanContext::default();
     let max_seq = memtable.last_sequence();
@@ -183,6 +297,7 @@ pub fn stream_from_memtable(
         projected_schema,
         need_dedup,
         reverse,
+        tombstones: tombstones.to_vec(),
     };
 
     let iter = memtable.scan(scan_ctx, scan_req).context(ScanMemtable)?;
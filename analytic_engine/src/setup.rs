@@ -5,7 +5,10 @@
 use std::{path::Path, sync::Arc};
 
 use common_util::{define_result, runtime::Runtime};
-use object_store::{aliyun::AliyunOSS, LocalFileSystem, ObjectStore};
+use object_store::{
+    aliyun::AliyunOSS, disk_cache::DiskCacheStore, s3::S3, LocalFileSystem, ObjectStore,
+    ObjectStoreRef,
+};
 use parquet::{
     cache::{LruDataCache, LruMetaCache},
     DataCacheRef, MetaCacheRef,
@@ -13,17 +16,21 @@ use parquet::{
 use snafu::{ResultExt, Snafu};
 use table_engine::engine::{EngineRuntimes, TableEngineRef};
 use wal::{
+    file_impl::manager::Builder as FileWalBuilder,
     manager::{self, WalManager},
-    rocks_impl::manager::Builder as WalBuilder,
+    rocks_impl::manager::Builder as RocksWalBuilder,
 };
 
 use crate::{
     context::OpenContext,
     engine::TableEngineImpl,
     instance::{Instance, InstanceRef},
-    meta::{details::ManifestImpl, Manifest},
+    meta::{details::ManifestImpl, object_store_impl::ObjectStoreManifest, Manifest},
     sst::factory::{Factory, FactoryImpl},
-    storage_options::{AliyunOptions, LocalOptions},
+    storage_options::{
+        AliyunOptions, FileWalOptions, LocalOptions, ManifestStorageOptions, S3Options,
+        StorageOptions, WalStorageOptions,
+    },
     Config,
 };
 
@@ -66,29 +73,113 @@ pub async fn open_analytic_table_engine(
     config: Config,
     engine_runtimes: Arc<EngineRuntimes>,
 ) -> Result<TableEngineRef> {
-    let wal = open_wal(
-        config.clone(),
-        engine_runtimes.write_runtime.clone(),
-        WAL_DIR_NAME,
-    )
-    .await?;
-    let manifest_wal = open_wal(
-        config.clone(),
-        engine_runtimes.write_runtime.clone(),
-        MANIFEST_DIR_NAME,
-    )
-    .await?;
-    let manifest = open_manifest(config.clone(), manifest_wal).await?;
+    let runtime = engine_runtimes.write_runtime.clone();
+    match config.wal_storage.clone() {
+        WalStorageOptions::RocksDB => {
+            let wal = open_rocks_wal(&config, runtime.clone(), WAL_DIR_NAME)?;
+            let open_manifest_wal = || open_rocks_wal(&config, runtime, MANIFEST_DIR_NAME);
+            open_engine_with_wal(config.clone(), wal, open_manifest_wal, engine_runtimes).await
+        }
+        WalStorageOptions::File(ref opts) => {
+            let wal = open_file_wal(&config, opts, runtime.clone(), WAL_DIR_NAME)?;
+            let open_manifest_wal = || open_file_wal(&config, opts, runtime, MANIFEST_DIR_NAME);
+            open_engine_with_wal(config.clone(), wal, open_manifest_wal, engine_runtimes).await
+        }
+    }
+}
+
+/// Open an object store outside the engine, such as the store to backup the
+/// tables.
+pub async fn open_object_store(opts: StorageOptions) -> Result<ObjectStoreRef> {
+    let store: ObjectStoreRef = match opts {
+        StorageOptions::Local(opts) => Arc::new(open_storage_local(opts).await?),
+        StorageOptions::Aliyun(opts) => Arc::new(open_storage_aliyun(opts).await?),
+        StorageOptions::S3(opts) => Arc::new(open_storage_s3(opts).await?),
+    };
+
+    Ok(store)
+}
 
+/// Open the engine with the manifest specified in the config, the wal of the
+/// manifest is opened by `open_manifest_wal` only if it is required.
+async fn open_engine_with_wal<Wal, MetaWal, F>(
+    config: Config,
+    wal: Wal,
+    open_manifest_wal: F,
+    engine_runtimes: Arc<EngineRuntimes>,
+) -> Result<TableEngineRef>
+where
+    Wal: WalManager + Send + Sync + 'static,
+    MetaWal: WalManager + Send + Sync + 'static,
+    F: FnOnce() -> Result<MetaWal>,
+{
+    match config.manifest_storage {
+        ManifestStorageOptions::Wal => {
+            let manifest = open_manifest(config.clone(), open_manifest_wal()?).await?;
+            open_engine_with_manifest(config, wal, manifest, engine_runtimes).await
+        }
+        ManifestStorageOptions::ObjectStore => {
+            // Store the manifest along with the sst files.
+            let store = open_object_store(config.storage.clone()).await?;
+            let manifest = ObjectStoreManifest::new(store, config.manifest.clone());
+            open_engine_with_manifest(config, wal, manifest, engine_runtimes).await
+        }
+    }
+}
+
+async fn open_engine_with_manifest<Wal, M>(
+    config: Config,
+    wal: Wal,
+    manifest: M,
+    engine_runtimes: Arc<EngineRuntimes>,
+) -> Result<TableEngineRef>
+where
+    Wal: WalManager + Send + Sync + 'static,
+    M: Manifest + Send + Sync + 'static,
+{
     match config.storage {
         crate::storage_options::StorageOptions::Local(ref opts) => {
             let storage = open_storage_local(opts.clone()).await?;
+            open_engine_with_storage(config, wal, manifest, storage, engine_runtimes).await
+        }
+        crate::storage_options::StorageOptions::Aliyun(ref opts) => {
+            let storage = open_storage_aliyun(opts.clone()).await?;
+            open_engine_with_storage(config, wal, manifest, storage, engine_runtimes).await
+        }
+        crate::storage_options::StorageOptions::S3(ref opts) => {
+            let storage = open_storage_s3(opts.clone()).await?;
+            open_engine_with_storage(config, wal, manifest, storage, engine_runtimes).await
+        }
+    }
+}
+
+async fn open_engine_with_storage<Wal, M, Store>(
+    config: Config,
+    wal: Wal,
+    manifest: M,
+    storage: Store,
+    engine_runtimes: Arc<EngineRuntimes>,
+) -> Result<TableEngineRef>
+where
+    Wal: WalManager + Send + Sync + 'static,
+    M: Manifest + Send + Sync + 'static,
+    Store: ObjectStore,
+{
+    match config.sst_disk_cache.clone() {
+        Some(opts) => {
+            let storage = DiskCacheStore::open(
+                opts.path,
+                opts.capacity.as_bytes() as usize,
+                opts.page_size.as_bytes() as usize,
+                storage,
+            )
+            .await
+            .context(OpenObjectStore)?;
             let instance =
                 open_instance(config, wal, manifest, storage, FactoryImpl, engine_runtimes).await?;
             Ok(Arc::new(TableEngineImpl::new(instance)))
         }
-        crate::storage_options::StorageOptions::Aliyun(ref opts) => {
-            let storage = open_storage_aliyun(opts.clone()).await?;
+        None => {
             let instance =
                 open_instance(config, wal, manifest, storage, FactoryImpl, engine_runtimes).await?;
             Ok(Arc::new(TableEngineImpl::new(instance)))
@@ -138,14 +229,30 @@ where
     Ok(instance)
 }
 
-async fn open_wal(
-    config: Config,
+fn open_rocks_wal(
+    config: &Config,
     runtime: Arc<Runtime>,
     sub_path: &str,
 ) -> Result<impl WalManager + Send + Sync + 'static> {
     let data_path = Path::new(&config.wal_path);
     let wal_path = data_path.join(sub_path);
-    WalBuilder::with_default_rocksdb_config(wal_path, runtime)
+    RocksWalBuilder::with_default_rocksdb_config(wal_path, runtime)
+        .build()
+        .context(OpenWal)
+}
+
+fn open_file_wal(
+    config: &Config,
+    opts: &FileWalOptions,
+    runtime: Arc<Runtime>,
+    sub_path: &str,
+) -> Result<impl WalManager + Send + Sync + 'static> {
+    let data_path = Path::new(&config.wal_path);
+    let wal_path = data_path.join(sub_path);
+    FileWalBuilder::new(wal_path, runtime)
+        .segment_size(opts.segment_size.as_bytes() as usize)
+        .sync_on_write(opts.sync_on_write)
+        .sync_interval(opts.sync_interval.0)
         .build()
         .context(OpenWal)
 }
@@ -181,3 +288,21 @@ async fn open_storage_aliyun(opts: AliyunOptions) -> Result<impl ObjectStore> {
         opts.bucket,
     ))
 }
+
+async fn open_storage_s3(opts: S3Options) -> Result<impl ObjectStore> {
+    let s3 = S3::try_new(
+        opts.endpoint,
+        opts.bucket,
+        opts.region,
+        opts.key_id,
+        opts.key_secret,
+    )
+    .context(OpenObjectStore)?
+    .with_session_token(opts.session_token)
+    .with_virtual_hosted_style(opts.virtual_hosted_style)
+    .with_timeouts(opts.connect_timeout.0, opts.read_timeout.0)
+    .context(OpenObjectStore)?
+    .with_max_retries(opts.max_retries);
+
+    Ok(s3)
+}
@@ -6,7 +6,7 @@ use async_trait::async_trait;
 use common_types::{record_batch::RecordBatchWithKey, request_id::RequestId};
 use futures::Stream;
 
-use crate::sst::file::SstMetaData;
+use crate::sst::{file::SstMetaData, index::InvertedIndex};
 
 pub mod error {
     use common_util::define_result;
@@ -60,6 +60,8 @@ pub type RecordBatchStream = Box<dyn Stream<Item = RecordBatchStreamItem> + Send
 pub struct SstInfo {
     pub file_size: usize,
     pub row_num: usize,
+    /// Inverted index of the tag columns built from the rows of the sst.
+    pub inverted_index: Option<InvertedIndex>,
 }
 
 /// The builder for sst.
@@ -8,6 +8,7 @@ use common_types::projected_schema::ProjectedSchema;
 use common_util::runtime::Runtime;
 use object_store::{ObjectStore, Path};
 use parquet::{DataCacheRef, MetaCacheRef};
+use serde_derive::Deserialize;
 use table_engine::predicate::PredicateRef;
 
 use crate::{
@@ -15,6 +16,7 @@ use crate::{
         builder::SstBuilder,
         parquet::{builder::ParquetSstBuilder, reader::ParquetSstReader},
         reader::SstReader,
+        time_series::{builder::TimeSeriesSstBuilder, reader::TimeSeriesSstReader},
     },
     table_options::Compression,
 };
@@ -35,9 +37,11 @@ pub trait Factory: Clone {
     ) -> Option<Box<dyn SstBuilder + Send + 'a>>;
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
 pub enum SstType {
     Parquet,
+    /// Rows are grouped by series and encoded by delta-of-delta and gorilla.
+    TimeSeries,
 }
 
 #[derive(Debug, Clone)]
@@ -71,6 +75,7 @@ impl Factory for FactoryImpl {
     ) -> Option<Box<dyn SstReader + Send + 'a>> {
         match options.sst_type {
             SstType::Parquet => Some(Box::new(ParquetSstReader::new(path, storage, options))),
+            SstType::TimeSeries => Some(Box::new(TimeSeriesSstReader::new(path, storage, options))),
         }
     }
 
@@ -82,6 +87,9 @@ impl Factory for FactoryImpl {
     ) -> Option<Box<dyn SstBuilder + Send + 'a>> {
         match options.sst_type {
             SstType::Parquet => Some(Box::new(ParquetSstBuilder::new(path, storage, options))),
+            SstType::TimeSeries => {
+                Some(Box::new(TimeSeriesSstBuilder::new(path, storage, options)))
+            }
         }
     }
 }
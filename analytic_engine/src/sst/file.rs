@@ -31,13 +31,17 @@ use log::{debug, error, info};
 use object_store::ObjectStore;
 use proto::{common::TimeRange as TimeRangePb, sst::SstMetaData as SstMetaDataPb};
 use snafu::{ResultExt, Snafu};
-use table_engine::table::TableId;
+use table_engine::{predicate::Predicate, table::TableId};
 use tokio::sync::{
     mpsc::{self, UnboundedReceiver, UnboundedSender},
     Mutex,
 };
 
-use crate::{space::SpaceId, sst::manager::FileId, table::sst_util};
+use crate::{
+    space::SpaceId,
+    sst::{filter::SstFilter, index::InvertedIndex, manager::FileId},
+    table::sst_util,
+};
 
 /// Error of sst file.
 #[derive(Debug, Snafu)]
@@ -83,11 +87,7 @@ impl LevelHandler {
     }
 
     pub fn pick_ssts(&self, time_range: TimeRange) -> Vec<FileHandle> {
-        if self.level == 0 {
-            self.files.files_by_time_range(time_range)
-        } else {
-            Vec::new()
-        }
+        self.files.files_by_time_range(time_range)
     }
 
     #[inline]
@@ -206,6 +206,18 @@ impl FileHandle {
         self.inner.meta.meta.max_sequence
     }
 
+    /// Returns false if the inverted index of the sst tells that no series
+    /// matches the `predicate`, so the sst can be skipped.
+    pub fn may_match(&self, predicate: &Predicate) -> bool {
+        let meta = &self.inner.meta.meta;
+        match &meta.inverted_index {
+            Some(index) => index
+                .match_tsids(&meta.schema, &predicate.exprs)
+                .map_or(true, |tsids| !tsids.is_empty()),
+            None => true,
+        }
+    }
+
     #[inline]
     pub fn being_compacted(&self) -> bool {
         self.inner.being_compacted.load(Ordering::Relaxed)
@@ -420,6 +432,10 @@ pub struct SstMetaData {
     pub size: u64,
     // total row number
     pub row_num: u64,
+    /// Filters of the row groups, only set by the sst builder
+    pub sst_filter: Option<SstFilter>,
+    /// Inverted index of the tag columns, only set by the sst builder
+    pub inverted_index: Option<InvertedIndex>,
 }
 
 impl From<SstMetaData> for SstMetaDataPb {
@@ -433,6 +449,12 @@ impl From<SstMetaData> for SstMetaDataPb {
         target.set_schema(src.schema.into());
         target.set_size(src.size);
         target.set_row_num(src.row_num);
+        if let Some(sst_filter) = src.sst_filter {
+            target.set_sst_filter(sst_filter.into());
+        }
+        if let Some(inverted_index) = src.inverted_index {
+            target.set_inverted_index(inverted_index.into());
+        }
 
         target
     }
@@ -444,6 +466,16 @@ impl TryFrom<SstMetaDataPb> for SstMetaData {
     fn try_from(mut src: SstMetaDataPb) -> Result<Self> {
         let time_range = TimeRange::try_from(src.take_time_range()).context(ConvertTimeRange)?;
         let schema = Schema::try_from(src.take_schema()).context(ConvertTableSchema)?;
+        let sst_filter = if src.has_sst_filter() {
+            Some(SstFilter::from(src.take_sst_filter()))
+        } else {
+            None
+        };
+        let inverted_index = if src.has_inverted_index() {
+            Some(InvertedIndex::from(src.take_inverted_index()))
+        } else {
+            None
+        };
         Ok(Self {
             min_key: src.min_key.into(),
             max_key: src.max_key.into(),
@@ -452,6 +484,8 @@ impl TryFrom<SstMetaDataPb> for SstMetaData {
             schema,
             size: src.size,
             row_num: src.row_num,
+            sst_filter,
+            inverted_index,
         })
     }
 }
@@ -636,6 +670,8 @@ pub fn merge_sst_meta(files: &[FileHandle], schema: Schema) -> SstMetaData {
         // we don't know file size and total row number yet
         size: 0,
         row_num: 0,
+        sst_filter: None,
+        inverted_index: None,
     }
 }
 
@@ -691,6 +727,8 @@ pub mod tests {
                 schema: self.schema.clone(),
                 size: 0,
                 row_num: 0,
+                sst_filter: None,
+                inverted_index: None,
             }
         }
     }
@@ -0,0 +1,492 @@
+// Copyright 2022 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Filters of the row groups in sst
+//!
+//! The min/max statistics of a row group are useless to prune row groups for
+//! high cardinality tag columns, so a bloom filter is built for every tag
+//! column of each row group, which helps to skip row groups for the `=` and
+//! `IN` predicates. The tsid column is also filtered, so the row groups can be
+//! pruned by the tsids matched by the inverted index of the sst.
+
+use std::{collections::HashSet, ops::Range};
+
+use arrow_deps::datafusion::logical_plan::Expr;
+use common_types::{
+    datum::DatumView,
+    hash::hash64,
+    record_batch::RecordBatchWithKey,
+    schema::{Schema, TSID_COLUMN},
+};
+use proto::sst as sst_pb;
+use table_engine::predicate::extract_column_literals;
+
+/// Bits for each key, the false positive rate is about 1% with
+/// [NUM_HASHES] hash functions.
+const BITS_PER_KEY: usize = 10;
+const NUM_HASHES: u32 = 7;
+const MIN_NUM_BYTES: usize = 8;
+
+/// A bloom filter using double hashing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BloomFilter {
+    num_hashes: u32,
+    bits: Vec<u8>,
+}
+
+impl BloomFilter {
+    /// Create a filter able to hold about `num_keys` keys.
+    pub fn with_num_keys(num_keys: usize) -> Self {
+        let num_bytes = ((num_keys * BITS_PER_KEY + 7) / 8).max(MIN_NUM_BYTES);
+
+        Self {
+            num_hashes: NUM_HASHES,
+            bits: vec![0; num_bytes],
+        }
+    }
+
+    pub fn insert(&mut self, key: &[u8]) {
+        self.insert_hash(hash64(key));
+    }
+
+    /// Insert the key by its hash computed by [hash64].
+    fn insert_hash(&mut self, hash: u64) {
+        let num_bits = self.num_bits();
+        for i in 0..self.num_hashes {
+            let pos = Self::bit_position(hash, i, num_bits);
+            self.bits[pos / 8] |= 1 << (pos % 8);
+        }
+    }
+
+    /// Returns false if the `key` is definitely not in the filter.
+    pub fn contains(&self, key: &[u8]) -> bool {
+        let num_bits = self.num_bits();
+        if num_bits == 0 {
+            return true;
+        }
+
+        let hash = hash64(key);
+        (0..self.num_hashes).all(|i| {
+            let pos = Self::bit_position(hash, i, num_bits);
+            self.bits[pos / 8] & (1 << (pos % 8)) != 0
+        })
+    }
+
+    /// Insert the datum, null is ignored.
+    pub fn insert_datum(&mut self, datum: &DatumView) {
+        with_datum_bytes(datum, |key| self.insert(key));
+    }
+
+    /// Returns false if the `datum` is definitely not in the filter.
+    pub fn contains_datum(&self, datum: &DatumView) -> bool {
+        with_datum_bytes(datum, |key| self.contains(key)).unwrap_or(false)
+    }
+
+    #[inline]
+    fn num_bits(&self) -> usize {
+        self.bits.len() * 8
+    }
+
+    #[inline]
+    fn bit_position(hash: u64, i: u32, num_bits: usize) -> usize {
+        let h1 = hash & 0xffff_ffff;
+        let h2 = hash >> 32;
+        (h1.wrapping_add(u64::from(i).wrapping_mul(h2)) % num_bits as u64) as usize
+    }
+}
+
+/// Call `f` with the bytes of the non-null `datum`.
+pub(crate) fn with_datum_bytes<R>(datum: &DatumView, f: impl FnOnce(&[u8]) -> R) -> Option<R> {
+    let v = match datum {
+        DatumView::Null => return None,
+        DatumView::Varbinary(v) => f(v),
+        DatumView::String(v) => f(v.as_bytes()),
+        DatumView::Timestamp(v) => f(&v.as_i64().to_le_bytes()),
+        DatumView::Double(v) => f(&v.to_bits().to_le_bytes()),
+        DatumView::Float(v) => f(&v.to_bits().to_le_bytes()),
+        DatumView::UInt64(v) => f(&v.to_le_bytes()),
+        DatumView::UInt32(v) => f(&v.to_le_bytes()),
+        DatumView::UInt16(v) => f(&v.to_le_bytes()),
+        DatumView::UInt8(v) => f(&v.to_le_bytes()),
+        DatumView::Int64(v) => f(&v.to_le_bytes()),
+        DatumView::Int32(v) => f(&v.to_le_bytes()),
+        DatumView::Int16(v) => f(&v.to_le_bytes()),
+        DatumView::Int8(v) => f(&v.to_le_bytes()),
+        DatumView::Boolean(v) => f(&[*v as u8]),
+    };
+
+    Some(v)
+}
+
+/// Filters of the columns in a row group.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowGroupFilter {
+    /// Filters indexed by the column index in the schema, `None` if the column
+    /// has no filter.
+    column_filters: Vec<Option<BloomFilter>>,
+}
+
+impl RowGroupFilter {
+    pub fn new(column_filters: Vec<Option<BloomFilter>>) -> Self {
+        Self { column_filters }
+    }
+
+    pub fn column_filter(&self, column_idx: usize) -> Option<&BloomFilter> {
+        self.column_filters.get(column_idx).and_then(|v| v.as_ref())
+    }
+}
+
+/// Builder to build the [RowGroupFilter] of tag columns and the tsid column.
+///
+/// The hashes of the distinct values are collected first, so every filter is
+/// sized by the number of distinct values of the column in the row group
+/// instead of the number of rows.
+pub struct RowGroupFilterBuilder {
+    /// Hashes of the distinct values indexed by the column index in the
+    /// schema, `None` if the column has no filter.
+    column_hashes: Vec<Option<HashSet<u64>>>,
+}
+
+impl RowGroupFilterBuilder {
+    pub fn new(schema: &Schema) -> Self {
+        let column_hashes = schema
+            .columns()
+            .iter()
+            .map(|column| {
+                if column.is_tag || column.name == TSID_COLUMN {
+                    Some(HashSet::new())
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        Self { column_hashes }
+    }
+
+    /// Add the `rows` of the `record_batch` to the filters.
+    pub fn add_rows(
+        &mut self,
+        schema: &Schema,
+        record_batch: &RecordBatchWithKey,
+        rows: Range<usize>,
+    ) {
+        for (column_idx, hashes) in self.column_hashes.iter_mut().enumerate() {
+            let hashes = match hashes {
+                Some(v) => v,
+                None => continue,
+            };
+
+            // The rows are all null if the column is absent, nothing to add.
+            let column_name = &schema.column(column_idx).name;
+            if let Some(idx) = record_batch.schema_with_key().index_of(column_name) {
+                let column = record_batch.column(idx);
+                for row_idx in rows.clone() {
+                    with_datum_bytes(&column.datum_view(row_idx), |key| {
+                        hashes.insert(hash64(key));
+                    });
+                }
+            }
+        }
+    }
+
+    pub fn build(self) -> RowGroupFilter {
+        let column_filters = self
+            .column_hashes
+            .into_iter()
+            .map(|hashes| {
+                hashes.map(|hashes| {
+                    let mut filter = BloomFilter::with_num_keys(hashes.len());
+                    for hash in hashes {
+                        filter.insert_hash(hash);
+                    }
+                    filter
+                })
+            })
+            .collect();
+
+        RowGroupFilter::new(column_filters)
+    }
+}
+
+/// Filters of all the row groups in a sst.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SstFilter {
+    row_group_filters: Vec<RowGroupFilter>,
+}
+
+impl SstFilter {
+    pub fn new(row_group_filters: Vec<RowGroupFilter>) -> Self {
+        Self { row_group_filters }
+    }
+
+    /// Returns true if any column of the `schema` needs filter.
+    pub fn need_filter(schema: &Schema) -> bool {
+        schema.columns().iter().any(|column| column.is_tag)
+    }
+
+    #[inline]
+    pub fn row_group_filters(&self) -> &[RowGroupFilter] {
+        &self.row_group_filters
+    }
+
+    /// Mark the row groups which can't match the `exprs` as false in
+    /// `results`, the logical relationship of the `exprs` is `AND`.
+    ///
+    /// Only the `=` and `IN` predicates on columns with filter are used, and
+    /// nothing is done if the length of `results` mismatches the number of
+    /// row groups.
+    pub fn filter_row_groups(&self, schema: &Schema, exprs: &[Expr], results: &mut [bool]) {
+        if results.len() != self.row_group_filters.len() {
+            return;
+        }
+
+        for expr in exprs {
+            let (column_name, literals) = match extract_column_literals(expr) {
+                Some(v) => v,
+                None => continue,
+            };
+            let column_idx = match schema.index_of(column_name) {
+                Some(v) => v,
+                None => continue,
+            };
+            let kind = schema.column(column_idx).data_type;
+
+            for (row_group_filter, result) in self.row_group_filters.iter().zip(results.iter_mut())
+            {
+                if !*result {
+                    continue;
+                }
+
+                if let Some(filter) = row_group_filter.column_filter(column_idx) {
+                    *result = literals.iter().any(|literal| {
+                        match DatumView::from_scalar_value(literal) {
+                            Some(datum) if datum.kind() == kind => filter.contains_datum(&datum),
+                            // Keep the row group if the literal can't be checked.
+                            _ => true,
+                        }
+                    });
+                }
+            }
+        }
+    }
+}
+
+impl From<BloomFilter> for sst_pb::BloomFilter {
+    fn from(src: BloomFilter) -> Self {
+        let mut target = sst_pb::BloomFilter::default();
+        target.set_num_hashes(src.num_hashes);
+        target.set_bits(src.bits);
+
+        target
+    }
+}
+
+impl From<sst_pb::BloomFilter> for BloomFilter {
+    fn from(mut src: sst_pb::BloomFilter) -> Self {
+        Self {
+            num_hashes: src.num_hashes,
+            bits: src.take_bits(),
+        }
+    }
+}
+
+impl From<SstFilter> for sst_pb::SstFilter {
+    fn from(src: SstFilter) -> Self {
+        let row_group_filters = src
+            .row_group_filters
+            .into_iter()
+            .map(|row_group_filter| {
+                let column_filters = row_group_filter
+                    .column_filters
+                    .into_iter()
+                    // Empty filter means the column has no filter.
+                    .map(|v| v.map(sst_pb::BloomFilter::from).unwrap_or_default())
+                    .collect();
+                let mut target = sst_pb::RowGroupFilter::default();
+                target.set_column_filters(column_filters);
+                target
+            })
+            .collect();
+
+        let mut target = sst_pb::SstFilter::default();
+        target.set_row_group_filters(row_group_filters);
+
+        target
+    }
+}
+
+impl From<sst_pb::SstFilter> for SstFilter {
+    fn from(mut src: sst_pb::SstFilter) -> Self {
+        let row_group_filters = src
+            .take_row_group_filters()
+            .into_iter()
+            .map(|mut row_group_filter| {
+                let column_filters = row_group_filter
+                    .take_column_filters()
+                    .into_iter()
+                    .map(|v| {
+                        if v.bits.is_empty() {
+                            None
+                        } else {
+                            Some(BloomFilter::from(v))
+                        }
+                    })
+                    .collect();
+                RowGroupFilter::new(column_filters)
+            })
+            .collect();
+
+        Self { row_group_filters }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_deps::datafusion::logical_plan::{col, lit};
+    use common_types::{
+        column_schema,
+        datum::{Datum, DatumKind},
+        row::Row,
+        schema,
+        string::StringBytes,
+        time::Timestamp,
+    };
+
+    use super::*;
+    use crate::row_iter::tests::build_record_batch_with_key;
+
+    fn build_schema_with_tag() -> Schema {
+        schema::Builder::new()
+            .auto_increment_column_id(true)
+            .add_key_column(
+                column_schema::Builder::new("key".to_string(), DatumKind::Timestamp)
+                    .build()
+                    .unwrap(),
+            )
+            .unwrap()
+            .add_normal_column(
+                column_schema::Builder::new("host".to_string(), DatumKind::String)
+                    .is_tag(true)
+                    .build()
+                    .unwrap(),
+            )
+            .unwrap()
+            .add_normal_column(
+                column_schema::Builder::new("value".to_string(), DatumKind::Double)
+                    .build()
+                    .unwrap(),
+            )
+            .unwrap()
+            .build()
+            .unwrap()
+    }
+
+    fn build_row_group_filter(schema: &Schema, hosts: &[&str]) -> RowGroupFilter {
+        let column_filters = schema
+            .columns()
+            .iter()
+            .map(|column| {
+                if column.is_tag {
+                    let mut filter = BloomFilter::with_num_keys(hosts.len());
+                    for host in hosts {
+                        filter.insert_datum(&DatumView::String(host));
+                    }
+                    Some(filter)
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        RowGroupFilter::new(column_filters)
+    }
+
+    #[test]
+    fn test_bloom_filter() {
+        let mut filter = BloomFilter::with_num_keys(100);
+        for i in 0..100 {
+            filter.insert(format!("key{}", i).as_bytes());
+        }
+
+        for i in 0..100 {
+            assert!(filter.contains(format!("key{}", i).as_bytes()));
+        }
+        let num_false_positive = (100..1100)
+            .filter(|i| filter.contains(format!("key{}", i).as_bytes()))
+            .count();
+        assert!(num_false_positive < 50);
+
+        assert!(!filter.contains_datum(&DatumView::Null));
+    }
+
+    #[test]
+    fn test_sst_filter_row_groups() {
+        let schema = build_schema_with_tag();
+        let sst_filter = SstFilter::new(vec![
+            build_row_group_filter(&schema, &["host1", "host2"]),
+            build_row_group_filter(&schema, &["host3"]),
+        ]);
+
+        let check = |exprs: &[Expr], expect: Vec<bool>| {
+            let mut results = vec![true; 2];
+            sst_filter.filter_row_groups(&schema, exprs, &mut results);
+            assert_eq!(expect, results);
+        };
+
+        check(&[col("host").eq(lit("host1"))], vec![true, false]);
+        check(&[lit("host3").eq(col("host"))], vec![false, true]);
+        check(&[col("host").eq(lit("host4"))], vec![false, false]);
+        check(
+            &[col("host").in_list(vec![lit("host2"), lit("host3")], false)],
+            vec![true, true],
+        );
+        check(
+            &[col("host").in_list(vec![lit("host1")], true)],
+            vec![true, true],
+        );
+        // Column without filter.
+        check(&[col("value").eq(lit(1.0))], vec![true, true]);
+        // Literal of different type.
+        check(&[col("host").eq(lit(1))], vec![true, true]);
+    }
+
+    #[test]
+    fn test_row_group_filter_builder() {
+        let schema = build_schema_with_tag();
+        let rows = (0..100)
+            .map(|i| {
+                let host = if i % 2 == 0 { "host1" } else { "host2" };
+                Row::from_datums(vec![
+                    Datum::Timestamp(Timestamp::new(i)),
+                    Datum::String(StringBytes::from(host)),
+                    Datum::Double(1.0),
+                ])
+            })
+            .collect();
+        let record_batch = build_record_batch_with_key(schema.clone(), rows);
+
+        let mut builder = RowGroupFilterBuilder::new(&schema);
+        builder.add_rows(&schema, &record_batch, 0..50);
+        builder.add_rows(&schema, &record_batch, 50..100);
+        let row_group_filter = builder.build();
+
+        // The filter is sized by the 2 distinct hosts instead of the 100 rows.
+        let filter = row_group_filter.column_filter(1).unwrap();
+        assert_eq!(BloomFilter::with_num_keys(2).bits.len(), filter.bits.len());
+        assert!(filter.contains_datum(&DatumView::String("host1")));
+        assert!(filter.contains_datum(&DatumView::String("host2")));
+        assert!(row_group_filter.column_filter(2).is_none());
+    }
+
+    #[test]
+    fn test_sst_filter_pb_conversion() {
+        let schema = build_schema_with_tag();
+        let sst_filter = SstFilter::new(vec![
+            build_row_group_filter(&schema, &["host1", "host2"]),
+            build_row_group_filter(&schema, &[]),
+        ]);
+
+        let pb = sst_pb::SstFilter::from(sst_filter.clone());
+        assert_eq!(sst_filter, SstFilter::from(pb));
+    }
+}
@@ -0,0 +1,403 @@
+// Copyright 2022 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Inverted index of the tag columns in sst
+//!
+//! The index maps every value of the tag columns to the tsids of the series
+//! having that value. It is built from the rows written into the sst, i.e. the
+//! rows of the memtables when flushing and the merged rows when compacting,
+//! and tells the exact series matched by the `=` and `IN` predicates on tag
+//! columns. So the sst can be skipped if no series is matched, and the row
+//! groups can be pruned by the filters of the tsid column.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use arrow_deps::datafusion::logical_plan::{col, lit, Expr};
+use common_types::{
+    datum::DatumView,
+    record_batch::RecordBatchWithKey,
+    schema::{Schema, TSID_COLUMN},
+};
+use proto::sst as sst_pb;
+use table_engine::predicate::extract_column_literals;
+
+use crate::sst::filter::with_datum_bytes;
+
+/// Inverted index from the values of the tag columns to the tsids.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct InvertedIndex {
+    /// Tag column name -> tag value -> sorted tsids of the series.
+    columns: BTreeMap<String, BTreeMap<Vec<u8>, Vec<u64>>>,
+}
+
+impl InvertedIndex {
+    /// Returns true if the `schema` has the tsid column and any tag column.
+    pub fn need_index(schema: &Schema) -> bool {
+        schema.tsid_column().is_some() && schema.columns().iter().any(|column| column.is_tag)
+    }
+
+    /// Returns the sorted tsids of the series whose `column_name` is `value`.
+    pub fn tsids(&self, column_name: &str, value: &DatumView) -> &[u64] {
+        self.columns
+            .get(column_name)
+            .and_then(|values| with_datum_bytes(value, |key| values.get(key)).flatten())
+            .map(|tsids| tsids.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Returns the sorted tsids of the series matching all the `exprs`, the
+    /// logical relationship of the `exprs` is `AND`.
+    ///
+    /// Only the `=` and `IN` predicates on the indexed tag columns are used,
+    /// and `None` is returned if no expr can be answered by the index.
+    pub fn match_tsids(&self, schema: &Schema, exprs: &[Expr]) -> Option<Vec<u64>> {
+        let mut matched: Option<Vec<u64>> = None;
+        for expr in exprs {
+            let (column_name, literals) = match extract_column_literals(expr) {
+                Some(v) => v,
+                None => continue,
+            };
+            let values = match self.columns.get(column_name) {
+                Some(v) => v,
+                None => continue,
+            };
+            let kind = match schema.column_with_name(column_name) {
+                Some(v) => v.data_type,
+                None => continue,
+            };
+
+            let mut tsids = Some(Vec::new());
+            for literal in literals {
+                match DatumView::from_scalar_value(literal) {
+                    Some(datum) if datum.kind() == kind => {
+                        if let Some(Some(v)) = with_datum_bytes(&datum, |key| values.get(key)) {
+                            tsids = tsids.map(|tsids| union_sorted(&tsids, v));
+                        }
+                    }
+                    // The expr can't be answered if any literal can't be checked.
+                    _ => tsids = None,
+                }
+            }
+
+            if let Some(tsids) = tsids {
+                matched = Some(match matched {
+                    Some(v) => intersect_sorted(&v, &tsids),
+                    None => tsids,
+                });
+            }
+        }
+
+        matched
+    }
+}
+
+/// Build the `tsid IN (tsids)` expr, which helps to prune the row groups by
+/// the filters of the tsid column.
+pub fn tsid_in_list_expr(tsids: &[u64]) -> Expr {
+    col(TSID_COLUMN).in_list(tsids.iter().map(|v| lit(*v)).collect(), false)
+}
+
+fn union_sorted(a: &[u64], b: &[u64]) -> Vec<u64> {
+    let mut result = Vec::with_capacity(a.len() + b.len());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] < b[j] {
+            result.push(a[i]);
+            i += 1;
+        } else if a[i] > b[j] {
+            result.push(b[j]);
+            j += 1;
+        } else {
+            result.push(a[i]);
+            i += 1;
+            j += 1;
+        }
+    }
+    result.extend_from_slice(&a[i..]);
+    result.extend_from_slice(&b[j..]);
+
+    result
+}
+
+fn intersect_sorted(a: &[u64], b: &[u64]) -> Vec<u64> {
+    let mut result = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] < b[j] {
+            i += 1;
+        } else if a[i] > b[j] {
+            j += 1;
+        } else {
+            result.push(a[i]);
+            i += 1;
+            j += 1;
+        }
+    }
+
+    result
+}
+
+/// Builder to build the [InvertedIndex] from the rows written into the sst.
+pub struct InvertedIndexBuilder {
+    /// Tag column name -> tag value -> tsids of the series.
+    columns: BTreeMap<String, BTreeMap<Vec<u8>, BTreeSet<u64>>>,
+}
+
+impl InvertedIndexBuilder {
+    /// Create a builder indexing all the tag columns of the `schema`.
+    pub fn new(schema: &Schema) -> Self {
+        let columns = schema
+            .columns()
+            .iter()
+            .filter(|column| column.is_tag)
+            .map(|column| (column.name.clone(), BTreeMap::new()))
+            .collect();
+
+        Self { columns }
+    }
+
+    /// Add all the rows of the `record_batch` to the index.
+    pub fn add_record_batch(&mut self, record_batch: &RecordBatchWithKey) {
+        let schema_with_key = record_batch.schema_with_key();
+        let tsid_column = match schema_with_key.index_of(TSID_COLUMN) {
+            Some(idx) => record_batch.column(idx),
+            None => return,
+        };
+
+        for (column_name, values) in &mut self.columns {
+            // The rows are all null if the column is absent, nothing to add.
+            let column = match schema_with_key.index_of(column_name) {
+                Some(idx) => record_batch.column(idx),
+                None => continue,
+            };
+            for row_idx in 0..record_batch.num_rows() {
+                let tsid = match tsid_column.datum_view(row_idx) {
+                    DatumView::UInt64(v) => v,
+                    _ => continue,
+                };
+                with_datum_bytes(&column.datum_view(row_idx), |value| {
+                    match values.get_mut(value) {
+                        Some(tsids) => {
+                            tsids.insert(tsid);
+                        }
+                        None => {
+                            values.insert(value.to_vec(), BTreeSet::from([tsid]));
+                        }
+                    }
+                });
+            }
+        }
+    }
+
+    pub fn build(self) -> InvertedIndex {
+        let columns = self
+            .columns
+            .into_iter()
+            .map(|(column_name, values)| {
+                let values = values
+                    .into_iter()
+                    .map(|(value, tsids)| (value, tsids.into_iter().collect()))
+                    .collect();
+                (column_name, values)
+            })
+            .collect();
+
+        InvertedIndex { columns }
+    }
+}
+
+impl From<InvertedIndex> for sst_pb::InvertedIndex {
+    fn from(src: InvertedIndex) -> Self {
+        let columns = src
+            .columns
+            .into_iter()
+            .map(|(column_name, values)| {
+                let values = values
+                    .into_iter()
+                    .map(|(value, tsids)| {
+                        let mut target = sst_pb::TagValueTsids::default();
+                        target.set_value(value);
+                        target.set_tsids(tsids);
+                        target
+                    })
+                    .collect();
+                let mut target = sst_pb::ColumnIndex::default();
+                target.set_name(column_name);
+                target.set_values(values);
+                target
+            })
+            .collect();
+
+        let mut target = sst_pb::InvertedIndex::default();
+        target.set_columns(columns);
+
+        target
+    }
+}
+
+impl From<sst_pb::InvertedIndex> for InvertedIndex {
+    fn from(mut src: sst_pb::InvertedIndex) -> Self {
+        let columns = src
+            .take_columns()
+            .into_iter()
+            .map(|mut column| {
+                let values = column
+                    .take_values()
+                    .into_iter()
+                    .map(|mut v| (v.take_value(), v.take_tsids()))
+                    .collect();
+                (column.take_name(), values)
+            })
+            .collect();
+
+        Self { columns }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use common_types::{
+        column_schema,
+        datum::{Datum, DatumKind},
+        row::Row,
+        schema,
+        string::StringBytes,
+        time::Timestamp,
+    };
+
+    use super::*;
+    use crate::row_iter::tests::build_record_batch_with_key;
+
+    /// Build a schema with primary key (ts, tsid), tags host and region.
+    pub(crate) fn build_tsid_schema() -> Schema {
+        let mut builder = schema::Builder::new()
+            .auto_increment_column_id(true)
+            .enable_tsid_primary_key(true);
+        for (name, kind) in [
+            ("ts", DatumKind::Timestamp),
+            (TSID_COLUMN, DatumKind::UInt64),
+        ] {
+            let column_schema = column_schema::Builder::new(name.to_string(), kind)
+                .is_nullable(false)
+                .build()
+                .unwrap();
+            builder = builder.add_key_column(column_schema).unwrap();
+        }
+        for tag in ["host", "region"] {
+            let column_schema = column_schema::Builder::new(tag.to_string(), DatumKind::String)
+                .is_tag(true)
+                .build()
+                .unwrap();
+            builder = builder.add_normal_column(column_schema).unwrap();
+        }
+        builder
+            .add_normal_column(
+                column_schema::Builder::new("value".to_string(), DatumKind::Double)
+                    .build()
+                    .unwrap(),
+            )
+            .unwrap()
+            .build()
+            .unwrap()
+    }
+
+    pub(crate) fn build_tsid_row(ts: i64, tsid: u64, host: &str, region: Option<&str>) -> Row {
+        Row::from_datums(vec![
+            Datum::Timestamp(Timestamp::new(ts)),
+            Datum::UInt64(tsid),
+            Datum::String(StringBytes::from(host)),
+            region
+                .map(|v| Datum::String(StringBytes::from(v)))
+                .unwrap_or(Datum::Null),
+            Datum::Double(1.0),
+        ])
+    }
+
+    fn build_index(schema: &Schema, rows: Vec<Row>) -> InvertedIndex {
+        let mut builder = InvertedIndexBuilder::new(schema);
+        builder.add_record_batch(&build_record_batch_with_key(schema.clone(), rows));
+        builder.build()
+    }
+
+    #[test]
+    fn test_build_inverted_index() {
+        let schema = build_tsid_schema();
+        assert!(InvertedIndex::need_index(&schema));
+
+        let index = build_index(
+            &schema,
+            vec![
+                build_tsid_row(1, 3, "host1", Some("east")),
+                build_tsid_row(1, 1, "host2", Some("west")),
+                build_tsid_row(2, 3, "host1", Some("east")),
+                build_tsid_row(1, 2, "host1", None),
+            ],
+        );
+
+        let tsids = |column_name, value| index.tsids(column_name, &DatumView::String(value));
+        assert_eq!(&[2, 3], tsids("host", "host1"));
+        assert_eq!(&[1], tsids("host", "host2"));
+        assert_eq!(&[3], tsids("region", "east"));
+        assert!(tsids("host", "host3").is_empty());
+        assert!(tsids("value", "host1").is_empty());
+    }
+
+    #[test]
+    fn test_inverted_index_match_tsids() {
+        let schema = build_tsid_schema();
+        let index = build_index(
+            &schema,
+            vec![
+                build_tsid_row(1, 1, "host1", Some("east")),
+                build_tsid_row(1, 2, "host2", Some("east")),
+                build_tsid_row(1, 3, "host3", Some("west")),
+            ],
+        );
+
+        let check = |exprs: &[Expr], expect: Option<Vec<u64>>| {
+            assert_eq!(expect, index.match_tsids(&schema, exprs));
+        };
+
+        check(&[col("host").eq(lit("host1"))], Some(vec![1]));
+        check(&[lit("east").eq(col("region"))], Some(vec![1, 2]));
+        check(
+            &[col("host").in_list(vec![lit("host3"), lit("host1"), lit("host4")], false)],
+            Some(vec![1, 3]),
+        );
+        check(
+            &[
+                col("region").eq(lit("east")),
+                col("host").in_list(vec![lit("host2"), lit("host3")], false),
+            ],
+            Some(vec![2]),
+        );
+        check(
+            &[col("region").eq(lit("west")), col("host").eq(lit("host1"))],
+            Some(vec![]),
+        );
+        check(&[col("host").eq(lit("host4"))], Some(vec![]));
+        // Exprs can't be answered by the index.
+        check(&[], None);
+        check(&[col("host").in_list(vec![lit("host1")], true)], None);
+        check(&[col("value").eq(lit(1.0))], None);
+        check(&[col("host").eq(lit(1))], None);
+        check(
+            &[col("value").eq(lit(1.0)), col("host").eq(lit("host2"))],
+            Some(vec![2]),
+        );
+    }
+
+    #[test]
+    fn test_inverted_index_pb_conversion() {
+        let schema = build_tsid_schema();
+        let index = build_index(
+            &schema,
+            vec![
+                build_tsid_row(1, 1, "host1", Some("east")),
+                build_tsid_row(1, 2, "host2", None),
+            ],
+        );
+
+        let pb = sst_pb::InvertedIndex::from(index.clone());
+        assert_eq!(index, InvertedIndex::from(pb));
+    }
+}
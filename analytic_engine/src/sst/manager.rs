@@ -11,8 +11,8 @@ use crate::{
 
 /// Id for a sst file
 pub type FileId = u64;
-/// We use two level merge tree, the max level should less than u16::MAX
-pub const MAX_LEVEL: usize = 2;
+/// Number of levels of the merge tree, the max level should less than u16::MAX
+pub const MAX_LEVEL: usize = 4;
 
 /// A table level manager that manages all the sst files of the table
 pub struct LevelsController {
@@ -124,19 +124,24 @@ pub mod tests {
     use tokio::sync::mpsc;
 
     use crate::sst::{
-        file::{FileMeta, FilePurgeQueue, SstMetaData},
+        file::{FileMeta, FilePurgeQueue, Level, SstMetaData},
         manager::{FileId, LevelsController},
     };
 
     #[must_use]
     #[derive(Default)]
     pub struct LevelsControllerMockBuilder {
-        sst_meta_vec: Vec<SstMetaData>,
+        sst_meta_vec: Vec<(Level, SstMetaData)>,
     }
 
     impl LevelsControllerMockBuilder {
-        pub fn add_sst(mut self, mut sst_meta: Vec<SstMetaData>) -> Self {
-            self.sst_meta_vec.append(&mut sst_meta);
+        pub fn add_sst(self, sst_meta: Vec<SstMetaData>) -> Self {
+            self.add_sst_to_level(0, sst_meta)
+        }
+
+        pub fn add_sst_to_level(mut self, level: Level, sst_meta: Vec<SstMetaData>) -> Self {
+            self.sst_meta_vec
+                .extend(sst_meta.into_iter().map(|meta| (level, meta)));
             self
         }
 
@@ -144,9 +149,9 @@ pub mod tests {
             let (tx, _rx) = mpsc::unbounded_channel();
             let file_purge_queue = FilePurgeQueue::new(100, TableId::from(101), tx);
             let mut levels_controller = LevelsController::new(file_purge_queue);
-            for (id, sst_meta) in self.sst_meta_vec.into_iter().enumerate() {
+            for (id, (level, sst_meta)) in self.sst_meta_vec.into_iter().enumerate() {
                 levels_controller.add_sst_to_level(
-                    0,
+                    level,
                     FileMeta {
                         id: id as FileId,
                         meta: sst_meta,
@@ -5,6 +5,9 @@
 pub mod builder;
 pub mod factory;
 pub mod file;
+pub mod filter;
+pub mod index;
 pub mod manager;
 pub mod parquet;
 pub mod reader;
+pub mod time_series;
@@ -4,12 +4,7 @@
 
 use std::{
     io::SeekFrom,
-    pin::Pin,
-    sync::{
-        atomic::{AtomicUsize, Ordering},
-        Arc, Mutex,
-    },
-    task::{Context, Poll},
+    sync::{Arc, Mutex},
 };
 
 use arrow_deps::{
@@ -21,16 +16,19 @@ use arrow_deps::{
     },
 };
 use async_trait::async_trait;
-use common_types::{bytes::BufMut, request_id::RequestId};
-use futures::{AsyncRead, AsyncReadExt};
+use common_types::{record_batch::RecordBatchWithKey, request_id::RequestId};
+use futures::StreamExt;
 use log::debug;
 use object_store::{ObjectStore, Path};
+use parquet::footer;
 use snafu::ResultExt;
 
 use crate::sst::{
     builder::{RecordBatchStream, SstBuilder, *},
     factory::SstBuilderOptions,
     file::SstMetaData,
+    filter::{RowGroupFilter, RowGroupFilterBuilder, SstFilter},
+    index::{InvertedIndex, InvertedIndexBuilder},
     parquet::encoding,
 };
 
@@ -59,89 +57,19 @@ impl<'a, S: ObjectStore> ParquetSstBuilder<'a, S> {
 
 /// A memory writer implementing the [ParquetWriter].
 ///
-/// The writer accepts the encoded bytes by parquet format and provides the byte
-/// stream to the reader.
-#[derive(Clone, Debug)]
+/// The writer accepts the encoded bytes by parquet format and the bytes can be
+/// taken out after the [ArrowWriter] is closed.
+#[derive(Clone, Debug, Default)]
 struct EncodingBuffer {
     // In order to reuse the buffer, the buffer must be wrapped in the Arc and the Mutex because
     // the writer is consumed when building a ArrowWriter.
-    inner: Arc<Mutex<EncodingBufferInner>>,
-}
-
-impl Default for EncodingBuffer {
-    fn default() -> Self {
-        Self {
-            inner: Arc::new(Mutex::new(EncodingBufferInner {
-                bytes_written: 0,
-                read_offset: 0,
-                buf: Vec::new(),
-            })),
-        }
-    }
+    buf: Arc<Mutex<Vec<u8>>>,
 }
 
 impl std::io::Write for EncodingBuffer {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        let mut inner = self.inner.lock().unwrap();
-        inner.write(buf)
-    }
-
-    fn flush(&mut self) -> std::io::Result<()> {
-        let mut inner = self.inner.lock().unwrap();
-        inner.flush()
-    }
-}
-
-impl std::io::Seek for EncodingBuffer {
-    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
-        let mut inner = self.inner.lock().unwrap();
-        inner.seek(pos)
-    }
-}
-
-impl TryClone for EncodingBuffer {
-    fn try_clone(&self) -> std::io::Result<Self> {
-        Ok(self.clone())
-    }
-}
-
-impl EncodingBuffer {
-    fn read(&self, read_buf: &mut [u8]) -> usize {
-        let mut inner = self.inner.lock().unwrap();
-        inner.read(read_buf)
-    }
-}
-
-/// The underlying buffer implementing [ParquetWriter].
-///
-/// Provides the write function for [ArrowWriter] and read function for
-/// [AsyncRead].
-#[derive(Clone, Debug)]
-struct EncodingBufferInner {
-    bytes_written: usize,
-    read_offset: usize,
-    buf: Vec<u8>,
-}
-
-impl std::io::Write for EncodingBufferInner {
-    /// Write the `buf` to the `self.buf`.
-    ///
-    /// The readable bytes should be exhausted before writing new bytes.
-    /// `self.bytes_written` and `self.read_offset` is updated after writing.
-    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        if self.read_offset != 0 {
-            assert_eq!(self.buf.len(), self.read_offset);
-            self.buf.clear();
-            self.buf.reserve(buf.len());
-            // reset the read offset
-            self.read_offset = 0;
-        }
-
-        let bytes_written = self.buf.write(buf)?;
-        // accumulate the written bytes
-        self.bytes_written += bytes_written;
-
-        Ok(bytes_written)
+        self.buf.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
     }
 
     /// Actually nothing to flush.
@@ -150,90 +78,76 @@ impl std::io::Write for EncodingBufferInner {
     }
 }
 
-impl std::io::Seek for EncodingBufferInner {
+impl std::io::Seek for EncodingBuffer {
     /// Given the assumption that the seek usage of the [ParquetWriter] in the
     /// parquet project is just `seek(SeekFrom::Current(0))`, the
     /// implementation panics if seek to a different target.
     fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
         if let SeekFrom::Current(offset) = pos {
             assert_eq!(offset, 0);
-            return Ok(self.bytes_written as u64);
+            return Ok(self.buf.lock().unwrap().len() as u64);
         }
 
         unreachable!("Only can handle the case where seek to current(0)")
     }
 }
 
-impl EncodingBufferInner {
-    /// Read the content in `self.buf[self.offset..]` into `read_buf`.
-    ///
-    /// When finishing reading, advance the `self.offset`.
-    fn read(&mut self, mut read_buf: &mut [u8]) -> usize {
-        if self.read_offset >= self.buf.len() {
-            return 0;
-        }
-        let remaining_size = self.buf.len() - self.read_offset;
-
-        let read_len = remaining_size.min(read_buf.len());
-        read_buf.put(&self.buf[self.read_offset..self.read_offset + read_len]);
-
-        self.advance(read_len);
-        read_len
+impl TryClone for EncodingBuffer {
+    fn try_clone(&self) -> std::io::Result<Self> {
+        Ok(self.clone())
     }
+}
 
-    /// Advance the `self.offset` by `len`.
-    ///
-    /// Caller should ensures the advanced offset wont exceed `self.buf.len()`.
-    fn advance(&mut self, len: usize) {
-        self.read_offset += len;
-
-        assert!(self.read_offset <= self.buf.len());
+impl EncodingBuffer {
+    /// Take all the bytes written.
+    fn take_bytes(&self) -> Vec<u8> {
+        std::mem::take(&mut *self.buf.lock().unwrap())
     }
 }
 
-/// RecordBytesReader provides AsyncRead implementation for the encoded records
-/// by parquet.
+/// RecordBytesReader fetches the records from the stream and encodes them by
+/// parquet.
+///
+/// The records are encoded partition by partition once fetched, so only one
+/// partition is buffered. The filters of the row groups and the inverted index
+/// are built along with the encoding, and the sst meta data containing them is
+/// set into the footer after all the records are encoded.
 struct RecordBytesReader {
     request_id: RequestId,
     record_stream: RecordBatchStream,
-    encoding_buffer: EncodingBuffer,
-    arrow_writer: Mutex<Option<ArrowWriter<EncodingBuffer>>>,
     num_rows_per_row_group: usize,
     compression: Compression,
     meta_data: SstMetaData,
-    total_row_num: Arc<AtomicUsize>,
-    arrow_record_batch_vec: Vec<ArrowRecordBatch>,
-    // Whether the underlying `record_stream` is finished
-    stream_finished: bool,
-
-    fetched_row_num: usize,
+    /// The partition being fetched, each partition contains at least
+    /// `num_rows_per_row_group` rows except the last one, and is encoded by
+    /// one write to the [ArrowWriter].
+    partition: Vec<RecordBatchWithKey>,
+    num_rows_in_partition: usize,
+    /// Filters of the encoded row groups, `None` if no column needs filter.
+    row_group_filters: Option<Vec<RowGroupFilter>>,
+    inverted_index_builder: Option<InvertedIndexBuilder>,
+    encoding_buffer: EncodingBuffer,
+    arrow_writer: Option<ArrowWriter<EncodingBuffer>>,
+    total_row_num: usize,
 }
 
-/// Build the write properties containing the sst meta data.
+/// Build the write properties of the sst.
 fn build_write_properties(
     num_rows_per_row_group: usize,
     compression: Compression,
-    meta_data: &SstMetaData,
-) -> Result<WriterProperties> {
-    let meta_data_kv = encoding::encode_sst_meta_data(meta_data.clone())
-        .map_err(|e| Box::new(e) as _)
-        .context(EncodeMetaData)?;
-
-    Ok(WriterProperties::builder()
-        .set_key_value_metadata(Some(vec![meta_data_kv]))
+) -> WriterProperties {
+    WriterProperties::builder()
         .set_max_row_group_size(num_rows_per_row_group)
         .set_compression(compression)
-        .build())
+        .build()
 }
 
 /// Encode the record batch with [ArrowWriter] and the encoded contents is
 /// written to the [EncodingBuffer].
-// TODO(xikai): too many parameters
 fn encode_record_batch(
     arrow_writer: &mut Option<ArrowWriter<EncodingBuffer>>,
     num_rows_per_row_group: usize,
     compression: Compression,
-    meta_data: &SstMetaData,
     mem_buf_writer: EncodingBuffer,
     arrow_record_batch_vec: Vec<ArrowRecordBatch>,
 ) -> Result<usize> {
@@ -245,7 +159,7 @@ fn encode_record_batch(
 
     // create arrow writer if not exist
     if arrow_writer.is_none() {
-        let write_props = build_write_properties(num_rows_per_row_group, compression, meta_data)?;
+        let write_props = build_write_properties(num_rows_per_row_group, compression);
         let writer = ArrowWriter::try_new(mem_buf_writer, arrow_schema.clone(), Some(write_props))
             .map_err(|e| Box::new(e) as _)
             .context(EncodeRecordBatch)?;
@@ -277,89 +191,148 @@ fn close_writer(arrow_writer: &mut Option<ArrowWriter<EncodingBuffer>>) -> Resul
     Ok(())
 }
 
-impl AsyncRead for RecordBytesReader {
-    fn poll_read(
-        self: Pin<&mut Self>,
-        cx: &mut Context<'_>,
-        buf: &mut [u8],
-    ) -> Poll<std::io::Result<usize>> {
-        let mut reader = self.get_mut();
-        let size = reader.encoding_buffer.read(buf);
-        if size > 0 {
-            return Poll::Ready(Ok(size));
-        }
+impl RecordBytesReader {
+    fn new(
+        request_id: RequestId,
+        record_stream: RecordBatchStream,
+        num_rows_per_row_group: usize,
+        compression: Compression,
+        meta_data: SstMetaData,
+    ) -> Self {
+        let schema = &meta_data.schema;
+        let row_group_filters = SstFilter::need_filter(schema).then(Vec::new);
+        let inverted_index_builder =
+            InvertedIndex::need_index(schema).then(|| InvertedIndexBuilder::new(schema));
 
-        // The stream is also finished
-        if reader.stream_finished {
-            return Poll::Ready(Ok(0));
+        Self {
+            request_id,
+            record_stream,
+            num_rows_per_row_group,
+            compression,
+            meta_data,
+            partition: Vec::new(),
+            num_rows_in_partition: 0,
+            row_group_filters,
+            inverted_index_builder,
+            encoding_buffer: EncodingBuffer::default(),
+            arrow_writer: None,
+            total_row_num: 0,
         }
+    }
 
-        // FIXME(xikai): no data may cause empty sst file.
-        // fetch more rows from the stream.
-        while reader.fetched_row_num < reader.num_rows_per_row_group {
-            match Pin::new(reader.record_stream.as_mut()).poll_next(cx) {
-                Poll::Pending => return Poll::Pending,
-                Poll::Ready(v) => match v {
-                    Some(record_batch) => match record_batch.context(PollRecordBatch) {
-                        Ok(record_batch) => {
-                            assert!(
-                                !record_batch.is_empty(),
-                                "found empty record batch, request id:{}",
-                                reader.request_id
-                            );
-
-                            reader.fetched_row_num += record_batch.num_rows();
-                            reader
-                                .arrow_record_batch_vec
-                                .push(record_batch.into_record_batch().into_arrow_record_batch());
-                        }
-                        Err(e) => {
-                            return Poll::Ready(Err(std::io::Error::new(
-                                std::io::ErrorKind::Other,
-                                e,
-                            )))
-                        }
-                    },
-                    None => {
-                        reader.stream_finished = true;
-                        debug!(
-                            "Record stream finished, request_id:{}, batch_len:{}, fetched_row_num:{}, num_rows_per_row_group:{}",
-                            reader.request_id,
-                            reader.arrow_record_batch_vec.len(),
-                            reader.fetched_row_num,
-                            reader.num_rows_per_row_group,
-                        );
-                        break;
-                    }
-                },
+    /// Build filters for the row groups of the partition.
+    ///
+    /// The [ArrowWriter] splits each partition into row groups by
+    /// `num_rows_per_row_group`.
+    fn build_row_group_filters(&mut self, partition: &[RecordBatchWithKey]) {
+        let row_group_filters = match &mut self.row_group_filters {
+            Some(v) => v,
+            None => return,
+        };
+
+        let schema = &self.meta_data.schema;
+        let num_rows_per_row_group = self.num_rows_per_row_group;
+        let mut builder = RowGroupFilterBuilder::new(schema);
+        let mut row_num_in_group = 0;
+        for record_batch in partition {
+            let mut offset = 0;
+            while offset < record_batch.num_rows() {
+                let len = (num_rows_per_row_group - row_num_in_group)
+                    .min(record_batch.num_rows() - offset);
+                builder.add_rows(schema, record_batch, offset..offset + len);
+                offset += len;
+                row_num_in_group += len;
+
+                if row_num_in_group == num_rows_per_row_group {
+                    let next_builder = RowGroupFilterBuilder::new(schema);
+                    row_group_filters.push(std::mem::replace(&mut builder, next_builder).build());
+                    row_num_in_group = 0;
+                }
             }
         }
 
-        assert!(reader.stream_finished || reader.fetched_row_num >= reader.num_rows_per_row_group);
-
-        // Reset fetched row num.
-        reader.fetched_row_num = 0;
-        match encode_record_batch(
-            reader.arrow_writer.get_mut().unwrap(),
-            reader.num_rows_per_row_group,
-            reader.compression,
-            &reader.meta_data,
-            reader.encoding_buffer.clone(),
-            std::mem::take(&mut reader.arrow_record_batch_vec),
-        ) {
-            Err(e) => return Poll::Ready(Err(std::io::Error::new(std::io::ErrorKind::Other, e))),
-            Ok(row_num) => {
-                reader.total_row_num.fetch_add(row_num, Ordering::Relaxed);
+        if row_num_in_group > 0 {
+            row_group_filters.push(builder.build());
+        }
+    }
+
+    /// Encode the fetched partition and feed it to the filters and the
+    /// inverted index.
+    fn encode_partition(&mut self) -> Result<()> {
+        let partition = std::mem::take(&mut self.partition);
+        self.num_rows_in_partition = 0;
+        if partition.is_empty() {
+            return Ok(());
+        }
+
+        self.build_row_group_filters(&partition);
+        if let Some(builder) = &mut self.inverted_index_builder {
+            for record_batch in &partition {
+                builder.add_record_batch(record_batch);
             }
         }
 
-        if reader.stream_finished {
-            if let Err(e) = close_writer(reader.arrow_writer.get_mut().unwrap()) {
-                return Poll::Ready(Err(std::io::Error::new(std::io::ErrorKind::Other, e)));
+        let arrow_record_batch_vec = partition
+            .into_iter()
+            .map(|v| v.into_record_batch().into_arrow_record_batch())
+            .collect();
+        self.total_row_num += encode_record_batch(
+            &mut self.arrow_writer,
+            self.num_rows_per_row_group,
+            self.compression,
+            self.encoding_buffer.clone(),
+            arrow_record_batch_vec,
+        )?;
+
+        Ok(())
+    }
+
+    /// Returns the encoded bytes and the number of rows.
+    /// Encode all the records, returns the encoded bytes, the number of rows
+    /// and the inverted index built from the rows.
+    async fn read_all(mut self) -> Result<(Vec<u8>, usize, Option<InvertedIndex>)> {
+        while let Some(record_batch) = self.record_stream.next().await {
+            let record_batch = record_batch.context(PollRecordBatch)?;
+            assert!(
+                !record_batch.is_empty(),
+                "found empty record batch, request id:{}",
+                self.request_id
+            );
+
+            self.num_rows_in_partition += record_batch.num_rows();
+            self.partition.push(record_batch);
+            if self.num_rows_in_partition >= self.num_rows_per_row_group {
+                self.encode_partition()?;
             }
         }
+        self.encode_partition()?;
 
-        Poll::Ready(Ok(reader.encoding_buffer.read(buf)))
+        debug!(
+            "Record stream finished, request_id:{}, total_row_num:{}, num_rows_per_row_group:{}",
+            self.request_id, self.total_row_num, self.num_rows_per_row_group,
+        );
+
+        // FIXME(xikai): no data may cause empty sst file.
+        if self.arrow_writer.is_none() {
+            return Ok((Vec::new(), 0, None));
+        }
+        close_writer(&mut self.arrow_writer)?;
+
+        self.meta_data.sst_filter = self.row_group_filters.take().map(SstFilter::new);
+        self.meta_data.inverted_index = self.inverted_index_builder.take().map(|v| v.build());
+        let meta_data_kv = encoding::encode_sst_meta_data(self.meta_data.clone())
+            .map_err(|e| Box::new(e) as _)
+            .context(EncodeMetaData)?;
+        let mut bytes = self.encoding_buffer.take_bytes();
+        footer::set_key_value_metadata(
+            &mut bytes,
+            meta_data_kv.key,
+            meta_data_kv.value.unwrap_or_default(),
+        )
+        .map_err(|e| Box::new(e) as _)
+        .context(EncodeMetaData)?;
+
+        Ok((bytes, self.total_row_num, self.meta_data.inverted_index))
     }
 }
 
@@ -376,30 +349,17 @@ impl<'a, S: ObjectStore> SstBuilder for ParquetSstBuilder<'a, S> {
             request_id, meta, self.num_rows_per_row_group
         );
 
-        let total_row_num = Arc::new(AtomicUsize::new(0));
-        let mut reader = RecordBytesReader {
+        let reader = RecordBytesReader::new(
             request_id,
             record_stream,
-            encoding_buffer: EncodingBuffer::default(),
-            arrow_writer: Mutex::new(None),
-            num_rows_per_row_group: self.num_rows_per_row_group,
-            compression: self.compression,
-            total_row_num: total_row_num.clone(),
-            arrow_record_batch_vec: Vec::new(),
+            self.num_rows_per_row_group,
+            self.compression,
             // TODO(xikai): should we avoid this clone?
-            meta_data: meta.to_owned(),
-            stream_finished: false,
-            fetched_row_num: 0,
-        };
-        // TODO(ruihang): `RecordBytesReader` support stream read. It could be improved
-        // if the storage supports streaming upload (maltipart upload).
-        let mut bytes = vec![];
-        reader
-            .read_to_end(&mut bytes)
-            .await
-            .map_err(|e| Box::new(e) as _)
-            .context(ReadData)?;
-        drop(reader);
+            meta.to_owned(),
+        );
+        // TODO(ruihang): Upload the encoded bytes by stream if the storage supports
+        // streaming upload (multipart upload).
+        let (bytes, row_num, inverted_index) = reader.read_all().await?;
 
         self.storage
             .put(self.path, bytes.into())
@@ -410,17 +370,26 @@ impl<'a, S: ObjectStore> SstBuilder for ParquetSstBuilder<'a, S> {
 
         Ok(SstInfo {
             file_size: file_head.size,
-            row_num: total_row_num.load(Ordering::Relaxed),
+            row_num,
+            inverted_index,
         })
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::task::Poll;
 
+    use arrow_deps::datafusion::{
+        logical_plan::{col, lit, Expr},
+        scalar::ScalarValue,
+    };
     use common_types::{
         bytes::Bytes,
+        column_schema,
+        datum::{DatumKind, DatumView},
         projected_schema::ProjectedSchema,
+        schema::{self, Schema},
         tests::{build_row, build_schema},
         time::{TimeRange, Timestamp},
     };
@@ -435,6 +404,7 @@ mod tests {
         row_iter::tests::build_record_batch_with_key,
         sst::{
             factory::{Factory, FactoryImpl, SstBuilderOptions, SstReaderOptions, SstType},
+            index::tests::{build_tsid_row, build_tsid_schema},
             parquet::reader::ParquetSstReader,
             reader::{tests::check_stream, SstReader},
         },
@@ -481,6 +451,8 @@ mod tests {
                 schema: schema.clone(),
                 size: 10,
                 row_num: 2,
+                sst_filter: None,
+                inverted_index: None,
             };
 
             let mut counter = 10;
@@ -548,4 +520,239 @@ mod tests {
             check_stream(&mut stream, expect_rows).await;
         });
     }
+
+    /// Build a schema same as [build_schema] except that `key1` is a tag.
+    fn build_schema_with_tag() -> Schema {
+        schema::Builder::new()
+            .auto_increment_column_id(true)
+            .add_key_column(
+                column_schema::Builder::new("key1".to_string(), DatumKind::Varbinary)
+                    .is_tag(true)
+                    .build()
+                    .unwrap(),
+            )
+            .unwrap()
+            .add_key_column(
+                column_schema::Builder::new("key2".to_string(), DatumKind::Timestamp)
+                    .build()
+                    .unwrap(),
+            )
+            .unwrap()
+            .add_normal_column(
+                column_schema::Builder::new("field1".to_string(), DatumKind::Double)
+                    .build()
+                    .unwrap(),
+            )
+            .unwrap()
+            .add_normal_column(
+                column_schema::Builder::new("field2".to_string(), DatumKind::String)
+                    .build()
+                    .unwrap(),
+            )
+            .unwrap()
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_parquet_build_and_read_with_filter() {
+        let runtime = Arc::new(runtime::Builder::default().build().unwrap());
+        runtime.clone().block_on(async {
+            let sst_builder_options = SstBuilderOptions {
+                sst_type: SstType::Parquet,
+                num_rows_per_row_group: 2,
+                compression: table_options::Compression::Uncompressed,
+            };
+
+            let dir = tempdir().unwrap();
+            let store = LocalFileSystem::new_with_prefix(dir.path()).unwrap();
+            let sst_file_path = Path::from("data.par");
+
+            let schema = build_schema_with_tag();
+            let sst_meta = SstMetaData {
+                min_key: Bytes::from_static(b"a"),
+                max_key: Bytes::from_static(b"c"),
+                time_range: TimeRange::new_unchecked(Timestamp::new(1), Timestamp::new(3)),
+                max_sequence: 200,
+                schema: schema.clone(),
+                size: 0,
+                row_num: 0,
+                sst_filter: None,
+                inverted_index: None,
+            };
+
+            // Row groups: [a, a], [b, b], [c, c].
+            let batches: Vec<RecordBatchStreamItem> = vec![
+                Ok(build_record_batch_with_key(
+                    schema.clone(),
+                    vec![
+                        build_row(b"a", 1, 10.0, "v1"),
+                        build_row(b"a", 2, 10.0, "v1"),
+                    ],
+                )),
+                Ok(build_record_batch_with_key(
+                    schema.clone(),
+                    vec![
+                        build_row(b"b", 1, 10.0, "v2"),
+                        build_row(b"b", 2, 10.0, "v2"),
+                    ],
+                )),
+                Ok(build_record_batch_with_key(
+                    schema.clone(),
+                    vec![
+                        build_row(b"c", 1, 10.0, "v3"),
+                        build_row(b"c", 2, 10.0, "v3"),
+                    ],
+                )),
+            ];
+            let mut builder = FactoryImpl
+                .new_sst_builder(&sst_builder_options, &sst_file_path, &store)
+                .unwrap();
+            let sst_info = builder
+                .build(
+                    RequestId::next_id(),
+                    &sst_meta,
+                    Box::new(stream::iter(batches)),
+                )
+                .await
+                .unwrap();
+            assert_eq!(6, sst_info.row_num);
+
+            let exprs = vec![Expr::Column("key1".into())
+                .eq(Expr::Literal(ScalarValue::Binary(Some(b"b".to_vec()))))];
+            let sst_reader_options = SstReaderOptions {
+                sst_type: SstType::Parquet,
+                read_batch_row_num: 5,
+                reverse: false,
+                projected_schema: ProjectedSchema::no_projection(schema.clone()),
+                predicate: Arc::new(Predicate {
+                    exprs,
+                    time_range: TimeRange::min_to_max(),
+                }),
+                meta_cache: None,
+                data_cache: None,
+                runtime,
+            };
+            let mut reader = ParquetSstReader::new(&sst_file_path, &store, &sst_reader_options);
+            let sst_filter = reader
+                .meta_data()
+                .await
+                .unwrap()
+                .sst_filter
+                .clone()
+                .unwrap();
+            assert_eq!(3, sst_filter.row_group_filters().len());
+            assert_eq!(3, reader.row_groups().await.len());
+
+            // Only the row group containing `b` is read.
+            let mut stream = reader.read().await.unwrap();
+            check_stream(
+                &mut stream,
+                vec![
+                    build_row(b"b", 1, 10.0, "v2"),
+                    build_row(b"b", 2, 10.0, "v2"),
+                ],
+            )
+            .await;
+        });
+    }
+
+    #[test]
+    fn test_parquet_build_and_read_with_inverted_index() {
+        let runtime = Arc::new(runtime::Builder::default().build().unwrap());
+        runtime.clone().block_on(async {
+            let sst_builder_options = SstBuilderOptions {
+                sst_type: SstType::Parquet,
+                num_rows_per_row_group: 4,
+                compression: table_options::Compression::Uncompressed,
+            };
+
+            let dir = tempdir().unwrap();
+            let store = LocalFileSystem::new_with_prefix(dir.path()).unwrap();
+            let sst_file_path = Path::from("data.par");
+
+            let schema = build_tsid_schema();
+            let sst_meta = SstMetaData {
+                min_key: Bytes::from_static(b"a"),
+                max_key: Bytes::from_static(b"c"),
+                time_range: TimeRange::new_unchecked(Timestamp::new(1), Timestamp::new(3)),
+                max_sequence: 200,
+                schema: schema.clone(),
+                size: 0,
+                row_num: 0,
+                sst_filter: None,
+                inverted_index: None,
+            };
+
+            // Only one row group containing two series.
+            let rows = vec![
+                build_tsid_row(1, 1, "host1", Some("east")),
+                build_tsid_row(1, 2, "host2", Some("west")),
+                build_tsid_row(2, 1, "host1", Some("east")),
+                build_tsid_row(2, 2, "host2", Some("west")),
+            ];
+            let batches: Vec<RecordBatchStreamItem> = vec![Ok(build_record_batch_with_key(
+                schema.clone(),
+                rows.clone(),
+            ))];
+            let mut builder = FactoryImpl
+                .new_sst_builder(&sst_builder_options, &sst_file_path, &store)
+                .unwrap();
+            builder
+                .build(
+                    RequestId::next_id(),
+                    &sst_meta,
+                    Box::new(stream::iter(batches)),
+                )
+                .await
+                .unwrap();
+
+            let read_with_exprs = |exprs: Vec<Expr>| {
+                let sst_reader_options = SstReaderOptions {
+                    sst_type: SstType::Parquet,
+                    read_batch_row_num: 5,
+                    reverse: false,
+                    projected_schema: ProjectedSchema::no_projection(schema.clone()),
+                    predicate: Arc::new(Predicate {
+                        exprs,
+                        time_range: TimeRange::min_to_max(),
+                    }),
+                    meta_cache: None,
+                    data_cache: None,
+                    runtime: runtime.clone(),
+                };
+                ParquetSstReader::new(&sst_file_path, &store, &sst_reader_options)
+            };
+
+            let mut reader = read_with_exprs(Vec::new());
+            let inverted_index = reader
+                .meta_data()
+                .await
+                .unwrap()
+                .inverted_index
+                .clone()
+                .unwrap();
+            assert_eq!(
+                &[1],
+                inverted_index.tsids("host", &DatumView::String("host1"))
+            );
+            assert_eq!(
+                &[2],
+                inverted_index.tsids("region", &DatumView::String("west"))
+            );
+
+            // The row group is read as some series is matched.
+            let mut reader = read_with_exprs(vec![col("host").eq(lit("host1"))]);
+            let mut stream = reader.read().await.unwrap();
+            check_stream(&mut stream, rows).await;
+
+            // Both the values are in the row group, but no series is matched.
+            let mut reader = read_with_exprs(vec![
+                col("host").eq(lit("host1")),
+                col("region").eq(lit("west")),
+            ]);
+            let mut stream = reader.read().await.unwrap();
+            check_stream(&mut stream, Vec::new()).await;
+        });
+    }
 }
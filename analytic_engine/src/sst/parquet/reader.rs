@@ -38,11 +38,16 @@ use tokio::sync::mpsc::{self, Receiver, Sender};
 use crate::sst::{
     factory::SstReaderOptions,
     file::SstMetaData,
+    filter::SstFilter,
+    index,
     parquet::encoding,
     reader::{error::*, SstReader},
 };
 
 const DEFAULT_CHANNEL_CAP: usize = 1000;
+/// Checking too many tsids against the filters of the row groups costs more
+/// than it saves.
+const MAX_TSIDS_TO_FILTER_ROW_GROUPS: usize = 1024;
 
 pub async fn read_sst_meta<S: ObjectStore>(
     storage: &S,
@@ -165,9 +170,17 @@ impl<'a, S: ObjectStore> ParquetSstReader<'a, S> {
 
         let file_reader = self.file_reader.take().unwrap();
         let batch_size = self.batch_size;
-        let schema = {
+        let (schema, sst_filter, matched_tsids) = {
             let meta_data = self.meta_data.as_ref().unwrap();
-            meta_data.schema.clone()
+            let matched_tsids = meta_data
+                .inverted_index
+                .as_ref()
+                .and_then(|v| v.match_tsids(&meta_data.schema, &self.predicate.exprs));
+            (
+                meta_data.schema.clone(),
+                meta_data.sst_filter.clone(),
+                matched_tsids,
+            )
         };
         let projected_schema = self.projected_schema.clone();
         let row_projector = projected_schema
@@ -201,6 +214,8 @@ impl<'a, S: ObjectStore> ParquetSstReader<'a, S> {
                 projected_schema,
                 row_projector,
                 predicate,
+                sst_filter,
+                matched_tsids,
                 batch_size,
                 reverse,
             };
@@ -247,6 +262,10 @@ struct ProjectAndFilterReader {
     projected_schema: ProjectedSchema,
     row_projector: RowProjector,
     predicate: PredicateRef,
+    sst_filter: Option<SstFilter>,
+    /// Tsids of the series matched by the inverted index, `None` if the
+    /// predicate can't be answered by the index.
+    matched_tsids: Option<Vec<u64>>,
     batch_size: usize,
     reverse: bool,
 }
@@ -256,7 +275,21 @@ impl ProjectAndFilterReader {
         assert!(self.file_reader.is_some());
 
         let row_groups = self.file_reader.as_ref().unwrap().metadata().row_groups();
-        let filter_results = self.predicate.filter_row_groups(&self.schema, row_groups);
+        let mut filter_results = self.predicate.filter_row_groups(&self.schema, row_groups);
+        if let Some(sst_filter) = &self.sst_filter {
+            sst_filter.filter_row_groups(&self.schema, &self.predicate.exprs, &mut filter_results);
+        }
+        match &self.matched_tsids {
+            // No series matched, the whole sst can be skipped.
+            Some(tsids) if tsids.is_empty() => filter_results.fill(false),
+            Some(tsids) if tsids.len() <= MAX_TSIDS_TO_FILTER_ROW_GROUPS => {
+                if let Some(sst_filter) = &self.sst_filter {
+                    let tsid_expr = index::tsid_in_list_expr(tsids);
+                    sst_filter.filter_row_groups(&self.schema, &[tsid_expr], &mut filter_results);
+                }
+            }
+            _ => (),
+        }
 
         trace!("Finish build row group predicate, predicate:{:?}, schema:{:?}, filter_results:{:?}, row_groups meta data:{:?}", self.predicate, self.schema, filter_results, row_groups);
 
@@ -0,0 +1,364 @@
+// Copyright 2022 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Sst builder implementation of the time series format.
+
+use async_trait::async_trait;
+use common_types::{
+    datum::Datum, record_batch::RecordBatchWithKey, request_id::RequestId, schema::Schema,
+};
+use futures::StreamExt;
+use log::debug;
+use object_store::{ObjectStore, Path};
+use snafu::ResultExt;
+
+use crate::sst::{
+    builder::{RecordBatchStream, SstBuilder, *},
+    factory::SstBuilderOptions,
+    file::SstMetaData,
+    index::{InvertedIndex, InvertedIndexBuilder},
+    time_series::encoding::{self, BlockIndexEntry},
+};
+
+/// The implementation of sst in time series format based on object storage.
+#[derive(Debug)]
+pub struct TimeSeriesSstBuilder<'a, S: ObjectStore> {
+    /// The path where the data is persisted.
+    path: &'a Path,
+    /// The storage where the data is persist.
+    storage: &'a S,
+    /// Max rows of one block, a long series is split into multiple blocks.
+    num_rows_per_block: usize,
+}
+
+impl<'a, S: ObjectStore> TimeSeriesSstBuilder<'a, S> {
+    pub fn new(path: &'a Path, storage: &'a S, options: &SstBuilderOptions) -> Self {
+        Self {
+            path,
+            storage,
+            num_rows_per_block: options.num_rows_per_row_group,
+        }
+    }
+}
+
+/// Builder to collect the rows of one series into a block.
+struct BlockBuilder<'a> {
+    schema: &'a Schema,
+    /// Indexes of the columns identifying a series.
+    series_key_indexes: Vec<usize>,
+    /// Datums of the rows organized by columns.
+    columns: Vec<Vec<Datum>>,
+    num_rows: usize,
+}
+
+impl<'a> BlockBuilder<'a> {
+    fn new(schema: &'a Schema) -> Self {
+        Self {
+            schema,
+            series_key_indexes: encoding::series_key_indexes(schema),
+            columns: vec![Vec::new(); schema.num_columns()],
+            num_rows: 0,
+        }
+    }
+
+    /// Whether the row at `index` of the `record_batch` belongs to the series
+    /// of this block.
+    fn is_same_series(&self, record_batch: &RecordBatchWithKey, index: usize) -> bool {
+        self.series_key_indexes
+            .iter()
+            .all(|i| self.columns[*i].last() == Some(&record_batch.column(*i).datum(index)))
+    }
+
+    fn append(&mut self, record_batch: &RecordBatchWithKey, index: usize) {
+        for (i, column) in self.columns.iter_mut().enumerate() {
+            column.push(record_batch.column(i).datum(index));
+        }
+        self.num_rows += 1;
+    }
+
+    /// Encode the collected rows as a block into `buf`, record it in the
+    /// `block_index` and reset the builder.
+    fn flush(&mut self, buf: &mut Vec<u8>, block_index: &mut Vec<BlockIndexEntry>) -> Result<()> {
+        if self.num_rows == 0 {
+            return Ok(());
+        }
+
+        let offset = buf.len();
+        let header = encoding::encode_block(self.schema, &self.columns, buf)
+            .map_err(|e| Box::new(e) as _)
+            .context(EncodeRecordBatch)?;
+        block_index.push(BlockIndexEntry {
+            offset: offset as u64,
+            len: buf.len() - offset,
+            header,
+        });
+        for column in &mut self.columns {
+            column.clear();
+        }
+        self.num_rows = 0;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<'a, S: ObjectStore> SstBuilder for TimeSeriesSstBuilder<'a, S> {
+    async fn build(
+        &mut self,
+        request_id: RequestId,
+        meta: &SstMetaData,
+        mut record_stream: RecordBatchStream,
+    ) -> Result<SstInfo> {
+        debug!(
+            "Build time series sst, request_id:{}, meta:{:?}, num_rows_per_block:{}",
+            request_id, meta, self.num_rows_per_block
+        );
+
+        let mut buf = Vec::new();
+        encoding::encode_header(&mut buf);
+
+        let mut row_num = 0;
+        let mut block_index = Vec::new();
+        let mut block_builder = BlockBuilder::new(&meta.schema);
+        let mut index_builder = if InvertedIndex::need_index(&meta.schema) {
+            Some(InvertedIndexBuilder::new(&meta.schema))
+        } else {
+            None
+        };
+        while let Some(record_batch) = record_stream.next().await {
+            let record_batch = record_batch.context(PollRecordBatch)?;
+            if let Some(index_builder) = &mut index_builder {
+                index_builder.add_record_batch(&record_batch);
+            }
+            for index in 0..record_batch.num_rows() {
+                if block_builder.num_rows > 0
+                    && (block_builder.num_rows >= self.num_rows_per_block
+                        || !block_builder.is_same_series(&record_batch, index))
+                {
+                    block_builder.flush(&mut buf, &mut block_index)?;
+                }
+                block_builder.append(&record_batch, index);
+            }
+            row_num += record_batch.num_rows();
+        }
+        block_builder.flush(&mut buf, &mut block_index)?;
+
+        // The time series sst has no row group, so no filter is built.
+        let mut meta_data = meta.clone();
+        meta_data.sst_filter = None;
+        meta_data.inverted_index = index_builder.map(|v| v.build());
+        let inverted_index = meta_data.inverted_index.clone();
+        encoding::encode_index_meta_and_footer(&block_index, meta_data, &mut buf)
+            .map_err(|e| Box::new(e) as _)
+            .context(EncodeMetaData)?;
+
+        debug!(
+            "Time series sst encoded, request_id:{}, row_num:{}, block_num:{}, size:{}",
+            request_id,
+            row_num,
+            block_index.len(),
+            buf.len()
+        );
+
+        self.storage
+            .put(self.path, buf.into())
+            .await
+            .context(Storage)?;
+
+        let file_head = self.storage.head(self.path).await.context(Storage)?;
+
+        Ok(SstInfo {
+            file_size: file_head.size,
+            row_num,
+            inverted_index,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use common_types::{
+        bytes::Bytes,
+        projected_schema::ProjectedSchema,
+        row::Row,
+        tests::{build_row, build_row_opt, build_schema},
+        time::{TimeRange, Timestamp},
+    };
+    use common_util::runtime::{self, Runtime};
+    use futures::stream;
+    use object_store::LocalFileSystem;
+    use parquet::{cache::LruDataCache, DataCacheRef};
+    use table_engine::predicate::Predicate;
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::{
+        row_iter::tests::build_record_batch_with_key,
+        sst::{
+            factory::{Factory, FactoryImpl, SstReaderOptions, SstType},
+            reader::{tests::check_stream, SstReader},
+        },
+        table_options,
+    };
+
+    fn build_rows() -> Vec<Row> {
+        vec![
+            build_row(b"a", 1, 10.0, "v1"),
+            build_row(b"a", 2, 10.5, "v1"),
+            build_row_opt(b"a", 3, None, Some("v1")),
+            build_row(b"a", 4, 11.0, "v2"),
+            build_row_opt(b"a", 5, Some(11.0), None),
+            build_row(b"b", 1, 20.0, "v3"),
+            build_row(b"b", 2, 21.0, "v3"),
+            build_row(b"b", 3, 22.0, "v3"),
+            build_row(b"c", 10, 30.0, "v4"),
+        ]
+    }
+
+    async fn read_rows(
+        runtime: Arc<Runtime>,
+        store: &LocalFileSystem,
+        sst_file_path: &Path,
+        time_range: TimeRange,
+        reverse: bool,
+        expected_rows: Vec<Row>,
+    ) {
+        let sst_reader_options = SstReaderOptions {
+            sst_type: SstType::TimeSeries,
+            read_batch_row_num: 2,
+            reverse,
+            projected_schema: ProjectedSchema::no_projection(build_schema()),
+            predicate: Arc::new(Predicate::new(time_range)),
+            meta_cache: None,
+            data_cache: None,
+            runtime,
+        };
+        let mut reader = FactoryImpl
+            .new_sst_reader(&sst_reader_options, sst_file_path, store)
+            .unwrap();
+        let mut stream = reader.read().await.unwrap();
+        check_stream(&mut stream, expected_rows).await;
+    }
+
+    #[test]
+    fn test_time_series_build_and_read() {
+        let runtime = Arc::new(runtime::Builder::default().build().unwrap());
+        runtime.clone().block_on(async {
+            let sst_builder_options = SstBuilderOptions {
+                sst_type: SstType::TimeSeries,
+                num_rows_per_row_group: 2,
+                compression: table_options::Compression::Uncompressed,
+            };
+
+            let dir = tempdir().unwrap();
+            let store = LocalFileSystem::new_with_prefix(dir.path()).unwrap();
+            let sst_file_path = Path::from("data.sst");
+
+            let schema = build_schema();
+            let sst_meta = SstMetaData {
+                min_key: Bytes::from_static(b"a"),
+                max_key: Bytes::from_static(b"c"),
+                time_range: TimeRange::new_unchecked(Timestamp::new(1), Timestamp::new(11)),
+                max_sequence: 200,
+                schema: schema.clone(),
+                size: 0,
+                row_num: 0,
+                sst_filter: None,
+                inverted_index: None,
+            };
+
+            // The rows of series `a` are split into two record batches.
+            let rows = build_rows();
+            let batches: Vec<RecordBatchStreamItem> = vec![
+                Ok(build_record_batch_with_key(
+                    schema.clone(),
+                    rows[..3].to_vec(),
+                )),
+                Ok(build_record_batch_with_key(
+                    schema.clone(),
+                    rows[3..].to_vec(),
+                )),
+            ];
+            let mut builder = FactoryImpl
+                .new_sst_builder(&sst_builder_options, &sst_file_path, &store)
+                .unwrap();
+            let sst_info = builder
+                .build(
+                    RequestId::next_id(),
+                    &sst_meta,
+                    Box::new(stream::iter(batches)),
+                )
+                .await
+                .unwrap();
+            assert_eq!(rows.len(), sst_info.row_num);
+
+            let sst_reader_options = SstReaderOptions {
+                sst_type: SstType::TimeSeries,
+                read_batch_row_num: 2,
+                reverse: false,
+                projected_schema: ProjectedSchema::no_projection(schema.clone()),
+                predicate: Arc::new(Predicate::new(TimeRange::min_to_max())),
+                meta_cache: None,
+                data_cache: None,
+                runtime: runtime.clone(),
+            };
+            let mut reader = FactoryImpl
+                .new_sst_reader(&sst_reader_options, &sst_file_path, &store)
+                .unwrap();
+            assert_eq!(reader.meta_data().await.unwrap(), &sst_meta);
+
+            read_rows(
+                runtime.clone(),
+                &store,
+                &sst_file_path,
+                TimeRange::min_to_max(),
+                false,
+                rows.clone(),
+            )
+            .await;
+
+            let mut reversed_rows = rows.clone();
+            reversed_rows.reverse();
+            read_rows(
+                runtime.clone(),
+                &store,
+                &sst_file_path,
+                TimeRange::min_to_max(),
+                true,
+                reversed_rows,
+            )
+            .await;
+
+            // Blocks: a[1, 2], a[3, 4], a[5], b[1, 2], b[3], c[10], only the
+            // blocks overlapping with [3, 5) are read.
+            read_rows(
+                runtime.clone(),
+                &store,
+                &sst_file_path,
+                TimeRange::new_unchecked(Timestamp::new(3), Timestamp::new(5)),
+                false,
+                vec![rows[2].clone(), rows[3].clone(), rows[7].clone()],
+            )
+            .await;
+
+            // The meta data and the blocks are read from the cache once cached.
+            let data_cache: DataCacheRef = Arc::new(LruDataCache::new(100));
+            let sst_reader_options = SstReaderOptions {
+                data_cache: Some(data_cache),
+                ..sst_reader_options
+            };
+            for i in 0..2 {
+                if i == 1 {
+                    store.delete(&sst_file_path).await.unwrap();
+                }
+                let mut reader = FactoryImpl
+                    .new_sst_reader(&sst_reader_options, &sst_file_path, &store)
+                    .unwrap();
+                assert_eq!(reader.meta_data().await.unwrap(), &sst_meta);
+                let mut stream = reader.read().await.unwrap();
+                check_stream(&mut stream, rows.clone()).await;
+            }
+        });
+    }
+}
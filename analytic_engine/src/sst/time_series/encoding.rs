@@ -0,0 +1,1011 @@
+// Copyright 2022 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Encoding of the time series sst.
+//!
+//! The layout of the sst file:
+//! ```plaintext
+//! | magic(u32) | version(u8) | block ... | block index | meta data | footer |
+//! ```
+//! The footer has a fixed length and consists of the offset(u64) of the block
+//! index, the offset(u64) and length(u64) of the meta data, the version(u8)
+//! and the magic(u32) again. So the reader can fetch the footer at the end of
+//! the file, then the block index and the meta data, and finally only the
+//! blocks it needs.
+//!
+//! The block index holds one entry for every block:
+//! ```plaintext
+//! | offset(u64) | length(u32) | num rows(u32) | min timestamp(i64) | max timestamp(i64) |
+//! ```
+//! where the offset is from the start of the file and the length is the length
+//! of the whole encoded block.
+//!
+//! Each block holds the rows of one series (or part of a long series) and is
+//! laid out as:
+//! ```plaintext
+//! | block length(u32) | num rows(u32) | min timestamp(i64) | max timestamp(i64) | column ... |
+//! ```
+//! where the block length is the length of the columns, and every column is
+//! prefixed by its encoded length, so the columns not projected can be skipped.
+//!
+//! The columns are encoded by the schema:
+//! - The timestamp column is encoded by delta-of-delta.
+//! - The other key columns are the same in one series, so only the first value
+//!   is stored.
+//! - The double columns are encoded by the XOR encoding of gorilla.
+//! - Other columns are encoded by the mem compact format.
+
+use std::{convert::TryFrom, ops::Range};
+
+use common_types::{
+    bytes::{MemBuf, MemBufMut},
+    datum::{Datum, DatumKind},
+    schema::Schema,
+    time::Timestamp,
+};
+use common_util::{
+    codec::{
+        compact::{MemCompactDecoder, MemCompactEncoder},
+        DecodeTo, Encoder,
+    },
+    define_result,
+};
+use proto::sst::SstMetaData as SstMetaDataPb;
+use protobuf::Message;
+use snafu::{ensure, Backtrace, ResultExt, Snafu};
+
+use crate::sst::file::SstMetaData;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display(
+        "Failed to encode sst meta data, err:{}.\nBacktrace:\n{}",
+        source,
+        backtrace
+    ))]
+    EncodeIntoPb {
+        source: protobuf::ProtobufError,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "Failed to decode sst meta data, err:{}.\nBacktrace:\n{}",
+        source,
+        backtrace
+    ))]
+    DecodeFromPb {
+        source: protobuf::ProtobufError,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("Failed to convert sst meta data from protobuf, err:{}", source))]
+    ConvertSstMetaData { source: crate::sst::file::Error },
+
+    #[snafu(display("Failed to encode datum, err:{}", source))]
+    EncodeDatum {
+        source: common_util::codec::compact::Error,
+    },
+
+    #[snafu(display("Failed to decode datum, err:{}", source))]
+    DecodeDatum {
+        source: common_util::codec::compact::Error,
+    },
+
+    #[snafu(display("Failed to read bytes, err:{}", source))]
+    ReadBytes { source: common_types::bytes::Error },
+
+    #[snafu(display("Insufficient bits to decode value.\nBacktrace:\n{}", backtrace))]
+    InsufficientBits { backtrace: Backtrace },
+
+    #[snafu(display("Invalid magic number, given:{}.\nBacktrace:\n{}", given, backtrace))]
+    InvalidMagic { given: u32, backtrace: Backtrace },
+
+    #[snafu(display("Unsupported version, given:{}.\nBacktrace:\n{}", given, backtrace))]
+    UnsupportedVersion { given: u8, backtrace: Backtrace },
+
+    #[snafu(display(
+        "Invalid sst file, file size:{}, index offset:{}, meta offset:{}, \
+         meta length:{}.\nBacktrace:\n{}",
+        file_size,
+        index_offset,
+        meta_offset,
+        meta_len,
+        backtrace
+    ))]
+    InvalidFileLayout {
+        file_size: usize,
+        index_offset: u64,
+        meta_offset: u64,
+        meta_len: u64,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "Invalid block in the index, offset:{}, length:{}, index offset:{}.\nBacktrace:\n{}",
+        offset,
+        len,
+        index_offset,
+        backtrace
+    ))]
+    InvalidBlockIndex {
+        offset: u64,
+        len: usize,
+        index_offset: u64,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "Invalid block length, expect:{}, remaining:{}.\nBacktrace:\n{}",
+        expect,
+        remaining,
+        backtrace
+    ))]
+    InvalidBlockLen {
+        expect: usize,
+        remaining: usize,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("Timestamp column cannot be null.\nBacktrace:\n{}", backtrace))]
+    NullTimestamp { backtrace: Backtrace },
+}
+
+define_result!(Error);
+
+pub const MAGIC: u32 = 0x4354_5353;
+pub const VERSION: u8 = 0;
+/// Length of the magic and the version at the start of the file.
+pub const HEADER_LEN: usize = 5;
+/// Length of the index offset, meta offset, meta length, version and magic at
+/// the end of the file.
+pub const FOOTER_LEN: usize = 29;
+/// Length of one entry of the block index.
+pub const BLOCK_INDEX_ENTRY_LEN: usize = 32;
+
+/// Encode the header of the sst file into `buf`.
+pub fn encode_header(buf: &mut Vec<u8>) {
+    buf.write_u32(MAGIC).unwrap();
+    buf.write_u8(VERSION).unwrap();
+}
+
+/// Location and header of a block, recorded in the block index.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BlockIndexEntry {
+    /// Offset of the block from the start of the file.
+    pub offset: u64,
+    /// Length of the whole encoded block.
+    pub len: usize,
+    pub header: BlockHeader,
+}
+
+impl BlockIndexEntry {
+    /// Range of the block in the file.
+    pub fn range(&self) -> Range<usize> {
+        let start = self.offset as usize;
+        start..start + self.len
+    }
+}
+
+/// Encode the block index, the sst meta data and the footer into `buf`, they
+/// are placed at the end of `buf`.
+pub fn encode_index_meta_and_footer(
+    block_index: &[BlockIndexEntry],
+    meta_data: SstMetaData,
+    buf: &mut Vec<u8>,
+) -> Result<()> {
+    let index_offset = buf.len() as u64;
+    for entry in block_index {
+        buf.write_u64(entry.offset).unwrap();
+        buf.write_u32(entry.len as u32).unwrap();
+        buf.write_u32(entry.header.num_rows as u32).unwrap();
+        buf.write_u64(entry.header.min_timestamp.as_i64() as u64)
+            .unwrap();
+        buf.write_u64(entry.header.max_timestamp.as_i64() as u64)
+            .unwrap();
+    }
+
+    let meta_data_pb = SstMetaDataPb::from(meta_data);
+    let meta_offset = buf.len() as u64;
+    meta_data_pb.write_to_vec(buf).context(EncodeIntoPb)?;
+    let meta_len = buf.len() as u64 - meta_offset;
+
+    buf.write_u64(index_offset).unwrap();
+    buf.write_u64(meta_offset).unwrap();
+    buf.write_u64(meta_len).unwrap();
+    buf.write_u8(VERSION).unwrap();
+    buf.write_u32(MAGIC).unwrap();
+
+    Ok(())
+}
+
+/// The decoded footer of the sst file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Footer {
+    pub index_offset: u64,
+    pub meta_offset: u64,
+    pub meta_len: u64,
+}
+
+impl Footer {
+    /// Size of the sst file described by the footer.
+    pub fn file_size(&self) -> usize {
+        (self.meta_offset + self.meta_len) as usize + FOOTER_LEN
+    }
+
+    /// Range of the block index and the meta data in the file.
+    pub fn index_and_meta_range(&self) -> Range<usize> {
+        self.index_offset as usize..(self.meta_offset + self.meta_len) as usize
+    }
+
+    /// Check whether the footer is decoded from the file of `file_size`.
+    pub fn check_file_size(&self, file_size: usize) -> Result<()> {
+        ensure!(
+            self.file_size() == file_size,
+            InvalidFileLayout {
+                file_size,
+                index_offset: self.index_offset,
+                meta_offset: self.meta_offset,
+                meta_len: self.meta_len,
+            }
+        );
+
+        Ok(())
+    }
+}
+
+/// Decode the footer from the last [FOOTER_LEN] bytes of the sst file.
+pub fn decode_footer(mut buf: &[u8]) -> Result<Footer> {
+    ensure!(
+        buf.len() == FOOTER_LEN,
+        InvalidBlockLen {
+            expect: FOOTER_LEN,
+            remaining: buf.len(),
+        }
+    );
+
+    let index_offset = buf.read_u64().context(ReadBytes)?;
+    let meta_offset = buf.read_u64().context(ReadBytes)?;
+    let meta_len = buf.read_u64().context(ReadBytes)?;
+    let version = buf.read_u8().context(ReadBytes)?;
+    let magic = buf.read_u32().context(ReadBytes)?;
+    ensure!(magic == MAGIC, InvalidMagic { given: magic });
+    ensure!(version == VERSION, UnsupportedVersion { given: version });
+
+    let file_size = meta_offset
+        .checked_add(meta_len)
+        .and_then(|v| v.checked_add(FOOTER_LEN as u64));
+    ensure!(
+        index_offset >= HEADER_LEN as u64
+            && index_offset <= meta_offset
+            && (meta_offset - index_offset) % BLOCK_INDEX_ENTRY_LEN as u64 == 0
+            && file_size.map_or(false, |v| v <= usize::MAX as u64),
+        InvalidFileLayout {
+            file_size: 0usize,
+            index_offset,
+            meta_offset,
+            meta_len,
+        }
+    );
+
+    Ok(Footer {
+        index_offset,
+        meta_offset,
+        meta_len,
+    })
+}
+
+/// Decode the block index and the sst meta data from `buf`, which holds the
+/// bytes in the [Footer::index_and_meta_range] of the file.
+pub fn decode_index_and_meta(
+    footer: &Footer,
+    buf: &[u8],
+) -> Result<(Vec<BlockIndexEntry>, SstMetaData)> {
+    let index_len = (footer.meta_offset - footer.index_offset) as usize;
+    let expect = index_len + footer.meta_len as usize;
+    ensure!(
+        buf.len() == expect,
+        InvalidBlockLen {
+            expect,
+            remaining: buf.len(),
+        }
+    );
+
+    let (mut index_buf, meta_buf) = buf.split_at(index_len);
+    let mut block_index = Vec::with_capacity(index_len / BLOCK_INDEX_ENTRY_LEN);
+    while !index_buf.is_empty() {
+        let offset = index_buf.read_u64().context(ReadBytes)?;
+        let len = index_buf.read_u32().context(ReadBytes)? as usize;
+        let num_rows = index_buf.read_u32().context(ReadBytes)? as usize;
+        let min_timestamp = Timestamp::new(index_buf.read_u64().context(ReadBytes)? as i64);
+        let max_timestamp = Timestamp::new(index_buf.read_u64().context(ReadBytes)? as i64);
+        ensure!(
+            offset >= HEADER_LEN as u64 && offset + len as u64 <= footer.index_offset,
+            InvalidBlockIndex {
+                offset,
+                len,
+                index_offset: footer.index_offset,
+            }
+        );
+
+        block_index.push(BlockIndexEntry {
+            offset,
+            len,
+            header: BlockHeader {
+                num_rows,
+                min_timestamp,
+                max_timestamp,
+            },
+        });
+    }
+
+    let meta_data_pb = SstMetaDataPb::parse_from_bytes(meta_buf).context(DecodeFromPb)?;
+    let meta_data = SstMetaData::try_from(meta_data_pb).context(ConvertSstMetaData)?;
+
+    Ok((block_index, meta_data))
+}
+
+/// Writer to write values bit by bit.
+#[derive(Debug, Default)]
+pub struct BitWriter {
+    buf: Vec<u8>,
+    /// Number of bits used in the last byte, 0 means the last byte is full.
+    used_bits: u32,
+}
+
+impl BitWriter {
+    pub fn write_bit(&mut self, bit: bool) {
+        self.write_bits(bit as u64, 1);
+    }
+
+    /// Write the lowest `num_bits` bits of `value`.
+    pub fn write_bits(&mut self, value: u64, mut num_bits: u32) {
+        debug_assert!(num_bits <= 64);
+
+        while num_bits > 0 {
+            if self.used_bits == 0 {
+                self.buf.push(0);
+            }
+            let free_bits = 8 - self.used_bits;
+            let n = free_bits.min(num_bits);
+            // Take the highest `n` bits of the remaining bits.
+            let bits = ((value >> (num_bits - n)) & ((1u64 << n) - 1)) as u8;
+            *self.buf.last_mut().unwrap() |= bits << (free_bits - n);
+
+            num_bits -= n;
+            self.used_bits = (self.used_bits + n) % 8;
+        }
+    }
+
+    /// Returns the written bytes, the unused bits of the last byte are zero.
+    pub fn finish(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+/// Reader to read values written by the [BitWriter].
+#[derive(Debug)]
+pub struct BitReader<'a> {
+    buf: &'a [u8],
+    /// Position of the next bit to read.
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    pub fn read_bit(&mut self) -> Result<bool> {
+        Ok(self.read_bits(1)? == 1)
+    }
+
+    /// Read `num_bits` bits as the lowest bits of the returned value.
+    pub fn read_bits(&mut self, mut num_bits: u32) -> Result<u64> {
+        debug_assert!(num_bits <= 64);
+        ensure!(
+            self.pos + num_bits as usize <= self.buf.len() * 8,
+            InsufficientBits
+        );
+
+        let mut value = 0u64;
+        while num_bits > 0 {
+            let byte = self.buf[self.pos / 8];
+            let offset = (self.pos % 8) as u32;
+            let n = (8 - offset).min(num_bits);
+            let bits = (byte >> (8 - offset - n)) & (((1u16 << n) - 1) as u8);
+            value = (value << n) | bits as u64;
+
+            num_bits -= n;
+            self.pos += n as usize;
+        }
+
+        Ok(value)
+    }
+}
+
+#[inline]
+fn zigzag_encode(v: i64) -> u64 {
+    ((v << 1) ^ (v >> 63)) as u64
+}
+
+#[inline]
+fn zigzag_decode(v: u64) -> i64 {
+    ((v >> 1) as i64) ^ -((v & 1) as i64)
+}
+
+/// Encode timestamps by delta-of-delta.
+///
+/// The first timestamp is stored as is, and the zigzag encoded delta-of-delta
+/// of the following timestamps is stored with a prefix indicating its width:
+/// - `0`: delta-of-delta is zero
+/// - `10`: 7 bits
+/// - `110`: 9 bits
+/// - `1110`: 12 bits
+/// - `1111`: 64 bits
+pub fn encode_timestamps(timestamps: &[i64]) -> Vec<u8> {
+    let mut writer = BitWriter::default();
+    let (mut prev, mut prev_delta) = (0i64, 0i64);
+    for (i, ts) in timestamps.iter().enumerate() {
+        if i == 0 {
+            writer.write_bits(*ts as u64, 64);
+            prev = *ts;
+            continue;
+        }
+
+        let delta = ts.wrapping_sub(prev);
+        let dod = zigzag_encode(delta.wrapping_sub(prev_delta));
+        if dod == 0 {
+            writer.write_bit(false);
+        } else if dod < (1 << 7) {
+            writer.write_bits(0b10, 2);
+            writer.write_bits(dod, 7);
+        } else if dod < (1 << 9) {
+            writer.write_bits(0b110, 3);
+            writer.write_bits(dod, 9);
+        } else if dod < (1 << 12) {
+            writer.write_bits(0b1110, 4);
+            writer.write_bits(dod, 12);
+        } else {
+            writer.write_bits(0b1111, 4);
+            writer.write_bits(dod, 64);
+        }
+
+        prev = *ts;
+        prev_delta = delta;
+    }
+
+    writer.finish()
+}
+
+/// Decode `num` timestamps encoded by [encode_timestamps].
+pub fn decode_timestamps(buf: &[u8], num: usize) -> Result<Vec<i64>> {
+    let mut reader = BitReader::new(buf);
+    let mut timestamps = Vec::with_capacity(num);
+    let (mut prev, mut prev_delta) = (0i64, 0i64);
+    for i in 0..num {
+        if i == 0 {
+            prev = reader.read_bits(64)? as i64;
+            timestamps.push(prev);
+            continue;
+        }
+
+        let mut num_prefix_bits = 0;
+        while num_prefix_bits < 4 && reader.read_bit()? {
+            num_prefix_bits += 1;
+        }
+        let dod = match num_prefix_bits {
+            0 => 0,
+            1 => reader.read_bits(7)?,
+            2 => reader.read_bits(9)?,
+            3 => reader.read_bits(12)?,
+            _ => reader.read_bits(64)?,
+        };
+
+        let delta = prev_delta.wrapping_add(zigzag_decode(dod));
+        prev = prev.wrapping_add(delta);
+        prev_delta = delta;
+        timestamps.push(prev);
+    }
+
+    Ok(timestamps)
+}
+
+/// Encode doubles by the XOR encoding of gorilla.
+///
+/// The first value is stored as is, and the XOR of the following value with
+/// its previous value is stored as:
+/// - `0`: the XOR is zero
+/// - `10`: the meaningful bits of the XOR fall in the window of the previous
+///   one, followed by the bits in the window
+/// - `11`: followed by the number of leading zeros in 5 bits, the length of the
+///   meaningful bits in 6 bits and the meaningful bits
+pub fn encode_doubles(values: &[f64]) -> Vec<u8> {
+    let mut writer = BitWriter::default();
+    let mut prev = 0u64;
+    // Leading and trailing zeros of the previous window.
+    let mut prev_window: Option<(u32, u32)> = None;
+    for (i, value) in values.iter().enumerate() {
+        let bits = value.to_bits();
+        if i == 0 {
+            writer.write_bits(bits, 64);
+            prev = bits;
+            continue;
+        }
+
+        let xor = bits ^ prev;
+        prev = bits;
+        if xor == 0 {
+            writer.write_bit(false);
+            continue;
+        }
+
+        let leading = xor.leading_zeros().min(31);
+        let trailing = xor.trailing_zeros();
+        match prev_window {
+            Some((prev_leading, prev_trailing))
+                if leading >= prev_leading && trailing >= prev_trailing =>
+            {
+                writer.write_bits(0b10, 2);
+                writer.write_bits(xor >> prev_trailing, 64 - prev_leading - prev_trailing);
+            }
+            _ => {
+                let meaningful_bits = 64 - leading - trailing;
+                writer.write_bits(0b11, 2);
+                writer.write_bits(leading as u64, 5);
+                // The length 64 is stored as 0, a meaningful length is never 0.
+                writer.write_bits((meaningful_bits % 64) as u64, 6);
+                writer.write_bits(xor >> trailing, meaningful_bits);
+                prev_window = Some((leading, trailing));
+            }
+        }
+    }
+
+    writer.finish()
+}
+
+/// Decode `num` doubles encoded by [encode_doubles].
+pub fn decode_doubles(buf: &[u8], num: usize) -> Result<Vec<f64>> {
+    let mut reader = BitReader::new(buf);
+    let mut values = Vec::with_capacity(num);
+    let mut prev = 0u64;
+    let (mut prev_leading, mut prev_trailing) = (0u32, 0u32);
+    for i in 0..num {
+        if i == 0 {
+            prev = reader.read_bits(64)?;
+            values.push(f64::from_bits(prev));
+            continue;
+        }
+
+        if reader.read_bit()? {
+            if reader.read_bit()? {
+                prev_leading = reader.read_bits(5)? as u32;
+                let meaningful_bits = match reader.read_bits(6)? as u32 {
+                    0 => 64,
+                    n => n,
+                };
+                prev_trailing = (64 - prev_leading).saturating_sub(meaningful_bits);
+            }
+
+            let meaningful_bits = 64 - prev_leading - prev_trailing;
+            let xor = reader.read_bits(meaningful_bits)? << prev_trailing;
+            prev ^= xor;
+        }
+
+        values.push(f64::from_bits(prev));
+    }
+
+    Ok(values)
+}
+
+/// How a column is encoded in the block.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ColumnEncoding {
+    /// Delta-of-delta encoding for the timestamp key.
+    Timestamp,
+    /// Only the first value is stored as the key is same in a series.
+    SeriesKey,
+    /// Gorilla encoding with null bitmap.
+    Double,
+    /// Mem compact encoding of every datum.
+    Compact,
+}
+
+impl ColumnEncoding {
+    fn of_column(schema: &Schema, index: usize) -> Self {
+        if index == schema.timestamp_index() {
+            ColumnEncoding::Timestamp
+        } else if index < schema.num_key_columns() {
+            ColumnEncoding::SeriesKey
+        } else if schema.column(index).data_type == DatumKind::Double {
+            ColumnEncoding::Double
+        } else {
+            ColumnEncoding::Compact
+        }
+    }
+}
+
+/// Returns the indexes of the key columns except the timestamp key, the rows
+/// with the same values of these columns are in the same series.
+pub fn series_key_indexes(schema: &Schema) -> Vec<usize> {
+    (0..schema.num_key_columns())
+        .filter(|i| *i != schema.timestamp_index())
+        .collect()
+}
+
+/// Encode the rows of one series, organized by columns of the `schema`, into
+/// one block.
+///
+/// Returns the header of the encoded block.
+///
+/// REQUIRE: `columns` must have the same number of rows and not be empty.
+pub fn encode_block(
+    schema: &Schema,
+    columns: &[Vec<Datum>],
+    buf: &mut Vec<u8>,
+) -> Result<BlockHeader> {
+    let num_rows = columns[schema.timestamp_index()].len();
+    let mut min_ts = i64::MAX;
+    let mut max_ts = i64::MIN;
+
+    let mut column_buf = Vec::new();
+    let mut body = Vec::new();
+    for (index, column) in columns.iter().enumerate() {
+        column_buf.clear();
+        match ColumnEncoding::of_column(schema, index) {
+            ColumnEncoding::Timestamp => {
+                let mut timestamps = Vec::with_capacity(num_rows);
+                for datum in column {
+                    let ts = match datum.as_timestamp() {
+                        Some(ts) => ts.as_i64(),
+                        None => return NullTimestamp.fail(),
+                    };
+                    min_ts = min_ts.min(ts);
+                    max_ts = max_ts.max(ts);
+                    timestamps.push(ts);
+                }
+                column_buf.extend_from_slice(&encode_timestamps(&timestamps));
+            }
+            ColumnEncoding::SeriesKey => {
+                MemCompactEncoder
+                    .encode(&mut column_buf, &column[0])
+                    .context(EncodeDatum)?;
+            }
+            ColumnEncoding::Double => {
+                let values: Vec<_> = column.iter().filter_map(|v| v.as_f64()).collect();
+                if values.len() == num_rows {
+                    column_buf.write_u8(0).unwrap();
+                } else {
+                    column_buf.write_u8(1).unwrap();
+                    let mut bitmap = vec![0u8; (num_rows + 7) / 8];
+                    for (i, datum) in column.iter().enumerate() {
+                        if !datum.is_null() {
+                            bitmap[i / 8] |= 1 << (i % 8);
+                        }
+                    }
+                    column_buf.extend_from_slice(&bitmap);
+                }
+                column_buf.extend_from_slice(&encode_doubles(&values));
+            }
+            ColumnEncoding::Compact => {
+                for datum in column {
+                    MemCompactEncoder
+                        .encode(&mut column_buf, datum)
+                        .context(EncodeDatum)?;
+                }
+            }
+        }
+
+        body.write_u32(column_buf.len() as u32).unwrap();
+        body.extend_from_slice(&column_buf);
+    }
+
+    buf.write_u32(body.len() as u32).unwrap();
+    buf.write_u32(num_rows as u32).unwrap();
+    buf.write_u64(min_ts as u64).unwrap();
+    buf.write_u64(max_ts as u64).unwrap();
+    buf.extend_from_slice(&body);
+
+    Ok(BlockHeader {
+        num_rows,
+        min_timestamp: Timestamp::new(min_ts),
+        max_timestamp: Timestamp::new(max_ts),
+    })
+}
+
+/// Header of an encoded block.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BlockHeader {
+    pub num_rows: usize,
+    pub min_timestamp: Timestamp,
+    pub max_timestamp: Timestamp,
+}
+
+/// Decode the next block from `buf`, returns the header and the encoded
+/// columns of the block, and `buf` is advanced to the next block.
+pub fn next_block<'a>(buf: &mut &'a [u8]) -> Result<(BlockHeader, &'a [u8])> {
+    let block_len = buf.read_u32().context(ReadBytes)? as usize;
+    let num_rows = buf.read_u32().context(ReadBytes)? as usize;
+    let min_timestamp = Timestamp::new(buf.read_u64().context(ReadBytes)? as i64);
+    let max_timestamp = Timestamp::new(buf.read_u64().context(ReadBytes)? as i64);
+    ensure!(
+        block_len <= buf.len(),
+        InvalidBlockLen {
+            expect: block_len,
+            remaining: buf.len(),
+        }
+    );
+
+    let (body, remaining) = buf.split_at(block_len);
+    *buf = remaining;
+    let header = BlockHeader {
+        num_rows,
+        min_timestamp,
+        max_timestamp,
+    };
+
+    Ok((header, body))
+}
+
+/// Decode the columns of a block, only the columns whose `projection` is true
+/// are decoded and others are `None`.
+pub fn decode_block(
+    schema: &Schema,
+    header: &BlockHeader,
+    mut body: &[u8],
+    projection: &[bool],
+) -> Result<Vec<Option<Vec<Datum>>>> {
+    let num_rows = header.num_rows;
+    let mut columns = Vec::with_capacity(schema.num_columns());
+    for (index, need_decode) in projection.iter().enumerate() {
+        let column_len = body.read_u32().context(ReadBytes)? as usize;
+        ensure!(
+            column_len <= body.len(),
+            InvalidBlockLen {
+                expect: column_len,
+                remaining: body.len(),
+            }
+        );
+        let (mut column_buf, remaining) = body.split_at(column_len);
+        body = remaining;
+
+        if !need_decode {
+            columns.push(None);
+            continue;
+        }
+
+        let datum_kind = &schema.column(index).data_type;
+        let column = match ColumnEncoding::of_column(schema, index) {
+            ColumnEncoding::Timestamp => decode_timestamps(column_buf, num_rows)?
+                .into_iter()
+                .map(|v| Datum::Timestamp(Timestamp::new(v)))
+                .collect(),
+            ColumnEncoding::SeriesKey => {
+                let mut datum = Datum::empty(datum_kind);
+                MemCompactDecoder
+                    .decode_to(&mut column_buf, &mut datum)
+                    .context(DecodeDatum)?;
+                vec![datum; num_rows]
+            }
+            ColumnEncoding::Double => {
+                let has_null = column_buf.read_u8().context(ReadBytes)? != 0;
+                if has_null {
+                    let bitmap_len = (num_rows + 7) / 8;
+                    ensure!(
+                        bitmap_len <= column_buf.len(),
+                        InvalidBlockLen {
+                            expect: bitmap_len,
+                            remaining: column_buf.len(),
+                        }
+                    );
+                    let (bitmap, values_buf) = column_buf.split_at(bitmap_len);
+                    let is_valid = |i: usize| bitmap[i / 8] & (1 << (i % 8)) != 0;
+                    let num_values = (0..num_rows).filter(|i| is_valid(*i)).count();
+                    let mut values = decode_doubles(values_buf, num_values)?.into_iter();
+                    (0..num_rows)
+                        .map(|i| {
+                            if is_valid(i) {
+                                values.next().map(Datum::Double).unwrap_or(Datum::Null)
+                            } else {
+                                Datum::Null
+                            }
+                        })
+                        .collect()
+                } else {
+                    decode_doubles(column_buf, num_rows)?
+                        .into_iter()
+                        .map(Datum::Double)
+                        .collect()
+                }
+            }
+            ColumnEncoding::Compact => {
+                let mut datums = Vec::with_capacity(num_rows);
+                for _ in 0..num_rows {
+                    let mut datum = Datum::empty(datum_kind);
+                    MemCompactDecoder
+                        .decode_to(&mut column_buf, &mut datum)
+                        .context(DecodeDatum)?;
+                    datums.push(datum);
+                }
+                datums
+            }
+        };
+        columns.push(Some(column));
+    }
+
+    Ok(columns)
+}
+
+#[cfg(test)]
+mod tests {
+    use common_types::tests::build_schema;
+
+    use super::*;
+
+    #[test]
+    fn test_bit_writer_and_reader() {
+        let mut writer = BitWriter::default();
+        writer.write_bit(true);
+        writer.write_bits(0b101, 3);
+        writer.write_bits(u64::MAX, 64);
+        writer.write_bits(0x1234, 13);
+        let buf = writer.finish();
+        assert_eq!((1 + 3 + 64 + 13 + 7) / 8, buf.len());
+
+        let mut reader = BitReader::new(&buf);
+        assert!(reader.read_bit().unwrap());
+        assert_eq!(0b101, reader.read_bits(3).unwrap());
+        assert_eq!(u64::MAX, reader.read_bits(64).unwrap());
+        assert_eq!(0x1234, reader.read_bits(13).unwrap());
+        assert!(reader.read_bits(8).is_err());
+    }
+
+    #[test]
+    fn test_timestamps_codec() {
+        let cases = vec![
+            vec![],
+            vec![1000],
+            vec![1000, 2000, 3000, 4000, 5000],
+            vec![1000, 1010, 1009, 1300, 5000, 5000, i64::MAX, i64::MIN, 0],
+            vec![-5, -3, 0, 100, 100_000, 100_001],
+        ];
+
+        for timestamps in cases {
+            let buf = encode_timestamps(&timestamps);
+            let decoded = decode_timestamps(&buf, timestamps.len()).unwrap();
+            assert_eq!(timestamps, decoded);
+        }
+
+        // Timestamps with fixed interval only take one bit except the first two.
+        let timestamps: Vec<_> = (0..1000).map(|i| 1_600_000_000_000 + i * 10_000).collect();
+        let buf = encode_timestamps(&timestamps);
+        assert!(buf.len() < 8 + 8 + 1000 / 8 + 1);
+    }
+
+    #[test]
+    fn test_doubles_codec() {
+        let cases = vec![
+            vec![],
+            vec![1.0],
+            vec![1.0, 1.0, 1.0, 2.0, 2.5, 3.75, -1.0, 0.0],
+            vec![
+                f64::MAX,
+                f64::MIN,
+                f64::EPSILON,
+                f64::INFINITY,
+                1e-300,
+                12.5,
+            ],
+            (0..100).map(|i| i as f64 * 0.1).collect(),
+        ];
+
+        for values in cases {
+            let buf = encode_doubles(&values);
+            let decoded = decode_doubles(&buf, values.len()).unwrap();
+            assert_eq!(values, decoded);
+        }
+
+        let nan = decode_doubles(&encode_doubles(&[1.0, f64::NAN]), 2).unwrap();
+        assert!(nan[1].is_nan());
+    }
+
+    #[test]
+    fn test_block_codec() {
+        let schema = build_schema();
+        let num_rows = 3;
+        let columns = vec![
+            vec![Datum::Varbinary(common_types::bytes::Bytes::from_static(b"a")); num_rows],
+            (0..num_rows)
+                .map(|i| Datum::Timestamp(Timestamp::new(100 + i as i64)))
+                .collect(),
+            vec![Datum::Double(1.0), Datum::Null, Datum::Double(3.0)],
+            vec![
+                Datum::String("v1".into()),
+                Datum::Null,
+                Datum::String("v3".into()),
+            ],
+        ];
+
+        let expect_header = BlockHeader {
+            num_rows,
+            min_timestamp: Timestamp::new(100),
+            max_timestamp: Timestamp::new(102),
+        };
+        let mut buf = Vec::new();
+        for _ in 0..2 {
+            let header = encode_block(&schema, &columns, &mut buf).unwrap();
+            assert_eq!(expect_header, header);
+        }
+
+        let mut blocks = &buf[..];
+        for _ in 0..2 {
+            let (header, body) = next_block(&mut blocks).unwrap();
+            assert_eq!(expect_header, header);
+
+            let decoded = decode_block(&schema, &header, body, &[true; 4]).unwrap();
+            let decoded: Vec<_> = decoded.into_iter().map(Option::unwrap).collect();
+            assert_eq!(columns, decoded);
+
+            let decoded =
+                decode_block(&schema, &header, body, &[false, true, false, true]).unwrap();
+            assert!(decoded[0].is_none());
+            assert_eq!(Some(&columns[3]), decoded[3].as_ref());
+        }
+        assert!(blocks.is_empty());
+    }
+
+    #[test]
+    fn test_index_meta_and_footer_codec() {
+        let schema = build_schema();
+        let columns = vec![
+            vec![Datum::Varbinary(common_types::bytes::Bytes::from_static(
+                b"a",
+            ))],
+            vec![Datum::Timestamp(Timestamp::new(100))],
+            vec![Datum::Double(1.0)],
+            vec![Datum::String("v1".into())],
+        ];
+
+        let mut buf = Vec::new();
+        encode_header(&mut buf);
+        let mut block_index = Vec::new();
+        for _ in 0..3 {
+            let offset = buf.len() as u64;
+            let header = encode_block(&schema, &columns, &mut buf).unwrap();
+            block_index.push(BlockIndexEntry {
+                offset,
+                len: buf.len() - offset as usize,
+                header,
+            });
+        }
+        let meta_data = SstMetaData {
+            min_key: common_types::bytes::Bytes::from_static(b"a"),
+            max_key: common_types::bytes::Bytes::from_static(b"a"),
+            time_range: common_types::time::TimeRange::min_to_max(),
+            max_sequence: 10,
+            schema: schema.clone(),
+            size: 0,
+            row_num: 3,
+            sst_filter: None,
+            inverted_index: None,
+        };
+        encode_index_meta_and_footer(&block_index, meta_data.clone(), &mut buf).unwrap();
+
+        let file_size = buf.len();
+        let footer = decode_footer(&buf[file_size - FOOTER_LEN..]).unwrap();
+        footer.check_file_size(file_size).unwrap();
+        assert!(footer.check_file_size(file_size + 1).is_err());
+        let index_and_meta = &buf[footer.index_and_meta_range()];
+        let (decoded_index, decoded_meta) = decode_index_and_meta(&footer, index_and_meta).unwrap();
+        assert_eq!(block_index, decoded_index);
+        assert_eq!(meta_data, decoded_meta);
+
+        // Every block can be decoded from its own range.
+        for entry in &decoded_index {
+            let mut block = &buf[entry.range()];
+            let (header, _) = next_block(&mut block).unwrap();
+            assert_eq!(entry.header, header);
+            assert!(block.is_empty());
+        }
+
+        assert!(decode_footer(&buf[..FOOTER_LEN]).is_err());
+    }
+}
@@ -0,0 +1,11 @@
+// Copyright 2022 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Sst implementation of the time series format.
+//!
+//! Rows of the same series are grouped into blocks, the timestamps are
+//! encoded by delta-of-delta and the doubles are encoded by the XOR encoding
+//! of gorilla, see [encoding] for details.
+
+pub mod builder;
+pub mod encoding;
+pub mod reader;
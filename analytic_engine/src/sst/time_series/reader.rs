@@ -0,0 +1,502 @@
+// Copyright 2022 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Sst reader implementation of the time series format.
+
+use std::{
+    ops::Range,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Instant,
+};
+
+use async_trait::async_trait;
+use common_types::{
+    bytes::Bytes,
+    datum::Datum,
+    projected_schema::{ProjectedSchema, RowProjector},
+    record_batch::{RecordBatchWithKey, RecordBatchWithKeyBuilder},
+    row::Row,
+    schema::Schema,
+    time::{TimeRange, Timestamp},
+};
+use common_util::runtime::Runtime;
+use futures::Stream;
+use log::{debug, error, trace};
+use object_store::{ObjectStore, Path};
+use parquet::DataCacheRef;
+use snafu::{ensure, ResultExt};
+use table_engine::predicate::PredicateRef;
+use tokio::sync::mpsc::{self, Receiver, Sender};
+
+use crate::sst::{
+    factory::SstReaderOptions,
+    file::SstMetaData,
+    reader::{error::*, SstReader},
+    time_series::encoding::{self, BlockHeader, BlockIndexEntry},
+};
+
+const DEFAULT_CHANNEL_CAP: usize = 1000;
+
+/// Read a range of the sst file from the storage.
+async fn read_range<S: ObjectStore>(
+    storage: &S,
+    path: &Path,
+    range: Range<usize>,
+) -> Result<Bytes> {
+    storage
+        .get_range(path, range)
+        .await
+        .map_err(|e| Box::new(e) as _)
+        .with_context(|| ReadPersist {
+            path: path.to_string(),
+        })
+}
+
+fn format_meta_key(path: &Path) -> String {
+    format!("{}_meta", path)
+}
+
+fn format_block_key(path: &Path, entry: &BlockIndexEntry) -> String {
+    format!("{}_{}_{}", path, entry.offset, entry.len)
+}
+
+/// Read the block index and the meta data of the time series sst.
+///
+/// The footer at the end of the file is read first to locate the block index
+/// and the meta data, and their bytes are cached in the `data_cache` so the
+/// following reads of the same file needn't touch the storage.
+pub async fn read_sst_meta<S: ObjectStore>(
+    storage: &S,
+    path: &Path,
+    data_cache: &Option<DataCacheRef>,
+) -> Result<(Vec<BlockIndexEntry>, SstMetaData)> {
+    let meta_key = format_meta_key(path);
+    let cached = data_cache.as_ref().and_then(|cache| cache.get(&meta_key));
+    let buf = match cached {
+        Some(v) => v,
+        None => {
+            let file_size = storage
+                .head(path)
+                .await
+                .map_err(|e| Box::new(e) as _)
+                .with_context(|| ReadPersist {
+                    path: path.to_string(),
+                })?
+                .size;
+            ensure!(file_size >= encoding::FOOTER_LEN, SstMetaNotFound);
+
+            let footer_buf =
+                read_range(storage, path, file_size - encoding::FOOTER_LEN..file_size).await?;
+            let footer = encoding::decode_footer(&footer_buf)
+                .and_then(|footer| footer.check_file_size(file_size).map(|_| footer))
+                .map_err(|e| Box::new(e) as _)
+                .context(DecodeSstMeta)?;
+            let index_and_meta = read_range(storage, path, footer.index_and_meta_range()).await?;
+
+            // The footer is kept at the end to decode the cached bytes.
+            let mut buf = Vec::with_capacity(index_and_meta.len() + footer_buf.len());
+            buf.extend_from_slice(&index_and_meta);
+            buf.extend_from_slice(&footer_buf);
+            let buf = Arc::new(buf);
+            if let Some(cache) = data_cache {
+                cache.put(meta_key, buf.clone());
+            }
+            buf
+        }
+    };
+
+    decode_index_and_meta(&buf)
+        .map_err(|e| Box::new(e) as _)
+        .context(DecodeSstMeta)
+}
+
+/// Decode the block index and the meta data from the bytes of them followed by
+/// the footer.
+fn decode_index_and_meta(buf: &[u8]) -> encoding::Result<(Vec<BlockIndexEntry>, SstMetaData)> {
+    let split_at = buf.len().saturating_sub(encoding::FOOTER_LEN);
+    let (index_and_meta, footer_buf) = buf.split_at(split_at);
+    let footer = encoding::decode_footer(footer_buf)?;
+    encoding::decode_index_and_meta(&footer, index_and_meta)
+}
+
+/// The implementation of sst in time series format based on object storage.
+pub struct TimeSeriesSstReader<'a, S: ObjectStore> {
+    /// The path where the data is persisted.
+    path: &'a Path,
+    /// The storage where the data is persist.
+    storage: &'a S,
+    projected_schema: ProjectedSchema,
+    predicate: PredicateRef,
+    meta_data: Option<SstMetaData>,
+    /// Index of the blocks in the sst file.
+    block_index: Vec<BlockIndexEntry>,
+    data_cache: Option<DataCacheRef>,
+    /// The batch of rows in one `record_batch`.
+    batch_size: usize,
+    /// Read the rows in reverse order.
+    reverse: bool,
+    channel_cap: usize,
+
+    runtime: Arc<Runtime>,
+}
+
+impl<'a, S: ObjectStore> TimeSeriesSstReader<'a, S> {
+    pub fn new(path: &'a Path, storage: &'a S, options: &SstReaderOptions) -> Self {
+        Self {
+            path,
+            storage,
+            projected_schema: options.projected_schema.clone(),
+            predicate: options.predicate.clone(),
+            meta_data: None,
+            block_index: Vec::new(),
+            data_cache: options.data_cache.clone(),
+            batch_size: options.read_batch_row_num,
+            reverse: options.reverse,
+            channel_cap: DEFAULT_CHANNEL_CAP,
+            runtime: options.runtime.clone(),
+        }
+    }
+}
+
+impl<'a, S: ObjectStore> TimeSeriesSstReader<'a, S> {
+    async fn init_if_necessary(&mut self) -> Result<()> {
+        if self.meta_data.is_some() {
+            return Ok(());
+        }
+
+        let (block_index, sst_meta) =
+            read_sst_meta(self.storage, self.path, &self.data_cache).await?;
+
+        self.block_index = block_index;
+        self.meta_data = Some(sst_meta);
+
+        Ok(())
+    }
+
+    fn new_block_reader(&self) -> Result<BlockReader> {
+        let meta_data = self.meta_data.as_ref().unwrap();
+        let schema = meta_data.schema.clone();
+        let matched_tsids = meta_data
+            .inverted_index
+            .as_ref()
+            .and_then(|v| v.match_tsids(&schema, &self.predicate.exprs));
+        let projected_schema = self.projected_schema.clone();
+        let row_projector = projected_schema
+            .try_project_with_key(&schema)
+            .map_err(|e| Box::new(e) as _)
+            .context(Projection)?;
+
+        Ok(BlockReader {
+            file_path: self.path.to_string(),
+            schema,
+            projected_schema,
+            row_projector,
+            time_range: self.predicate.time_range,
+            matched_tsids,
+            batch_size: self.batch_size,
+            reverse: self.reverse,
+        })
+    }
+
+    /// Fetch the blocks needed by the `reader`, the adjacent blocks not cached
+    /// are fetched by one ranged read.
+    async fn fetch_blocks(&self, reader: &BlockReader) -> Result<Vec<Arc<Vec<u8>>>> {
+        if let Some(tsids) = &reader.matched_tsids {
+            if tsids.is_empty() {
+                // No series matched, the whole sst can be skipped.
+                return Ok(Vec::new());
+            }
+        }
+
+        let entries: Vec<_> = self
+            .block_index
+            .iter()
+            .filter(|entry| {
+                let need_read = reader.need_read(&entry.header);
+                if !need_read {
+                    trace!(
+                        "Skip block of the sst:{}, block:{:?}, time_range:{:?}",
+                        reader.file_path,
+                        entry,
+                        reader.time_range
+                    );
+                }
+                need_read
+            })
+            .collect();
+
+        let mut blocks: Vec<Option<Arc<Vec<u8>>>> = entries
+            .iter()
+            .map(|entry| {
+                self.data_cache
+                    .as_ref()
+                    .and_then(|cache| cache.get(&format_block_key(self.path, entry)))
+            })
+            .collect();
+
+        let mut start = 0;
+        while start < entries.len() {
+            if blocks[start].is_some() {
+                start += 1;
+                continue;
+            }
+
+            let mut end = start + 1;
+            while end < entries.len()
+                && blocks[end].is_none()
+                && entries[end].offset == entries[end - 1].range().end as u64
+            {
+                end += 1;
+            }
+
+            let range_start = entries[start].range().start;
+            let range = range_start..entries[end - 1].range().end;
+            let buf = read_range(self.storage, self.path, range).await?;
+            for (entry, block) in entries[start..end].iter().zip(&mut blocks[start..end]) {
+                let block_range = entry.range();
+                let fetched = Arc::new(
+                    buf[block_range.start - range_start..block_range.end - range_start].to_vec(),
+                );
+                if let Some(cache) = &self.data_cache {
+                    cache.put(format_block_key(self.path, entry), fetched.clone());
+                }
+                *block = Some(fetched);
+            }
+            start = end;
+        }
+
+        Ok(blocks.into_iter().map(Option::unwrap).collect())
+    }
+
+    fn read_record_batches(
+        &self,
+        reader: BlockReader,
+        blocks: Vec<Arc<Vec<u8>>>,
+        tx: Sender<Result<RecordBatchWithKey>>,
+    ) {
+        let _ = self.runtime.spawn_blocking(move || {
+            let path = reader.file_path.clone();
+            debug!(
+                "begin reading record batch from the time series sst:{}, reader:{:?}, block_num:{}",
+                path,
+                reader,
+                blocks.len(),
+            );
+
+            let mut send_failed = false;
+            let send = |v| -> Result<()> {
+                tx.blocking_send(v)
+                    .map_err(|e| {
+                        send_failed = true;
+                        Box::new(e) as _
+                    })
+                    .context(Other)?;
+                Ok(())
+            };
+
+            let start_fetch = Instant::now();
+            match reader.fetch_and_send_record_batch(&blocks, send) {
+                Ok(row_num) => {
+                    debug!(
+                        "finish reading record batch({} rows) from the sst:{}, time cost:{:?}",
+                        row_num,
+                        path,
+                        start_fetch.elapsed(),
+                    );
+                }
+                Err(e) => {
+                    if send_failed {
+                        error!("fail to send the fetched record batch result, err:{}", e);
+                    } else {
+                        error!(
+                            "failed to read record batch from the sst:{}, err:{}",
+                            path, e
+                        );
+                        let _ = tx.blocking_send(Err(e));
+                    }
+                }
+            }
+        });
+    }
+}
+
+/// A reader decoding the blocks of the time series sst.
+#[derive(Debug)]
+struct BlockReader {
+    file_path: String,
+    schema: Schema,
+    projected_schema: ProjectedSchema,
+    row_projector: RowProjector,
+    time_range: TimeRange,
+    /// Tsids of the series matched by the inverted index, `None` if the
+    /// predicate can't be answered by the index.
+    matched_tsids: Option<Vec<u64>>,
+    batch_size: usize,
+    reverse: bool,
+}
+
+impl BlockReader {
+    /// Whether the block may contain rows in the time range to read.
+    fn need_read(&self, header: &BlockHeader) -> bool {
+        let block_range = TimeRange::new_unchecked(
+            header.min_timestamp,
+            header
+                .max_timestamp
+                .checked_add_i64(1)
+                .unwrap_or(Timestamp::MAX),
+        );
+        self.time_range.intersect_with(block_range)
+    }
+
+    /// Whether the series of the decoded block is matched by the inverted
+    /// index, all the rows of a block belong to the same series.
+    fn is_series_matched(&self, columns: &[Option<Vec<Datum>>]) -> bool {
+        let (tsids, tsid_index) = match (&self.matched_tsids, self.schema.index_of_tsid()) {
+            (Some(tsids), Some(index)) => (tsids, index),
+            _ => return true,
+        };
+
+        match columns[tsid_index].as_ref().and_then(|v| v.first()) {
+            Some(Datum::UInt64(tsid)) => tsids.binary_search(tsid).is_ok(),
+            _ => true,
+        }
+    }
+
+    /// Decode the fetched blocks and return the record batches.
+    fn read_record_batches(&self, blocks: &[Arc<Vec<u8>>]) -> Result<Vec<RecordBatchWithKey>> {
+        let mut projection = vec![false; self.schema.num_columns()];
+        for index in self.row_projector.existed_source_projection() {
+            projection[index] = true;
+        }
+        // The tsid is needed to check whether the series is matched.
+        if let (Some(_), Some(index)) = (&self.matched_tsids, self.schema.index_of_tsid()) {
+            projection[index] = true;
+        }
+
+        let mut record_batches = Vec::new();
+        let mut builder = RecordBatchWithKeyBuilder::with_capacity(
+            self.projected_schema.to_record_schema_with_key(),
+            self.batch_size,
+        );
+        for block in blocks {
+            let (header, body) = encoding::next_block(&mut &block[..])
+                .map_err(|e| Box::new(e) as _)
+                .context(DecodeRecordBatch)?;
+
+            let columns = encoding::decode_block(&self.schema, &header, body, &projection)
+                .map_err(|e| Box::new(e) as _)
+                .context(DecodeRecordBatch)?;
+            if !self.is_series_matched(&columns) {
+                continue;
+            }
+
+            for row_index in 0..header.num_rows {
+                let datums = self
+                    .row_projector
+                    .source_projection()
+                    .iter()
+                    .map(|source_index| match source_index {
+                        Some(i) => columns[*i].as_ref().unwrap()[row_index].clone(),
+                        // The column is not in the sst, fill by null.
+                        None => Datum::Null,
+                    })
+                    .collect();
+                builder
+                    .append_row(Row::from_datums(datums))
+                    .map_err(|e| Box::new(e) as _)
+                    .context(DecodeRecordBatch)?;
+
+                if builder.len() >= self.batch_size {
+                    let record_batch = builder
+                        .build()
+                        .map_err(|e| Box::new(e) as _)
+                        .context(DecodeRecordBatch)?;
+                    record_batches.push(record_batch);
+                }
+            }
+        }
+
+        if !builder.is_empty() {
+            let record_batch = builder
+                .build()
+                .map_err(|e| Box::new(e) as _)
+                .context(DecodeRecordBatch)?;
+            record_batches.push(record_batch);
+        }
+
+        Ok(record_batches)
+    }
+
+    /// Fetch the record batches from the `blocks` and send them.
+    /// Returns the fetched row number.
+    fn fetch_and_send_record_batch(
+        self,
+        blocks: &[Arc<Vec<u8>>],
+        mut send: impl FnMut(Result<RecordBatchWithKey>) -> Result<()>,
+    ) -> Result<usize> {
+        let mut record_batches = self.read_record_batches(blocks)?;
+        if self.reverse {
+            record_batches.reverse();
+            for record_batch in &mut record_batches {
+                record_batch
+                    .reverse_data()
+                    .map_err(|e| Box::new(e) as _)
+                    .context(DecodeRecordBatch)?;
+            }
+        }
+
+        let mut row_num = 0;
+        for record_batch in record_batches {
+            trace!(
+                "Fetch one record batch from sst:{}, num_rows:{}",
+                self.file_path,
+                record_batch.num_rows()
+            );
+
+            row_num += record_batch.num_rows();
+            send(Ok(record_batch))?;
+        }
+
+        Ok(row_num)
+    }
+}
+
+struct RecordBatchReceiver {
+    rx: Receiver<Result<RecordBatchWithKey>>,
+}
+
+impl Stream for RecordBatchReceiver {
+    type Item = Result<RecordBatchWithKey>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.as_mut().rx.poll_recv(cx)
+    }
+}
+
+#[async_trait]
+impl<'a, S: ObjectStore> SstReader for TimeSeriesSstReader<'a, S> {
+    async fn meta_data(&mut self) -> Result<&SstMetaData> {
+        self.init_if_necessary().await?;
+        Ok(self.meta_data.as_ref().unwrap())
+    }
+
+    async fn read(
+        &mut self,
+    ) -> Result<Box<dyn Stream<Item = Result<RecordBatchWithKey>> + Send + Unpin>> {
+        debug!(
+            "read time series sst:{}, projected_schema:{:?}, predicate:{:?}",
+            self.path.to_string(),
+            self.projected_schema,
+            self.predicate
+        );
+
+        self.init_if_necessary().await?;
+        let reader = self.new_block_reader()?;
+        let blocks = self.fetch_blocks(&reader).await?;
+        let (tx, rx) = mpsc::channel::<Result<RecordBatchWithKey>>(self.channel_cap);
+        self.read_record_batches(reader, blocks, tx);
+
+        Ok(Box::new(RecordBatchReceiver { rx }))
+    }
+}
@@ -1,5 +1,6 @@
 // Copyright 2022 CeresDB Project Authors. Licensed under Apache-2.0.
 
+use common_util::config::{ReadableDuration, ReadableSize};
 use serde::Deserialize;
 
 /// Options for storage backend
@@ -7,6 +8,7 @@ use serde::Deserialize;
 pub enum StorageOptions {
     Local(LocalOptions),
     Aliyun(AliyunOptions),
+    S3(S3Options),
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -21,3 +23,108 @@ pub struct AliyunOptions {
     pub endpoint: String,
     pub bucket: String,
 }
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct S3Options {
+    /// Endpoint of the S3 service, e.g. `https://s3.us-east-1.amazonaws.com`
+    /// or `http://127.0.0.1:9000` for MinIO.
+    pub endpoint: String,
+    pub bucket: String,
+    pub region: String,
+    pub key_id: String,
+    pub key_secret: String,
+    /// Session token of the temporary credentials.
+    #[serde(default)]
+    pub session_token: Option<String>,
+    /// Use the virtual-hosted-style url instead of the path-style url.
+    #[serde(default)]
+    pub virtual_hosted_style: bool,
+    /// Timeout to connect the S3 service.
+    #[serde(default = "default_s3_connect_timeout")]
+    pub connect_timeout: ReadableDuration,
+    /// Timeout to wait for the response or the next chunk of the body.
+    #[serde(default = "default_s3_read_timeout")]
+    pub read_timeout: ReadableDuration,
+    /// Max times to retry the request failed by the server errors,
+    /// throttling or the broken connections.
+    #[serde(default = "default_s3_max_retries")]
+    pub max_retries: usize,
+}
+
+fn default_s3_connect_timeout() -> ReadableDuration {
+    ReadableDuration::secs(10)
+}
+
+fn default_s3_read_timeout() -> ReadableDuration {
+    ReadableDuration::secs(30)
+}
+
+fn default_s3_max_retries() -> usize {
+    3
+}
+
+/// Options for the disk cache in front of the storage backend
+#[derive(Debug, Clone, Deserialize)]
+pub struct DiskCacheOptions {
+    /// Directory of the cache files.
+    pub path: String,
+    /// Max bytes of the cached files.
+    pub capacity: ReadableSize,
+    /// Size of the page to cache, the objects are fetched and cached by pages.
+    #[serde(default = "default_disk_cache_page_size")]
+    pub page_size: ReadableSize,
+}
+
+fn default_disk_cache_page_size() -> ReadableSize {
+    ReadableSize::mb(1)
+}
+
+/// Options for manifest storage backend
+#[derive(Debug, Clone, Deserialize)]
+pub enum ManifestStorageOptions {
+    /// Store the manifest in the wal.
+    Wal,
+    /// Store the manifest in the object store of the sst files.
+    ObjectStore,
+}
+
+impl Default for ManifestStorageOptions {
+    fn default() -> Self {
+        ManifestStorageOptions::Wal
+    }
+}
+
+/// Options for wal storage backend
+#[derive(Debug, Clone, Deserialize)]
+pub enum WalStorageOptions {
+    RocksDB,
+    File(FileWalOptions),
+}
+
+impl Default for WalStorageOptions {
+    fn default() -> Self {
+        WalStorageOptions::RocksDB
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct FileWalOptions {
+    /// Max size of a wal segment file.
+    pub segment_size: ReadableSize,
+    /// Whether to sync the segment file after every write.
+    pub sync_on_write: bool,
+    /// Interval to sync the segment file if `sync_on_write` is disabled, the
+    /// writes in the last interval may be lost on crash.
+    pub sync_interval: ReadableDuration,
+}
+
+impl Default for FileWalOptions {
+    fn default() -> Self {
+        Self {
+            segment_size: ReadableSize::mb(64),
+            sync_on_write: true,
+            sync_interval: ReadableDuration::secs(1),
+        }
+    }
+}
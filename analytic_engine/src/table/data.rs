@@ -25,12 +25,20 @@ use common_util::define_result;
 use log::{debug, info};
 use object_store::Path;
 use snafu::{Backtrace, OptionExt, ResultExt, Snafu};
-use table_engine::{engine::CreateTableRequest, table::TableId};
+use table_engine::{
+    engine::CreateTableRequest,
+    partition::{rule::PartitionRule, PartitionInfo},
+    table::TableId,
+};
 use wal::manager::RegionId;
 
 use crate::{
-    instance::write_worker::{WorkerLocal, WriteHandle},
+    instance::{
+        rollup::RollupState,
+        write_worker::{WorkerLocal, WriteHandle},
+    },
     memtable::{
+        columnar::factory::ColumnarMemTableFactory,
         factory::{FactoryRef as MemTableFactoryRef, Options as MemTableOptions},
         skiplist::factory::SkiplistMemTableFactory,
     },
@@ -42,6 +50,7 @@ use crate::{
         sst_util,
         version::{MemTableForWrite, MemTableState, SamplingMemTable, TableVersion},
     },
+    table_options::MemTableType,
     TableOptions,
 };
 
@@ -68,6 +77,11 @@ pub enum Error {
     FindMemTable {
         source: crate::table::version::Error,
     },
+
+    #[snafu(display("Failed to build partition rule, err:{}", source))]
+    BuildPartitionRule {
+        source: table_engine::partition::rule::Error,
+    },
 }
 
 define_result!(Error);
@@ -86,6 +100,11 @@ pub struct TableData {
     pub space_id: SpaceId,
     /// The sst type of this table
     pub sst_type: SstType,
+    /// Partition info of this table
+    pub partition_info: Option<PartitionInfo>,
+    /// Partition rule built from the `partition_info`, which is used to
+    /// validate the rows to write and to prune the partitions to read
+    pub partition_rule: Option<PartitionRule>,
 
     /// Mutable memtable memory size limitation
     mutable_limit: AtomicU32,
@@ -126,6 +145,9 @@ pub struct TableData {
     /// No write/alter is allowed if the table is dropped.
     dropped: AtomicBool,
 
+    /// State of the rollups of this table.
+    pub rollup_state: RollupState,
+
     /// Metrics of this table.
     pub metrics: Metrics,
 }
@@ -137,6 +159,7 @@ impl fmt::Debug for TableData {
             .field("name", &self.name)
             .field("space", &self.space_id)
             .field("sst_type", &self.sst_type)
+            .field("partition_info", &self.partition_info)
             .field("mutable_limit", &self.mutable_limit)
             .field("opts", &self.opts)
             .field("last_sequence", &self.last_sequence)
@@ -158,6 +181,25 @@ fn get_mutable_limit(opts: &TableOptions) -> u32 {
     opts.write_buffer_size * 7 / 8
 }
 
+fn build_partition_rule(
+    partition_info: Option<&PartitionInfo>,
+    schema: &Schema,
+) -> Result<Option<PartitionRule>> {
+    partition_info
+        .map(|info| PartitionRule::new(info, schema))
+        .transpose()
+        .context(BuildPartitionRule)
+}
+
+/// Returns the factory of the memtable type in the options, the type is
+/// immutable once the table was created.
+fn memtable_factory_of(opts: &TableOptions) -> MemTableFactoryRef {
+    match opts.memtable_type {
+        MemTableType::Skiplist => Arc::new(SkiplistMemTableFactory),
+        MemTableType::Columnar => Arc::new(ColumnarMemTableFactory),
+    }
+}
+
 impl TableData {
     /// Create a new TableData
     ///
@@ -174,18 +216,21 @@ impl TableData {
         // FIXME(yingwen): Validate TableOptions, such as bucket_duration >=
         // segment_duration and bucket_duration is aligned to segment_duration
 
-        let memtable_factory = Arc::new(SkiplistMemTableFactory);
+        let memtable_factory = memtable_factory_of(&table_opts);
         let purge_queue = purger.create_purge_queue(space_id, request.table_id);
         let current_version = TableVersion::new(purge_queue);
         let metrics = Metrics::new(&request.table_name);
+        let partition_rule =
+            build_partition_rule(request.partition_info.as_ref(), &request.table_schema)?;
 
         Ok(Self {
             id: request.table_id,
             name: request.table_name,
             schema: Mutex::new(request.table_schema),
             space_id,
-            // TODO(xikai): sst type should be decided by the `request`.
-            sst_type: SstType::Parquet,
+            sst_type: table_opts.sst_type,
+            partition_info: request.partition_info,
+            partition_rule,
             mutable_limit: AtomicU32::new(get_mutable_limit(&table_opts)),
             opts: ArcSwap::new(Arc::new(table_opts)),
             memtable_factory,
@@ -196,6 +241,7 @@ impl TableData {
             last_memtable_id: AtomicU64::new(0),
             last_file_id: AtomicU64::new(0),
             dropped: AtomicBool::new(false),
+            rollup_state: RollupState::default(),
             metrics,
         })
     }
@@ -209,18 +255,21 @@ impl TableData {
         purger: &FilePurger,
         mem_usage_collector: CollectorRef,
     ) -> Result<Self> {
-        let memtable_factory = Arc::new(SkiplistMemTableFactory);
+        let memtable_factory = memtable_factory_of(&add_meta.opts);
         let purge_queue = purger.create_purge_queue(add_meta.space_id, add_meta.table_id);
         let current_version = TableVersion::new(purge_queue);
         let metrics = Metrics::new(&add_meta.table_name);
+        let partition_rule =
+            build_partition_rule(add_meta.partition_info.as_ref(), &add_meta.schema)?;
 
         Ok(Self {
             id: add_meta.table_id,
             name: add_meta.table_name,
             schema: Mutex::new(add_meta.schema),
             space_id: add_meta.space_id,
-            // TODO(xikai): it should be recovered from `add_meta` struct.
-            sst_type: SstType::Parquet,
+            sst_type: add_meta.opts.sst_type,
+            partition_info: add_meta.partition_info,
+            partition_rule,
             mutable_limit: AtomicU32::new(get_mutable_limit(&add_meta.opts)),
             opts: ArcSwap::new(Arc::new(add_meta.opts)),
             memtable_factory,
@@ -231,6 +280,7 @@ impl TableData {
             last_memtable_id: AtomicU64::new(0),
             last_file_id: AtomicU64::new(0),
             dropped: AtomicBool::new(false),
+            rollup_state: RollupState::default(),
             metrics,
         })
     }
@@ -302,6 +352,22 @@ impl TableData {
         self.dropped.store(true, Ordering::SeqCst);
     }
 
+    /// Returns the timestamp before which all the written rows are aggregated
+    /// into the rollups, returns None if the table has no rollup.
+    pub fn rollup_watermark(&self) -> Option<Timestamp> {
+        if self.table_options().rollups.is_empty() {
+            return None;
+        }
+
+        let unflushed_start = self.current_version.unflushed_start();
+        let watermark = match self.rollup_state.min_pending_start() {
+            Some(pending_start) => unflushed_start.min(pending_start),
+            None => unflushed_start,
+        };
+
+        Some(watermark)
+    }
+
     /// Returns total memtable memory usage in bytes.
     #[inline]
     pub fn memtable_memory_usage(&self) -> usize {
@@ -11,12 +11,14 @@ use futures::TryStreamExt;
 use object_store::ObjectStore;
 use snafu::{ensure, OptionExt, ResultExt};
 use table_engine::{
+    partition::PartitionInfo,
     predicate::Predicate,
     stream::{PartitionedStreams, SendableRecordBatchStream},
     table::{
-        AlterOptions, AlterSchema, AlterSchemaRequest, Compact, Flush, FlushRequest, Get,
-        GetInvalidPrimaryKey, GetNullPrimaryKey, GetRequest, ReadOptions, ReadOrder, ReadRequest,
-        Result, Scan, Table, TableId, TableStats, Write, WriteRequest,
+        AlterOptions, AlterSchema, AlterSchemaRequest, Compact, Delete, DeleteRequest, Flush,
+        FlushRequest, Get, GetInvalidPrimaryKey, GetNullPrimaryKey, GetRequest, ReadOptions,
+        ReadOrder, ReadRequest, Restore, RestoreRequest, Result, Scan, Snapshot, SnapshotRequest,
+        SnapshotStats, Table, TableId, TableStats, Write, WriteRequest,
     },
 };
 use tokio::sync::oneshot;
@@ -32,6 +34,7 @@ use crate::{
 pub mod data;
 pub mod metrics;
 pub mod sst_util;
+pub mod tombstone;
 pub mod version;
 pub mod version_edit;
 
@@ -93,6 +96,10 @@ impl<
         self.space_table.table_data().table_options().to_raw_map()
     }
 
+    fn partition_info(&self) -> Option<PartitionInfo> {
+        self.space_table.table_data().partition_info.clone()
+    }
+
     fn engine_type(&self) -> &str {
         &self.engine_type
     }
@@ -104,6 +111,7 @@ impl<
             num_write: metrics.write_request_counter.get(),
             num_read: metrics.read_request_counter.get(),
             num_flush: metrics.flush_duration_histogram.get_sample_count(),
+            rollup_watermark: self.space_table.table_data().rollup_watermark(),
         }
     }
 
@@ -117,6 +125,15 @@ impl<
         Ok(num_rows)
     }
 
+    async fn delete(&self, request: DeleteRequest) -> Result<()> {
+        self.instance
+            .delete_from_table(&self.space_table, request)
+            .await
+            .map_err(|e| Box::new(e) as _)
+            .context(Delete { table: self.name() })?;
+        Ok(())
+    }
+
     async fn read(&self, mut request: ReadRequest) -> Result<SendableRecordBatchStream> {
         request.opts.read_parallelism = 1;
         let mut streams = self
@@ -267,4 +284,27 @@ impl<
             .context(Compact { table: self.name() })?;
         Ok(())
     }
+
+    async fn snapshot(&self, request: SnapshotRequest) -> Result<SnapshotStats> {
+        // Flush the memtables so the snapshot contains all the written data.
+        let flush_request = FlushRequest {
+            compact_after_flush: false,
+            sync: true,
+        };
+        self.flush(flush_request).await?;
+
+        self.instance
+            .snapshot_table(&self.space_table, &*request.store, &request.prefix)
+            .await
+            .map_err(|e| Box::new(e) as _)
+            .context(Snapshot { table: self.name() })
+    }
+
+    async fn restore(&self, request: RestoreRequest) -> Result<SnapshotStats> {
+        self.instance
+            .restore_table(&self.space_table, &*request.store, &request.prefix)
+            .await
+            .map_err(|e| Box::new(e) as _)
+            .context(Restore { table: self.name() })
+    }
 }
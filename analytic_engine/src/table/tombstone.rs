@@ -0,0 +1,416 @@
+// Copyright 2022 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Tombstones of deleted rows
+//!
+//! A delete request is persisted as a [Tombstone] instead of rewriting data
+//! in place. A tombstone with sequence `S` hides rows in its time range (and
+//! of its series) from all memtables and ssts whose max sequence is less than
+//! `S`, so rows written after the delete are not affected. Compaction drops
+//! the hidden rows physically.
+
+use std::{
+    collections::HashSet,
+    convert::{TryFrom, TryInto},
+    mem,
+    sync::Arc,
+};
+
+use common_types::{
+    bytes::{Bytes, BytesMut},
+    datum::Datum,
+    record_batch::RecordBatchWithKey,
+    schema::RecordSchemaWithKey,
+    time::TimeRange,
+    SequenceNumber,
+};
+use common_util::{
+    codec::{memcomparable::MemComparable, Encoder},
+    define_result,
+};
+use proto::{meta_update as meta_pb, table_requests};
+use snafu::{ResultExt, Snafu};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Failed to encode series key, err:{}", source))]
+    EncodeSeriesKey {
+        source: common_util::codec::memcomparable::Error,
+    },
+
+    #[snafu(display("Failed to convert time range, err:{}", source))]
+    ConvertTimeRange { source: common_types::time::Error },
+}
+
+define_result!(Error);
+
+/// Encoded keys of series.
+pub type SeriesKeySet = Arc<HashSet<Bytes>>;
+
+/// Encode the series key from values of series columns.
+pub fn encode_series_key(datums: &[Datum], buf: &mut BytesMut) -> Result<()> {
+    buf.clear();
+    let encoder = MemComparable;
+    for datum in datums {
+        encoder.encode(buf, datum).context(EncodeSeriesKey)?;
+    }
+
+    Ok(())
+}
+
+/// Encode the series keys into a [SeriesKeySet].
+pub fn encode_series_keys(series_keys: &[Vec<Datum>]) -> Result<SeriesKeySet> {
+    let mut buf = BytesMut::new();
+    let mut key_set = HashSet::with_capacity(series_keys.len());
+    for datums in series_keys {
+        encode_series_key(datums, &mut buf)?;
+        key_set.insert(Bytes::copy_from_slice(&buf));
+    }
+
+    Ok(Arc::new(key_set))
+}
+
+/// Rows deleted by a delete request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tombstone {
+    /// Sequence of the delete request, only rows written before this
+    /// sequence are deleted.
+    pub sequence: SequenceNumber,
+    /// Time range of the deleted rows.
+    pub time_range: TimeRange,
+    /// Encoded keys of the deleted series, `None` means all series.
+    pub series_keys: Option<SeriesKeySet>,
+}
+
+impl Tombstone {
+    /// Returns true if rows with max sequence `sequence` may be hidden by this
+    /// tombstone.
+    #[inline]
+    pub fn applies_to(&self, sequence: SequenceNumber) -> bool {
+        sequence < self.sequence
+    }
+
+    /// Build tombstone from the delete request in the wal.
+    pub fn from_delete_request_pb(
+        sequence: SequenceNumber,
+        mut src: table_requests::DeleteRequest,
+    ) -> Result<Self> {
+        let time_range = src.take_time_range().try_into().context(ConvertTimeRange)?;
+        let series_keys = if src.all_series {
+            None
+        } else {
+            Some(Arc::new(
+                src.take_series_keys()
+                    .into_iter()
+                    .map(Bytes::from)
+                    .collect(),
+            ))
+        };
+
+        Ok(Self {
+            sequence,
+            time_range,
+            series_keys,
+        })
+    }
+
+    /// Convert into the delete request to persist in the wal.
+    pub fn to_delete_request_pb(&self) -> table_requests::DeleteRequest {
+        let mut target = table_requests::DeleteRequest::new();
+        target.set_time_range(self.time_range.into());
+        match &self.series_keys {
+            Some(keys) => {
+                target.set_series_keys(keys.iter().map(|v| v.to_vec()).collect());
+            }
+            None => target.set_all_series(true),
+        }
+
+        target
+    }
+
+    /// Convert into protobuf struct
+    pub fn into_pb(self) -> meta_pb::TombstoneMeta {
+        let mut target = meta_pb::TombstoneMeta::new();
+        target.set_sequence(self.sequence);
+        target.set_time_range(self.time_range.into());
+        match self.series_keys {
+            Some(keys) => {
+                target.set_series_keys(keys.iter().map(|v| v.to_vec()).collect());
+            }
+            None => target.set_all_series(true),
+        }
+
+        target
+    }
+}
+
+impl TryFrom<meta_pb::TombstoneMeta> for Tombstone {
+    type Error = Error;
+
+    fn try_from(mut src: meta_pb::TombstoneMeta) -> Result<Self> {
+        let time_range = src.take_time_range().try_into().context(ConvertTimeRange)?;
+        let series_keys = if src.all_series {
+            None
+        } else {
+            Some(Arc::new(
+                src.take_series_keys()
+                    .into_iter()
+                    .map(Bytes::from)
+                    .collect(),
+            ))
+        };
+
+        Ok(Self {
+            sequence: src.sequence,
+            time_range,
+            series_keys,
+        })
+    }
+}
+
+/// Filter to remove rows hidden by tombstones from record batches.
+pub struct TombstoneFilter {
+    tombstones: Vec<Tombstone>,
+    /// Index of the timestamp column in the record batch.
+    timestamp_index: usize,
+    /// Index of the series columns in the record batch.
+    series_indexes: Vec<usize>,
+    datums_buf: Vec<Datum>,
+    key_buf: BytesMut,
+    selected_rows_buf: Vec<bool>,
+}
+
+impl TombstoneFilter {
+    /// Create a filter for the record batches with `schema`, returns `None` if
+    /// no tombstone need to be applied.
+    ///
+    /// The series columns are key columns except the timestamp column.
+    pub fn new(
+        tombstones: Vec<Tombstone>,
+        schema: &RecordSchemaWithKey,
+        timestamp_name: &str,
+    ) -> Option<Self> {
+        if tombstones.is_empty() {
+            return None;
+        }
+
+        // Timestamp column is always a key column.
+        let timestamp_index = schema.index_of(timestamp_name)?;
+        let series_indexes = (0..schema.num_key_columns())
+            .filter(|idx| *idx != timestamp_index)
+            .collect();
+
+        Some(Self {
+            tombstones,
+            timestamp_index,
+            series_indexes,
+            datums_buf: Vec::new(),
+            key_buf: BytesMut::new(),
+            selected_rows_buf: Vec::new(),
+        })
+    }
+
+    /// Mark the deleted rows as false in `selected_rows`, returns the number of
+    /// selected rows.
+    ///
+    /// REQUIRE: `selected_rows.len() >= record_batch.num_rows()`.
+    pub fn filter(
+        &mut self,
+        record_batch: &RecordBatchWithKey,
+        selected_rows: &mut [bool],
+    ) -> usize {
+        self.filter_rows(record_batch, None, selected_rows)
+    }
+
+    /// Like [TombstoneFilter::filter], but a tombstone is only applied to the
+    /// rows written before it, `sequences` holds the sequence of each row.
+    ///
+    /// REQUIRE: `sequences.len() >= record_batch.num_rows()`.
+    pub fn filter_by_sequences(
+        &mut self,
+        record_batch: &RecordBatchWithKey,
+        sequences: &[SequenceNumber],
+        selected_rows: &mut [bool],
+    ) -> usize {
+        self.filter_rows(record_batch, Some(sequences), selected_rows)
+    }
+
+    /// Remove rows deleted by the tombstones written after them from the
+    /// `record_batch`, `sequences` holds the sequence of each row.
+    ///
+    /// REQUIRE: `sequences.len() >= record_batch.num_rows()`.
+    pub fn retain_rows(
+        &mut self,
+        record_batch: &mut RecordBatchWithKey,
+        sequences: &[SequenceNumber],
+    ) -> common_types::record_batch::Result<()> {
+        let mut selected_rows = mem::take(&mut self.selected_rows_buf);
+        selected_rows.clear();
+        selected_rows.resize(record_batch.num_rows(), true);
+        let num_selected = self.filter_by_sequences(record_batch, sequences, &mut selected_rows);
+        let res = if num_selected < record_batch.num_rows() {
+            record_batch.select_data(&selected_rows)
+        } else {
+            Ok(())
+        };
+        self.selected_rows_buf = selected_rows;
+
+        res
+    }
+
+    fn filter_rows(
+        &mut self,
+        record_batch: &RecordBatchWithKey,
+        sequences: Option<&[SequenceNumber]>,
+        selected_rows: &mut [bool],
+    ) -> usize {
+        let mut num_selected = 0;
+        let timestamp_column = record_batch.column(self.timestamp_index);
+        for (row_idx, selected) in selected_rows
+            .iter_mut()
+            .take(record_batch.num_rows())
+            .enumerate()
+        {
+            let sequence = sequences.map(|v| v[row_idx]);
+            *selected = match timestamp_column.datum(row_idx).as_timestamp() {
+                Some(timestamp) => !self.is_deleted(record_batch, row_idx, timestamp, sequence),
+                None => true,
+            };
+            if *selected {
+                num_selected += 1;
+            }
+        }
+
+        num_selected
+    }
+
+    fn is_deleted(
+        &mut self,
+        record_batch: &RecordBatchWithKey,
+        row_idx: usize,
+        timestamp: common_types::time::Timestamp,
+        sequence: Option<SequenceNumber>,
+    ) -> bool {
+        let mut key_encoded = false;
+        for tombstone in &self.tombstones {
+            if !tombstone.time_range.contains(timestamp) {
+                continue;
+            }
+            if let Some(sequence) = sequence {
+                if !tombstone.applies_to(sequence) {
+                    continue;
+                }
+            }
+
+            let series_keys = match &tombstone.series_keys {
+                Some(v) => v,
+                None => return true,
+            };
+
+            if !key_encoded {
+                self.datums_buf.clear();
+                self.datums_buf.extend(
+                    self.series_indexes
+                        .iter()
+                        .map(|idx| record_batch.column(*idx).datum(row_idx)),
+                );
+                // Encoding datums of the schema should never fail, keep the row
+                // if it happens.
+                if encode_series_key(&self.datums_buf, &mut self.key_buf).is_err() {
+                    return false;
+                }
+                key_encoded = true;
+            }
+
+            if series_keys.contains(&self.key_buf[..]) {
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common_types::{
+        bytes::Bytes,
+        tests::{build_row, build_schema},
+        time::Timestamp,
+    };
+
+    use super::*;
+    use crate::row_iter::tests::build_record_batch_with_key;
+
+    fn series_key(key: &[u8]) -> Vec<Datum> {
+        vec![Datum::Varbinary(Bytes::copy_from_slice(key))]
+    }
+
+    #[test]
+    fn test_tombstone_filter() {
+        let schema = build_schema();
+        let record_batch = build_record_batch_with_key(
+            schema.clone(),
+            vec![
+                build_row(b"a", 1000, 10.0, "v1"),
+                build_row(b"a", 2000, 10.0, "v2"),
+                build_row(b"b", 1000, 10.0, "v3"),
+                build_row(b"c", 3000, 10.0, "v4"),
+            ],
+        );
+
+        let tombstones = vec![
+            Tombstone {
+                sequence: 10,
+                time_range: TimeRange::new_unchecked_for_test(1000, 2000),
+                series_keys: Some(encode_series_keys(&[series_key(b"a")]).unwrap()),
+            },
+            Tombstone {
+                sequence: 11,
+                time_range: TimeRange::new_unchecked_for_test(3000, 4000),
+                series_keys: None,
+            },
+        ];
+        let mut filter = TombstoneFilter::new(
+            tombstones,
+            &schema.to_record_schema_with_key(),
+            schema.timestamp_name(),
+        )
+        .unwrap();
+
+        let mut selected_rows = vec![true; record_batch.num_rows()];
+        let num_selected = filter.filter(&record_batch, &mut selected_rows);
+        assert_eq!(2, num_selected);
+        assert_eq!(vec![false, true, true, false], selected_rows);
+
+        // The last row is written after the second tombstone.
+        let num_selected =
+            filter.filter_by_sequences(&record_batch, &[9, 9, 9, 12], &mut selected_rows);
+        assert_eq!(3, num_selected);
+        assert_eq!(vec![false, true, true, true], selected_rows);
+    }
+
+    #[test]
+    fn test_tombstone_pb_conversion() {
+        let tombstone = Tombstone {
+            sequence: 100,
+            time_range: TimeRange::new(Timestamp::new(10), Timestamp::new(20)).unwrap(),
+            series_keys: Some(encode_series_keys(&[series_key(b"a"), series_key(b"b")]).unwrap()),
+        };
+
+        let pb = tombstone.clone().into_pb();
+        assert_eq!(tombstone, Tombstone::try_from(pb).unwrap());
+
+        let pb = tombstone.to_delete_request_pb();
+        assert_eq!(
+            tombstone,
+            Tombstone::from_delete_request_pb(100, pb).unwrap()
+        );
+
+        let tombstone = Tombstone {
+            series_keys: None,
+            ..tombstone
+        };
+        let pb = tombstone.clone().into_pb();
+        assert_eq!(tombstone, Tombstone::try_from(pb).unwrap());
+    }
+}
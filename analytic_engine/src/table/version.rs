@@ -34,7 +34,8 @@ use crate::{
     },
     table::{
         data::MemTableId,
-        version_edit::{AddFile, VersionEdit},
+        tombstone::Tombstone,
+        version_edit::{AddFile, DeleteFile, VersionEdit},
     },
 };
 
@@ -271,6 +272,23 @@ impl MemTableView {
         mutable_usage + immutable_usage
     }
 
+    /// Returns the min start of the time ranges of the memtables,
+    /// `Timestamp::MIN` if the sampling memtable exists as its time range is
+    /// unknown, or `Timestamp::MAX` if there is no memtable.
+    fn unflushed_start(&self) -> Timestamp {
+        if self.sampling_mem.is_some() {
+            return Timestamp::MIN;
+        }
+
+        self.mutables
+            .0
+            .values()
+            .chain(self.immutables.0.values())
+            .map(|mem| mem.time_range.inclusive_start())
+            .min()
+            .unwrap_or(Timestamp::MAX)
+    }
+
     /// Switch all memtables or just sample the segment duration.
     ///
     /// If the sampling memtable is still active, return the suggested segment
@@ -476,6 +494,8 @@ pub struct ReadView {
     ///
     /// The `ReadView` MUST ensure the length of `leveled_ssts` >= MAX_LEVEL.
     pub leveled_ssts: LeveledFiles,
+    /// Tombstones intersecting with the time range to read.
+    pub tombstones: Vec<Tombstone>,
 }
 
 impl Default for ReadView {
@@ -484,6 +504,7 @@ impl Default for ReadView {
             sampling_mem: None,
             memtables: Vec::new(),
             leveled_ssts: vec![Vec::new(); MAX_LEVEL],
+            tombstones: Vec::new(),
         }
     }
 }
@@ -504,6 +525,10 @@ struct TableVersionInner {
     /// The earliest sequence number of the entries already flushed (inclusive).
     /// All log entry with sequence <= `flushed_sequence` can be deleted
     flushed_sequence: SequenceNumber,
+
+    /// All tombstones ordered by sequence, tombstones with sequence <=
+    /// `flushed_sequence` are persisted in the manifest.
+    tombstones: Vec<Tombstone>,
 }
 
 impl TableVersionInner {
@@ -525,6 +550,17 @@ impl TableVersionInner {
             .cloned()
             .map(MemTableForWrite::Normal)
     }
+
+    fn add_tombstone(&mut self, tombstone: Tombstone) {
+        match self
+            .tombstones
+            .binary_search_by_key(&tombstone.sequence, |v| v.sequence)
+        {
+            // The tombstone already exists.
+            Ok(_) => (),
+            Err(pos) => self.tombstones.insert(pos, tombstone),
+        }
+    }
 }
 
 // TODO(yingwen): How to support snapshot?
@@ -546,6 +582,7 @@ impl TableVersion {
                 memtable_view: MemTableView::new(),
                 levels: LevelsController::new(purge_queue),
                 flushed_sequence: 0,
+                tombstones: Vec::new(),
             }),
         }
     }
@@ -568,6 +605,11 @@ impl TableVersion {
             .total_memory_usage()
     }
 
+    /// See [MemTableView::unflushed_start]
+    pub fn unflushed_start(&self) -> Timestamp {
+        self.inner.read().unwrap().memtable_view.unflushed_start()
+    }
+
     /// Switch all mutable memtables or just return the suggested segment
     /// duration if sampling memtable is still active.
     ///
@@ -686,6 +728,18 @@ impl TableVersion {
         for mem_id in edit.mems_to_remove {
             inner.memtable_view.remove_immutable_or_sampling(mem_id);
         }
+
+        // Tombstones to add are usually already in the version, they are added
+        // when the delete request is written.
+        for tombstone in edit.tombstones_to_add {
+            inner.add_tombstone(tombstone);
+        }
+
+        if !edit.tombstones_to_delete.is_empty() {
+            inner
+                .tombstones
+                .retain(|v| !edit.tombstones_to_delete.contains(&v.sequence));
+        }
     }
 
     /// Atomically apply the meta to the version, useful in recover.
@@ -697,12 +751,73 @@ impl TableVersion {
         for add_file in meta.files.into_values() {
             inner.levels.add_sst_to_level(add_file.level, add_file.file);
         }
+
+        for tombstone in meta.tombstones.into_values() {
+            inner.add_tombstone(tombstone);
+        }
+    }
+
+    /// Add the tombstone of a delete request.
+    ///
+    /// REQUIRE: Do in write worker, so the tombstone is in the version before
+    /// the memtable containing rows written before it is switched.
+    pub fn add_tombstone(&self, _worker_local: &WorkerLocal, tombstone: Tombstone) {
+        self.inner.write().unwrap().add_tombstone(tombstone);
+    }
+
+    /// Returns tombstones not persisted yet but will be persisted once data up
+    /// to `flushed_sequence` is flushed.
+    pub fn tombstones_to_persist(&self, flushed_sequence: SequenceNumber) -> Vec<Tombstone> {
+        let inner = self.inner.read().unwrap();
+
+        inner
+            .tombstones
+            .iter()
+            .filter(|v| v.sequence > inner.flushed_sequence && v.sequence <= flushed_sequence)
+            .cloned()
+            .collect()
+    }
+
+    /// Returns sequence of the persisted tombstones in `applied` which hide no
+    /// row once the files are deleted by compaction.
+    ///
+    /// Rows hidden by `applied` tombstones are dropped by compaction, so such
+    /// a tombstone can be removed if every remaining sst is written after it.
+    /// A persisted tombstone is always older than the rows in memtables.
+    pub fn obsolete_tombstones(
+        &self,
+        applied: &[Tombstone],
+        files_to_delete: &[DeleteFile],
+    ) -> Vec<SequenceNumber> {
+        if applied.is_empty() {
+            return Vec::new();
+        }
+
+        let inner = self.inner.read().unwrap();
+        let mut min_max_sequence = SequenceNumber::MAX;
+        for level in 0..inner.levels.num_levels() {
+            for file in inner.levels.iter_ssts_at_level(level) {
+                let deleted = files_to_delete
+                    .iter()
+                    .any(|v| v.level == level && v.file_id == file.id());
+                if !deleted {
+                    min_max_sequence = cmp::min(min_max_sequence, file.max_sequence());
+                }
+            }
+        }
+
+        applied
+            .iter()
+            .filter(|v| v.sequence <= inner.flushed_sequence && !v.applies_to(min_max_sequence))
+            .map(|v| v.sequence)
+            .collect()
     }
 
     pub fn pick_read_view(&self, time_range: TimeRange) -> ReadView {
         let mut sampling_mem = None;
         let mut memtables = MemTableVec::new();
         let mut leveled_ssts = vec![Vec::new(); MAX_LEVEL];
+        let tombstones;
 
         {
             // Pick memtables for read.
@@ -716,15 +831,35 @@ impl TableVersion {
             inner.levels.pick_ssts(time_range, |level, ssts| {
                 leveled_ssts[level as usize].extend_from_slice(ssts)
             });
+
+            tombstones = inner
+                .tombstones
+                .iter()
+                .filter(|v| v.time_range.intersect_with(time_range))
+                .cloned()
+                .collect();
         }
 
         ReadView {
             sampling_mem,
             memtables,
             leveled_ssts,
+            tombstones,
         }
     }
 
+    /// Returns all tombstones intersecting with `time_range`.
+    pub fn tombstones(&self, time_range: TimeRange) -> Vec<Tombstone> {
+        let inner = self.inner.read().unwrap();
+
+        inner
+            .tombstones
+            .iter()
+            .filter(|v| v.time_range.intersect_with(time_range))
+            .cloned()
+            .collect()
+    }
+
     /// Pick ssts for compaction using given `picker`.
     pub fn pick_for_compaction(
         &self,
@@ -757,6 +892,7 @@ pub struct TableVersionMeta {
     pub flushed_sequence: SequenceNumber,
     files: HashMap<FileId, AddFile>,
     max_file_id: FileId,
+    tombstones: BTreeMap<SequenceNumber, Tombstone>,
 }
 
 impl TableVersionMeta {
@@ -772,6 +908,14 @@ impl TableVersionMeta {
         for delete_file in edit.files_to_delete {
             self.files.remove(&delete_file.file_id);
         }
+
+        for tombstone in edit.tombstones_to_add {
+            self.tombstones.insert(tombstone.sequence, tombstone);
+        }
+
+        for sequence in edit.tombstones_to_delete {
+            self.tombstones.remove(&sequence);
+        }
     }
 
     /// Returns the max file id in the files to add.
@@ -785,6 +929,10 @@ impl TableVersionMeta {
 
         files_vec
     }
+
+    pub fn ordered_tombstones(&self) -> Vec<Tombstone> {
+        self.tombstones.values().cloned().collect()
+    }
 }
 
 #[cfg(test)]
@@ -1082,6 +1230,8 @@ mod tests {
             mems_to_remove: vec![memtable_id1, memtable_id2],
             files_to_add: vec![add_file],
             files_to_delete: vec![],
+            tombstones_to_add: vec![],
+            tombstones_to_delete: vec![],
         };
         version.apply_edit(edit);
 
@@ -1093,4 +1243,69 @@ mod tests {
         assert_eq!(1, read_view.leveled_ssts[0].len());
         assert_eq!(file_id, read_view.leveled_ssts[0][0].id());
     }
+
+    #[test]
+    fn test_table_version_tombstones() {
+        let mocked_write_handle = WriteHandleMocker::default().build();
+        let worker_local = mocked_write_handle.worker_local;
+        let version = new_table_version();
+
+        let memtable = MemTableMocker::default().build();
+        let schema = memtable.schema().clone();
+        let time_range = TimeRange::min_to_max();
+
+        version.add_tombstone(
+            &worker_local,
+            Tombstone {
+                sequence: 100,
+                time_range,
+                series_keys: None,
+            },
+        );
+        version.add_tombstone(
+            &worker_local,
+            Tombstone {
+                sequence: 200,
+                time_range,
+                series_keys: None,
+            },
+        );
+
+        let read_view = version.pick_read_view(time_range);
+        assert_eq!(2, read_view.tombstones.len());
+
+        // Only the first tombstone is persisted by the flush.
+        let tombstones_to_add = version.tombstones_to_persist(150);
+        assert_eq!(1, tombstones_to_add.len());
+        assert_eq!(100, tombstones_to_add[0].sequence);
+
+        let flushed_file_id = 1;
+        let sst_meta = SstMetaDataMocker::new(schema).max_sequence(99).build();
+        let add_file = AddFileMocker::new(sst_meta)
+            .file_id(flushed_file_id)
+            .build();
+        version.apply_edit(VersionEdit {
+            flushed_sequence: 150,
+            mems_to_remove: vec![],
+            files_to_add: vec![add_file.clone()],
+            files_to_delete: vec![],
+            tombstones_to_add,
+            tombstones_to_delete: vec![],
+        });
+        assert!(version.tombstones_to_persist(150).is_empty());
+
+        // The flushed sst still contains rows hidden by the tombstone.
+        let applied = version.tombstones(time_range);
+        assert!(version.obsolete_tombstones(&applied, &[]).is_empty());
+
+        // Compact the flushed sst, the unpersisted tombstone is not removed.
+        let files_to_delete = vec![DeleteFile {
+            level: add_file.level,
+            file_id: flushed_file_id,
+        }];
+        assert_eq!(
+            vec![100],
+            version.obsolete_tombstones(&applied, &files_to_delete)
+        );
+    }
 }
@@ -12,9 +12,10 @@ use snafu::{Backtrace, ResultExt, Snafu};
 use crate::{
     sst::{
         file::{FileMeta, SstMetaData},
+        index::InvertedIndex,
         manager::FileId,
     },
-    table::data::MemTableId,
+    table::{data::MemTableId, tombstone::Tombstone},
 };
 
 #[derive(Debug, Snafu)]
@@ -57,6 +58,9 @@ impl AddFile {
         target.set_schema(self.file.meta.schema.into());
         target.set_size(self.file.meta.size);
         target.set_row_num(self.file.meta.row_num);
+        if let Some(inverted_index) = self.file.meta.inverted_index {
+            target.set_inverted_index(inverted_index.into());
+        }
 
         target
     }
@@ -68,6 +72,11 @@ impl TryFrom<meta_pb::AddFileMeta> for AddFile {
     fn try_from(mut src: meta_pb::AddFileMeta) -> Result<Self> {
         let time_range = TimeRange::try_from(src.take_time_range()).context(ConvertTimeRange)?;
         let schema = Schema::try_from(src.take_schema()).context(ConvertTableSchema)?;
+        let inverted_index = if src.has_inverted_index() {
+            Some(InvertedIndex::from(src.take_inverted_index()))
+        } else {
+            None
+        };
         Ok(Self {
             level: src
                 .level
@@ -83,6 +92,8 @@ impl TryFrom<meta_pb::AddFileMeta> for AddFile {
                     schema,
                     size: src.size,
                     row_num: src.row_num,
+                    sst_filter: None,
+                    inverted_index,
                 },
             },
         })
@@ -138,6 +149,10 @@ pub struct VersionEdit {
     pub files_to_add: Vec<AddFile>,
     /// Sst files to delete.
     pub files_to_delete: Vec<DeleteFile>,
+    /// Tombstones to persist.
+    pub tombstones_to_add: Vec<Tombstone>,
+    /// Sequence of tombstones to delete.
+    pub tombstones_to_delete: Vec<SequenceNumber>,
 }
 
 #[cfg(test)]
@@ -13,14 +13,22 @@ use common_util::{
 };
 use proto::analytic_common::{
     CompactionOptions as CompactionOptionsPb, CompactionStrategy as CompactionStrategyPb,
-    Compression as CompressionPb, TableOptions as TableOptionsPb, UpdateMode as UpdateModePb,
+    Compression as CompressionPb, MemTableType as MemTableTypePb, SstType as SstTypePb,
+    TableOptions as TableOptionsPb, UpdateMode as UpdateModePb,
 };
 use serde_derive::Deserialize;
-use snafu::{Backtrace, GenerateBacktrace, ResultExt, Snafu};
-use table_engine::OPTION_KEY_ENABLE_TTL;
+use snafu::{ensure, Backtrace, GenerateBacktrace, ResultExt, Snafu};
+use table_engine::{
+    rollup::{self, Rollup},
+    OPTION_KEY_ENABLE_TTL, OPTION_KEY_ROLLUPS,
+};
 
-use crate::compaction::{
-    CompactionStrategy, SizeTieredCompactionOptions, TimeWindowCompactionOptions,
+use crate::{
+    compaction::{
+        CompactionStrategy, LeveledCompactionOptions, SizeTieredCompactionOptions,
+        TimeWindowCompactionOptions,
+    },
+    sst::factory::SstType,
 };
 
 pub const SEGMENT_DURATION: &str = "segment_duration";
@@ -32,6 +40,9 @@ pub const COMPACTION_STRATEGY: &str = "compaction_strategy";
 pub const NUM_ROWS_PER_ROW_GROUP: &str = "num_rows_per_row_group";
 pub const UPDATE_MODE: &str = "update_mode";
 pub const COMPRESSION: &str = "compression";
+pub const ROLLUPS: &str = OPTION_KEY_ROLLUPS;
+pub const MEMTABLE_TYPE: &str = "memtable_type";
+pub const SST_TYPE: &str = "sst_type";
 
 const UPDATE_MODE_OVERWRITE: &str = "OVERWRITE";
 const UPDATE_MODE_APPEND: &str = "APPEND";
@@ -39,7 +50,11 @@ const COMPRESSION_UNCOMPRESSED: &str = "UNCOMPRESSED";
 const COMPRESSION_LZ4: &str = "LZ4";
 const COMPRESSION_SNAPPY: &str = "SNAPPY";
 const COMPRESSION_ZSTD: &str = "ZSTD";
-const AT_LEAST_OPTIONS_NUM: usize = 9;
+const MEMTABLE_TYPE_SKIPLIST: &str = "SKIPLIST";
+const MEMTABLE_TYPE_COLUMNAR: &str = "COLUMNAR";
+const SST_TYPE_PARQUET: &str = "PARQUET";
+const SST_TYPE_TIME_SERIES: &str = "TIME_SERIES";
+const AT_LEAST_OPTIONS_NUM: usize = 11;
 
 /// Default bucket duration (1d)
 const BUCKET_DURATION_1D: Duration = Duration::from_secs(24 * 60 * 60);
@@ -97,6 +112,32 @@ pub enum Error {
         backtrace
     ))]
     ParseCompressionName { name: String, backtrace: Backtrace },
+
+    #[snafu(display(
+        "Failed to parse memtable type, name:{}.\nBacktrace:\n{}",
+        name,
+        backtrace
+    ))]
+    ParseMemTableType { name: String, backtrace: Backtrace },
+
+    #[snafu(display(
+        "Memtable type {} requires update mode {}, given:{}.\nBacktrace:\n{}",
+        memtable_type,
+        UPDATE_MODE_APPEND,
+        update_mode,
+        backtrace
+    ))]
+    InvalidMemTableType {
+        memtable_type: String,
+        update_mode: String,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("Failed to parse sst type, name:{}.\nBacktrace:\n{}", name, backtrace))]
+    ParseSstType { name: String, backtrace: Backtrace },
+
+    #[snafu(display("Failed to parse rollups, err:{}", source))]
+    ParseRollups { source: table_engine::rollup::Error },
 }
 
 define_result!(Error);
@@ -196,6 +237,93 @@ impl From<Compression> for ParquetCompression {
     }
 }
 
+/// Type of the memtable.
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq)]
+pub enum MemTableType {
+    /// Row oriented memtable based on skiplist, supports all update modes.
+    Skiplist,
+    /// Memtable buffers rows in columnar format, only supports append mode.
+    Columnar,
+}
+
+impl MemTableType {
+    pub fn parse_from(name: &str) -> Result<Self> {
+        if name.eq_ignore_ascii_case(MEMTABLE_TYPE_SKIPLIST) {
+            Ok(MemTableType::Skiplist)
+        } else if name.eq_ignore_ascii_case(MEMTABLE_TYPE_COLUMNAR) {
+            Ok(MemTableType::Columnar)
+        } else {
+            ParseMemTableType { name }.fail()
+        }
+    }
+}
+
+impl ToString for MemTableType {
+    fn to_string(&self) -> String {
+        match self {
+            MemTableType::Skiplist => MEMTABLE_TYPE_SKIPLIST.to_string(),
+            MemTableType::Columnar => MEMTABLE_TYPE_COLUMNAR.to_string(),
+        }
+    }
+}
+
+impl From<MemTableType> for MemTableTypePb {
+    fn from(memtable_type: MemTableType) -> Self {
+        match memtable_type {
+            MemTableType::Skiplist => MemTableTypePb::SKIPLIST,
+            MemTableType::Columnar => MemTableTypePb::COLUMNAR,
+        }
+    }
+}
+
+impl From<MemTableTypePb> for MemTableType {
+    fn from(memtable_type: MemTableTypePb) -> Self {
+        match memtable_type {
+            MemTableTypePb::SKIPLIST => MemTableType::Skiplist,
+            MemTableTypePb::COLUMNAR => MemTableType::Columnar,
+        }
+    }
+}
+
+impl SstType {
+    pub fn parse_from(name: &str) -> Result<Self> {
+        if name.eq_ignore_ascii_case(SST_TYPE_PARQUET) {
+            Ok(SstType::Parquet)
+        } else if name.eq_ignore_ascii_case(SST_TYPE_TIME_SERIES) {
+            Ok(SstType::TimeSeries)
+        } else {
+            ParseSstType { name }.fail()
+        }
+    }
+}
+
+impl ToString for SstType {
+    fn to_string(&self) -> String {
+        match self {
+            SstType::Parquet => SST_TYPE_PARQUET.to_string(),
+            SstType::TimeSeries => SST_TYPE_TIME_SERIES.to_string(),
+        }
+    }
+}
+
+impl From<SstType> for SstTypePb {
+    fn from(sst_type: SstType) -> Self {
+        match sst_type {
+            SstType::Parquet => SstTypePb::PARQUET,
+            SstType::TimeSeries => SstTypePb::TIME_SERIES,
+        }
+    }
+}
+
+impl From<SstTypePb> for SstType {
+    fn from(sst_type: SstTypePb) -> Self {
+        match sst_type {
+            SstTypePb::PARQUET => SstType::Parquet,
+            SstTypePb::TIME_SERIES => SstType::TimeSeries,
+        }
+    }
+}
+
 /// Options for a table.
 #[derive(Debug, Clone, Deserialize, PartialEq)]
 #[serde(default)]
@@ -208,6 +336,10 @@ pub struct TableOptions {
     pub segment_duration: Option<ReadableDuration>,
     /// Table update mode, now support Overwrite(Default) and Append
     pub update_mode: UpdateMode,
+    /// Type of the memtable, columnar memtable requires append mode.
+    pub memtable_type: MemTableType,
+    /// Format of the sst files.
+    pub sst_type: SstType,
 
     // The following options can be altered.
     /// Enable ttl
@@ -224,6 +356,9 @@ pub struct TableOptions {
     pub num_rows_per_row_group: usize,
     /// Table Compression
     pub compression: Compression,
+    /// Rollups maintained from the table.
+    #[serde(skip)]
+    pub rollups: Vec<Rollup>,
 }
 
 impl TableOptions {
@@ -251,6 +386,8 @@ impl TableOptions {
                 .unwrap_or_else(String::new),
         );
         m.insert(UPDATE_MODE.to_string(), self.update_mode.to_string());
+        m.insert(MEMTABLE_TYPE.to_string(), self.memtable_type.to_string());
+        m.insert(SST_TYPE.to_string(), self.sst_type.to_string());
         m.insert(ENABLE_TTL.to_string(), self.enable_ttl.to_string());
         m.insert(TTL.to_string(), format!("{}", self.ttl));
         m.insert(
@@ -267,6 +404,9 @@ impl TableOptions {
             format!("{}", self.num_rows_per_row_group),
         );
         m.insert(COMPRESSION.to_string(), self.compression.to_string());
+        if !self.rollups.is_empty() {
+            m.insert(ROLLUPS.to_string(), rollup::format_rollups(&self.rollups));
+        }
 
         assert!(m.len() >= AT_LEAST_OPTIONS_NUM);
 
@@ -369,6 +509,29 @@ impl From<CompactionOptionsPb> for TimeWindowCompactionOptions {
     }
 }
 
+impl From<LeveledCompactionOptions> for CompactionOptionsPb {
+    fn from(opts: LeveledCompactionOptions) -> Self {
+        let mut target = CompactionOptionsPb::new();
+        target.set_level0_file_num_trigger(opts.level0_file_num_trigger as u32);
+        target.set_max_bytes_for_level_base(opts.max_bytes_for_level_base.0);
+        target.set_level_size_multiplier(opts.level_size_multiplier as u32);
+        target.set_target_file_size(opts.target_file_size.0);
+
+        target
+    }
+}
+
+impl From<CompactionOptionsPb> for LeveledCompactionOptions {
+    fn from(opts: CompactionOptionsPb) -> Self {
+        Self {
+            level0_file_num_trigger: opts.level0_file_num_trigger as usize,
+            max_bytes_for_level_base: ReadableSize(opts.max_bytes_for_level_base),
+            level_size_multiplier: opts.level_size_multiplier as usize,
+            target_file_size: ReadableSize(opts.target_file_size),
+        }
+    }
+}
+
 impl From<TableOptions> for TableOptionsPb {
     fn from(opts: TableOptions) -> Self {
         let mut target = TableOptionsPb::new();
@@ -396,6 +559,10 @@ impl From<TableOptions> for TableOptionsPb {
                 target.set_compaction_strategy(CompactionStrategyPb::TIME_WINDOW);
                 target.set_compaction_options(opts.into());
             }
+            CompactionStrategy::Leveled(opts) => {
+                target.set_compaction_strategy(CompactionStrategyPb::LEVELED);
+                target.set_compaction_options(opts.into());
+            }
         }
 
         match opts.update_mode {
@@ -407,15 +574,20 @@ impl From<TableOptions> for TableOptionsPb {
             }
         }
 
+        target.set_memtable_type(opts.memtable_type.into());
+        target.set_sst_type(opts.sst_type.into());
         target.set_write_buffer_size(opts.write_buffer_size);
         target.set_compression(opts.compression.into());
+        let rollups: Vec<_> = opts.rollups.into_iter().map(Into::into).collect();
+        target.set_rollups(rollups.into());
 
         target
     }
 }
 
 impl From<TableOptionsPb> for TableOptions {
-    fn from(opts: TableOptionsPb) -> Self {
+    fn from(mut opts: TableOptionsPb) -> Self {
+        let rollups = opts.take_rollups().into_iter().map(Rollup::from).collect();
         let compaction_strategy = match opts.compaction_strategy {
             CompactionStrategyPb::DEFAULT => CompactionStrategy::default(),
             CompactionStrategyPb::SIZE_TIERED => {
@@ -432,6 +604,13 @@ impl From<TableOptionsPb> for TableOptions {
                     .unwrap_or_default();
                 CompactionStrategy::TimeWindow(opts)
             }
+            CompactionStrategyPb::LEVELED => {
+                let opts = opts
+                    .compaction_options
+                    .map(LeveledCompactionOptions::from)
+                    .unwrap_or_default();
+                CompactionStrategy::Leveled(opts)
+            }
         };
 
         let update_mode = match opts.update_mode {
@@ -458,8 +637,11 @@ impl From<TableOptionsPb> for TableOptions {
             compaction_strategy,
             num_rows_per_row_group: opts.num_rows_per_row_group as usize,
             update_mode,
+            memtable_type: opts.memtable_type.into(),
+            sst_type: opts.sst_type.into(),
             write_buffer_size: opts.write_buffer_size,
             compression: opts.compression.into(),
+            rollups,
         }
     }
 }
@@ -474,8 +656,11 @@ impl Default for TableOptions {
             compaction_strategy: CompactionStrategy::default(),
             num_rows_per_row_group: DEFAULT_NUM_ROW_PER_ROW_GROUP,
             update_mode: UpdateMode::Overwrite,
+            memtable_type: MemTableType::Skiplist,
+            sst_type: SstType::Parquet,
             write_buffer_size: DEFAULT_WRITE_BUFFER_SIZE,
             compression: Compression::Zstd,
+            rollups: Vec::new(),
         }
     }
 }
@@ -508,6 +693,20 @@ fn merge_table_options(
         if let Some(v) = options.get(UPDATE_MODE) {
             table_opts.update_mode = UpdateMode::parse_from(v)?;
         }
+        if let Some(v) = options.get(MEMTABLE_TYPE) {
+            table_opts.memtable_type = MemTableType::parse_from(v)?;
+        }
+        if let Some(v) = options.get(SST_TYPE) {
+            table_opts.sst_type = SstType::parse_from(v)?;
+        }
+        ensure!(
+            table_opts.memtable_type != MemTableType::Columnar
+                || table_opts.update_mode == UpdateMode::Append,
+            InvalidMemTableType {
+                memtable_type: table_opts.memtable_type.to_string(),
+                update_mode: table_opts.update_mode.to_string(),
+            }
+        );
     }
 
     if let Some(v) = options.get(TTL) {
@@ -534,6 +733,9 @@ fn merge_table_options(
     if let Some(v) = options.get(COMPRESSION) {
         table_opts.compression = Compression::parse_from(v)?;
     }
+    if let Some(v) = options.get(ROLLUPS) {
+        table_opts.rollups = rollup::parse_rollups(v).context(ParseRollups)?;
+    }
     Ok(table_opts)
 }
 
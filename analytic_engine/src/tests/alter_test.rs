@@ -369,6 +369,10 @@ fn test_alter_table_options() {
 
         alter_immutable_option_case(&test_ctx, test_table1, "update_mode", "Append").await;
 
+        alter_immutable_option_case(&test_ctx, test_table1, "memtable_type", "Columnar").await;
+
+        alter_immutable_option_case(&test_ctx, test_table1, "sst_type", "TIME_SERIES").await;
+
         alter_mutable_option_case(&mut test_ctx, test_table1, "enable_ttl", "false").await;
         alter_mutable_option_case(&mut test_ctx, test_table1, "enable_ttl", "true").await;
 
@@ -0,0 +1,154 @@
+// Copyright 2022 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Snapshot and restore tests.
+
+use std::sync::Arc;
+
+use common_types::time::Timestamp;
+use object_store::{LocalFileSystem, ObjectStoreRef};
+use table_engine::table::{FlushRequest, RestoreRequest, SnapshotRequest};
+
+use crate::tests::util::{self, TestEnv};
+
+#[test]
+fn test_table_snapshot_and_restore() {
+    let env = TestEnv::builder().build();
+    let mut test_ctx = env.new_context();
+    let backup_dir = tempfile::tempdir().unwrap();
+    let backup_store: ObjectStoreRef =
+        Arc::new(LocalFileSystem::new_with_prefix(backup_dir.path()).unwrap());
+
+    env.block_on(async {
+        test_ctx.open().await;
+
+        let test_table1 = "test_table1";
+        let fixed_schema_table = test_ctx.create_fixed_schema_table(test_table1).await;
+
+        let start_ms = test_ctx.start_ms();
+        let flushed_rows = [
+            (
+                "key1",
+                Timestamp::new(start_ms),
+                "tag1-1",
+                11.0,
+                110.0,
+                "tag2-1",
+            ),
+            (
+                "key2",
+                Timestamp::new(start_ms),
+                "tag1-2",
+                12.0,
+                110.0,
+                "tag2-2",
+            ),
+        ];
+        let row_group = fixed_schema_table.rows_to_row_group(&flushed_rows);
+        test_ctx.write_to_table(test_table1, row_group).await;
+        test_ctx
+            .flush_table_with_request(
+                test_table1,
+                FlushRequest {
+                    compact_after_flush: false,
+                    sync: true,
+                },
+            )
+            .await;
+
+        // Rows in memtable should be flushed by the snapshot.
+        let unflushed_rows = [(
+            "key1",
+            Timestamp::new(start_ms + 1),
+            "tag1-3",
+            13.0,
+            130.0,
+            "tag2-3",
+        )];
+        let row_group = fixed_schema_table.rows_to_row_group(&unflushed_rows);
+        test_ctx.write_to_table(test_table1, row_group).await;
+
+        let mut expect_rows = flushed_rows.to_vec();
+        expect_rows.extend_from_slice(&unflushed_rows);
+        expect_rows.sort_unstable_by_key(|row_tuple| (row_tuple.0, row_tuple.1));
+
+        let prefix = "backup/test_table1".to_string();
+        let snapshot_stats = test_ctx
+            .table(test_table1)
+            .snapshot(SnapshotRequest {
+                store: backup_store.clone(),
+                prefix: prefix.clone(),
+            })
+            .await
+            .unwrap();
+        assert_eq!(2, snapshot_stats.num_files);
+        assert_eq!(3, snapshot_stats.num_rows);
+
+        let test_table2 = "test_table2";
+        test_ctx.create_fixed_schema_table(test_table2).await;
+        let restore_request = RestoreRequest {
+            store: backup_store.clone(),
+            prefix,
+        };
+        let restore_stats = test_ctx
+            .table(test_table2)
+            .restore(restore_request.clone())
+            .await
+            .unwrap();
+        assert_eq!(snapshot_stats, restore_stats);
+
+        util::check_read(
+            &test_ctx,
+            &fixed_schema_table,
+            "Test read after restore",
+            test_table2,
+            &expect_rows,
+        )
+        .await;
+
+        // The table is not empty now.
+        assert!(test_ctx
+            .table(test_table2)
+            .restore(restore_request)
+            .await
+            .is_err());
+
+        // Restored files are recorded in the manifest.
+        test_ctx
+            .reopen_with_tables(&[test_table1, test_table2])
+            .await;
+
+        util::check_read(
+            &test_ctx,
+            &fixed_schema_table,
+            "Test read after reopen",
+            test_table2,
+            &expect_rows,
+        )
+        .await;
+    });
+}
+
+#[test]
+fn test_restore_without_snapshot() {
+    let env = TestEnv::builder().build();
+    let mut test_ctx = env.new_context();
+    let backup_dir = tempfile::tempdir().unwrap();
+    let backup_store: ObjectStoreRef =
+        Arc::new(LocalFileSystem::new_with_prefix(backup_dir.path()).unwrap());
+
+    env.block_on(async {
+        test_ctx.open().await;
+
+        let test_table1 = "test_table1";
+        test_ctx.create_fixed_schema_table(test_table1).await;
+
+        let res = test_ctx
+            .table(test_table1)
+            .restore(RestoreRequest {
+                store: backup_store,
+                prefix: "not_exist".to_string(),
+            })
+            .await;
+        assert!(res.is_err());
+    });
+}
@@ -2,12 +2,18 @@
 
 //! Compaction integration tests.
 
+use std::collections::HashMap;
+
 use common_types::time::Timestamp;
 use table_engine::table::FlushRequest;
 
 use crate::{
-    compaction::SizeTieredCompactionOptions,
-    tests::util::{self, TestEnv},
+    compaction::{CompactionStrategy, LeveledCompactionOptions, SizeTieredCompactionOptions},
+    table_options::TTL,
+    tests::{
+        table::RowTuple,
+        util::{self, TestEnv},
+    },
 };
 
 #[test]
@@ -88,3 +94,232 @@ fn test_table_compact_current_segment() {
         .await;
     });
 }
+
+#[test]
+fn test_table_leveled_compaction() {
+    let env = TestEnv::builder().build();
+    let mut test_ctx = env.new_context();
+
+    env.block_on(async {
+        test_ctx.open().await;
+
+        let test_table1 = "test_table1";
+        let strategy = CompactionStrategy::Leveled(LeveledCompactionOptions {
+            level0_file_num_trigger: 2,
+            ..Default::default()
+        });
+        let mut options = HashMap::new();
+        strategy.fill_raw_map(&mut options);
+        let fixed_schema_table = test_ctx
+            .create_fixed_schema_table_with_options(test_table1, options)
+            .await;
+
+        let start_ms = test_ctx.start_ms();
+        let rows_of_flushes = [
+            [
+                (
+                    "key1",
+                    Timestamp::new(start_ms),
+                    "tag1-1",
+                    11.0,
+                    110.0,
+                    "tag2-1",
+                ),
+                (
+                    "key2",
+                    Timestamp::new(start_ms),
+                    "tag1-2",
+                    12.0,
+                    110.0,
+                    "tag2-2",
+                ),
+            ],
+            [
+                (
+                    "key2",
+                    Timestamp::new(start_ms + 1),
+                    "tag1-3",
+                    13.0,
+                    110.0,
+                    "tag2-3",
+                ),
+                (
+                    "key3",
+                    Timestamp::new(start_ms),
+                    "tag1-4",
+                    14.0,
+                    110.0,
+                    "tag2-4",
+                ),
+            ],
+            // Overwrite the rows compacted into level 1.
+            [
+                (
+                    "key1",
+                    Timestamp::new(start_ms),
+                    "tag1-5",
+                    15.0,
+                    150.0,
+                    "tag2-5",
+                ),
+                (
+                    "key2",
+                    Timestamp::new(start_ms + 1),
+                    "tag1-6",
+                    16.0,
+                    160.0,
+                    "tag2-6",
+                ),
+            ],
+            [
+                (
+                    "key3",
+                    Timestamp::new(start_ms + 1),
+                    "tag1-7",
+                    17.0,
+                    170.0,
+                    "tag2-7",
+                ),
+                (
+                    "key4",
+                    Timestamp::new(start_ms),
+                    "tag1-8",
+                    18.0,
+                    180.0,
+                    "tag2-8",
+                ),
+            ],
+        ];
+
+        let mut expect_rows: Vec<RowTuple> = Vec::new();
+        for (idx, rows) in rows_of_flushes.iter().enumerate() {
+            let row_group = fixed_schema_table.rows_to_row_group(rows);
+            test_ctx.write_to_table(test_table1, row_group).await;
+            test_ctx
+                .flush_table_with_request(
+                    test_table1,
+                    FlushRequest {
+                        compact_after_flush: false,
+                        sync: true,
+                    },
+                )
+                .await;
+
+            for row in rows {
+                // The rows with the same key are overwritten.
+                expect_rows.retain(|v| (v.0, v.1) != (row.0, row.1));
+                expect_rows.push(*row);
+            }
+            expect_rows.sort_unstable_by_key(|row_tuple| (row_tuple.0, row_tuple.1));
+
+            // Compact the level 0 files into level 1 after every two flushes.
+            if idx % 2 == 1 {
+                test_ctx.compact_table(test_table1).await;
+
+                util::check_read(
+                    &test_ctx,
+                    &fixed_schema_table,
+                    "Test read after compaction",
+                    test_table1,
+                    &expect_rows,
+                )
+                .await;
+            }
+        }
+
+        test_ctx.reopen_with_tables(&[test_table1]).await;
+
+        util::check_read(
+            &test_ctx,
+            &fixed_schema_table,
+            "Test read after reopen",
+            test_table1,
+            &expect_rows,
+        )
+        .await;
+    });
+}
+
+#[test]
+fn test_table_compact_expired_rows() {
+    let env = TestEnv::builder().build();
+    let mut test_ctx = env.new_context();
+
+    env.block_on(async {
+        test_ctx.open().await;
+
+        let test_table1 = "test_table1";
+        let fixed_schema_table = test_ctx.create_fixed_schema_table(test_table1).await;
+
+        // 3 days ago.
+        let start_ms = test_ctx.start_ms();
+        let now_ms = Timestamp::now().as_i64();
+        let old_rows = [(
+            "key1",
+            Timestamp::new(start_ms),
+            "tag1-1",
+            11.0,
+            110.0,
+            "tag2-1",
+        )];
+        let new_rows = [(
+            "key2",
+            Timestamp::new(now_ms),
+            "tag1-2",
+            12.0,
+            120.0,
+            "tag2-2",
+        )];
+        for rows in [&old_rows, &new_rows] {
+            let row_group = fixed_schema_table.rows_to_row_group(rows);
+            test_ctx.write_to_table(test_table1, row_group).await;
+        }
+
+        // The old rows are expired after the ttl is shortened.
+        let new_opts = HashMap::from([(TTL.to_string(), "1d".to_string())]);
+        test_ctx
+            .try_alter_options(test_table1, new_opts)
+            .await
+            .unwrap();
+
+        util::check_read(
+            &test_ctx,
+            &fixed_schema_table,
+            "Test read expired rows in memtable",
+            test_table1,
+            &new_rows,
+        )
+        .await;
+
+        test_ctx
+            .flush_table_with_request(
+                test_table1,
+                FlushRequest {
+                    compact_after_flush: false,
+                    sync: true,
+                },
+            )
+            .await;
+
+        util::check_read(
+            &test_ctx,
+            &fixed_schema_table,
+            "Test read expired rows in sst",
+            test_table1,
+            &new_rows,
+        )
+        .await;
+
+        test_ctx.compact_table(test_table1).await;
+        test_ctx.reopen_with_tables(&[test_table1]).await;
+
+        util::check_read(
+            &test_ctx,
+            &fixed_schema_table,
+            "Test read after compaction",
+            test_table1,
+            &new_rows,
+        )
+        .await;
+    });
+}
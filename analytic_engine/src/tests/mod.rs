@@ -5,6 +5,8 @@
 #[cfg(test)]
 mod alter_test;
 #[cfg(test)]
+mod backup_test;
+#[cfg(test)]
 mod compaction_test;
 #[cfg(test)]
 mod drop_test;
@@ -12,6 +14,8 @@ mod drop_test;
 mod open_test;
 #[cfg(test)]
 mod read_write_test;
+#[cfg(test)]
+mod rollup_test;
 pub mod row_util;
 pub mod table;
 pub mod util;
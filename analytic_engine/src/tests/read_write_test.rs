@@ -2,11 +2,16 @@
 
 //! Read write test.
 
-use std::{thread, time};
+use std::{collections::HashMap, thread, time};
 
-use common_types::time::Timestamp;
+use arrow_deps::datafusion::logical_plan::{col, lit};
+use common_types::{datum::Datum, string::StringBytes, time::Timestamp};
 use log::info;
-use table_engine::table::ReadOrder;
+use table_engine::{
+    partition::{PartitionBound, PartitionDefinition, PartitionInfo, PartitionType},
+    predicate::PredicateBuilder,
+    table::{ReadOptions, ReadOrder, WriteRequest},
+};
 
 use crate::{
     table_options,
@@ -222,6 +227,196 @@ fn test_table_write_read() {
     });
 }
 
+#[test]
+fn test_columnar_memtable_write_read() {
+    let env = TestEnv::builder().build();
+    let mut test_ctx = env.new_context();
+
+    env.block_on(async {
+        test_ctx.open().await;
+
+        let test_table1 = "test_table1";
+        let mut options = HashMap::new();
+        options.insert(table_options::UPDATE_MODE.to_string(), "append".to_string());
+        options.insert(
+            table_options::MEMTABLE_TYPE.to_string(),
+            "columnar".to_string(),
+        );
+        let fixed_schema_table = test_ctx
+            .create_fixed_schema_table_with_options(test_table1, options)
+            .await;
+
+        let start_ms = test_ctx.start_ms();
+        let rows = [
+            (
+                "key1",
+                Timestamp::new(start_ms),
+                "tag1-1",
+                11.0,
+                110.0,
+                "tag2-1",
+            ),
+            (
+                "key2",
+                Timestamp::new(start_ms),
+                "tag1-2",
+                12.0,
+                110.0,
+                "tag2-2",
+            ),
+            (
+                "key2",
+                Timestamp::new(start_ms + 1),
+                "tag1-3",
+                13.0,
+                110.0,
+                "tag2-3",
+            ),
+            (
+                "key3",
+                Timestamp::new(start_ms),
+                "tag1-4",
+                14.0,
+                110.0,
+                "tag2-4",
+            ),
+        ];
+
+        // Write data to table out of order.
+        let row_group = fixed_schema_table.rows_to_row_group(&[rows[3], rows[1]]);
+        test_ctx.write_to_table(test_table1, row_group).await;
+        let row_group = fixed_schema_table.rows_to_row_group(&[rows[2], rows[0]]);
+        test_ctx.write_to_table(test_table1, row_group).await;
+
+        util::check_read(
+            &test_ctx,
+            &fixed_schema_table,
+            "Test read columnar memtable",
+            test_table1,
+            &rows,
+        )
+        .await;
+
+        // Reopen db, the rows are replayed from wal into the columnar memtable.
+        test_ctx.reopen_with_tables(&[test_table1]).await;
+
+        util::check_read(
+            &test_ctx,
+            &fixed_schema_table,
+            "Test read columnar memtable after reopen",
+            test_table1,
+            &rows,
+        )
+        .await;
+
+        test_ctx.flush_table(test_table1).await;
+
+        util::check_read(
+            &test_ctx,
+            &fixed_schema_table,
+            "Test read columnar memtable after flush",
+            test_table1,
+            &rows,
+        )
+        .await;
+    });
+}
+
+#[test]
+fn test_time_series_sst_write_read() {
+    let env = TestEnv::builder().build();
+    let mut test_ctx = env.new_context();
+
+    env.block_on(async {
+        test_ctx.open().await;
+
+        let test_table1 = "test_table1";
+        let mut options = HashMap::new();
+        options.insert(
+            table_options::SST_TYPE.to_string(),
+            "time_series".to_string(),
+        );
+        let fixed_schema_table = test_ctx
+            .create_fixed_schema_table_with_options(test_table1, options)
+            .await;
+
+        let start_ms = test_ctx.start_ms();
+        let rows = [
+            (
+                "key1",
+                Timestamp::new(start_ms),
+                "tag1-1",
+                11.0,
+                110.0,
+                "tag2-1",
+            ),
+            (
+                "key1",
+                Timestamp::new(start_ms + 1),
+                "tag1-1",
+                12.0,
+                110.0,
+                "tag2-1",
+            ),
+            (
+                "key2",
+                Timestamp::new(start_ms),
+                "tag1-2",
+                13.0,
+                111.0,
+                "tag2-2",
+            ),
+            (
+                "key2",
+                Timestamp::new(start_ms + 2),
+                "tag1-3",
+                14.0,
+                112.0,
+                "tag2-3",
+            ),
+        ];
+
+        let row_group = fixed_schema_table.rows_to_row_group(&rows[..2]);
+        test_ctx.write_to_table(test_table1, row_group).await;
+        test_ctx.flush_table(test_table1).await;
+        let row_group = fixed_schema_table.rows_to_row_group(&rows[2..]);
+        test_ctx.write_to_table(test_table1, row_group).await;
+        test_ctx.flush_table(test_table1).await;
+
+        util::check_read(
+            &test_ctx,
+            &fixed_schema_table,
+            "Test read time series sst",
+            test_table1,
+            &rows,
+        )
+        .await;
+
+        // Reopen db, the sst type is recovered from the table options.
+        test_ctx.reopen_with_tables(&[test_table1]).await;
+
+        util::check_read(
+            &test_ctx,
+            &fixed_schema_table,
+            "Test read time series sst after reopen",
+            test_table1,
+            &rows,
+        )
+        .await;
+
+        test_ctx.compact_table(test_table1).await;
+
+        util::check_read(
+            &test_ctx,
+            &fixed_schema_table,
+            "Test read time series sst after compaction",
+            test_table1,
+            &rows,
+        )
+        .await;
+    });
+}
+
 #[test]
 fn test_table_write_get() {
     let env = TestEnv::builder().build();
@@ -733,3 +928,77 @@ fn test_table_write_read_reverse_after_flush() {
         .await;
     });
 }
+
+fn list_partition(name: &str, values: &[&str]) -> PartitionDefinition {
+    PartitionDefinition {
+        name: name.to_string(),
+        bound: PartitionBound::In(
+            values
+                .iter()
+                .map(|v| Datum::String(StringBytes::from(*v)))
+                .collect(),
+        ),
+    }
+}
+
+#[test]
+fn test_write_read_partitioned_table() {
+    let env = TestEnv::builder().build();
+    let mut test_ctx = env.new_context();
+
+    env.block_on(async {
+        test_ctx.open().await;
+
+        let test_table = "test_write_read_partitioned_table";
+        let partition_info = PartitionInfo {
+            partition_type: PartitionType::List,
+            columns: vec!["string_tag".to_string()],
+            partition_num: 2,
+            definitions: vec![
+                list_partition("p0", &["tag1"]),
+                list_partition("p1", &["tag2"]),
+            ],
+        };
+        let fixed_schema_table = test_ctx
+            .create_fixed_schema_table_with_partition(test_table, partition_info)
+            .await;
+
+        let start_ms = test_ctx.start_ms();
+        let rows = [
+            ("key1", Timestamp::new(start_ms), "tag1", 11.0, 110.0, "v1"),
+            ("key2", Timestamp::new(start_ms), "tag2", 12.0, 120.0, "v2"),
+        ];
+        let row_group = fixed_schema_table.rows_to_row_group(&rows);
+        test_ctx.write_to_table(test_table, row_group).await;
+
+        // Rows not belonging to any partition are rejected.
+        let row_group = fixed_schema_table.rows_to_row_group(&[(
+            "key3",
+            Timestamp::new(start_ms),
+            "tag3",
+            13.0,
+            130.0,
+            "v3",
+        )]);
+        let table = test_ctx.table(test_table);
+        assert!(table.write(WriteRequest { row_group }).await.is_err());
+
+        util::check_read(
+            &test_ctx,
+            &fixed_schema_table,
+            "Test read partitioned table",
+            test_table,
+            &rows,
+        )
+        .await;
+
+        // Nothing is read if all the partitions are pruned.
+        let mut read_request =
+            fixed_schema_table.new_read_all_request(ReadOptions::default(), ReadOrder::None);
+        read_request.predicate = PredicateBuilder::default()
+            .add_pushdown_exprs(&[col("string_tag").eq(lit("tag3"))])
+            .build();
+        let record_batches = test_ctx.read_table(test_table, read_request).await;
+        assert!(record_batches.iter().all(|batch| batch.num_rows() == 0));
+    });
+}
@@ -0,0 +1,247 @@
+// Copyright 2022 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Rollup integration tests.
+
+use std::collections::HashMap;
+
+use common_types::{
+    column_schema,
+    datum::{Datum, DatumKind},
+    record_batch::RecordBatch,
+    schema::{self, Schema},
+    time::Timestamp,
+};
+use table_engine::{table::ReadOptions, OPTION_KEY_ROLLUPS};
+
+use crate::tests::{
+    table,
+    util::{TestContext, TestEnv},
+};
+
+const MINUTE_MS: i64 = 60 * 1000;
+
+fn rollup_target_schema() -> Schema {
+    let mut builder = schema::Builder::new()
+        .auto_increment_column_id(true)
+        .enable_tsid_primary_key(true);
+    for (name, kind) in [("ts", DatumKind::Timestamp), ("tsid", DatumKind::UInt64)] {
+        let column_schema = column_schema::Builder::new(name.to_string(), kind)
+            .is_nullable(false)
+            .build()
+            .unwrap();
+        builder = builder.add_key_column(column_schema).unwrap();
+    }
+
+    let normal_columns = [
+        ("string_tag", DatumKind::String, true),
+        ("double_field1_sum", DatumKind::Double, false),
+        ("double_field1_count", DatumKind::UInt64, false),
+        ("double_field2_max", DatumKind::Double, false),
+    ];
+    for (name, kind, is_tag) in normal_columns {
+        let column_schema = column_schema::Builder::new(name.to_string(), kind)
+            .is_nullable(true)
+            .is_tag(is_tag)
+            .build()
+            .unwrap();
+        builder = builder.add_normal_column(column_schema).unwrap();
+    }
+
+    builder.build().unwrap()
+}
+
+/// Collect (ts, tag, sum, count, max) of the rows of rollup target table.
+fn collect_rollup_rows(
+    schema: &Schema,
+    record_batches: &[RecordBatch],
+) -> Vec<(i64, Datum, Datum, Datum, Datum)> {
+    let column_names = [
+        "ts",
+        "string_tag",
+        "double_field1_sum",
+        "double_field1_count",
+        "double_field2_max",
+    ];
+    let indexes: Vec<_> = column_names
+        .iter()
+        .map(|name| schema.index_of(name).unwrap())
+        .collect();
+
+    let mut rows = Vec::new();
+    for batch in record_batches {
+        for row_idx in 0..batch.num_rows() {
+            let mut datums = indexes.iter().map(|idx| batch.column(*idx).datum(row_idx));
+            let ts = datums.next().unwrap().as_timestamp().unwrap().as_i64();
+            rows.push((
+                ts,
+                datums.next().unwrap(),
+                datums.next().unwrap(),
+                datums.next().unwrap(),
+                datums.next().unwrap(),
+            ));
+        }
+    }
+    rows.sort_by_key(|row| row.0);
+
+    rows
+}
+
+async fn check_rollup_rows(
+    test_ctx: &TestContext,
+    table_name: &str,
+    expect_rows: &[(i64, Datum, Datum, Datum, Datum)],
+) {
+    let schema = test_ctx.table(table_name).schema();
+    let record_batches = test_ctx
+        .read_table(
+            table_name,
+            table::new_read_all_request(schema.clone(), ReadOptions::default()),
+        )
+        .await;
+
+    assert_eq!(expect_rows, collect_rollup_rows(&schema, &record_batches));
+}
+
+#[test]
+fn test_rollup_at_flush() {
+    let env = TestEnv::builder().build();
+    let mut test_ctx = env.new_context();
+
+    env.block_on(async {
+        test_ctx.open().await;
+
+        let source_table = "test_rollup_source";
+        let target_table = "test_rollup_target";
+        test_ctx
+            .create_table_with_schema(target_table, rollup_target_schema(), HashMap::new())
+            .await;
+        let mut options = HashMap::new();
+        options.insert(
+            OPTION_KEY_ROLLUPS.to_string(),
+            format!(
+                "{}:PT1M:string_tag:avg(double_field1),max(double_field2)",
+                target_table
+            ),
+        );
+        let fixed_schema_table = test_ctx
+            .create_fixed_schema_table_with_options(source_table, options)
+            .await;
+
+        let base = test_ctx.start_ms() / MINUTE_MS * MINUTE_MS;
+        let rows = [
+            ("key1", Timestamp::new(base + 1000), "tag1", 1.0, 10.0, "v1"),
+            ("key2", Timestamp::new(base + 2000), "tag1", 3.0, 30.0, "v2"),
+            (
+                "key1",
+                Timestamp::new(base + MINUTE_MS),
+                "tag1",
+                5.0,
+                50.0,
+                "v3",
+            ),
+        ];
+        let row_group = fixed_schema_table.rows_to_row_group(&rows);
+        test_ctx.write_to_table(source_table, row_group).await;
+
+        // Rows of the source table are rolled up into the target table on flush.
+        test_ctx.flush_table(source_table).await;
+
+        let expect_rows = [
+            (
+                base,
+                Datum::from("tag1"),
+                Datum::Double(4.0),
+                Datum::UInt64(2),
+                Datum::Double(30.0),
+            ),
+            (
+                base + MINUTE_MS,
+                Datum::from("tag1"),
+                Datum::Double(5.0),
+                Datum::UInt64(1),
+                Datum::Double(50.0),
+            ),
+        ];
+        check_rollup_rows(&test_ctx, target_table, &expect_rows).await;
+
+        test_ctx
+            .reopen_with_tables(&[source_table, target_table])
+            .await;
+
+        check_rollup_rows(&test_ctx, target_table, &expect_rows).await;
+    });
+}
+
+#[test]
+fn test_rollup_bucket_across_flushes() {
+    let env = TestEnv::builder().build();
+    let mut test_ctx = env.new_context();
+
+    env.block_on(async {
+        test_ctx.open().await;
+
+        let source_table = "test_rollup_source";
+        let target_table = "test_rollup_target";
+        test_ctx
+            .create_table_with_schema(target_table, rollup_target_schema(), HashMap::new())
+            .await;
+        let mut options = HashMap::new();
+        options.insert(
+            OPTION_KEY_ROLLUPS.to_string(),
+            format!(
+                "{}:PT1M:string_tag:avg(double_field1),max(double_field2)",
+                target_table
+            ),
+        );
+        let fixed_schema_table = test_ctx
+            .create_fixed_schema_table_with_options(source_table, options)
+            .await;
+
+        let base = test_ctx.start_ms() / MINUTE_MS * MINUTE_MS;
+        let rows = [("key1", Timestamp::new(base + 1000), "tag1", 1.0, 10.0, "v1")];
+        let row_group = fixed_schema_table.rows_to_row_group(&rows);
+        test_ctx.write_to_table(source_table, row_group).await;
+
+        // The unflushed rows are not in the rollup yet.
+        let watermark = test_ctx.table(source_table).stats().rollup_watermark;
+        assert!(watermark.unwrap() <= Timestamp::new(base + 1000));
+
+        test_ctx.flush_table(source_table).await;
+
+        let watermark = test_ctx.table(source_table).stats().rollup_watermark;
+        assert_eq!(Some(Timestamp::MAX), watermark);
+        let expect_rows = [(
+            base,
+            Datum::from("tag1"),
+            Datum::Double(1.0),
+            Datum::UInt64(1),
+            Datum::Double(10.0),
+        )];
+        check_rollup_rows(&test_ctx, target_table, &expect_rows).await;
+
+        // Overwrite a row and add a new row to the same bucket.
+        let rows = [
+            ("key1", Timestamp::new(base + 1000), "tag1", 2.0, 20.0, "v2"),
+            ("key2", Timestamp::new(base + 3000), "tag1", 3.0, 30.0, "v3"),
+        ];
+        let row_group = fixed_schema_table.rows_to_row_group(&rows);
+        test_ctx.write_to_table(source_table, row_group).await;
+
+        test_ctx.flush_table(source_table).await;
+
+        // The bucket is recomputed from all the rows instead of the flushed rows
+        // only.
+        let expect_rows = [(
+            base,
+            Datum::from("tag1"),
+            Datum::Double(5.0),
+            Datum::UInt64(2),
+            Datum::Double(30.0),
+        )];
+        check_rollup_rows(&test_ctx, target_table, &expect_rows).await;
+
+        // Flushing the target table keeps the latest aggregates.
+        test_ctx.flush_table(target_table).await;
+        check_rollup_rows(&test_ctx, target_table, &expect_rows).await;
+    });
+}
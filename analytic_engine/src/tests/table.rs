@@ -18,6 +18,7 @@ use common_util::config::ReadableDuration;
 use table_engine::{
     self,
     engine::{CreateTableRequest, TableState},
+    partition::PartitionInfo,
     predicate::Predicate,
     table::{GetRequest, ReadOptions, ReadOrder, ReadRequest, SchemaId, TableId, TableSeq},
 };
@@ -270,6 +271,11 @@ impl Builder {
         self
     }
 
+    pub fn table_schema(mut self, table_schema: Schema) -> Self {
+        self.create_request.table_schema = table_schema;
+        self
+    }
+
     pub fn enable_ttl(mut self, enable_ttl: bool) -> Self {
         self.create_request.options.insert(
             table_engine::OPTION_KEY_ENABLE_TTL.to_string(),
@@ -285,6 +291,16 @@ impl Builder {
         self
     }
 
+    pub fn options(mut self, options: HashMap<String, String>) -> Self {
+        self.create_request.options.extend(options);
+        self
+    }
+
+    pub fn partition_info(mut self, partition_info: PartitionInfo) -> Self {
+        self.create_request.partition_info = Some(partition_info);
+        self
+    }
+
     pub fn build_fixed(self) -> FixedSchemaTable {
         FixedSchemaTable {
             create_request: self.create_request,
@@ -8,6 +8,7 @@ use common_types::{
     datum::Datum,
     record_batch::RecordBatch,
     row::{Row, RowGroup},
+    schema::Schema,
     time::Timestamp,
 };
 use common_util::{config::ReadableDuration, runtime};
@@ -18,6 +19,7 @@ use table_engine::{
         CreateTableRequest, DropTableRequest, EngineRuntimes, OpenTableRequest,
         Result as EngineResult, TableEngineRef,
     },
+    partition::PartitionInfo,
     table::{
         AlterSchemaRequest, FlushRequest, GetRequest, ReadOrder, ReadRequest, Result, SchemaId,
         TableId, TableRef, WriteRequest,
@@ -225,11 +227,40 @@ impl TestContext {
     }
 
     pub async fn create_fixed_schema_table(&mut self, table_name: &str) -> FixedSchemaTable {
+        self.create_fixed_schema_table_with_options(table_name, HashMap::new())
+            .await
+    }
+
+    pub async fn create_fixed_schema_table_with_options(
+        &mut self,
+        table_name: &str,
+        options: HashMap<String, String>,
+    ) -> FixedSchemaTable {
+        let fixed_schema_table = FixedSchemaTable::builder()
+            .schema_id(self.schema_id)
+            .table_name(table_name.to_string())
+            .table_id(self.next_table_id())
+            .ttl("7d".parse::<ReadableDuration>().unwrap())
+            .options(options)
+            .build_fixed();
+
+        self.create_table(fixed_schema_table.create_request().clone())
+            .await;
+
+        fixed_schema_table
+    }
+
+    pub async fn create_fixed_schema_table_with_partition(
+        &mut self,
+        table_name: &str,
+        partition_info: PartitionInfo,
+    ) -> FixedSchemaTable {
         let fixed_schema_table = FixedSchemaTable::builder()
             .schema_id(self.schema_id)
             .table_name(table_name.to_string())
             .table_id(self.next_table_id())
             .ttl("7d".parse::<ReadableDuration>().unwrap())
+            .partition_info(partition_info)
             .build_fixed();
 
         self.create_table(fixed_schema_table.create_request().clone())
@@ -238,6 +269,27 @@ impl TestContext {
         fixed_schema_table
     }
 
+    /// Create a table with the given schema, note that the rows of the
+    /// returned table should not be built by the helpers of the fixed schema.
+    pub async fn create_table_with_schema(
+        &mut self,
+        table_name: &str,
+        table_schema: Schema,
+        options: HashMap<String, String>,
+    ) -> FixedSchemaTable {
+        let table = FixedSchemaTable::builder()
+            .schema_id(self.schema_id)
+            .table_name(table_name.to_string())
+            .table_id(self.next_table_id())
+            .table_schema(table_schema)
+            .options(options)
+            .build_fixed();
+
+        self.create_table(table.create_request().clone()).await;
+
+        table
+    }
+
     async fn create_table(&mut self, create_request: CreateTableRequest) {
         let table_name = create_request.table_name.clone();
         let table = self.engine().create_table(create_request).await.unwrap();
@@ -12,6 +12,7 @@ use benchmarks::{
     parquet_bench::ParquetBench,
     scan_memtable_bench::ScanMemTableBench,
     sst_bench::SstBench,
+    sst_format_bench::SstFormatBench,
 };
 use criterion::*;
 
@@ -196,6 +197,42 @@ fn bench_arrow2(c: &mut Criterion) {
     group.finish();
 }
 
+fn bench_build_sst_format_iter(b: &mut Bencher<'_>, bench: &SstFormatBench) {
+    b.iter(|| bench.run_build_bench())
+}
+
+fn bench_read_sst_format_iter(b: &mut Bencher<'_>, bench: &SstFormatBench) {
+    b.iter(|| bench.run_read_bench())
+}
+
+fn bench_sst_format(c: &mut Criterion) {
+    let config = init_bench();
+
+    let mut group = c.benchmark_group("sst_format");
+
+    group.measurement_time(config.sst_format_bench.bench_measurement_time.0);
+    group.sample_size(config.sst_format_bench.bench_sample_size);
+
+    let mut bench = SstFormatBench::new(config.sst_format_bench);
+
+    for i in 0..bench.num_benches() {
+        bench.init_for_bench(i);
+
+        group.bench_with_input(
+            BenchmarkId::new("build_sst_format", format!("{:?}", bench.sst_type())),
+            &bench,
+            bench_build_sst_format_iter,
+        );
+        group.bench_with_input(
+            BenchmarkId::new("read_sst_format", format!("{:?}", bench.sst_type())),
+            &bench,
+            bench_read_sst_format_iter,
+        );
+    }
+
+    group.finish();
+}
+
 criterion_group!(
     benches,
     bench_read_sst,
@@ -204,5 +241,6 @@ criterion_group!(
     bench_scan_memtable,
     bench_merge_memtable,
     bench_arrow2,
+    bench_sst_format,
 );
 criterion_main!(benches);
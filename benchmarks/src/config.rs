@@ -21,6 +21,7 @@ pub struct BenchConfig {
     pub merge_sst_bench: MergeSstBenchConfig,
     pub scan_memtable_bench: ScanMemTableBenchConfig,
     pub merge_memtable_bench: MergeMemTableBenchConfig,
+    pub sst_format_bench: SstFormatBenchConfig,
 }
 
 // TODO(yingwen): Maybe we can use layze static to load config first.
@@ -121,3 +122,20 @@ pub struct MergeMemTableBenchConfig {
 
     pub arena_block_size: ReadableSize,
 }
+
+#[derive(Deserialize)]
+pub struct SstFormatBenchConfig {
+    pub store_path: String,
+    /// The parquet sst to load rows from.
+    pub sst_file_name: String,
+    /// Where to put the sst built by each format.
+    pub output_store_path: String,
+    pub runtime_thread_num: usize,
+
+    pub bench_measurement_time: ReadableDuration,
+    pub bench_sample_size: usize,
+
+    pub read_batch_row_num: usize,
+    pub num_rows_per_row_group: usize,
+    pub predicate: BenchPredicate,
+}
@@ -11,6 +11,7 @@ pub mod merge_sst_bench;
 pub mod parquet_bench;
 pub mod scan_memtable_bench;
 pub mod sst_bench;
+pub mod sst_format_bench;
 pub mod sst_tools;
 pub mod util;
 
@@ -90,6 +90,7 @@ impl ScanMemTableBench {
             projected_schema: self.projected_schema.clone(),
             need_dedup: true,
             reverse: false,
+            tombstones: Vec::new(),
         };
 
         let iter = self.memtable.scan(scan_ctx, scan_req).unwrap();
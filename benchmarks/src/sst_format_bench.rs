@@ -0,0 +1,206 @@
+// Copyright 2022 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Bench to compare the sst formats.
+
+use std::{sync::Arc, time::Instant};
+
+use analytic_engine::{
+    sst::{
+        builder::RecordBatchStreamItem,
+        factory::{Factory, FactoryImpl, SstBuilderOptions, SstReaderOptions, SstType},
+        file::SstMetaData,
+    },
+    table_options::Compression,
+};
+use common_types::{
+    projected_schema::ProjectedSchema, record_batch::RecordBatchWithKeyBuilder,
+    request_id::RequestId, row::Row,
+};
+use common_util::runtime::Runtime;
+use futures::stream::{self, StreamExt};
+use log::info;
+use object_store::{LocalFileSystem, ObjectStore, Path};
+use table_engine::predicate::PredicateRef;
+
+use crate::{config::SstFormatBenchConfig, util};
+
+/// Sst formats to compare.
+const SST_TYPES: [SstType; 2] = [SstType::Parquet, SstType::TimeSeries];
+
+pub struct SstFormatBench {
+    output_store: LocalFileSystem,
+    sst_meta: SstMetaData,
+    /// Rows loaded from the input sst.
+    rows: Vec<Row>,
+    read_batch_row_num: usize,
+    num_rows_per_row_group: usize,
+    predicate: PredicateRef,
+    sst_type: SstType,
+    runtime: Arc<Runtime>,
+}
+
+impl SstFormatBench {
+    pub fn new(config: SstFormatBenchConfig) -> Self {
+        let runtime = Arc::new(util::new_runtime(config.runtime_thread_num));
+
+        let store = LocalFileSystem::new_with_prefix(config.store_path).unwrap();
+        let sst_path = Path::from(config.sst_file_name);
+        let sst_meta = runtime.block_on(util::meta_from_sst(&store, &sst_path, &None, &None));
+
+        let sst_reader_options = SstReaderOptions {
+            sst_type: SstType::Parquet,
+            read_batch_row_num: config.read_batch_row_num,
+            reverse: false,
+            projected_schema: ProjectedSchema::no_projection(sst_meta.schema.clone()),
+            predicate: Arc::new(config.predicate.into_predicate()),
+            meta_cache: None,
+            data_cache: None,
+            runtime: runtime.clone(),
+        };
+        let rows = runtime.block_on(async {
+            let mut sst_reader = FactoryImpl
+                .new_sst_reader(&sst_reader_options, &sst_path, &store)
+                .unwrap();
+            let mut sst_stream = sst_reader.read().await.unwrap();
+
+            let mut rows = Vec::new();
+            while let Some(batch) = sst_stream.next().await {
+                let batch = batch.unwrap();
+                for i in 0..batch.num_rows() {
+                    rows.push(batch.clone_row_at(i));
+                }
+            }
+            rows
+        });
+
+        let bench = SstFormatBench {
+            output_store: LocalFileSystem::new_with_prefix(config.output_store_path).unwrap(),
+            sst_meta,
+            rows,
+            read_batch_row_num: config.read_batch_row_num,
+            num_rows_per_row_group: config.num_rows_per_row_group,
+            predicate: sst_reader_options.predicate,
+            sst_type: SstType::Parquet,
+            runtime,
+        };
+
+        // Build the sst of every format for the read bench.
+        for sst_type in SST_TYPES {
+            let file_size = bench.build_sst(sst_type);
+            info!(
+                "\nSstFormatBench build sst, sst_type:{:?}, rows:{}, file_size:{}",
+                sst_type,
+                bench.rows.len(),
+                file_size
+            );
+        }
+
+        bench
+    }
+
+    pub fn num_benches(&self) -> usize {
+        SST_TYPES.len()
+    }
+
+    pub fn init_for_bench(&mut self, i: usize) {
+        self.sst_type = SST_TYPES[i];
+    }
+
+    pub fn sst_type(&self) -> SstType {
+        self.sst_type
+    }
+
+    fn sst_path(sst_type: SstType) -> Path {
+        Path::from(format!("{:?}.sst", sst_type))
+    }
+
+    /// Build the sst of `sst_type` from the rows, returns the file size.
+    fn build_sst(&self, sst_type: SstType) -> usize {
+        let sst_builder_options = SstBuilderOptions {
+            sst_type,
+            num_rows_per_row_group: self.num_rows_per_row_group,
+            compression: Compression::Zstd,
+        };
+        let sst_path = Self::sst_path(sst_type);
+
+        // Both formats take the same cost to build the record batches.
+        let record_schema_with_key = ProjectedSchema::no_projection(self.sst_meta.schema.clone())
+            .to_record_schema_with_key();
+        let batches: Vec<RecordBatchStreamItem> = self
+            .rows
+            .chunks(self.read_batch_row_num)
+            .map(|rows| {
+                let mut builder = RecordBatchWithKeyBuilder::with_capacity(
+                    record_schema_with_key.clone(),
+                    rows.len(),
+                );
+                for row in rows {
+                    builder.append_row(row.clone()).unwrap();
+                }
+                Ok(builder.build().unwrap())
+            })
+            .collect();
+
+        self.runtime.block_on(async {
+            let mut builder = FactoryImpl
+                .new_sst_builder(&sst_builder_options, &sst_path, &self.output_store)
+                .unwrap();
+            builder
+                .build(
+                    RequestId::next_id(),
+                    &self.sst_meta,
+                    Box::new(stream::iter(batches)),
+                )
+                .await
+                .unwrap();
+
+            self.output_store.head(&sst_path).await.unwrap().size
+        })
+    }
+
+    pub fn run_build_bench(&self) {
+        let begin_instant = Instant::now();
+        let file_size = self.build_sst(self.sst_type);
+
+        info!(
+            "\nSstFormatBench build sst_type:{:?}, file_size:{}, cost:{:?}",
+            self.sst_type,
+            file_size,
+            begin_instant.elapsed(),
+        );
+    }
+
+    pub fn run_read_bench(&self) {
+        let sst_path = Self::sst_path(self.sst_type);
+        let sst_reader_options = SstReaderOptions {
+            sst_type: self.sst_type,
+            read_batch_row_num: self.read_batch_row_num,
+            reverse: false,
+            projected_schema: ProjectedSchema::no_projection(self.sst_meta.schema.clone()),
+            predicate: self.predicate.clone(),
+            meta_cache: None,
+            data_cache: None,
+            runtime: self.runtime.clone(),
+        };
+        let mut sst_reader = FactoryImpl
+            .new_sst_reader(&sst_reader_options, &sst_path, &self.output_store)
+            .unwrap();
+
+        self.runtime.block_on(async {
+            let begin_instant = Instant::now();
+            let mut sst_stream = sst_reader.read().await.unwrap();
+
+            let mut total_rows = 0;
+            while let Some(batch) = sst_stream.next().await {
+                total_rows += batch.unwrap().num_rows();
+            }
+
+            info!(
+                "\nSstFormatBench read sst_type:{:?}, total rows:{}, cost:{:?}",
+                self.sst_type,
+                total_rows,
+                begin_instant.elapsed(),
+            );
+        });
+    }
+}
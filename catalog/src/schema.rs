@@ -9,6 +9,7 @@ use common_types::column_schema::ColumnSchema;
 use snafu::{Backtrace, Snafu};
 use table_engine::{
     engine::{self, TableEngineRef, TableState},
+    partition::PartitionInfo,
     table::{SchemaId, TableId, TableRef},
 };
 
@@ -145,6 +146,8 @@ pub struct CreateTableRequest {
     pub table_name: String,
     /// Table schema
     pub table_schema: common_types::schema::Schema,
+    /// Partition info if this is a partitioned table
+    pub partition_info: Option<PartitionInfo>,
     /// Table engine type
     pub engine: String,
     /// Table options used by each engine
@@ -162,7 +165,7 @@ impl CreateTableRequest {
             table_name: self.table_name,
             table_id,
             table_schema: self.table_schema,
-            partition_info: None,
+            partition_info: self.partition_info,
             engine: self.engine,
             options: self.options,
             state: self.state,
@@ -921,6 +921,7 @@ mod tests {
             schema_id: schema.id(),
             table_name: table_name.to_string(),
             table_schema: common_types::tests::build_schema(),
+            partition_info: None,
             engine: ANALYTIC_ENGINE_TYPE.to_string(),
             options: HashMap::new(),
             state: TableState::Stable,
@@ -118,6 +118,11 @@ impl ProjectedSchema {
         self.0.is_all_projection()
     }
 
+    /// Returns the schema of the table before projection.
+    pub fn table_schema(&self) -> &Schema {
+        &self.0.original_schema
+    }
+
     /// Returns the [RowProjector] to project the rows with source schema to
     /// rows with [RecordSchemaWithKey].
     ///
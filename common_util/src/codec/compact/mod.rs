@@ -8,10 +8,12 @@ mod bytes;
 mod datum;
 mod float;
 mod number;
+mod tsid;
 
 use common_types::bytes::MemBuf;
 use snafu::{ensure, Backtrace, ResultExt, Snafu};
 
+pub use self::tsid::TsidBuilder;
 use crate::codec::consts;
 
 #[derive(Debug, Snafu)]
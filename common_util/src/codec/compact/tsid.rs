@@ -0,0 +1,47 @@
+// Copyright 2022 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Tsid builder
+
+use common_types::{column_schema::ColumnId, datum::Datum, hash::hash64};
+
+use crate::codec::{
+    compact::{MemCompactEncoder, Result},
+    Encoder,
+};
+
+/// Builder to compute the tsid of a row from its tag columns.
+pub struct TsidBuilder<'a> {
+    encoder: MemCompactEncoder,
+    hash_bytes: &'a mut Vec<u8>,
+}
+
+impl<'a> TsidBuilder<'a> {
+    pub fn new(hash_bytes: &'a mut Vec<u8>) -> Self {
+        // Clear the bytes buffer.
+        hash_bytes.clear();
+
+        Self {
+            encoder: MemCompactEncoder,
+            hash_bytes,
+        }
+    }
+
+    pub fn maybe_write_datum(&mut self, column_id: ColumnId, datum: &Datum) -> Result<()> {
+        // Null datum will be ignored, so tsid remains unchanged after adding a null
+        // column.
+        if datum.is_null() {
+            return Ok(());
+        }
+
+        // Write column id first.
+        self.encoder
+            .encode(self.hash_bytes, &Datum::UInt64(u64::from(column_id)))?;
+        // Write datum.
+        self.encoder.encode(self.hash_bytes, datum)?;
+        Ok(())
+    }
+
+    pub fn finish(self) -> u64 {
+        hash64(self.hash_bytes)
+    }
+}
@@ -93,11 +93,26 @@ impl ObjectStore for AliyunOSS {
         Ok(GetResult::Stream(stream::once(async { Ok(bytes) }).boxed()))
     }
 
-    async fn get_range(&self, _location: &Path, _range: Range<usize>) -> Result<Bytes> {
-        Err(Error::Unimplemented {
-            op: "get_range".to_string(),
+    async fn get_range(&self, location: &Path, range: Range<usize>) -> Result<Bytes> {
+        if range.is_empty() {
+            return Ok(Bytes::new());
         }
-        .into())
+
+        // The end of the http range is inclusive.
+        let mut headers = HashMap::with_capacity(1);
+        headers.insert(
+            "Range".to_string(),
+            format!("bytes={}-{}", range.start, range.end - 1),
+        );
+        let bytes = self
+            .oss
+            .get_object(&location.to_string(), Some(headers), None)
+            .await
+            .context(GetObject {
+                path: &location.to_string(),
+            })?;
+
+        Ok(bytes)
     }
 
     async fn head(&self, location: &Path) -> Result<ObjectMeta> {
@@ -0,0 +1,767 @@
+// Copyright 2022 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! A read-through cache on the local disk in front of another
+//! [ObjectStore].
+//!
+//! Objects are cached by pages of a fixed size, a missing page is fetched by a
+//! ranged read from the inner store, so reading a small range of a large
+//! object won't download the whole object. Every page is cached in a file
+//! under the cache directory, whose name is the encoded path of the object
+//! and the index of the page. Every cache file ends with the crc32 checksum of
+//! the page, which is verified every time the file is read. The cache files
+//! are evicted in LRU order once the total bytes exceeds the capacity, and
+//! they are reloaded after restart.
+
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+    io,
+    ops::Range,
+    path::{Path as StdPath, PathBuf},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use async_trait::async_trait;
+use bytes::{Bytes, BytesMut};
+use futures::{
+    stream::{self, BoxStream},
+    StreamExt,
+};
+use lazy_static::lazy_static;
+use log::{info, warn};
+use lru::LruCache;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, NON_ALPHANUMERIC};
+use prometheus::{register_int_counter_vec, register_int_gauge, IntCounterVec, IntGauge};
+use snafu::{ensure, ResultExt, Snafu};
+use upstream::{
+    path::Path, Error as OssError, GetResult, ListResult, ObjectMeta, ObjectStore, Result,
+};
+
+lazy_static! {
+    // Counters:
+    static ref DISK_CACHE_COUNTER: IntCounterVec = register_int_counter_vec!(
+        "object_store_disk_cache_counter",
+        "Counters of the disk cache of the object store",
+        &["type"]
+    )
+    .unwrap();
+    // Gauges:
+    static ref DISK_CACHE_USED_BYTES_GAUGE: IntGauge = register_int_gauge!(
+        "object_store_disk_cache_used_bytes",
+        "Bytes of the objects in the disk cache of the object store"
+    )
+    .unwrap();
+}
+
+/// Suffix of the cache file.
+const CACHE_FILE_SUFFIX: &str = ".cache";
+/// Suffix of the temporary file being written.
+const TMP_FILE_SUFFIX: &str = ".tmp";
+/// Size of the checksum at the end of the cache file.
+const CHECKSUM_SIZE: usize = 4;
+/// Separator between the encoded path and the page index in the name of the
+/// cache file, which never appears in the encoded path.
+const PAGE_SEPARATOR: char = '-';
+
+#[derive(Debug, Snafu)]
+enum Error {
+    #[snafu(display("Failed to create cache dir: {:?}, err: {}", path, source))]
+    CreateDir { path: PathBuf, source: io::Error },
+
+    #[snafu(display("Failed to load cache dir: {:?}, err: {}", path, source))]
+    LoadDir { path: PathBuf, source: io::Error },
+
+    #[snafu(display(
+        "Unexpected size of range read, path: {}, range: {:?}, size: {}",
+        path,
+        range,
+        size
+    ))]
+    UnexpectedRangeSize {
+        path: String,
+        range: Range<usize>,
+        size: usize,
+    },
+}
+
+impl From<Error> for OssError {
+    fn from(source: Error) -> Self {
+        Self::Generic {
+            store: "DiskCache",
+            source: Box::new(source),
+        }
+    }
+}
+
+/// Key of a cached page: (object path, page index).
+type PageKey = (String, usize);
+
+/// Index of the cached pages.
+struct CacheIndex {
+    /// Cached pages: page key -> size of the page.
+    lru: LruCache<PageKey, usize>,
+    used_bytes: usize,
+    /// Objects with cached pages: object path -> cached object, the entry is
+    /// removed once the last page of the object is removed.
+    objects: HashMap<String, CachedObject>,
+}
+
+/// Object with cached pages.
+#[derive(Default)]
+struct CachedObject {
+    /// Number of the cached pages of the object.
+    num_pages: usize,
+    /// Size of the object, `None` if not known yet.
+    size: Option<usize>,
+}
+
+impl CacheIndex {
+    /// Insert the page of the object of `object_size` and returns the pages
+    /// evicted to keep the used bytes under the `capacity`.
+    fn insert(
+        &mut self,
+        key: PageKey,
+        size: usize,
+        object_size: Option<usize>,
+        capacity: usize,
+    ) -> Vec<PageKey> {
+        let object = self.objects.entry(key.0.clone()).or_default();
+        if object_size.is_some() {
+            object.size = object_size;
+        }
+        match self.lru.put(key, size) {
+            Some(old_size) => self.used_bytes -= old_size,
+            None => object.num_pages += 1,
+        }
+        self.used_bytes += size;
+
+        let mut evicted = Vec::new();
+        while self.used_bytes > capacity {
+            match self.lru.pop_lru() {
+                Some((key, size)) => {
+                    self.used_bytes -= size;
+                    self.on_page_removed(&key.0);
+                    evicted.push(key);
+                }
+                None => break,
+            }
+        }
+        DISK_CACHE_USED_BYTES_GAUGE.set(self.used_bytes as i64);
+
+        evicted
+    }
+
+    /// Returns the size of the object if known.
+    fn object_size(&self, path: &str) -> Option<usize> {
+        self.objects.get(path).and_then(|object| object.size)
+    }
+
+    /// Remember the size of the object, which is ignored if the object has no
+    /// cached page, so the sizes are bounded by the cached pages.
+    fn set_object_size(&mut self, path: &str, size: usize) {
+        if let Some(object) = self.objects.get_mut(path) {
+            object.size = Some(size);
+        }
+    }
+
+    fn on_page_removed(&mut self, path: &str) {
+        if let Some(object) = self.objects.get_mut(path) {
+            object.num_pages -= 1;
+            if object.num_pages == 0 {
+                self.objects.remove(path);
+            }
+        }
+    }
+
+    /// Remove the pages of the object, returns the removed pages.
+    fn remove_object(&mut self, path: &str) -> Vec<PageKey> {
+        self.objects.remove(path);
+
+        let keys: Vec<_> = self
+            .lru
+            .iter()
+            .filter(|(key, _)| key.0 == path)
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in &keys {
+            if let Some(size) = self.lru.pop(key) {
+                self.used_bytes -= size;
+            }
+        }
+        DISK_CACHE_USED_BYTES_GAUGE.set(self.used_bytes as i64);
+
+        keys
+    }
+
+    fn remove(&mut self, key: &PageKey) -> bool {
+        match self.lru.pop(key) {
+            Some(size) => {
+                self.used_bytes -= size;
+                self.on_page_removed(&key.0);
+                DISK_CACHE_USED_BYTES_GAUGE.set(self.used_bytes as i64);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// [ObjectStore] with a disk cache in front of the `inner` store.
+pub struct DiskCacheStore<T> {
+    inner: T,
+    /// Directory of the cache files
+    cache_dir: PathBuf,
+    /// Max bytes of the cached pages
+    capacity: usize,
+    /// Size of the page to cache
+    page_size: usize,
+    index: Arc<Mutex<CacheIndex>>,
+}
+
+impl<T: ObjectStore> DiskCacheStore<T> {
+    /// Create a cache store in front of the `inner` store which caches pages
+    /// of `page_size` bytes, the cache files already in the `cache_dir` will
+    /// be reused.
+    ///
+    /// Panic if `page_size` is zero.
+    pub async fn open(
+        cache_dir: impl Into<PathBuf>,
+        capacity: usize,
+        page_size: usize,
+        inner: T,
+    ) -> Result<Self> {
+        assert!(page_size > 0);
+        let cache_dir = cache_dir.into();
+        tokio::fs::create_dir_all(&cache_dir)
+            .await
+            .context(CreateDir { path: &cache_dir })?;
+
+        let store = Self {
+            inner,
+            cache_dir,
+            capacity,
+            page_size,
+            index: Arc::new(Mutex::new(CacheIndex {
+                lru: LruCache::unbounded(),
+                used_bytes: 0,
+                objects: HashMap::new(),
+            })),
+        };
+        store.load_cache_files().await?;
+
+        Ok(store)
+    }
+
+    /// Rebuild the index from the cache files, the least recently modified
+    /// files are regarded as the least recently used ones.
+    async fn load_cache_files(&self) -> Result<()> {
+        let mut files = Vec::new();
+        let mut entries = tokio::fs::read_dir(&self.cache_dir)
+            .await
+            .context(LoadDir {
+                path: &self.cache_dir,
+            })?;
+        while let Some(entry) = entries.next_entry().await.context(LoadDir {
+            path: &self.cache_dir,
+        })? {
+            let path = entry.path();
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            let key = match decode_file_name(&file_name) {
+                Some(key) => key,
+                None => {
+                    // Remove the temporary files left by the last run.
+                    if file_name.ends_with(TMP_FILE_SUFFIX) {
+                        remove_file(&path).await;
+                    }
+                    continue;
+                }
+            };
+
+            let metadata = entry.metadata().await.context(LoadDir {
+                path: &self.cache_dir,
+            })?;
+            let file_size = metadata.len() as usize;
+            if file_size < CHECKSUM_SIZE {
+                remove_file(&path).await;
+                continue;
+            }
+            let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            files.push((modified, key, file_size - CHECKSUM_SIZE));
+        }
+        files.sort_unstable();
+
+        let evicted = {
+            let mut index = self.index.lock().unwrap();
+            let mut evicted = Vec::new();
+            for (_, key, size) in files {
+                evicted.extend(index.insert(key, size, None, self.capacity));
+            }
+            evicted
+        };
+        for key in &evicted {
+            remove_file(&self.cache_file_path(key)).await;
+        }
+
+        let index = self.index.lock().unwrap();
+        info!(
+            "Disk cache loaded, cache_dir:{:?}, pages:{}, used_bytes:{}, capacity:{}",
+            self.cache_dir,
+            index.lru.len(),
+            index.used_bytes,
+            self.capacity
+        );
+
+        Ok(())
+    }
+
+    fn cache_file_path(&self, key: &PageKey) -> PathBuf {
+        self.cache_dir.join(encode_file_name(key))
+    }
+
+    /// Read the page from the cache, returns `None` if the page is not cached
+    /// or the cache file is corrupted.
+    async fn read_cache(&self, key: &PageKey) -> Option<Bytes> {
+        // Promote the object in the lru.
+        let cached = self.index.lock().unwrap().lru.get(key).is_some();
+        if !cached {
+            DISK_CACHE_COUNTER.with_label_values(&["miss"]).inc();
+            return None;
+        }
+
+        let path = self.cache_file_path(key);
+        let checked = match tokio::fs::read(&path).await {
+            Ok(buf) => verify_checksum(buf),
+            Err(e) => {
+                warn!("Failed to read disk cache, path:{:?}, err:{}", path, e);
+                None
+            }
+        };
+        match checked {
+            Some(bytes) => {
+                DISK_CACHE_COUNTER.with_label_values(&["hit"]).inc();
+                Some(bytes)
+            }
+            None => {
+                warn!("Disk cache is corrupted, path:{:?}", path);
+                DISK_CACHE_COUNTER.with_label_values(&["corrupted"]).inc();
+                self.invalidate(key).await;
+                None
+            }
+        }
+    }
+
+    /// Write the page of the object of `object_size` into the cache, failures
+    /// are ignored as the page can be read from the inner store.
+    async fn write_cache(&self, key: PageKey, bytes: &Bytes, object_size: usize) {
+        if bytes.len() > self.capacity {
+            return;
+        }
+
+        let path = self.cache_file_path(&key);
+        let tmp_path =
+            path.with_file_name(format!("{}{}", encode_file_name(&key), TMP_FILE_SUFFIX));
+        let mut buf = Vec::with_capacity(bytes.len() + CHECKSUM_SIZE);
+        buf.extend_from_slice(bytes);
+        buf.extend_from_slice(&crc32fast::hash(bytes).to_be_bytes());
+
+        // Write to a temporary file first so no partial cache files will be
+        // seen after crash.
+        let res = match tokio::fs::write(&tmp_path, &buf).await {
+            Ok(()) => tokio::fs::rename(&tmp_path, &path).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = res {
+            warn!("Failed to write disk cache, path:{:?}, err:{}", path, e);
+            remove_file(&tmp_path).await;
+            return;
+        }
+
+        let evicted =
+            self.index
+                .lock()
+                .unwrap()
+                .insert(key, bytes.len(), Some(object_size), self.capacity);
+        for key in &evicted {
+            DISK_CACHE_COUNTER.with_label_values(&["evict"]).inc();
+            remove_file(&self.cache_file_path(key)).await;
+        }
+    }
+
+    async fn invalidate(&self, key: &PageKey) {
+        if self.index.lock().unwrap().remove(key) {
+            remove_file(&self.cache_file_path(key)).await;
+        }
+    }
+
+    /// Remove all the cached pages of the object.
+    async fn invalidate_object(&self, location: &Path) {
+        let keys = self
+            .index
+            .lock()
+            .unwrap()
+            .remove_object(&location.to_string());
+        for key in &keys {
+            remove_file(&self.cache_file_path(key)).await;
+        }
+    }
+
+    /// Returns the size of the object, which is remembered while the object
+    /// has cached pages as the objects are immutable.
+    async fn object_size(&self, location: &Path) -> Result<usize> {
+        let path = location.to_string();
+        if let Some(size) = self.index.lock().unwrap().object_size(&path) {
+            return Ok(size);
+        }
+
+        let size = self.inner.head(location).await?.size;
+        self.index.lock().unwrap().set_object_size(&path, size);
+
+        Ok(size)
+    }
+
+    /// Fetch the `pages` from the inner store by one ranged read and cache
+    /// them.
+    async fn fetch_pages(
+        &self,
+        location: &Path,
+        pages: Range<usize>,
+        object_size: usize,
+    ) -> Result<Vec<Bytes>> {
+        let range = pages.start * self.page_size..(pages.end * self.page_size).min(object_size);
+        let bytes = self.inner.get_range(location, range.clone()).await?;
+        ensure!(
+            bytes.len() == range.len(),
+            UnexpectedRangeSize {
+                path: location.to_string(),
+                range,
+                size: bytes.len(),
+            }
+        );
+
+        let path = location.to_string();
+        let mut page_bytes = Vec::with_capacity(pages.len());
+        for (i, page) in pages.enumerate() {
+            let start = i * self.page_size;
+            let end = (start + self.page_size).min(bytes.len());
+            let page_data = bytes.slice(start..end);
+            self.write_cache((path.clone(), page), &page_data, object_size)
+                .await;
+            page_bytes.push(page_data);
+        }
+
+        Ok(page_bytes)
+    }
+
+    /// Get the `range` of the object from the cached pages, the missing pages
+    /// are fetched from the inner store.
+    async fn get_range_by_pages(&self, location: &Path, range: Range<usize>) -> Result<Bytes> {
+        let object_size = self.object_size(location).await?;
+        let end = range.end.min(object_size);
+        let start = range.start.min(end);
+        if start == end {
+            return Ok(Bytes::new());
+        }
+
+        let first_page = start / self.page_size;
+        let last_page = (end - 1) / self.page_size;
+        let path = location.to_string();
+        let mut pages = Vec::with_capacity(last_page - first_page + 1);
+        // Start of the consecutive pages missing in the cache.
+        let mut missing_start = None;
+        for page in first_page..=last_page {
+            match self.read_cache(&(path.clone(), page)).await {
+                Some(bytes) => {
+                    if let Some(missing_start) = missing_start.take() {
+                        pages.extend(
+                            self.fetch_pages(location, missing_start..page, object_size)
+                                .await?,
+                        );
+                    }
+                    pages.push(bytes);
+                }
+                None => {
+                    if missing_start.is_none() {
+                        missing_start = Some(page);
+                    }
+                }
+            }
+        }
+        if let Some(missing_start) = missing_start {
+            pages.extend(
+                self.fetch_pages(location, missing_start..last_page + 1, object_size)
+                    .await?,
+            );
+        }
+
+        let offset = start - first_page * self.page_size;
+        if pages.len() == 1 {
+            return Ok(pages[0].slice(offset..offset + end - start));
+        }
+        let mut buf = BytesMut::with_capacity(pages.iter().map(|v| v.len()).sum());
+        for page in &pages {
+            buf.extend_from_slice(page);
+        }
+
+        Ok(buf.freeze().slice(offset..offset + end - start))
+    }
+}
+
+/// Returns the object if the checksum at the end of `buf` matches.
+fn verify_checksum(mut buf: Vec<u8>) -> Option<Bytes> {
+    if buf.len() < CHECKSUM_SIZE {
+        return None;
+    }
+
+    let data_len = buf.len() - CHECKSUM_SIZE;
+    let mut checksum = [0; CHECKSUM_SIZE];
+    checksum.copy_from_slice(&buf[data_len..]);
+    buf.truncate(data_len);
+    if u32::from_be_bytes(checksum) != crc32fast::hash(&buf) {
+        return None;
+    }
+
+    Some(Bytes::from(buf))
+}
+
+fn encode_file_name(key: &PageKey) -> String {
+    format!(
+        "{}{}{}{}",
+        utf8_percent_encode(&key.0, NON_ALPHANUMERIC),
+        PAGE_SEPARATOR,
+        key.1,
+        CACHE_FILE_SUFFIX
+    )
+}
+
+fn decode_file_name(file_name: &str) -> Option<PageKey> {
+    let (encoded, page) = file_name
+        .strip_suffix(CACHE_FILE_SUFFIX)?
+        .rsplit_once(PAGE_SEPARATOR)?;
+    let page = page.parse().ok()?;
+    let path = percent_decode_str(encoded).decode_utf8().ok()?.into_owned();
+
+    Some((path, page))
+}
+
+async fn remove_file(path: &StdPath) {
+    if let Err(e) = tokio::fs::remove_file(path).await {
+        if e.kind() != io::ErrorKind::NotFound {
+            warn!(
+                "Failed to remove disk cache file, path:{:?}, err:{}",
+                path, e
+            );
+        }
+    }
+}
+
+#[async_trait]
+impl<T: ObjectStore> ObjectStore for DiskCacheStore<T> {
+    async fn put(&self, location: &Path, bytes: Bytes) -> Result<()> {
+        self.inner.put(location, bytes).await?;
+        // The object may be overwritten.
+        self.invalidate_object(location).await;
+
+        Ok(())
+    }
+
+    async fn get(&self, location: &Path) -> Result<GetResult> {
+        let bytes = self.get_range_by_pages(location, 0..usize::MAX).await?;
+
+        Ok(GetResult::Stream(stream::once(async { Ok(bytes) }).boxed()))
+    }
+
+    async fn get_range(&self, location: &Path, range: Range<usize>) -> Result<Bytes> {
+        self.get_range_by_pages(location, range).await
+    }
+
+    async fn head(&self, location: &Path) -> Result<ObjectMeta> {
+        self.inner.head(location).await
+    }
+
+    async fn delete(&self, location: &Path) -> Result<()> {
+        self.inner.delete(location).await?;
+        self.invalidate_object(location).await;
+
+        Ok(())
+    }
+
+    async fn list(&self, prefix: Option<&Path>) -> Result<BoxStream<'_, Result<ObjectMeta>>> {
+        self.inner.list(prefix).await
+    }
+
+    async fn list_with_delimiter(&self, prefix: Option<&Path>) -> Result<ListResult> {
+        self.inner.list_with_delimiter(prefix).await
+    }
+}
+
+impl<T: ObjectStore> Display for DiskCacheStore<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "DiskCacheStore({}, {:?})", self.inner, self.cache_dir)
+    }
+}
+
+impl<T: ObjectStore> fmt::Debug for DiskCacheStore<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DiskCacheStore")
+            .field("inner", &self.inner)
+            .field("cache_dir", &self.cache_dir)
+            .field("capacity", &self.capacity)
+            .field("page_size", &self.page_size)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use upstream::local::LocalFileSystem;
+
+    use super::*;
+
+    const PAGE_SIZE: usize = 4;
+
+    async fn open_store(
+        store_dir: &StdPath,
+        cache_dir: &StdPath,
+        capacity: usize,
+    ) -> DiskCacheStore<LocalFileSystem> {
+        let inner = LocalFileSystem::new_with_prefix(store_dir).unwrap();
+        DiskCacheStore::open(cache_dir, capacity, PAGE_SIZE, inner)
+            .await
+            .unwrap()
+    }
+
+    fn cached_keys(store: &DiskCacheStore<LocalFileSystem>) -> Vec<PageKey> {
+        let index = store.index.lock().unwrap();
+        let mut keys: Vec<_> = index.lru.iter().map(|(k, _)| k.clone()).collect();
+        keys.sort();
+        keys
+    }
+
+    fn cached_objects(store: &DiskCacheStore<LocalFileSystem>) -> Vec<(String, usize)> {
+        let index = store.index.lock().unwrap();
+        let mut objects: Vec<_> = index
+            .objects
+            .iter()
+            .map(|(path, object)| (path.clone(), object.num_pages))
+            .collect();
+        objects.sort();
+        objects
+    }
+
+    fn page_keys(path: &str, pages: Range<usize>) -> Vec<PageKey> {
+        pages.map(|page| (path.to_string(), page)).collect()
+    }
+
+    #[tokio::test]
+    async fn test_disk_cache_read_through() {
+        let store_dir = tempfile::tempdir().unwrap();
+        let cache_dir = tempfile::tempdir().unwrap();
+        let store = open_store(store_dir.path(), cache_dir.path(), 1024).await;
+
+        let location = Path::from("dir/1.sst");
+        let data = Bytes::from_static(b"hello world");
+        store.put(&location, data.clone()).await.unwrap();
+        assert!(cached_keys(&store).is_empty());
+
+        // Only the pages covering the range are fetched and cached.
+        let bytes = store.get_range(&location, 6..11).await.unwrap();
+        assert_eq!(Bytes::from_static(b"world"), bytes);
+        assert_eq!(page_keys("dir/1.sst", 1..3), cached_keys(&store));
+
+        // Remove the object in the inner store, the cache still serves the
+        // cached pages.
+        store.inner.delete(&location).await.unwrap();
+        let bytes = store.get_range(&location, 4..9).await.unwrap();
+        assert_eq!(Bytes::from_static(b"o wor"), bytes);
+        assert!(store.get(&location).await.is_err());
+
+        // The whole object is cached by the first read.
+        store.put(&location, data.clone()).await.unwrap();
+        assert!(cached_keys(&store).is_empty());
+        let bytes = store.get(&location).await.unwrap().bytes().await.unwrap();
+        assert_eq!(data, bytes);
+        assert_eq!(page_keys("dir/1.sst", 0..3), cached_keys(&store));
+
+        store.delete(&location).await.unwrap();
+        assert!(cached_keys(&store).is_empty());
+        assert!(store.get(&location).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_disk_cache_evict() {
+        let store_dir = tempfile::tempdir().unwrap();
+        let cache_dir = tempfile::tempdir().unwrap();
+        let store = open_store(store_dir.path(), cache_dir.path(), 10).await;
+
+        for name in ["a", "b", "c"] {
+            let location = Path::from(name);
+            store
+                .put(&location, Bytes::from_static(b"1234"))
+                .await
+                .unwrap();
+            store.get_range(&location, 0..1).await.unwrap();
+        }
+        // Only 2 pages can be kept in the cache.
+        let mut expect = page_keys("b", 0..1);
+        expect.extend(page_keys("c", 0..1));
+        assert_eq!(expect, cached_keys(&store));
+        // The size of the evicted object is forgotten.
+        assert_eq!(
+            vec![("b".to_string(), 1), ("c".to_string(), 1)],
+            cached_objects(&store)
+        );
+        assert!(!cache_dir
+            .path()
+            .join(encode_file_name(&("a".to_string(), 0)))
+            .exists());
+
+        // Objects larger than the capacity are cached by pages.
+        let location = Path::from("d");
+        let data = Bytes::from_static(b"12345678901");
+        store.put(&location, data.clone()).await.unwrap();
+        let bytes = store.get_range(&location, 9..11).await.unwrap();
+        assert_eq!(Bytes::from_static(b"01"), bytes);
+        let mut expect = page_keys("c", 0..1);
+        expect.extend(page_keys("d", 2..3));
+        assert_eq!(expect, cached_keys(&store));
+
+        let bytes = store.get(&location).await.unwrap().bytes().await.unwrap();
+        assert_eq!(data, bytes);
+        assert_eq!(vec![("d".to_string(), 2)], cached_objects(&store));
+
+        store.delete(&location).await.unwrap();
+        assert!(cached_objects(&store).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_disk_cache_reload_and_verify() {
+        let store_dir = tempfile::tempdir().unwrap();
+        let cache_dir = tempfile::tempdir().unwrap();
+        let location = Path::from("dir/1.sst");
+        let data = Bytes::from_static(b"hello world");
+        {
+            let store = open_store(store_dir.path(), cache_dir.path(), 1024).await;
+            store.put(&location, data.clone()).await.unwrap();
+            store.get(&location).await.unwrap();
+        }
+
+        // The cache survives restart.
+        let store = open_store(store_dir.path(), cache_dir.path(), 1024).await;
+        assert_eq!(page_keys("dir/1.sst", 0..3), cached_keys(&store));
+
+        // Corrupt the cache file, the page is read from the inner store.
+        let cache_file = cache_dir
+            .path()
+            .join(encode_file_name(&("dir/1.sst".to_string(), 1)));
+        let mut buf = std::fs::read(&cache_file).unwrap();
+        buf[0] ^= 0xff;
+        std::fs::write(&cache_file, &buf).unwrap();
+        let bytes = store.get(&location).await.unwrap().bytes().await.unwrap();
+        assert_eq!(data, bytes);
+
+        // The cache file is rewritten.
+        let buf = std::fs::read(&cache_file).unwrap();
+        assert_eq!(Some(data.slice(4..8)), verify_checksum(buf));
+    }
+}
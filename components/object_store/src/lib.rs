@@ -2,9 +2,15 @@
 
 //! Re-export of [object_store] crate.
 
+use std::sync::Arc;
+
 pub use upstream::{
     local::LocalFileSystem, path::Path, Error as ObjectStoreError, GetResult, ListResult,
     ObjectMeta, ObjectStore,
 };
 
 pub mod aliyun;
+pub mod disk_cache;
+pub mod s3;
+
+pub type ObjectStoreRef = Arc<dyn ObjectStore>;
@@ -0,0 +1,63 @@
+// Copyright 2022 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Rewrite the footer of the encoded parquet file.
+//!
+//! The key value metadata of the parquet file is given by the writer
+//! properties before any row is written, but some metadata can only be built
+//! after all the rows are written. Rewriting the footer allows to set such
+//! metadata without buffering the rows.
+
+use std::convert::TryInto;
+
+use arrow_deps::parquet::errors::{ParquetError, Result};
+use parquet_format::{FileMetaData, KeyValue};
+use thrift::protocol::{TCompactInputProtocol, TCompactOutputProtocol, TOutputProtocol};
+
+const PARQUET_MAGIC: &[u8; 4] = b"PAR1";
+/// Size of the metadata length and the magic at the end of the file.
+const FOOTER_SIZE: usize = 8;
+
+/// Set the `value` of the `key` in the key value metadata of the parquet file
+/// encoded in `buf`, the old value of the `key` is replaced.
+pub fn set_key_value_metadata(buf: &mut Vec<u8>, key: String, value: String) -> Result<()> {
+    if buf.len() < FOOTER_SIZE + PARQUET_MAGIC.len()
+        || buf[buf.len() - PARQUET_MAGIC.len()..] != PARQUET_MAGIC[..]
+    {
+        return Err(ParquetError::General(
+            "Invalid parquet file, corrupt footer".to_string(),
+        ));
+    }
+
+    let len_start = buf.len() - FOOTER_SIZE;
+    let metadata_len = i32::from_le_bytes(buf[len_start..len_start + 4].try_into().unwrap());
+    if metadata_len < 0 || metadata_len as usize > len_start - PARQUET_MAGIC.len() {
+        return Err(ParquetError::General(format!(
+            "Invalid parquet file, metadata length:{}, file length:{}",
+            metadata_len,
+            buf.len()
+        )));
+    }
+    let metadata_start = len_start - metadata_len as usize;
+
+    let mut metadata = {
+        let mut prot = TCompactInputProtocol::new(&buf[metadata_start..len_start]);
+        FileMetaData::read_from_in_protocol(&mut prot)?
+    };
+    let key_values = metadata.key_value_metadata.get_or_insert_with(Vec::new);
+    key_values.retain(|kv| kv.key != key);
+    key_values.push(KeyValue::new(key, value));
+
+    // The row groups are located by their offsets from the start of the file,
+    // so only the footer needs to be rewritten.
+    buf.truncate(metadata_start);
+    {
+        let mut prot = TCompactOutputProtocol::new(&mut *buf);
+        metadata.write_to_out_protocol(&mut prot)?;
+        prot.flush()?;
+    }
+    let metadata_len = (buf.len() - metadata_start) as i32;
+    buf.extend_from_slice(&metadata_len.to_le_bytes());
+    buf.extend_from_slice(PARQUET_MAGIC);
+
+    Ok(())
+}
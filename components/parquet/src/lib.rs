@@ -1,6 +1,7 @@
 // Copyright 2022 CeresDB Project Authors. Licensed under Apache-2.0.
 
 pub mod cache;
+pub mod footer;
 pub mod reverse_reader;
 mod serialized_reader;
 #[cfg(test)]
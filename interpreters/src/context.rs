@@ -2,7 +2,7 @@
 
 //! Interpreter context
 
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use common_types::request_id::RequestId;
 use query_engine::context::{Context as QueryContext, ContextRef as QueryContextRef};
@@ -20,6 +20,12 @@ pub struct Context {
     request_id: RequestId,
     default_catalog: String,
     default_schema: String,
+    /// Text of the query.
+    query: String,
+    /// Deadline of the query, the query is cancelled once exceeded.
+    timeout: Option<Duration>,
+    /// Returns the results of query as a stream instead of collecting them.
+    stream_output: bool,
 }
 
 impl Context {
@@ -28,6 +34,9 @@ impl Context {
             request_id,
             default_catalog: String::new(),
             default_schema: String::new(),
+            query: String::new(),
+            timeout: None,
+            stream_output: false,
         }
     }
 
@@ -53,6 +62,21 @@ impl Context {
     pub fn request_id(&self) -> RequestId {
         self.request_id
     }
+
+    #[inline]
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    #[inline]
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    #[inline]
+    pub fn stream_output(&self) -> bool {
+        self.stream_output
+    }
 }
 
 #[must_use]
@@ -60,6 +84,9 @@ pub struct Builder {
     request_id: RequestId,
     default_catalog: String,
     default_schema: String,
+    query: String,
+    timeout: Option<Duration>,
+    stream_output: bool,
 }
 
 impl Builder {
@@ -69,11 +96,29 @@ impl Builder {
         self
     }
 
+    pub fn query(mut self, query: String) -> Self {
+        self.query = query;
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn stream_output(mut self, stream_output: bool) -> Self {
+        self.stream_output = stream_output;
+        self
+    }
+
     pub fn build(self) -> Context {
         Context {
             request_id: self.request_id,
             default_catalog: self.default_catalog,
             default_schema: self.default_schema,
+            query: self.query,
+            timeout: self.timeout,
+            stream_output: self.stream_output,
         }
     }
 }
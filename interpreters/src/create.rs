@@ -99,6 +99,7 @@ impl<C: Manager> CreateInterpreter<C> {
             engine,
             table,
             table_schema,
+            partition_info,
             if_not_exists,
             options,
         } = self.plan;
@@ -109,6 +110,7 @@ impl<C: Manager> CreateInterpreter<C> {
             schema_id: schema.id(),
             table_name: table.clone(),
             table_schema,
+            partition_info,
             engine,
             options,
             state: TableState::Stable,
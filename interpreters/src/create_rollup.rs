@@ -0,0 +1,118 @@
+// Copyright 2022 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Interpreter for create rollup statement
+
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use catalog::manager::Manager;
+use snafu::{ensure, ResultExt, Snafu};
+use sql::plan::CreateRollupPlan;
+use table_engine::{engine::TableEngineRef, rollup, OPTION_KEY_ROLLUPS};
+
+use crate::{
+    context::Context,
+    create::CreateInterpreter,
+    interpreter::{self, CreateRollup, Interpreter, InterpreterPtr, Output},
+};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Failed to parse rollups of table, table:{}, err:{}", table, source))]
+    ParseRollups {
+        table: String,
+        source: table_engine::rollup::Error,
+    },
+
+    #[snafu(display("Rollup already exists, table:{}, rollup:{}", table, rollup))]
+    RollupExists { table: String, rollup: String },
+
+    #[snafu(display("Failed to create target table of rollup, err:{}", source))]
+    CreateTarget {
+        source: Box<crate::interpreter::Error>,
+    },
+
+    #[snafu(display("Failed to add rollup to table, table:{}, err:{}", table, source))]
+    AlterOptions {
+        table: String,
+        source: table_engine::table::Error,
+    },
+}
+
+define_result!(Error);
+
+/// Create rollup interpreter, creates the target table and then adds the
+/// rollup to the options of the source table.
+pub struct CreateRollupInterpreter<C> {
+    ctx: Context,
+    plan: CreateRollupPlan,
+    catalog_manager: C,
+    table_engine: TableEngineRef,
+}
+
+impl<C: Manager + 'static> CreateRollupInterpreter<C> {
+    pub fn create(
+        ctx: Context,
+        plan: CreateRollupPlan,
+        catalog_manager: C,
+        table_engine: TableEngineRef,
+    ) -> InterpreterPtr {
+        Box::new(Self {
+            ctx,
+            plan,
+            catalog_manager,
+            table_engine,
+        })
+    }
+
+    async fn execute_create_rollup(self: Box<Self>) -> Result<Output> {
+        let CreateRollupPlan {
+            table,
+            rollup,
+            target,
+        } = self.plan;
+
+        let mut rollups = match table.options().get(OPTION_KEY_ROLLUPS) {
+            Some(v) => rollup::parse_rollups(v).context(ParseRollups {
+                table: table.name(),
+            })?,
+            None => Vec::new(),
+        };
+        if rollups.iter().any(|v| v.table == rollup.table) {
+            ensure!(
+                target.if_not_exists,
+                RollupExists {
+                    table: table.name(),
+                    rollup: &rollup.table,
+                }
+            );
+
+            return Ok(Output::AffectedRows(0));
+        }
+
+        CreateInterpreter::create(self.ctx, target, self.catalog_manager, self.table_engine)
+            .execute()
+            .await
+            .map_err(Box::new)
+            .context(CreateTarget)?;
+
+        rollups.push(rollup);
+        let mut options = HashMap::with_capacity(1);
+        options.insert(
+            OPTION_KEY_ROLLUPS.to_string(),
+            rollup::format_rollups(&rollups),
+        );
+        table.alter_options(options).await.context(AlterOptions {
+            table: table.name(),
+        })?;
+
+        Ok(Output::AffectedRows(1))
+    }
+}
+
+#[async_trait]
+impl<C: Manager + 'static> Interpreter for CreateRollupInterpreter<C> {
+    async fn execute(self: Box<Self>) -> interpreter::Result<Output> {
+        self.execute_create_rollup().await.context(CreateRollup)
+    }
+}
@@ -0,0 +1,126 @@
+// Copyright 2022 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Interpreter for delete statement
+
+use async_trait::async_trait;
+use log::debug;
+use query_engine::executor::{Executor, Query};
+use snafu::{ResultExt, Snafu};
+use sql::plan::DeletePlan;
+use table_engine::table::DeleteRequest;
+
+use crate::{
+    context::Context,
+    interpreter::{Delete, Interpreter, InterpreterPtr, Output, Result as InterpreterResult},
+};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Failed to create query context, err:{}", source))]
+    CreateQueryContext { source: crate::context::Error },
+
+    #[snafu(display("Failed to execute logical plan, err:{}", source))]
+    ExecutePlan {
+        source: query_engine::executor::Error,
+    },
+
+    #[snafu(display("Failed to delete from table, err:{}", source))]
+    DeleteTable { source: table_engine::table::Error },
+}
+
+define_result!(Error);
+
+/// Delete interpreter
+pub struct DeleteInterpreter<T> {
+    ctx: Context,
+    plan: DeletePlan,
+    executor: T,
+}
+
+impl<T: Executor + 'static> DeleteInterpreter<T> {
+    pub fn create(ctx: Context, plan: DeletePlan, executor: T) -> InterpreterPtr {
+        Box::new(Self {
+            ctx,
+            plan,
+            executor,
+        })
+    }
+}
+
+#[async_trait]
+impl<T: Executor> Interpreter for DeleteInterpreter<T> {
+    async fn execute(self: Box<Self>) -> InterpreterResult<Output> {
+        let request_id = self.ctx.request_id();
+        debug!(
+            "Interpreter execute delete begin, request_id:{}, plan:{:?}",
+            request_id, self.plan
+        );
+
+        let DeletePlan {
+            table,
+            time_range,
+            query,
+            series_columns,
+        } = self.plan;
+
+        // Find out the series to delete and the number of rows to delete.
+        let query_ctx = self
+            .ctx
+            .new_query_context()
+            .context(CreateQueryContext)
+            .context(Delete)?;
+        let record_batches = self
+            .executor
+            .execute_logical_plan(query_ctx, Query::new(query))
+            .await
+            .context(ExecutePlan)
+            .context(Delete)?;
+
+        let num_series_columns = series_columns.len();
+        let mut num_rows = 0;
+        let mut series_keys = Vec::new();
+        for record_batch in &record_batches {
+            // The last column is the number of rows of the series.
+            let count_column = record_batch.column(num_series_columns);
+            for row_idx in 0..record_batch.num_rows() {
+                let count = count_column.datum(row_idx).as_u64().unwrap_or(0);
+                if count == 0 {
+                    continue;
+                }
+                num_rows += count as usize;
+
+                if num_series_columns > 0 {
+                    let series_key = (0..num_series_columns)
+                        .map(|col_idx| record_batch.column(col_idx).datum(row_idx))
+                        .collect();
+                    series_keys.push(series_key);
+                }
+            }
+        }
+
+        if num_rows == 0 {
+            return Ok(Output::AffectedRows(0));
+        }
+
+        let request = DeleteRequest {
+            time_range,
+            series_keys: if num_series_columns == 0 {
+                None
+            } else {
+                Some(series_keys)
+            },
+        };
+        table
+            .delete(request)
+            .await
+            .context(DeleteTable)
+            .context(Delete)?;
+
+        debug!(
+            "Interpreter execute delete finish, request_id:{}, num_rows:{}",
+            request_id, num_rows
+        );
+
+        Ok(Output::AffectedRows(num_rows))
+    }
+}
@@ -9,9 +9,11 @@ use table_engine::engine::TableEngineRef;
 
 use crate::{
     alter_table::AlterTableInterpreter, context::Context, create::CreateInterpreter,
+    create_rollup::CreateRollupInterpreter, delete::DeleteInterpreter,
     describe::DescribeInterpreter, drop::DropInterpreter, exists::ExistsInterpreter,
-    insert::InsertInterpreter, interpreter::InterpreterPtr, select::SelectInterpreter,
-    show_create::ShowCreateInInterpreter,
+    insert::InsertInterpreter, interpreter::InterpreterPtr, kill_query::KillQueryInterpreter,
+    registry::QueryRegistryRef, select::SelectInterpreter, show_create::ShowCreateInInterpreter,
+    show_process_list::ShowProcessListInterpreter,
 };
 
 /// A factory to create interpreters
@@ -19,21 +21,31 @@ pub struct Factory<Q, C> {
     query_executor: Q,
     catalog_manager: C,
     table_engine: TableEngineRef,
+    query_registry: QueryRegistryRef,
 }
 
 impl<Q: Executor + 'static, C: CatalogManager + 'static> Factory<Q, C> {
-    pub fn new(query_executor: Q, catalog_manager: C, table_engine: TableEngineRef) -> Self {
+    pub fn new(
+        query_executor: Q,
+        catalog_manager: C,
+        table_engine: TableEngineRef,
+        query_registry: QueryRegistryRef,
+    ) -> Self {
         Self {
             query_executor,
             catalog_manager,
             table_engine,
+            query_registry,
         }
     }
 
     pub fn create(self, ctx: Context, plan: Plan) -> InterpreterPtr {
         match plan {
-            Plan::Query(p) => SelectInterpreter::create(ctx, p, self.query_executor),
+            Plan::Query(p) => {
+                SelectInterpreter::create(ctx, p, self.query_executor, self.query_registry)
+            }
             Plan::Insert(p) => InsertInterpreter::create(ctx, p),
+            Plan::Delete(p) => DeleteInterpreter::create(ctx, p, self.query_executor),
             Plan::Create(p) => {
                 CreateInterpreter::create(ctx, p, self.catalog_manager, self.table_engine)
             }
@@ -44,6 +56,11 @@ impl<Q: Executor + 'static, C: CatalogManager + 'static> Factory<Q, C> {
             Plan::AlterTable(p) => AlterTableInterpreter::create(p),
             Plan::ShowCreate(p) => ShowCreateInInterpreter::create(p),
             Plan::Exists(p) => ExistsInterpreter::create(p),
+            Plan::CreateRollup(p) => {
+                CreateRollupInterpreter::create(ctx, p, self.catalog_manager, self.table_engine)
+            }
+            Plan::ShowProcessList => ShowProcessListInterpreter::create(ctx, self.query_registry),
+            Plan::KillQuery(p) => KillQueryInterpreter::create(ctx, p, self.query_registry),
         }
     }
 }
@@ -3,8 +3,8 @@
 //! Interpreter for insert statement
 
 use async_trait::async_trait;
-use common_types::{column_schema::ColumnId, datum::Datum, hash::hash64};
-use common_util::codec::{compact::MemCompactEncoder, Encoder};
+use common_types::datum::Datum;
+use common_util::codec::compact::TsidBuilder;
 use snafu::{ResultExt, Snafu};
 use sql::plan::InsertPlan;
 use table_engine::table::WriteRequest;
@@ -85,7 +85,10 @@ impl InsertInterpreter {
                 let mut tsid_builder = TsidBuilder::new(&mut hash_bytes);
 
                 for (idx, column_id) in &tag_idx_column_ids {
-                    tsid_builder.maybe_write_datum(*column_id, &row[*idx])?;
+                    tsid_builder
+                        .maybe_write_datum(*column_id, &row[*idx])
+                        .context(EncodeTsid)
+                        .context(Insert)?;
                 }
 
                 let tsid = tsid_builder.finish();
@@ -95,44 +98,3 @@ impl InsertInterpreter {
         Ok(())
     }
 }
-
-struct TsidBuilder<'a> {
-    encoder: MemCompactEncoder,
-    hash_bytes: &'a mut Vec<u8>,
-}
-
-impl<'a> TsidBuilder<'a> {
-    fn new(hash_bytes: &'a mut Vec<u8>) -> Self {
-        // Clear the bytes buffer.
-        hash_bytes.clear();
-
-        Self {
-            encoder: MemCompactEncoder,
-            hash_bytes,
-        }
-    }
-
-    fn maybe_write_datum(&mut self, column_id: ColumnId, datum: &Datum) -> Result<()> {
-        // Null datum will be ignored, so tsid remains unchanged after adding a null
-        // column.
-        if datum.is_null() {
-            return Ok(());
-        }
-
-        // Write column id first.
-        self.encoder
-            .encode(self.hash_bytes, &Datum::UInt64(u64::from(column_id)))
-            .context(EncodeTsid)
-            .context(Insert)?;
-        // Write datum.
-        self.encoder
-            .encode(self.hash_bytes, datum)
-            .context(EncodeTsid)
-            .context(Insert)?;
-        Ok(())
-    }
-
-    fn finish(self) -> u64 {
-        hash64(self.hash_bytes)
-    }
-}
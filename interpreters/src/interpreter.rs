@@ -5,6 +5,7 @@
 use async_trait::async_trait;
 use query_engine::executor::RecordBatchVec;
 use snafu::Snafu;
+use table_engine::stream::SendableRecordBatchStream;
 
 // Make the variant closer to actual error code like invalid arguments.
 #[derive(Debug, Snafu)]
@@ -22,6 +23,9 @@ pub enum Error {
     #[snafu(display("Failed to execute insert, err:{}", source))]
     Insert { source: crate::insert::Error },
 
+    #[snafu(display("Failed to execute delete, err:{}", source))]
+    Delete { source: crate::delete::Error },
+
     #[snafu(display("Failed to execute describe, err:{}", source))]
     Describe { source: crate::describe::Error },
 
@@ -33,17 +37,30 @@ pub enum Error {
 
     #[snafu(display("Failed to execute exists, err:{}", source))]
     Exists { source: crate::exists::Error },
+
+    #[snafu(display("Failed to execute create rollup, err:{}", source))]
+    CreateRollup { source: crate::create_rollup::Error },
+
+    #[snafu(display("Failed to show processlist, err:{}", source))]
+    ShowProcessList {
+        source: crate::show_process_list::Error,
+    },
+
+    #[snafu(display("Failed to execute kill query, err:{}", source))]
+    KillQuery { source: crate::kill_query::Error },
 }
 
 define_result!(Error);
 
-// TODO(yingwen): Maybe add a stream variant for streaming result
 /// The interpreter output
 pub enum Output {
     /// Affected rows number
     AffectedRows(usize),
     /// A vec of RecordBatch
     Records(RecordBatchVec),
+    /// A stream of RecordBatch, only returned by query if the stream output
+    /// is enabled in the context
+    Stream(SendableRecordBatchStream),
 }
 
 /// Interpreter executes the plan it holds
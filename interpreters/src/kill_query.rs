@@ -0,0 +1,75 @@
+// Copyright 2022 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Interpreter for kill query statement
+
+use async_trait::async_trait;
+use log::info;
+use snafu::{ensure, Backtrace, ResultExt, Snafu};
+use sql::plan::KillQueryPlan;
+
+use crate::{
+    context::Context,
+    interpreter::{Interpreter, InterpreterPtr, KillQuery, Output, Result as InterpreterResult},
+    registry::QueryRegistryRef,
+};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display(
+        "Query not found, request_id:{}.\nBacktrace:\n{}",
+        request_id,
+        backtrace
+    ))]
+    QueryNotFound {
+        request_id: u64,
+        backtrace: Backtrace,
+    },
+}
+
+define_result!(Error);
+
+/// Kills a running query of the current tenant.
+pub struct KillQueryInterpreter {
+    ctx: Context,
+    plan: KillQueryPlan,
+    query_registry: QueryRegistryRef,
+}
+
+impl KillQueryInterpreter {
+    pub fn create(
+        ctx: Context,
+        plan: KillQueryPlan,
+        query_registry: QueryRegistryRef,
+    ) -> InterpreterPtr {
+        Box::new(Self {
+            ctx,
+            plan,
+            query_registry,
+        })
+    }
+
+    async fn execute_kill_query(self: Box<Self>) -> Result<Output> {
+        let request_id = self.plan.request_id;
+        let tenant = self.ctx.default_schema();
+        ensure!(
+            self.query_registry.kill(tenant, request_id),
+            QueryNotFound { request_id }
+        );
+
+        info!(
+            "Interpreter kill query, request_id:{}, killed_request_id:{}, tenant:{}",
+            self.ctx.request_id(),
+            request_id,
+            tenant
+        );
+
+        Ok(Output::AffectedRows(1))
+    }
+}
+
+#[async_trait]
+impl Interpreter for KillQueryInterpreter {
+    async fn execute(self: Box<Self>) -> InterpreterResult<Output> {
+        self.execute_kill_query().await.context(KillQuery)
+    }
+}
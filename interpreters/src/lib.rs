@@ -10,14 +10,19 @@ extern crate common_util;
 pub mod alter_table;
 pub mod context;
 pub mod create;
+pub mod create_rollup;
+pub mod delete;
 pub mod describe;
 pub mod drop;
 pub mod exists;
 pub mod factory;
 pub mod insert;
 pub mod interpreter;
+pub mod kill_query;
+pub mod registry;
 pub mod select;
 pub mod show_create;
+pub mod show_process_list;
 
 #[cfg(test)]
 mod tests;
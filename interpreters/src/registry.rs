@@ -0,0 +1,351 @@
+// Copyright 2022 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Registry of the running queries
+//!
+//! Queries registered here can be listed and killed, and may be cancelled
+//! once they exceed their deadline. Cancelling a query drops its execution
+//! future, which also stops the underlying read streams of the engine.
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use common_types::{
+    record_batch::RecordBatch, request_id::RequestId, schema::RecordSchema, time::Timestamp,
+};
+use futures::{
+    future::{AbortHandle, AbortRegistration, Abortable},
+    Stream,
+};
+use snafu::{Backtrace, Snafu};
+use table_engine::stream::{self, RecordBatchStream, SendableRecordBatchStream};
+use tokio::task::JoinHandle;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display(
+        "Query is killed, request_id:{}.\nBacktrace:\n{}",
+        request_id,
+        backtrace
+    ))]
+    Killed {
+        request_id: RequestId,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "Query timeout, request_id:{}, timeout:{:?}.\nBacktrace:\n{}",
+        request_id,
+        timeout,
+        backtrace
+    ))]
+    Timeout {
+        request_id: RequestId,
+        timeout: Duration,
+        backtrace: Backtrace,
+    },
+}
+
+define_result!(Error);
+
+/// Information of a running query.
+#[derive(Debug, Clone)]
+pub struct QueryInfo {
+    pub request_id: RequestId,
+    /// Text of the query.
+    pub query: String,
+    pub tenant: String,
+    /// Time the query started at.
+    pub start_time: Timestamp,
+    start_instant: Instant,
+}
+
+impl QueryInfo {
+    pub fn new(request_id: RequestId, query: String, tenant: String) -> Self {
+        Self {
+            request_id,
+            query,
+            tenant,
+            start_time: Timestamp::now(),
+            start_instant: Instant::now(),
+        }
+    }
+
+    /// Time elapsed since the query started.
+    #[inline]
+    pub fn elapsed(&self) -> Duration {
+        self.start_instant.elapsed()
+    }
+}
+
+struct Entry {
+    info: QueryInfo,
+    abort_handle: AbortHandle,
+}
+
+/// Registry of the running queries, keyed by the request id.
+#[derive(Default)]
+pub struct QueryRegistry {
+    queries: Mutex<HashMap<u64, Entry>>,
+}
+
+pub type QueryRegistryRef = Arc<QueryRegistry>;
+
+impl QueryRegistry {
+    /// Register a query, the query is unregistered once the returned
+    /// [RunningQuery] is dropped.
+    pub fn register(self: &Arc<Self>, info: QueryInfo) -> RunningQuery {
+        let (abort_handle, abort_registration) = AbortHandle::new_pair();
+        let request_id = info.request_id;
+
+        self.queries.lock().unwrap().insert(
+            request_id.as_u64(),
+            Entry {
+                info,
+                abort_handle: abort_handle.clone(),
+            },
+        );
+
+        RunningQuery {
+            request_id,
+            registry: self.clone(),
+            abort_handle,
+            abort_registration: Some(abort_registration),
+        }
+    }
+
+    /// Kill the query with given `request_id` of the `tenant`, returns false
+    /// if no such query is running.
+    pub fn kill(&self, tenant: &str, request_id: u64) -> bool {
+        let queries = self.queries.lock().unwrap();
+        match queries.get(&request_id) {
+            Some(entry) if entry.info.tenant == tenant => {
+                entry.abort_handle.abort();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// List the running queries of the `tenant`, ordered by the request id.
+    pub fn list(&self, tenant: &str) -> Vec<QueryInfo> {
+        let queries = self.queries.lock().unwrap();
+        let mut infos: Vec<_> = queries
+            .values()
+            .filter(|entry| entry.info.tenant == tenant)
+            .map(|entry| entry.info.clone())
+            .collect();
+        infos.sort_unstable_by_key(|info| info.request_id.as_u64());
+
+        infos
+    }
+
+    fn unregister(&self, request_id: RequestId) {
+        self.queries.lock().unwrap().remove(&request_id.as_u64());
+    }
+}
+
+/// A query registered in the [QueryRegistry].
+pub struct RunningQuery {
+    request_id: RequestId,
+    registry: QueryRegistryRef,
+    abort_handle: AbortHandle,
+    abort_registration: Option<AbortRegistration>,
+}
+
+impl RunningQuery {
+    /// Run the `fut` of the query until it finishes, is killed or exceeds
+    /// the `timeout`.
+    pub async fn run<F: Future>(mut self, fut: F, timeout: Option<Duration>) -> Result<F::Output> {
+        let fut = Abortable::new(fut, self.take_abort_registration());
+
+        let output = match timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, fut).await {
+                Ok(output) => output,
+                Err(_) => {
+                    return Timeout {
+                        request_id: self.request_id,
+                        timeout,
+                    }
+                    .fail()
+                }
+            },
+            None => fut.await,
+        };
+
+        output.map_err(|_| {
+            Killed {
+                request_id: self.request_id,
+            }
+            .build()
+        })
+    }
+
+    /// Wrap the result `stream` of the query, the query keeps running until
+    /// the stream is exhausted or dropped, or is killed or exceeds the
+    /// `timeout`.
+    pub fn run_stream(
+        mut self,
+        stream: SendableRecordBatchStream,
+        timeout: Option<Duration>,
+    ) -> SendableRecordBatchStream {
+        let stream = Abortable::new(stream, self.take_abort_registration());
+        let timed_out = Arc::new(AtomicBool::new(false));
+        let timer = timeout.map(|timeout| {
+            let abort_handle = self.abort_handle.clone();
+            let timed_out = timed_out.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(timeout).await;
+                timed_out.store(true, Ordering::Relaxed);
+                abort_handle.abort();
+            })
+        });
+
+        Box::pin(RunningStream {
+            stream,
+            running_query: self,
+            timeout,
+            timed_out,
+            timer,
+            done: false,
+        })
+    }
+
+    fn take_abort_registration(&mut self) -> AbortRegistration {
+        self.abort_registration
+            .take()
+            .expect("Running query should only run once")
+    }
+}
+
+impl Drop for RunningQuery {
+    fn drop(&mut self) {
+        self.registry.unregister(self.request_id);
+    }
+}
+
+/// Result stream of a [RunningQuery].
+struct RunningStream {
+    stream: Abortable<SendableRecordBatchStream>,
+    running_query: RunningQuery,
+    timeout: Option<Duration>,
+    /// Whether the stream is aborted by the timer.
+    timed_out: Arc<AtomicBool>,
+    timer: Option<JoinHandle<()>>,
+    done: bool,
+}
+
+impl RunningStream {
+    fn cancelled_error(&self) -> stream::Error {
+        let request_id = self.running_query.request_id;
+        let err = match self.timeout {
+            Some(timeout) if self.timed_out.load(Ordering::Relaxed) => Timeout {
+                request_id,
+                timeout,
+            }
+            .build(),
+            _ => Killed { request_id }.build(),
+        };
+
+        stream::Error::ErrWithSource {
+            msg: "Query is cancelled".to_string(),
+            source: Box::new(err),
+        }
+    }
+}
+
+impl Stream for RunningStream {
+    type Item = stream::Result<RecordBatch>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.done {
+            return Poll::Ready(None);
+        }
+
+        match Pin::new(&mut self.stream).poll_next(cx) {
+            Poll::Ready(None) => {
+                self.done = true;
+                if self.stream.is_aborted() {
+                    Poll::Ready(Some(Err(self.cancelled_error())))
+                } else {
+                    Poll::Ready(None)
+                }
+            }
+            other => other,
+        }
+    }
+}
+
+impl RecordBatchStream for RunningStream {
+    fn schema(&self) -> &RecordSchema {
+        self.stream.get_ref().schema()
+    }
+}
+
+impl Drop for RunningStream {
+    fn drop(&mut self) {
+        if let Some(timer) = self.timer.take() {
+            timer.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_query_info(tenant: &str) -> QueryInfo {
+        QueryInfo::new(
+            RequestId::next_id(),
+            "select 1".to_string(),
+            tenant.to_string(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_kill_query() {
+        let registry = QueryRegistryRef::default();
+        let info = new_query_info("tenant");
+        let request_id = info.request_id.as_u64();
+        let running = registry.register(info);
+        assert_eq!(1, registry.list("tenant").len());
+        assert!(registry.list("other").is_empty());
+
+        // Queries of other tenants can't be killed.
+        assert!(!registry.kill("other", request_id));
+        assert!(registry.kill("tenant", request_id));
+
+        let res = running.run(futures::future::pending::<()>(), None).await;
+        assert!(matches!(res, Err(Error::Killed { .. })));
+        assert!(registry.list("tenant").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_query_timeout() {
+        let registry = QueryRegistryRef::default();
+        let running = registry.register(new_query_info("tenant"));
+        let res = running
+            .run(
+                futures::future::pending::<()>(),
+                Some(Duration::from_millis(10)),
+            )
+            .await;
+        assert!(matches!(res, Err(Error::Timeout { .. })));
+
+        let running = registry.register(new_query_info("tenant"));
+        let res = running
+            .run(async { 1 }, Some(Duration::from_secs(10)))
+            .await
+            .unwrap();
+        assert_eq!(1, res);
+        assert!(registry.list("tenant").is_empty());
+    }
+}
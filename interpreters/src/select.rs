@@ -11,6 +11,7 @@ use sql::plan::QueryPlan;
 use crate::{
     context::Context,
     interpreter::{Interpreter, InterpreterPtr, Output, Result as InterpreterResult, Select},
+    registry::{QueryInfo, QueryRegistryRef},
 };
 
 #[derive(Debug, Snafu)]
@@ -22,6 +23,9 @@ pub enum Error {
     ExecutePlan {
         source: query_engine::executor::Error,
     },
+
+    #[snafu(display("Query is cancelled, err:{}", source))]
+    Cancelled { source: crate::registry::Error },
 }
 
 define_result!(Error);
@@ -31,14 +35,21 @@ pub struct SelectInterpreter<T> {
     ctx: Context,
     plan: QueryPlan,
     executor: T,
+    query_registry: QueryRegistryRef,
 }
 
 impl<T: Executor + 'static> SelectInterpreter<T> {
-    pub fn create(ctx: Context, plan: QueryPlan, executor: T) -> InterpreterPtr {
+    pub fn create(
+        ctx: Context,
+        plan: QueryPlan,
+        executor: T,
+        query_registry: QueryRegistryRef,
+    ) -> InterpreterPtr {
         Box::new(Self {
             ctx,
             plan,
             executor,
+            query_registry,
         })
     }
 }
@@ -58,10 +69,38 @@ impl<T: Executor> Interpreter for SelectInterpreter<T> {
             .context(CreateQueryContext)
             .context(Select)?;
         let query = Query::new(self.plan);
-        let record_batches = self
-            .executor
-            .execute_logical_plan(query_ctx, query)
+        let running_query = self.query_registry.register(QueryInfo::new(
+            request_id,
+            self.ctx.query().to_string(),
+            self.ctx.default_schema().to_string(),
+        ));
+        if self.ctx.stream_output() {
+            let stream = self
+                .executor
+                .execute_logical_plan_stream(query_ctx, query)
+                .await
+                .context(ExecutePlan)
+                .context(Select)?;
+
+            debug!(
+                "Interpreter execute select returns stream, request_id:{}",
+                request_id
+            );
+
+            return Ok(Output::Stream(
+                running_query.run_stream(stream, self.ctx.timeout()),
+            ));
+        }
+
+        // Dropping the execution future on cancellation also stops the read streams.
+        let record_batches = running_query
+            .run(
+                self.executor.execute_logical_plan(query_ctx, query),
+                self.ctx.timeout(),
+            )
             .await
+            .context(Cancelled)
+            .context(Select)?
             .context(ExecutePlan)
             .context(Select)?;
 
@@ -8,10 +8,14 @@ use arrow_deps::arrow::{
     record_batch::RecordBatch,
 };
 use async_trait::async_trait;
+use common_types::datum::Datum;
 use query_engine::executor::RecordBatchVec;
 use snafu::{ensure, Backtrace, ResultExt, Snafu};
 use sql::{ast::ShowCreateObject, plan::ShowCreatePlan};
-use table_engine::table::TableRef;
+use table_engine::{
+    partition::{PartitionBound, PartitionInfo, PartitionType},
+    table::TableRef,
+};
 
 use crate::interpreter::{
     Interpreter, InterpreterPtr, Output, Result as InterpreterResult, ShowCreate,
@@ -78,10 +82,11 @@ impl ShowCreateInInterpreter {
     fn render_table_sql(table_ref: TableRef) -> String {
         //TODO(boyan) pretty output
         format!(
-            "CREATE TABLE `{}` ({}) ENGINE={}{}",
+            "CREATE TABLE `{}` ({}) ENGINE={}{}{}",
             table_ref.name(),
             Self::render_columns_and_constrains(&table_ref),
             table_ref.engine_type(),
+            Self::render_partition_info(table_ref.partition_info()),
             Self::render_options(table_ref.options())
         )
     }
@@ -113,6 +118,60 @@ impl ShowCreateInInterpreter {
         res
     }
 
+    fn render_partition_info(partition_info: Option<PartitionInfo>) -> String {
+        let partition_info = match partition_info {
+            Some(v) if v.partition_type != PartitionType::None => v,
+            _ => return "".to_string(),
+        };
+
+        let columns: Vec<String> = partition_info
+            .columns
+            .iter()
+            .map(|col| format!("`{}`", col))
+            .collect();
+        let mut res = format!(
+            " PARTITION BY {}({})",
+            partition_info.partition_type.as_str(),
+            columns.join(", ")
+        );
+        if partition_info.partition_type == PartitionType::Hash {
+            res += format!(" PARTITIONS {}", partition_info.partition_num).as_str();
+            return res;
+        }
+
+        let definitions: Vec<String> = partition_info
+            .definitions
+            .iter()
+            .map(|definition| {
+                let values = match &definition.bound {
+                    PartitionBound::LessThan(Some(value)) => {
+                        format!("LESS THAN ({})", Self::render_datum(value))
+                    }
+                    PartitionBound::LessThan(None) => "LESS THAN (MAXVALUE)".to_string(),
+                    PartitionBound::In(values) => {
+                        let values: Vec<String> = values.iter().map(Self::render_datum).collect();
+                        format!("IN ({})", values.join(", "))
+                    }
+                };
+                format!("PARTITION `{}` VALUES {}", definition.name, values)
+            })
+            .collect();
+        res += format!(" ({})", definitions.join(", ")).as_str();
+
+        res
+    }
+
+    /// Render the datum as a sql literal.
+    fn render_datum(datum: &Datum) -> String {
+        match datum {
+            Datum::Null => "NULL".to_string(),
+            Datum::Timestamp(v) => v.as_i64().to_string(),
+            Datum::Varbinary(v) => format!("'{}'", String::from_utf8_lossy(v).replace('\'', "''")),
+            Datum::String(v) => format!("'{}'", v.replace('\'', "''")),
+            v => v.display_string(),
+        }
+    }
+
     fn render_options(opts: HashMap<String, String>) -> String {
         if !opts.is_empty() {
             let mut v: Vec<String> = opts
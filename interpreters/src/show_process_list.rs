@@ -0,0 +1,96 @@
+// Copyright 2022 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Interpreter for show processlist statement
+
+use std::{convert::TryInto, sync::Arc};
+
+use arrow_deps::arrow::{
+    array::{StringArray, TimestampMillisecondArray, UInt64Array},
+    datatypes::{DataType, Field, Schema, TimeUnit},
+    record_batch::RecordBatch,
+};
+use async_trait::async_trait;
+use query_engine::executor::RecordBatchVec;
+use snafu::{ResultExt, Snafu};
+
+use crate::{
+    context::Context,
+    interpreter::{
+        Interpreter, InterpreterPtr, Output, Result as InterpreterResult, ShowProcessList,
+    },
+    registry::{QueryInfo, QueryRegistryRef},
+};
+
+#[derive(Debug, Snafu)]
+pub enum Error {}
+
+define_result!(Error);
+
+/// Lists the running queries of the current tenant.
+pub struct ShowProcessListInterpreter {
+    ctx: Context,
+    query_registry: QueryRegistryRef,
+}
+
+impl ShowProcessListInterpreter {
+    pub fn create(ctx: Context, query_registry: QueryRegistryRef) -> InterpreterPtr {
+        Box::new(Self {
+            ctx,
+            query_registry,
+        })
+    }
+
+    async fn execute_show_process_list(self: Box<Self>) -> Result<Output> {
+        let queries = self.query_registry.list(self.ctx.default_schema());
+
+        process_list_result(&queries).map(Output::Records)
+    }
+}
+
+fn process_list_result(queries: &[QueryInfo]) -> Result<RecordBatchVec> {
+    let schema = Schema::new(vec![
+        Field::new("request_id", DataType::UInt64, false),
+        Field::new("tenant", DataType::Utf8, false),
+        Field::new("query", DataType::Utf8, false),
+        Field::new(
+            "start_time",
+            DataType::Timestamp(TimeUnit::Millisecond, None),
+            false,
+        ),
+        Field::new("elapsed_ms", DataType::UInt64, false),
+    ]);
+
+    let request_ids: Vec<_> = queries.iter().map(|q| q.request_id.as_u64()).collect();
+    let tenants: Vec<_> = queries.iter().map(|q| q.tenant.as_str()).collect();
+    let texts: Vec<_> = queries.iter().map(|q| q.query.as_str()).collect();
+    let start_times: Vec<_> = queries.iter().map(|q| q.start_time.as_i64()).collect();
+    let elapsed: Vec<_> = queries
+        .iter()
+        .map(|q| q.elapsed().as_millis() as u64)
+        .collect();
+
+    let arrow_record_batch = RecordBatch::try_new(
+        Arc::new(schema),
+        vec![
+            Arc::new(UInt64Array::from(request_ids)),
+            Arc::new(StringArray::from(tenants)),
+            Arc::new(StringArray::from(texts)),
+            Arc::new(TimestampMillisecondArray::from(start_times)),
+            Arc::new(UInt64Array::from(elapsed)),
+        ],
+    )
+    .unwrap();
+
+    let record_batch = arrow_record_batch.try_into().unwrap();
+
+    Ok(vec![record_batch])
+}
+
+#[async_trait]
+impl Interpreter for ShowProcessListInterpreter {
+    async fn execute(self: Box<Self>) -> InterpreterResult<Output> {
+        self.execute_show_process_list()
+            .await
+            .context(ShowProcessList)
+    }
+}
@@ -4,9 +4,15 @@ use analytic_engine::tests::util::TestEnv;
 use catalog::consts::{DEFAULT_CATALOG, DEFAULT_SCHEMA};
 use catalog_impls::table_based::TableBasedManager;
 use common_types::request_id::RequestId;
+use futures::TryStreamExt;
 use query_engine::executor::ExecutorImpl;
 use sql::{
-    parser::Parser, plan::Plan, planner::Planner, provider::MetaProvider, tests::MockMetaProvider,
+    parser::Parser,
+    plan::Plan,
+    planner::Planner,
+    promql::{parse_expr, EvalParams},
+    provider::MetaProvider,
+    tests::MockMetaProvider,
 };
 use table_engine::engine::TableEngineRef;
 
@@ -14,6 +20,7 @@ use crate::{
     context::Context,
     factory::Factory,
     interpreter::{Output, Result},
+    registry::QueryRegistryRef,
 };
 
 async fn build_catalog_manager(analytic: TableEngineRef) -> TableBasedManager {
@@ -55,7 +62,12 @@ where
 {
     async fn build_factory(&self) -> Factory<ExecutorImpl, TableBasedManager> {
         let catalog_manager = build_catalog_manager(self.engine()).await;
-        Factory::new(ExecutorImpl::new(), catalog_manager, self.engine())
+        Factory::new(
+            ExecutorImpl::new(),
+            catalog_manager,
+            self.engine(),
+            QueryRegistryRef::default(),
+        )
     }
 
     async fn sql_to_output(&self, sql: &str) -> Result<Output> {
@@ -132,6 +144,24 @@ where
         }
     }
 
+    async fn test_select_table_stream(&self) {
+        let plan = sql_to_plan(&self.meta_provider, "select * from test_table");
+        let ctx = Context::builder(RequestId::next_id())
+            .default_catalog_and_schema(DEFAULT_CATALOG.to_string(), DEFAULT_SCHEMA.to_string())
+            .stream_output(true)
+            .build();
+
+        let factory = self.build_factory().await;
+        let output = factory.create(ctx, plan).execute().await.unwrap();
+        if let Output::Stream(stream) = output {
+            let record_batches: Vec<_> = stream.try_collect().await.unwrap();
+            let num_rows: usize = record_batches.iter().map(|v| v.num_rows()).sum();
+            assert_eq!(num_rows, 2);
+        } else {
+            panic!();
+        }
+    }
+
     async fn test_show_create_table(&self) {
         let sql = "show create table test_table";
         let output = self.sql_to_output(sql).await.unwrap();
@@ -143,6 +173,21 @@ where
         }
     }
 
+    async fn test_process_list(&self) {
+        let sql = "show processlist";
+        let output = self.sql_to_output(sql).await.unwrap();
+        if let Output::Records(v) = output {
+            assert_eq!(v.len(), 1);
+            assert_eq!(v[0].num_rows(), 0);
+        } else {
+            panic!();
+        }
+
+        // No such query is running.
+        let sql = "kill query 123456";
+        assert!(self.sql_to_output(sql).await.is_err());
+    }
+
     async fn test_alter_table(&self) {
         let sql = "alter table test_table add column add_col string";
         let output = self.sql_to_output(sql).await.unwrap();
@@ -161,6 +206,73 @@ where
         }
     }
 
+    async fn promql_to_values(&self, query: &str, time: i64) -> Vec<f64> {
+        let expr = parse_expr(query, EvalParams::instant(time)).unwrap();
+        let planner = Planner::new(&self.meta_provider, RequestId::next_id(), 1);
+        let (plan, column_name) = planner.promql_expr_to_aligned_plan(expr).unwrap();
+        let ctx = Context::builder(RequestId::next_id())
+            .default_catalog_and_schema(DEFAULT_CATALOG.to_string(), DEFAULT_SCHEMA.to_string())
+            .build();
+
+        let factory = self.build_factory().await;
+        let output = factory.create(ctx, plan).execute().await.unwrap();
+        if let Output::Records(v) = output {
+            let mut values = Vec::new();
+            for record_batch in v {
+                let field_idx = record_batch.schema().index_of(&column_name.field).unwrap();
+                values.extend(
+                    (0..record_batch.num_rows()).filter_map(|row_idx| {
+                        record_batch.column(field_idx).datum(row_idx).as_f64()
+                    }),
+                );
+            }
+            values
+        } else {
+            panic!();
+        }
+    }
+
+    async fn test_promql_binary(&self) {
+        // A counter increasing 1 per second.
+        let sql = "INSERT INTO prom_metric(ts, host, value) VALUES \
+        (1638428400000, 'a', 100), (1638428460000, 'a', 160), (1638428520000, 'a', 220), \
+        (1638428580000, 'a', 280), (1638428640000, 'a', 340);";
+        let output = self.sql_to_output(sql).await.unwrap();
+        if let Output::AffectedRows(v) = output {
+            assert_eq!(v, 5);
+        } else {
+            panic!();
+        }
+
+        let time = 1638428640000;
+        let rates = self.promql_to_values("rate(prom_metric[5m])", time).await;
+        assert!(!rates.is_empty());
+        assert!(rates.iter().all(|v| *v > 0.0));
+
+        let scaled = self
+            .promql_to_values("rate(prom_metric[5m]) * 8", time)
+            .await;
+        assert_eq!(rates.iter().map(|v| v * 8.0).collect::<Vec<_>>(), scaled);
+        let scaled = self
+            .promql_to_values("8 * rate(prom_metric[5m])", time)
+            .await;
+        assert_eq!(rates.iter().map(|v| v * 8.0).collect::<Vec<_>>(), scaled);
+
+        // Comparisons without bool filter the samples.
+        let kept = self
+            .promql_to_values("rate(prom_metric[5m]) > 0", time)
+            .await;
+        assert_eq!(rates, kept);
+        let dropped = self
+            .promql_to_values("rate(prom_metric[5m]) < 0", time)
+            .await;
+        assert!(dropped.is_empty());
+        let compared = self
+            .promql_to_values("rate(prom_metric[5m]) < bool 0", time)
+            .await;
+        assert_eq!(vec![0.0; rates.len()], compared);
+    }
+
     async fn test_drop_table(&self) {
         let sql = "drop table test_table";
         let output = self.sql_to_output(sql).await.unwrap();
@@ -188,7 +300,10 @@ async fn test_interpreters() {
     env.test_exists_table().await;
     env.test_insert_table().await;
     env.test_select_table().await;
+    env.test_select_table_stream().await;
     env.test_show_create_table().await;
+    env.test_process_list().await;
     env.test_alter_table().await;
+    env.test_promql_binary().await;
     env.test_drop_table().await;
 }
@@ -138,6 +138,18 @@ impl PromAlignExec {
             PromFunc::Delta => Arc::new(DeltaFunc {}),
             PromFunc::Idelta => Arc::new(IdeltaFunc {}),
             PromFunc::Increase => Arc::new(IncreaseFunc {}),
+            PromFunc::AvgOverTime => Arc::new(OverTimeFunc::Avg),
+            PromFunc::SumOverTime => Arc::new(OverTimeFunc::Sum),
+            PromFunc::MinOverTime => Arc::new(OverTimeFunc::Min),
+            PromFunc::MaxOverTime => Arc::new(OverTimeFunc::Max),
+            PromFunc::CountOverTime => Arc::new(OverTimeFunc::Count),
+            PromFunc::QuantileOverTime(phi) => Arc::new(OverTimeFunc::Quantile(phi)),
+            PromFunc::StddevOverTime => Arc::new(OverTimeFunc::Stddev),
+            PromFunc::LastOverTime => Arc::new(OverTimeFunc::Last),
+            PromFunc::Changes => Arc::new(ChangesFunc),
+            PromFunc::Resets => Arc::new(ResetsFunc),
+            PromFunc::Deriv => Arc::new(DerivFunc),
+            PromFunc::PredictLinear(duration) => Arc::new(PredictLinearFunc { duration }),
         };
         Ok(Self {
             input,
@@ -929,3 +941,376 @@ impl AlignFunc for InstantFunc {
         }))
     }
 }
+
+/// Functions aggregating all the samples in the range, such as
+/// `avg_over_time`. More
+/// [details](https://prometheus.io/docs/prometheus/latest/querying/functions/#aggregation_over_time)
+#[derive(Debug)]
+enum OverTimeFunc {
+    Avg,
+    Sum,
+    Min,
+    Max,
+    Count,
+    /// φ-quantile of the values
+    Quantile(f64),
+    /// Population standard deviation of the values
+    Stddev,
+    Last,
+}
+
+impl AlignFunc for OverTimeFunc {
+    fn call(
+        &self,
+        data: &VecDeque<Sample>,
+        tail_index: usize,
+        timestamp: Timestamp,
+        _param: &AlignParameter,
+    ) -> Result<Option<Sample>> {
+        let values = data.range(..=tail_index).map(|sample| sample.value);
+        let count = (tail_index + 1) as f64;
+        let value = match self {
+            OverTimeFunc::Avg => values.sum::<f64>() / count,
+            OverTimeFunc::Sum => values.sum(),
+            // NaN is only kept when all values are NaN, the same as Prometheus.
+            OverTimeFunc::Min => {
+                values.fold(
+                    f64::NAN,
+                    |min, v| {
+                        if v < min || min.is_nan() {
+                            v
+                        } else {
+                            min
+                        }
+                    },
+                )
+            }
+            OverTimeFunc::Max => {
+                values.fold(
+                    f64::NAN,
+                    |max, v| {
+                        if v > max || max.is_nan() {
+                            v
+                        } else {
+                            max
+                        }
+                    },
+                )
+            }
+            OverTimeFunc::Count => count,
+            OverTimeFunc::Quantile(phi) => quantile(*phi, values.collect()),
+            OverTimeFunc::Stddev => {
+                // Welford's online algorithm.
+                let (mut n, mut mean, mut aux) = (0.0, 0.0, 0.0);
+                for v in values {
+                    n += 1.0;
+                    let delta = v - mean;
+                    mean += delta / n;
+                    aux += delta * (v - mean);
+                }
+                (aux / n).sqrt()
+            }
+            OverTimeFunc::Last => data[tail_index].value,
+        };
+
+        Ok(Some(Sample { timestamp, value }))
+    }
+}
+
+// Port from `quantile` in https://github.com/prometheus/prometheus/blob/063154eab720d8c3d495bd78312c0df090d0bf23/promql/quantile.go
+fn quantile(phi: f64, mut values: Vec<f64>) -> f64 {
+    if values.is_empty() || phi.is_nan() {
+        return f64::NAN;
+    }
+    if phi < 0.0 {
+        return f64::NEG_INFINITY;
+    }
+    if phi > 1.0 {
+        return f64::INFINITY;
+    }
+
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let n = values.len() as f64;
+    let rank = phi * (n - 1.0);
+    let lower_index = rank.floor().max(0.0);
+    let upper_index = (lower_index + 1.0).min(n - 1.0);
+    let weight = rank - rank.floor();
+
+    values[lower_index as usize] * (1.0 - weight) + values[upper_index as usize] * weight
+}
+
+/// Count adjacent samples in the range matching `pred(previous, current)`.
+fn count_adjacent(
+    data: &VecDeque<Sample>,
+    tail_index: usize,
+    timestamp: Timestamp,
+    pred: impl Fn(f64, f64) -> bool,
+) -> Option<Sample> {
+    let count = data
+        .range(..=tail_index)
+        .zip(data.range(1..=tail_index))
+        .filter(|(prev, curr)| pred(prev.value, curr.value))
+        .count();
+
+    Some(Sample {
+        timestamp,
+        value: count as f64,
+    })
+}
+
+/// Implementation of `changes` function in `Prometheus`. More
+/// [details](https://prometheus.io/docs/prometheus/latest/querying/functions/#changes)
+#[derive(Debug)]
+struct ChangesFunc;
+
+impl AlignFunc for ChangesFunc {
+    fn call(
+        &self,
+        data: &VecDeque<Sample>,
+        tail_index: usize,
+        timestamp: Timestamp,
+        _param: &AlignParameter,
+    ) -> Result<Option<Sample>> {
+        Ok(count_adjacent(data, tail_index, timestamp, |prev, curr| {
+            prev != curr && !(prev.is_nan() && curr.is_nan())
+        }))
+    }
+}
+
+/// Implementation of `resets` function in `Prometheus`. More
+/// [details](https://prometheus.io/docs/prometheus/latest/querying/functions/#resets)
+#[derive(Debug)]
+struct ResetsFunc;
+
+impl AlignFunc for ResetsFunc {
+    fn call(
+        &self,
+        data: &VecDeque<Sample>,
+        tail_index: usize,
+        timestamp: Timestamp,
+        _param: &AlignParameter,
+    ) -> Result<Option<Sample>> {
+        Ok(count_adjacent(data, tail_index, timestamp, |prev, curr| {
+            curr < prev
+        }))
+    }
+}
+
+/// Simple linear regression of the samples in the range, returns the slope
+/// per second and the intercept at `intercept_time`.
+///
+/// Port from `linearRegression` in https://github.com/prometheus/prometheus/blob/063154eab720d8c3d495bd78312c0df090d0bf23/promql/functions.go
+fn linear_regression(
+    data: &VecDeque<Sample>,
+    tail_index: usize,
+    intercept_time: Timestamp,
+) -> (f64, f64) {
+    let init_y = data[0].value;
+    let mut const_y = true;
+    let (mut n, mut sum_x, mut sum_y, mut sum_xy, mut sum_x2) = (0.0, 0.0, 0.0, 0.0, 0.0);
+    for sample in data.range(..=tail_index) {
+        const_y = const_y && sample.value == init_y;
+        let x = (sample.timestamp.as_i64() - intercept_time.as_i64()) as f64 / 1000.0;
+        n += 1.0;
+        sum_x += x;
+        sum_y += sample.value;
+        sum_xy += x * sample.value;
+        sum_x2 += x * x;
+    }
+    if const_y {
+        if init_y.is_infinite() {
+            return (f64::NAN, f64::NAN);
+        }
+        return (0.0, init_y);
+    }
+
+    let cov_xy = sum_xy - sum_x * sum_y / n;
+    let var_x = sum_x2 - sum_x * sum_x / n;
+    let slope = cov_xy / var_x;
+    let intercept = sum_y / n - slope * sum_x / n;
+
+    (slope, intercept)
+}
+
+/// Implementation of `deriv` function in `Prometheus`. More
+/// [details](https://prometheus.io/docs/prometheus/latest/querying/functions/#deriv)
+#[derive(Debug)]
+struct DerivFunc;
+
+impl AlignFunc for DerivFunc {
+    fn call(
+        &self,
+        data: &VecDeque<Sample>,
+        tail_index: usize,
+        timestamp: Timestamp,
+        _param: &AlignParameter,
+    ) -> Result<Option<Sample>> {
+        if tail_index < 1 {
+            return Ok(None);
+        }
+
+        // Intercept at the first sample to avoid floating point accuracy issues.
+        let (slope, _) = linear_regression(data, tail_index, data[0].timestamp);
+        Ok(Some(Sample {
+            timestamp,
+            value: slope,
+        }))
+    }
+}
+
+/// Implementation of `predict_linear` function in `Prometheus`. More
+/// [details](https://prometheus.io/docs/prometheus/latest/querying/functions/#predict_linear)
+#[derive(Debug)]
+struct PredictLinearFunc {
+    /// Seconds after the evaluation time to predict
+    duration: f64,
+}
+
+impl AlignFunc for PredictLinearFunc {
+    fn call(
+        &self,
+        data: &VecDeque<Sample>,
+        tail_index: usize,
+        timestamp: Timestamp,
+        _param: &AlignParameter,
+    ) -> Result<Option<Sample>> {
+        if tail_index < 1 {
+            return Ok(None);
+        }
+
+        let (slope, intercept) = linear_regression(data, tail_index, timestamp);
+        Ok(Some(Sample {
+            timestamp,
+            value: slope * self.duration + intercept,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINUTE: i64 = 60_000;
+
+    /// Build samples scraped every 5 minutes starting from 0.
+    fn build_samples(values: &[f64]) -> VecDeque<Sample> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| Sample {
+                timestamp: Timestamp::new(i as i64 * 5 * MINUTE),
+                value: *value,
+            })
+            .collect()
+    }
+
+    /// Evaluate the func on all samples at the timestamp of the last sample.
+    fn eval(func: &dyn AlignFunc, values: &[f64]) -> Option<f64> {
+        let data = build_samples(values);
+        let tail_index = data.len() - 1;
+        let timestamp = data[tail_index].timestamp;
+        let param = AlignParameter {
+            align_range: TimeRange::new_unchecked(Timestamp::new(0), Timestamp::new(i64::MAX)),
+            step: Timestamp::new(5 * MINUTE),
+            offset: Timestamp::new(0),
+            lookback_delta: timestamp,
+        };
+
+        func.call(&data, tail_index, timestamp, &param)
+            .unwrap()
+            .map(|sample| sample.value)
+    }
+
+    fn assert_float_eq(expected: f64, actual: f64) {
+        assert!(
+            (expected - actual).abs() < 1e-9 * expected.abs().max(1.0),
+            "expected:{}, actual:{}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn test_aggr_over_time() {
+        let values = [3.0, 1.0, 4.0, 1.0, 5.0];
+        assert_eq!(Some(2.8), eval(&OverTimeFunc::Avg, &values));
+        assert_eq!(Some(14.0), eval(&OverTimeFunc::Sum, &values));
+        assert_eq!(Some(1.0), eval(&OverTimeFunc::Min, &values));
+        assert_eq!(Some(5.0), eval(&OverTimeFunc::Max, &values));
+        assert_eq!(Some(5.0), eval(&OverTimeFunc::Count, &values));
+        assert_eq!(Some(5.0), eval(&OverTimeFunc::Last, &values));
+
+        // NaN is ignored by min and max unless all values are NaN.
+        let values = [f64::NAN, 2.0, 1.0];
+        assert_eq!(Some(1.0), eval(&OverTimeFunc::Min, &values));
+        assert_eq!(Some(2.0), eval(&OverTimeFunc::Max, &values));
+        assert!(eval(&OverTimeFunc::Max, &[f64::NAN]).unwrap().is_nan());
+    }
+
+    #[test]
+    fn test_quantile_over_time() {
+        // Cases from the test data of Prometheus.
+        let cases = [
+            (0.0, [0.0, 0.0, 0.0]),
+            (0.5, [0.5, 1.0, 1.0]),
+            (0.75, [0.75, 1.5, 2.5]),
+            (0.8, [0.8, 1.6, 2.8]),
+            (1.0, [1.0, 2.0, 4.0]),
+            (-1.0, [f64::NEG_INFINITY; 3]),
+            (2.0, [f64::INFINITY; 3]),
+        ];
+        let inputs: [&[f64]; 3] = [&[0.0, 1.0], &[0.0, 1.0, 2.0], &[0.0, 1.0, 4.0]];
+        for (phi, expected) in cases {
+            for (input, expected) in inputs.iter().zip(expected) {
+                let actual = eval(&OverTimeFunc::Quantile(phi), input).unwrap();
+                assert_eq!(expected, actual, "phi:{}, input:{:?}", phi, input);
+            }
+        }
+        assert!(eval(&OverTimeFunc::Quantile(f64::NAN), &[1.0])
+            .unwrap()
+            .is_nan());
+    }
+
+    #[test]
+    fn test_stddev_over_time() {
+        let actual = eval(&OverTimeFunc::Stddev, &[0.0, 8.0, 8.0, 2.0, 3.0]).unwrap();
+        assert_float_eq(10.56_f64.sqrt(), actual);
+        assert_eq!(Some(0.0), eval(&OverTimeFunc::Stddev, &[1.0]));
+    }
+
+    #[test]
+    fn test_changes_and_resets() {
+        // Cases from the test data of Prometheus.
+        let values = [1.0, 2.0, 3.0, 0.0, 1.0, 0.0, 0.0, 1.0, 2.0, 0.0];
+        assert_eq!(Some(8.0), eval(&ChangesFunc, &values));
+        assert_eq!(Some(3.0), eval(&ResetsFunc, &values));
+
+        assert_eq!(Some(0.0), eval(&ChangesFunc, &[1.0]));
+        assert_eq!(Some(0.0), eval(&ChangesFunc, &[f64::NAN, f64::NAN]));
+        assert_eq!(Some(1.0), eval(&ChangesFunc, &[1.0, f64::NAN]));
+    }
+
+    #[test]
+    fn test_deriv_and_predict_linear() {
+        // Cases from the test data of Prometheus, a counter reset in the middle.
+        let values = [
+            0.0, 10.0, 20.0, 30.0, 40.0, 0.0, 10.0, 20.0, 30.0, 40.0, 50.0,
+        ];
+        assert_float_eq(0.010606060606060607, eval(&DerivFunc, &values).unwrap());
+        let func = PredictLinearFunc { duration: 3600.0 };
+        assert_float_eq(76.81818181818181, eval(&func, &values).unwrap());
+
+        // Linear values.
+        let values = [0.0, 300.0, 600.0];
+        assert_float_eq(1.0, eval(&DerivFunc, &values).unwrap());
+        assert_float_eq(4200.0, eval(&func, &values).unwrap());
+
+        // Constant values.
+        assert_eq!(Some(0.0), eval(&DerivFunc, &[3.0, 3.0]));
+        assert_eq!(Some(3.0), eval(&func, &[3.0, 3.0]));
+
+        // One sample is not enough.
+        assert_eq!(None, eval(&DerivFunc, &[1.0]));
+        assert_eq!(None, eval(&func, &[1.0]));
+    }
+}
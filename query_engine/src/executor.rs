@@ -63,12 +63,22 @@ impl Query {
 /// Executes the logical plan
 #[async_trait]
 pub trait Executor: Clone + Send + Sync {
-    // TODO(yingwen): Maybe return a stream
     /// Execute the query, returning the query results as RecordBatchVec
     ///
     /// REQUIRE: The meta data of tables in query should be found from
     /// ContextRef
     async fn execute_logical_plan(&self, ctx: ContextRef, query: Query) -> Result<RecordBatchVec>;
+
+    /// Execute the query, returning the query results as a stream so the
+    /// results can be consumed incrementally
+    ///
+    /// REQUIRE: The meta data of tables in query should be found from
+    /// ContextRef
+    async fn execute_logical_plan_stream(
+        &self,
+        ctx: ContextRef,
+        query: Query,
+    ) -> Result<SendableRecordBatchStream>;
 }
 
 #[derive(Clone, Default)]
@@ -83,22 +93,8 @@ impl ExecutorImpl {
 #[async_trait]
 impl Executor for ExecutorImpl {
     async fn execute_logical_plan(&self, ctx: ContextRef, query: Query) -> Result<RecordBatchVec> {
-        let plan = query.plan;
-
-        // Register catalogs to datafusion execution context.
-        let catalogs = CatalogProviderAdapter::new_adapters(plan.tables.clone());
-        let df_ctx = ctx.df_exec_ctx();
-        for (name, catalog) in catalogs {
-            df_ctx.register_catalog(&name, Arc::new(catalog));
-        }
         let request_id = ctx.request_id();
-
-        let physical_plan = optimize_plan(ctx, plan).await?;
-
-        debug!(
-            "Executor physical optimization finished, request_id:{}, physical_plan: {:?}",
-            request_id, physical_plan
-        );
+        let physical_plan = build_physical_plan(ctx, query).await?;
 
         let stream = physical_plan.execute().await.context(ExecutePhysical)?;
 
@@ -114,6 +110,37 @@ impl Executor for ExecutorImpl {
 
         Ok(record_batches)
     }
+
+    async fn execute_logical_plan_stream(
+        &self,
+        ctx: ContextRef,
+        query: Query,
+    ) -> Result<SendableRecordBatchStream> {
+        let physical_plan = build_physical_plan(ctx, query).await?;
+
+        physical_plan.execute().await.context(ExecutePhysical)
+    }
+}
+
+async fn build_physical_plan(ctx: ContextRef, query: Query) -> Result<PhysicalPlanPtr> {
+    let plan = query.plan;
+
+    // Register catalogs to datafusion execution context.
+    let catalogs = CatalogProviderAdapter::new_adapters(plan.tables.clone());
+    let df_ctx = ctx.df_exec_ctx();
+    for (name, catalog) in catalogs {
+        df_ctx.register_catalog(&name, Arc::new(catalog));
+    }
+    let request_id = ctx.request_id();
+
+    let physical_plan = optimize_plan(ctx, plan).await?;
+
+    debug!(
+        "Executor physical optimization finished, request_id:{}, physical_plan: {:?}",
+        request_id, physical_plan
+    );
+
+    Ok(physical_plan)
 }
 
 async fn optimize_plan(ctx: ContextRef, plan: QueryPlan) -> Result<PhysicalPlanPtr> {
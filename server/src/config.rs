@@ -2,11 +2,12 @@
 
 //! Server configs
 
-use analytic_engine;
+use analytic_engine::{self, storage_options::StorageOptions};
+use common_util::config::{ReadableDuration, ReadableSize};
 use meta_client::MetaClientConfig;
 use serde_derive::Deserialize;
 
-use crate::router::RuleList;
+use crate::{limiter::LimiterConfig, router::RuleList};
 
 #[derive(Debug, Deserialize)]
 #[serde(default)]
@@ -27,6 +28,15 @@ pub struct Config {
     pub bind_addr: String,
     pub http_port: u16,
     pub grpc_port: u16,
+    /// Port of the arrow flight service, the service is disabled if not set.
+    pub flight_port: Option<u16>,
+    /// Port of the mysql protocol service, the service is disabled if not set.
+    pub mysql_port: Option<u16>,
+    /// Max size of the packet sent by the mysql client.
+    pub mysql_max_allowed_packet: ReadableSize,
+    /// Port of the postgresql protocol service, the service is disabled if not
+    /// set.
+    pub postgresql_port: Option<u16>,
     pub grpc_server_cq_count: usize,
 
     // Engine related configs:
@@ -49,6 +59,14 @@ pub struct Config {
 
     // Analytic engine configs:
     pub analytic: analytic_engine::Config,
+    /// Storage to snapshot the tables to, the admin snapshot and restore apis
+    /// are disabled if not set.
+    pub backup_storage: Option<StorageOptions>,
+    /// Quotas of the tenants.
+    pub limiter: LimiterConfig,
+    /// Queries running longer than the timeout are cancelled, no timeout if
+    /// not set.
+    pub query_timeout: Option<ReadableDuration>,
 }
 
 impl Default for RuntimeConfig {
@@ -68,6 +86,10 @@ impl Default for Config {
             bind_addr: String::from("127.0.0.1"),
             http_port: 5000,
             grpc_port,
+            flight_port: None,
+            mysql_port: None,
+            mysql_max_allowed_packet: ReadableSize::mb(64),
+            postgresql_port: None,
             grpc_server_cq_count: 20,
             runtime: RuntimeConfig::default(),
             log_level: "debug".to_string(),
@@ -83,6 +105,9 @@ impl Default for Config {
             },
             route_rules: RuleList::default(),
             analytic: analytic_engine::Config::default(),
+            backup_storage: None,
+            limiter: LimiterConfig::default(),
+            query_timeout: None,
         }
     }
 }
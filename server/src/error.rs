@@ -11,6 +11,8 @@ pub enum StatusCode {
     Ok = 200,
     InvalidArgument = 400,
     NotFound = 404,
+    /// The request is rejected by the limiter, including the quota of the
+    /// tenant.
     TooManyRequests = 429,
     InternalError = 500,
 }
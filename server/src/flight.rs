@@ -0,0 +1,644 @@
+// Copyright 2022 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Arrow Flight service
+//!
+//! Executes sql and streams the record batches as arrow ipc, which is much
+//! more efficient than the avro encoded response of the grpc query for large
+//! result sets.
+//!
+//! Both plain Flight and the statement queries of Flight SQL are supported:
+//! - Plain Flight: the command of the flight descriptor and the ticket are the
+//!   utf8 sql text.
+//! - Flight SQL: the command is a `CommandStatementQuery` and the ticket is a
+//!   `TicketStatementQuery` whose handle is the sql text, both packed in
+//!   `google.protobuf.Any`.
+
+use std::{
+    collections::VecDeque,
+    net::IpAddr,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use arrow_deps::arrow::{
+    datatypes::{Schema, SchemaRef as ArrowSchemaRef},
+    ipc::writer::{write_message, IpcDataGenerator, IpcWriteOptions},
+};
+use arrow_flight::{
+    flight_service_server::{FlightService, FlightServiceServer},
+    utils::flight_data_from_arrow_batch,
+    Action, ActionType, Criteria, Empty, FlightData, FlightDescriptor, FlightEndpoint, FlightInfo,
+    HandshakeRequest, HandshakeResponse, PutResult, SchemaAsIpc, SchemaResult, Ticket,
+};
+use async_trait::async_trait;
+use catalog::manager::Manager as CatalogManager;
+use common_types::{record_batch::RecordBatch, request_id::RequestId};
+use futures::{stream, Stream};
+use interpreters::{context::Context as InterpreterContext, factory::Factory, interpreter::Output};
+use log::{error, info};
+use prost::Message;
+use query_engine::executor::Executor as QueryExecutor;
+use snafu::{Backtrace, OptionExt, ResultExt, Snafu};
+use sql::{
+    frontend::{Context as SqlContext, Frontend},
+    plan::Plan,
+    provider::CatalogMetaProvider,
+};
+use table_engine::engine::EngineRuntimes;
+use tokio::sync::oneshot::{self, Sender};
+use tonic::{Request, Response, Status, Streaming};
+
+use crate::{
+    consts, handlers::sql::affected_rows_record_batch, instance::InstanceRef, limiter::QueryPermit,
+};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Missing runtimes to build service.\nBacktrace:\n{}", backtrace))]
+    MissingRuntimes { backtrace: Backtrace },
+
+    #[snafu(display("Missing instance to build service.\nBacktrace:\n{}", backtrace))]
+    MissingInstance { backtrace: Backtrace },
+
+    #[snafu(display(
+        "Failed to parse ip addr, ip:{}, err:{}.\nBacktrace:\n{}",
+        ip,
+        source,
+        backtrace
+    ))]
+    ParseIpAddr {
+        ip: String,
+        source: std::net::AddrParseError,
+        backtrace: Backtrace,
+    },
+}
+
+define_result!(Error);
+
+/// Type url of the `CommandStatementQuery` of Flight SQL.
+const COMMAND_STATEMENT_QUERY_TYPE_URL: &str =
+    "type.googleapis.com/arrow.flight.protocol.sql.CommandStatementQuery";
+/// Type url of the `TicketStatementQuery` of Flight SQL.
+const TICKET_STATEMENT_QUERY_TYPE_URL: &str =
+    "type.googleapis.com/arrow.flight.protocol.sql.TicketStatementQuery";
+
+/// `google.protobuf.Any`
+#[derive(Clone, PartialEq, Message)]
+struct Any {
+    #[prost(string, tag = "1")]
+    type_url: String,
+    #[prost(bytes = "vec", tag = "2")]
+    value: Vec<u8>,
+}
+
+/// `arrow.flight.protocol.sql.CommandStatementQuery`
+#[derive(Clone, PartialEq, Message)]
+struct CommandStatementQuery {
+    #[prost(string, tag = "1")]
+    query: String,
+}
+
+/// `arrow.flight.protocol.sql.TicketStatementQuery`
+#[derive(Clone, PartialEq, Message)]
+struct TicketStatementQuery {
+    #[prost(bytes = "vec", tag = "1")]
+    statement_handle: Vec<u8>,
+}
+
+/// A sql statement to execute, decoded from the flight descriptor or the
+/// ticket.
+struct Statement {
+    sql: String,
+    /// Whether the statement is sent by the Flight SQL protocol.
+    flight_sql: bool,
+}
+
+impl Statement {
+    fn from_command(cmd: &[u8]) -> std::result::Result<Self, Status> {
+        if let Some(any) = decode_any(cmd, COMMAND_STATEMENT_QUERY_TYPE_URL) {
+            let command = CommandStatementQuery::decode(any.value.as_slice())
+                .map_err(|e| Status::invalid_argument(format!("Invalid command, err:{}", e)))?;
+            return Ok(Self {
+                sql: command.query,
+                flight_sql: true,
+            });
+        }
+
+        Self::from_sql_bytes(cmd)
+    }
+
+    fn from_ticket(ticket: &[u8]) -> std::result::Result<Self, Status> {
+        if let Some(any) = decode_any(ticket, TICKET_STATEMENT_QUERY_TYPE_URL) {
+            let ticket = TicketStatementQuery::decode(any.value.as_slice())
+                .map_err(|e| Status::invalid_argument(format!("Invalid ticket, err:{}", e)))?;
+            let mut statement = Self::from_sql_bytes(&ticket.statement_handle)?;
+            statement.flight_sql = true;
+            return Ok(statement);
+        }
+
+        Self::from_sql_bytes(ticket)
+    }
+
+    fn from_sql_bytes(bytes: &[u8]) -> std::result::Result<Self, Status> {
+        let sql = String::from_utf8(bytes.to_vec())
+            .map_err(|e| Status::invalid_argument(format!("Sql is not utf8, err:{}", e)))?;
+        Ok(Self {
+            sql,
+            flight_sql: false,
+        })
+    }
+
+    fn to_ticket(&self) -> Ticket {
+        let ticket = if self.flight_sql {
+            let handle = TicketStatementQuery {
+                statement_handle: self.sql.as_bytes().to_vec(),
+            };
+            Any {
+                type_url: TICKET_STATEMENT_QUERY_TYPE_URL.to_string(),
+                value: handle.encode_to_vec(),
+            }
+            .encode_to_vec()
+        } else {
+            self.sql.as_bytes().to_vec()
+        };
+
+        Ticket { ticket }
+    }
+}
+
+/// Decode the `bytes` as `google.protobuf.Any` with given `type_url`.
+fn decode_any(bytes: &[u8], type_url: &str) -> Option<Any> {
+    Any::decode(bytes)
+        .ok()
+        .filter(|any| any.type_url == type_url)
+}
+
+/// Encode the `schema` as an ipc message.
+fn encode_schema(schema: &Schema) -> std::result::Result<Vec<u8>, Status> {
+    let options = IpcWriteOptions::default();
+    let message = IpcDataGenerator::default().schema_to_bytes(schema, &options);
+    let mut buf = Vec::new();
+    write_message(&mut buf, message, &options)
+        .map_err(|e| Status::internal(format!("Failed to encode schema, err:{}", e)))?;
+
+    Ok(buf)
+}
+
+type BoxedFlightStream<T> =
+    Pin<Box<dyn Stream<Item = std::result::Result<T, Status>> + Send + Sync + 'static>>;
+
+type RecordBatchStream =
+    Pin<Box<dyn Stream<Item = table_engine::stream::Result<RecordBatch>> + Send + Sync>>;
+
+/// Converts the record batches of a query into flight data, the schema is
+/// sent before the first record batch.
+struct FlightDataStream {
+    records: RecordBatchStream,
+    /// Schema to send, None if it has been sent or unknown.
+    schema: Option<ArrowSchemaRef>,
+    schema_sent: bool,
+    options: IpcWriteOptions,
+    pending: VecDeque<FlightData>,
+    /// Permit of the query, the rows scanned are charged once the stream is
+    /// exhausted.
+    permit: Option<QueryPermit>,
+    done: bool,
+}
+
+impl FlightDataStream {
+    fn new(output: Output, permit: Option<QueryPermit>) -> Self {
+        let (schema, records): (_, RecordBatchStream) = match output {
+            Output::AffectedRows(n) => {
+                let record_batch = affected_rows_record_batch(n);
+                let schema = record_batch.schema().to_arrow_schema_ref();
+                (Some(schema), Box::pin(stream::iter(vec![Ok(record_batch)])))
+            }
+            Output::Records(records) => {
+                let schema = records.first().map(|v| v.schema().to_arrow_schema_ref());
+                (schema, Box::pin(stream::iter(records.into_iter().map(Ok))))
+            }
+            Output::Stream(stream) => (
+                Some(stream.schema().to_arrow_schema_ref()),
+                Box::pin(stream),
+            ),
+        };
+
+        Self {
+            records,
+            schema,
+            schema_sent: false,
+            options: IpcWriteOptions::default(),
+            pending: VecDeque::new(),
+            permit,
+            done: false,
+        }
+    }
+
+    fn maybe_push_schema(&mut self, schema: &Schema) {
+        if !self.schema_sent {
+            self.pending
+                .push_back(SchemaAsIpc::new(schema, &self.options).into());
+            self.schema_sent = true;
+        }
+    }
+
+    fn push_record_batch(&mut self, record_batch: RecordBatch) {
+        let arrow_record_batch = record_batch.into_arrow_record_batch();
+        match self.schema.take() {
+            Some(schema) => self.maybe_push_schema(&schema),
+            None => self.maybe_push_schema(&arrow_record_batch.schema()),
+        }
+
+        let (dictionaries, data) = flight_data_from_arrow_batch(&arrow_record_batch, &self.options);
+        self.pending.extend(dictionaries);
+        self.pending.push_back(data);
+    }
+
+    fn finish(&mut self) {
+        self.done = true;
+        if let Some(schema) = self.schema.take() {
+            // Sends the schema even if there is no record batch.
+            self.maybe_push_schema(&schema);
+        }
+        // Release the query and charge the rows scanned.
+        self.permit.take();
+    }
+}
+
+impl Stream for FlightDataStream {
+    type Item = std::result::Result<FlightData, Status>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if let Some(data) = self.pending.pop_front() {
+                return Poll::Ready(Some(Ok(data)));
+            }
+            if self.done {
+                return Poll::Ready(None);
+            }
+
+            match self.records.as_mut().poll_next(cx) {
+                Poll::Ready(Some(Ok(record_batch))) => self.push_record_batch(record_batch),
+                Poll::Ready(Some(Err(e))) => {
+                    self.done = true;
+                    error!("Flight service failed to poll record batch, err:{}", e);
+                    return Poll::Ready(Some(Err(Status::internal(format!(
+                        "Failed to poll record batch, err:{}",
+                        e
+                    )))));
+                }
+                Poll::Ready(None) => self.finish(),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+/// Implementation of the Arrow Flight service
+struct FlightServiceImpl<C, Q> {
+    instance: InstanceRef<C, Q>,
+}
+
+impl<C: CatalogManager + 'static, Q: QueryExecutor + 'static> FlightServiceImpl<C, Q> {
+    /// Returns the catalog and tenant of the request.
+    fn catalog_and_tenant<T>(&self, request: &Request<T>) -> (String, String) {
+        let header = |key: &str| {
+            request
+                .metadata()
+                .get(key)
+                .and_then(|v| v.to_str().ok())
+                .map(|v| v.to_string())
+        };
+        let catalog = header(consts::CATALOG_HEADER).unwrap_or_else(|| {
+            self.instance
+                .catalog_manager
+                .default_catalog_name()
+                .to_string()
+        });
+        let tenant = header(consts::TENANT_HEADER).unwrap_or_else(|| {
+            self.instance
+                .catalog_manager
+                .default_schema_name()
+                .to_string()
+        });
+
+        (catalog, tenant)
+    }
+
+    fn sql_to_plan(
+        &self,
+        request_id: RequestId,
+        catalog: &str,
+        tenant: &str,
+        sql: &str,
+    ) -> std::result::Result<Plan, Status> {
+        // We use tenant as schema
+        let provider = CatalogMetaProvider {
+            manager: &self.instance.catalog_manager,
+            default_catalog: catalog,
+            default_schema: tenant,
+            function_registry: &*self.instance.function_registry,
+        };
+        let frontend = Frontend::new(provider);
+
+        let mut sql_ctx = SqlContext::new(request_id);
+        let mut stmts = frontend
+            .parse_sql(&mut sql_ctx, sql)
+            .map_err(|e| Status::invalid_argument(format!("Failed to parse sql, err:{}", e)))?;
+        if stmts.len() != 1 {
+            return Err(Status::invalid_argument(format!(
+                "Only support execute one statement now, current num:{}, query:{}",
+                stmts.len(),
+                sql
+            )));
+        }
+
+        frontend
+            .statement_to_plan(&mut sql_ctx, stmts.remove(0))
+            .map_err(|e| Status::internal(format!("Failed to create plan, err:{}", e)))
+    }
+
+    /// Returns the schema of the results of the `sql`, which is empty if the
+    /// statement is not a query.
+    fn schema_of_sql(
+        &self,
+        catalog: &str,
+        tenant: &str,
+        sql: &str,
+    ) -> std::result::Result<Schema, Status> {
+        let plan = self.sql_to_plan(RequestId::next_id(), catalog, tenant, sql)?;
+        let schema = match plan {
+            Plan::Query(plan) => Schema::from(plan.df_plan.schema().as_ref().clone()),
+            _ => Schema::empty(),
+        };
+
+        Ok(schema)
+    }
+
+    async fn execute_sql(
+        &self,
+        catalog: String,
+        tenant: String,
+        sql: String,
+    ) -> std::result::Result<FlightDataStream, Status> {
+        let request_id = RequestId::next_id();
+        info!(
+            "Flight service execute sql, catalog:{}, tenant:{}, request_id:{}, sql:{}",
+            catalog, tenant, request_id, sql
+        );
+
+        let plan = self.sql_to_plan(request_id, &catalog, &tenant, &sql)?;
+        let instance = &self.instance;
+        if instance.limiter.should_limit(&plan) {
+            return Err(Status::resource_exhausted("Query limited by reject list"));
+        }
+        // Only the queries are limited by the read quota of the tenant.
+        let permit = instance
+            .limiter
+            .try_begin_query(&tenant, &plan)
+            .map_err(|e| Status::resource_exhausted(e.to_string()))?;
+
+        let interpreter_ctx = InterpreterContext::builder(request_id)
+            .default_catalog_and_schema(catalog, tenant)
+            .query(sql)
+            .timeout(instance.query_timeout)
+            .stream_output(true)
+            .build();
+        let interpreter_factory = Factory::new(
+            instance.query_executor.clone(),
+            instance.catalog_manager.clone(),
+            instance.table_engine.clone(),
+            instance.query_registry.clone(),
+        );
+        let interpreter = interpreter_factory.create(interpreter_ctx, plan);
+
+        let output = interpreter.execute().await.map_err(|e| {
+            error!("Flight service failed to execute sql, err:{}", e);
+            Status::internal(format!("Failed to execute interpreter, err:{}", e))
+        })?;
+
+        Ok(FlightDataStream::new(output, permit))
+    }
+}
+
+#[async_trait]
+impl<C: CatalogManager + 'static, Q: QueryExecutor + 'static> FlightService
+    for FlightServiceImpl<C, Q>
+{
+    type DoActionStream = BoxedFlightStream<arrow_flight::Result>;
+    type DoExchangeStream = BoxedFlightStream<FlightData>;
+    type DoGetStream = BoxedFlightStream<FlightData>;
+    type DoPutStream = BoxedFlightStream<PutResult>;
+    type HandshakeStream = BoxedFlightStream<HandshakeResponse>;
+    type ListActionsStream = BoxedFlightStream<ActionType>;
+    type ListFlightsStream = BoxedFlightStream<FlightInfo>;
+
+    async fn handshake(
+        &self,
+        _request: Request<Streaming<HandshakeRequest>>,
+    ) -> std::result::Result<Response<Self::HandshakeStream>, Status> {
+        Err(Status::unimplemented("Handshake is not supported"))
+    }
+
+    async fn list_flights(
+        &self,
+        _request: Request<Criteria>,
+    ) -> std::result::Result<Response<Self::ListFlightsStream>, Status> {
+        Err(Status::unimplemented("List flights is not supported"))
+    }
+
+    async fn get_flight_info(
+        &self,
+        request: Request<FlightDescriptor>,
+    ) -> std::result::Result<Response<FlightInfo>, Status> {
+        let (catalog, tenant) = self.catalog_and_tenant(&request);
+        let descriptor = request.into_inner();
+        let statement = Statement::from_command(&descriptor.cmd)?;
+        let schema = self.schema_of_sql(&catalog, &tenant, &statement.sql)?;
+
+        let endpoint = FlightEndpoint {
+            ticket: Some(statement.to_ticket()),
+            location: vec![],
+        };
+        let info = FlightInfo {
+            schema: encode_schema(&schema)?,
+            flight_descriptor: Some(descriptor),
+            endpoint: vec![endpoint],
+            total_records: -1,
+            total_bytes: -1,
+        };
+
+        Ok(Response::new(info))
+    }
+
+    async fn get_schema(
+        &self,
+        request: Request<FlightDescriptor>,
+    ) -> std::result::Result<Response<SchemaResult>, Status> {
+        let (catalog, tenant) = self.catalog_and_tenant(&request);
+        let statement = Statement::from_command(&request.get_ref().cmd)?;
+        let schema = self.schema_of_sql(&catalog, &tenant, &statement.sql)?;
+
+        Ok(Response::new(SchemaResult {
+            schema: encode_schema(&schema)?,
+        }))
+    }
+
+    async fn do_get(
+        &self,
+        request: Request<Ticket>,
+    ) -> std::result::Result<Response<Self::DoGetStream>, Status> {
+        let (catalog, tenant) = self.catalog_and_tenant(&request);
+        let statement = Statement::from_ticket(&request.get_ref().ticket)?;
+        let stream = self.execute_sql(catalog, tenant, statement.sql).await?;
+
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    async fn do_put(
+        &self,
+        _request: Request<Streaming<FlightData>>,
+    ) -> std::result::Result<Response<Self::DoPutStream>, Status> {
+        Err(Status::unimplemented("Do put is not supported"))
+    }
+
+    async fn do_action(
+        &self,
+        _request: Request<Action>,
+    ) -> std::result::Result<Response<Self::DoActionStream>, Status> {
+        Err(Status::unimplemented("Do action is not supported"))
+    }
+
+    async fn list_actions(
+        &self,
+        _request: Request<Empty>,
+    ) -> std::result::Result<Response<Self::ListActionsStream>, Status> {
+        Ok(Response::new(Box::pin(stream::empty())))
+    }
+
+    async fn do_exchange(
+        &self,
+        _request: Request<Streaming<FlightData>>,
+    ) -> std::result::Result<Response<Self::DoExchangeStream>, Status> {
+        Err(Status::unimplemented("Do exchange is not supported"))
+    }
+}
+
+#[derive(Debug)]
+pub struct Config {
+    pub ip: String,
+    pub port: u16,
+}
+
+/// Arrow Flight service
+///
+/// Note that the service does not owns the runtime
+pub struct Service {
+    tx: Sender<()>,
+}
+
+impl Service {
+    pub fn stop(self) {
+        let _ = self.tx.send(());
+    }
+}
+
+/// Service builder
+pub struct Builder<C, Q> {
+    config: Config,
+    runtimes: Option<Arc<EngineRuntimes>>,
+    instance: Option<InstanceRef<C, Q>>,
+}
+
+impl<C, Q> Builder<C, Q> {
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            runtimes: None,
+            instance: None,
+        }
+    }
+
+    pub fn runtimes(mut self, runtimes: Arc<EngineRuntimes>) -> Self {
+        self.runtimes = Some(runtimes);
+        self
+    }
+
+    pub fn instance(mut self, instance: InstanceRef<C, Q>) -> Self {
+        self.instance = Some(instance);
+        self
+    }
+}
+
+impl<C: CatalogManager + 'static, Q: QueryExecutor + 'static> Builder<C, Q> {
+    /// Build and start the service
+    pub fn build(self) -> Result<Service> {
+        let runtimes = self.runtimes.context(MissingRuntimes)?;
+        let instance = self.instance.context(MissingInstance)?;
+        let (tx, rx) = oneshot::channel();
+
+        let ip_addr: IpAddr = self
+            .config
+            .ip
+            .parse()
+            .context(ParseIpAddr { ip: self.config.ip })?;
+        let addr = (ip_addr, self.config.port).into();
+
+        let server = tonic::transport::Server::builder()
+            .add_service(FlightServiceServer::new(FlightServiceImpl { instance }))
+            .serve_with_shutdown(addr, async {
+                rx.await.ok();
+            });
+        // Run the service
+        runtimes.bg_runtime.spawn(async move {
+            if let Err(e) = server.await {
+                error!("Flight service failed to serve, addr:{}, err:{}", addr, e);
+            }
+        });
+
+        Ok(Service { tx })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_statement() {
+        let sql = "select * from t";
+
+        // Plain flight.
+        let statement = Statement::from_command(sql.as_bytes()).unwrap();
+        assert_eq!(sql, statement.sql);
+        assert!(!statement.flight_sql);
+        let ticket = statement.to_ticket();
+        assert_eq!(sql.as_bytes(), ticket.ticket.as_slice());
+        let statement = Statement::from_ticket(&ticket.ticket).unwrap();
+        assert_eq!(sql, statement.sql);
+        assert!(!statement.flight_sql);
+
+        // Flight sql.
+        let command = Any {
+            type_url: COMMAND_STATEMENT_QUERY_TYPE_URL.to_string(),
+            value: CommandStatementQuery {
+                query: sql.to_string(),
+            }
+            .encode_to_vec(),
+        }
+        .encode_to_vec();
+        let statement = Statement::from_command(&command).unwrap();
+        assert_eq!(sql, statement.sql);
+        assert!(statement.flight_sql);
+        let ticket = statement.to_ticket();
+        let any = decode_any(&ticket.ticket, TICKET_STATEMENT_QUERY_TYPE_URL).unwrap();
+        let handle = TicketStatementQuery::decode(any.value.as_slice()).unwrap();
+        assert_eq!(sql.as_bytes(), handle.statement_handle.as_slice());
+        let statement = Statement::from_ticket(&ticket.ticket).unwrap();
+        assert_eq!(sql, statement.sql);
+        assert!(statement.flight_sql);
+
+        assert!(Statement::from_command(&[0xff, 0xfe]).is_err());
+    }
+}
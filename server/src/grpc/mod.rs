@@ -37,8 +37,8 @@ use meta_client::{
 };
 use query_engine::executor::Executor as QueryExecutor;
 use snafu::{ensure, Backtrace, OptionExt, ResultExt, Snafu};
-use sql::plan::CreateTablePlan;
-use table_engine::engine::EngineRuntimes;
+use sql::plan::{AlterTableOperation, AlterTablePlan, CreateTablePlan, Plan};
+use table_engine::{engine::EngineRuntimes, table::TableRef};
 use tokio::sync::oneshot;
 
 use crate::{
@@ -46,6 +46,7 @@ use crate::{
     error::{ErrNoCause, ErrWithCause, Result as ServerResult, ServerError, StatusCode},
     grpc::metrics::GRPC_HANDLER_DURATION_HISTOGRAM_VEC,
     instance::InstanceRef,
+    limiter::QueryPermit,
     router::{Router, RouterRef, RuleBasedRouter, RuleList},
 };
 
@@ -55,6 +56,8 @@ mod query;
 mod route;
 mod write;
 
+pub(crate) use self::write::write_metric_to_insert_plan;
+
 #[derive(Debug, Snafu)]
 pub enum Error {
     #[snafu(display(
@@ -211,6 +214,19 @@ impl<'a, C: CatalogManager, Q> HandlerContext<'a, C, Q> {
     fn tenant(&self) -> &str {
         &self.schema
     }
+
+    /// Admit the query of the tenant by its quota, returns None if the `plan`
+    /// is not a query.
+    fn begin_query(&self, plan: &Plan) -> ServerResult<Option<QueryPermit>> {
+        self.instance
+            .limiter
+            .try_begin_query(self.tenant(), plan)
+            .map_err(|e| Box::new(e) as _)
+            .context(ErrWithCause {
+                code: StatusCode::TooManyRequests,
+                msg: "Query limited by quota",
+            })
+    }
 }
 
 /// Rpc services manages all grpc services of the server.
@@ -237,6 +253,11 @@ impl RpcServices {
     pub fn shutdown(&mut self) {
         self.rpc_server.shutdown();
     }
+
+    /// Meta client shared with other services
+    pub fn meta_client(&self) -> Arc<dyn MetaClient + Send + Sync> {
+        self.meta_client.clone()
+    }
 }
 
 pub struct Builder<C, Q> {
@@ -631,7 +652,7 @@ impl<C: CatalogManager + 'static, Q: QueryExecutor + 'static> StorageService
                     error!("Failed to handle request, mod:stream_query, handler:handle_stream_query, err:{}", e);
                     e
                 })?;
-            if let Some(batch) = query::get_record_batch(&output) {
+            if let Some(batch) = output {
                 for i in 0..batch.len() {
                     let resp = query::convert_records(&batch[i..i + 1]);
                     if tx.send(resp).await.is_err() {
@@ -688,25 +709,62 @@ impl<C: CatalogManager + 'static, Q: QueryExecutor + 'static> StorageService
     }
 }
 
-/// Create CreateTablePlan from a write metric.
-// The caller must ENSURE that the HandlerContext's schema_config is not None.
-pub fn write_metric_to_create_table_plan<
-    C: CatalogManager + 'static,
-    Q: QueryExecutor + 'static,
->(
-    ctx: &HandlerContext<C, Q>,
+/// Create CreateTablePlan from a write metric, using the default engine type
+/// and timestamp column of the schema.
+pub fn write_metric_to_create_table_plan(
+    schema_config: &SchemaConfig,
     write_metric: &WriteMetric,
 ) -> Result<CreateTablePlan> {
-    let schema_config = ctx.schema_config.unwrap();
     Ok(CreateTablePlan {
         engine: schema_config.default_engine_type.clone(),
         if_not_exists: true,
         table: write_metric.get_metric().to_string(),
         table_schema: build_schema_from_metric(schema_config, write_metric)?,
+        partition_info: None,
         options: HashMap::default(),
     })
 }
 
+/// Create AlterTablePlan adding the columns in the write metric but not in the
+/// table, returns None if no column is missing.
+pub fn write_metric_to_alter_table_plan(
+    schema_config: &SchemaConfig,
+    table: TableRef,
+    write_metric: &WriteMetric,
+) -> Result<Option<AlterTablePlan>> {
+    let columns = missing_columns_of_metric(schema_config, &table.schema(), write_metric)?;
+    if columns.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(AlterTablePlan {
+        table,
+        operations: AlterTableOperation::AddColumn(columns),
+    }))
+}
+
+fn missing_columns_of_metric(
+    schema_config: &SchemaConfig,
+    table_schema: &Schema,
+    write_metric: &WriteMetric,
+) -> Result<Vec<ColumnSchema>> {
+    let metric_schema = build_schema_from_metric(schema_config, write_metric)?;
+    let timestamp_index = metric_schema.timestamp_index();
+
+    // The timestamp and tsid columns are always in the table.
+    Ok(metric_schema
+        .columns()
+        .iter()
+        .enumerate()
+        .filter(|(idx, column)| {
+            *idx != timestamp_index
+                && column.name != TSID_COLUMN
+                && table_schema.column_with_name(&column.name).is_none()
+        })
+        .map(|(_, column)| column.clone())
+        .collect())
+}
+
 fn build_column_schema(
     column_name: &str,
     data_type: DatumKind,
@@ -979,6 +1037,42 @@ mod tests {
         write_metric
     }
 
+    #[test]
+    fn test_missing_columns_of_metric() {
+        let schema_config = SchemaConfig {
+            auto_create_tables: true,
+            default_timestamp_column_name: TIMESTAMP_COLUMN.to_string(),
+            ..SchemaConfig::default()
+        };
+        let mut write_metric = generate_write_metric();
+        let table_schema = build_schema_from_metric(&schema_config, &write_metric).unwrap();
+
+        let columns =
+            missing_columns_of_metric(&schema_config, &table_schema, &write_metric).unwrap();
+        assert!(columns.is_empty());
+
+        // Add a new tag to the metric.
+        write_metric.mut_tag_names().push("zone".to_string());
+        let mut tag = Tag::new();
+        tag.set_name_index(2);
+        let mut tag_val = Value::new();
+        tag_val.set_string_value("test.zone".to_string());
+        tag.set_value(tag_val);
+        write_metric.mut_entries()[0].mut_tags().push(tag);
+
+        // Timestamp column of the table is not affected by the schema config.
+        let schema_config = SchemaConfig {
+            auto_create_tables: true,
+            ..SchemaConfig::default()
+        };
+        let columns =
+            missing_columns_of_metric(&schema_config, &table_schema, &write_metric).unwrap();
+        assert_eq!(1, columns.len());
+        assert_eq!("zone", columns[0].name);
+        assert!(columns[0].is_tag);
+        assert!(columns[0].is_nullable);
+    }
+
     #[test]
     fn test_build_schema_from_metric() {
         let schema_config = SchemaConfig {
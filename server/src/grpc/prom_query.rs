@@ -16,7 +16,7 @@ use common_types::{
     request_id::RequestId,
     schema::{RecordSchema, TSID_COLUMN},
 };
-use interpreters::{context::Context as InterpreterContext, factory::Factory, interpreter::Output};
+use interpreters::{context::Context as InterpreterContext, factory::Factory};
 use log::debug;
 use query_engine::executor::{Executor as QueryExecutor, RecordBatchVec};
 use snafu::{ensure, OptionExt, ResultExt};
@@ -28,7 +28,7 @@ use sql::{
 
 use crate::{
     error::{ErrNoCause, ErrWithCause, Result, ServerError, StatusCode},
-    grpc::HandlerContext,
+    grpc::{query, HandlerContext},
 };
 
 fn is_table_not_found_error(e: &FrontendError) -> bool {
@@ -56,6 +56,7 @@ where
     );
 
     let instance = &ctx.instance;
+    let query = format!("{:?}", req.get_expr());
     // We use tenant as schema
     // TODO(yingwen): Privilege check, cannot access data of other tenant
     // TODO(yingwen): Maybe move MetaProvider to instance
@@ -98,16 +99,20 @@ where
         }
         .fail()?;
     }
+    let _permit = ctx.begin_query(&plan)?;
 
     // Execute in interpreter
     let interpreter_ctx = InterpreterContext::builder(request_id)
         // Use current ctx's catalog and tenant as default catalog and tenant
         .default_catalog_and_schema(ctx.catalog().to_string(), ctx.tenant().to_string())
+        .query(query)
+        .timeout(instance.query_timeout)
         .build();
     let interpreter_factory = Factory::new(
         instance.query_executor.clone(),
         instance.catalog_manager.clone(),
         instance.table_engine.clone(),
+        instance.query_registry.clone(),
     );
     let interpreter = interpreter_factory.create(interpreter_ctx, plan);
 
@@ -120,7 +125,8 @@ where
             msg: "Failed to execute interpreter",
         })?;
 
-    let resp = convert_output(output, column_name)
+    let records = query::collect_records(output).await?.unwrap_or_default();
+    let resp = convert_records(records, column_name)
         .map_err(|e| Box::new(e) as _)
         .with_context(|| ErrWithCause {
             code: StatusCode::InternalError,
@@ -130,16 +136,6 @@ where
     Ok(resp)
 }
 
-fn convert_output(
-    output: Output,
-    column_name: Arc<ColumnNames>,
-) -> Result<PrometheusQueryResponse> {
-    match output {
-        Output::Records(records) => convert_records(records, column_name),
-        _ => unreachable!(),
-    }
-}
-
 fn convert_records(
     records: RecordBatchVec,
     column_name: Arc<ColumnNames>,
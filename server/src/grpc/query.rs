@@ -11,6 +11,7 @@ use ceresdbproto::{
 };
 use common_types::{record_batch::RecordBatch, request_id::RequestId};
 use common_util::time::InstantExt;
+use futures::TryStreamExt;
 use interpreters::{context::Context as InterpreterContext, factory::Factory, interpreter::Output};
 use log::info;
 use query_engine::executor::{Executor as QueryExecutor, RecordBatchVec};
@@ -44,8 +45,8 @@ pub async fn handle_query<C: CatalogManager + 'static, Q: QueryExecutor + 'stati
     req: QueryRequest,
 ) -> Result<QueryResponse> {
     let output_result = fetch_query_output(ctx, &req).await?;
-    if let Some(output) = output_result {
-        convert_output(&output)
+    if let Some(records) = output_result {
+        convert_records(&records)
             .map_err(|e| Box::new(e) as _)
             .with_context(|| ErrWithCause {
                 code: StatusCode::InternalError,
@@ -56,10 +57,32 @@ pub async fn handle_query<C: CatalogManager + 'static, Q: QueryExecutor + 'stati
     }
 }
 
+/// Collect the records of the output, returns None if the output is not
+/// records, e.g. the affected rows of an insert.
+pub(crate) async fn collect_records(output: Output) -> Result<Option<RecordBatchVec>> {
+    match output {
+        Output::AffectedRows(_) => Ok(None),
+        Output::Records(records) => Ok(Some(records)),
+        Output::Stream(stream) => {
+            let records = stream
+                .try_collect()
+                .await
+                .map_err(|e| Box::new(e) as _)
+                .context(ErrWithCause {
+                    code: StatusCode::InternalError,
+                    msg: "Failed to poll record batch stream",
+                })?;
+            Ok(Some(records))
+        }
+    }
+}
+
+/// Execute the query and fetch the records, returns None if there is no
+/// statement or the statement is not a query.
 pub async fn fetch_query_output<C: CatalogManager + 'static, Q: QueryExecutor + 'static>(
     ctx: &HandlerContext<'_, C, Q>,
     req: &QueryRequest,
-) -> Result<Option<Output>> {
+) -> Result<Option<RecordBatchVec>> {
     let request_id = RequestId::next_id();
     let begin_instant = Instant::now();
 
@@ -131,16 +154,22 @@ pub async fn fetch_query_output<C: CatalogManager + 'static, Q: QueryExecutor +
         }
         .fail()?;
     }
+    // Only the queries are limited by the read quota of the tenant, the rows
+    // scanned are charged when the permit is dropped.
+    let _permit = ctx.begin_query(&plan)?;
 
     // Execute in interpreter
     let interpreter_ctx = InterpreterContext::builder(request_id)
         // Use current ctx's catalog and tenant as default catalog and tenant
         .default_catalog_and_schema(ctx.catalog().to_string(), ctx.tenant().to_string())
+        .query(req.ql.clone())
+        .timeout(instance.query_timeout)
         .build();
     let interpreter_factory = Factory::new(
         instance.query_executor.clone(),
         instance.catalog_manager.clone(),
         instance.table_engine.clone(),
+        instance.query_registry.clone(),
     );
     let interpreter = interpreter_factory.create(interpreter_ctx, plan);
 
@@ -152,6 +181,9 @@ pub async fn fetch_query_output<C: CatalogManager + 'static, Q: QueryExecutor +
             code: StatusCode::InternalError,
             msg: format!("Failed to execute interpreter, query:{}", req.ql),
         })?;
+    // The records are collected before the permit is released, so all the
+    // rows scanned are charged.
+    let records = collect_records(output).await?;
 
     info!(
         "Grpc handle query success, catalog:{}, tenant:{}, request_id:{}, cost:{}, request:{:?}",
@@ -162,25 +194,7 @@ pub async fn fetch_query_output<C: CatalogManager + 'static, Q: QueryExecutor +
         req,
     );
 
-    Ok(Some(output))
-}
-
-fn convert_output(output: &Output) -> Result<QueryResponse> {
-    match output {
-        Output::Records(records) => convert_records(records),
-        _ => unreachable!(),
-    }
-}
-
-pub fn get_record_batch(op: &Option<Output>) -> Option<&RecordBatchVec> {
-    if let Some(output) = op {
-        match output {
-            Output::Records(records) => Some(records),
-            _ => unreachable!(),
-        }
-    } else {
-        None
-    }
+    Ok(records)
 }
 
 /// REQUIRE: records have same schema
@@ -18,6 +18,8 @@ use common_types::{
 };
 use interpreters::{context::Context as InterpreterContext, factory::Factory, interpreter::Output};
 use log::debug;
+use meta_client::SchemaConfig;
+use protobuf::Message;
 use query_engine::executor::Executor as QueryExecutor;
 use snafu::{ensure, OptionExt, ResultExt};
 use sql::plan::{InsertPlan, Plan};
@@ -45,9 +47,20 @@ pub(crate) async fn handle_write<C: CatalogManager + 'static, Q: QueryExecutor +
         req.get_metrics().len(),
     );
 
+    let num_bytes = req.compute_size() as usize;
     let instance = &ctx.instance;
     let plan_vec = write_request_to_insert_plan(ctx, req, request_id).await?;
 
+    let num_rows = plan_vec.iter().map(|plan| plan.rows.num_rows()).sum();
+    instance
+        .limiter
+        .try_acquire_write(ctx.tenant(), num_rows, num_bytes)
+        .map_err(|e| Box::new(e) as _)
+        .context(ErrWithCause {
+            code: StatusCode::TooManyRequests,
+            msg: "Write limited by quota",
+        })?;
+
     let mut success = 0;
     for insert_plan in plan_vec {
         debug!(
@@ -73,6 +86,7 @@ pub(crate) async fn handle_write<C: CatalogManager + 'static, Q: QueryExecutor +
             instance.query_executor.clone(),
             instance.catalog_manager.clone(),
             instance.table_engine.clone(),
+            instance.query_registry.clone(),
         );
         let interpreter = interpreter_factory.create(interpreter_ctx, plan);
 
@@ -119,7 +133,7 @@ async fn write_request_to_insert_plan<C: CatalogManager + 'static, Q: QueryExecu
         if table.is_none() {
             if let Some(config) = ctx.schema_config {
                 if config.auto_create_tables {
-                    create_table(ctx, &write_metric, request_id).await?;
+                    create_table(ctx, config, &write_metric, request_id).await?;
                     // try to get table again
                     table = try_get_table(ctx, table_name)?;
                 }
@@ -180,10 +194,11 @@ fn try_get_table<C: CatalogManager + 'static, Q: QueryExecutor + 'static>(
 
 async fn create_table<C: CatalogManager + 'static, Q: QueryExecutor + 'static>(
     ctx: &HandlerContext<'_, C, Q>,
+    schema_config: &SchemaConfig,
     write_metric: &WriteMetric,
     request_id: RequestId,
 ) -> Result<()> {
-    let create_table_plan = grpc::write_metric_to_create_table_plan(ctx, write_metric)
+    let create_table_plan = grpc::write_metric_to_create_table_plan(schema_config, write_metric)
         .map_err(|e| Box::new(e) as _)
         .with_context(|| ErrWithCause {
             code: StatusCode::InternalError,
@@ -217,6 +232,7 @@ async fn create_table<C: CatalogManager + 'static, Q: QueryExecutor + 'static>(
         instance.query_executor.clone(),
         instance.catalog_manager.clone(),
         instance.table_engine.clone(),
+        instance.query_registry.clone(),
     );
     let interpreter = interpreter_factory.create(interpreter_ctx, plan);
 
@@ -235,7 +251,8 @@ async fn create_table<C: CatalogManager + 'static, Q: QueryExecutor + 'static>(
     Ok(())
 }
 
-fn write_metric_to_insert_plan(
+/// Build the insert plan of a write metric to the `table`.
+pub(crate) fn write_metric_to_insert_plan(
     table: TableRef,
     mut write_metric: WriteMetric,
 ) -> Result<InsertPlan> {
@@ -1,8 +1,18 @@
 // Copyright 2022 CeresDB Project Authors. Licensed under Apache-2.0.
 
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 
-use crate::handlers::prelude::*;
+use snafu::OptionExt;
+use table_engine::table::{RestoreRequest, SnapshotRequest, SnapshotStats, TableRef};
+
+use crate::{
+    handlers::{
+        error::{FindTable, MissingBackupStore, RestoreTable, SnapshotTable, TableNotFound},
+        prelude::*,
+        sql::find_schema,
+    },
+    limiter::QuotaConfig,
+};
 
 #[derive(Debug, Deserialize)]
 pub enum Operation {
@@ -69,3 +79,122 @@ pub async fn handle_reject<C: CatalogManager + 'static, Q: QueryExecutor + 'stat
             .collect::<BTreeSet<_>>(),
     })
 }
+
+/// Request to adjust the quotas of the tenants, the default quota is adjusted
+/// if the `tenant` is not given.
+#[derive(Debug, Deserialize)]
+pub struct QuotaRequest {
+    operation: Operation,
+    tenant: Option<String>,
+    #[serde(default)]
+    quota: QuotaConfig,
+}
+
+#[derive(Serialize)]
+pub struct QuotaResponse {
+    default_quota: QuotaConfig,
+    tenant_quotas: BTreeMap<String, QuotaConfig>,
+}
+
+pub async fn handle_quota<C: CatalogManager + 'static, Q: QueryExecutor + 'static>(
+    _ctx: RequestContext,
+    instance: InstanceRef<C, Q>,
+    request: QuotaRequest,
+) -> Result<QuotaResponse> {
+    let tenant = request.tenant.as_deref();
+    match request.operation {
+        Operation::Add | Operation::Set => instance.limiter.set_quota(tenant, request.quota),
+        Operation::Remove => instance.limiter.remove_quota(tenant),
+    }
+
+    let config = instance.limiter.get_quota_config();
+    Ok(QuotaResponse {
+        default_quota: config.default_quota,
+        tenant_quotas: config.tenant_quotas.into_iter().collect(),
+    })
+}
+
+/// Request to snapshot or restore a table, the `prefix` is the path of the
+/// snapshot in the backup store.
+#[derive(Debug, Deserialize)]
+pub struct BackupRequest {
+    table: String,
+    prefix: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BackupResponse {
+    table: String,
+    prefix: String,
+    num_files: usize,
+    num_bytes: u64,
+    num_rows: u64,
+}
+
+impl BackupResponse {
+    fn new(request: BackupRequest, stats: SnapshotStats) -> Self {
+        Self {
+            table: request.table,
+            prefix: request.prefix,
+            num_files: stats.num_files,
+            num_bytes: stats.num_bytes,
+            num_rows: stats.num_rows,
+        }
+    }
+}
+
+fn find_table<C: CatalogManager + 'static, Q: QueryExecutor + 'static>(
+    ctx: &RequestContext,
+    instance: &InstanceRef<C, Q>,
+    table: &str,
+) -> Result<TableRef> {
+    let schema = find_schema(ctx, instance)?;
+    schema
+        .table_by_name(table)
+        .context(FindTable { table })?
+        .context(TableNotFound { table })
+}
+
+/// Flush the table and copy its sst files to the backup store.
+pub async fn handle_snapshot<C: CatalogManager + 'static, Q: QueryExecutor + 'static>(
+    ctx: RequestContext,
+    instance: InstanceRef<C, Q>,
+    request: BackupRequest,
+) -> Result<BackupResponse> {
+    let store = instance.backup_store.clone().context(MissingBackupStore)?;
+    let table = find_table(&ctx, &instance, &request.table)?;
+
+    let stats = table
+        .snapshot(SnapshotRequest {
+            store,
+            prefix: request.prefix.clone(),
+        })
+        .await
+        .context(SnapshotTable {
+            table: &request.table,
+        })?;
+
+    Ok(BackupResponse::new(request, stats))
+}
+
+/// Restore the snapshot in the backup store into an empty table.
+pub async fn handle_restore<C: CatalogManager + 'static, Q: QueryExecutor + 'static>(
+    ctx: RequestContext,
+    instance: InstanceRef<C, Q>,
+    request: BackupRequest,
+) -> Result<BackupResponse> {
+    let store = instance.backup_store.clone().context(MissingBackupStore)?;
+    let table = find_table(&ctx, &instance, &request.table)?;
+
+    let stats = table
+        .restore(RestoreRequest {
+            store,
+            prefix: request.prefix.clone(),
+        })
+        .await
+        .context(RestoreTable {
+            table: &request.table,
+        })?;
+
+    Ok(BackupResponse::new(request, stats))
+}
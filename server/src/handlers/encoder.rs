@@ -0,0 +1,238 @@
+// Copyright 2022 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Encoders of the record batches streamed to the client
+
+use std::collections::HashMap;
+
+use arrow_deps::arrow::{
+    csv::WriterBuilder,
+    datatypes::Schema,
+    error::ArrowError,
+    ipc::writer::{write_message, DictionaryTracker, IpcDataGenerator, IpcWriteOptions},
+    record_batch::RecordBatch as ArrowRecordBatch,
+};
+use common_types::record_batch::RecordBatch;
+use snafu::{ResultExt, Snafu};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Failed to encode json, err:{}", source))]
+    EncodeJson { source: serde_json::Error },
+
+    #[snafu(display("Failed to encode csv, err:{}", source))]
+    EncodeCsv { source: ArrowError },
+
+    #[snafu(display("Failed to encode arrow ipc, err:{}", source))]
+    EncodeArrow { source: ArrowError },
+}
+
+define_result!(Error);
+
+/// End of the arrow ipc stream, which is a continuation marker followed by a
+/// zero length.
+const ARROW_STREAM_EOS: [u8; 8] = [0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0];
+
+/// Encodes the record batches into chunks of bytes, so the records can be
+/// sent to the client once a batch is available.
+pub enum RecordEncoder {
+    /// Newline delimited json, one object per row.
+    Ndjson,
+    /// Csv with a header line.
+    Csv { has_headers: bool },
+    /// Arrow ipc streaming format.
+    Arrow(ArrowEncoder),
+}
+
+impl RecordEncoder {
+    pub fn ndjson() -> Self {
+        RecordEncoder::Ndjson
+    }
+
+    pub fn csv() -> Self {
+        RecordEncoder::Csv { has_headers: true }
+    }
+
+    /// Create an arrow ipc encoder, all the record batches should have the
+    /// same `schema`.
+    pub fn arrow(schema: Schema) -> Self {
+        RecordEncoder::Arrow(ArrowEncoder::new(schema))
+    }
+
+    /// Encode the `record_batch`, returns the encoded bytes.
+    pub fn encode(&mut self, record_batch: RecordBatch) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        match self {
+            RecordEncoder::Ndjson => {
+                let schema = record_batch.schema();
+                for row_idx in 0..record_batch.num_rows() {
+                    let mut row = HashMap::with_capacity(record_batch.num_columns());
+                    for col_idx in 0..record_batch.num_columns() {
+                        let column_name = schema.column(col_idx).name.as_str();
+                        row.insert(column_name, record_batch.column(col_idx).datum(row_idx));
+                    }
+
+                    serde_json::to_writer(&mut buf, &row).context(EncodeJson)?;
+                    buf.push(b'\n');
+                }
+            }
+            RecordEncoder::Csv { has_headers } => {
+                let arrow_record_batch = record_batch.into_arrow_record_batch();
+                // The csv writer flushes the buffered data once it is dropped.
+                let mut writer = WriterBuilder::new()
+                    .has_headers(*has_headers)
+                    .build(&mut buf);
+                writer.write(&arrow_record_batch).context(EncodeCsv)?;
+                *has_headers = false;
+            }
+            RecordEncoder::Arrow(encoder) => {
+                encoder.encode(&record_batch.into_arrow_record_batch(), &mut buf)?
+            }
+        }
+
+        Ok(buf)
+    }
+
+    /// Finish the encoding, returns the remaining bytes.
+    pub fn finish(&mut self) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        if let RecordEncoder::Arrow(encoder) = self {
+            encoder.finish(&mut buf)?;
+        }
+
+        Ok(buf)
+    }
+}
+
+/// Encoder of the arrow ipc streaming format.
+pub struct ArrowEncoder {
+    schema: Schema,
+    options: IpcWriteOptions,
+    data_gen: IpcDataGenerator,
+    dictionary_tracker: DictionaryTracker,
+    schema_written: bool,
+}
+
+impl ArrowEncoder {
+    fn new(schema: Schema) -> Self {
+        Self {
+            schema,
+            options: IpcWriteOptions::default(),
+            data_gen: IpcDataGenerator::default(),
+            dictionary_tracker: DictionaryTracker::new(false),
+            schema_written: false,
+        }
+    }
+
+    fn encode(&mut self, record_batch: &ArrowRecordBatch, buf: &mut Vec<u8>) -> Result<()> {
+        self.maybe_write_schema(buf)?;
+
+        let (dictionaries, message) = self
+            .data_gen
+            .encoded_batch(record_batch, &mut self.dictionary_tracker, &self.options)
+            .context(EncodeArrow)?;
+        for dictionary in dictionaries {
+            write_message(&mut *buf, dictionary, &self.options).context(EncodeArrow)?;
+        }
+        write_message(buf, message, &self.options).context(EncodeArrow)?;
+
+        Ok(())
+    }
+
+    fn finish(&mut self, buf: &mut Vec<u8>) -> Result<()> {
+        // The schema is still required if there is no record batch.
+        self.maybe_write_schema(buf)?;
+        buf.extend_from_slice(&ARROW_STREAM_EOS);
+
+        Ok(())
+    }
+
+    fn maybe_write_schema(&mut self, buf: &mut Vec<u8>) -> Result<()> {
+        if !self.schema_written {
+            let message = self.data_gen.schema_to_bytes(&self.schema, &self.options);
+            write_message(buf, message, &self.options).context(EncodeArrow)?;
+            self.schema_written = true;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{convert::TryInto, sync::Arc};
+
+    use arrow_deps::arrow::{
+        array::{StringArray, UInt64Array},
+        datatypes::{DataType, Field},
+        ipc::reader::StreamReader,
+    };
+
+    use super::*;
+
+    fn build_record_batch() -> (Schema, RecordBatch) {
+        let schema = Schema::new(vec![
+            Field::new("name", DataType::Utf8, false),
+            Field::new("value", DataType::UInt64, false),
+        ]);
+        let arrow_record_batch = ArrowRecordBatch::try_new(
+            Arc::new(schema.clone()),
+            vec![
+                Arc::new(StringArray::from(vec!["a", "b"])),
+                Arc::new(UInt64Array::from(vec![1, 2])),
+            ],
+        )
+        .unwrap();
+
+        (schema, arrow_record_batch.try_into().unwrap())
+    }
+
+    #[test]
+    fn test_encode_ndjson() {
+        let mut encoder = RecordEncoder::ndjson();
+        let buf = encoder.encode(build_record_batch().1).unwrap();
+        let lines: Vec<serde_json::Value> = String::from_utf8(buf)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(2, lines.len());
+        assert_eq!(serde_json::json!({"name": "a", "value": 1}), lines[0]);
+        assert_eq!(serde_json::json!({"name": "b", "value": 2}), lines[1]);
+        assert!(encoder.finish().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_encode_csv() {
+        let mut encoder = RecordEncoder::csv();
+        let mut buf = encoder.encode(build_record_batch().1).unwrap();
+        buf.extend(encoder.encode(build_record_batch().1).unwrap());
+        buf.extend(encoder.finish().unwrap());
+
+        assert_eq!(
+            "name,value\na,1\nb,2\na,1\nb,2\n",
+            String::from_utf8(buf).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_encode_arrow() {
+        let (schema, record_batch) = build_record_batch();
+        let mut encoder = RecordEncoder::arrow(schema.clone());
+        let mut buf = encoder.encode(record_batch).unwrap();
+        buf.extend(encoder.encode(build_record_batch().1).unwrap());
+        buf.extend(encoder.finish().unwrap());
+
+        let reader = StreamReader::try_new(std::io::Cursor::new(buf)).unwrap();
+        assert_eq!(schema, *reader.schema());
+        let batches: Vec<_> = reader.map(|batch| batch.unwrap()).collect();
+        assert_eq!(2, batches.len());
+        assert_eq!(2, batches[0].num_rows());
+
+        // Only the schema is written if there is no record batch.
+        let mut encoder = RecordEncoder::arrow(schema.clone());
+        let buf = encoder.finish().unwrap();
+        let mut reader = StreamReader::try_new(std::io::Cursor::new(buf)).unwrap();
+        assert_eq!(schema, *reader.schema());
+        assert!(reader.next().is_none());
+    }
+}
@@ -47,6 +47,182 @@ pub enum Error {
         source: arrow_deps::arrow::error::ArrowError,
         backtrace: Backtrace,
     },
+
+    #[snafu(display(
+        "Invalid prometheus api param, msg:{}.\nBacktrace:\n{}",
+        msg,
+        backtrace
+    ))]
+    InvalidPromParam { msg: String, backtrace: Backtrace },
+
+    #[snafu(display("Failed to parse promql, err:{}", source))]
+    ParsePromQL { source: sql::frontend::Error },
+
+    #[snafu(display(
+        "Query is limited by reject list, query:{}.\nBacktrace:\n{}",
+        query,
+        backtrace
+    ))]
+    QueryLimited { query: String, backtrace: Backtrace },
+
+    #[snafu(display("Request is limited by the quota of the tenant, err:{}", source))]
+    QuotaExceeded { source: crate::limiter::Error },
+
+    #[snafu(display(
+        "Failed to convert prometheus query result, query:{}, msg:{}.\nBacktrace:\n{}",
+        query,
+        msg,
+        backtrace
+    ))]
+    ConvertPromResult {
+        query: String,
+        msg: String,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "Failed to find schema, catalog:{}, schema:{}, err:{}",
+        catalog,
+        schema,
+        source
+    ))]
+    FindSchema {
+        catalog: String,
+        schema: String,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+
+    #[snafu(display(
+        "Schema not found, catalog:{}, schema:{}.\nBacktrace:\n{}",
+        catalog,
+        schema,
+        backtrace
+    ))]
+    SchemaNotFound {
+        catalog: String,
+        schema: String,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("Failed to list tables, schema:{}, err:{}", schema, source))]
+    ListTables {
+        schema: String,
+        source: catalog::schema::Error,
+    },
+
+    #[snafu(display(
+        "Failed to decompress prometheus remote request, err:{}.\nBacktrace:\n{}",
+        source,
+        backtrace
+    ))]
+    DecompressRemoteRequest {
+        source: snap::Error,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "Failed to decode prometheus remote request, err:{}.\nBacktrace:\n{}",
+        source,
+        backtrace
+    ))]
+    DecodeRemoteRequest {
+        source: protobuf::ProtobufError,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "Invalid prometheus remote request, msg:{}.\nBacktrace:\n{}",
+        msg,
+        backtrace
+    ))]
+    InvalidRemoteRequest { msg: String, backtrace: Backtrace },
+
+    #[snafu(display(
+        "Failed to encode prometheus remote response, err:{}.\nBacktrace:\n{}",
+        source,
+        backtrace
+    ))]
+    EncodeRemoteResponse {
+        source: protobuf::ProtobufError,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "Failed to compress prometheus remote response, err:{}.\nBacktrace:\n{}",
+        source,
+        backtrace
+    ))]
+    CompressRemoteResponse {
+        source: snap::Error,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "Request body is not valid utf8, err:{}.\nBacktrace:\n{}",
+        source,
+        backtrace
+    ))]
+    InvalidUtf8Body {
+        source: std::str::Utf8Error,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("Invalid line protocol, err:{}", source))]
+    InvalidLineProtocol { source: crate::line_protocol::Error },
+
+    #[snafu(display("Failed to find table, table:{}, err:{}", table, source))]
+    FindTable {
+        table: String,
+        source: catalog::schema::Error,
+    },
+
+    #[snafu(display("Table not found, table:{}.\nBacktrace:\n{}", table, backtrace))]
+    TableNotFound { table: String, backtrace: Backtrace },
+
+    #[snafu(display("Failed to build create table plan, table:{}, err:{}", table, source))]
+    BuildCreateTablePlan {
+        table: String,
+        source: crate::grpc::Error,
+    },
+
+    #[snafu(display("Failed to build alter table plan, table:{}, err:{}", table, source))]
+    BuildAlterTablePlan {
+        table: String,
+        source: crate::grpc::Error,
+    },
+
+    #[snafu(display("Failed to build insert plan, table:{}, err:{}", table, source))]
+    BuildInsertPlan {
+        table: String,
+        source: crate::error::ServerError,
+    },
+
+    #[snafu(display("Backup storage is not configured.\nBacktrace:\n{}", backtrace))]
+    MissingBackupStore { backtrace: Backtrace },
+
+    #[snafu(display("Failed to snapshot table, table:{}, err:{}", table, source))]
+    SnapshotTable {
+        table: String,
+        source: table_engine::table::Error,
+    },
+
+    #[snafu(display("Failed to restore table, table:{}, err:{}", table, source))]
+    RestoreTable {
+        table: String,
+        source: table_engine::table::Error,
+    },
+
+    #[snafu(display("Failed to poll record batch stream, query:{}, err:{}", query, source))]
+    PollStream {
+        query: String,
+        source: table_engine::stream::Error,
+    },
+
+    #[snafu(display("Failed to encode records, query:{}, err:{}", query, source))]
+    EncodeRecords {
+        query: String,
+        source: crate::handlers::encoder::Error,
+    },
 }
 
 define_result!(Error);
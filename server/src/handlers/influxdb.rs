@@ -0,0 +1,226 @@
+// Copyright 2022 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Handler of the InfluxDB line protocol write apis, both `/influxdb/v1/write`
+//! and `/api/v2/write` are served.
+
+use std::{collections::BTreeMap, sync::Arc};
+
+use ceresdbproto::storage::{Field, FieldGroup, Tag, Value, WriteEntry, WriteMetric};
+use common_types::{request_id::RequestId, time::Timestamp};
+use log::info;
+use meta_client::MetaClient;
+use warp::http::StatusCode;
+
+use crate::{
+    handlers::{
+        error::{InvalidLineProtocol, InvalidUtf8Body},
+        prelude::*,
+        write,
+    },
+    line_protocol::{self, FieldValue, Line, Precision},
+};
+
+/// Params of the write request, other params such as `db` and `bucket` are
+/// ignored, the tenant of the request is used instead.
+#[derive(Debug, Default, Deserialize)]
+pub struct WriteParams {
+    #[serde(default)]
+    pub precision: Option<String>,
+}
+
+/// Response of the failed request
+#[derive(Debug, Serialize)]
+pub struct ErrorResponse {
+    error: String,
+}
+
+/// Convert the error into status code and response in the format of InfluxDB.
+pub fn convert_error(err: &Error) -> (StatusCode, ErrorResponse) {
+    let code = match err {
+        Error::InvalidUtf8Body { .. }
+        | Error::InvalidLineProtocol { .. }
+        | Error::TableNotFound { .. }
+        | Error::BuildCreateTablePlan { .. }
+        | Error::BuildAlterTablePlan { .. }
+        | Error::BuildInsertPlan { .. } => StatusCode::BAD_REQUEST,
+        Error::QueryLimited { .. } => StatusCode::SERVICE_UNAVAILABLE,
+        Error::QuotaExceeded { .. } => StatusCode::TOO_MANY_REQUESTS,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    let err_string = err.to_string();
+    let resp = ErrorResponse {
+        error: crate::error::first_line_in_error(&err_string).to_string(),
+    };
+
+    (code, resp)
+}
+
+/// Handle the write request in line protocol
+///
+/// Lines are written to the table named by the measurement, tags are written
+/// to tag columns and fields are written to field columns. The table is created
+/// or altered if the tenant allows auto creating tables.
+pub async fn handle_write<C: CatalogManager + 'static, Q: QueryExecutor + 'static>(
+    ctx: RequestContext,
+    instance: InstanceRef<C, Q>,
+    meta_client: Arc<dyn MetaClient + Send + Sync>,
+    params: WriteParams,
+    body: &[u8],
+) -> Result<()> {
+    let request_id = RequestId::next_id();
+    let precision = match &params.precision {
+        Some(precision) => Precision::parse(precision).context(InvalidLineProtocol)?,
+        None => Precision::default(),
+    };
+    let body = std::str::from_utf8(body).context(InvalidUtf8Body)?;
+    let lines = line_protocol::parse_lines(body).context(InvalidLineProtocol)?;
+
+    info!(
+        "influxdb handler try to write lines, request_id:{}, lines:{}, precision:{:?}",
+        request_id,
+        lines.len(),
+        precision
+    );
+
+    let write_metrics = convert_lines(lines, precision, Timestamp::now().as_i64());
+    write::write_metrics(&ctx, &instance, &*meta_client, request_id, write_metrics).await
+}
+
+#[derive(Default)]
+struct MetricBuilder {
+    tag_names: Vec<String>,
+    field_names: Vec<String>,
+    entries: Vec<WriteEntry>,
+}
+
+/// Returns the index of the name, the name is appended if not exists.
+fn name_index(names: &mut Vec<String>, name: String) -> u32 {
+    let idx = match names.iter().position(|v| *v == name) {
+        Some(idx) => idx,
+        None => {
+            names.push(name);
+            names.len() - 1
+        }
+    };
+
+    idx as u32
+}
+
+/// Group the lines into write metrics by the measurement, lines without
+/// timestamp are written at `default_timestamp` in milliseconds.
+fn convert_lines(
+    lines: Vec<Line>,
+    precision: Precision,
+    default_timestamp: i64,
+) -> Vec<WriteMetric> {
+    let mut metrics: BTreeMap<String, MetricBuilder> = BTreeMap::new();
+    for line in lines {
+        let builder = metrics.entry(line.measurement).or_default();
+
+        let tags = line
+            .tags
+            .into_iter()
+            .map(|(name, value)| {
+                let mut tag_value = Value::new();
+                tag_value.set_string_value(value);
+
+                let mut tag = Tag::new();
+                tag.set_name_index(name_index(&mut builder.tag_names, name));
+                tag.set_value(tag_value);
+                tag
+            })
+            .collect::<Vec<_>>();
+        let fields = line
+            .fields
+            .into_iter()
+            .map(|(name, value)| {
+                let mut field_value = Value::new();
+                match value {
+                    FieldValue::Float(v) => field_value.set_float64_value(v),
+                    FieldValue::Integer(v) => field_value.set_int64_value(v),
+                    FieldValue::UInteger(v) => field_value.set_uint64_value(v),
+                    FieldValue::String(v) => field_value.set_string_value(v),
+                    FieldValue::Boolean(v) => field_value.set_bool_value(v),
+                }
+
+                let mut field = Field::new();
+                field.set_name_index(name_index(&mut builder.field_names, name));
+                field.set_value(field_value);
+                field
+            })
+            .collect::<Vec<_>>();
+
+        let timestamp = line
+            .timestamp
+            .map(|v| precision.to_millis(v))
+            .unwrap_or(default_timestamp);
+        let mut field_group = FieldGroup::new();
+        field_group.set_timestamp(timestamp);
+        field_group.set_fields(fields.into());
+
+        let mut entry = WriteEntry::new();
+        entry.set_tags(tags.into());
+        entry.set_field_groups(vec![field_group].into());
+        builder.entries.push(entry);
+    }
+
+    metrics
+        .into_iter()
+        .map(|(measurement, builder)| {
+            let mut write_metric = WriteMetric::new();
+            write_metric.set_metric(measurement);
+            write_metric.set_tag_names(builder.tag_names.into());
+            write_metric.set_field_names(builder.field_names.into());
+            write_metric.set_entries(builder.entries.into());
+            write_metric
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_convert_lines() {
+        let input = "cpu,host=a usage=0.5,count=2i 1000\n\
+                     cpu,region=us,host=b usage=0.6,ok=true 2000\n\
+                     mem free=10u";
+        let lines = line_protocol::parse_lines(input).unwrap();
+        let write_metrics = convert_lines(lines, Precision::Second, 42);
+        assert_eq!(2, write_metrics.len());
+
+        let cpu = &write_metrics[0];
+        assert_eq!("cpu", cpu.get_metric());
+        assert_eq!(
+            &["host".to_string(), "region".to_string()],
+            cpu.get_tag_names()
+        );
+        assert_eq!(
+            &["usage".to_string(), "count".to_string(), "ok".to_string()],
+            cpu.get_field_names()
+        );
+        let entries = cpu.get_entries();
+        assert_eq!(2, entries.len());
+        let tags = entries[1].get_tags();
+        assert_eq!(1, tags[0].get_name_index());
+        assert_eq!("us", tags[0].get_value().get_string_value());
+        assert_eq!(0, tags[1].get_name_index());
+        let field_group = &entries[1].get_field_groups()[0];
+        assert_eq!(2_000_000, field_group.get_timestamp());
+        let fields = field_group.get_fields();
+        assert_eq!(2, fields[1].get_name_index());
+        assert!(fields[1].get_value().get_bool_value());
+
+        let mem = &write_metrics[1];
+        assert_eq!("mem", mem.get_metric());
+        assert!(mem.get_tag_names().is_empty());
+        let field_group = &mem.get_entries()[0].get_field_groups()[0];
+        // Timestamp is absent.
+        assert_eq!(42, field_group.get_timestamp());
+        assert_eq!(
+            10,
+            field_group.get_fields()[0].get_value().get_uint64_value()
+        );
+    }
+}
@@ -3,8 +3,13 @@
 //! Request handlers
 
 pub mod admin;
+pub(crate) mod encoder;
 pub mod error;
+pub mod influxdb;
+pub mod prom;
+pub mod prom_remote;
 pub mod sql;
+mod write;
 
 mod prelude {
     pub use catalog::manager::Manager as CatalogManager;
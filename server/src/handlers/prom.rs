@@ -0,0 +1,687 @@
+// Copyright 2022 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Prometheus http api handlers
+//!
+//! Refer to: https://prometheus.io/docs/prometheus/latest/querying/api/
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use common_types::{datum::Datum, request_id::RequestId, schema::TSID_COLUMN, time::Timestamp};
+use log::info;
+use query_engine::executor::RecordBatchVec;
+use snafu::ensure;
+use sql::{
+    frontend::{Context as SqlContext, Error as FrontendError, Frontend},
+    promql::{self, ColumnNames, EvalParams, Expr, Selector, METRIC_NAME_LABEL},
+    provider::CatalogMetaProvider,
+};
+use table_engine::table::TableRef;
+use warp::http::StatusCode;
+
+use crate::handlers::{
+    error::{ConvertPromResult, CreatePlan, InvalidPromParam, ListTables, ParsePromQL},
+    prelude::*,
+    sql::{execute_plan, find_schema},
+};
+
+const MATCH_PARAM: &str = "match[]";
+/// Default max points of a series in range query, same as prometheus.
+const MAX_POINTS_PER_SERIES: i64 = 11_000;
+
+/// Params of the request, from query string of GET request or form body of
+/// POST request. Some params like `match[]` may occur multiple times so a map
+/// is not used.
+pub type Params = Vec<(String, String)>;
+
+/// Response of the successful request
+#[derive(Debug, Serialize)]
+pub struct Response {
+    status: &'static str,
+    data: ResponseData,
+}
+
+impl Response {
+    fn success(data: ResponseData) -> Self {
+        Self {
+            status: "success",
+            data,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum ResponseData {
+    Query(QueryResult),
+    Labels(Vec<String>),
+    Series(Vec<BTreeMap<String, String>>),
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "resultType", content = "result", rename_all = "lowercase")]
+pub enum QueryResult {
+    Vector(Vec<InstantSeries>),
+    Matrix(Vec<RangeSeries>),
+    Scalar(SamplePair),
+    String(SamplePair),
+}
+
+#[derive(Debug, Serialize)]
+pub struct InstantSeries {
+    metric: BTreeMap<String, String>,
+    value: SamplePair,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RangeSeries {
+    metric: BTreeMap<String, String>,
+    values: Vec<SamplePair>,
+}
+
+/// Pair of timestamp in seconds and value, the value is always serialized
+/// as string.
+#[derive(Debug, Serialize)]
+pub struct SamplePair(f64, String);
+
+impl SamplePair {
+    fn new(timestamp: i64, value: f64) -> Self {
+        Self(timestamp_to_secs(timestamp), format_value(value))
+    }
+
+    fn new_string(timestamp: i64, value: String) -> Self {
+        Self(timestamp_to_secs(timestamp), value)
+    }
+}
+
+/// Response of the failed request
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ErrorResponse {
+    status: &'static str,
+    error_type: &'static str,
+    error: String,
+}
+
+/// Convert the error into status code and response in the format of
+/// prometheus.
+pub fn convert_error(err: &Error) -> (StatusCode, ErrorResponse) {
+    let (code, error_type) = match err {
+        Error::InvalidPromParam { .. } | Error::ParsePromQL { .. } => {
+            (StatusCode::BAD_REQUEST, "bad_data")
+        }
+        Error::CreatePlan { .. } | Error::InterpreterExec { .. } => {
+            (StatusCode::UNPROCESSABLE_ENTITY, "execution")
+        }
+        Error::QueryLimited { .. } => (StatusCode::SERVICE_UNAVAILABLE, "unavailable"),
+        Error::QuotaExceeded { .. } => (StatusCode::TOO_MANY_REQUESTS, "unavailable"),
+        _ => (StatusCode::INTERNAL_SERVER_ERROR, "internal"),
+    };
+    let err_string = err.to_string();
+    let resp = ErrorResponse {
+        status: "error",
+        error_type,
+        error: crate::error::first_line_in_error(&err_string).to_string(),
+    };
+
+    (code, resp)
+}
+
+fn timestamp_to_secs(timestamp: i64) -> f64 {
+    timestamp as f64 / 1000.0
+}
+
+/// Format the value like prometheus does.
+fn format_value(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value == f64::INFINITY {
+        "+Inf".to_string()
+    } else if value == f64::NEG_INFINITY {
+        "-Inf".to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+fn get_param<'a>(params: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    params
+        .iter()
+        .find(|(k, _)| k == name)
+        .map(|(_, v)| v.as_str())
+}
+
+fn get_required_param<'a>(params: &'a [(String, String)], name: &str) -> Result<&'a str> {
+    get_param(params, name).with_context(|| InvalidPromParam {
+        msg: format!("param {} is required", name),
+    })
+}
+
+/// Parse time in unix seconds or rfc3339 format into milliseconds.
+fn parse_time(name: &str, value: &str) -> Result<i64> {
+    if let Ok(secs) = value.parse::<f64>() {
+        return Ok((secs * 1000.0).round() as i64);
+    }
+
+    chrono::DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|t| t.timestamp_millis())
+        .with_context(|| InvalidPromParam {
+            msg: format!("invalid time of param {}, value:{}", name, value),
+        })
+}
+
+fn parse_time_param(params: &[(String, String)], name: &str, default: i64) -> Result<i64> {
+    match get_param(params, name) {
+        Some(value) => parse_time(name, value),
+        None => Ok(default),
+    }
+}
+
+/// Parse duration in seconds or promql duration format into milliseconds.
+fn parse_duration(name: &str, value: &str) -> Result<i64> {
+    if let Ok(secs) = value.parse::<f64>() {
+        return Ok((secs * 1000.0).round() as i64);
+    }
+
+    promql::parse_duration(value)
+        .ok()
+        .with_context(|| InvalidPromParam {
+            msg: format!("invalid duration of param {}, value:{}", name, value),
+        })
+}
+
+/// Time range of the metadata apis, all time by default.
+fn parse_series_params(params: &[(String, String)]) -> Result<EvalParams> {
+    let start = parse_time_param(params, "start", Timestamp::MIN.as_i64())?;
+    let end = parse_time_param(params, "end", Timestamp::MAX.as_i64() - 1)?;
+    ensure!(
+        start <= end,
+        InvalidPromParam {
+            msg: "end timestamp must not be before start time",
+        }
+    );
+
+    Ok(EvalParams::series(start, end))
+}
+
+pub(crate) fn is_table_not_found_error(e: &FrontendError) -> bool {
+    matches!(&e, FrontendError::CreatePlan { source }
+             if matches!(source, sql::planner::Error::BuildPromPlanError { source }
+                         if matches!(source, sql::promql::Error::TableNotFound { .. })))
+}
+
+/// Handle `/api/v1/query`
+pub async fn handle_query<C: CatalogManager + 'static, Q: QueryExecutor + 'static>(
+    ctx: RequestContext,
+    instance: InstanceRef<C, Q>,
+    params: Params,
+) -> Result<Response> {
+    let query = get_required_param(&params, "query")?;
+    let time = parse_time_param(&params, "time", Timestamp::now().as_i64())?;
+
+    let result = query_promql(&ctx, &instance, query, EvalParams::instant(time)).await?;
+    let result = match result {
+        PromQLResult::Series(series) => QueryResult::Vector(
+            series
+                .into_iter()
+                .filter_map(|(metric, samples)| {
+                    // Only the last sample is required by instant query.
+                    samples.last().map(|(timestamp, value)| InstantSeries {
+                        metric,
+                        value: SamplePair::new(*timestamp, *value),
+                    })
+                })
+                .collect(),
+        ),
+        PromQLResult::Scalar(value) => QueryResult::Scalar(SamplePair::new(time, value)),
+        PromQLResult::String(value) => QueryResult::String(SamplePair::new_string(time, value)),
+    };
+
+    Ok(Response::success(ResponseData::Query(result)))
+}
+
+/// Handle `/api/v1/query_range`
+pub async fn handle_query_range<C: CatalogManager + 'static, Q: QueryExecutor + 'static>(
+    ctx: RequestContext,
+    instance: InstanceRef<C, Q>,
+    params: Params,
+) -> Result<Response> {
+    let query = get_required_param(&params, "query")?;
+    let start = parse_time("start", get_required_param(&params, "start")?)?;
+    let end = parse_time("end", get_required_param(&params, "end")?)?;
+    let step = parse_duration("step", get_required_param(&params, "step")?)?;
+    ensure!(
+        start <= end,
+        InvalidPromParam {
+            msg: "end timestamp must not be before start time",
+        }
+    );
+    ensure!(
+        step > 0,
+        InvalidPromParam {
+            msg: "zero or negative query resolution step widths are not accepted",
+        }
+    );
+    ensure!(
+        end.saturating_sub(start) / step < MAX_POINTS_PER_SERIES,
+        InvalidPromParam {
+            msg: format!(
+                "exceeded maximum resolution of {} points per timeseries",
+                MAX_POINTS_PER_SERIES
+            ),
+        }
+    );
+
+    let result = query_promql(&ctx, &instance, query, EvalParams::range(start, end, step)).await?;
+    let result = match result {
+        PromQLResult::Series(series) => QueryResult::Matrix(
+            series
+                .into_iter()
+                .map(|(metric, samples)| RangeSeries {
+                    metric,
+                    values: samples
+                        .into_iter()
+                        .map(|(timestamp, value)| SamplePair::new(timestamp, value))
+                        .collect(),
+                })
+                .collect(),
+        ),
+        // Scalar is evaluated to the same value at every step.
+        PromQLResult::Scalar(value) => QueryResult::Matrix(vec![RangeSeries {
+            metric: BTreeMap::new(),
+            values: (start..=end)
+                .step_by(step as usize)
+                .map(|timestamp| SamplePair::new(timestamp, value))
+                .collect(),
+        }]),
+        PromQLResult::String(_) => return InvalidPromParam {
+            msg: "invalid expression type string for range query, must be scalar or instant vector",
+        }
+        .fail(),
+    };
+
+    Ok(Response::success(ResponseData::Query(result)))
+}
+
+/// Handle `/api/v1/labels`
+pub async fn handle_labels<C: CatalogManager + 'static, Q: QueryExecutor + 'static>(
+    ctx: RequestContext,
+    instance: InstanceRef<C, Q>,
+    params: Params,
+) -> Result<Response> {
+    let mut labels = BTreeSet::new();
+    labels.insert(METRIC_NAME_LABEL.to_string());
+
+    if has_match_param(&params) {
+        for series in find_series(&ctx, &instance, &params).await? {
+            labels.extend(series.into_keys());
+        }
+    } else {
+        for table in list_tables(&ctx, &instance)? {
+            let schema = table.schema();
+            labels.extend(
+                schema
+                    .columns()
+                    .iter()
+                    .filter(|column| column.is_tag)
+                    .map(|column| column.name.clone()),
+            );
+        }
+    }
+
+    Ok(Response::success(ResponseData::Labels(
+        labels.into_iter().collect(),
+    )))
+}
+
+/// Handle `/api/v1/label/<name>/values`
+pub async fn handle_label_values<C: CatalogManager + 'static, Q: QueryExecutor + 'static>(
+    ctx: RequestContext,
+    instance: InstanceRef<C, Q>,
+    name: String,
+    params: Params,
+) -> Result<Response> {
+    let mut values = BTreeSet::new();
+
+    if has_match_param(&params) {
+        for mut series in find_series(&ctx, &instance, &params).await? {
+            if let Some(value) = series.remove(&name) {
+                values.insert(value);
+            }
+        }
+    } else if name == METRIC_NAME_LABEL {
+        for table in list_tables(&ctx, &instance)? {
+            values.insert(table.name().to_string());
+        }
+    } else {
+        let series_params = parse_series_params(&params)?;
+        for table in list_tables(&ctx, &instance)? {
+            let has_tag = table
+                .schema()
+                .column_with_name(&name)
+                .map_or(false, |column| column.is_tag);
+            if !has_tag {
+                continue;
+            }
+
+            let selector = promql::table_selector(table.name().to_string(), series_params);
+            for mut series in query_series(&ctx, &instance, selector).await? {
+                if let Some(value) = series.remove(&name) {
+                    values.insert(value);
+                }
+            }
+        }
+    }
+
+    Ok(Response::success(ResponseData::Labels(
+        values.into_iter().collect(),
+    )))
+}
+
+/// Handle `/api/v1/series`
+pub async fn handle_series<C: CatalogManager + 'static, Q: QueryExecutor + 'static>(
+    ctx: RequestContext,
+    instance: InstanceRef<C, Q>,
+    params: Params,
+) -> Result<Response> {
+    ensure!(
+        has_match_param(&params),
+        InvalidPromParam {
+            msg: "no match[] parameter provided",
+        }
+    );
+
+    let series = find_series(&ctx, &instance, &params).await?;
+
+    Ok(Response::success(ResponseData::Series(series)))
+}
+
+fn has_match_param(params: &[(String, String)]) -> bool {
+    params.iter().any(|(k, _)| k == MATCH_PARAM)
+}
+
+/// Result of the promql, series are sorted by labels.
+enum PromQLResult {
+    Series(BTreeMap<BTreeMap<String, String>, Vec<(i64, f64)>>),
+    Scalar(f64),
+    String(String),
+}
+
+async fn query_promql<C: CatalogManager + 'static, Q: QueryExecutor + 'static>(
+    ctx: &RequestContext,
+    instance: &InstanceRef<C, Q>,
+    query: &str,
+    params: EvalParams,
+) -> Result<PromQLResult> {
+    let request_id = RequestId::next_id();
+
+    info!(
+        "prom handler try to process query, request_id:{}, query:{}, params:{:?}",
+        request_id, query, params
+    );
+
+    let provider = CatalogMetaProvider {
+        manager: &instance.catalog_manager,
+        default_catalog: &ctx.catalog,
+        default_schema: &ctx.tenant,
+        function_registry: &*instance.function_registry,
+    };
+    let frontend = Frontend::new(provider);
+    let mut sql_ctx = SqlContext::new(request_id);
+
+    let expr = frontend
+        .parse_promql_query(&mut sql_ctx, query, params)
+        .context(ParsePromQL)?;
+    let metric_name = match &expr {
+        Expr::SimpleExpr(promql::Operand::Float(v)) => return Ok(PromQLResult::Scalar(*v)),
+        Expr::SimpleExpr(promql::Operand::String(v)) => return Ok(PromQLResult::String(v.clone())),
+        // Metric name is kept only for the plain selector.
+        expr if expr.is_selector() => Some(expr.get_selector().table.clone()),
+        _ => None,
+    };
+
+    let (plan, column_name) = match frontend.promql_expr_to_aligned_plan(&mut sql_ctx, expr) {
+        Ok(v) => v,
+        // No series for the metric not exists, which is not an error for prometheus.
+        Err(e) if is_table_not_found_error(&e) => return Ok(PromQLResult::Series(BTreeMap::new())),
+        Err(e) => return Err(e).context(CreatePlan { query }),
+    };
+
+    let records = execute_plan(ctx, instance, request_id, plan, query).await?;
+    let series = convert_records(records, &column_name, metric_name, query)?;
+
+    Ok(PromQLResult::Series(series))
+}
+
+fn tag_value(datum: Datum) -> Option<String> {
+    let value = match datum {
+        Datum::Null => return None,
+        Datum::String(v) => v.to_string(),
+        v => v.display_string(),
+    };
+
+    // Prometheus doesn't allow empty label value.
+    if value.is_empty() {
+        None
+    } else {
+        Some(value)
+    }
+}
+
+/// Convert the aligned records into series, samples of each series are
+/// sorted by timestamp.
+pub(crate) fn convert_records(
+    records: RecordBatchVec,
+    column_name: &ColumnNames,
+    metric_name: Option<String>,
+    query: &str,
+) -> Result<BTreeMap<BTreeMap<String, String>, Vec<(i64, f64)>>> {
+    let mut tsid_to_series: HashMap<u64, (BTreeMap<String, String>, Vec<(i64, f64)>)> =
+        HashMap::new();
+
+    for record_batch in records {
+        let schema = record_batch.schema();
+        let find_column = |name: &str| {
+            schema.index_of(name).with_context(|| ConvertPromResult {
+                query,
+                msg: format!("column {} not found", name),
+            })
+        };
+        let tsid_idx = find_column(TSID_COLUMN)?;
+        let timestamp_idx = find_column(&column_name.timestamp)?;
+        let field_idx = find_column(&column_name.field)?;
+        let tag_idxs = column_name
+            .tag_keys
+            .iter()
+            .filter_map(|tag_key| schema.index_of(tag_key).map(|idx| (tag_key, idx)))
+            .collect::<Vec<_>>();
+
+        for row_idx in 0..record_batch.num_rows() {
+            let value = record_batch.column(field_idx).datum(row_idx);
+            // Raw samples of the field may be absent.
+            if value.is_null() {
+                continue;
+            }
+            let value = value.as_f64().with_context(|| ConvertPromResult {
+                query,
+                msg: "field must be f64-compatible type",
+            })?;
+            let tsid = record_batch
+                .column(tsid_idx)
+                .datum(row_idx)
+                .as_u64()
+                .with_context(|| ConvertPromResult {
+                    query,
+                    msg: "invalid tsid",
+                })?;
+            let timestamp = record_batch
+                .column(timestamp_idx)
+                .datum(row_idx)
+                .as_timestamp()
+                .with_context(|| ConvertPromResult {
+                    query,
+                    msg: "invalid timestamp",
+                })?
+                .as_i64();
+
+            let (_, samples) = tsid_to_series.entry(tsid).or_insert_with(|| {
+                let mut labels = tag_idxs
+                    .iter()
+                    .filter_map(|(tag_key, idx)| {
+                        tag_value(record_batch.column(*idx).datum(row_idx))
+                            .map(|v| (tag_key.to_string(), v))
+                    })
+                    .collect::<BTreeMap<_, _>>();
+                if let Some(metric_name) = &metric_name {
+                    labels.insert(METRIC_NAME_LABEL.to_string(), metric_name.clone());
+                }
+                (labels, Vec::new())
+            });
+            samples.push((timestamp, value));
+        }
+    }
+
+    Ok(tsid_to_series
+        .into_values()
+        .map(|(labels, mut samples)| {
+            samples.sort_by_key(|(timestamp, _)| *timestamp);
+            (labels, samples)
+        })
+        .collect())
+}
+
+/// Find the series matched by the `match[]` params.
+async fn find_series<C: CatalogManager + 'static, Q: QueryExecutor + 'static>(
+    ctx: &RequestContext,
+    instance: &InstanceRef<C, Q>,
+    params: &[(String, String)],
+) -> Result<Vec<BTreeMap<String, String>>> {
+    let series_params = parse_series_params(params)?;
+
+    let mut all_series = BTreeSet::new();
+    for (_, matcher) in params.iter().filter(|(k, _)| k == MATCH_PARAM) {
+        let selector = promql::parse_selector(matcher, series_params)
+            .map_err(|e| FrontendError::InvalidPromQL {
+                query: matcher.clone(),
+                source: e,
+            })
+            .context(ParsePromQL)?;
+        all_series.extend(query_series(ctx, instance, selector).await?);
+    }
+
+    Ok(all_series.into_iter().collect())
+}
+
+async fn query_series<C: CatalogManager + 'static, Q: QueryExecutor + 'static>(
+    ctx: &RequestContext,
+    instance: &InstanceRef<C, Q>,
+    selector: Selector,
+) -> Result<Vec<BTreeMap<String, String>>> {
+    let request_id = RequestId::next_id();
+    let table = selector.table.clone();
+    let query = format!("series of {}", table);
+
+    let provider = CatalogMetaProvider {
+        manager: &instance.catalog_manager,
+        default_catalog: &ctx.catalog,
+        default_schema: &ctx.tenant,
+        function_registry: &*instance.function_registry,
+    };
+    let frontend = Frontend::new(provider);
+    let mut sql_ctx = SqlContext::new(request_id);
+
+    let (plan, tag_keys) = match frontend.promql_series_to_plan(&mut sql_ctx, selector) {
+        Ok(v) => v,
+        Err(e) if is_table_not_found_error(&e) => return Ok(Vec::new()),
+        Err(e) => return Err(e).context(CreatePlan { query: &query }),
+    };
+
+    let records = execute_plan(ctx, instance, request_id, plan, &query).await?;
+
+    let mut series = Vec::new();
+    for record_batch in records {
+        let schema = record_batch.schema();
+        let tag_idxs = tag_keys
+            .iter()
+            .filter_map(|tag_key| schema.index_of(tag_key).map(|idx| (tag_key, idx)))
+            .collect::<Vec<_>>();
+
+        for row_idx in 0..record_batch.num_rows() {
+            let mut labels = tag_idxs
+                .iter()
+                .filter_map(|(tag_key, idx)| {
+                    tag_value(record_batch.column(*idx).datum(row_idx))
+                        .map(|v| (tag_key.to_string(), v))
+                })
+                .collect::<BTreeMap<_, _>>();
+            labels.insert(METRIC_NAME_LABEL.to_string(), table.clone());
+            series.push(labels);
+        }
+    }
+
+    Ok(series)
+}
+
+fn list_tables<C: CatalogManager + 'static, Q: QueryExecutor + 'static>(
+    ctx: &RequestContext,
+    instance: &InstanceRef<C, Q>,
+) -> Result<Vec<TableRef>> {
+    find_schema(ctx, instance)?
+        .all_tables()
+        .context(ListTables {
+            schema: &ctx.tenant,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_time() {
+        assert_eq!(
+            1_435_781_451_781,
+            parse_time("time", "1435781451.781").unwrap()
+        );
+        assert_eq!(
+            1_435_781_451_781,
+            parse_time("time", "2015-07-01T20:10:51.781Z").unwrap()
+        );
+        assert!(parse_time("time", "yesterday").is_err());
+
+        assert_eq!(15_000, parse_duration("step", "15").unwrap());
+        assert_eq!(15_000, parse_duration("step", "15s").unwrap());
+        assert!(parse_duration("step", "15x").is_err());
+    }
+
+    #[test]
+    fn test_format_value() {
+        assert_eq!("1", format_value(1.0));
+        assert_eq!("0.5", format_value(0.5));
+        assert_eq!("NaN", format_value(f64::NAN));
+        assert_eq!("+Inf", format_value(f64::INFINITY));
+        assert_eq!("-Inf", format_value(f64::NEG_INFINITY));
+    }
+
+    #[test]
+    fn test_response_format() {
+        let resp = Response::success(ResponseData::Query(QueryResult::Vector(vec![
+            InstantSeries {
+                metric: vec![("job".to_string(), "api".to_string())]
+                    .into_iter()
+                    .collect(),
+                value: SamplePair::new(1_500, 2.0),
+            },
+        ])));
+        assert_eq!(
+            r#"{"status":"success","data":{"resultType":"vector","result":[{"metric":{"job":"api"},"value":[1.5,"2"]}]}}"#,
+            serde_json::to_string(&resp).unwrap()
+        );
+
+        let resp = Response::success(ResponseData::Labels(vec!["__name__".to_string()]));
+        assert_eq!(
+            r#"{"status":"success","data":["__name__"]}"#,
+            serde_json::to_string(&resp).unwrap()
+        );
+    }
+}
@@ -0,0 +1,461 @@
+// Copyright 2022 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Handlers of the prometheus remote storage apis, bodies of the requests and
+//! responses are snappy compressed protobuf messages.
+//!
+//! See https://prometheus.io/docs/prometheus/latest/storage/#remote-storage-integrations
+
+use std::{collections::BTreeMap, sync::Arc};
+
+use ceresdbproto::storage::{Field, FieldGroup, Tag, Value, WriteEntry, WriteMetric};
+use common_types::request_id::RequestId;
+use log::info;
+use meta_client::MetaClient;
+use proto::prometheus::{
+    Label, LabelMatcher as LabelMatcherPb, MatchType, Query, QueryResult, ReadRequest,
+    ReadResponse, ResponseType, Sample, TimeSeries, WriteRequest,
+};
+use protobuf::Message;
+use snafu::{ensure, OptionExt};
+use sql::{
+    frontend::{Context as SqlContext, Error as FrontendError, Frontend},
+    promql::{
+        self, EvalParams, Expr, FilterType, LabelMatcher, Operand, DEFAULT_FIELD, METRIC_NAME_LABEL,
+    },
+    provider::CatalogMetaProvider,
+};
+use warp::http::StatusCode;
+
+use crate::handlers::{
+    error::{
+        CompressRemoteResponse, CreatePlan, DecodeRemoteRequest, DecompressRemoteRequest,
+        EncodeRemoteResponse, InvalidRemoteRequest, ParsePromQL,
+    },
+    prelude::*,
+    prom,
+    sql::execute_plan,
+    write,
+};
+
+/// Map the error to status code, prometheus only retries the request on 5xx
+/// and 429 if `retry_on_http_429` is enabled.
+///
+/// Only the errors of the request itself are 4xx, as the request is dropped
+/// by prometheus. The insert plan is built after adding the columns of the new
+/// labels to the table, so failing to build it means the schema change is not
+/// visible yet, which is transient like the failures of the catalog and the
+/// engine.
+pub fn error_to_status_code(err: &Error) -> StatusCode {
+    match err {
+        Error::DecompressRemoteRequest { .. }
+        | Error::DecodeRemoteRequest { .. }
+        | Error::InvalidRemoteRequest { .. }
+        | Error::ParsePromQL { .. }
+        | Error::TableNotFound { .. }
+        | Error::BuildCreateTablePlan { .. }
+        | Error::BuildAlterTablePlan { .. } => StatusCode::BAD_REQUEST,
+        Error::QuotaExceeded { .. } => StatusCode::TOO_MANY_REQUESTS,
+        Error::QueryLimited { .. } | Error::BuildInsertPlan { .. } => {
+            StatusCode::SERVICE_UNAVAILABLE
+        }
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// Handle `/prom/v1/write`
+///
+/// Series are written to the table named by the metric name, other labels are
+/// written as tags and the sample value is written to the `value` field. The
+/// table is created or altered if the tenant allows auto creating tables.
+pub async fn handle_remote_write<C: CatalogManager + 'static, Q: QueryExecutor + 'static>(
+    ctx: RequestContext,
+    instance: InstanceRef<C, Q>,
+    meta_client: Arc<dyn MetaClient + Send + Sync>,
+    body: &[u8],
+) -> Result<()> {
+    let request_id = RequestId::next_id();
+    let request: WriteRequest = decode_request(body)?;
+    let write_metrics = convert_write_request(request)?;
+
+    info!(
+        "prom remote write handler try to write metrics, request_id:{}, metrics:{}",
+        request_id,
+        write_metrics.len()
+    );
+
+    write::write_metrics(&ctx, &instance, &*meta_client, request_id, write_metrics).await
+}
+
+/// Handle `/prom/v1/read`, returns the compressed response.
+///
+/// Only the `SAMPLES` response type is supported, raw samples in the time range
+/// of each query are returned.
+pub async fn handle_remote_read<C: CatalogManager + 'static, Q: QueryExecutor + 'static>(
+    ctx: RequestContext,
+    instance: InstanceRef<C, Q>,
+    body: &[u8],
+) -> Result<Vec<u8>> {
+    let mut request: ReadRequest = decode_request(body)?;
+    let accepted_types = request.get_accepted_response_types();
+    ensure!(
+        accepted_types.is_empty() || accepted_types.contains(&ResponseType::SAMPLES),
+        InvalidRemoteRequest {
+            msg: format!("unsupported response types:{:?}", accepted_types),
+        }
+    );
+
+    let mut results = Vec::with_capacity(request.get_queries().len());
+    for query in request.take_queries().into_iter() {
+        results.push(read_query(&ctx, &instance, query).await?);
+    }
+
+    let mut response = ReadResponse::new();
+    response.set_results(results.into());
+    encode_response(&response)
+}
+
+async fn read_query<C: CatalogManager + 'static, Q: QueryExecutor + 'static>(
+    ctx: &RequestContext,
+    instance: &InstanceRef<C, Q>,
+    mut query: Query,
+) -> Result<QueryResult> {
+    let request_id = RequestId::next_id();
+    let matchers = query
+        .take_matchers()
+        .into_iter()
+        .map(convert_matcher)
+        .collect::<Vec<_>>();
+    let query_str = format_matchers(&matchers);
+
+    info!(
+        "prom remote read handler try to process query, request_id:{}, query:{}, start:{}, end:{}",
+        request_id,
+        query_str,
+        query.get_start_timestamp_ms(),
+        query.get_end_timestamp_ms()
+    );
+
+    let params = EvalParams::series(query.get_start_timestamp_ms(), query.get_end_timestamp_ms());
+    let selector = promql::matchers_to_selector(matchers, params)
+        .map_err(|e| FrontendError::InvalidPromQL {
+            query: query_str.clone(),
+            source: e,
+        })
+        .context(ParsePromQL)?;
+    let metric_name = selector.table.clone();
+
+    let provider = CatalogMetaProvider {
+        manager: &instance.catalog_manager,
+        default_catalog: &ctx.catalog,
+        default_schema: &ctx.tenant,
+        function_registry: &*instance.function_registry,
+    };
+    let frontend = Frontend::new(provider);
+    let mut sql_ctx = SqlContext::new(request_id);
+
+    // The selector is not aligned, so the raw samples are returned.
+    let expr = Expr::SimpleExpr(Operand::Selector(selector));
+    let (plan, column_name) = match frontend.promql_expr_to_plan(&mut sql_ctx, expr) {
+        Ok(v) => v,
+        Err(e) if prom::is_table_not_found_error(&e) => return Ok(QueryResult::new()),
+        Err(e) => return Err(e).context(CreatePlan { query: &query_str }),
+    };
+
+    let records = execute_plan(ctx, instance, request_id, plan, &query_str).await?;
+    let series = prom::convert_records(records, &column_name, Some(metric_name), &query_str)?;
+
+    let timeseries = series
+        .into_iter()
+        .map(|(labels, samples)| build_time_series(labels, samples))
+        .collect::<Vec<_>>();
+    let mut result = QueryResult::new();
+    result.set_timeseries(timeseries.into());
+
+    Ok(result)
+}
+
+fn decode_request<T: Message>(body: &[u8]) -> Result<T> {
+    let buf = snap::raw::Decoder::new()
+        .decompress_vec(body)
+        .context(DecompressRemoteRequest)?;
+
+    T::parse_from_bytes(&buf).context(DecodeRemoteRequest)
+}
+
+fn encode_response<T: Message>(response: &T) -> Result<Vec<u8>> {
+    let buf = response.write_to_bytes().context(EncodeRemoteResponse)?;
+
+    snap::raw::Encoder::new()
+        .compress_vec(&buf)
+        .context(CompressRemoteResponse)
+}
+
+/// Group the series of the request into write metrics by the metric name.
+fn convert_write_request(mut request: WriteRequest) -> Result<Vec<WriteMetric>> {
+    // Metric name -> (tag names, write entries)
+    let mut metrics: BTreeMap<String, (Vec<String>, Vec<WriteEntry>)> = BTreeMap::new();
+    for mut series in request.take_timeseries().into_iter() {
+        let mut metric_name = None;
+        let mut labels = Vec::with_capacity(series.get_labels().len());
+        for mut label in series.take_labels().into_iter() {
+            if label.get_name() == METRIC_NAME_LABEL {
+                metric_name = Some(label.take_value());
+            } else {
+                labels.push(label);
+            }
+        }
+        let metric_name = metric_name.with_context(|| InvalidRemoteRequest {
+            msg: format!(
+                "label {} is required, labels:{:?}",
+                METRIC_NAME_LABEL, labels
+            ),
+        })?;
+        if series.get_samples().is_empty() {
+            continue;
+        }
+
+        let (tag_names, entries) = metrics.entry(metric_name).or_default();
+        let tags = labels
+            .into_iter()
+            .map(|mut label| {
+                let name_index = match tag_names.iter().position(|v| v == label.get_name()) {
+                    Some(idx) => idx,
+                    None => {
+                        tag_names.push(label.take_name());
+                        tag_names.len() - 1
+                    }
+                };
+                let mut value = Value::new();
+                value.set_string_value(label.take_value());
+
+                let mut tag = Tag::new();
+                tag.set_name_index(name_index as u32);
+                tag.set_value(value);
+                tag
+            })
+            .collect::<Vec<_>>();
+        let field_groups = series
+            .take_samples()
+            .into_iter()
+            .map(|sample| {
+                let mut value = Value::new();
+                value.set_float64_value(sample.get_value());
+
+                let mut field = Field::new();
+                field.set_name_index(0);
+                field.set_value(value);
+
+                let mut field_group = FieldGroup::new();
+                field_group.set_timestamp(sample.get_timestamp());
+                field_group.set_fields(vec![field].into());
+                field_group
+            })
+            .collect::<Vec<_>>();
+
+        let mut entry = WriteEntry::new();
+        entry.set_tags(tags.into());
+        entry.set_field_groups(field_groups.into());
+        entries.push(entry);
+    }
+
+    Ok(metrics
+        .into_iter()
+        .map(|(metric_name, (tag_names, entries))| {
+            let mut write_metric = WriteMetric::new();
+            write_metric.set_metric(metric_name);
+            write_metric.set_tag_names(tag_names.into());
+            write_metric.set_field_names(vec![DEFAULT_FIELD.to_string()].into());
+            write_metric.set_entries(entries.into());
+            write_metric
+        })
+        .collect())
+}
+
+fn convert_matcher(mut matcher: LabelMatcherPb) -> LabelMatcher {
+    let typ = match matcher.get_field_type() {
+        MatchType::EQ => FilterType::LiteralOr,
+        MatchType::NEQ => FilterType::NotLiteralOr,
+        MatchType::RE => FilterType::Regexp,
+        MatchType::NRE => FilterType::NotRegexpMatch,
+    };
+
+    LabelMatcher {
+        name: matcher.take_name(),
+        typ,
+        value: matcher.take_value(),
+    }
+}
+
+/// Format the matchers like `{__name__="up",job=~"api.*"}`.
+fn format_matchers(matchers: &[LabelMatcher]) -> String {
+    let matchers = matchers
+        .iter()
+        .map(|matcher| {
+            let op = match matcher.typ {
+                FilterType::LiteralOr => "=",
+                FilterType::NotLiteralOr => "!=",
+                FilterType::Regexp => "=~",
+                FilterType::NotRegexpMatch => "!~",
+            };
+            format!("{}{}{:?}", matcher.name, op, matcher.value)
+        })
+        .collect::<Vec<_>>();
+
+    format!("{{{}}}", matchers.join(","))
+}
+
+fn build_time_series(labels: BTreeMap<String, String>, samples: Vec<(i64, f64)>) -> TimeSeries {
+    let labels = labels
+        .into_iter()
+        .map(|(name, value)| {
+            let mut label = Label::new();
+            label.set_name(name);
+            label.set_value(value);
+            label
+        })
+        .collect::<Vec<_>>();
+    let samples = samples
+        .into_iter()
+        .map(|(timestamp, value)| {
+            let mut sample = Sample::new();
+            sample.set_timestamp(timestamp);
+            sample.set_value(value);
+            sample
+        })
+        .collect::<Vec<_>>();
+
+    let mut series = TimeSeries::new();
+    series.set_labels(labels.into());
+    series.set_samples(samples.into());
+    series
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_series(labels: &[(&str, &str)], samples: &[(i64, f64)]) -> TimeSeries {
+        let labels = labels
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        build_time_series(labels, samples.to_vec())
+    }
+
+    #[test]
+    fn test_decode_request() {
+        let mut request = WriteRequest::new();
+        request.set_timeseries(vec![build_series(&[("__name__", "up")], &[(1000, 1.0)])].into());
+        let body = encode_response(&request).unwrap();
+
+        let decoded: WriteRequest = decode_request(&body).unwrap();
+        assert_eq!(request, decoded);
+
+        assert!(decode_request::<WriteRequest>(b"invalid").is_err());
+    }
+
+    #[test]
+    fn test_convert_write_request() {
+        let mut request = WriteRequest::new();
+        request.set_timeseries(
+            vec![
+                build_series(
+                    &[("__name__", "up"), ("job", "api")],
+                    &[(1000, 1.0), (2000, 0.0)],
+                ),
+                build_series(
+                    &[("__name__", "up"), ("instance", "host1"), ("job", "db")],
+                    &[(1000, 1.0)],
+                ),
+                build_series(&[("__name__", "cpu")], &[(1000, 0.5)]),
+                // Series without samples is ignored.
+                build_series(&[("__name__", "mem")], &[]),
+            ]
+            .into(),
+        );
+
+        let write_metrics = convert_write_request(request).unwrap();
+        assert_eq!(2, write_metrics.len());
+
+        let cpu = &write_metrics[0];
+        assert_eq!("cpu", cpu.get_metric());
+        assert!(cpu.get_tag_names().is_empty());
+        assert_eq!(&[DEFAULT_FIELD.to_string()], cpu.get_field_names());
+        assert_eq!(1, cpu.get_entries().len());
+
+        let up = &write_metrics[1];
+        assert_eq!("up", up.get_metric());
+        assert_eq!(
+            &["job".to_string(), "instance".to_string()],
+            up.get_tag_names()
+        );
+        let entries = up.get_entries();
+        assert_eq!(2, entries.len());
+        assert_eq!(2, entries[0].get_field_groups().len());
+        let tags = entries[1].get_tags();
+        assert_eq!(1, tags[0].get_name_index());
+        assert_eq!("host1", tags[0].get_value().get_string_value());
+        assert_eq!(0, tags[1].get_name_index());
+        assert_eq!("db", tags[1].get_value().get_string_value());
+        let field_group = &entries[1].get_field_groups()[0];
+        assert_eq!(1000, field_group.get_timestamp());
+        assert_eq!(
+            1.0,
+            field_group.get_fields()[0].get_value().get_float64_value()
+        );
+
+        // Metric name is required.
+        let mut request = WriteRequest::new();
+        request.set_timeseries(vec![build_series(&[("job", "api")], &[(1000, 1.0)])].into());
+        assert!(convert_write_request(request).is_err());
+    }
+
+    #[test]
+    fn test_error_to_status_code() {
+        let err = InvalidRemoteRequest { msg: "no samples" }
+            .fail::<()>()
+            .unwrap_err();
+        assert_eq!(StatusCode::BAD_REQUEST, error_to_status_code(&err));
+
+        // Columns of the new labels are not visible yet.
+        let err = Error::BuildInsertPlan {
+            table: "up".to_string(),
+            source: crate::error::ServerError::ErrNoCause {
+                code: crate::error::StatusCode::InvalidArgument,
+                msg: "Can't find tag in schema, table:up, tag_name:job".to_string(),
+            },
+        };
+        assert_eq!(StatusCode::SERVICE_UNAVAILABLE, error_to_status_code(&err));
+
+        let err = Error::PollStream {
+            query: "up".to_string(),
+            source: table_engine::stream::Error::ErrWithSource {
+                msg: "read sst".to_string(),
+                source: Box::new(std::io::Error::new(std::io::ErrorKind::Other, "timeout")),
+            },
+        };
+        assert_eq!(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            error_to_status_code(&err)
+        );
+    }
+
+    #[test]
+    fn test_convert_matchers() {
+        let mut matcher = LabelMatcherPb::new();
+        matcher.set_field_type(MatchType::NRE);
+        matcher.set_name("job".to_string());
+        matcher.set_value("api.*".to_string());
+
+        let mut name_matcher = LabelMatcherPb::new();
+        name_matcher.set_name(METRIC_NAME_LABEL.to_string());
+        name_matcher.set_value("up".to_string());
+
+        let matchers = vec![convert_matcher(name_matcher), convert_matcher(matcher)];
+        assert!(matches!(matchers[1].typ, FilterType::NotRegexpMatch));
+        assert_eq!(
+            r#"{__name__="up",job!~"api.*"}"#,
+            format_matchers(&matchers)
+        );
+    }
+}
@@ -2,28 +2,107 @@
 
 //! SQL request handler
 
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    convert::TryInto,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
 
-use arrow_deps::arrow::error::Result as ArrowResult;
-use common_types::{datum::Datum, request_id::RequestId};
+use arrow_deps::arrow::{
+    array::UInt64Array,
+    datatypes::{DataType, Field, Schema, SchemaRef as ArrowSchemaRef},
+    error::Result as ArrowResult,
+    record_batch::RecordBatch as ArrowRecordBatch,
+};
+use catalog::schema::SchemaRef;
+use common_types::{
+    datum::Datum, record_batch::RecordBatch, request_id::RequestId, schema::RecordSchema,
+};
+use futures::{
+    stream::{self, BoxStream, Stream},
+    StreamExt, TryStreamExt,
+};
 use interpreters::{context::Context as InterpreterContext, factory::Factory, interpreter::Output};
-use log::info;
+use log::{error, info};
 use query_engine::executor::RecordBatchVec;
 use serde_derive::Serialize;
-use snafu::ensure;
+use snafu::{ensure, OptionExt};
 use sql::{
     frontend::{Context as SqlContext, Frontend},
+    plan::Plan,
     provider::CatalogMetaProvider,
 };
+use table_engine::stream::{RecordBatchStream, SendableRecordBatchStream};
 
-use crate::handlers::{
-    error::{ArrowToString, CreatePlan, InterpreterExec, ParseSql, TooMuchStmt},
-    prelude::*,
+use crate::{
+    handlers::{
+        encoder::RecordEncoder,
+        error::{
+            ArrowToString, CreatePlan, EncodeRecords, FindSchema, InterpreterExec, ParseSql,
+            PollStream, QueryLimited, QuotaExceeded, SchemaNotFound, TooMuchStmt,
+        },
+        prelude::*,
+    },
+    limiter::QueryPermit,
 };
 
+/// Format of the response.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Format {
+    /// All the rows in a single json response.
+    Json,
+    /// Streams the rows as newline delimited json.
+    Ndjson,
+    /// Streams the rows as csv.
+    Csv,
+    /// Streams the rows in arrow ipc streaming format.
+    Arrow,
+}
+
+impl Default for Format {
+    fn default() -> Self {
+        Format::Json
+    }
+}
+
+impl Format {
+    /// Whether the response is streamed to the client.
+    pub fn is_stream(&self) -> bool {
+        *self != Format::Json
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Format::Json => "application/json",
+            Format::Ndjson => "application/x-ndjson",
+            Format::Csv => "text/csv",
+            Format::Arrow => "application/vnd.apache.arrow.stream",
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct Request {
     query: String,
+    #[serde(default)]
+    format: Format,
+}
+
+impl Request {
+    pub fn new(query: String) -> Self {
+        Self {
+            query,
+            format: Format::default(),
+        }
+    }
+
+    #[inline]
+    pub fn format(&self) -> Format {
+        self.format
+    }
 }
 
 // TODO(yingwen): Improve serialize performance
@@ -34,11 +113,89 @@ pub enum Response {
     Rows(Vec<HashMap<String, Datum>>),
 }
 
+/// Chunks of the encoded records streamed to the client.
+pub type ResponseStream = BoxStream<'static, Result<Vec<u8>>>;
+
 pub async fn handle_sql<C: CatalogManager + 'static, Q: QueryExecutor + 'static>(
     ctx: RequestContext,
     instance: InstanceRef<C, Q>,
     request: Request,
 ) -> Result<Response> {
+    let output = execute_sql(ctx, &instance, &request, false).await?;
+
+    // Convert output to json
+    let resp = convert_output(output, &request.query).await?;
+
+    info!(
+        "sql handler finished processing request, request:{:?}",
+        request
+    );
+
+    Ok(resp)
+}
+
+/// Handle the sql and streams the records in the format of the request, the
+/// records are pulled from the query only when the client is ready to receive
+/// more data.
+pub async fn handle_sql_stream<C: CatalogManager + 'static, Q: QueryExecutor + 'static>(
+    ctx: RequestContext,
+    instance: InstanceRef<C, Q>,
+    request: Request,
+) -> Result<ResponseStream> {
+    let output = execute_sql(ctx, &instance, &request, true).await?;
+
+    let (schema, records) = output_to_stream(output);
+    let encoder = match request.format {
+        Format::Ndjson => RecordEncoder::ndjson(),
+        Format::Csv => RecordEncoder::csv(),
+        // The schema is unknown only if there is no record batch, so the empty
+        // schema makes no difference.
+        Format::Arrow => RecordEncoder::arrow(
+            schema
+                .map(|v| v.as_ref().clone())
+                .unwrap_or_else(Schema::empty),
+        ),
+        Format::Json => unreachable!(),
+    };
+
+    info!(
+        "sql handler starts streaming response, request:{:?}",
+        request
+    );
+
+    let state = Some((records, encoder));
+    let query = request.query;
+    let response = stream::unfold(state, move |state| {
+        let query = query.clone();
+        async move {
+            let (mut records, mut encoder) = state?;
+            let (chunk, state) = match records.next().await {
+                Some(Ok(record_batch)) => match encoder.encode(record_batch) {
+                    Ok(chunk) => (Ok(chunk), Some((records, encoder))),
+                    Err(e) => (Err(e).context(EncodeRecords { query }), None),
+                },
+                Some(Err(e)) => (Err(e).context(PollStream { query }), None),
+                None => (encoder.finish().context(EncodeRecords { query }), None),
+            };
+            if let Err(e) = &chunk {
+                error!("sql handler failed to stream response, err:{}", e);
+            }
+
+            Some((chunk, state))
+        }
+    });
+
+    Ok(response.boxed())
+}
+
+/// Execute the sql of the request, which is limited by the reject list and the
+/// quota of the tenant, shared by the http, mysql and postgresql services.
+pub(crate) async fn execute_sql<C: CatalogManager + 'static, Q: QueryExecutor + 'static>(
+    ctx: RequestContext,
+    instance: &InstanceRef<C, Q>,
+    request: &Request,
+    stream_output: bool,
+) -> Result<Output> {
     let request_id = RequestId::next_id();
 
     info!(
@@ -46,6 +203,181 @@ pub async fn handle_sql<C: CatalogManager + 'static, Q: QueryExecutor + 'static>
         request_id, request
     );
 
+    let plan = match sql_to_plan(&ctx, instance, request_id, &request.query)? {
+        Some(plan) => plan,
+        None => return Ok(Output::AffectedRows(0)),
+    };
+    ensure!(
+        !instance.limiter.should_limit(&plan),
+        QueryLimited {
+            query: &request.query
+        }
+    );
+    // Only the queries are limited by the read quota of the tenant.
+    let permit = instance
+        .limiter
+        .try_begin_query(&ctx.tenant, &plan)
+        .context(QuotaExceeded)?;
+
+    // Execute in interpreter
+    let interpreter_ctx = InterpreterContext::builder(request_id)
+        // Use current ctx's catalog and tenant as default catalog and tenant
+        .default_catalog_and_schema(ctx.catalog, ctx.tenant)
+        .query(request.query.clone())
+        .timeout(instance.query_timeout)
+        .stream_output(stream_output)
+        .build();
+    let interpreter_factory = Factory::new(
+        instance.query_executor.clone(),
+        instance.catalog_manager.clone(),
+        instance.table_engine.clone(),
+        instance.query_registry.clone(),
+    );
+    let interpreter = interpreter_factory.create(interpreter_ctx, plan);
+
+    let output = interpreter.execute().await.context(InterpreterExec {
+        query: &request.query,
+    })?;
+
+    // The rows of the stream are scanned lazily, so the permit is held until
+    // the stream is dropped.
+    let output = match (output, permit) {
+        (Output::Stream(stream), Some(permit)) => Output::Stream(Box::pin(PermitStream {
+            stream,
+            _permit: permit,
+        })),
+        (output, _) => output,
+    };
+
+    Ok(output)
+}
+
+/// Stream holding the permit of the query.
+struct PermitStream {
+    stream: SendableRecordBatchStream,
+    _permit: QueryPermit,
+}
+
+impl Stream for PermitStream {
+    type Item = table_engine::stream::Result<RecordBatch>;
+
+    fn poll_next(mut self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.stream.as_mut().poll_next(ctx)
+    }
+}
+
+impl RecordBatchStream for PermitStream {
+    fn schema(&self) -> &RecordSchema {
+        self.stream.schema()
+    }
+}
+
+/// Execute the `plan` and collect the records, the `query` is used in the
+/// errors only.
+pub(crate) async fn execute_plan<C: CatalogManager + 'static, Q: QueryExecutor + 'static>(
+    ctx: &RequestContext,
+    instance: &InstanceRef<C, Q>,
+    request_id: RequestId,
+    plan: Plan,
+    query: &str,
+) -> Result<RecordBatchVec> {
+    ensure!(
+        !instance.limiter.should_limit(&plan),
+        QueryLimited { query }
+    );
+    // The rows scanned are charged when the permit is dropped.
+    let _permit = instance
+        .limiter
+        .try_begin_query(&ctx.tenant, &plan)
+        .context(QuotaExceeded)?;
+
+    let interpreter_ctx = InterpreterContext::builder(request_id)
+        // Use current ctx's catalog and tenant as default catalog and tenant
+        .default_catalog_and_schema(ctx.catalog.clone(), ctx.tenant.clone())
+        .query(query.to_string())
+        .timeout(instance.query_timeout)
+        .build();
+    let interpreter_factory = Factory::new(
+        instance.query_executor.clone(),
+        instance.catalog_manager.clone(),
+        instance.table_engine.clone(),
+        instance.query_registry.clone(),
+    );
+    let interpreter = interpreter_factory.create(interpreter_ctx, plan);
+
+    let output = interpreter
+        .execute()
+        .await
+        .context(InterpreterExec { query })?;
+
+    match output {
+        Output::Records(records) => Ok(records),
+        Output::AffectedRows(_) => Ok(Vec::new()),
+        Output::Stream(stream) => collect_stream(stream, query).await,
+    }
+}
+
+/// Collect the records of the stream, the `query` is used in the errors only.
+async fn collect_stream(stream: SendableRecordBatchStream, query: &str) -> Result<RecordBatchVec> {
+    stream.try_collect().await.context(PollStream { query })
+}
+
+/// Find the schema of the tenant.
+pub(crate) fn find_schema<C: CatalogManager + 'static, Q: QueryExecutor + 'static>(
+    ctx: &RequestContext,
+    instance: &InstanceRef<C, Q>,
+) -> Result<SchemaRef> {
+    let catalog = instance
+        .catalog_manager
+        .catalog_by_name(&ctx.catalog)
+        .map_err(|e| Box::new(e) as _)
+        .context(FindSchema {
+            catalog: &ctx.catalog,
+            schema: &ctx.tenant,
+        })?
+        .context(SchemaNotFound {
+            catalog: &ctx.catalog,
+            schema: &ctx.tenant,
+        })?;
+
+    catalog
+        .schema_by_name(&ctx.tenant)
+        .map_err(|e| Box::new(e) as _)
+        .context(FindSchema {
+            catalog: &ctx.catalog,
+            schema: &ctx.tenant,
+        })?
+        .context(SchemaNotFound {
+            catalog: &ctx.catalog,
+            schema: &ctx.tenant,
+        })
+}
+
+/// Plan the sql without executing it, returns the schema of the result, which
+/// is None if the sql is not a query.
+pub(crate) fn describe_sql<C: CatalogManager + 'static, Q: QueryExecutor + 'static>(
+    ctx: &RequestContext,
+    instance: &InstanceRef<C, Q>,
+    query: &str,
+) -> Result<Option<ArrowSchemaRef>> {
+    let plan = sql_to_plan(ctx, instance, RequestId::next_id(), query)?;
+    let schema = match plan {
+        Some(Plan::Query(plan)) => Some(Arc::new(Schema::from(
+            plan.df_plan.schema().as_ref().clone(),
+        ))),
+        _ => None,
+    };
+
+    Ok(schema)
+}
+
+/// Parse and plan the sql, returns None if there is no statement.
+fn sql_to_plan<C: CatalogManager + 'static, Q: QueryExecutor + 'static>(
+    ctx: &RequestContext,
+    instance: &InstanceRef<C, Q>,
+    request_id: RequestId,
+    query: &str,
+) -> Result<Option<Plan>> {
     // We use tenant as schema
     // TODO(yingwen): Privilege check, cannot access data of other tenant
     // TODO(yingwen): Maybe move MetaProvider to instance
@@ -60,12 +392,10 @@ pub async fn handle_sql<C: CatalogManager + 'static, Q: QueryExecutor + 'static>
     let mut sql_ctx = SqlContext::new(request_id);
     // Parse sql, frontend error of invalid sql already contains sql
     // TODO(yingwen): Maybe move sql from frontend error to outer error
-    let mut stmts = frontend
-        .parse_sql(&mut sql_ctx, &request.query)
-        .context(ParseSql)?;
+    let mut stmts = frontend.parse_sql(&mut sql_ctx, query).context(ParseSql)?;
 
     if stmts.is_empty() {
-        return Ok(Response::AffectedRows(0));
+        return Ok(None);
     }
 
     // TODO(yingwen): For simplicity, we only support executing one statement now
@@ -74,7 +404,7 @@ pub async fn handle_sql<C: CatalogManager + 'static, Q: QueryExecutor + 'static>
         stmts.len() == 1,
         TooMuchStmt {
             len: stmts.len(),
-            query: request.query,
+            query,
         }
     );
 
@@ -82,46 +412,54 @@ pub async fn handle_sql<C: CatalogManager + 'static, Q: QueryExecutor + 'static>
     // Note: Remember to store sql in error when creating logical plan
     let plan = frontend
         .statement_to_plan(&mut sql_ctx, stmts.remove(0))
-        .context(CreatePlan {
-            query: &request.query,
-        })?;
-
-    // Execute in interpreter
-    let interpreter_ctx = InterpreterContext::builder(request_id)
-        // Use current ctx's catalog and tenant as default catalog and tenant
-        .default_catalog_and_schema(ctx.catalog, ctx.tenant)
-        .build();
-    let interpreter_factory = Factory::new(
-        instance.query_executor.clone(),
-        instance.catalog_manager.clone(),
-        instance.table_engine.clone(),
-    );
-    let interpreter = interpreter_factory.create(interpreter_ctx, plan);
-
-    let output = interpreter.execute().await.context(InterpreterExec {
-        query: &request.query,
-    })?;
+        .context(CreatePlan { query })?;
 
-    // Convert output to json
-    let resp = convert_output(output).context(ArrowToString {
-        query: &request.query,
-    })?;
+    Ok(Some(plan))
+}
 
-    info!(
-        "sql handler finished processing request, request:{:?}",
-        request
-    );
+async fn convert_output(output: Output, query: &str) -> Result<Response> {
+    let records = match output {
+        Output::AffectedRows(n) => return Ok(Response::AffectedRows(n)),
+        Output::Records(records) => records,
+        Output::Stream(stream) => collect_stream(stream, query).await?,
+    };
 
-    Ok(resp)
+    convert_records(records).context(ArrowToString { query })
 }
 
-fn convert_output(output: Output) -> ArrowResult<Response> {
+/// Converts the output into a stream of record batches, also returns the
+/// schema of the record batches if it is known.
+fn output_to_stream(
+    output: Output,
+) -> (
+    Option<ArrowSchemaRef>,
+    BoxStream<'static, table_engine::stream::Result<RecordBatch>>,
+) {
     match output {
-        Output::AffectedRows(n) => Ok(Response::AffectedRows(n)),
-        Output::Records(records) => convert_records(records),
+        Output::AffectedRows(n) => {
+            let record_batch = affected_rows_record_batch(n);
+            let schema = record_batch.schema().to_arrow_schema_ref();
+            (Some(schema), stream::iter(vec![Ok(record_batch)]).boxed())
+        }
+        Output::Records(records) => {
+            let schema = records.first().map(|v| v.schema().to_arrow_schema_ref());
+            (schema, stream::iter(records.into_iter().map(Ok)).boxed())
+        }
+        Output::Stream(stream) => (Some(stream.schema().to_arrow_schema_ref()), stream.boxed()),
     }
 }
 
+pub(crate) fn affected_rows_record_batch(affected_rows: usize) -> RecordBatch {
+    let schema = Schema::new(vec![Field::new("affected_rows", DataType::UInt64, false)]);
+    let arrow_record_batch = ArrowRecordBatch::try_new(
+        Arc::new(schema),
+        vec![Arc::new(UInt64Array::from(vec![affected_rows as u64]))],
+    )
+    .unwrap();
+
+    arrow_record_batch.try_into().unwrap()
+}
+
 fn convert_records(records: RecordBatchVec) -> ArrowResult<Response> {
     let total_rows = records.iter().map(|v| v.num_rows()).sum();
     let mut resp = Vec::with_capacity(total_rows);
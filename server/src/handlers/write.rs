@@ -0,0 +1,101 @@
+// Copyright 2022 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Write metrics to tables, shared by the write apis of other protocols
+
+use ceresdbproto::storage::WriteMetric;
+use common_types::request_id::RequestId;
+use log::info;
+use meta_client::MetaClient;
+use protobuf::Message;
+use snafu::OptionExt;
+use sql::plan::Plan;
+
+use crate::{
+    grpc,
+    handlers::{
+        error::{
+            BuildAlterTablePlan, BuildCreateTablePlan, BuildInsertPlan, FindTable, QuotaExceeded,
+            TableNotFound,
+        },
+        prelude::*,
+        sql::{execute_plan, find_schema},
+    },
+};
+
+/// Write the metrics to the tables named by the metric names.
+///
+/// If the tenant allows auto creating tables, missing tables are created and
+/// missing columns are added to the tables. The whole request is rejected if
+/// it exceeds the write quota of the tenant.
+pub(crate) async fn write_metrics<C: CatalogManager + 'static, Q: QueryExecutor + 'static>(
+    ctx: &RequestContext,
+    instance: &InstanceRef<C, Q>,
+    meta_client: &(dyn MetaClient + Send + Sync),
+    request_id: RequestId,
+    write_metrics: Vec<WriteMetric>,
+) -> Result<()> {
+    let cluster_view = meta_client.get_cluster_view();
+    let schema_config = cluster_view
+        .schema_configs
+        .get(&ctx.tenant)
+        .filter(|config| config.auto_create_tables);
+    let schema = find_schema(ctx, instance)?;
+
+    // Each field group of the entries is a row.
+    let num_rows = write_metrics
+        .iter()
+        .flat_map(|m| m.get_entries())
+        .map(|entry| entry.get_field_groups().len())
+        .sum();
+    let num_bytes = write_metrics
+        .iter()
+        .map(|m| m.compute_size() as usize)
+        .sum();
+    instance
+        .limiter
+        .try_acquire_write(&ctx.tenant, num_rows, num_bytes)
+        .context(QuotaExceeded)?;
+
+    for write_metric in write_metrics {
+        let table_name = write_metric.get_metric().to_string();
+        let query = format!("write of {}", table_name);
+        let mut table = schema
+            .table_by_name(&table_name)
+            .context(FindTable { table: &table_name })?;
+
+        if let Some(config) = schema_config {
+            match table {
+                Some(existing) => {
+                    let plan =
+                        grpc::write_metric_to_alter_table_plan(config, existing, &write_metric)
+                            .context(BuildAlterTablePlan { table: &table_name })?;
+                    if let Some(plan) = plan {
+                        info!(
+                            "Http write try to add columns, request_id:{}, table:{}, plan:{:?}",
+                            request_id, table_name, plan
+                        );
+                        let plan = Plan::AlterTable(plan);
+                        execute_plan(ctx, instance, request_id, plan, &query).await?;
+                    }
+                }
+                None => {
+                    let plan = grpc::write_metric_to_create_table_plan(config, &write_metric)
+                        .context(BuildCreateTablePlan { table: &table_name })?;
+                    let plan = Plan::Create(plan);
+                    execute_plan(ctx, instance, request_id, plan, &query).await?;
+                }
+            }
+            // Try to get table again
+            table = schema
+                .table_by_name(&table_name)
+                .context(FindTable { table: &table_name })?;
+        }
+
+        let table = table.context(TableNotFound { table: &table_name })?;
+        let plan = grpc::write_metric_to_insert_plan(table, write_metric)
+            .context(BuildInsertPlan { table: &table_name })?;
+        execute_plan(ctx, instance, request_id, Plan::Insert(plan), &query).await?;
+    }
+
+    Ok(())
+}
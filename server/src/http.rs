@@ -6,6 +6,7 @@ use std::{convert::Infallible, net::IpAddr, sync::Arc};
 
 use catalog::manager::Manager as CatalogManager;
 use log::error;
+use meta_client::MetaClient;
 use profile::Profiler;
 use query_engine::executor::Executor as QueryExecutor;
 use serde_derive::Serialize;
@@ -14,13 +15,29 @@ use table_engine::engine::EngineRuntimes;
 use tokio::sync::oneshot::{self, Sender};
 use warp::{
     header,
-    http::StatusCode,
+    http::{
+        header::{HeaderValue, CONTENT_TYPE},
+        StatusCode,
+    },
+    hyper::Body,
     reject,
     reply::{self, Reply},
     Filter,
 };
 
-use crate::{consts, context::RequestContext, error, handlers, instance::InstanceRef, metrics};
+use crate::{
+    consts,
+    context::RequestContext,
+    error,
+    handlers::{self, influxdb::WriteParams as InfluxDBWriteParams, prom::Params as PromParams},
+    instance::InstanceRef,
+    metrics,
+};
+
+/// Max body size of the prometheus remote write/read request
+const MAX_PROM_REMOTE_BODY_SIZE: u64 = 32 * 1024 * 1024;
+/// Max body size of the InfluxDB write request
+const MAX_INFLUXDB_WRITE_BODY_SIZE: u64 = 32 * 1024 * 1024;
 
 #[derive(Debug)]
 pub struct Config {
@@ -44,6 +61,9 @@ pub enum Error {
     #[snafu(display("Missing instance to build service.\nBacktrace:\n{}", backtrace))]
     MissingInstance { backtrace: Backtrace },
 
+    #[snafu(display("Missing meta client to build service.\nBacktrace:\n{}", backtrace))]
+    MissingMetaClient { backtrace: Backtrace },
+
     #[snafu(display(
         "Fail to do heap profiling, err:{}.\nBacktrace:\n{}",
         source,
@@ -80,6 +100,7 @@ impl reject::Reject for Error {}
 pub struct Service<C, Q> {
     runtimes: Arc<EngineRuntimes>,
     instance: InstanceRef<C, Q>,
+    meta_client: Arc<dyn MetaClient + Send + Sync>,
     profiler: Arc<Profiler>,
     tx: Sender<()>,
 }
@@ -99,6 +120,17 @@ impl<C: CatalogManager + 'static, Q: QueryExecutor + 'static> Service<C, Q> {
             .or(self.sql())
             .or(self.heap_profile())
             .or(self.admin_reject())
+            .or(self.admin_quota())
+            .or(self.admin_snapshot())
+            .or(self.admin_restore())
+            .or(self.prom_query())
+            .or(self.prom_query_range())
+            .or(self.prom_labels())
+            .or(self.prom_label_values())
+            .or(self.prom_series())
+            .or(self.prom_remote_write())
+            .or(self.prom_remote_read())
+            .or(self.influxdb_write())
     }
 
     fn home(&self) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
@@ -118,20 +150,171 @@ impl<C: CatalogManager + 'static, Q: QueryExecutor + 'static> Service<C, Q> {
             .and(warp::body::json())
             .and(self.with_context())
             .and(self.with_instance())
-            .and_then(|req, ctx, instance| async {
+            .and_then(|req: handlers::sql::Request, ctx, instance| async {
                 // TODO(yingwen): Wrap common logic such as metrics, trace and error log
-                let result = handlers::sql::handle_sql(ctx, instance, req)
-                    .await
+                let format = req.format();
+                let result = if format.is_stream() {
+                    handlers::sql::handle_sql_stream(ctx, instance, req)
+                        .await
+                        .map(|stream| {
+                            // The records are encoded and sent in chunks.
+                            let mut resp = reply::Response::new(Body::wrap_stream(stream));
+                            resp.headers_mut().insert(
+                                CONTENT_TYPE,
+                                HeaderValue::from_static(format.content_type()),
+                            );
+                            resp
+                        })
+                } else {
+                    handlers::sql::handle_sql(ctx, instance, req)
+                        .await
+                        .map(|res| reply::json(&res).into_response())
+                };
+                let result = result
                     .map_err(|e| {
                         // TODO(yingwen): Maybe truncate and print the sql
                         error!("Http service Failed to handle sql, err:{}", e);
                         e
                     })
                     .context(HandleRequest);
-                match result {
-                    Ok(res) => Ok(reply::json(&res)),
-                    Err(e) => Err(reject::custom(e)),
-                }
+                result.map_err(reject::custom)
+            })
+    }
+
+    fn prom_query(
+        &self,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("api" / "v1" / "query")
+            .and(prom_params())
+            .and(self.with_context())
+            .and(self.with_instance())
+            .and_then(|params, ctx, instance| async {
+                let result = handlers::prom::handle_query(ctx, instance, params).await;
+                Ok::<_, warp::Rejection>(prom_reply(result))
+            })
+    }
+
+    fn prom_query_range(
+        &self,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("api" / "v1" / "query_range")
+            .and(prom_params())
+            .and(self.with_context())
+            .and(self.with_instance())
+            .and_then(|params, ctx, instance| async {
+                let result = handlers::prom::handle_query_range(ctx, instance, params).await;
+                Ok::<_, warp::Rejection>(prom_reply(result))
+            })
+    }
+
+    fn prom_labels(
+        &self,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("api" / "v1" / "labels")
+            .and(prom_params())
+            .and(self.with_context())
+            .and(self.with_instance())
+            .and_then(|params, ctx, instance| async {
+                let result = handlers::prom::handle_labels(ctx, instance, params).await;
+                Ok::<_, warp::Rejection>(prom_reply(result))
+            })
+    }
+
+    fn prom_label_values(
+        &self,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("api" / "v1" / "label" / String / "values")
+            .and(prom_params())
+            .and(self.with_context())
+            .and(self.with_instance())
+            .and_then(|name, params, ctx, instance| async {
+                let result = handlers::prom::handle_label_values(ctx, instance, name, params).await;
+                Ok::<_, warp::Rejection>(prom_reply(result))
+            })
+    }
+
+    fn prom_series(
+        &self,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("api" / "v1" / "series")
+            .and(prom_params())
+            .and(self.with_context())
+            .and(self.with_instance())
+            .and_then(|params, ctx, instance| async {
+                let result = handlers::prom::handle_series(ctx, instance, params).await;
+                Ok::<_, warp::Rejection>(prom_reply(result))
+            })
+    }
+
+    fn prom_remote_write(
+        &self,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("prom" / "v1" / "write")
+            .and(warp::post())
+            .and(warp::body::content_length_limit(MAX_PROM_REMOTE_BODY_SIZE))
+            .and(warp::body::bytes())
+            .and(self.with_context())
+            .and(self.with_instance())
+            .and(self.with_meta_client())
+            .and_then(|body, ctx, instance, meta_client| async move {
+                let result =
+                    handlers::prom_remote::handle_remote_write(ctx, instance, meta_client, &body)
+                        .await
+                        .map(|_| StatusCode::NO_CONTENT);
+                Ok::<_, warp::Rejection>(prom_remote_reply(result))
+            })
+    }
+
+    fn prom_remote_read(
+        &self,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("prom" / "v1" / "read")
+            .and(warp::post())
+            .and(warp::body::content_length_limit(MAX_PROM_REMOTE_BODY_SIZE))
+            .and(warp::body::bytes())
+            .and(self.with_context())
+            .and(self.with_instance())
+            .and_then(|body, ctx, instance| async move {
+                let result = handlers::prom_remote::handle_remote_read(ctx, instance, &body)
+                    .await
+                    .map(|body| {
+                        let reply =
+                            reply::with_header(body, "Content-Type", "application/x-protobuf");
+                        reply::with_header(reply, "Content-Encoding", "snappy")
+                    });
+                Ok::<_, warp::Rejection>(prom_remote_reply(result))
+            })
+    }
+
+    fn influxdb_write(
+        &self,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        let v1 = warp::path!("influxdb" / "v1" / "write");
+        let v2 = warp::path!("api" / "v2" / "write");
+        v1.or(v2)
+            .unify()
+            .and(warp::post())
+            .and(warp::query::<InfluxDBWriteParams>())
+            .and(warp::body::content_length_limit(
+                MAX_INFLUXDB_WRITE_BODY_SIZE,
+            ))
+            .and(warp::body::bytes())
+            .and(self.with_context())
+            .and(self.with_instance())
+            .and(self.with_meta_client())
+            .and_then(|params, body, ctx, instance, meta_client| async move {
+                let result =
+                    handlers::influxdb::handle_write(ctx, instance, meta_client, params, &body)
+                        .await;
+                let reply = match result {
+                    Ok(()) => StatusCode::NO_CONTENT.into_response(),
+                    Err(e) => {
+                        error!("Http service failed to handle influxdb write, err:{}", e);
+                        let (code, resp) = handlers::influxdb::convert_error(&e);
+                        reply::with_status(reply::json(&resp), code).into_response()
+                    }
+                };
+                Ok::<_, warp::Rejection>(reply)
             })
     }
 
@@ -209,6 +392,14 @@ impl<C: CatalogManager + 'static, Q: QueryExecutor + 'static> Service<C, Q> {
         warp::any().map(move || instance.clone())
     }
 
+    fn with_meta_client(
+        &self,
+    ) -> impl Filter<Extract = (Arc<dyn MetaClient + Send + Sync>,), Error = Infallible> + Clone
+    {
+        let meta_client = self.meta_client.clone();
+        warp::any().map(move || meta_client.clone())
+    }
+
     fn admin_reject(
         &self,
     ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
@@ -232,6 +423,78 @@ impl<C: CatalogManager + 'static, Q: QueryExecutor + 'static> Service<C, Q> {
                 }
             })
     }
+
+    fn admin_quota(
+        &self,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("admin" / "quota")
+            .and(warp::post())
+            .and(warp::body::json())
+            .and(self.with_context())
+            .and(self.with_instance())
+            .and_then(|req, ctx, instance| async {
+                let result = handlers::admin::handle_quota(ctx, instance, req)
+                    .await
+                    .map_err(|e| {
+                        error!("Http service failed to handle admin quota, err:{}", e);
+                        e
+                    })
+                    .context(HandleRequest);
+
+                match result {
+                    Ok(res) => Ok(reply::json(&res)),
+                    Err(e) => Err(reject::custom(e)),
+                }
+            })
+    }
+
+    fn admin_snapshot(
+        &self,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("admin" / "snapshot")
+            .and(warp::post())
+            .and(warp::body::json())
+            .and(self.with_context())
+            .and(self.with_instance())
+            .and_then(|req, ctx, instance| async {
+                let result = handlers::admin::handle_snapshot(ctx, instance, req)
+                    .await
+                    .map_err(|e| {
+                        error!("Http service failed to handle admin snapshot, err:{}", e);
+                        e
+                    })
+                    .context(HandleRequest);
+
+                match result {
+                    Ok(res) => Ok(reply::json(&res)),
+                    Err(e) => Err(reject::custom(e)),
+                }
+            })
+    }
+
+    fn admin_restore(
+        &self,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("admin" / "restore")
+            .and(warp::post())
+            .and(warp::body::json())
+            .and(self.with_context())
+            .and(self.with_instance())
+            .and_then(|req, ctx, instance| async {
+                let result = handlers::admin::handle_restore(ctx, instance, req)
+                    .await
+                    .map_err(|e| {
+                        error!("Http service failed to handle admin restore, err:{}", e);
+                        e
+                    })
+                    .context(HandleRequest);
+
+                match result {
+                    Ok(res) => Ok(reply::json(&res)),
+                    Err(e) => Err(reject::custom(e)),
+                }
+            })
+    }
 }
 
 /// Service builder
@@ -239,6 +502,7 @@ pub struct Builder<C, Q> {
     config: Config,
     runtimes: Option<Arc<EngineRuntimes>>,
     instance: Option<InstanceRef<C, Q>>,
+    meta_client: Option<Arc<dyn MetaClient + Send + Sync>>,
 }
 
 impl<C, Q> Builder<C, Q> {
@@ -247,6 +511,7 @@ impl<C, Q> Builder<C, Q> {
             config,
             runtimes: None,
             instance: None,
+            meta_client: None,
         }
     }
 
@@ -259,6 +524,11 @@ impl<C, Q> Builder<C, Q> {
         self.instance = Some(instance);
         self
     }
+
+    pub fn meta_client(mut self, meta_client: Arc<dyn MetaClient + Send + Sync>) -> Self {
+        self.meta_client = Some(meta_client);
+        self
+    }
 }
 
 impl<C: CatalogManager + 'static, Q: QueryExecutor + 'static> Builder<C, Q> {
@@ -266,11 +536,13 @@ impl<C: CatalogManager + 'static, Q: QueryExecutor + 'static> Builder<C, Q> {
     pub fn build(self) -> Result<Service<C, Q>> {
         let runtimes = self.runtimes.context(MissingRuntimes)?;
         let instance = self.instance.context(MissingInstance)?;
+        let meta_client = self.meta_client.context(MissingMetaClient)?;
         let (tx, rx) = oneshot::channel();
 
         let service = Service {
             runtimes: runtimes.clone(),
             instance,
+            meta_client,
             profiler: Arc::new(Profiler::default()),
             tx,
         };
@@ -294,6 +566,52 @@ impl<C: CatalogManager + 'static, Q: QueryExecutor + 'static> Builder<C, Q> {
     }
 }
 
+/// Params of prometheus api, from the query string of GET request or the form
+/// body of POST request.
+fn prom_params() -> impl Filter<Extract = (PromParams,), Error = warp::Rejection> + Clone {
+    warp::get()
+        .and(warp::query::<PromParams>())
+        .or(warp::post().and(warp::body::form::<PromParams>()))
+        .unify()
+}
+
+/// Reply of prometheus api, errors are also replied in the format of
+/// prometheus instead of rejected.
+fn prom_reply(
+    result: std::result::Result<handlers::prom::Response, handlers::error::Error>,
+) -> reply::WithStatus<reply::Json> {
+    match result {
+        Ok(resp) => reply::with_status(reply::json(&resp), StatusCode::OK),
+        Err(e) => {
+            error!(
+                "Http service failed to handle prometheus request, err:{}",
+                e
+            );
+            let (code, resp) = handlers::prom::convert_error(&e);
+            reply::with_status(reply::json(&resp), code)
+        }
+    }
+}
+
+/// Reply of prometheus remote storage api, errors are replied as plain text.
+fn prom_remote_reply<T: Reply>(
+    result: std::result::Result<T, handlers::error::Error>,
+) -> reply::Response {
+    match result {
+        Ok(v) => v.into_response(),
+        Err(e) => {
+            error!(
+                "Http service failed to handle prometheus remote request, err:{}",
+                e
+            );
+            let code = handlers::prom_remote::error_to_status_code(&e);
+            let err_string = e.to_string();
+            let message = error::first_line_in_error(&err_string).to_string();
+            reply::with_status(message, code).into_response()
+        }
+    }
+}
+
 #[derive(Debug, Serialize)]
 struct ErrorResponse {
     code: u16,
@@ -303,10 +621,14 @@ struct ErrorResponse {
 fn error_to_status_code(err: &Error) -> StatusCode {
     match err {
         Error::CreateContext { .. } => StatusCode::BAD_REQUEST,
+        Error::HandleRequest {
+            source: handlers::error::Error::QuotaExceeded { .. },
+        } => StatusCode::TOO_MANY_REQUESTS,
         // TODO(yingwen): Map handle request error to more accurate status code
         Error::HandleRequest { .. }
         | Error::MissingRuntimes { .. }
         | Error::MissingInstance { .. }
+        | Error::MissingMetaClient { .. }
         | Error::ParseIpAddr { .. }
         | Error::ProfileHeap { .. }
         | Error::JoinAsyncTask { .. } => StatusCode::INTERNAL_SERVER_ERROR,
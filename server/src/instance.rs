@@ -2,8 +2,10 @@
 
 //! Instance contains shared states of service
 
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
+use interpreters::registry::QueryRegistryRef;
+use object_store::ObjectStoreRef;
 use table_engine::engine::TableEngineRef;
 use udf::registry::FunctionRegistryRef;
 
@@ -20,6 +22,12 @@ pub struct Instance<C, Q> {
     // User defined functions registry.
     pub function_registry: FunctionRegistryRef,
     pub limiter: Limiter,
+    /// Store to snapshot the tables to.
+    pub backup_store: Option<ObjectStoreRef>,
+    /// Registry of the running queries.
+    pub query_registry: QueryRegistryRef,
+    /// Queries running longer than the timeout are cancelled.
+    pub query_timeout: Option<Duration>,
 }
 
 /// A reference counted instance pointer
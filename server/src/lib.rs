@@ -13,13 +13,17 @@ pub mod config;
 mod consts;
 mod context;
 mod error;
+mod flight;
 mod grpc;
 mod handlers;
 mod http;
 mod instance;
+mod line_protocol;
 pub mod limiter;
 pub mod logger;
 mod metrics;
+mod mysql;
+mod postgresql;
 mod router;
 pub mod server;
 pub mod table_engine;
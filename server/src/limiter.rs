@@ -1,25 +1,326 @@
 // Copyright 2022 CeresDB Project Authors. Licensed under Apache-2.0.
 
-use std::{collections::HashSet, sync::RwLock};
+//! Limiter of the requests, includes the reject lists of tables and the
+//! quotas of tenants.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, RwLock,
+    },
+    time::Instant,
+};
 
 use arrow_deps::datafusion::catalog::TableReference;
-use sql::plan::Plan;
+use common_util::define_result;
+use serde_derive::{Deserialize, Serialize};
+use snafu::{ensure, Backtrace, Snafu};
+use sql::{container::TableContainer, plan::Plan};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display(
+        "Write rows per second exceeds the quota, tenant:{}, quota:{}.\nBacktrace:\n{}",
+        tenant,
+        quota,
+        backtrace
+    ))]
+    WriteRowsExceeded {
+        tenant: String,
+        quota: u64,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "Write bytes per second exceeds the quota, tenant:{}, quota:{}.\nBacktrace:\n{}",
+        tenant,
+        quota,
+        backtrace
+    ))]
+    WriteBytesExceeded {
+        tenant: String,
+        quota: u64,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "Concurrent queries exceed the quota, tenant:{}, quota:{}.\nBacktrace:\n{}",
+        tenant,
+        quota,
+        backtrace
+    ))]
+    TooManyQueries {
+        tenant: String,
+        quota: usize,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "Read rows per second exceeds the quota, tenant:{}, quota:{}.\nBacktrace:\n{}",
+        tenant,
+        quota,
+        backtrace
+    ))]
+    ReadRowsExceeded {
+        tenant: String,
+        quota: u64,
+        backtrace: Backtrace,
+    },
+}
+
+define_result!(Error);
+
+/// Quota of a tenant, the unset items are unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct QuotaConfig {
+    /// Max rows written per second.
+    pub write_rows_per_sec: Option<u64>,
+    /// Max bytes of the write requests per second.
+    pub write_bytes_per_sec: Option<u64>,
+    /// Max queries executed at the same time.
+    pub max_concurrent_queries: Option<usize>,
+    /// Max rows read by the queries per second.
+    pub read_rows_per_sec: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct LimiterConfig {
+    /// Quota of the tenants not in `tenant_quotas`.
+    pub default_quota: QuotaConfig,
+    /// Quotas of the tenants, keyed by the tenant name.
+    pub tenant_quotas: HashMap<String, QuotaConfig>,
+}
+
+/// Token bucket refilled by `rate` tokens per second, holding at most the
+/// tokens of one second.
+///
+/// Requests are admitted as long as the bucket is not empty, and the bucket
+/// may run into debt so a large request won't be rejected forever. The debt is
+/// paid back by the following refills.
+#[derive(Debug)]
+struct TokenBucket {
+    rate: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: u64, now: Instant) -> Self {
+        Self {
+            rate: rate as f64,
+            tokens: rate as f64,
+            last_refill: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * self.rate).min(self.rate);
+        self.last_refill = now;
+    }
+
+    fn is_empty(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        self.tokens <= 0.0
+    }
+
+    fn consume(&mut self, tokens: usize, now: Instant) {
+        self.refill(now);
+        self.tokens -= tokens as f64;
+    }
+}
+
+/// Quota and token buckets of a tenant.
+#[derive(Debug)]
+struct QuotaState {
+    quota: QuotaConfig,
+    write_rows: Option<TokenBucket>,
+    write_bytes: Option<TokenBucket>,
+    read_rows: Option<TokenBucket>,
+}
+
+impl QuotaState {
+    fn new(quota: QuotaConfig, now: Instant) -> Self {
+        Self {
+            quota,
+            write_rows: quota.write_rows_per_sec.map(|v| TokenBucket::new(v, now)),
+            write_bytes: quota.write_bytes_per_sec.map(|v| TokenBucket::new(v, now)),
+            read_rows: quota.read_rows_per_sec.map(|v| TokenBucket::new(v, now)),
+        }
+    }
+}
+
+/// Limiter of a tenant.
+#[derive(Debug)]
+struct TenantLimiter {
+    tenant: String,
+    state: Mutex<QuotaState>,
+    running_queries: AtomicUsize,
+}
+
+impl TenantLimiter {
+    fn new(tenant: String, quota: QuotaConfig) -> Self {
+        Self {
+            tenant,
+            state: Mutex::new(QuotaState::new(quota, Instant::now())),
+            running_queries: AtomicUsize::new(0),
+        }
+    }
+
+    fn set_quota(&self, quota: QuotaConfig) {
+        let mut state = self.state.lock().unwrap();
+        if state.quota != quota {
+            *state = QuotaState::new(quota, Instant::now());
+        }
+    }
+
+    fn try_acquire_write(&self, rows: usize, bytes: usize, now: Instant) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let quota = state.quota;
+        // Check all the buckets before consuming any of them.
+        if let Some(bucket) = &mut state.write_rows {
+            ensure!(
+                !bucket.is_empty(now),
+                WriteRowsExceeded {
+                    tenant: &self.tenant,
+                    quota: quota.write_rows_per_sec.unwrap_or_default(),
+                }
+            );
+        }
+        if let Some(bucket) = &mut state.write_bytes {
+            ensure!(
+                !bucket.is_empty(now),
+                WriteBytesExceeded {
+                    tenant: &self.tenant,
+                    quota: quota.write_bytes_per_sec.unwrap_or_default(),
+                }
+            );
+        }
+
+        if let Some(bucket) = &mut state.write_rows {
+            bucket.consume(rows, now);
+        }
+        if let Some(bucket) = &mut state.write_bytes {
+            bucket.consume(bytes, now);
+        }
+
+        Ok(())
+    }
+
+    fn try_begin_query(
+        self: &Arc<Self>,
+        tables: Arc<TableContainer>,
+        now: Instant,
+    ) -> Result<QueryPermit> {
+        let max_concurrent_queries = {
+            let mut state = self.state.lock().unwrap();
+            let quota = state.quota;
+            if let Some(bucket) = &mut state.read_rows {
+                ensure!(
+                    !bucket.is_empty(now),
+                    ReadRowsExceeded {
+                        tenant: &self.tenant,
+                        quota: quota.read_rows_per_sec.unwrap_or_default(),
+                    }
+                );
+            }
+            quota.max_concurrent_queries
+        };
+
+        let running_queries = self.running_queries.fetch_add(1, Ordering::Relaxed);
+        // The permit releases the query on drop, even if it is rejected.
+        let permit = QueryPermit {
+            limiter: self.clone(),
+            tables,
+        };
+        if let Some(quota) = max_concurrent_queries {
+            ensure!(
+                running_queries < quota,
+                TooManyQueries {
+                    tenant: &self.tenant,
+                    quota,
+                }
+            );
+        }
+
+        Ok(permit)
+    }
+
+    fn consume_read_rows(&self, rows: usize, now: Instant) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(bucket) = &mut state.read_rows {
+            bucket.consume(rows, now);
+        }
+    }
+}
+
+/// Permit of a running query, the query is finished when the permit is
+/// dropped.
+///
+/// The rows scanned from the tables of the query are charged to the quota of
+/// the tenant on drop, so the permit should be held until the output of the
+/// query is consumed.
+pub struct QueryPermit {
+    limiter: Arc<TenantLimiter>,
+    tables: Arc<TableContainer>,
+}
+
+impl fmt::Debug for QueryPermit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QueryPermit")
+            .field("tenant", &self.limiter.tenant)
+            .field("scanned_rows", &self.scanned_rows())
+            .finish()
+    }
+}
+
+impl QueryPermit {
+    /// Returns the rows scanned from the tables of the query so far.
+    pub fn scanned_rows(&self) -> usize {
+        let mut rows = 0;
+        let _ = self.tables.visit::<_, ()>(|_, table| {
+            rows += table.scanned_rows();
+            Ok(())
+        });
+        rows
+    }
+}
+
+impl Drop for QueryPermit {
+    fn drop(&mut self) {
+        self.limiter
+            .consume_read_rows(self.scanned_rows(), Instant::now());
+        self.limiter.running_queries.fetch_sub(1, Ordering::Relaxed);
+    }
+}
 
 pub struct Limiter {
     write_reject_list: RwLock<HashSet<String>>,
     read_reject_list: RwLock<HashSet<String>>,
+    config: RwLock<LimiterConfig>,
+    tenant_limiters: RwLock<HashMap<String, Arc<TenantLimiter>>>,
 }
 
 impl Default for Limiter {
     fn default() -> Self {
+        Self::new(LimiterConfig::default())
+    }
+}
+
+impl Limiter {
+    pub fn new(config: LimiterConfig) -> Self {
         Self {
             write_reject_list: RwLock::new(HashSet::new()),
             read_reject_list: RwLock::new(HashSet::new()),
+            config: RwLock::new(config),
+            tenant_limiters: RwLock::new(HashMap::new()),
         }
     }
-}
 
-impl Limiter {
     pub fn should_limit(&self, plan: &Plan) -> bool {
         match plan {
             Plan::Query(query) => {
@@ -40,6 +341,11 @@ impl Limiter {
                 .read()
                 .unwrap()
                 .contains(insert.table.name()),
+            Plan::Delete(delete) => self
+                .write_reject_list
+                .read()
+                .unwrap()
+                .contains(delete.table.name()),
             _ => false,
         }
     }
@@ -87,14 +393,102 @@ impl Limiter {
             read_reject_list.remove(&value);
         }
     }
+
+    /// Admit a write request of the `tenant` with `rows` rows and `bytes`
+    /// bytes by its quota.
+    pub fn try_acquire_write(&self, tenant: &str, rows: usize, bytes: usize) -> Result<()> {
+        self.tenant_limiter(tenant)
+            .try_acquire_write(rows, bytes, Instant::now())
+    }
+
+    /// Admit the `plan` of the `tenant` by its quota, only the queries are
+    /// limited so None is returned for other plans.
+    ///
+    /// The returned permit should be held until the output of the query is
+    /// consumed.
+    pub fn try_begin_query(&self, tenant: &str, plan: &Plan) -> Result<Option<QueryPermit>> {
+        match plan {
+            Plan::Query(query) => self
+                .tenant_limiter(tenant)
+                .try_begin_query(query.tables.clone(), Instant::now())
+                .map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Set the quota of the `tenant`, or the default quota if `tenant` is
+    /// None.
+    pub fn set_quota(&self, tenant: Option<&str>, quota: QuotaConfig) {
+        let mut config = self.config.write().unwrap();
+        match tenant {
+            Some(tenant) => {
+                config.tenant_quotas.insert(tenant.to_string(), quota);
+            }
+            None => config.default_quota = quota,
+        }
+        self.apply_config(&config);
+    }
+
+    /// Remove the quota of the `tenant` so the default quota is used, or reset
+    /// the default quota to unlimited if `tenant` is None.
+    pub fn remove_quota(&self, tenant: Option<&str>) {
+        let mut config = self.config.write().unwrap();
+        match tenant {
+            Some(tenant) => {
+                config.tenant_quotas.remove(tenant);
+            }
+            None => config.default_quota = QuotaConfig::default(),
+        }
+        self.apply_config(&config);
+    }
+
+    pub fn get_quota_config(&self) -> LimiterConfig {
+        self.config.read().unwrap().clone()
+    }
+
+    fn apply_config(&self, config: &LimiterConfig) {
+        let tenant_limiters = self.tenant_limiters.read().unwrap();
+        for (tenant, limiter) in tenant_limiters.iter() {
+            let quota = config
+                .tenant_quotas
+                .get(tenant)
+                .unwrap_or(&config.default_quota);
+            limiter.set_quota(*quota);
+        }
+    }
+
+    fn tenant_limiter(&self, tenant: &str) -> Arc<TenantLimiter> {
+        if let Some(limiter) = self.tenant_limiters.read().unwrap().get(tenant) {
+            return limiter.clone();
+        }
+
+        // Hold the config lock so the quota won't be changed before the limiter is
+        // inserted.
+        let config = self.config.read().unwrap();
+        let quota = config
+            .tenant_quotas
+            .get(tenant)
+            .unwrap_or(&config.default_quota);
+        self.tenant_limiters
+            .write()
+            .unwrap()
+            .entry(tenant.to_string())
+            .or_insert_with(|| Arc::new(TenantLimiter::new(tenant.to_string(), *quota)))
+            .clone()
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::{
+        collections::HashMap,
+        time::{Duration, Instant},
+    };
+
     use common_types::request_id::RequestId;
     use sql::{parser::Parser, plan::Plan, planner::Planner, tests::MockMetaProvider};
 
-    use crate::limiter::Limiter;
+    use crate::limiter::{Error, Limiter, LimiterConfig, QuotaConfig, TokenBucket};
 
     fn sql_to_plan(meta_provider: &MockMetaProvider, sql: &str) -> Plan {
         let planner = Planner::new(meta_provider, RequestId::next_id(), 1);
@@ -191,4 +585,103 @@ mod tests {
         assert!(limiter.should_limit(&query_plan2));
         assert!(limiter.should_limit(&insert_plan2));
     }
+
+    #[test]
+    fn test_token_bucket() {
+        let now = Instant::now();
+        let mut bucket = TokenBucket::new(10, now);
+        assert!(!bucket.is_empty(now));
+
+        // The bucket runs into debt.
+        bucket.consume(15, now);
+        assert!(bucket.is_empty(now));
+        assert!(bucket.is_empty(now + Duration::from_millis(400)));
+        assert!(!bucket.is_empty(now + Duration::from_millis(600)));
+
+        // At most the tokens of one second are held.
+        let now = now + Duration::from_secs(10);
+        bucket.consume(10, now);
+        assert!(bucket.is_empty(now));
+    }
+
+    #[test]
+    fn test_write_quota() {
+        let limiter = Limiter::new(LimiterConfig {
+            default_quota: QuotaConfig {
+                write_rows_per_sec: Some(10),
+                ..Default::default()
+            },
+            tenant_quotas: HashMap::from([("tenant2".to_string(), QuotaConfig::default())]),
+        });
+
+        // The first request is admitted even if it exceeds the quota.
+        assert!(limiter.try_acquire_write("tenant1", 20, 100).is_ok());
+        assert!(matches!(
+            limiter.try_acquire_write("tenant1", 1, 1),
+            Err(Error::WriteRowsExceeded { .. })
+        ));
+        for _ in 0..2 {
+            assert!(limiter.try_acquire_write("tenant2", 100, 100).is_ok());
+        }
+
+        // Adjust the quotas at runtime.
+        limiter.remove_quota(None);
+        assert!(limiter.try_acquire_write("tenant1", 1, 1).is_ok());
+        let quota = QuotaConfig {
+            write_bytes_per_sec: Some(10),
+            ..Default::default()
+        };
+        limiter.set_quota(Some("tenant2"), quota);
+        assert!(limiter.try_acquire_write("tenant2", 1, 20).is_ok());
+        assert!(matches!(
+            limiter.try_acquire_write("tenant2", 1, 1),
+            Err(Error::WriteBytesExceeded { .. })
+        ));
+
+        let config = limiter.get_quota_config();
+        assert_eq!(QuotaConfig::default(), config.default_quota);
+        assert_eq!(Some(&quota), config.tenant_quotas.get("tenant2"));
+    }
+
+    #[test]
+    fn test_query_quota() {
+        let mock = MockMetaProvider::default();
+        let query_plan = sql_to_plan(&mock, "select * from test_table");
+        let insert="INSERT INTO test_table(key1, key2, field1,field2) VALUES('tagk', 1638428434000,100, 'hello3')";
+        let insert_plan = sql_to_plan(&mock, insert);
+
+        let limiter = Limiter::default();
+        let quota = QuotaConfig {
+            max_concurrent_queries: Some(1),
+            read_rows_per_sec: Some(10),
+            ..Default::default()
+        };
+        limiter.set_quota(Some("tenant1"), quota);
+
+        let permit = limiter
+            .try_begin_query("tenant1", &query_plan)
+            .unwrap()
+            .unwrap();
+        assert_eq!(0, permit.scanned_rows());
+        assert!(matches!(
+            limiter.try_begin_query("tenant1", &query_plan),
+            Err(Error::TooManyQueries { .. })
+        ));
+        assert!(limiter.try_begin_query("tenant2", &query_plan).is_ok());
+        // Only the queries are limited.
+        assert!(limiter
+            .try_begin_query("tenant1", &insert_plan)
+            .unwrap()
+            .is_none());
+        drop(permit);
+
+        // The read rows exceed the quota.
+        limiter
+            .tenant_limiter("tenant1")
+            .consume_read_rows(20, Instant::now());
+        assert!(matches!(
+            limiter.try_begin_query("tenant1", &query_plan),
+            Err(Error::ReadRowsExceeded { .. })
+        ));
+    }
 }
@@ -0,0 +1,405 @@
+// Copyright 2022 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Parser of the InfluxDB line protocol
+//!
+//! A line is in the format of:
+//! `measurement[,tag_key=tag_value...] field_key=field_value[,...] [timestamp]`
+//!
+//! See https://docs.influxdata.com/influxdb/v1.8/write_protocols/line_protocol_reference/
+
+use common_util::define_result;
+use snafu::{Backtrace, Snafu};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display(
+        "Invalid line protocol, line:{}, msg:{}.\nBacktrace:\n{}",
+        line_num,
+        msg,
+        backtrace
+    ))]
+    InvalidLine {
+        line_num: usize,
+        msg: String,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "Invalid precision, precision:{}.\nBacktrace:\n{}",
+        precision,
+        backtrace
+    ))]
+    InvalidPrecision {
+        precision: String,
+        backtrace: Backtrace,
+    },
+}
+
+define_result!(Error);
+
+/// Precision of the timestamp in lines
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Precision {
+    Nanosecond,
+    Microsecond,
+    Millisecond,
+    Second,
+    Minute,
+    Hour,
+}
+
+impl Default for Precision {
+    fn default() -> Self {
+        Precision::Nanosecond
+    }
+}
+
+impl Precision {
+    /// Parse the precision param, both the abbreviations of v1 api (`n`, `u`,
+    /// `ms`, `s`, `m`, `h`) and v2 api (`ns`, `us`, `ms`, `s`) are accepted.
+    pub fn parse(precision: &str) -> Result<Self> {
+        let precision = match precision {
+            "n" | "ns" => Precision::Nanosecond,
+            "u" | "us" | "µ" | "µs" => Precision::Microsecond,
+            "ms" => Precision::Millisecond,
+            "s" => Precision::Second,
+            "m" => Precision::Minute,
+            "h" => Precision::Hour,
+            _ => return InvalidPrecision { precision }.fail(),
+        };
+
+        Ok(precision)
+    }
+
+    /// Convert the timestamp in this precision to milliseconds.
+    pub fn to_millis(self, timestamp: i64) -> i64 {
+        match self {
+            Precision::Nanosecond => timestamp / 1_000_000,
+            Precision::Microsecond => timestamp / 1_000,
+            Precision::Millisecond => timestamp,
+            Precision::Second => timestamp.saturating_mul(1_000),
+            Precision::Minute => timestamp.saturating_mul(60_000),
+            Precision::Hour => timestamp.saturating_mul(3_600_000),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    Float(f64),
+    Integer(i64),
+    UInteger(u64),
+    String(String),
+    Boolean(bool),
+}
+
+/// A parsed line, the timestamp is in the precision of the request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    pub measurement: String,
+    pub tags: Vec<(String, String)>,
+    pub fields: Vec<(String, FieldValue)>,
+    pub timestamp: Option<i64>,
+}
+
+/// Parse all lines of the input, empty lines and comments are skipped.
+pub fn parse_lines(input: &str) -> Result<Vec<Line>> {
+    let mut lines = Vec::new();
+    for (idx, line) in input.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut parser = LineParser {
+            buf: line.as_bytes(),
+            pos: 0,
+        };
+        match parser.parse_line() {
+            Ok(line) => lines.push(line),
+            Err(msg) => {
+                return InvalidLine {
+                    line_num: idx + 1,
+                    msg,
+                }
+                .fail()
+            }
+        }
+    }
+
+    Ok(lines)
+}
+
+struct LineParser<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> LineParser<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.buf.get(self.pos).copied()
+    }
+
+    fn consume(&mut self, expected: u8) -> bool {
+        if self.peek() == Some(expected) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, expected: u8, what: &str) -> std::result::Result<(), String> {
+        if self.consume(expected) {
+            Ok(())
+        } else {
+            Err(format!("expect {} at position {}", what, self.pos))
+        }
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.consume(b' ') {}
+    }
+
+    /// Read until one of the `stops`, backslash escaped stop chars are
+    /// unescaped.
+    fn read_escaped(&mut self, stops: &[u8]) -> std::result::Result<String, String> {
+        let mut value = Vec::new();
+        while let Some(c) = self.peek() {
+            if stops.contains(&c) {
+                break;
+            }
+            self.pos += 1;
+            match (c, self.peek()) {
+                (b'\\', Some(next)) if next == b'\\' || stops.contains(&next) => {
+                    value.push(next);
+                    self.pos += 1;
+                }
+                _ => value.push(c),
+            }
+        }
+
+        String::from_utf8(value).map_err(|e| format!("invalid utf8, err:{}", e))
+    }
+
+    /// Read a non-empty key or name.
+    fn read_name(&mut self, stops: &[u8], what: &str) -> std::result::Result<String, String> {
+        let name = self.read_escaped(stops)?;
+        if name.is_empty() {
+            return Err(format!("missing {} at position {}", what, self.pos));
+        }
+
+        Ok(name)
+    }
+
+    fn parse_line(&mut self) -> std::result::Result<Line, String> {
+        let measurement = self.read_name(b", ", "measurement")?;
+
+        let mut tags = Vec::new();
+        while self.consume(b',') {
+            let key = self.read_name(b"=, ", "tag key")?;
+            self.expect(b'=', "'='")?;
+            let value = self.read_name(b", ", "tag value")?;
+            tags.push((key, value));
+        }
+
+        self.expect(b' ', "space")?;
+        self.skip_spaces();
+
+        let mut fields = Vec::new();
+        loop {
+            let key = self.read_name(b"=, ", "field key")?;
+            self.expect(b'=', "'='")?;
+            let value = self.parse_field_value()?;
+            fields.push((key, value));
+
+            if !self.consume(b',') {
+                break;
+            }
+        }
+
+        self.skip_spaces();
+        let timestamp = if self.pos < self.buf.len() {
+            let raw = std::str::from_utf8(&self.buf[self.pos..]).unwrap_or_default();
+            let timestamp = raw
+                .trim()
+                .parse::<i64>()
+                .map_err(|e| format!("invalid timestamp {}, err:{}", raw, e))?;
+            Some(timestamp)
+        } else {
+            None
+        };
+
+        Ok(Line {
+            measurement,
+            tags,
+            fields,
+            timestamp,
+        })
+    }
+
+    fn parse_field_value(&mut self) -> std::result::Result<FieldValue, String> {
+        if self.consume(b'"') {
+            let mut value = Vec::new();
+            loop {
+                match self.peek() {
+                    None => return Err("unterminated string field value".to_string()),
+                    Some(b'"') => {
+                        self.pos += 1;
+                        break;
+                    }
+                    Some(b'\\') if matches!(self.buf.get(self.pos + 1), Some(b'"' | b'\\')) => {
+                        value.push(self.buf[self.pos + 1]);
+                        self.pos += 2;
+                    }
+                    Some(c) => {
+                        value.push(c);
+                        self.pos += 1;
+                    }
+                }
+            }
+            let value = String::from_utf8(value).map_err(|e| format!("invalid utf8, err:{}", e))?;
+            return Ok(FieldValue::String(value));
+        }
+
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if c == b',' || c == b' ' {
+                break;
+            }
+            self.pos += 1;
+        }
+        // Input is a valid utf8 str and the value is split by ascii chars.
+        let raw = std::str::from_utf8(&self.buf[start..self.pos]).unwrap_or_default();
+
+        parse_unquoted_value(raw)
+    }
+}
+
+fn parse_unquoted_value(raw: &str) -> std::result::Result<FieldValue, String> {
+    let invalid = || format!("invalid field value {}", raw);
+
+    if raw.is_empty() {
+        return Err("missing field value".to_string());
+    }
+    let value = match raw {
+        "t" | "T" | "true" | "True" | "TRUE" => FieldValue::Boolean(true),
+        "f" | "F" | "false" | "False" | "FALSE" => FieldValue::Boolean(false),
+        _ => {
+            if let Some(v) = raw.strip_suffix('i') {
+                FieldValue::Integer(v.parse().map_err(|_| invalid())?)
+            } else if let Some(v) = raw.strip_suffix('u') {
+                FieldValue::UInteger(v.parse().map_err(|_| invalid())?)
+            } else {
+                let v: f64 = raw.parse().map_err(|_| invalid())?;
+                if !v.is_finite() {
+                    return Err(invalid());
+                }
+                FieldValue::Float(v)
+            }
+        }
+    };
+
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_line(line: &str) -> Line {
+        let mut lines = parse_lines(line).unwrap();
+        assert_eq!(1, lines.len());
+        lines.remove(0)
+    }
+
+    #[test]
+    fn test_parse_line() {
+        let line =
+            parse_line("cpu,host=server01,region=us-west usage=0.64,count=3i 1434055562000000000");
+        assert_eq!("cpu", line.measurement);
+        assert_eq!(
+            vec![
+                ("host".to_string(), "server01".to_string()),
+                ("region".to_string(), "us-west".to_string())
+            ],
+            line.tags
+        );
+        assert_eq!(
+            vec![
+                ("usage".to_string(), FieldValue::Float(0.64)),
+                ("count".to_string(), FieldValue::Integer(3))
+            ],
+            line.fields
+        );
+        assert_eq!(Some(1434055562000000000), line.timestamp);
+
+        let line = parse_line(r#"disk free=12u,ok=t,msg="a \"b\", c=d""#);
+        assert_eq!("disk", line.measurement);
+        assert!(line.tags.is_empty());
+        assert_eq!(
+            vec![
+                ("free".to_string(), FieldValue::UInteger(12)),
+                ("ok".to_string(), FieldValue::Boolean(true)),
+                (
+                    "msg".to_string(),
+                    FieldValue::String(r#"a "b", c=d"#.to_string())
+                ),
+            ],
+            line.fields
+        );
+        assert_eq!(None, line.timestamp);
+    }
+
+    #[test]
+    fn test_parse_escaped() {
+        let line = parse_line(r#"my\ cpu,host\=name=a\,b\ c value=1 10"#);
+        assert_eq!("my cpu", line.measurement);
+        assert_eq!(
+            vec![("host=name".to_string(), "a,b c".to_string())],
+            line.tags
+        );
+        assert_eq!(Some(10), line.timestamp);
+    }
+
+    #[test]
+    fn test_parse_lines() {
+        let input = "# comment\n\ncpu value=1 1\n  mem value=2 2  \n";
+        let lines = parse_lines(input).unwrap();
+        assert_eq!(2, lines.len());
+        assert_eq!("mem", lines[1].measurement);
+        assert_eq!(Some(2), lines[1].timestamp);
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        let invalid_lines = [
+            "cpu",
+            "cpu value",
+            "cpu value=",
+            "cpu,host value=1",
+            "cpu value=1 abc",
+            "cpu value=abc",
+            "cpu value=1.5i",
+            r#"cpu value="abc"#,
+            ",host=a value=1",
+        ];
+        for line in invalid_lines {
+            assert!(parse_lines(line).is_err(), "line:{}", line);
+        }
+
+        let err = parse_lines("cpu value=1\ncpu value=").unwrap_err();
+        assert!(matches!(err, Error::InvalidLine { line_num: 2, .. }));
+    }
+
+    #[test]
+    fn test_precision() {
+        assert_eq!(Precision::Nanosecond, Precision::parse("n").unwrap());
+        assert_eq!(Precision::Microsecond, Precision::parse("us").unwrap());
+        assert!(Precision::parse("d").is_err());
+
+        assert_eq!(1_000, Precision::Nanosecond.to_millis(1_000_000_000));
+        assert_eq!(1_000, Precision::Millisecond.to_millis(1_000));
+        assert_eq!(60_000, Precision::Minute.to_millis(1));
+    }
+}
@@ -0,0 +1,309 @@
+// Copyright 2022 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Connection of the mysql protocol
+
+use std::{
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use catalog::manager::Manager as CatalogManager;
+use common_types::{record_batch::RecordBatch, schema::RecordSchema};
+use common_util::runtime::Runtime;
+use futures::StreamExt;
+use interpreters::interpreter::Output;
+use log::{error, info};
+use query_engine::executor::Executor as QueryExecutor;
+use snafu::{ResultExt, Snafu};
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use crate::{
+    context::RequestContext,
+    handlers::sql::{execute_sql, Request},
+    instance::InstanceRef,
+    mysql::protocol::{self, HandshakeResponse, PacketIo},
+};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Failed to handle packet, err:{}", source))]
+    Protocol { source: protocol::Error },
+
+    #[snafu(display("Failed to handshake, err:{}", source))]
+    Handshake { source: protocol::Error },
+
+    #[snafu(display("Failed to create request context, err:{}", source))]
+    CreateContext { source: crate::context::Error },
+}
+
+define_result!(Error);
+
+/// Sql state of the errors.
+const SQL_STATE_GENERAL: &str = "HY000";
+const SQL_STATE_UNKNOWN_COM: &str = "08S01";
+
+/// A client connection, the statements are executed by the same pipeline as
+/// the sql handler of the http service.
+pub struct Connection<C, Q, S> {
+    io: PacketIo<S>,
+    instance: InstanceRef<C, Q>,
+    runtime: Arc<Runtime>,
+    connection_id: u32,
+    catalog: String,
+    /// Tenant of the connection, which is the database of mysql.
+    tenant: String,
+}
+
+impl<C, Q, S> Connection<C, Q, S>
+where
+    C: CatalogManager + 'static,
+    Q: QueryExecutor + 'static,
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    pub fn new(
+        stream: S,
+        instance: InstanceRef<C, Q>,
+        runtime: Arc<Runtime>,
+        connection_id: u32,
+        max_allowed_packet: usize,
+    ) -> Self {
+        let catalog = instance.catalog_manager.default_catalog_name().to_string();
+        let tenant = instance.catalog_manager.default_schema_name().to_string();
+
+        Self {
+            io: PacketIo::new(stream, max_allowed_packet),
+            instance,
+            runtime,
+            connection_id,
+            catalog,
+            tenant,
+        }
+    }
+
+    /// Serve the connection until it is closed.
+    pub async fn run(mut self) -> Result<()> {
+        self.handshake().await?;
+
+        loop {
+            self.io.reset_seq();
+            let payload = match self.read_packet().await.context(Protocol)? {
+                Some(payload) => payload,
+                None => return Ok(()),
+            };
+
+            match payload.first() {
+                Some(&protocol::COM_QUIT) => return Ok(()),
+                Some(&protocol::COM_PING) => self.write_ok(0).await?,
+                Some(&protocol::COM_INIT_DB) => {
+                    self.tenant = String::from_utf8_lossy(&payload[1..]).to_string();
+                    self.write_ok(0).await?;
+                }
+                Some(&protocol::COM_QUERY) => {
+                    let query = String::from_utf8_lossy(&payload[1..]).to_string();
+                    self.handle_query(query).await?;
+                }
+                cmd => {
+                    let msg = format!("Unsupported command:{:?}", cmd);
+                    self.write_err(protocol::ER_UNKNOWN_COM, SQL_STATE_UNKNOWN_COM, &msg)
+                        .await?;
+                }
+            }
+
+            self.io.flush().await.context(Protocol)?;
+        }
+    }
+
+    async fn handshake(&mut self) -> Result<()> {
+        let salt = new_salt(self.connection_id);
+        self.io
+            .write_packet(&protocol::handshake_packet(self.connection_id, &salt))
+            .await
+            .context(Handshake)?;
+        self.io.flush().await.context(Handshake)?;
+
+        let payload = self
+            .read_packet()
+            .await
+            .context(Handshake)?
+            .unwrap_or_default();
+        // TODO: Authenticate the user once the access control is supported.
+        let response = match HandshakeResponse::decode(&payload) {
+            Ok(response) => response,
+            Err(e) => {
+                let msg = e.to_string();
+                self.write_err(protocol::ER_UNKNOWN, SQL_STATE_GENERAL, &msg)
+                    .await?;
+                self.io.flush().await.context(Protocol)?;
+                return Err(e).context(Handshake);
+            }
+        };
+        if let Some(database) = response.database {
+            self.tenant = database;
+        }
+
+        info!(
+            "Mysql service accept connection, connection_id:{}, user:{}, tenant:{}",
+            self.connection_id, response.username, self.tenant
+        );
+
+        self.write_ok(0).await?;
+        self.io.flush().await.context(Handshake)
+    }
+
+    async fn handle_query(&mut self, query: String) -> Result<()> {
+        let ctx = RequestContext::builder()
+            .catalog(self.catalog.clone())
+            .tenant(self.tenant.clone())
+            .runtime(self.runtime.clone())
+            .build()
+            .context(CreateContext)?;
+        let request = Request::new(query);
+
+        match execute_sql(ctx, &self.instance, &request, true).await {
+            Ok(output) => self.write_output(output).await,
+            Err(e) => {
+                error!(
+                    "Mysql service failed to execute sql, connection_id:{}, err:{}",
+                    self.connection_id, e
+                );
+                self.write_err(protocol::ER_UNKNOWN, SQL_STATE_GENERAL, &e.to_string())
+                    .await
+            }
+        }
+    }
+
+    /// Write the output as a text resultset.
+    async fn write_output(&mut self, output: Output) -> Result<()> {
+        match output {
+            Output::AffectedRows(n) => self.write_ok(n as u64).await,
+            Output::Records(records) => {
+                let schema = match records.first() {
+                    Some(record_batch) => record_batch.schema().clone(),
+                    // The resultset requires the columns, returns an ok packet instead.
+                    None => return self.write_ok(0).await,
+                };
+
+                self.write_columns(&schema).await?;
+                for record_batch in &records {
+                    self.write_rows(record_batch).await?;
+                }
+                self.write_eof().await
+            }
+            Output::Stream(mut stream) => {
+                self.write_columns(stream.schema()).await?;
+                while let Some(record_batch) = stream.next().await {
+                    match record_batch {
+                        Ok(record_batch) => self.write_rows(&record_batch).await?,
+                        Err(e) => {
+                            // The error packet terminates the resultset.
+                            error!(
+                                "Mysql service failed to poll stream, connection_id:{}, err:{}",
+                                self.connection_id, e
+                            );
+                            let msg = e.to_string();
+                            return self
+                                .write_err(protocol::ER_UNKNOWN, SQL_STATE_GENERAL, &msg)
+                                .await;
+                        }
+                    }
+                }
+                self.write_eof().await
+            }
+        }
+    }
+
+    async fn write_columns(&mut self, schema: &RecordSchema) -> Result<()> {
+        self.io
+            .write_packet(&protocol::column_count_packet(schema.num_columns()))
+            .await
+            .context(Protocol)?;
+        for column in schema.columns() {
+            let packet = protocol::column_definition_packet(
+                &column.name,
+                column.data_type,
+                column.is_nullable,
+            );
+            self.io.write_packet(&packet).await.context(Protocol)?;
+        }
+
+        self.write_eof().await
+    }
+
+    async fn write_rows(&mut self, record_batch: &RecordBatch) -> Result<()> {
+        let mut buf = Vec::new();
+        for row_idx in 0..record_batch.num_rows() {
+            buf.clear();
+            for col_idx in 0..record_batch.num_columns() {
+                let datum = record_batch.column(col_idx).datum(row_idx);
+                protocol::write_text_value(&mut buf, &datum);
+            }
+            self.io.write_packet(&buf).await.context(Protocol)?;
+        }
+
+        Ok(())
+    }
+
+    async fn write_ok(&mut self, affected_rows: u64) -> Result<()> {
+        self.io
+            .write_packet(&protocol::ok_packet(affected_rows))
+            .await
+            .context(Protocol)
+    }
+
+    async fn write_eof(&mut self) -> Result<()> {
+        self.io
+            .write_packet(&protocol::eof_packet())
+            .await
+            .context(Protocol)
+    }
+
+    /// Read the payload of the next packet. If the payload is too large, the
+    /// client is told the reason, and the connection should be closed since
+    /// the rest of the payload is not consumed.
+    async fn read_packet(&mut self) -> protocol::Result<Option<Vec<u8>>> {
+        let res = self.io.read_packet().await;
+        if let Err(protocol::Error::PacketTooLarge { .. }) = &res {
+            let msg = "Got a packet bigger than 'max_allowed_packet' bytes";
+            // The connection is going to be closed, ignore the error.
+            let _ = self
+                .write_err(
+                    protocol::ER_NET_PACKET_TOO_LARGE,
+                    SQL_STATE_UNKNOWN_COM,
+                    msg,
+                )
+                .await;
+            let _ = self.io.flush().await;
+        }
+
+        res
+    }
+
+    async fn write_err(&mut self, code: u16, sql_state: &str, msg: &str) -> Result<()> {
+        self.io
+            .write_packet(&protocol::err_packet(code, sql_state, msg))
+            .await
+            .context(Protocol)
+    }
+}
+
+/// Salt of the auth, the password is not checked now so the salt needs not to
+/// be cryptographically random.
+fn new_salt(connection_id: u32) -> [u8; 20] {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|v| v.subsec_nanos())
+        .unwrap_or_default();
+    let mut state = u64::from(nanos) << 32 | u64::from(connection_id) | 1;
+
+    let mut salt = [0; 20];
+    for v in &mut salt {
+        // Xorshift
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        // The salt should be printable and contain no zero.
+        *v = b'!' + (state % 94) as u8;
+    }
+
+    salt
+}
@@ -0,0 +1,178 @@
+// Copyright 2022 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Mysql protocol service
+//!
+//! Supports the handshake, `COM_QUERY`, `COM_PING` and `COM_INIT_DB` of the
+//! text protocol, so the mysql cli and most of the tools can be used to run
+//! sql. The database of mysql is used as the tenant.
+
+mod conn;
+mod protocol;
+
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+
+use catalog::manager::Manager as CatalogManager;
+use log::{error, info, warn};
+use query_engine::executor::Executor as QueryExecutor;
+use snafu::{Backtrace, OptionExt, ResultExt, Snafu};
+use table_engine::engine::EngineRuntimes;
+use tokio::{
+    io::BufStream,
+    net::TcpListener,
+    sync::oneshot::{self, Sender},
+};
+
+use crate::{instance::InstanceRef, mysql::conn::Connection};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Missing runtimes to build service.\nBacktrace:\n{}", backtrace))]
+    MissingRuntimes { backtrace: Backtrace },
+
+    #[snafu(display("Missing instance to build service.\nBacktrace:\n{}", backtrace))]
+    MissingInstance { backtrace: Backtrace },
+
+    #[snafu(display(
+        "Failed to parse ip addr, ip:{}, err:{}.\nBacktrace:\n{}",
+        ip,
+        source,
+        backtrace
+    ))]
+    ParseIpAddr {
+        ip: String,
+        source: std::net::AddrParseError,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "Failed to bind addr, addr:{}, err:{}.\nBacktrace:\n{}",
+        addr,
+        source,
+        backtrace
+    ))]
+    BindAddr {
+        addr: SocketAddr,
+        source: std::io::Error,
+        backtrace: Backtrace,
+    },
+}
+
+define_result!(Error);
+
+#[derive(Debug)]
+pub struct Config {
+    pub ip: String,
+    pub port: u16,
+    /// Max size of the packet sent by the client, the connection is closed
+    /// if exceeded.
+    pub max_allowed_packet: usize,
+}
+
+/// Mysql service
+///
+/// Note that the service does not owns the runtime
+pub struct Service {
+    tx: Sender<()>,
+}
+
+impl Service {
+    pub fn stop(self) {
+        let _ = self.tx.send(());
+    }
+}
+
+/// Service builder
+pub struct Builder<C, Q> {
+    config: Config,
+    runtimes: Option<Arc<EngineRuntimes>>,
+    instance: Option<InstanceRef<C, Q>>,
+}
+
+impl<C, Q> Builder<C, Q> {
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            runtimes: None,
+            instance: None,
+        }
+    }
+
+    pub fn runtimes(mut self, runtimes: Arc<EngineRuntimes>) -> Self {
+        self.runtimes = Some(runtimes);
+        self
+    }
+
+    pub fn instance(mut self, instance: InstanceRef<C, Q>) -> Self {
+        self.instance = Some(instance);
+        self
+    }
+}
+
+impl<C: CatalogManager + 'static, Q: QueryExecutor + 'static> Builder<C, Q> {
+    /// Build and start the service
+    pub fn build(self) -> Result<Service> {
+        let runtimes = self.runtimes.context(MissingRuntimes)?;
+        let instance = self.instance.context(MissingInstance)?;
+        let (tx, mut rx) = oneshot::channel();
+
+        let ip_addr: IpAddr = self
+            .config
+            .ip
+            .parse()
+            .context(ParseIpAddr { ip: self.config.ip })?;
+        let addr = SocketAddr::new(ip_addr, self.config.port);
+        // Bind the addr here so the error is returned to the caller.
+        let listener = std::net::TcpListener::bind(addr).context(BindAddr { addr })?;
+        listener.set_nonblocking(true).context(BindAddr { addr })?;
+
+        let runtime = runtimes.bg_runtime.clone();
+        let max_allowed_packet = self.config.max_allowed_packet;
+        // Run the service
+        runtimes.bg_runtime.spawn(async move {
+            let listener = match TcpListener::from_std(listener) {
+                Ok(listener) => listener,
+                Err(e) => {
+                    error!("Mysql service failed to listen, addr:{}, err:{}", addr, e);
+                    return;
+                }
+            };
+            info!("Mysql service listens on {}", addr);
+
+            let mut connection_id: u32 = 0;
+            loop {
+                let (stream, peer_addr) = tokio::select! {
+                    _ = &mut rx => return,
+                    res = listener.accept() => match res {
+                        Ok(v) => v,
+                        Err(e) => {
+                            error!("Mysql service failed to accept connection, err:{}", e);
+                            continue;
+                        }
+                    },
+                };
+
+                connection_id = connection_id.wrapping_add(1);
+                let conn = Connection::new(
+                    BufStream::new(stream),
+                    instance.clone(),
+                    runtime.clone(),
+                    connection_id,
+                    max_allowed_packet,
+                );
+                runtime.spawn(async move {
+                    if let Err(e) = conn.run().await {
+                        warn!(
+                            "Mysql service connection closed with error, peer_addr:{}, err:{}",
+                            peer_addr, e
+                        );
+                    }
+                });
+            }
+        });
+
+        Ok(Service { tx })
+    }
+}
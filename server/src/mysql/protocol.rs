@@ -0,0 +1,683 @@
+// Copyright 2022 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Packets of the mysql client/server protocol
+//!
+//! Only the subset required by the text protocol is implemented, see
+//! <https://dev.mysql.com/doc/internals/en/client-server-protocol.html>.
+
+use chrono::{Local, TimeZone};
+use common_types::datum::{Datum, DatumKind};
+use snafu::{ensure, Backtrace, ResultExt, Snafu};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Failed to read packet, err:{}", source))]
+    ReadPacket { source: std::io::Error },
+
+    #[snafu(display("Failed to write packet, err:{}", source))]
+    WritePacket { source: std::io::Error },
+
+    #[snafu(display(
+        "Packet out of order, expect:{}, given:{}.\nBacktrace:\n{}",
+        expect,
+        given,
+        backtrace
+    ))]
+    OutOfOrder {
+        expect: u8,
+        given: u8,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("Invalid packet, msg:{}.\nBacktrace:\n{}", msg, backtrace))]
+    InvalidPacket { msg: String, backtrace: Backtrace },
+
+    #[snafu(display(
+        "Packet too large, size:{}, max_allowed_packet:{}.\nBacktrace:\n{}",
+        size,
+        max_allowed_packet,
+        backtrace
+    ))]
+    PacketTooLarge {
+        size: usize,
+        max_allowed_packet: usize,
+        backtrace: Backtrace,
+    },
+}
+
+define_result!(Error);
+
+/// Version reported to the client, some clients refuse to work with a server
+/// older than 5.x.
+pub const SERVER_VERSION: &str = "5.7.0-CeresDB";
+pub const AUTH_PLUGIN_NAME: &str = "mysql_native_password";
+/// utf8_general_ci
+pub const CHARSET_UTF8: u8 = 33;
+/// binary
+const CHARSET_BINARY: u16 = 63;
+
+/// Max payload of a single packet, larger payload is split into several
+/// packets.
+const MAX_PAYLOAD_LEN: usize = 0xff_ff_ff;
+
+// Capability flags.
+pub const CLIENT_LONG_PASSWORD: u32 = 0x0000_0001;
+pub const CLIENT_FOUND_ROWS: u32 = 0x0000_0002;
+pub const CLIENT_LONG_FLAG: u32 = 0x0000_0004;
+pub const CLIENT_CONNECT_WITH_DB: u32 = 0x0000_0008;
+pub const CLIENT_PROTOCOL_41: u32 = 0x0000_0200;
+pub const CLIENT_TRANSACTIONS: u32 = 0x0000_2000;
+pub const CLIENT_SECURE_CONNECTION: u32 = 0x0000_8000;
+pub const CLIENT_PLUGIN_AUTH: u32 = 0x0008_0000;
+pub const CLIENT_PLUGIN_AUTH_LENENC_CLIENT_DATA: u32 = 0x0020_0000;
+
+/// Capabilities supported by the server.
+pub const SERVER_CAPABILITIES: u32 = CLIENT_LONG_PASSWORD
+    | CLIENT_FOUND_ROWS
+    | CLIENT_LONG_FLAG
+    | CLIENT_CONNECT_WITH_DB
+    | CLIENT_PROTOCOL_41
+    | CLIENT_TRANSACTIONS
+    | CLIENT_SECURE_CONNECTION
+    | CLIENT_PLUGIN_AUTH
+    | CLIENT_PLUGIN_AUTH_LENENC_CLIENT_DATA;
+
+/// SERVER_STATUS_AUTOCOMMIT
+pub const SERVER_STATUS: u16 = 0x0002;
+
+// Commands.
+pub const COM_QUIT: u8 = 0x01;
+pub const COM_INIT_DB: u8 = 0x02;
+pub const COM_QUERY: u8 = 0x03;
+pub const COM_PING: u8 = 0x0e;
+
+// Error codes.
+/// ER_UNKNOWN_COM_ERROR
+pub const ER_UNKNOWN_COM: u16 = 1047;
+/// ER_UNKNOWN_ERROR
+pub const ER_UNKNOWN: u16 = 1105;
+/// ER_NET_PACKET_TOO_LARGE
+pub const ER_NET_PACKET_TOO_LARGE: u16 = 1153;
+
+// Column flags.
+const NOT_NULL_FLAG: u16 = 0x0001;
+const BINARY_FLAG: u16 = 0x0080;
+const UNSIGNED_FLAG: u16 = 0x0020;
+
+/// Column types of mysql.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u8)]
+pub enum ColumnType {
+    Tiny = 0x01,
+    Short = 0x02,
+    Long = 0x03,
+    Float = 0x04,
+    Double = 0x05,
+    Null = 0x06,
+    Timestamp = 0x07,
+    LongLong = 0x08,
+    Blob = 0xfc,
+    VarString = 0xfd,
+}
+
+impl ColumnType {
+    /// Returns the column type and whether it is unsigned.
+    pub fn from_datum_kind(kind: DatumKind) -> (Self, bool) {
+        match kind {
+            DatumKind::Null => (ColumnType::Null, false),
+            DatumKind::Timestamp => (ColumnType::Timestamp, false),
+            DatumKind::Double => (ColumnType::Double, false),
+            DatumKind::Float => (ColumnType::Float, false),
+            DatumKind::Varbinary => (ColumnType::Blob, false),
+            DatumKind::String => (ColumnType::VarString, false),
+            DatumKind::UInt64 => (ColumnType::LongLong, true),
+            DatumKind::UInt32 => (ColumnType::Long, true),
+            DatumKind::UInt16 => (ColumnType::Short, true),
+            DatumKind::UInt8 => (ColumnType::Tiny, true),
+            DatumKind::Int64 => (ColumnType::LongLong, false),
+            DatumKind::Int32 => (ColumnType::Long, false),
+            DatumKind::Int16 => (ColumnType::Short, false),
+            DatumKind::Int8 => (ColumnType::Tiny, false),
+            DatumKind::Boolean => (ColumnType::Tiny, false),
+        }
+    }
+
+    /// Max display length of the column.
+    fn column_len(&self) -> u32 {
+        match self {
+            ColumnType::Tiny => 4,
+            ColumnType::Short => 6,
+            ColumnType::Long => 11,
+            ColumnType::Float => 12,
+            ColumnType::Double => 22,
+            ColumnType::Null => 0,
+            ColumnType::Timestamp => 23,
+            ColumnType::LongLong => 20,
+            ColumnType::Blob | ColumnType::VarString => MAX_PAYLOAD_LEN as u32,
+        }
+    }
+}
+
+/// Reads and writes the packets of a connection, also tracks the sequence id
+/// of the packets.
+pub struct PacketIo<S> {
+    stream: S,
+    seq: u8,
+    /// Max size of the payload read from the client.
+    max_allowed_packet: usize,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> PacketIo<S> {
+    pub fn new(stream: S, max_allowed_packet: usize) -> Self {
+        Self {
+            stream,
+            seq: 0,
+            max_allowed_packet,
+        }
+    }
+
+    /// Reset the sequence id, which should be called before reading a new
+    /// command.
+    pub fn reset_seq(&mut self) {
+        self.seq = 0;
+    }
+
+    /// Read the payload of a packet, returns None if the connection is
+    /// closed by the client. Returns [Error::PacketTooLarge] if the payload
+    /// exceeds the max allowed packet, the connection should be closed then.
+    pub async fn read_packet(&mut self) -> Result<Option<Vec<u8>>> {
+        let mut payload = Vec::new();
+        loop {
+            let mut header = [0; 4];
+            match self.stream.read_exact(&mut header).await {
+                Ok(_) => (),
+                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof && payload.is_empty() => {
+                    return Ok(None)
+                }
+                Err(e) => return Err(e).context(ReadPacket),
+            }
+
+            let len = u32::from_le_bytes([header[0], header[1], header[2], 0]) as usize;
+            ensure!(
+                header[3] == self.seq,
+                OutOfOrder {
+                    expect: self.seq,
+                    given: header[3],
+                }
+            );
+            self.seq = self.seq.wrapping_add(1);
+
+            let offset = payload.len();
+            ensure!(
+                offset + len <= self.max_allowed_packet,
+                PacketTooLarge {
+                    size: offset + len,
+                    max_allowed_packet: self.max_allowed_packet,
+                }
+            );
+            payload.resize(offset + len, 0);
+            self.stream
+                .read_exact(&mut payload[offset..])
+                .await
+                .context(ReadPacket)?;
+
+            // A payload of max length is followed by the remaining part.
+            if len < MAX_PAYLOAD_LEN {
+                return Ok(Some(payload));
+            }
+        }
+    }
+
+    /// Write the `payload` as packets, [PacketIo::flush] should be called
+    /// if the stream is buffered.
+    pub async fn write_packet(&mut self, payload: &[u8]) -> Result<()> {
+        let mut buf = Vec::with_capacity(payload.len() + 4);
+        encode_packets(payload, &mut self.seq, &mut buf);
+        self.stream.write_all(&buf).await.context(WritePacket)
+    }
+
+    pub async fn flush(&mut self) -> Result<()> {
+        self.stream.flush().await.context(WritePacket)
+    }
+}
+
+/// Encode the `payload` into packets, the `seq` is advanced for each packet.
+fn encode_packets(payload: &[u8], seq: &mut u8, buf: &mut Vec<u8>) {
+    let mut chunks = payload.chunks(MAX_PAYLOAD_LEN);
+    loop {
+        let chunk = chunks.next().unwrap_or(&[]);
+        buf.extend_from_slice(&(chunk.len() as u32).to_le_bytes()[..3]);
+        buf.push(*seq);
+        buf.extend_from_slice(chunk);
+        *seq = seq.wrapping_add(1);
+
+        // An empty packet is required if the length of the last chunk is max
+        // length.
+        if chunk.len() < MAX_PAYLOAD_LEN {
+            return;
+        }
+    }
+}
+
+pub fn write_lenenc_int(buf: &mut Vec<u8>, v: u64) {
+    if v < 0xfb {
+        buf.push(v as u8);
+    } else if v <= 0xff_ff {
+        buf.push(0xfc);
+        buf.extend_from_slice(&(v as u16).to_le_bytes());
+    } else if v <= 0xff_ff_ff {
+        buf.push(0xfd);
+        buf.extend_from_slice(&(v as u32).to_le_bytes()[..3]);
+    } else {
+        buf.push(0xfe);
+        buf.extend_from_slice(&v.to_le_bytes());
+    }
+}
+
+pub fn write_lenenc_bytes(buf: &mut Vec<u8>, v: &[u8]) {
+    write_lenenc_int(buf, v.len() as u64);
+    buf.extend_from_slice(v);
+}
+
+/// Initial handshake packet (protocol version 10).
+pub fn handshake_packet(connection_id: u32, salt: &[u8; 20]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(128);
+    buf.push(10);
+    buf.extend_from_slice(SERVER_VERSION.as_bytes());
+    buf.push(0);
+    buf.extend_from_slice(&connection_id.to_le_bytes());
+    buf.extend_from_slice(&salt[..8]);
+    buf.push(0);
+    buf.extend_from_slice(&(SERVER_CAPABILITIES as u16).to_le_bytes());
+    buf.push(CHARSET_UTF8);
+    buf.extend_from_slice(&SERVER_STATUS.to_le_bytes());
+    buf.extend_from_slice(&((SERVER_CAPABILITIES >> 16) as u16).to_le_bytes());
+    // Length of the auth plugin data, including the trailing zero.
+    buf.push(salt.len() as u8 + 1);
+    buf.extend_from_slice(&[0; 10]);
+    buf.extend_from_slice(&salt[8..]);
+    buf.push(0);
+    buf.extend_from_slice(AUTH_PLUGIN_NAME.as_bytes());
+    buf.push(0);
+
+    buf
+}
+
+/// Handshake response from the client (HandshakeResponse41).
+#[derive(Debug, Default, PartialEq)]
+pub struct HandshakeResponse {
+    pub capabilities: u32,
+    pub username: String,
+    pub auth_response: Vec<u8>,
+    pub database: Option<String>,
+}
+
+impl HandshakeResponse {
+    pub fn decode(payload: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(payload);
+        let capabilities = reader.read_u32()?;
+        ensure!(
+            capabilities & CLIENT_PROTOCOL_41 != 0,
+            InvalidPacket {
+                msg: "client protocol older than 4.1 is not supported",
+            }
+        );
+        // Max packet size, character set and the reserved bytes.
+        reader.skip(4 + 1 + 23)?;
+
+        let username = reader.read_null_terminated_string()?;
+        let auth_response = if capabilities & CLIENT_PLUGIN_AUTH_LENENC_CLIENT_DATA != 0 {
+            let len = reader.read_lenenc_int()?;
+            reader.read_bytes(len as usize)?.to_vec()
+        } else if capabilities & CLIENT_SECURE_CONNECTION != 0 {
+            let len = reader.read_u8()?;
+            reader.read_bytes(len as usize)?.to_vec()
+        } else {
+            reader.read_null_terminated()?.to_vec()
+        };
+        let database = if capabilities & CLIENT_CONNECT_WITH_DB != 0 && !reader.is_empty() {
+            Some(reader.read_null_terminated_string()?).filter(|db| !db.is_empty())
+        } else {
+            None
+        };
+
+        Ok(Self {
+            capabilities,
+            username,
+            auth_response,
+            database,
+        })
+    }
+}
+
+pub fn ok_packet(affected_rows: u64) -> Vec<u8> {
+    let mut buf = vec![0x00];
+    write_lenenc_int(&mut buf, affected_rows);
+    // Last insert id.
+    write_lenenc_int(&mut buf, 0);
+    buf.extend_from_slice(&SERVER_STATUS.to_le_bytes());
+    // Warnings.
+    buf.extend_from_slice(&0u16.to_le_bytes());
+
+    buf
+}
+
+pub fn err_packet(code: u16, sql_state: &str, msg: &str) -> Vec<u8> {
+    let mut buf = vec![0xff];
+    buf.extend_from_slice(&code.to_le_bytes());
+    buf.push(b'#');
+    buf.extend_from_slice(sql_state.as_bytes());
+    buf.extend_from_slice(msg.as_bytes());
+
+    buf
+}
+
+pub fn eof_packet() -> Vec<u8> {
+    let mut buf = vec![0xfe];
+    // Warnings.
+    buf.extend_from_slice(&0u16.to_le_bytes());
+    buf.extend_from_slice(&SERVER_STATUS.to_le_bytes());
+
+    buf
+}
+
+pub fn column_count_packet(num_columns: usize) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(9);
+    write_lenenc_int(&mut buf, num_columns as u64);
+
+    buf
+}
+
+/// Column definition packet (ColumnDefinition41).
+pub fn column_definition_packet(name: &str, kind: DatumKind, is_nullable: bool) -> Vec<u8> {
+    let (column_type, is_unsigned) = ColumnType::from_datum_kind(kind);
+    let mut flags = 0;
+    if !is_nullable {
+        flags |= NOT_NULL_FLAG;
+    }
+    if is_unsigned {
+        flags |= UNSIGNED_FLAG;
+    }
+    let charset = match column_type {
+        ColumnType::VarString => u16::from(CHARSET_UTF8),
+        _ => {
+            flags |= BINARY_FLAG;
+            CHARSET_BINARY
+        }
+    };
+    let decimals = match column_type {
+        ColumnType::Float | ColumnType::Double => 0x1f,
+        ColumnType::Timestamp => 3,
+        _ => 0,
+    };
+
+    let mut buf = Vec::with_capacity(32 + 2 * name.len());
+    // Catalog, schema, table, org_table, name and org_name.
+    write_lenenc_bytes(&mut buf, b"def");
+    write_lenenc_bytes(&mut buf, b"");
+    write_lenenc_bytes(&mut buf, b"");
+    write_lenenc_bytes(&mut buf, b"");
+    write_lenenc_bytes(&mut buf, name.as_bytes());
+    write_lenenc_bytes(&mut buf, name.as_bytes());
+    // Length of the fixed length fields.
+    buf.push(0x0c);
+    buf.extend_from_slice(&charset.to_le_bytes());
+    buf.extend_from_slice(&column_type.column_len().to_le_bytes());
+    buf.push(column_type as u8);
+    buf.extend_from_slice(&flags.to_le_bytes());
+    buf.push(decimals);
+    buf.extend_from_slice(&[0, 0]);
+
+    buf
+}
+
+/// Append the `datum` as a value of the text resultset row.
+pub fn write_text_value(buf: &mut Vec<u8>, datum: &Datum) {
+    match datum {
+        Datum::Null => buf.push(0xfb),
+        Datum::Timestamp(v) => {
+            let value = Local
+                .timestamp_millis(v.as_i64())
+                .format("%Y-%m-%d %H:%M:%S%.3f")
+                .to_string();
+            write_lenenc_bytes(buf, value.as_bytes());
+        }
+        Datum::Varbinary(v) => write_lenenc_bytes(buf, v),
+        Datum::String(v) => write_lenenc_bytes(buf, v.as_bytes()),
+        Datum::Boolean(v) => write_lenenc_bytes(buf, if *v { b"1" } else { b"0" }),
+        _ => write_lenenc_bytes(buf, datum.display_string().as_bytes()),
+    }
+}
+
+/// Reader of the payload of a packet.
+struct Reader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self { buf }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        ensure!(
+            self.buf.len() >= len,
+            InvalidPacket {
+                msg: format!("expect {} bytes, remaining:{}", len, self.buf.len()),
+            }
+        );
+        let (bytes, remaining) = self.buf.split_at(len);
+        self.buf = remaining;
+
+        Ok(bytes)
+    }
+
+    fn skip(&mut self, len: usize) -> Result<()> {
+        self.read_bytes(len).map(|_| ())
+    }
+
+    fn read_u8(&mut self) -> Result<u8> {
+        self.read_bytes(1).map(|v| v[0])
+    }
+
+    fn read_u32(&mut self) -> Result<u32> {
+        let bytes = self.read_bytes(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn read_lenenc_int(&mut self) -> Result<u64> {
+        let len = match self.read_u8()? {
+            0xfc => 2,
+            0xfd => 3,
+            0xfe => 8,
+            v => return Ok(u64::from(v)),
+        };
+        let mut bytes = [0; 8];
+        bytes[..len].copy_from_slice(self.read_bytes(len)?);
+
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    fn read_null_terminated(&mut self) -> Result<&'a [u8]> {
+        let len = self
+            .buf
+            .iter()
+            .position(|v| *v == 0)
+            .unwrap_or(self.buf.len());
+        let bytes = self.read_bytes(len)?;
+        // Skip the trailing zero.
+        if !self.buf.is_empty() {
+            self.buf = &self.buf[1..];
+        }
+
+        Ok(bytes)
+    }
+
+    fn read_null_terminated_string(&mut self) -> Result<String> {
+        let bytes = self.read_null_terminated()?;
+        String::from_utf8(bytes.to_vec()).map_err(|e| {
+            InvalidPacket {
+                msg: format!("string is not utf8, err:{}", e),
+            }
+            .build()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lenenc_int() {
+        for v in [0, 250, 251, 0xffff, 0x10000, 0xffffff, 0x1000000, u64::MAX] {
+            let mut buf = Vec::new();
+            write_lenenc_int(&mut buf, v);
+            let mut reader = Reader::new(&buf);
+            assert_eq!(v, reader.read_lenenc_int().unwrap());
+            assert!(reader.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_encode_packets() {
+        let mut seq = 3;
+        let mut buf = Vec::new();
+        encode_packets(b"abc", &mut seq, &mut buf);
+        assert_eq!(vec![3, 0, 0, 3, b'a', b'b', b'c'], buf);
+        assert_eq!(4, seq);
+
+        // Payload of max length is followed by an empty packet.
+        let payload = vec![1; MAX_PAYLOAD_LEN];
+        let mut buf = Vec::new();
+        encode_packets(&payload, &mut seq, &mut buf);
+        assert_eq!(MAX_PAYLOAD_LEN + 8, buf.len());
+        assert_eq!([0xff, 0xff, 0xff, 4], buf[..4]);
+        assert_eq!([0, 0, 0, 5], buf[MAX_PAYLOAD_LEN + 4..]);
+        assert_eq!(6, seq);
+    }
+
+    #[tokio::test]
+    async fn test_read_packet() {
+        let mut seq = 0;
+        let mut buf = Vec::new();
+        encode_packets(b"hello", &mut seq, &mut buf);
+        let payload = vec![2; MAX_PAYLOAD_LEN + 10];
+        encode_packets(&payload, &mut seq, &mut buf);
+
+        let mut io = PacketIo::new(std::io::Cursor::new(buf), 2 * MAX_PAYLOAD_LEN);
+        assert_eq!(b"hello".to_vec(), io.read_packet().await.unwrap().unwrap());
+        assert_eq!(payload, io.read_packet().await.unwrap().unwrap());
+        assert!(io.read_packet().await.unwrap().is_none());
+
+        // The sequence id is checked.
+        let mut seq = 1;
+        let mut buf = Vec::new();
+        encode_packets(b"hello", &mut seq, &mut buf);
+        let mut io = PacketIo::new(std::io::Cursor::new(buf), MAX_PAYLOAD_LEN);
+        assert!(matches!(
+            io.read_packet().await,
+            Err(Error::OutOfOrder { .. })
+        ));
+
+        // The payload split into several packets is also bounded.
+        let mut seq = 0;
+        let mut buf = Vec::new();
+        encode_packets(&payload, &mut seq, &mut buf);
+        let mut io = PacketIo::new(std::io::Cursor::new(buf), MAX_PAYLOAD_LEN);
+        assert!(matches!(
+            io.read_packet().await,
+            Err(Error::PacketTooLarge { .. })
+        ));
+        let mut seq = 0;
+        let mut buf = Vec::new();
+        encode_packets(b"hello", &mut seq, &mut buf);
+        let mut io = PacketIo::new(std::io::Cursor::new(buf), 4);
+        assert!(matches!(
+            io.read_packet().await,
+            Err(Error::PacketTooLarge { .. })
+        ));
+    }
+
+    #[test]
+    fn test_decode_handshake_response() {
+        let capabilities = CLIENT_PROTOCOL_41
+            | CLIENT_SECURE_CONNECTION
+            | CLIENT_PLUGIN_AUTH_LENENC_CLIENT_DATA
+            | CLIENT_CONNECT_WITH_DB;
+        let mut payload = Vec::new();
+        payload.extend_from_slice(&capabilities.to_le_bytes());
+        payload.extend_from_slice(&(1u32 << 24).to_le_bytes());
+        payload.push(CHARSET_UTF8);
+        payload.extend_from_slice(&[0; 23]);
+        payload.extend_from_slice(b"root\0");
+        write_lenenc_bytes(&mut payload, &[1, 2, 3]);
+        payload.extend_from_slice(b"public\0");
+        payload.extend_from_slice(AUTH_PLUGIN_NAME.as_bytes());
+        payload.push(0);
+
+        let response = HandshakeResponse::decode(&payload).unwrap();
+        assert_eq!(
+            HandshakeResponse {
+                capabilities,
+                username: "root".to_string(),
+                auth_response: vec![1, 2, 3],
+                database: Some("public".to_string()),
+            },
+            response
+        );
+
+        // Protocol 4.1 is required.
+        payload[..4].copy_from_slice(&CLIENT_SECURE_CONNECTION.to_le_bytes());
+        assert!(HandshakeResponse::decode(&payload).is_err());
+    }
+
+    #[test]
+    fn test_handshake_packet() {
+        let salt = [b'a'; 20];
+        let packet = handshake_packet(7, &salt);
+        assert_eq!(10, packet[0]);
+        let version_end = 1 + SERVER_VERSION.len();
+        assert_eq!(SERVER_VERSION.as_bytes(), &packet[1..version_end]);
+        assert_eq!(0, packet[version_end]);
+        assert_eq!(7u32.to_le_bytes(), packet[version_end + 1..version_end + 5]);
+        assert!(packet.ends_with(b"mysql_native_password\0"));
+    }
+
+    #[test]
+    fn test_text_value() {
+        let mut buf = Vec::new();
+        write_text_value(&mut buf, &Datum::Null);
+        write_text_value(&mut buf, &Datum::Int32(-12));
+        write_text_value(&mut buf, &Datum::Boolean(true));
+        write_text_value(&mut buf, &Datum::String("ab".into()));
+        assert_eq!(vec![0xfb, 3, b'-', b'1', b'2', 1, b'1', 2, b'a', b'b'], buf);
+    }
+
+    #[test]
+    fn test_column_type() {
+        assert_eq!(
+            (ColumnType::LongLong, true),
+            ColumnType::from_datum_kind(DatumKind::UInt64)
+        );
+        assert_eq!(
+            (ColumnType::VarString, false),
+            ColumnType::from_datum_kind(DatumKind::String)
+        );
+        assert_eq!(
+            (ColumnType::Timestamp, false),
+            ColumnType::from_datum_kind(DatumKind::Timestamp)
+        );
+
+        let packet = column_definition_packet("name", DatumKind::String, false);
+        // Type and flags follow the fixed length of the packet.
+        let pos = packet.iter().rposition(|v| *v == 0x0c).unwrap();
+        assert_eq!(ColumnType::VarString as u8, packet[pos + 7]);
+        assert_eq!(NOT_NULL_FLAG.to_le_bytes(), packet[pos + 8..pos + 10]);
+    }
+}
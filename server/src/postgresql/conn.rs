@@ -0,0 +1,601 @@
+// Copyright 2022 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Connection of the postgresql protocol
+
+use std::{collections::HashMap, sync::Arc};
+
+use arrow_deps::arrow::datatypes::Schema as ArrowSchema;
+use catalog::manager::Manager as CatalogManager;
+use common_types::{datum::DatumKind, record_batch::RecordBatch};
+use common_util::runtime::Runtime;
+use futures::StreamExt;
+use interpreters::interpreter::Output;
+use log::{error, info};
+use query_engine::executor::Executor as QueryExecutor;
+use snafu::{Backtrace, OptionExt, ResultExt, Snafu};
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use crate::{
+    context::RequestContext,
+    handlers::sql::{describe_sql, execute_sql, Request},
+    instance::InstanceRef,
+    postgresql::{
+        protocol::{
+            self, Bind, DataRowBuilder, Execute, FieldDescription, Format, MessageIo, Parse,
+            StartupMessage, Target,
+        },
+        types,
+    },
+};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Failed to handle message, err:{}", source))]
+    Protocol { source: protocol::Error },
+
+    #[snafu(display("Failed to decode message, err:{}", source))]
+    DecodeMessage { source: protocol::Error },
+
+    #[snafu(display("Unsupported message, tag:{}.\nBacktrace:\n{}", tag, backtrace))]
+    UnsupportedMessage { tag: char, backtrace: Backtrace },
+
+    #[snafu(display("Statement not found, name:{}.\nBacktrace:\n{}", name, backtrace))]
+    StatementNotFound { name: String, backtrace: Backtrace },
+
+    #[snafu(display("Portal not found, name:{}.\nBacktrace:\n{}", name, backtrace))]
+    PortalNotFound { name: String, backtrace: Backtrace },
+
+    #[snafu(display("Failed to bind parameters, err:{}", source))]
+    BindParams { source: types::Error },
+
+    #[snafu(display("Failed to create request context, err:{}", source))]
+    CreateContext { source: crate::context::Error },
+
+    #[snafu(display("Failed to execute sql, err:{}", source))]
+    ExecuteSql {
+        source: crate::handlers::error::Error,
+    },
+
+    #[snafu(display("Failed to poll record batch, err:{}", source))]
+    PollStream { source: table_engine::stream::Error },
+}
+
+define_result!(Error);
+
+impl Error {
+    /// Whether the connection should be closed.
+    fn is_fatal(&self) -> bool {
+        matches!(self, Error::Protocol { .. })
+    }
+
+    /// Error code of the ErrorResponse.
+    fn code(&self) -> &'static str {
+        match self {
+            Error::Protocol { .. } | Error::DecodeMessage { .. } => protocol::PROTOCOL_VIOLATION,
+            Error::UnsupportedMessage { .. } => protocol::FEATURE_NOT_SUPPORTED,
+            Error::StatementNotFound { .. } => protocol::INVALID_STATEMENT_NAME,
+            Error::PortalNotFound { .. } => protocol::INVALID_CURSOR_NAME,
+            Error::BindParams { .. } => protocol::INVALID_PARAMETER_VALUE,
+            Error::CreateContext { .. } | Error::ExecuteSql { .. } | Error::PollStream { .. } => {
+                protocol::INTERNAL_ERROR
+            }
+        }
+    }
+}
+
+/// Version reported to the client.
+const SERVER_VERSION: &str = "14.0";
+
+/// Statement created by the Parse message.
+struct Statement {
+    query: String,
+    param_types: Vec<u32>,
+}
+
+/// Portal created by the Bind message.
+struct Portal {
+    /// Query with the parameters bound.
+    query: String,
+    result_formats: Vec<Format>,
+    /// Output of the query if it has been executed to describe the portal.
+    output: Option<Output>,
+}
+
+/// A client connection, the statements are executed by the same pipeline as
+/// the sql handler of the http service.
+pub struct Connection<C, Q, S> {
+    io: MessageIo<S>,
+    instance: InstanceRef<C, Q>,
+    runtime: Arc<Runtime>,
+    connection_id: u32,
+    catalog: String,
+    /// Tenant of the connection, which is the database of postgresql.
+    tenant: String,
+    statements: HashMap<String, Statement>,
+    portals: HashMap<String, Portal>,
+}
+
+impl<C, Q, S> Connection<C, Q, S>
+where
+    C: CatalogManager + 'static,
+    Q: QueryExecutor + 'static,
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    pub fn new(
+        stream: S,
+        instance: InstanceRef<C, Q>,
+        runtime: Arc<Runtime>,
+        connection_id: u32,
+    ) -> Self {
+        let catalog = instance.catalog_manager.default_catalog_name().to_string();
+        let tenant = instance.catalog_manager.default_schema_name().to_string();
+
+        Self {
+            io: MessageIo::new(stream),
+            instance,
+            runtime,
+            connection_id,
+            catalog,
+            tenant,
+            statements: HashMap::new(),
+            portals: HashMap::new(),
+        }
+    }
+
+    /// Serve the connection until it is closed.
+    pub async fn run(mut self) -> Result<()> {
+        if !self.startup().await? {
+            return Ok(());
+        }
+
+        // Messages of the extended query are skipped until the Sync message
+        // once an error occurs.
+        let mut skip_until_sync = false;
+        loop {
+            let (tag, body) = match self.io.read_message().await.context(Protocol)? {
+                Some(message) => message,
+                None => return Ok(()),
+            };
+            if skip_until_sync && tag != protocol::SYNC && tag != protocol::TERMINATE {
+                continue;
+            }
+
+            let res = match tag {
+                protocol::QUERY => self.handle_query(&body).await,
+                protocol::PARSE => self.handle_parse(&body).await,
+                protocol::BIND => self.handle_bind(&body).await,
+                protocol::DESCRIBE => self.handle_describe(&body).await,
+                protocol::EXECUTE => self.handle_execute(&body).await,
+                protocol::CLOSE => self.handle_close(&body).await,
+                protocol::SYNC => {
+                    skip_until_sync = false;
+                    // The unnamed portal is dropped at the end of the transaction.
+                    self.portals.remove("");
+                    self.write_message(protocol::ready_for_query()).await
+                }
+                protocol::FLUSH => Ok(()),
+                protocol::TERMINATE => return Ok(()),
+                _ => UnsupportedMessage { tag: tag as char }.fail(),
+            };
+
+            if let Err(e) = res {
+                if e.is_fatal() {
+                    return Err(e);
+                }
+
+                error!(
+                    "Postgresql service failed to handle message, conn_id:{}, tag:{}, err:{}",
+                    self.connection_id, tag as char, e
+                );
+                self.write_message(protocol::error_response(e.code(), &e.to_string()))
+                    .await?;
+                if tag == protocol::QUERY {
+                    self.write_message(protocol::ready_for_query()).await?;
+                } else {
+                    skip_until_sync = true;
+                }
+            }
+
+            // The responses of the extended query are sent after the Sync or Flush.
+            if matches!(tag, protocol::QUERY | protocol::SYNC | protocol::FLUSH) {
+                self.io.flush().await.context(Protocol)?;
+            }
+        }
+    }
+
+    /// Handle the startup messages, returns false if the connection should be
+    /// closed.
+    async fn startup(&mut self) -> Result<bool> {
+        loop {
+            let body = match self.io.read_startup().await.context(Protocol)? {
+                Some(body) => body,
+                None => return Ok(false),
+            };
+
+            match StartupMessage::decode(&body).context(Protocol)? {
+                StartupMessage::SslRequest | StartupMessage::GssEncRequest => {
+                    // Encryption is not supported, the client may continue
+                    // the startup without encryption.
+                    self.io.write_byte(b'N').await.context(Protocol)?;
+                    self.io.flush().await.context(Protocol)?;
+                }
+                // Cancelling the query is not supported.
+                StartupMessage::CancelRequest => return Ok(false),
+                StartupMessage::Startup { params } => {
+                    if let Some(database) = params.get("database").filter(|v| !v.is_empty()) {
+                        self.tenant = database.clone();
+                    }
+
+                    info!(
+                        "Postgresql service accept connection, conn_id:{}, user:{:?}, tenant:{}",
+                        self.connection_id,
+                        params.get("user"),
+                        self.tenant
+                    );
+
+                    // TODO: Authenticate the user once the access control is supported.
+                    self.write_message(protocol::authentication_ok()).await?;
+                    for (name, value) in [
+                        ("server_version", SERVER_VERSION),
+                        ("server_encoding", "UTF8"),
+                        ("client_encoding", "UTF8"),
+                        ("DateStyle", "ISO, MDY"),
+                        ("integer_datetimes", "on"),
+                        ("standard_conforming_strings", "on"),
+                    ] {
+                        self.write_message(protocol::parameter_status(name, value))
+                            .await?;
+                    }
+                    self.write_message(protocol::backend_key_data(self.connection_id, 0))
+                        .await?;
+                    self.write_message(protocol::ready_for_query()).await?;
+                    self.io.flush().await.context(Protocol)?;
+
+                    return Ok(true);
+                }
+            }
+        }
+    }
+
+    /// Handle the simple query.
+    async fn handle_query(&mut self, body: &[u8]) -> Result<()> {
+        let query = protocol::decode_query(body).context(DecodeMessage)?;
+
+        if is_empty_query(&query) {
+            self.write_message(protocol::empty_query_response()).await?;
+        } else if let Some(tag) = ignored_command_tag(&query) {
+            self.write_message(protocol::command_complete(tag)).await?;
+        } else {
+            let output = self.execute(&query).await?;
+            if let Some(schema) = output_schema(&output) {
+                self.write_message(row_description(&schema, &[])).await?;
+            }
+            self.write_output(output, &query, &[]).await?;
+        }
+
+        self.write_message(protocol::ready_for_query()).await
+    }
+
+    async fn handle_parse(&mut self, body: &[u8]) -> Result<()> {
+        let parse = Parse::decode(body).context(DecodeMessage)?;
+        self.statements.insert(
+            parse.statement,
+            Statement {
+                query: parse.query,
+                param_types: parse.param_types,
+            },
+        );
+
+        self.write_message(protocol::parse_complete()).await
+    }
+
+    async fn handle_bind(&mut self, body: &[u8]) -> Result<()> {
+        let bind = Bind::decode(body).context(DecodeMessage)?;
+        let statement = self
+            .statements
+            .get(&bind.statement)
+            .context(StatementNotFound {
+                name: &bind.statement,
+            })?;
+        let query = types::bind_params(
+            &statement.query,
+            &statement.param_types,
+            &bind.param_formats,
+            &bind.params,
+        )
+        .context(BindParams)?;
+        self.portals.insert(
+            bind.portal,
+            Portal {
+                query,
+                result_formats: bind.result_formats,
+                output: None,
+            },
+        );
+
+        self.write_message(protocol::bind_complete()).await
+    }
+
+    async fn handle_describe(&mut self, body: &[u8]) -> Result<()> {
+        match Target::decode(body).context(DecodeMessage)? {
+            Target::Statement(name) => {
+                let statement = self
+                    .statements
+                    .get(&name)
+                    .context(StatementNotFound { name: &name })?;
+                // The type of the parameter not specified by the client is reported as text.
+                let num_params = types::num_params(&statement.query);
+                let param_types: Vec<_> = (0..num_params.max(statement.param_types.len()))
+                    .map(|idx| match statement.param_types.get(idx) {
+                        Some(&oid) if oid != types::UNSPECIFIED => oid,
+                        _ => types::TEXT,
+                    })
+                    .collect();
+                let query = types::params_to_null(&statement.query);
+
+                self.write_message(protocol::parameter_description(&param_types))
+                    .await?;
+                match self.describe(&query)? {
+                    Some(schema) => {
+                        let fields = arrow_field_descriptions(&schema);
+                        self.write_message(protocol::row_description(&fields)).await
+                    }
+                    None => self.write_message(protocol::no_data()).await,
+                }
+            }
+            Target::Portal(name) => {
+                let portal = self
+                    .portals
+                    .get(&name)
+                    .context(PortalNotFound { name: &name })?;
+                if is_empty_query(&portal.query) || ignored_command_tag(&portal.query).is_some() {
+                    return self.write_message(protocol::no_data()).await;
+                }
+
+                // The schema is known only after the query is executed, so the
+                // portal is executed here and its output is sent by the
+                // Execute message.
+                let query = portal.query.clone();
+                let output = self.execute(&query).await?;
+                let portal = self
+                    .portals
+                    .get_mut(&name)
+                    .context(PortalNotFound { name: &name })?;
+                let message = match output_schema(&output) {
+                    Some(schema) => row_description(&schema, &portal.result_formats),
+                    None => protocol::no_data(),
+                };
+                portal.output = Some(output);
+
+                self.write_message(message).await
+            }
+        }
+    }
+
+    async fn handle_execute(&mut self, body: &[u8]) -> Result<()> {
+        // The max rows is ignored and all the rows are returned.
+        let execute = Execute::decode(body).context(DecodeMessage)?;
+        let portal = self
+            .portals
+            .get_mut(&execute.portal)
+            .context(PortalNotFound {
+                name: &execute.portal,
+            })?;
+        let query = portal.query.clone();
+        let result_formats = portal.result_formats.clone();
+        let output = portal.output.take();
+
+        if is_empty_query(&query) {
+            return self.write_message(protocol::empty_query_response()).await;
+        }
+        if let Some(tag) = ignored_command_tag(&query) {
+            return self.write_message(protocol::command_complete(tag)).await;
+        }
+
+        let output = match output {
+            Some(output) => output,
+            None => self.execute(&query).await?,
+        };
+        self.write_output(output, &query, &result_formats).await
+    }
+
+    async fn handle_close(&mut self, body: &[u8]) -> Result<()> {
+        // Closing a nonexistent statement or portal is not an error.
+        match Target::decode(body).context(DecodeMessage)? {
+            Target::Statement(name) => {
+                self.statements.remove(&name);
+            }
+            Target::Portal(name) => {
+                self.portals.remove(&name);
+            }
+        }
+
+        self.write_message(protocol::close_complete()).await
+    }
+
+    async fn execute(&self, query: &str) -> Result<Output> {
+        let ctx = self.request_context()?;
+        let request = Request::new(query.to_string());
+
+        execute_sql(ctx, &self.instance, &request, true)
+            .await
+            .context(ExecuteSql)
+    }
+
+    /// Returns the schema of the result of the `query`, None if the query has
+    /// no result.
+    fn describe(&self, query: &str) -> Result<Option<Arc<ArrowSchema>>> {
+        if is_empty_query(query) || ignored_command_tag(query).is_some() {
+            return Ok(None);
+        }
+
+        let ctx = self.request_context()?;
+        describe_sql(&ctx, &self.instance, query).context(ExecuteSql)
+    }
+
+    fn request_context(&self) -> Result<RequestContext> {
+        RequestContext::builder()
+            .catalog(self.catalog.clone())
+            .tenant(self.tenant.clone())
+            .runtime(self.runtime.clone())
+            .build()
+            .context(CreateContext)
+    }
+
+    /// Write the rows of the output and the CommandComplete message.
+    async fn write_output(
+        &mut self,
+        output: Output,
+        query: &str,
+        formats: &[Format],
+    ) -> Result<()> {
+        let tag = match output {
+            Output::AffectedRows(n) => affected_rows_tag(query, n),
+            Output::Records(records) => {
+                let mut num_rows = 0;
+                for record_batch in &records {
+                    self.write_rows(record_batch, formats).await?;
+                    num_rows += record_batch.num_rows();
+                }
+                format!("SELECT {}", num_rows)
+            }
+            Output::Stream(mut stream) => {
+                let mut num_rows = 0;
+                while let Some(record_batch) = stream.next().await {
+                    let record_batch = record_batch.context(PollStream)?;
+                    self.write_rows(&record_batch, formats).await?;
+                    num_rows += record_batch.num_rows();
+                }
+                format!("SELECT {}", num_rows)
+            }
+        };
+
+        self.write_message(protocol::command_complete(&tag)).await
+    }
+
+    async fn write_rows(&mut self, record_batch: &RecordBatch, formats: &[Format]) -> Result<()> {
+        for row_idx in 0..record_batch.num_rows() {
+            let mut builder = DataRowBuilder::new(record_batch.num_columns());
+            for col_idx in 0..record_batch.num_columns() {
+                let datum = record_batch.column(col_idx).datum(row_idx);
+                match types::encode_datum(&datum, Format::of(formats, col_idx)) {
+                    Some(value) => builder.append_value(&value),
+                    None => builder.append_null(),
+                }
+            }
+            self.write_message(builder.build()).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn write_message(&mut self, (tag, body): (u8, Vec<u8>)) -> Result<()> {
+        self.io.write_message(tag, &body).await.context(Protocol)
+    }
+}
+
+/// Fields of the rows, None if the output has no rows.
+fn output_schema(output: &Output) -> Option<Vec<(String, DatumKind)>> {
+    let schema = match output {
+        Output::AffectedRows(_) => return None,
+        Output::Records(records) => records.first()?.schema(),
+        Output::Stream(stream) => stream.schema(),
+    };
+    let fields = schema
+        .columns()
+        .iter()
+        .map(|column| (column.name.clone(), column.data_type))
+        .collect();
+
+    Some(fields)
+}
+
+fn row_description(fields: &[(String, DatumKind)], formats: &[Format]) -> (u8, Vec<u8>) {
+    let fields: Vec<_> = fields
+        .iter()
+        .enumerate()
+        .map(|(idx, (name, kind))| {
+            let (type_oid, type_size) = types::type_of(*kind);
+            FieldDescription {
+                name,
+                type_oid,
+                type_size,
+                format: Format::of(formats, idx),
+            }
+        })
+        .collect();
+
+    protocol::row_description(&fields)
+}
+
+fn arrow_field_descriptions(schema: &ArrowSchema) -> Vec<FieldDescription> {
+    schema
+        .fields()
+        .iter()
+        .map(|field| {
+            // Types not supported by the datum are reported as text.
+            let (type_oid, type_size) = DatumKind::from_data_type(field.data_type())
+                .map(types::type_of)
+                .unwrap_or((types::TEXT, -1));
+            FieldDescription {
+                name: field.name(),
+                type_oid,
+                type_size,
+                // The format is unknown before binding.
+                format: Format::Text,
+            }
+        })
+        .collect()
+}
+
+fn is_empty_query(query: &str) -> bool {
+    query.trim().trim_end_matches(';').trim().is_empty()
+}
+
+/// Returns the command tag if the query should be ignored, such as the `SET`
+/// statements sent by the drivers to set the session variables.
+fn ignored_command_tag(query: &str) -> Option<&'static str> {
+    let first_word = query.split_whitespace().next()?;
+    if first_word.eq_ignore_ascii_case("SET") {
+        Some("SET")
+    } else {
+        None
+    }
+}
+
+/// Tag of the CommandComplete message of the query without rows.
+fn affected_rows_tag(query: &str, affected_rows: usize) -> String {
+    let command = query
+        .split_whitespace()
+        .next()
+        .unwrap_or_default()
+        .to_uppercase();
+    match command.as_str() {
+        "INSERT" => format!("INSERT 0 {}", affected_rows),
+        "UPDATE" | "DELETE" => format!("{} {}", command, affected_rows),
+        _ => command,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_tag() {
+        assert!(is_empty_query(" ; "));
+        assert!(!is_empty_query("select 1"));
+
+        assert_eq!(
+            Some("SET"),
+            ignored_command_tag("set extra_float_digits = 3")
+        );
+        assert_eq!(None, ignored_command_tag("select 1"));
+
+        assert_eq!(
+            "INSERT 0 2",
+            affected_rows_tag("insert into t values (1), (2)", 2)
+        );
+        assert_eq!("CREATE", affected_rows_tag("CREATE TABLE t (...)", 0));
+    }
+}
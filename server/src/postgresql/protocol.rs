@@ -0,0 +1,657 @@
+// Copyright 2022 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Messages of the postgresql frontend/backend protocol (version 3.0)
+//!
+//! See <https://www.postgresql.org/docs/current/protocol-message-formats.html>.
+
+use std::collections::HashMap;
+
+use snafu::{ensure, Backtrace, OptionExt, ResultExt, Snafu};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Failed to read message, err:{}", source))]
+    ReadMessage { source: std::io::Error },
+
+    #[snafu(display("Failed to write message, err:{}", source))]
+    WriteMessage { source: std::io::Error },
+
+    #[snafu(display("Invalid message, msg:{}.\nBacktrace:\n{}", msg, backtrace))]
+    InvalidMessage { msg: String, backtrace: Backtrace },
+}
+
+define_result!(Error);
+
+/// Version 3.0 of the protocol.
+const PROTOCOL_VERSION: i32 = 196608;
+const SSL_REQUEST_CODE: i32 = 80877103;
+const GSSENC_REQUEST_CODE: i32 = 80877104;
+const CANCEL_REQUEST_CODE: i32 = 80877102;
+
+/// Max length of a message, to avoid allocating too much memory for a broken
+/// message.
+const MAX_MESSAGE_LEN: usize = 1 << 30;
+
+// Tags of the frontend messages.
+pub const BIND: u8 = b'B';
+pub const CLOSE: u8 = b'C';
+pub const DESCRIBE: u8 = b'D';
+pub const EXECUTE: u8 = b'E';
+pub const FLUSH: u8 = b'H';
+pub const PARSE: u8 = b'P';
+pub const QUERY: u8 = b'Q';
+pub const SYNC: u8 = b'S';
+pub const TERMINATE: u8 = b'X';
+
+// Error codes.
+pub const PROTOCOL_VIOLATION: &str = "08P01";
+pub const FEATURE_NOT_SUPPORTED: &str = "0A000";
+pub const INVALID_PARAMETER_VALUE: &str = "22023";
+pub const INVALID_CURSOR_NAME: &str = "34000";
+pub const INVALID_STATEMENT_NAME: &str = "26000";
+pub const INTERNAL_ERROR: &str = "XX000";
+
+/// Transaction status of the ReadyForQuery message, idle as transaction is
+/// not supported.
+const TRANSACTION_IDLE: u8 = b'I';
+
+/// Format of the values.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Text,
+    Binary,
+}
+
+impl Format {
+    fn from_code(code: i16) -> Result<Self> {
+        match code {
+            0 => Ok(Format::Text),
+            1 => Ok(Format::Binary),
+            _ => InvalidMessage {
+                msg: format!("unknown format code {}", code),
+            }
+            .fail(),
+        }
+    }
+
+    fn code(&self) -> i16 {
+        match self {
+            Format::Text => 0,
+            Format::Binary => 1,
+        }
+    }
+
+    /// Returns the format of the `idx`-th value, the `formats` may be empty
+    /// (all values are text) or contain only one format for all values.
+    pub fn of(formats: &[Format], idx: usize) -> Format {
+        match formats {
+            [] => Format::Text,
+            [format] => *format,
+            _ => formats.get(idx).copied().unwrap_or(Format::Text),
+        }
+    }
+}
+
+/// The first message from the client.
+#[derive(Debug, PartialEq)]
+pub enum StartupMessage {
+    Startup { params: HashMap<String, String> },
+    SslRequest,
+    GssEncRequest,
+    CancelRequest,
+}
+
+impl StartupMessage {
+    pub fn decode(body: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(body);
+        let message = match reader.read_i32()? {
+            PROTOCOL_VERSION => {
+                let mut params = HashMap::new();
+                loop {
+                    let key = reader.read_cstring()?;
+                    if key.is_empty() {
+                        break;
+                    }
+                    let value = reader.read_cstring()?;
+                    params.insert(key, value);
+                }
+
+                StartupMessage::Startup { params }
+            }
+            SSL_REQUEST_CODE => StartupMessage::SslRequest,
+            GSSENC_REQUEST_CODE => StartupMessage::GssEncRequest,
+            CANCEL_REQUEST_CODE => StartupMessage::CancelRequest,
+            version => {
+                return InvalidMessage {
+                    msg: format!("unsupported protocol version {}", version),
+                }
+                .fail()
+            }
+        };
+
+        Ok(message)
+    }
+}
+
+/// Parse message of the extended query.
+#[derive(Debug, PartialEq)]
+pub struct Parse {
+    pub statement: String,
+    pub query: String,
+    /// Oids of the parameters, 0 means unspecified.
+    pub param_types: Vec<u32>,
+}
+
+impl Parse {
+    pub fn decode(body: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(body);
+        let statement = reader.read_cstring()?;
+        let query = reader.read_cstring()?;
+        let num_params = reader.read_i16()?;
+        let param_types = (0..num_params)
+            .map(|_| reader.read_i32().map(|v| v as u32))
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            statement,
+            query,
+            param_types,
+        })
+    }
+}
+
+/// Bind message of the extended query.
+#[derive(Debug, PartialEq)]
+pub struct Bind {
+    pub portal: String,
+    pub statement: String,
+    pub param_formats: Vec<Format>,
+    /// Values of the parameters, None is null.
+    pub params: Vec<Option<Vec<u8>>>,
+    pub result_formats: Vec<Format>,
+}
+
+impl Bind {
+    pub fn decode(body: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(body);
+        let portal = reader.read_cstring()?;
+        let statement = reader.read_cstring()?;
+        let param_formats = reader.read_formats()?;
+        let num_params = reader.read_i16()?;
+        let params = (0..num_params)
+            .map(|_| {
+                let len = reader.read_i32()?;
+                if len < 0 {
+                    Ok(None)
+                } else {
+                    reader.read_bytes(len as usize).map(|v| Some(v.to_vec()))
+                }
+            })
+            .collect::<Result<_>>()?;
+        let result_formats = reader.read_formats()?;
+
+        Ok(Self {
+            portal,
+            statement,
+            param_formats,
+            params,
+            result_formats,
+        })
+    }
+}
+
+/// Target of the Describe and Close message.
+#[derive(Debug, PartialEq)]
+pub enum Target {
+    Statement(String),
+    Portal(String),
+}
+
+impl Target {
+    pub fn decode(body: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(body);
+        let kind = reader.read_u8()?;
+        let name = reader.read_cstring()?;
+        match kind {
+            b'S' => Ok(Target::Statement(name)),
+            b'P' => Ok(Target::Portal(name)),
+            _ => InvalidMessage {
+                msg: format!("unknown target {}", kind),
+            }
+            .fail(),
+        }
+    }
+}
+
+/// Execute message of the extended query.
+#[derive(Debug, PartialEq)]
+pub struct Execute {
+    pub portal: String,
+    /// Max rows to return, 0 means no limit.
+    pub max_rows: i32,
+}
+
+impl Execute {
+    pub fn decode(body: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(body);
+        let portal = reader.read_cstring()?;
+        let max_rows = reader.read_i32()?;
+
+        Ok(Self { portal, max_rows })
+    }
+}
+
+/// Decode the query string of the simple Query message.
+pub fn decode_query(body: &[u8]) -> Result<String> {
+    Reader::new(body).read_cstring()
+}
+
+/// Description of a field of the RowDescription message.
+#[derive(Debug)]
+pub struct FieldDescription<'a> {
+    pub name: &'a str,
+    pub type_oid: u32,
+    pub type_size: i16,
+    pub format: Format,
+}
+
+/// Reads and writes the messages of a connection.
+pub struct MessageIo<S> {
+    stream: S,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> MessageIo<S> {
+    pub fn new(stream: S) -> Self {
+        Self { stream }
+    }
+
+    /// Read the body of the startup message, which has no tag. Returns None if
+    /// the connection is closed by the client.
+    pub async fn read_startup(&mut self) -> Result<Option<Vec<u8>>> {
+        let len = match self.read_len().await? {
+            Some(len) => len,
+            None => return Ok(None),
+        };
+        self.read_body(len).await.map(Some)
+    }
+
+    /// Read the tag and body of a message. Returns None if the connection is
+    /// closed by the client.
+    pub async fn read_message(&mut self) -> Result<Option<(u8, Vec<u8>)>> {
+        let tag = match self.stream.read_u8().await {
+            Ok(tag) => tag,
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e).context(ReadMessage),
+        };
+        let len = self.read_len().await?.context(InvalidMessage {
+            msg: "message is truncated",
+        })?;
+        let body = self.read_body(len).await?;
+
+        Ok(Some((tag, body)))
+    }
+
+    /// Write a message, [MessageIo::flush] should be called if the stream is
+    /// buffered.
+    pub async fn write_message(&mut self, tag: u8, body: &[u8]) -> Result<()> {
+        let mut buf = Vec::with_capacity(body.len() + 5);
+        encode_message(tag, body, &mut buf);
+        self.stream.write_all(&buf).await.context(WriteMessage)
+    }
+
+    /// Write a single byte response without length, which is used to reject
+    /// the ssl request.
+    pub async fn write_byte(&mut self, v: u8) -> Result<()> {
+        self.stream.write_u8(v).await.context(WriteMessage)
+    }
+
+    pub async fn flush(&mut self) -> Result<()> {
+        self.stream.flush().await.context(WriteMessage)
+    }
+
+    /// Read the length of the message, returns the length of the body.
+    async fn read_len(&mut self) -> Result<Option<usize>> {
+        let len = match self.stream.read_i32().await {
+            Ok(len) => len,
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e).context(ReadMessage),
+        };
+        // The length includes itself.
+        ensure!(
+            (4..=MAX_MESSAGE_LEN as i32).contains(&len),
+            InvalidMessage {
+                msg: format!("invalid message length {}", len),
+            }
+        );
+
+        Ok(Some(len as usize - 4))
+    }
+
+    async fn read_body(&mut self, len: usize) -> Result<Vec<u8>> {
+        let mut body = vec![0; len];
+        self.stream
+            .read_exact(&mut body)
+            .await
+            .context(ReadMessage)?;
+
+        Ok(body)
+    }
+}
+
+fn encode_message(tag: u8, body: &[u8], buf: &mut Vec<u8>) {
+    buf.push(tag);
+    buf.extend_from_slice(&(body.len() as i32 + 4).to_be_bytes());
+    buf.extend_from_slice(body);
+}
+
+fn write_cstring(buf: &mut Vec<u8>, v: &str) {
+    buf.extend_from_slice(v.as_bytes());
+    buf.push(0);
+}
+
+// Backend messages, returns the tag and body.
+
+pub fn authentication_ok() -> (u8, Vec<u8>) {
+    (b'R', 0i32.to_be_bytes().to_vec())
+}
+
+pub fn parameter_status(name: &str, value: &str) -> (u8, Vec<u8>) {
+    let mut buf = Vec::with_capacity(name.len() + value.len() + 2);
+    write_cstring(&mut buf, name);
+    write_cstring(&mut buf, value);
+
+    (b'S', buf)
+}
+
+pub fn backend_key_data(process_id: u32, secret_key: u32) -> (u8, Vec<u8>) {
+    let mut buf = Vec::with_capacity(8);
+    buf.extend_from_slice(&process_id.to_be_bytes());
+    buf.extend_from_slice(&secret_key.to_be_bytes());
+
+    (b'K', buf)
+}
+
+pub fn ready_for_query() -> (u8, Vec<u8>) {
+    (b'Z', vec![TRANSACTION_IDLE])
+}
+
+pub fn parse_complete() -> (u8, Vec<u8>) {
+    (b'1', Vec::new())
+}
+
+pub fn bind_complete() -> (u8, Vec<u8>) {
+    (b'2', Vec::new())
+}
+
+pub fn close_complete() -> (u8, Vec<u8>) {
+    (b'3', Vec::new())
+}
+
+pub fn no_data() -> (u8, Vec<u8>) {
+    (b'n', Vec::new())
+}
+
+pub fn empty_query_response() -> (u8, Vec<u8>) {
+    (b'I', Vec::new())
+}
+
+pub fn command_complete(tag: &str) -> (u8, Vec<u8>) {
+    let mut buf = Vec::with_capacity(tag.len() + 1);
+    write_cstring(&mut buf, tag);
+
+    (b'C', buf)
+}
+
+pub fn error_response(code: &str, msg: &str) -> (u8, Vec<u8>) {
+    let mut buf = Vec::with_capacity(msg.len() + 32);
+    for (field, value) in [(b'S', "ERROR"), (b'V', "ERROR"), (b'C', code), (b'M', msg)] {
+        buf.push(field);
+        write_cstring(&mut buf, value);
+    }
+    buf.push(0);
+
+    (b'E', buf)
+}
+
+pub fn parameter_description(param_types: &[u32]) -> (u8, Vec<u8>) {
+    let mut buf = Vec::with_capacity(2 + 4 * param_types.len());
+    buf.extend_from_slice(&(param_types.len() as i16).to_be_bytes());
+    for oid in param_types {
+        buf.extend_from_slice(&oid.to_be_bytes());
+    }
+
+    (b't', buf)
+}
+
+pub fn row_description(fields: &[FieldDescription]) -> (u8, Vec<u8>) {
+    let mut buf = Vec::with_capacity(2 + 32 * fields.len());
+    buf.extend_from_slice(&(fields.len() as i16).to_be_bytes());
+    for field in fields {
+        write_cstring(&mut buf, field.name);
+        // Oid of the table and attribute number of the column.
+        buf.extend_from_slice(&0u32.to_be_bytes());
+        buf.extend_from_slice(&0i16.to_be_bytes());
+        buf.extend_from_slice(&field.type_oid.to_be_bytes());
+        buf.extend_from_slice(&field.type_size.to_be_bytes());
+        // Type modifier.
+        buf.extend_from_slice(&(-1i32).to_be_bytes());
+        buf.extend_from_slice(&field.format.code().to_be_bytes());
+    }
+
+    (b'T', buf)
+}
+
+/// Builder of the DataRow message.
+pub struct DataRowBuilder {
+    buf: Vec<u8>,
+}
+
+impl DataRowBuilder {
+    pub fn new(num_columns: usize) -> Self {
+        let mut buf = Vec::with_capacity(2 + 8 * num_columns);
+        buf.extend_from_slice(&(num_columns as i16).to_be_bytes());
+
+        Self { buf }
+    }
+
+    pub fn append_null(&mut self) {
+        self.buf.extend_from_slice(&(-1i32).to_be_bytes());
+    }
+
+    pub fn append_value(&mut self, value: &[u8]) {
+        self.buf
+            .extend_from_slice(&(value.len() as i32).to_be_bytes());
+        self.buf.extend_from_slice(value);
+    }
+
+    pub fn build(self) -> (u8, Vec<u8>) {
+        (b'D', self.buf)
+    }
+}
+
+/// Reader of the body of a message.
+struct Reader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self { buf }
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        ensure!(
+            self.buf.len() >= len,
+            InvalidMessage {
+                msg: format!("expect {} bytes, remaining:{}", len, self.buf.len()),
+            }
+        );
+        let (bytes, remaining) = self.buf.split_at(len);
+        self.buf = remaining;
+
+        Ok(bytes)
+    }
+
+    fn read_u8(&mut self) -> Result<u8> {
+        self.read_bytes(1).map(|v| v[0])
+    }
+
+    fn read_i16(&mut self) -> Result<i16> {
+        let bytes = self.read_bytes(2)?;
+        Ok(i16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn read_i32(&mut self) -> Result<i32> {
+        let bytes = self.read_bytes(4)?;
+        Ok(i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn read_cstring(&mut self) -> Result<String> {
+        let len = self
+            .buf
+            .iter()
+            .position(|v| *v == 0)
+            .context(InvalidMessage {
+                msg: "string is not terminated",
+            })?;
+        let bytes = self.read_bytes(len)?;
+        // Skip the trailing zero.
+        self.buf = &self.buf[1..];
+
+        String::from_utf8(bytes.to_vec()).map_err(|e| {
+            InvalidMessage {
+                msg: format!("string is not utf8, err:{}", e),
+            }
+            .build()
+        })
+    }
+
+    fn read_formats(&mut self) -> Result<Vec<Format>> {
+        let num_formats = self.read_i16()?;
+        (0..num_formats)
+            .map(|_| self.read_i16().and_then(Format::from_code))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_startup() {
+        let mut body = Vec::new();
+        body.extend_from_slice(&PROTOCOL_VERSION.to_be_bytes());
+        write_cstring(&mut body, "user");
+        write_cstring(&mut body, "root");
+        write_cstring(&mut body, "database");
+        write_cstring(&mut body, "public");
+        body.push(0);
+
+        let params = match StartupMessage::decode(&body).unwrap() {
+            StartupMessage::Startup { params } => params,
+            v => panic!("Unexpected startup message:{:?}", v),
+        };
+        assert_eq!("root", params["user"]);
+        assert_eq!("public", params["database"]);
+
+        let body = SSL_REQUEST_CODE.to_be_bytes();
+        assert_eq!(
+            StartupMessage::SslRequest,
+            StartupMessage::decode(&body).unwrap()
+        );
+        assert!(StartupMessage::decode(&1i32.to_be_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_decode_extended_query() {
+        let mut body = Vec::new();
+        write_cstring(&mut body, "s1");
+        write_cstring(&mut body, "select * from t where a = $1");
+        body.extend_from_slice(&1i16.to_be_bytes());
+        body.extend_from_slice(&23u32.to_be_bytes());
+        assert_eq!(
+            Parse {
+                statement: "s1".to_string(),
+                query: "select * from t where a = $1".to_string(),
+                param_types: vec![23],
+            },
+            Parse::decode(&body).unwrap()
+        );
+
+        let mut body = Vec::new();
+        write_cstring(&mut body, "");
+        write_cstring(&mut body, "s1");
+        body.extend_from_slice(&1i16.to_be_bytes());
+        body.extend_from_slice(&1i16.to_be_bytes());
+        body.extend_from_slice(&2i16.to_be_bytes());
+        body.extend_from_slice(&4i32.to_be_bytes());
+        body.extend_from_slice(&7i32.to_be_bytes());
+        body.extend_from_slice(&(-1i32).to_be_bytes());
+        body.extend_from_slice(&0i16.to_be_bytes());
+        assert_eq!(
+            Bind {
+                portal: String::new(),
+                statement: "s1".to_string(),
+                param_formats: vec![Format::Binary],
+                params: vec![Some(7i32.to_be_bytes().to_vec()), None],
+                result_formats: vec![],
+            },
+            Bind::decode(&body).unwrap()
+        );
+
+        assert_eq!(
+            Target::Portal("p".to_string()),
+            Target::decode(b"Pp\0").unwrap()
+        );
+        assert!(Target::decode(b"Xp\0").is_err());
+
+        let mut body = b"p\0".to_vec();
+        body.extend_from_slice(&10i32.to_be_bytes());
+        assert_eq!(
+            Execute {
+                portal: "p".to_string(),
+                max_rows: 10,
+            },
+            Execute::decode(&body).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_format_of() {
+        assert_eq!(Format::Text, Format::of(&[], 3));
+        assert_eq!(Format::Binary, Format::of(&[Format::Binary], 3));
+        let formats = [Format::Text, Format::Binary];
+        assert_eq!(Format::Binary, Format::of(&formats, 1));
+    }
+
+    #[tokio::test]
+    async fn test_read_write_message() {
+        let mut io = MessageIo::new(std::io::Cursor::new(Vec::new()));
+        let (tag, body) = command_complete("SELECT 1");
+        io.write_message(tag, &body).await.unwrap();
+
+        let mut buf = Vec::new();
+        encode_message(tag, &body, &mut buf);
+        assert_eq!(&buf, io.stream.get_ref());
+        assert_eq!(b"C\0\0\0\x0dSELECT 1\0".to_vec(), buf);
+
+        let mut io = MessageIo::new(std::io::Cursor::new(buf));
+        assert_eq!(Some((tag, body)), io.read_message().await.unwrap());
+        assert!(io.read_message().await.unwrap().is_none());
+    }
+
+    #[test]
+    fn test_data_row() {
+        let mut builder = DataRowBuilder::new(2);
+        builder.append_value(b"ab");
+        builder.append_null();
+        let (tag, body) = builder.build();
+        assert_eq!(b'D', tag);
+        assert_eq!(
+            vec![0, 2, 0, 0, 0, 2, b'a', b'b', 0xff, 0xff, 0xff, 0xff],
+            body
+        );
+    }
+}
@@ -0,0 +1,403 @@
+// Copyright 2022 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Mapping between the datums and the types of postgresql
+
+use chrono::{Local, TimeZone};
+use common_types::datum::{Datum, DatumKind};
+use snafu::{Backtrace, OptionExt, Snafu};
+
+use crate::postgresql::protocol::Format;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display(
+        "Invalid parameter, idx:{}, msg:{}.\nBacktrace:\n{}",
+        idx,
+        msg,
+        backtrace
+    ))]
+    InvalidParam {
+        idx: usize,
+        msg: String,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "Unsupported binary parameter, idx:{}, oid:{}.\nBacktrace:\n{}",
+        idx,
+        oid,
+        backtrace
+    ))]
+    UnsupportedParam {
+        idx: usize,
+        oid: u32,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "Parameter is not bound, idx:{}, num_params:{}.\nBacktrace:\n{}",
+        idx,
+        num_params,
+        backtrace
+    ))]
+    ParamNotBound {
+        idx: usize,
+        num_params: usize,
+        backtrace: Backtrace,
+    },
+}
+
+define_result!(Error);
+
+// Oids of the types.
+pub const UNSPECIFIED: u32 = 0;
+pub const BOOL: u32 = 16;
+pub const BYTEA: u32 = 17;
+pub const INT8: u32 = 20;
+pub const INT2: u32 = 21;
+pub const INT4: u32 = 23;
+pub const TEXT: u32 = 25;
+pub const FLOAT4: u32 = 700;
+pub const FLOAT8: u32 = 701;
+pub const VARCHAR: u32 = 1043;
+pub const TIMESTAMPTZ: u32 = 1184;
+
+/// Microseconds between the unix epoch and the postgres epoch (2000-01-01).
+const POSTGRES_EPOCH_MICROS: i64 = 946_684_800_000_000;
+
+/// Returns the oid and size of the type of the `kind`, size of the variable
+/// length type is -1.
+///
+/// Postgresql has no unsigned integers, so the unsigned integers are mapped
+/// to the wider signed integers, except the `UInt64` which may overflow the
+/// `int8` in binary format.
+pub fn type_of(kind: DatumKind) -> (u32, i16) {
+    match kind {
+        DatumKind::Null => (TEXT, -1),
+        DatumKind::Timestamp => (TIMESTAMPTZ, 8),
+        DatumKind::Double => (FLOAT8, 8),
+        DatumKind::Float => (FLOAT4, 4),
+        DatumKind::Varbinary => (BYTEA, -1),
+        DatumKind::String => (VARCHAR, -1),
+        DatumKind::UInt64 | DatumKind::UInt32 | DatumKind::Int64 => (INT8, 8),
+        DatumKind::UInt16 | DatumKind::Int32 => (INT4, 4),
+        DatumKind::UInt8 | DatumKind::Int16 | DatumKind::Int8 => (INT2, 2),
+        DatumKind::Boolean => (BOOL, 1),
+    }
+}
+
+/// Encode the `datum` in the `format` of the type returned by [type_of],
+/// returns None if the datum is null.
+pub fn encode_datum(datum: &Datum, format: Format) -> Option<Vec<u8>> {
+    let value = match format {
+        Format::Text => match datum {
+            Datum::Null => return None,
+            Datum::Timestamp(v) => Local
+                .timestamp_millis(v.as_i64())
+                .format("%Y-%m-%d %H:%M:%S%.3f%:z")
+                .to_string()
+                .into_bytes(),
+            Datum::Double(v) => float_to_text(*v).into_bytes(),
+            Datum::Float(v) => float_to_text(f64::from(*v)).into_bytes(),
+            Datum::Varbinary(v) => {
+                let mut buf = Vec::with_capacity(2 + 2 * v.len());
+                buf.extend_from_slice(b"\\x");
+                for byte in v.iter() {
+                    buf.extend_from_slice(format!("{:02x}", byte).as_bytes());
+                }
+                buf
+            }
+            Datum::String(v) => v.as_bytes().to_vec(),
+            Datum::Boolean(v) => {
+                let text = if *v { "t" } else { "f" };
+                text.as_bytes().to_vec()
+            }
+            _ => datum.display_string().into_bytes(),
+        },
+        Format::Binary => match datum {
+            Datum::Null => return None,
+            Datum::Timestamp(v) => {
+                let micros = v.as_i64() * 1000 - POSTGRES_EPOCH_MICROS;
+                micros.to_be_bytes().to_vec()
+            }
+            Datum::Double(v) => v.to_be_bytes().to_vec(),
+            Datum::Float(v) => v.to_be_bytes().to_vec(),
+            Datum::Varbinary(v) => v.to_vec(),
+            Datum::String(v) => v.as_bytes().to_vec(),
+            Datum::UInt64(v) => (*v as i64).to_be_bytes().to_vec(),
+            Datum::UInt32(v) => i64::from(*v).to_be_bytes().to_vec(),
+            Datum::UInt16(v) => i32::from(*v).to_be_bytes().to_vec(),
+            Datum::UInt8(v) => i16::from(*v).to_be_bytes().to_vec(),
+            Datum::Int64(v) => v.to_be_bytes().to_vec(),
+            Datum::Int32(v) => v.to_be_bytes().to_vec(),
+            Datum::Int16(v) => v.to_be_bytes().to_vec(),
+            Datum::Int8(v) => i16::from(*v).to_be_bytes().to_vec(),
+            Datum::Boolean(v) => vec![u8::from(*v)],
+        },
+    };
+
+    Some(value)
+}
+
+fn float_to_text(v: f64) -> String {
+    if v == f64::INFINITY {
+        "Infinity".to_string()
+    } else if v == f64::NEG_INFINITY {
+        "-Infinity".to_string()
+    } else {
+        v.to_string()
+    }
+}
+
+/// Returns the number of the parameters (`$1`, `$2`...) in the `query`.
+pub fn num_params(query: &str) -> usize {
+    let mut num_params = 0;
+    // Never fails as the replacement always succeeds.
+    let _ = replace_params(query, |idx| {
+        num_params = num_params.max(idx + 1);
+        Ok(String::new())
+    });
+
+    num_params
+}
+
+/// Replace all the parameters of the `query` with null, so the query can be
+/// planned before binding the parameters.
+pub fn params_to_null(query: &str) -> String {
+    // Never fails as the replacement always succeeds.
+    replace_params(query, |_| Ok("NULL".to_string())).unwrap_or_else(|_| query.to_string())
+}
+
+/// Bind the parameters to the `query`, the parameters are replaced by the sql
+/// literals of their values.
+pub fn bind_params(
+    query: &str,
+    param_types: &[u32],
+    param_formats: &[Format],
+    params: &[Option<Vec<u8>>],
+) -> Result<String> {
+    let literals = params
+        .iter()
+        .enumerate()
+        .map(|(idx, value)| {
+            let oid = param_types.get(idx).copied().unwrap_or(UNSPECIFIED);
+            let format = Format::of(param_formats, idx);
+            param_to_literal(idx, oid, format, value.as_deref())
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    replace_params(query, |idx| {
+        literals.get(idx).cloned().context(ParamNotBound {
+            idx,
+            num_params: literals.len(),
+        })
+    })
+}
+
+/// Convert the value of the parameter into a sql literal.
+fn param_to_literal(idx: usize, oid: u32, format: Format, value: Option<&[u8]>) -> Result<String> {
+    let value = match value {
+        Some(v) => v,
+        None => return Ok("NULL".to_string()),
+    };
+
+    let literal = match format {
+        Format::Text => {
+            let text = std::str::from_utf8(value).map_err(|e| {
+                InvalidParam {
+                    idx,
+                    msg: format!("value is not utf8, err:{}", e),
+                }
+                .build()
+            })?;
+            match oid {
+                BOOL => match text {
+                    "t" | "true" | "TRUE" | "1" => "true".to_string(),
+                    "f" | "false" | "FALSE" | "0" => "false".to_string(),
+                    _ => {
+                        return InvalidParam {
+                            idx,
+                            msg: format!("invalid bool {}", text),
+                        }
+                        .fail()
+                    }
+                },
+                INT2 | INT4 | INT8 | FLOAT4 | FLOAT8 => {
+                    if !is_number(text) {
+                        return InvalidParam {
+                            idx,
+                            msg: format!("invalid number {}", text),
+                        }
+                        .fail();
+                    }
+                    text.to_string()
+                }
+                // The type of the parameter is unknown, treats it as a number if
+                // possible.
+                UNSPECIFIED if is_number(text) => text.to_string(),
+                _ => quote_string(text),
+            }
+        }
+        Format::Binary => match oid {
+            BOOL => (value.first() == Some(&1)).to_string(),
+            INT2 => i16::from_be_bytes(fixed_bytes(idx, value)?).to_string(),
+            INT4 => i32::from_be_bytes(fixed_bytes(idx, value)?).to_string(),
+            INT8 => i64::from_be_bytes(fixed_bytes(idx, value)?).to_string(),
+            FLOAT4 => f32::from_be_bytes(fixed_bytes(idx, value)?).to_string(),
+            FLOAT8 => f64::from_be_bytes(fixed_bytes(idx, value)?).to_string(),
+            TEXT | VARCHAR | UNSPECIFIED => {
+                let text = std::str::from_utf8(value).map_err(|e| {
+                    InvalidParam {
+                        idx,
+                        msg: format!("value is not utf8, err:{}", e),
+                    }
+                    .build()
+                })?;
+                quote_string(text)
+            }
+            _ => return UnsupportedParam { idx, oid }.fail(),
+        },
+    };
+
+    Ok(literal)
+}
+
+fn fixed_bytes<const N: usize>(idx: usize, value: &[u8]) -> Result<[u8; N]> {
+    let mut bytes = [0; N];
+    if value.len() != N {
+        return InvalidParam {
+            idx,
+            msg: format!("expect {} bytes, given:{}", N, value.len()),
+        }
+        .fail();
+    }
+    bytes.copy_from_slice(value);
+
+    Ok(bytes)
+}
+
+fn is_number(text: &str) -> bool {
+    text.parse::<i64>().is_ok() || text.parse::<f64>().map_or(false, |v| v.is_finite())
+}
+
+fn quote_string(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "''"))
+}
+
+/// Replace the parameters outside the quoted strings and identifiers of the
+/// `query`, `f` is called with the index (starts from 0) of each parameter.
+fn replace_params(query: &str, mut f: impl FnMut(usize) -> Result<String>) -> Result<String> {
+    let mut output = String::with_capacity(query.len());
+    let mut quote = None;
+    let mut chars = query.char_indices().peekable();
+    while let Some((_, c)) = chars.next() {
+        match (quote, c) {
+            (None, '\'') | (None, '"') => quote = Some(c),
+            (Some(q), _) if q == c => quote = None,
+            (None, '$') => {
+                let mut num = String::new();
+                while let Some(&(_, c)) = chars.peek() {
+                    if !c.is_ascii_digit() {
+                        break;
+                    }
+                    num.push(c);
+                    chars.next();
+                }
+                match num.parse::<usize>() {
+                    Ok(n) if n > 0 => output.push_str(&f(n - 1)?),
+                    _ => {
+                        output.push(c);
+                        output.push_str(&num);
+                    }
+                }
+                continue;
+            }
+            _ => (),
+        }
+        output.push(c);
+    }
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use common_types::time::Timestamp;
+
+    use super::*;
+
+    #[test]
+    fn test_bind_params() {
+        let query = "select * from t where a = $1 and b = '$2' and c > $2 and d = $3";
+        assert_eq!(3, num_params(query));
+        assert_eq!(
+            "select * from t where a = NULL and b = '$2' and c > NULL and d = NULL",
+            params_to_null(query)
+        );
+
+        let params = vec![
+            Some(b"it's".to_vec()),
+            Some(10i32.to_be_bytes().to_vec()),
+            None,
+        ];
+        let bound = bind_params(
+            query,
+            &[TEXT, INT4],
+            &[Format::Text, Format::Binary, Format::Text],
+            &params,
+        )
+        .unwrap();
+        assert_eq!(
+            "select * from t where a = 'it''s' and b = '$2' and c > 10 and d = NULL",
+            bound
+        );
+
+        // Unspecified types.
+        let bound = bind_params(
+            "select $1, $2",
+            &[],
+            &[],
+            &[Some(b"1.5".to_vec()), Some(b"a".to_vec())],
+        )
+        .unwrap();
+        assert_eq!("select 1.5, 'a'", bound);
+
+        assert!(bind_params("select $1", &[INT4], &[], &[Some(b"a".to_vec())]).is_err());
+        assert!(bind_params("select $2", &[], &[], &[None]).is_err());
+    }
+
+    #[test]
+    fn test_encode_datum() {
+        assert_eq!(None, encode_datum(&Datum::Null, Format::Text));
+        assert_eq!(
+            Some(b"t".to_vec()),
+            encode_datum(&Datum::Boolean(true), Format::Text)
+        );
+        assert_eq!(
+            Some(b"\\x0aff".to_vec()),
+            encode_datum(&Datum::Varbinary(vec![10, 255].into()), Format::Text)
+        );
+        assert_eq!(
+            Some(b"Infinity".to_vec()),
+            encode_datum(&Datum::Double(f64::INFINITY), Format::Text)
+        );
+        assert_eq!(
+            Some(7i16.to_be_bytes().to_vec()),
+            encode_datum(&Datum::UInt8(7), Format::Binary)
+        );
+
+        // 2000-01-01 00:00:01.
+        let ts = Datum::Timestamp(Timestamp::new(946_684_801_000));
+        assert_eq!(
+            Some(1_000_000i64.to_be_bytes().to_vec()),
+            encode_datum(&ts, Format::Binary)
+        );
+    }
+
+    #[test]
+    fn test_type_of() {
+        assert_eq!((INT8, 8), type_of(DatumKind::UInt32));
+        assert_eq!((VARCHAR, -1), type_of(DatumKind::String));
+        assert_eq!((TIMESTAMPTZ, 8), type_of(DatumKind::Timestamp));
+    }
+}
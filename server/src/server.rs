@@ -6,6 +6,8 @@ use std::sync::Arc;
 
 use catalog::manager::Manager as CatalogManager;
 use grpcio::Environment;
+use interpreters::registry::QueryRegistryRef;
+use object_store::ObjectStoreRef;
 use query_engine::executor::Executor as QueryExecutor;
 use snafu::{Backtrace, OptionExt, ResultExt, Snafu};
 use table_engine::engine::{EngineRuntimes, TableEngineRef};
@@ -13,10 +15,12 @@ use udf::registry::FunctionRegistryRef;
 
 use crate::{
     config::Config,
+    flight,
     grpc::{self, RpcServices},
     http::{self, Service},
     instance::{Instance, InstanceRef},
     limiter::Limiter,
+    mysql, postgresql,
 };
 
 #[derive(Debug, Snafu)]
@@ -50,6 +54,15 @@ pub enum Error {
 
     #[snafu(display("Failed to start grpc service, err:{}", source))]
     StartGrpcService { source: crate::grpc::Error },
+
+    #[snafu(display("Failed to start flight service, err:{}", source))]
+    StartFlightService { source: crate::flight::Error },
+
+    #[snafu(display("Failed to start mysql service, err:{}", source))]
+    StartMysqlService { source: crate::mysql::Error },
+
+    #[snafu(display("Failed to start postgresql service, err:{}", source))]
+    StartPostgresqlService { source: crate::postgresql::Error },
 }
 
 define_result!(Error);
@@ -59,12 +72,24 @@ define_result!(Error);
 pub struct Server<C, Q> {
     http_service: Service<C, Q>,
     rpc_services: RpcServices,
+    flight_service: Option<flight::Service>,
+    mysql_service: Option<mysql::Service>,
+    postgresql_service: Option<postgresql::Service>,
 }
 
 impl<C, Q> Server<C, Q> {
     pub fn stop(mut self) {
         self.rpc_services.shutdown();
         self.http_service.stop();
+        if let Some(flight_service) = self.flight_service {
+            flight_service.stop();
+        }
+        if let Some(mysql_service) = self.mysql_service {
+            mysql_service.stop();
+        }
+        if let Some(postgresql_service) = self.postgresql_service {
+            postgresql_service.stop();
+        }
     }
 
     pub async fn start(&mut self) -> Result<()> {
@@ -81,10 +106,12 @@ pub struct Builder<C, Q> {
     table_engine: Option<TableEngineRef>,
     function_registry: Option<FunctionRegistryRef>,
     limiter: Limiter,
+    backup_store: Option<ObjectStoreRef>,
 }
 
 impl<C: CatalogManager + 'static, Q: QueryExecutor + 'static> Builder<C, Q> {
     pub fn new(config: Config) -> Self {
+        let limiter = Limiter::new(config.limiter.clone());
         Self {
             config,
             runtimes: None,
@@ -92,7 +119,8 @@ impl<C: CatalogManager + 'static, Q: QueryExecutor + 'static> Builder<C, Q> {
             query_executor: None,
             table_engine: None,
             function_registry: None,
-            limiter: Limiter::default(),
+            limiter,
+            backup_store: None,
         }
     }
 
@@ -126,6 +154,11 @@ impl<C: CatalogManager + 'static, Q: QueryExecutor + 'static> Builder<C, Q> {
         self
     }
 
+    pub fn backup_store(mut self, val: ObjectStoreRef) -> Self {
+        self.backup_store = Some(val);
+        self
+    }
+
     /// Build and run the server
     pub fn build(self) -> Result<Server<C, Q>> {
         // Build runtimes
@@ -142,9 +175,25 @@ impl<C: CatalogManager + 'static, Q: QueryExecutor + 'static> Builder<C, Q> {
             table_engine,
             function_registry,
             limiter: self.limiter,
+            backup_store: self.backup_store,
+            query_registry: QueryRegistryRef::default(),
+            query_timeout: self.config.query_timeout.map(|v| v.0),
         };
         let instance = InstanceRef::new(instance);
 
+        let meta_client_config = self.config.meta_client;
+        let env = Arc::new(Environment::new(self.config.grpc_server_cq_count));
+        let rpc_services = grpc::Builder::new()
+            .bind_addr(self.config.bind_addr.clone())
+            .port(self.config.grpc_port)
+            .meta_client_config(meta_client_config)
+            .env(env)
+            .runtimes(runtimes.clone())
+            .instance(instance.clone())
+            .route_rules(self.config.route_rules)
+            .build()
+            .context(BuildGrpcService)?;
+
         // Create http config
         let http_config = http::Config {
             ip: self.config.bind_addr.clone(),
@@ -155,25 +204,68 @@ impl<C: CatalogManager + 'static, Q: QueryExecutor + 'static> Builder<C, Q> {
         let http_service = http::Builder::new(http_config)
             .runtimes(runtimes.clone())
             .instance(instance.clone())
+            .meta_client(rpc_services.meta_client())
             .build()
             .context(StartHttpService)?;
 
-        let meta_client_config = self.config.meta_client;
-        let env = Arc::new(Environment::new(self.config.grpc_server_cq_count));
-        let rpc_services = grpc::Builder::new()
-            .bind_addr(self.config.bind_addr)
-            .port(self.config.grpc_port)
-            .meta_client_config(meta_client_config)
-            .env(env)
-            .runtimes(runtimes)
-            .instance(instance)
-            .route_rules(self.config.route_rules)
-            .build()
-            .context(BuildGrpcService)?;
+        // Start flight service if its port is set
+        let flight_service = match self.config.flight_port {
+            Some(port) => {
+                let flight_config = flight::Config {
+                    ip: self.config.bind_addr.clone(),
+                    port,
+                };
+                let service = flight::Builder::new(flight_config)
+                    .runtimes(runtimes.clone())
+                    .instance(instance.clone())
+                    .build()
+                    .context(StartFlightService)?;
+                Some(service)
+            }
+            None => None,
+        };
+
+        // Start mysql service if its port is set
+        let mysql_service = match self.config.mysql_port {
+            Some(port) => {
+                let mysql_config = mysql::Config {
+                    ip: self.config.bind_addr.clone(),
+                    port,
+                    max_allowed_packet: self.config.mysql_max_allowed_packet.as_bytes() as usize,
+                };
+                let service = mysql::Builder::new(mysql_config)
+                    .runtimes(runtimes.clone())
+                    .instance(instance.clone())
+                    .build()
+                    .context(StartMysqlService)?;
+                Some(service)
+            }
+            None => None,
+        };
+
+        // Start postgresql service if its port is set
+        let postgresql_service = match self.config.postgresql_port {
+            Some(port) => {
+                let postgresql_config = postgresql::Config {
+                    ip: self.config.bind_addr,
+                    port,
+                };
+                let service = postgresql::Builder::new(postgresql_config)
+                    .runtimes(runtimes)
+                    .instance(instance)
+                    .build()
+                    .context(StartPostgresqlService)?;
+                Some(service)
+            }
+            None => None,
+        };
 
         let server = Server {
             http_service,
             rpc_services,
+            flight_service,
+            mysql_service,
+            postgresql_service,
         };
         Ok(server)
     }
@@ -3,7 +3,8 @@
 //! SQL statement
 
 use sqlparser::ast::{
-    ColumnDef, ObjectName, SqlOption, Statement as SqlStatement, TableConstraint,
+    ColumnDef, Expr, Ident, ObjectName, Query, SqlOption, Statement as SqlStatement,
+    TableConstraint,
 };
 
 /// Statement representations
@@ -22,6 +23,12 @@ pub enum Statement {
     /// SHOW CREATE TABLE
     ShowCreate(ShowCreate),
     Exists(ExistsTable),
+    /// CREATE ROLLUP
+    CreateRollup(CreateRollup),
+    /// SHOW PROCESSLIST
+    ShowProcessList,
+    /// KILL QUERY
+    KillQuery(KillQuery),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -39,10 +46,53 @@ pub struct CreateTable {
     pub columns: Vec<ColumnDef>,
     pub engine: String,
     pub constraints: Vec<TableConstraint>,
+    /// Partition clause in `PARTITION BY`.
+    pub partition: Option<Partition>,
     /// Table options in `WITH`.
     pub options: Vec<SqlOption>,
 }
 
+#[derive(Debug, PartialEq)]
+pub struct CreateRollup {
+    /// Create if not exists
+    pub if_not_exists: bool,
+    /// Name of the rollup, which is also the name of the target table
+    pub name: ObjectName,
+    /// Options of the target table in `WITH`.
+    pub options: Vec<SqlOption>,
+    /// Query in `AS` which aggregates the source table.
+    pub query: Box<Query>,
+}
+
+/// Partition clause of CREATE TABLE
+#[derive(Debug, PartialEq)]
+pub enum Partition {
+    /// `PARTITION BY HASH (columns) PARTITIONS num`
+    Hash {
+        columns: Vec<Ident>,
+        partition_num: u64,
+    },
+    /// `PARTITION BY RANGE (column) (PARTITION name VALUES LESS THAN (value),
+    /// ...)`
+    Range {
+        column: Ident,
+        definitions: Vec<PartitionDefinition>,
+    },
+    /// `PARTITION BY LIST (column) (PARTITION name VALUES IN (values), ...)`
+    List {
+        column: Ident,
+        definitions: Vec<PartitionDefinition>,
+    },
+}
+
+#[derive(Debug, PartialEq)]
+pub struct PartitionDefinition {
+    pub name: Ident,
+    /// Upper bound of range partition (empty for `MAXVALUE`) or values of
+    /// list partition.
+    pub values: Vec<Expr>,
+}
+
 #[derive(Debug, PartialEq)]
 pub struct DropTable {
     /// Table name
@@ -78,3 +128,9 @@ pub struct ShowCreate {
 pub struct ExistsTable {
     pub table_name: ObjectName,
 }
+
+#[derive(Debug, PartialEq)]
+pub struct KillQuery {
+    /// Request id of the query to kill.
+    pub request_id: u64,
+}
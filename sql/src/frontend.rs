@@ -14,7 +14,7 @@ use crate::{
     parser::Parser,
     plan::Plan,
     planner::Planner,
-    promql::{ColumnNames, Expr},
+    promql::{self, ColumnNames, EvalParams, Expr, Selector},
     provider::MetaProvider,
 };
 
@@ -33,6 +33,12 @@ pub enum Error {
 
     #[snafu(display("Invalid prom request, err:{}", source))]
     InvalidPromRequest { source: crate::promql::Error },
+
+    #[snafu(display("Invalid promql, query:{}, err:{}", query, source))]
+    InvalidPromQL {
+        query: String,
+        source: crate::promql::ParseError,
+    },
 }
 
 define_result!(Error);
@@ -86,6 +92,26 @@ impl<P> Frontend<P> {
     ) -> Result<Expr> {
         req.take_expr().try_into().context(InvalidPromRequest)
     }
+
+    /// Parse the promql text and returns the Expr
+    pub fn parse_promql_query(
+        &self,
+        _ctx: &mut Context,
+        query: &str,
+        params: EvalParams,
+    ) -> Result<Expr> {
+        promql::parse_expr(query, params).context(InvalidPromQL { query })
+    }
+
+    /// Parse the promql series selector
+    pub fn parse_promql_selector(
+        &self,
+        _ctx: &mut Context,
+        query: &str,
+        params: EvalParams,
+    ) -> Result<Selector> {
+        promql::parse_selector(query, params).context(InvalidPromQL { query })
+    }
 }
 
 impl<P: MetaProvider> Frontend<P> {
@@ -105,4 +131,30 @@ impl<P: MetaProvider> Frontend<P> {
 
         planner.promql_expr_to_plan(expr).context(CreatePlan)
     }
+
+    /// Create logical plan for the promql expr evaluated by ourselves, see
+    /// [Expr::to_aligned_plan].
+    pub fn promql_expr_to_aligned_plan(
+        &self,
+        ctx: &mut Context,
+        expr: Expr,
+    ) -> Result<(Plan, Arc<ColumnNames>)> {
+        let planner = Planner::new(&self.provider, ctx.request_id, ctx.read_parallelism);
+
+        planner
+            .promql_expr_to_aligned_plan(expr)
+            .context(CreatePlan)
+    }
+
+    /// Create logical plan to find out series matched by the selector, returns
+    /// the plan and tag keys of the table.
+    pub fn promql_series_to_plan(
+        &self,
+        ctx: &mut Context,
+        selector: Selector,
+    ) -> Result<(Plan, Vec<String>)> {
+        let planner = Planner::new(&self.provider, ctx.request_id, ctx.read_parallelism);
+
+        planner.promql_series_to_plan(selector).context(CreatePlan)
+    }
 }
@@ -15,5 +15,6 @@ pub mod plan;
 pub mod planner;
 pub mod promql;
 pub mod provider;
+pub mod rollup;
 #[cfg(any(test, feature = "test"))]
 pub mod tests;
@@ -15,8 +15,9 @@ use sqlparser::{
 use table_engine::ANALYTIC_ENGINE_TYPE;
 
 use crate::ast::{
-    AlterAddColumn, AlterModifySetting, CreateTable, DescribeTable, DropTable, ExistsTable,
-    ShowCreate, ShowCreateObject, Statement,
+    AlterAddColumn, AlterModifySetting, CreateRollup, CreateTable, DescribeTable, DropTable,
+    ExistsTable, KillQuery, Partition, PartitionDefinition, ShowCreate, ShowCreateObject,
+    Statement,
 };
 
 define_result!(ParserError);
@@ -34,6 +35,18 @@ const COMMENT: &str = "COMMENT";
 const UNSIGN: &str = "UNSIGN";
 const MODIFY: &str = "MODIFY";
 const SETTING: &str = "SETTING";
+const PARTITION: &str = "PARTITION";
+const PARTITIONS: &str = "PARTITIONS";
+const HASH: &str = "HASH";
+const RANGE: &str = "RANGE";
+const LIST: &str = "LIST";
+const LESS: &str = "LESS";
+const THAN: &str = "THAN";
+const MAXVALUE: &str = "MAXVALUE";
+const ROLLUP: &str = "ROLLUP";
+const PROCESSLIST: &str = "PROCESSLIST";
+const KILL: &str = "KILL";
+const QUERY: &str = "QUERY";
 
 macro_rules! is_custom_column {
     ($name: ident) => {
@@ -171,6 +184,10 @@ impl<'a> Parser<'a> {
                         self.parser.next_token();
                         self.parse_exists()
                     }
+                    _ if w.value.to_uppercase() == KILL => {
+                        self.parser.next_token();
+                        self.parse_kill()
+                    }
                     _ => {
                         // use the native parser
                         Ok(Statement::Standard(Box::new(
@@ -222,9 +239,20 @@ impl<'a> Parser<'a> {
             .is_some()
         {
             Ok(self.parse_show_create()?)
+        } else if self.consume_token(PROCESSLIST) {
+            Ok(Statement::ShowProcessList)
         } else {
-            self.expected("create", self.parser.peek_token())
+            self.expected("create or processlist", self.parser.peek_token())
+        }
+    }
+
+    pub fn parse_kill(&mut self) -> Result<Statement> {
+        if !self.consume_token(QUERY) {
+            return self.expected("query", self.parser.peek_token());
         }
+        let request_id = self.parser.parse_literal_uint()?;
+
+        Ok(Statement::KillQuery(KillQuery { request_id }))
     }
 
     fn parse_show_create(&mut self) -> Result<Statement> {
@@ -281,6 +309,10 @@ impl<'a> Parser<'a> {
 
     // Parse a SQL CREATE statement
     pub fn parse_create(&mut self) -> Result<Statement> {
+        if self.consume_token(ROLLUP) {
+            return self.parse_create_rollup();
+        }
+
         self.parser.expect_keyword(Keyword::TABLE)?;
         let if_not_exists =
             self.parser
@@ -288,6 +320,7 @@ impl<'a> Parser<'a> {
         let table_name = self.parser.parse_object_name()?;
         let (columns, constraints) = self.parse_columns()?;
         let engine = self.parse_table_engine()?;
+        let partition = self.parse_partition()?;
         let options = self.parser.parse_options(Keyword::WITH)?;
 
         Ok(Statement::Create(CreateTable {
@@ -296,10 +329,134 @@ impl<'a> Parser<'a> {
             columns,
             engine,
             constraints,
+            partition,
+            options,
+        }))
+    }
+
+    // Parse a CREATE ROLLUP statement, example:
+    // CREATE ROLLUP IF NOT EXISTS cpu_1m WITH (ttl='365d') AS
+    //     SELECT time_bucket(ts, 'PT1M'), host, avg(usage) FROM cpu GROUP BY 1, 2
+    fn parse_create_rollup(&mut self) -> Result<Statement> {
+        let if_not_exists =
+            self.parser
+                .parse_keywords(&[Keyword::IF, Keyword::NOT, Keyword::EXISTS]);
+        let name = self.parser.parse_object_name()?;
+        let options = self.parser.parse_options(Keyword::WITH)?;
+        self.parser.expect_keyword(Keyword::AS)?;
+        let query = self.parser.parse_query()?;
+
+        Ok(Statement::CreateRollup(CreateRollup {
+            if_not_exists,
+            name,
             options,
+            query: Box::new(query),
         }))
     }
 
+    // Parse the optional partition clause, examples:
+    // PARTITION BY HASH(c1, c2) PARTITIONS 4
+    // PARTITION BY RANGE(ts) (PARTITION p0 VALUES LESS THAN (1000),
+    //     PARTITION p1 VALUES LESS THAN MAXVALUE)
+    // PARTITION BY LIST(c1) (PARTITION p0 VALUES IN ('a', 'b'),
+    //     PARTITION p1 VALUES IN ('c'))
+    fn parse_partition(&mut self) -> Result<Option<Partition>> {
+        if !self.consume_token(PARTITION) {
+            return Ok(None);
+        }
+        self.parser.expect_keyword(Keyword::BY)?;
+
+        let partition = if self.consume_token(HASH) {
+            let columns = self.parser.parse_parenthesized_column_list(Mandatory)?;
+            if !self.consume_token(PARTITIONS) {
+                return self.expected(PARTITIONS, self.parser.peek_token());
+            }
+            let partition_num = self.parser.parse_literal_uint()?;
+
+            Partition::Hash {
+                columns,
+                partition_num,
+            }
+        } else if self.consume_token(RANGE) {
+            let column = self.parse_partition_column()?;
+            let definitions = self.parse_partition_definitions(true)?;
+
+            Partition::Range {
+                column,
+                definitions,
+            }
+        } else if self.consume_token(LIST) {
+            let column = self.parse_partition_column()?;
+            let definitions = self.parse_partition_definitions(false)?;
+
+            Partition::List {
+                column,
+                definitions,
+            }
+        } else {
+            return self.expected("HASH, RANGE or LIST", self.parser.peek_token());
+        };
+
+        Ok(Some(partition))
+    }
+
+    fn parse_partition_column(&mut self) -> Result<Ident> {
+        let mut columns = self.parser.parse_parenthesized_column_list(Mandatory)?;
+        if columns.len() != 1 {
+            return parser_err!("Expected exactly one partition column");
+        }
+
+        Ok(columns.remove(0))
+    }
+
+    fn parse_partition_definitions(&mut self, is_range: bool) -> Result<Vec<PartitionDefinition>> {
+        self.parser.expect_token(&Token::LParen)?;
+
+        let mut definitions = Vec::new();
+        loop {
+            if !self.consume_token(PARTITION) {
+                return self.expected(PARTITION, self.parser.peek_token());
+            }
+            let name = self.parser.parse_identifier()?;
+            self.parser.expect_keyword(Keyword::VALUES)?;
+
+            let values = if is_range {
+                if !(self.consume_token(LESS) && self.consume_token(THAN)) {
+                    return self.expected("LESS THAN", self.parser.peek_token());
+                }
+
+                // Both `MAXVALUE` and `(MAXVALUE)` are allowed.
+                if self.consume_token(MAXVALUE) {
+                    Vec::new()
+                } else {
+                    self.parser.expect_token(&Token::LParen)?;
+                    let values = if self.consume_token(MAXVALUE) {
+                        Vec::new()
+                    } else {
+                        vec![self.parser.parse_expr()?]
+                    };
+                    self.parser.expect_token(&Token::RParen)?;
+                    values
+                }
+            } else {
+                self.parser.expect_keyword(Keyword::IN)?;
+                self.parser.expect_token(&Token::LParen)?;
+                let values = self.parser.parse_comma_separated(SqlParser::parse_expr)?;
+                self.parser.expect_token(&Token::RParen)?;
+                values
+            };
+
+            definitions.push(PartitionDefinition { name, values });
+
+            if !self.parser.consume_token(&Token::Comma) {
+                self.parser.expect_token(&Token::RParen)?;
+                break;
+            }
+        }
+
+        Ok(definitions)
+    }
+
     pub fn parse_drop(&mut self) -> Result<Statement> {
         self.parser.expect_keyword(Keyword::TABLE)?;
         let if_exists = self.parser.parse_keywords(&[Keyword::IF, Keyword::EXISTS]);
@@ -491,7 +648,7 @@ impl<'a> Parser<'a> {
 
 #[cfg(test)]
 mod tests {
-    use sqlparser::ast::{DataType, Ident, ObjectName, Value};
+    use sqlparser::ast::{DataType, Expr, Ident, ObjectName, Value};
 
     use super::*;
 
@@ -568,6 +725,7 @@ mod tests {
             columns: vec![make_column_def("c1", DataType::Double)],
             engine: table_engine::ANALYTIC_ENGINE_TYPE.to_string(),
             constraints: vec![],
+            partition: None,
             options: vec![],
         });
         expect_parse_ok(sql, expected).unwrap();
@@ -584,6 +742,7 @@ mod tests {
             ],
             engine: "XX".to_string(),
             constraints: vec![],
+            partition: None,
             options: vec![],
         });
         expect_parse_ok(sql, expected).unwrap();
@@ -811,4 +970,123 @@ mod tests {
             expect_parse_ok(sql, expected).unwrap()
         }
     }
+
+    #[test]
+    fn test_process_list() {
+        expect_parse_ok("SHOW PROCESSLIST", Statement::ShowProcessList).unwrap();
+        expect_parse_ok("show processlist;", Statement::ShowProcessList).unwrap();
+
+        let expected = Statement::KillQuery(KillQuery { request_id: 42 });
+        expect_parse_ok("KILL QUERY 42", expected).unwrap();
+        let expected = Statement::KillQuery(KillQuery { request_id: 7 });
+        expect_parse_ok("kill query 7;", expected).unwrap();
+
+        expect_parse_error("KILL 42", "Expected query");
+        expect_parse_error("KILL QUERY abc", "Expected literal int");
+        expect_parse_error("SHOW TABLES", "Expected create or processlist");
+    }
+
+    #[test]
+    fn test_create_table_partition() {
+        let create_table_partition = |sql: &str| match Parser::parse_sql(sql).unwrap().remove(0) {
+            Statement::Create(v) => v.partition,
+            _ => panic!("failed"),
+        };
+
+        let sql = "CREATE TABLE t(c1 string tag, c2 string tag, ts timestamp, TIMESTAMP KEY(ts)) \
+                   PARTITION BY HASH(c1, c2) PARTITIONS 4";
+        let expected = Partition::Hash {
+            columns: vec![Ident::new("c1"), Ident::new("c2")],
+            partition_num: 4,
+        };
+        assert_eq!(Some(expected), create_table_partition(sql));
+
+        let sql = "CREATE TABLE t(c1 string tag, ts timestamp, TIMESTAMP KEY(ts)) ENGINE=Analytic \
+                   PARTITION BY RANGE(ts) (PARTITION p0 VALUES LESS THAN (1000), \
+                   PARTITION p1 VALUES LESS THAN (MAXVALUE), PARTITION p2 VALUES LESS THAN MAXVALUE) \
+                   WITH (ttl='7d')";
+        let expected = Partition::Range {
+            column: Ident::new("ts"),
+            definitions: vec![
+                PartitionDefinition {
+                    name: Ident::new("p0"),
+                    values: vec![Expr::Value(Value::Number("1000".to_string(), false))],
+                },
+                PartitionDefinition {
+                    name: Ident::new("p1"),
+                    values: vec![],
+                },
+                PartitionDefinition {
+                    name: Ident::new("p2"),
+                    values: vec![],
+                },
+            ],
+        };
+        assert_eq!(Some(expected), create_table_partition(sql));
+
+        let sql = "CREATE TABLE t(c1 string tag, ts timestamp, TIMESTAMP KEY(ts)) \
+                   PARTITION BY LIST(c1) (PARTITION p0 VALUES IN ('a', 'b'), PARTITION p1 VALUES IN ('c'))";
+        let expected = Partition::List {
+            column: Ident::new("c1"),
+            definitions: vec![
+                PartitionDefinition {
+                    name: Ident::new("p0"),
+                    values: vec![
+                        Expr::Value(Value::SingleQuotedString("a".to_string())),
+                        Expr::Value(Value::SingleQuotedString("b".to_string())),
+                    ],
+                },
+                PartitionDefinition {
+                    name: Ident::new("p1"),
+                    values: vec![Expr::Value(Value::SingleQuotedString("c".to_string()))],
+                },
+            ],
+        };
+        assert_eq!(Some(expected), create_table_partition(sql));
+
+        // Error cases.
+        expect_parse_error(
+            "CREATE TABLE t(c1 string, ts timestamp) PARTITION BY KEY(c1)",
+            "Expected HASH, RANGE or LIST",
+        );
+        expect_parse_error(
+            "CREATE TABLE t(c1 string, ts timestamp) PARTITION BY HASH(c1)",
+            "Expected PARTITIONS",
+        );
+        expect_parse_error(
+            "CREATE TABLE t(c1 string, ts timestamp) PARTITION BY RANGE(c1, ts) \
+             (PARTITION p0 VALUES LESS THAN (1))",
+            "Expected exactly one partition column",
+        );
+        expect_parse_error(
+            "CREATE TABLE t(c1 string, ts timestamp) PARTITION BY LIST(c1) \
+             (PARTITION p0 VALUES LESS THAN (1))",
+            "Expected IN",
+        );
+    }
+
+    #[test]
+    fn test_create_rollup() {
+        let sql = "CREATE ROLLUP IF NOT EXISTS cpu_1m WITH (ttl='365d') AS \
+                   SELECT time_bucket(ts, 'PT1M'), host, avg(usage) FROM cpu GROUP BY 1, 2";
+        let statements = Parser::parse_sql(sql).unwrap();
+        assert_eq!(statements.len(), 1);
+        match &statements[0] {
+            Statement::CreateRollup(v) => {
+                assert!(v.if_not_exists);
+                assert_eq!(make_object_name("cpu_1m"), v.name);
+                assert_eq!(1, v.options.len());
+                assert_eq!(
+                    "SELECT time_bucket(ts, 'PT1M'), host, avg(usage) FROM cpu GROUP BY 1, 2",
+                    v.query.to_string()
+                );
+            }
+            _ => panic!("failed"),
+        }
+
+        expect_parse_error(
+            "CREATE ROLLUP cpu_1m SELECT avg(usage) FROM cpu",
+            "Expected AS",
+        );
+    }
 }
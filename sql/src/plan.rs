@@ -10,10 +10,10 @@ use std::{
 };
 
 use arrow_deps::datafusion::logical_plan::LogicalPlan as DataFusionLogicalPlan;
-use common_types::{column_schema::ColumnSchema, row::RowGroup, schema::Schema};
+use common_types::{column_schema::ColumnSchema, row::RowGroup, schema::Schema, time::TimeRange};
 use common_util::define_result;
 use snafu::Snafu;
-use table_engine::table::TableRef;
+use table_engine::{partition::PartitionInfo, rollup::Rollup, table::TableRef};
 
 use crate::{ast::ShowCreateObject, container::TableContainer};
 
@@ -42,6 +42,8 @@ pub enum Plan {
     Query(QueryPlan),
     // TODO(yingwen): Other sql command
     Insert(InsertPlan),
+    /// Delete plan
+    Delete(DeletePlan),
     /// Create table plan
     Create(CreateTablePlan),
     /// Drop table plan
@@ -54,6 +56,12 @@ pub enum Plan {
     ShowCreate(ShowCreatePlan),
     /// Exists table
     Exists(ExistsTablePlan),
+    /// Create rollup plan
+    CreateRollup(CreateRollupPlan),
+    /// Show running queries
+    ShowProcessList,
+    /// Kill a running query
+    KillQuery(KillQueryPlan),
 }
 
 pub struct QueryPlan {
@@ -81,6 +89,8 @@ pub struct CreateTablePlan {
     pub table: String,
     /// Table schema
     pub table_schema: Schema,
+    /// Partition info of the table
+    pub partition_info: Option<PartitionInfo>,
     /// Table options
     pub options: HashMap<String, String>,
 }
@@ -92,6 +102,7 @@ impl Debug for CreateTablePlan {
             .field("if_not_exists", &self.if_not_exists)
             .field("table", &self.table)
             .field("table_schema", &self.table_schema)
+            .field("partition_info", &self.partition_info)
             .field(
                 "options",
                 &self
@@ -104,6 +115,17 @@ impl Debug for CreateTablePlan {
     }
 }
 
+/// Create rollup logical plan
+#[derive(Debug)]
+pub struct CreateRollupPlan {
+    /// The source table to roll up
+    pub table: TableRef,
+    /// The rollup to add to the source table
+    pub rollup: Rollup,
+    /// Plan to create the target table of the rollup
+    pub target: CreateTablePlan,
+}
+
 #[derive(Debug)]
 pub struct DropTablePlan {
     /// Engine
@@ -123,6 +145,22 @@ pub struct InsertPlan {
     pub rows: RowGroup,
 }
 
+/// Delete logical plan
+#[derive(Debug)]
+pub struct DeletePlan {
+    /// The table to delete from
+    pub table: TableRef,
+    /// Only rows in this time range are deleted
+    pub time_range: TimeRange,
+    /// Query to find the series to delete, the output contains the
+    /// `series_columns` followed by the number of rows to delete of each
+    /// series.
+    pub query: QueryPlan,
+    /// Series columns in the output of `query`, rows of all series are deleted
+    /// if it is empty.
+    pub series_columns: Vec<String>,
+}
+
 #[derive(Debug)]
 pub struct DescribeTablePlan {
     /// The table to describe
@@ -156,3 +194,9 @@ pub struct ShowCreatePlan {
 pub struct ExistsTablePlan {
     pub exists: bool,
 }
+
+#[derive(Debug)]
+pub struct KillQueryPlan {
+    /// Request id of the query to kill.
+    pub request_id: u64,
+}
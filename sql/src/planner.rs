@@ -7,6 +7,7 @@ use std::{
     convert::TryFrom,
     mem,
     sync::Arc,
+    time::Duration,
 };
 
 use arrow_deps::datafusion::{error::DataFusionError, sql::planner::SqlToRel};
@@ -16,27 +17,34 @@ use common_types::{
     request_id::RequestId,
     row::{RowGroup, RowGroupBuilder},
     schema::{self, Schema, TSID_COLUMN},
+    time::{TimeRange, Timestamp},
 };
 use log::debug;
 use snafu::{ensure, Backtrace, OptionExt, ResultExt, Snafu};
 use sqlparser::ast::{
-    ColumnDef, ColumnOption, Expr, ObjectName, Query, SetExpr, SqlOption,
-    Statement as SqlStatement, TableConstraint, Value, Values,
+    BinaryOperator, ColumnDef, ColumnOption, Expr, FunctionArg, Ident, ObjectName, Query,
+    SelectItem, SetExpr, SqlOption, Statement as SqlStatement, TableConstraint, TableFactor,
+    UnaryOperator, Value, Values,
+};
+use table_engine::{
+    partition::{rule::PartitionRule, PartitionBound, PartitionInfo, PartitionNum, PartitionType},
+    rollup::{self, AggregateFunction, PartialAggregate, Rollup, RollupAggregate},
+    table::TableRef,
 };
-use table_engine::table::TableRef;
 
 use crate::{
     ast::{
-        AlterAddColumn, AlterModifySetting, CreateTable, DescribeTable, DropTable, ExistsTable,
-        ShowCreate, Statement,
+        AlterAddColumn, AlterModifySetting, CreateRollup, CreateTable, DescribeTable, DropTable,
+        ExistsTable, Partition, PartitionDefinition, ShowCreate, Statement,
     },
     container::TableReference,
     parser,
     plan::{
-        AlterTableOperation, AlterTablePlan, CreateTablePlan, DescribeTablePlan, DropTablePlan,
-        ExistsTablePlan, InsertPlan, Plan, QueryPlan, ShowCreatePlan,
+        AlterTableOperation, AlterTablePlan, CreateRollupPlan, CreateTablePlan, DeletePlan,
+        DescribeTablePlan, DropTablePlan, ExistsTablePlan, InsertPlan, KillQueryPlan, Plan,
+        QueryPlan, ShowCreatePlan,
     },
-    promql::{ColumnNames, Expr as PromExpr},
+    promql::{ColumnNames, Expr as PromExpr, Selector as PromSelector},
     provider::{ContextProviderAdapter, MetaProvider},
 };
 
@@ -145,6 +153,64 @@ pub enum Error {
 
     #[snafu(display("Failed to build plan from promql, error:{}", source))]
     BuildPromPlanError { source: crate::promql::Error },
+
+    #[snafu(display("Invalid delete stmt, where clause is required"))]
+    DeleteWithoutWhere,
+
+    #[snafu(display(
+        "Unsupported delete predicate, only time range of timestamp column and predicates of series columns are allowed, predicate:{}",
+        predicate
+    ))]
+    UnsupportedDeletePredicate { predicate: String },
+
+    #[snafu(display("Timestamp overflows in delete predicate, predicate:{}", predicate))]
+    DeleteTimestampOverflow { predicate: String },
+
+    #[snafu(display("Failed to parse the query to delete, err:{}", source))]
+    ParseDeleteQuery {
+        source: sqlparser::parser::ParserError,
+    },
+
+    #[snafu(display("Partition column not found, name:{}", name))]
+    PartitionColumnNotFound { name: String },
+
+    #[snafu(display("Invalid partition num, num:{}", num))]
+    InvalidPartitionNum { num: u64 },
+
+    #[snafu(display("Invalid partition value, only literal is allowed, value:{}", value))]
+    InvalidPartitionValue { value: String },
+
+    #[snafu(display("Failed to convert partition value, err:{}", source))]
+    ConvertPartitionValue { source: common_types::datum::Error },
+
+    #[snafu(display("Invalid partition, err:{}", source))]
+    InvalidPartition {
+        source: table_engine::partition::rule::Error,
+    },
+
+    #[snafu(display(
+        "Unsupported rollup query, only aggregates of a single table grouped by time bucket and tags are allowed, query:{}",
+        query
+    ))]
+    UnsupportedRollupQuery { query: String },
+
+    #[snafu(display(
+        "Rollup tag not found, only tag columns can be grouped by, name:{}",
+        name
+    ))]
+    RollupTagNotFound { name: String },
+
+    #[snafu(display(
+        "Invalid rollup field, only numeric columns can be aggregated, name:{}",
+        name
+    ))]
+    InvalidRollupField { name: String },
+
+    #[snafu(display("Invalid rollup, err:{}", source))]
+    InvalidRollup { source: table_engine::rollup::Error },
+
+    #[snafu(display("Failed to rewrite query by rollups, err:{}", source))]
+    RewriteRollupQuery { source: DataFusionError },
 }
 
 define_result!(Error);
@@ -189,6 +255,11 @@ impl<'a, P: MetaProvider> Planner<'a, P> {
             Statement::AlterAddColumn(s) => planner.alter_add_column_to_plan(s),
             Statement::ShowCreate(s) => planner.show_create_to_plan(s),
             Statement::Exists(s) => planner.exists_table_to_plan(s),
+            Statement::CreateRollup(s) => planner.create_rollup_to_plan(s),
+            Statement::ShowProcessList => Ok(Plan::ShowProcessList),
+            Statement::KillQuery(s) => Ok(Plan::KillQuery(KillQueryPlan {
+                request_id: s.request_id,
+            })),
         }
     }
 
@@ -204,6 +275,25 @@ impl<'a, P: MetaProvider> Planner<'a, P> {
         expr.to_plan(planner.meta_provider, self.read_parallelism)
             .context(BuildPromPlanError)
     }
+
+    pub fn promql_expr_to_aligned_plan(&self, expr: PromExpr) -> Result<(Plan, Arc<ColumnNames>)> {
+        let adapter =
+            ContextProviderAdapter::new(self.provider, self.request_id, self.read_parallelism);
+        let planner = PlannerDelegate::new(adapter);
+
+        expr.to_aligned_plan(planner.meta_provider, self.read_parallelism)
+            .context(BuildPromPlanError)
+    }
+
+    pub fn promql_series_to_plan(&self, selector: PromSelector) -> Result<(Plan, Vec<String>)> {
+        let adapter =
+            ContextProviderAdapter::new(self.provider, self.request_id, self.read_parallelism);
+        let planner = PlannerDelegate::new(adapter);
+
+        selector
+            .into_series_plan(planner.meta_provider)
+            .context(BuildPromPlanError)
+    }
 }
 
 /// A planner wraps the datafusion's logical planner, and delegate sql like
@@ -224,6 +314,7 @@ impl<'a, P: MetaProvider> PlannerDelegate<'a, P> {
                 self.sql_statement_to_datafusion_plan(sql_stmt)
             }
             SqlStatement::Insert { .. } => self.insert_to_plan(sql_stmt),
+            SqlStatement::Delete { .. } => self.delete_to_plan(sql_stmt),
             _ => UnsupportedStatement.fail(),
         }
     }
@@ -237,6 +328,16 @@ impl<'a, P: MetaProvider> PlannerDelegate<'a, P> {
 
         debug!("Sql statement to datafusion plan, df_plan:\n{:#?}", df_plan);
 
+        let df_plan = match crate::rollup::rewrite_by_rollups(&df_plan, &self.meta_provider)
+            .context(RewriteRollupQuery)?
+        {
+            Some(v) => {
+                debug!("Rewrite datafusion plan by rollups, df_plan:\n{:#?}", v);
+                v
+            }
+            None => df_plan,
+        };
+
         // Get all tables needed in the plan
         let tables = self.meta_provider.try_into_container().context(FindMeta)?;
 
@@ -358,6 +459,11 @@ impl<'a, P: MetaProvider> PlannerDelegate<'a, P> {
 
         let table_schema = schema_builder.build().context(BuildTableSchema)?;
 
+        let partition_info = match stmt.partition {
+            Some(partition) => Some(parse_partition(partition, &table_schema)?),
+            None => None,
+        };
+
         let options = parse_options(stmt.options)?;
 
         let plan = CreateTablePlan {
@@ -365,6 +471,7 @@ impl<'a, P: MetaProvider> PlannerDelegate<'a, P> {
             if_not_exists: stmt.if_not_exists,
             table,
             table_schema,
+            partition_info,
             options,
         };
 
@@ -373,6 +480,100 @@ impl<'a, P: MetaProvider> PlannerDelegate<'a, P> {
         Ok(Plan::Create(plan))
     }
 
+    fn create_rollup_to_plan(&self, stmt: CreateRollup) -> Result<Plan> {
+        ensure!(!stmt.name.0.is_empty(), CreateTableNameEmpty);
+
+        debug!("Create rollup to plan, stmt:{:?}", stmt);
+
+        let table_ref = TableReference::try_from(&stmt.name).context(InvalidCreateTableName)?;
+        let target = table_ref.table().to_string();
+
+        let select = match &stmt.query.body {
+            SetExpr::Select(select)
+                if select.from.len() == 1
+                    && select.from[0].joins.is_empty()
+                    && select.selection.is_none()
+                    && select.having.is_none() =>
+            {
+                select
+            }
+            _ => {
+                return UnsupportedRollupQuery {
+                    query: stmt.query.to_string(),
+                }
+                .fail()
+            }
+        };
+        let source_name = match &select.from[0].relation {
+            TableFactor::Table { name, .. } => name.clone(),
+            _ => {
+                return UnsupportedRollupQuery {
+                    query: stmt.query.to_string(),
+                }
+                .fail()
+            }
+        };
+        let table = self.find_table(source_name)?;
+        let source_schema = table.schema();
+
+        let mut period = None;
+        let mut tags = Vec::new();
+        let mut aggregates = Vec::new();
+        for item in &select.projection {
+            let expr = match item {
+                SelectItem::UnnamedExpr(expr) | SelectItem::ExprWithAlias { expr, .. } => expr,
+                _ => {
+                    return UnsupportedRollupQuery {
+                        query: stmt.query.to_string(),
+                    }
+                    .fail()
+                }
+            };
+
+            match parse_rollup_item(expr, &source_schema)? {
+                RollupItem::TimeBucket(v) if period.is_none() => period = Some(v),
+                RollupItem::Tag(v) => tags.push(v),
+                RollupItem::Aggregate(v) => aggregates.push(v),
+                RollupItem::TimeBucket(_) => {
+                    return UnsupportedRollupQuery {
+                        query: stmt.query.to_string(),
+                    }
+                    .fail()
+                }
+            }
+        }
+        let period = period.with_context(|| UnsupportedRollupQuery {
+            query: stmt.query.to_string(),
+        })?;
+
+        let rollup = Rollup {
+            table: target.clone(),
+            period,
+            tags,
+            aggregates,
+        };
+        rollup.validate().context(InvalidRollup)?;
+
+        let target = CreateTablePlan {
+            engine: table.engine_type().to_string(),
+            if_not_exists: stmt.if_not_exists,
+            table: target,
+            table_schema: build_rollup_schema(&rollup, &source_schema)?,
+            partition_info: None,
+            options: parse_options(stmt.options)?,
+        };
+
+        let plan = CreateRollupPlan {
+            table,
+            rollup,
+            target,
+        };
+
+        debug!("Create rollup to plan, plan:{:?}", plan);
+
+        Ok(Plan::CreateRollup(plan))
+    }
+
     fn drop_table_to_plan(&self, stmt: DropTable) -> Result<Plan> {
         let table = if stmt.if_exists {
             stmt.name.to_string()
@@ -461,6 +662,84 @@ impl<'a, P: MetaProvider> PlannerDelegate<'a, P> {
         }
     }
 
+    // REQUIRE: SqlStatement must be DELETE stmt
+    fn delete_to_plan(self, sql_stmt: SqlStatement) -> Result<Plan> {
+        let (table_name, selection) = match sql_stmt {
+            SqlStatement::Delete {
+                table_name,
+                selection,
+            } => (table_name, selection.context(DeleteWithoutWhere)?),
+            // We already known this stmt is a DELETE stmt
+            _ => unreachable!(),
+        };
+
+        let table = self.find_table(table_name.clone())?;
+        let schema = table.schema();
+        let timestamp_name = schema.timestamp_name();
+        let (predicate_columns, series_key_columns) = series_columns_of_schema(&schema);
+
+        // Split the predicate into time range and predicates of series.
+        let mut time_range = TimeRange::min_to_max();
+        let mut has_series_predicate = false;
+        let mut exprs = Vec::new();
+        split_conjunction(&selection, &mut exprs);
+        for expr in exprs {
+            let mut columns = Vec::new();
+            collect_columns(expr, &mut columns)?;
+
+            if !columns.is_empty() && columns.iter().all(|v| v == timestamp_name) {
+                time_range = intersect_time_range(time_range, timestamp_name, expr)?;
+            } else if !columns.is_empty() && columns.iter().all(|v| predicate_columns.contains(v)) {
+                has_series_predicate = true;
+            } else {
+                return UnsupportedDeletePredicate {
+                    predicate: expr.to_string(),
+                }
+                .fail();
+            }
+        }
+
+        let series_columns = if has_series_predicate {
+            series_key_columns
+        } else {
+            Vec::new()
+        };
+
+        // Query the series to delete and the number of rows to delete.
+        let mut select_items: Vec<_> = series_columns
+            .iter()
+            .map(|v| Ident::with_quote('`', v).to_string())
+            .collect();
+        let group_by = select_items.join(", ");
+        select_items.push("COUNT(*)".to_string());
+        let mut query_sql = format!(
+            "SELECT {} FROM {} WHERE {}",
+            select_items.join(", "),
+            table_name,
+            selection
+        );
+        if !series_columns.is_empty() {
+            query_sql = format!("{} GROUP BY {}", query_sql, group_by);
+        }
+
+        let mut statements = parser::Parser::parse_sql(&query_sql).context(ParseDeleteQuery)?;
+        let query_stmt = match statements.pop() {
+            Some(Statement::Standard(stmt)) => *stmt,
+            _ => return UnsupportedStatement.fail(),
+        };
+        let query = match self.sql_statement_to_datafusion_plan(query_stmt)? {
+            Plan::Query(v) => v,
+            _ => unreachable!(),
+        };
+
+        Ok(Plan::Delete(DeletePlan {
+            table,
+            time_range,
+            query,
+            series_columns,
+        }))
+    }
+
     fn alter_modify_setting_to_plan(&self, stmt: AlterModifySetting) -> Result<Plan> {
         let table = self.find_table(stmt.table_name)?;
         let plan = AlterTablePlan {
@@ -587,7 +866,328 @@ fn build_row_group(
     }
 }
 
+/// Returns the columns allowed in the series predicates of delete and the
+/// columns to identify a series.
+///
+/// If the table uses tsid as primary key, the tag columns are allowed and the
+/// series is identified by tsid. Otherwise, both are the key columns except the
+/// timestamp column.
 #[inline]
+fn series_columns_of_schema(schema: &Schema) -> (Vec<String>, Vec<String>) {
+    let timestamp_index = schema.timestamp_index();
+    let key_columns: Vec<_> = schema
+        .key_columns()
+        .iter()
+        .enumerate()
+        .filter(|(idx, _)| *idx != timestamp_index)
+        .map(|(_, column)| column.name.clone())
+        .collect();
+
+    if schema.index_of_tsid().is_some() {
+        let tag_columns = schema
+            .columns()
+            .iter()
+            .filter(|column| column.is_tag)
+            .map(|column| column.name.clone())
+            .collect();
+        (tag_columns, key_columns)
+    } else {
+        (key_columns.clone(), key_columns)
+    }
+}
+
+/// Split the expr into a list of exprs connected by AND.
+fn split_conjunction<'a>(expr: &'a Expr, exprs: &mut Vec<&'a Expr>) {
+    match expr {
+        Expr::BinaryOp {
+            left,
+            op: BinaryOperator::And,
+            right,
+        } => {
+            split_conjunction(left, exprs);
+            split_conjunction(right, exprs);
+        }
+        Expr::Nested(expr) => split_conjunction(expr, exprs),
+        other => exprs.push(other),
+    }
+}
+
+/// Collect the name of columns referenced by the expr, returns error if the
+/// expr is not supported in delete.
+fn collect_columns(expr: &Expr, columns: &mut Vec<String>) -> Result<()> {
+    match expr {
+        Expr::Identifier(ident) => columns.push(ident.value.clone()),
+        Expr::Value(_) => (),
+        Expr::Nested(expr)
+        | Expr::IsNull(expr)
+        | Expr::IsNotNull(expr)
+        | Expr::UnaryOp { expr, .. } => collect_columns(expr, columns)?,
+        Expr::BinaryOp { left, right, .. } => {
+            collect_columns(left, columns)?;
+            collect_columns(right, columns)?;
+        }
+        Expr::InList { expr, list, .. } => {
+            collect_columns(expr, columns)?;
+            for v in list {
+                collect_columns(v, columns)?;
+            }
+        }
+        Expr::Between {
+            expr, low, high, ..
+        } => {
+            collect_columns(expr, columns)?;
+            collect_columns(low, columns)?;
+            collect_columns(high, columns)?;
+        }
+        other => {
+            return UnsupportedDeletePredicate {
+                predicate: other.to_string(),
+            }
+            .fail()
+        }
+    }
+
+    Ok(())
+}
+
+/// Intersect the `time_range` with the range of timestamp column specified by
+/// the `expr`.
+fn intersect_time_range(
+    time_range: TimeRange,
+    timestamp_name: &str,
+    expr: &Expr,
+) -> Result<TimeRange> {
+    let mut start = time_range.inclusive_start().as_i64();
+    let mut end = time_range.exclusive_end().as_i64();
+    let is_timestamp_column =
+        |expr: &Expr| matches!(expr, Expr::Identifier(ident) if ident.value == timestamp_name);
+
+    match expr {
+        Expr::BinaryOp { left, op, right } => {
+            let (op, value) = if is_timestamp_column(left) {
+                (op.clone(), parse_timestamp_value(right)?)
+            } else if is_timestamp_column(right) {
+                // Flip the operator so the column is always at the left side.
+                let op = match op {
+                    BinaryOperator::Gt => BinaryOperator::Lt,
+                    BinaryOperator::GtEq => BinaryOperator::LtEq,
+                    BinaryOperator::Lt => BinaryOperator::Gt,
+                    BinaryOperator::LtEq => BinaryOperator::GtEq,
+                    other => other.clone(),
+                };
+                (op, parse_timestamp_value(left)?)
+            } else {
+                (op.clone(), None)
+            };
+
+            match (op, value) {
+                (BinaryOperator::Gt, Some(v)) => start = start.max(v.saturating_add(1)),
+                (BinaryOperator::GtEq, Some(v)) => start = start.max(v),
+                (BinaryOperator::Lt, Some(v)) => end = end.min(v),
+                (BinaryOperator::LtEq, Some(v)) => end = end.min(v.saturating_add(1)),
+                (BinaryOperator::Eq, Some(v)) => {
+                    start = start.max(v);
+                    end = end.min(v.saturating_add(1));
+                }
+                _ => {
+                    return UnsupportedDeletePredicate {
+                        predicate: expr.to_string(),
+                    }
+                    .fail()
+                }
+            }
+        }
+        Expr::Between {
+            expr: column,
+            negated: false,
+            low,
+            high,
+        } if is_timestamp_column(column) => {
+            match (parse_timestamp_value(low)?, parse_timestamp_value(high)?) {
+                (Some(low), Some(high)) => {
+                    start = start.max(low);
+                    end = end.min(high.saturating_add(1));
+                }
+                _ => {
+                    return UnsupportedDeletePredicate {
+                        predicate: expr.to_string(),
+                    }
+                    .fail()
+                }
+            }
+        }
+        _ => {
+            return UnsupportedDeletePredicate {
+                predicate: expr.to_string(),
+            }
+            .fail()
+        }
+    }
+
+    Ok(TimeRange::new(Timestamp::new(start), Timestamp::new(end)).unwrap_or_else(TimeRange::empty))
+}
+
+/// Parse the timestamp in millis from a number literal.
+///
+/// Returns error if the negation of the value overflows.
+fn parse_timestamp_value(expr: &Expr) -> Result<Option<i64>> {
+    match expr {
+        Expr::Value(Value::Number(n, _)) => Ok(n.parse().ok()),
+        Expr::UnaryOp {
+            op: UnaryOperator::Minus,
+            expr: inner,
+        } => match parse_timestamp_value(inner)? {
+            Some(v) => v
+                .checked_neg()
+                .map(Some)
+                .with_context(|| DeleteTimestampOverflow {
+                    predicate: expr.to_string(),
+                }),
+            None => Ok(None),
+        },
+        Expr::Nested(expr) => parse_timestamp_value(expr),
+        _ => Ok(None),
+    }
+}
+
+/// Item in the projection of the query of a rollup.
+enum RollupItem {
+    /// Period of `time_bucket(timestamp, period)`
+    TimeBucket(Duration),
+    Tag(String),
+    Aggregate(RollupAggregate),
+}
+
+fn parse_rollup_item(expr: &Expr, schema: &Schema) -> Result<RollupItem> {
+    let function = match expr {
+        Expr::Identifier(ident) => {
+            let is_tag = schema
+                .column_with_name(&ident.value)
+                .map(|column| column.is_tag)
+                .unwrap_or(false);
+            ensure!(is_tag, RollupTagNotFound { name: &ident.value });
+
+            return Ok(RollupItem::Tag(ident.value.clone()));
+        }
+        Expr::Function(function) if !function.distinct && function.over.is_none() => function,
+        _ => {
+            return UnsupportedRollupQuery {
+                query: expr.to_string(),
+            }
+            .fail()
+        }
+    };
+
+    let name = function.name.to_string();
+    let args = function
+        .args
+        .iter()
+        .map(|arg| match arg {
+            FunctionArg::Unnamed(v) => Some(v),
+            FunctionArg::Named { .. } => None,
+        })
+        .collect::<Option<Vec<_>>>()
+        .with_context(|| UnsupportedRollupQuery {
+            query: expr.to_string(),
+        })?;
+    match (name.to_lowercase().as_str(), args.as_slice()) {
+        (
+            "time_bucket",
+            [Expr::Identifier(column), Expr::Value(Value::SingleQuotedString(period))],
+        ) if column.value == schema.timestamp_name() => {
+            let period = rollup::parse_period(period).context(InvalidRollup)?;
+            Ok(RollupItem::TimeBucket(period))
+        }
+        (_, [Expr::Identifier(column)]) => {
+            let function = AggregateFunction::parse_from(&name).context(InvalidRollup)?;
+            let is_numeric = schema
+                .column_with_name(&column.value)
+                .map(|column| !column.is_tag && column.data_type.is_f64_castable())
+                .unwrap_or(false);
+            ensure!(
+                is_numeric,
+                InvalidRollupField {
+                    name: &column.value
+                }
+            );
+
+            Ok(RollupItem::Aggregate(RollupAggregate {
+                function,
+                column: column.value.clone(),
+            }))
+        }
+        _ => UnsupportedRollupQuery {
+            query: expr.to_string(),
+        }
+        .fail(),
+    }
+}
+
+/// Build the schema of the target table of the `rollup`, the primary key is
+/// (timestamp, tsid), followed by the tags and the partial aggregates.
+///
+/// The engine recomputes the whole time bucket from the source table when
+/// rows of the bucket are flushed, so the target table must overwrite the
+/// rows of the same key to keep only the latest aggregates of each bucket.
+fn build_rollup_schema(rollup: &Rollup, source_schema: &Schema) -> Result<Schema> {
+    let timestamp_name = source_schema.timestamp_name();
+    let timestamp_column =
+        column_schema::Builder::new(timestamp_name.to_string(), DatumKind::Timestamp)
+            .is_nullable(false)
+            .build()
+            .context(InvalidColumnSchema {
+                column_name: timestamp_name,
+            })?;
+    let tsid_column = column_schema::Builder::new(TSID_COLUMN.to_string(), DatumKind::UInt64)
+        .is_nullable(false)
+        .build()
+        .context(InvalidColumnSchema {
+            column_name: TSID_COLUMN,
+        })?;
+    let mut schema_builder = schema::Builder::new()
+        .auto_increment_column_id(true)
+        .enable_tsid_primary_key(true)
+        .add_key_column(timestamp_column)
+        .context(BuildTableSchema)?
+        .add_key_column(tsid_column)
+        .context(BuildTableSchema)?;
+
+    for tag in &rollup.tags {
+        let data_type = source_schema
+            .column_with_name(tag)
+            .context(RollupTagNotFound { name: tag })?
+            .data_type;
+        let column = column_schema::Builder::new(tag.clone(), data_type)
+            .is_nullable(true)
+            .is_tag(true)
+            .build()
+            .context(InvalidColumnSchema { column_name: tag })?;
+        schema_builder = schema_builder
+            .add_normal_column(column)
+            .context(BuildTableSchema)?;
+    }
+
+    for partial_column in rollup.partial_columns() {
+        let data_type = match partial_column.partial {
+            PartialAggregate::Count => DatumKind::UInt64,
+            PartialAggregate::Sum | PartialAggregate::Min | PartialAggregate::Max => {
+                DatumKind::Double
+            }
+        };
+        let column = column_schema::Builder::new(partial_column.name.clone(), data_type)
+            .is_nullable(true)
+            .build()
+            .context(InvalidColumnSchema {
+                column_name: &partial_column.name,
+            })?;
+        schema_builder = schema_builder
+            .add_normal_column(column)
+            .context(BuildTableSchema)?;
+    }
+
+    schema_builder.build().context(BuildTableSchema)
+}
+
 fn is_tsid_column(name: &str) -> bool {
     name == TSID_COLUMN
 }
@@ -644,6 +1244,109 @@ pub fn parse_for_option(value: Value) -> Result<Option<String>> {
     Ok(value_opt)
 }
 
+/// Build partition info from the partition clause and validate it against the
+/// table schema.
+fn parse_partition(partition: Partition, schema: &Schema) -> Result<PartitionInfo> {
+    let partition_info = match partition {
+        Partition::Hash {
+            columns,
+            partition_num,
+        } => PartitionInfo {
+            partition_type: PartitionType::Hash,
+            columns: columns.into_iter().map(|column| column.value).collect(),
+            partition_num: PartitionNum::try_from(partition_num)
+                .ok()
+                .context(InvalidPartitionNum { num: partition_num })?,
+            definitions: Vec::new(),
+        },
+        Partition::Range {
+            column,
+            definitions,
+        } => parse_partition_definitions(PartitionType::Range, column, definitions, schema)?,
+        Partition::List {
+            column,
+            definitions,
+        } => parse_partition_definitions(PartitionType::List, column, definitions, schema)?,
+    };
+
+    PartitionRule::new(&partition_info, schema).context(InvalidPartition)?;
+
+    Ok(partition_info)
+}
+
+fn parse_partition_definitions(
+    partition_type: PartitionType,
+    column: Ident,
+    definitions: Vec<PartitionDefinition>,
+    schema: &Schema,
+) -> Result<PartitionInfo> {
+    let kind = schema
+        .column_with_name(&column.value)
+        .context(PartitionColumnNotFound {
+            name: &column.value,
+        })?
+        .data_type;
+    let partition_num =
+        PartitionNum::try_from(definitions.len())
+            .ok()
+            .context(InvalidPartitionNum {
+                num: definitions.len() as u64,
+            })?;
+
+    let mut parsed_definitions = Vec::with_capacity(definitions.len());
+    for definition in definitions {
+        let mut values = definition
+            .values
+            .into_iter()
+            .map(|expr| parse_partition_value(&kind, expr))
+            .collect::<Result<Vec<_>>>()?;
+        let bound = if partition_type == PartitionType::Range {
+            // Empty upper bound is MAXVALUE.
+            PartitionBound::LessThan(values.pop())
+        } else {
+            PartitionBound::In(values)
+        };
+
+        parsed_definitions.push(table_engine::partition::PartitionDefinition {
+            name: definition.name.value,
+            bound,
+        });
+    }
+
+    Ok(PartitionInfo {
+        partition_type,
+        columns: vec![column.value],
+        partition_num,
+        definitions: parsed_definitions,
+    })
+}
+
+fn parse_partition_value(kind: &DatumKind, expr: Expr) -> Result<Datum> {
+    let value = match expr {
+        Expr::Value(value) => value,
+        Expr::UnaryOp {
+            op: UnaryOperator::Minus,
+            expr,
+        } => match *expr {
+            Expr::Value(Value::Number(n, long)) => Value::Number(format!("-{}", n), long),
+            expr => {
+                return InvalidPartitionValue {
+                    value: format!("-{}", expr),
+                }
+                .fail()
+            }
+        },
+        expr => {
+            return InvalidPartitionValue {
+                value: expr.to_string(),
+            }
+            .fail()
+        }
+    };
+
+    Datum::try_from_sql_value(kind, value).context(ConvertPartitionValue)
+}
+
 fn parse_columns(cols: Vec<ColumnDef>) -> Result<Vec<ColumnSchema>> {
     let mut parsed_columns = Vec::with_capacity(cols.len());
 
@@ -812,6 +1515,7 @@ mod tests {
             },
             version: 1,
         },
+        partition_info: None,
         options: {
             "arena_block_size": "1KB",
             "ttl": "70d",
@@ -823,6 +1527,121 @@ mod tests {
         .unwrap();
     }
 
+    fn create_table_partition_info(sql: &str) -> Result<Option<PartitionInfo>> {
+        let mock = MockMetaProvider::default();
+        let planner = build_planner(&mock);
+        let mut statements = Parser::parse_sql(sql).unwrap();
+        match planner.statement_to_plan(statements.remove(0))? {
+            Plan::Create(plan) => Ok(plan.partition_info),
+            plan => panic!("Unexpected plan:{:?}", plan),
+        }
+    }
+
+    fn create_rollup_to_plan(sql: &str) -> Result<CreateRollupPlan> {
+        let mock = MockMetaProvider::default();
+        let planner = build_planner(&mock);
+        let mut statements = Parser::parse_sql(sql).unwrap();
+        match planner.statement_to_plan(statements.remove(0))? {
+            Plan::CreateRollup(v) => Ok(v),
+            _ => panic!("Expect a create rollup plan"),
+        }
+    }
+
+    #[test]
+    fn test_create_rollup_statement_to_plan() {
+        let sql = "CREATE ROLLUP IF NOT EXISTS cpu_1h WITH (ttl='365d') AS \
+                   SELECT time_bucket(ts, '1h'), host, avg(usage), MAX(usage) \
+                   FROM rollup_source GROUP BY time_bucket(ts, '1h'), host";
+        let plan = create_rollup_to_plan(sql).unwrap();
+        assert_eq!("rollup_source", plan.table.name());
+        assert_eq!(
+            "cpu_1h:PT1H:host:avg(usage),max(usage)",
+            plan.rollup.to_string()
+        );
+        assert_eq!("cpu_1h", plan.target.table);
+        assert!(plan.target.if_not_exists);
+        assert_eq!("365d", plan.target.options["ttl"]);
+
+        let schema = &plan.target.table_schema;
+        let columns: Vec<_> = schema
+            .columns()
+            .iter()
+            .map(|column| (column.name.as_str(), column.data_type, column.is_tag))
+            .collect();
+        assert_eq!(
+            vec![
+                ("ts", DatumKind::Timestamp, false),
+                (TSID_COLUMN, DatumKind::UInt64, false),
+                ("host", DatumKind::String, true),
+                ("usage_sum", DatumKind::Double, false),
+                ("usage_count", DatumKind::UInt64, false),
+                ("usage_max", DatumKind::Double, false),
+            ],
+            columns
+        );
+        assert_eq!(Some(1), schema.index_of_tsid());
+
+        let invalid_sqls = [
+            // Without time bucket.
+            "CREATE ROLLUP r AS SELECT host, avg(usage) FROM rollup_source GROUP BY host",
+            // Group by the column not a tag.
+            "CREATE ROLLUP r AS SELECT time_bucket(ts, '1m'), usage, avg(usage) \
+             FROM rollup_source GROUP BY time_bucket(ts, '1m'), usage",
+            // Aggregate a tag.
+            "CREATE ROLLUP r AS SELECT time_bucket(ts, '1m'), max(host) \
+             FROM rollup_source GROUP BY time_bucket(ts, '1m')",
+            // Unsupported period.
+            "CREATE ROLLUP r AS SELECT time_bucket(ts, 'P1D'), avg(usage) \
+             FROM rollup_source GROUP BY time_bucket(ts, 'P1D')",
+            // With predicate.
+            "CREATE ROLLUP r AS SELECT time_bucket(ts, '1m'), avg(usage) \
+             FROM rollup_source WHERE host = 'h1' GROUP BY time_bucket(ts, '1m')",
+        ];
+        for sql in invalid_sqls {
+            assert!(create_rollup_to_plan(sql).is_err(), "sql:{}", sql);
+        }
+    }
+
+    #[test]
+    fn test_create_partition_statement_to_plan() {
+        let sql = "CREATE TABLE t(c1 string tag not null, ts timestamp not null, timestamp key(ts), primary key(c1, ts)) \
+        PARTITION BY RANGE(ts) (PARTITION p0 VALUES LESS THAN (1000), PARTITION p1 VALUES LESS THAN MAXVALUE)";
+        let partition_info = create_table_partition_info(sql).unwrap().unwrap();
+        assert_eq!(PartitionType::Range, partition_info.partition_type);
+        assert_eq!(vec!["ts".to_string()], partition_info.columns);
+        assert_eq!(2, partition_info.partition_num);
+        assert_eq!(
+            PartitionBound::LessThan(Some(Datum::Timestamp(Timestamp::new(1000)))),
+            partition_info.definitions[0].bound
+        );
+        assert_eq!(
+            PartitionBound::LessThan(None),
+            partition_info.definitions[1].bound
+        );
+
+        let sql = "CREATE TABLE t(c1 string tag not null, ts timestamp not null, timestamp key(ts), primary key(c1, ts)) \
+        PARTITION BY LIST(c1) (PARTITION p0 VALUES IN ('a', 'b'), PARTITION p1 VALUES IN ('c'))";
+        let partition_info = create_table_partition_info(sql).unwrap().unwrap();
+        assert_eq!(PartitionType::List, partition_info.partition_type);
+        assert_eq!(2, partition_info.partition_num);
+
+        let sql = "CREATE TABLE t(c1 string tag not null, ts timestamp not null, timestamp key(ts), primary key(c1, ts)) \
+        PARTITION BY HASH(c1) PARTITIONS 4";
+        let partition_info = create_table_partition_info(sql).unwrap().unwrap();
+        assert_eq!(PartitionType::Hash, partition_info.partition_type);
+        assert_eq!(4, partition_info.partition_num);
+
+        // Bounds of range partition must be increasing.
+        let sql = "CREATE TABLE t(c1 string tag not null, ts timestamp not null, timestamp key(ts), primary key(c1, ts)) \
+        PARTITION BY RANGE(ts) (PARTITION p0 VALUES LESS THAN (1000), PARTITION p1 VALUES LESS THAN (100))";
+        assert!(create_table_partition_info(sql).is_err());
+
+        // Partition column must exist.
+        let sql = "CREATE TABLE t(c1 string tag not null, ts timestamp not null, timestamp key(ts), primary key(c1, ts)) \
+        PARTITION BY LIST(c2) (PARTITION p0 VALUES IN ('a'))";
+        assert!(create_table_partition_info(sql).is_err());
+    }
+
     #[test]
     fn test_query_statement_to_plan() {
         let sql = "select * from test_tablex;";
@@ -1211,6 +2030,20 @@ mod tests {
         .unwrap();
     }
 
+    #[test]
+    fn test_process_list_statement_to_plan() {
+        quick_test("show processlist", "ShowProcessList").unwrap();
+        quick_test(
+            "kill query 42",
+            r#"KillQuery(
+    KillQueryPlan {
+        request_id: 42,
+    },
+)"#,
+        )
+        .unwrap();
+    }
+
     #[test]
     fn test_show_create_statement_to_plan() {
         let sql = "show create table test_tablex;";
@@ -1274,4 +2107,79 @@ mod tests {
         )
         .unwrap();
     }
+
+    fn delete_to_plan(sql: &str) -> Result<DeletePlan> {
+        let mock = MockMetaProvider::default();
+        let planner = build_planner(&mock);
+        let mut statements = Parser::parse_sql(sql).unwrap();
+        assert_eq!(statements.len(), 1);
+        match planner.statement_to_plan(statements.remove(0))? {
+            Plan::Delete(plan) => Ok(plan),
+            plan => panic!("Unexpected plan:{:?}", plan),
+        }
+    }
+
+    #[test]
+    fn test_delete_statement_to_plan() {
+        let plan = delete_to_plan(
+            "DELETE FROM test_table WHERE key1 = 'a' AND key2 >= 1000 AND key2 < 2000",
+        )
+        .unwrap();
+        assert_eq!(
+            TimeRange::new_unchecked_for_test(1000, 2000),
+            plan.time_range
+        );
+        assert_eq!(vec!["key1".to_string()], plan.series_columns);
+
+        let plan =
+            delete_to_plan("DELETE FROM test_table WHERE 1000 < key2 AND key2 <= 2000").unwrap();
+        assert_eq!(
+            TimeRange::new_unchecked_for_test(1001, 2001),
+            plan.time_range
+        );
+        assert!(plan.series_columns.is_empty());
+
+        let plan = delete_to_plan(
+            "DELETE FROM test_table WHERE key2 BETWEEN 10 AND 20 AND key1 IN ('a', 'b')",
+        )
+        .unwrap();
+        assert_eq!(TimeRange::new_unchecked_for_test(10, 21), plan.time_range);
+        assert_eq!(vec!["key1".to_string()], plan.series_columns);
+
+        let plan =
+            delete_to_plan("DELETE FROM test_table WHERE key2 > 2000 AND key2 < 1000").unwrap();
+        assert_eq!(TimeRange::empty(), plan.time_range);
+    }
+
+    #[test]
+    fn test_delete_statement_to_plan_error() {
+        let sqls = [
+            "DELETE FROM test_table",
+            "DELETE FROM test_table WHERE field1 > 10",
+            "DELETE FROM test_table WHERE key1 = 'a' OR key2 > 10",
+            "DELETE FROM test_table WHERE key2 != 10",
+            "DELETE FROM test_table WHERE key2 > key2",
+            "DELETE FROM test_table WHERE key1 = 'a' AND field2 = 'b'",
+            "DELETE FROM not_exist_table WHERE key2 > 10",
+        ];
+
+        for sql in sqls {
+            assert!(delete_to_plan(sql).is_err(), "sql:{}", sql);
+        }
+    }
+
+    #[test]
+    fn test_parse_timestamp_value_overflow() {
+        let min = Expr::Value(Value::Number(i64::MIN.to_string(), false));
+        assert_eq!(Some(i64::MIN), parse_timestamp_value(&min).unwrap());
+
+        let negated = Expr::UnaryOp {
+            op: UnaryOperator::Minus,
+            expr: Box::new(min),
+        };
+        assert!(matches!(
+            parse_timestamp_value(&negated),
+            Err(Error::DeleteTimestampOverflow { .. })
+        ));
+    }
 }
@@ -2,9 +2,14 @@
 
 mod convert;
 mod datafusion_util;
+mod parser;
 mod pushdown;
 mod udf;
 
-pub use convert::{Error, Expr};
+pub use convert::{Error, Expr, FilterType, Operand, Selector};
 pub use datafusion_util::{ColumnNames, PromAlignNode};
+pub use parser::{
+    matchers_to_selector, parse_duration, parse_expr, parse_selector, table_selector,
+    Error as ParseError, EvalParams, LabelMatcher, DEFAULT_FIELD, FIELD_LABEL, METRIC_NAME_LABEL,
+};
 pub use pushdown::{AlignParameter, Func};
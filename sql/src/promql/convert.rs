@@ -5,13 +5,16 @@ use std::{
     sync::Arc,
 };
 
-use arrow_deps::datafusion::{
-    error::DataFusionError,
-    logical_plan::{
-        avg, col, combine_filters, count, lit, max, min, plan::Extension, sum,
-        Expr as DataFusionExpr, LogicalPlan, LogicalPlanBuilder,
+use arrow_deps::{
+    arrow::datatypes::DataType,
+    datafusion::{
+        error::DataFusionError,
+        logical_plan::{
+            avg, col, combine_filters, count, lit, max, min, plan::Extension, sum,
+            Expr as DataFusionExpr, LogicalPlan, LogicalPlanBuilder,
+        },
+        sql::planner::ContextProvider,
     },
-    sql::planner::ContextProvider,
 };
 use ceresdbproto::prometheus::{
     Expr as ExprPb, Filter as FilterPb, FilterType as FilterPbType, Operand as OperandPb,
@@ -21,21 +24,24 @@ use common_types::{
     schema::{Schema, TSID_COLUMN},
     time::{TimeRange, Timestamp},
 };
+use regex::Regex;
 use snafu::{ensure, Backtrace, OptionExt, ResultExt, Snafu};
 
 use crate::{
     plan::{Plan, QueryPlan},
     promql::{
         datafusion_util::{default_sort_exprs, timerange_to_expr},
-        pushdown::{AlignParameter, Func},
-        udf::{create_unique_id, regex_match_expr},
+        pushdown::{self, AlignParameter, Func},
+        udf::{
+            binary_op_fn, create_unique_id, is_comparison_op, regex_match_expr, scalar_binary_expr,
+        },
         ColumnNames, PromAlignNode,
     },
     provider::{ContextProviderAdapter, MetaProvider},
 };
 
 const INIT_LEVEL: usize = 1;
-const DEFAULT_LOOKBACK: i64 = 300_000;
+pub(crate) const DEFAULT_LOOKBACK: i64 = 300_000;
 
 #[derive(Debug, Snafu)]
 pub enum Error {
@@ -182,9 +188,30 @@ impl Expr {
         self,
         meta_provider: ContextProviderAdapter<'_, P>,
         read_parallelism: usize,
+    ) -> Result<(Plan, Arc<ColumnNames>)> {
+        self.to_plan_with_level(meta_provider, INIT_LEVEL, read_parallelism)
+    }
+
+    /// Like [Expr::to_plan], but a top level selector is also aligned by
+    /// [Func::Instant], so samples in the output are already evaluated at
+    /// every step. Used when the query is evaluated by ourselves instead of
+    /// Prometheus, such as the http api.
+    pub fn to_aligned_plan<P: MetaProvider>(
+        self,
+        meta_provider: ContextProviderAdapter<'_, P>,
+        read_parallelism: usize,
+    ) -> Result<(Plan, Arc<ColumnNames>)> {
+        self.to_plan_with_level(meta_provider, INIT_LEVEL + 1, read_parallelism)
+    }
+
+    fn to_plan_with_level<P: MetaProvider>(
+        self,
+        meta_provider: ContextProviderAdapter<'_, P>,
+        level: usize,
+        read_parallelism: usize,
     ) -> Result<(Plan, Arc<ColumnNames>)> {
         let (logic_plan, column_name, _) =
-            self.build_plan_iter(&meta_provider, INIT_LEVEL, read_parallelism)?;
+            self.build_plan_iter(&meta_provider, level, read_parallelism)?;
         let tables = Arc::new(
             meta_provider
                 .try_into_container()
@@ -254,41 +281,54 @@ impl Expr {
             Expr::RecursiveExpr(recursive_expr) => match recursive_expr {
                 SubExpr::Func(FuncExpr { op, operands }) => {
                     assert!(!operands.is_empty());
-                    let func = Func::try_from(op.as_str()).context(PushdownError {})?;
-                    let first_arg = &operands[0];
-                    if first_arg.is_selector() {
-                        let selector = first_arg.get_selector();
-                        let (sub_plan, column_name, table_name) =
-                            selector.clone().into_scan_plan(meta_provider)?;
-                        let Selector {
-                            align_range,
-                            step,
-                            range,
-                            offset,
-                            ..
-                        } = selector;
-                        let align_param = AlignParameter {
-                            align_range: *align_range,
-                            step: step.into(),
-                            offset: offset.into(),
-                            lookback_delta: range.into(),
-                        };
-                        let align_plan = LogicalPlan::Extension(Extension {
-                            node: Arc::new(PromAlignNode {
-                                input: sub_plan,
-                                table_name: table_name.clone(),
-                                func,
-                                align_param,
-                                column_name: column_name.clone(),
-                                read_parallelism,
-                            }),
-                        });
-                        return Ok((align_plan, column_name, table_name));
-                    }
-                    InvalidExpr {
-                        msg: "first arg of func must be selector",
+                    // The range vector arg is not always the first one, such as
+                    // `quantile_over_time(0.5, v[5m])`, other args must be numbers.
+                    let mut selector = None;
+                    let mut args = Vec::with_capacity(operands.len());
+                    for operand in &operands {
+                        match operand {
+                            Expr::SimpleExpr(Operand::Selector(sel)) if selector.is_none() => {
+                                selector = Some(sel)
+                            }
+                            Expr::SimpleExpr(Operand::Float(v)) => args.push(*v),
+                            _ => {
+                                return InvalidExpr {
+                                    msg: "args of func must be one selector and numbers",
+                                }
+                                .fail()
+                            }
+                        }
                     }
-                    .fail()
+                    let selector = selector.context(InvalidExpr {
+                        msg: "func requires a selector arg",
+                    })?;
+                    let func = Func::try_new(&op, &args).context(PushdownError {})?;
+                    let (sub_plan, column_name, table_name) =
+                        selector.clone().into_scan_plan(meta_provider)?;
+                    let Selector {
+                        align_range,
+                        step,
+                        range,
+                        offset,
+                        ..
+                    } = selector;
+                    let align_param = AlignParameter {
+                        align_range: *align_range,
+                        step: step.into(),
+                        offset: offset.into(),
+                        lookback_delta: range.into(),
+                    };
+                    let align_plan = LogicalPlan::Extension(Extension {
+                        node: Arc::new(PromAlignNode {
+                            input: sub_plan,
+                            table_name: table_name.clone(),
+                            func,
+                            align_param,
+                            column_name: column_name.clone(),
+                            read_parallelism,
+                        }),
+                    });
+                    Ok((align_plan, column_name, table_name))
                 }
 
                 // New plan like:
@@ -367,10 +407,70 @@ impl Expr {
 
                     Ok((plan, column_name, table_name))
                 }
-                SubExpr::Binary(_) => InvalidExpr {
-                    msg: "Binary Expr not supported",
+                // New plan like:
+                // Projection: (arithmetic or bool comparison)
+                //   SubPlan
+                // or
+                // Filter: (comparison)
+                //   SubPlan
+                SubExpr::Binary(BinaryExpr {
+                    op,
+                    mut operands,
+                    return_bool,
+                }) => {
+                    assert_eq!(operands.len(), 2);
+                    // One of the operands must be a scalar, binary operators between
+                    // vectors are rejected by the parser.
+                    let (sub_node, scalar, scalar_on_left) =
+                        match (operands.remove(0), operands.remove(0)) {
+                            (Expr::SimpleExpr(Operand::Float(v)), rhs) => (rhs, v, true),
+                            (lhs, Expr::SimpleExpr(Operand::Float(v))) => (lhs, v, false),
+                            _ => {
+                                return InvalidExpr {
+                                    msg: "binary operator requires a scalar operand",
+                                }
+                                .fail()
+                            }
+                        };
+                    let func = binary_op_fn(&op).with_context(|| InvalidExpr {
+                        msg: format!("binary operator {} not supported", op),
+                    })?;
+                    let (sub_plan, column_name, table_name) =
+                        sub_node.build_plan_iter(meta_provider, level + 1, read_parallelism)?;
+                    let field = DataFusionExpr::Cast {
+                        expr: Box::new(col(&column_name.field)),
+                        data_type: DataType::Float64,
+                    };
+                    let value_expr = scalar_binary_expr(field, func, scalar, scalar_on_left);
+                    let plan = if is_comparison_op(&op) && !return_bool {
+                        // Comparisons without bool drop the samples whose result is false
+                        // and keep the values of the others.
+                        LogicalPlanBuilder::from(sub_plan)
+                            .filter(value_expr.eq(lit(1.0)))?
+                            .build()?
+                    } else {
+                        let projection = sub_plan
+                            .schema()
+                            .fields()
+                            .iter()
+                            .map(|field| {
+                                if field.name() == &column_name.field {
+                                    DataFusionExpr::Alias(
+                                        Box::new(value_expr.clone()),
+                                        column_name.field.clone(),
+                                    )
+                                } else {
+                                    DataFusionExpr::Column(field.qualified_column())
+                                }
+                            })
+                            .collect::<Vec<_>>();
+                        LogicalPlanBuilder::from(sub_plan)
+                            .project(projection)?
+                            .build()?
+                    };
+
+                    Ok((plan, column_name, table_name))
                 }
-                .fail(),
             },
         }
     }
@@ -446,42 +546,57 @@ impl TryFrom<PbSubExpr> for Expr {
 impl SubExpr {
     pub fn get_selector(&self) -> &Selector {
         match self {
-            SubExpr::Aggr(AggrExpr { operands, .. }) => operands[0].get_selector(),
-            SubExpr::Func(FuncExpr { operands, .. }) => operands[0].get_selector(),
-            SubExpr::Binary(BinaryExpr { operands, .. }) => operands[0].get_selector(),
+            SubExpr::Aggr(AggrExpr { operands, .. }) => {
+                Self::vector_operand(operands).get_selector()
+            }
+            SubExpr::Func(FuncExpr { operands, .. }) => {
+                Self::vector_operand(operands).get_selector()
+            }
+            SubExpr::Binary(BinaryExpr { operands, .. }) => {
+                Self::vector_operand(operands).get_selector()
+            }
         }
     }
 
     pub fn is_range_fn(&self) -> bool {
         match self {
-            Self::Func(FuncExpr { operands, .. }) => match &operands[0] {
+            Self::Func(FuncExpr { operands, .. }) => match Self::vector_operand(operands) {
                 Expr::SimpleExpr(Operand::Selector(sel)) => sel.range > 0,
                 _ => false,
             },
             _ => false,
         }
     }
+
+    /// Returns the first operand which is not a scalar, scalar args may come
+    /// before the vector, such as `quantile_over_time(0.5, v[5m])`.
+    fn vector_operand(operands: &[Expr]) -> &Expr {
+        operands
+            .iter()
+            .find(|e| !matches!(e, Expr::SimpleExpr(Operand::Float(_) | Operand::String(_))))
+            .unwrap_or(&operands[0])
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct AggrExpr {
-    op: String,
-    operands: Vec<Expr>,
-    group_by: Vec<String>,
-    without: bool,
+    pub(crate) op: String,
+    pub(crate) operands: Vec<Expr>,
+    pub(crate) group_by: Vec<String>,
+    pub(crate) without: bool,
 }
 
 #[derive(Debug, Clone)]
 pub struct FuncExpr {
-    op: String,
-    operands: Vec<Expr>,
+    pub(crate) op: String,
+    pub(crate) operands: Vec<Expr>,
 }
 
 #[derive(Debug, Clone)]
 pub struct BinaryExpr {
-    _op: String,
-    operands: Vec<Expr>,
-    _return_bool: bool,
+    pub(crate) op: String,
+    pub(crate) operands: Vec<Expr>,
+    pub(crate) return_bool: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -505,45 +620,157 @@ impl From<FilterPbType> for FilterType {
 
 #[derive(Debug, Clone)]
 pub struct FilterOperator {
-    typ: FilterType,
-    params: Vec<String>,
+    pub(crate) typ: FilterType,
+    pub(crate) params: Vec<String>,
 }
 
 #[derive(Debug, Clone)]
 pub struct Filter {
-    tag_key: String,
-    operators: Vec<FilterOperator>,
+    pub(crate) tag_key: String,
+    pub(crate) operators: Vec<FilterOperator>,
 }
 
-impl From<Filter> for DataFusionExpr {
-    fn from(mut f: Filter) -> DataFusionExpr {
-        let tag_key = col(&f.tag_key);
-        // TODO(chenxiang): only compare first op now
-        let mut first_op = f.operators.remove(0);
-        match first_op.typ {
-            // regepx filter only have one param
-            FilterType::Regexp => regex_match_expr(tag_key, first_op.params.remove(0), true),
-            FilterType::NotRegexpMatch => {
-                regex_match_expr(tag_key, first_op.params.remove(0), false)
+impl FilterOperator {
+    /// Translate the operator on the `tag_key` into expr with the semantics of
+    /// the prometheus label matcher: the absent label is regarded as empty
+    /// string, and the regex is fully anchored.
+    fn into_expr(self, tag_key: &str) -> DataFusionExpr {
+        match self.typ {
+            FilterType::LiteralOr => literal_or_expr(tag_key, self.params, false),
+            FilterType::NotLiteralOr => literal_or_expr(tag_key, self.params, true),
+            // regex filter only have one param
+            FilterType::Regexp => regex_expr(tag_key, first_param(self.params), false),
+            FilterType::NotRegexpMatch => regex_expr(tag_key, first_param(self.params), true),
+        }
+    }
+}
+
+impl Filter {
+    /// Whether the absent label, which is regarded as empty string, matches
+    /// all the operators of this filter.
+    pub(crate) fn matches_empty(&self) -> bool {
+        self.operators.iter().all(|op| {
+            let param = op.params.first().map(String::as_str).unwrap_or_default();
+            match op.typ {
+                FilterType::LiteralOr => op.params.iter().any(|v| v.is_empty()),
+                FilterType::NotLiteralOr => !op.params.iter().any(|v| v.is_empty()),
+                FilterType::Regexp => regex_matches_empty(param),
+                FilterType::NotRegexpMatch => !regex_matches_empty(param),
             }
-            FilterType::LiteralOr => tag_key.in_list(
-                first_op
-                    .params
-                    .iter()
-                    .map(|v| lit(v.as_str()))
-                    .collect::<Vec<_>>(),
-                false,
-            ),
-            FilterType::NotLiteralOr => tag_key.in_list(
-                first_op
-                    .params
-                    .iter()
-                    .map(|v| lit(v.as_str()))
-                    .collect::<Vec<_>>(),
-                true,
-            ),
+        })
+    }
+}
+
+fn regex_matches_empty(pattern: &str) -> bool {
+    Regex::new(&anchored_regex(pattern))
+        .map(|v| v.is_match(""))
+        .unwrap_or(false)
+}
+
+fn first_param(params: Vec<String>) -> String {
+    params.into_iter().next().unwrap_or_default()
+}
+
+/// Build the expr of whether the tag equals to any of the `values`, or equals
+/// to none of them if `negated`.
+fn literal_or_expr(tag_key: &str, values: Vec<String>, negated: bool) -> DataFusionExpr {
+    let column = col(tag_key);
+    // The absent label is regarded as empty string.
+    let match_null = values.iter().any(|v| v.is_empty());
+    let mut literals = values
+        .into_iter()
+        .filter(|v| !v.is_empty())
+        .map(lit)
+        .collect::<Vec<_>>();
+    // Translate the single value into `=`, which is also able to prune the row
+    // groups by the min/max statistics.
+    let values_expr = match literals.len() {
+        0 => None,
+        1 if negated => Some(column.clone().not_eq(literals.remove(0))),
+        1 => Some(column.clone().eq(literals.remove(0))),
+        _ => Some(column.clone().in_list(literals, negated)),
+    };
+
+    // A stored empty string is also regarded as absent.
+    let is_empty = || column.clone().is_null().or(column.clone().eq(lit("")));
+    match (values_expr, match_null, negated) {
+        (Some(expr), false, false) => expr,
+        (Some(expr), true, false) => is_empty().or(expr),
+        (Some(expr), false, true) => column.clone().is_null().or(expr),
+        // The null is already filtered out by the `!=` or `NOT IN`.
+        (Some(expr), true, true) => expr.and(column.clone().not_eq(lit(""))),
+        (None, true, false) => is_empty(),
+        (None, true, true) => column.clone().is_not_null().and(column.not_eq(lit(""))),
+        (None, false, _) => lit(negated),
+    }
+}
+
+/// Build the expr of whether the tag matches the regex `pattern`, or doesn't
+/// match it if `negated`.
+fn regex_expr(tag_key: &str, pattern: String, negated: bool) -> DataFusionExpr {
+    // Optimize the literal alternations like `a|b|c` into `IN` list, which can be
+    // answered by the filters and indexes of the tag columns.
+    if let Some(values) = literal_alternatives(&pattern) {
+        return literal_or_expr(tag_key, values, negated);
+    }
+    match pattern.as_str() {
+        ".*" => return lit(!negated),
+        // The empty string is regarded as absent.
+        ".+" if negated => return col(tag_key).is_null().or(col(tag_key).eq(lit(""))),
+        ".+" => return col(tag_key).is_not_null().and(col(tag_key).not_eq(lit(""))),
+        _ => (),
+    }
+
+    // The absent label is regarded as empty string.
+    let match_empty = regex_matches_empty(&pattern);
+    let expr = regex_match_expr(col(tag_key), anchored_regex(&pattern), !negated);
+    if match_empty != negated {
+        col(tag_key).is_null().or(expr)
+    } else {
+        expr
+    }
+}
+
+/// Anchor the regex `pattern` to match the whole value, as prometheus does.
+pub(crate) fn anchored_regex(pattern: &str) -> String {
+    format!("^(?:{})$", pattern)
+}
+
+/// Returns the alternatives if the regex `pattern` consists of literal
+/// alternations like `a|b|c` only, the escaped meta characters are allowed.
+fn literal_alternatives(pattern: &str) -> Option<Vec<String>> {
+    const META_CHARS: &str = r"\.+*?()|[]{}^$#&-~";
+
+    let mut alternatives = vec![String::new()];
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '|' => alternatives.push(String::new()),
+            '\\' => match chars.next() {
+                Some(c) if META_CHARS.contains(c) => alternatives.last_mut().unwrap().push(c),
+                _ => return None,
+            },
+            // The `#`, `&`, `-` and `~` are literal out of the character class.
+            '.' | '+' | '*' | '?' | '(' | ')' | '[' | ']' | '{' | '}' | '^' | '$' => return None,
+            c => alternatives.last_mut().unwrap().push(c),
         }
     }
+
+    Some(alternatives)
+}
+
+impl From<Filter> for DataFusionExpr {
+    fn from(f: Filter) -> DataFusionExpr {
+        let tag_key = f.tag_key;
+        let exprs = f
+            .operators
+            .into_iter()
+            .map(|op| op.into_expr(&tag_key))
+            .collect::<Vec<_>>();
+
+        // All the operators must be satisfied.
+        combine_filters(&exprs).unwrap_or_else(|| lit(true))
+    }
 }
 
 impl From<FilterPb> for Filter {
@@ -601,17 +828,7 @@ impl Selector {
         let schema = Schema::try_from(table_provider.schema()).context(BuildTableSchema)?;
         let timestamp_column_name = schema.timestamp_name().to_string();
         let (projection, tag_keys) = Self::build_projection_tag_keys(&schema, &field)?;
-        let mut filter_exprs = filters
-            .iter()
-            .filter_map(|f| {
-                // drop non_exist filter
-                if tag_keys.contains(&f.tag_key) {
-                    Some(DataFusionExpr::from(f.clone()))
-                } else {
-                    None
-                }
-            })
-            .collect::<Vec<_>>();
+        let mut filter_exprs = pushdown::tag_filter_exprs(filters, &tag_keys);
         filter_exprs.push(timerange_to_expr(query_range, &timestamp_column_name));
 
         let builder = LogicalPlanBuilder::scan(table.clone(), table_provider, None)?
@@ -627,6 +844,67 @@ impl Selector {
         Ok((scan_plan, column_name, table))
     }
 
+    /// Build plan to find out the distinct series matched by this selector,
+    /// returns the plan and the tag keys of the table.
+    ///
+    /// The plan is like:
+    /// Aggregate: group by tag keys
+    ///   Filter:
+    ///     TableScan
+    pub fn into_series_plan<P: MetaProvider>(
+        self,
+        meta_provider: ContextProviderAdapter<'_, P>,
+    ) -> Result<(Plan, Vec<String>)> {
+        let Selector {
+            query_range,
+            filters,
+            table,
+            ..
+        } = self;
+        let table_ref = meta_provider
+            .table(table.as_str().into())
+            .context(MetaProviderError {
+                msg: "failed to find table".to_string(),
+            })?
+            .context(TableNotFound { name: &table })?;
+
+        let table_provider = meta_provider
+            .get_table_provider(table_ref.name().into())
+            .context(TableNotFound { name: &table })?;
+        let schema = Schema::try_from(table_provider.schema()).context(BuildTableSchema)?;
+        let timestamp_column_name = schema.timestamp_name().to_string();
+        let tag_keys = schema
+            .columns()
+            .iter()
+            .filter(|column| column.is_tag)
+            .map(|column| column.name.clone())
+            .collect::<Vec<_>>();
+        let mut filter_exprs = pushdown::tag_filter_exprs(filters, &tag_keys);
+        filter_exprs.push(timerange_to_expr(query_range, &timestamp_column_name));
+
+        let builder = LogicalPlanBuilder::scan(table.clone(), table_provider, None)?
+            .filter(combine_filters(&filter_exprs).expect("at least one filter(timestamp)"))?;
+        let builder = if tag_keys.is_empty() {
+            // Only one series in the table, just check whether there is any data.
+            builder
+                .project(vec![col(&timestamp_column_name)])?
+                .limit(1)?
+        } else {
+            let group_exprs = tag_keys.iter().map(|v| col(v)).collect::<Vec<_>>();
+            builder.aggregate(group_exprs, Vec::<DataFusionExpr>::new())?
+        };
+        let df_plan = builder.build().context(BuildPlanError)?;
+
+        let tables = Arc::new(
+            meta_provider
+                .try_into_container()
+                .context(MetaProviderError {
+                    msg: "Failed to find meta",
+                })?,
+        );
+        Ok((Plan::Query(QueryPlan { df_plan, tables }), tag_keys))
+    }
+
     fn build_projection_tag_keys(
         schema: &Schema,
         field: &str,
@@ -671,3 +949,190 @@ impl Selector {
         Ok((projection, tag_keys))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter_expr(typ: FilterType, params: &[&str]) -> DataFusionExpr {
+        DataFusionExpr::from(Filter {
+            tag_key: "job".to_string(),
+            operators: vec![FilterOperator {
+                typ,
+                params: params.iter().map(|v| v.to_string()).collect(),
+            }],
+        })
+    }
+
+    #[test]
+    fn test_literal_filter_expr() {
+        let job = || col("job");
+
+        assert_eq!(
+            job().eq(lit("api")),
+            filter_expr(FilterType::LiteralOr, &["api"])
+        );
+        assert_eq!(
+            job().in_list(vec![lit("api"), lit("web")], false),
+            filter_expr(FilterType::LiteralOr, &["api", "web"])
+        );
+        // The absent label is regarded as empty string.
+        assert_eq!(
+            job().is_null().or(job().eq(lit(""))),
+            filter_expr(FilterType::LiteralOr, &[""])
+        );
+        assert_eq!(
+            job()
+                .is_null()
+                .or(job().eq(lit("")))
+                .or(job().eq(lit("api"))),
+            filter_expr(FilterType::LiteralOr, &["api", ""])
+        );
+
+        assert_eq!(
+            job().is_null().or(job().not_eq(lit("api"))),
+            filter_expr(FilterType::NotLiteralOr, &["api"])
+        );
+        assert_eq!(
+            job()
+                .is_null()
+                .or(job().in_list(vec![lit("api"), lit("web")], true)),
+            filter_expr(FilterType::NotLiteralOr, &["api", "web"])
+        );
+        assert_eq!(
+            job().is_not_null().and(job().not_eq(lit(""))),
+            filter_expr(FilterType::NotLiteralOr, &[""])
+        );
+        assert_eq!(
+            job().not_eq(lit("api")).and(job().not_eq(lit(""))),
+            filter_expr(FilterType::NotLiteralOr, &["api", ""])
+        );
+    }
+
+    #[test]
+    fn test_regex_filter_expr() {
+        let job = || col("job");
+
+        // Literal alternations are optimized into `IN` list.
+        assert_eq!(
+            job().in_list(vec![lit("api"), lit("web"), lit("db")], false),
+            filter_expr(FilterType::Regexp, &["api|web|db"])
+        );
+        assert_eq!(
+            job().eq(lit("a.b-c")),
+            filter_expr(FilterType::Regexp, &[r"a\.b-c"])
+        );
+        assert_eq!(
+            job()
+                .is_null()
+                .or(job().in_list(vec![lit("api"), lit("web")], true)),
+            filter_expr(FilterType::NotRegexpMatch, &["api|web"])
+        );
+        assert_eq!(
+            job()
+                .is_null()
+                .or(job().eq(lit("")))
+                .or(job().eq(lit("api"))),
+            filter_expr(FilterType::Regexp, &["api|"])
+        );
+
+        assert_eq!(lit(true), filter_expr(FilterType::Regexp, &[".*"]));
+        assert_eq!(lit(false), filter_expr(FilterType::NotRegexpMatch, &[".*"]));
+        assert_eq!(
+            job().is_not_null().and(job().not_eq(lit(""))),
+            filter_expr(FilterType::Regexp, &[".+"])
+        );
+        assert_eq!(
+            job().is_null().or(job().eq(lit(""))),
+            filter_expr(FilterType::NotRegexpMatch, &[".+"])
+        );
+
+        // The regex is anchored.
+        assert_eq!(
+            regex_match_expr(job(), "^(?:api.*)$".to_string(), true),
+            filter_expr(FilterType::Regexp, &["api.*"])
+        );
+        assert_eq!(
+            job()
+                .is_null()
+                .or(regex_match_expr(job(), "^(?:api.*)$".to_string(), false)),
+            filter_expr(FilterType::NotRegexpMatch, &["api.*"])
+        );
+        // The regex matching empty string also matches the absent label.
+        assert_eq!(
+            job()
+                .is_null()
+                .or(regex_match_expr(job(), "^(?:a?)$".to_string(), true)),
+            filter_expr(FilterType::Regexp, &["a?"])
+        );
+        assert_eq!(
+            regex_match_expr(job(), "^(?:a?)$".to_string(), false),
+            filter_expr(FilterType::NotRegexpMatch, &["a?"])
+        );
+    }
+
+    #[test]
+    fn test_filter_matches_empty() {
+        let matches_empty = |typ, params: &[&str]| {
+            Filter {
+                tag_key: "job".to_string(),
+                operators: vec![FilterOperator {
+                    typ,
+                    params: params.iter().map(|v| v.to_string()).collect(),
+                }],
+            }
+            .matches_empty()
+        };
+
+        assert!(matches_empty(FilterType::LiteralOr, &[""]));
+        assert!(!matches_empty(FilterType::LiteralOr, &["api"]));
+        assert!(matches_empty(FilterType::NotLiteralOr, &["api"]));
+        assert!(!matches_empty(FilterType::NotLiteralOr, &[""]));
+        assert!(matches_empty(FilterType::Regexp, &["a?"]));
+        assert!(!matches_empty(FilterType::Regexp, &[".+"]));
+        assert!(matches_empty(FilterType::NotRegexpMatch, &["api.*"]));
+        assert!(!matches_empty(FilterType::NotRegexpMatch, &[".*"]));
+    }
+
+    #[test]
+    fn test_multiple_operators_filter_expr() {
+        let expr = DataFusionExpr::from(Filter {
+            tag_key: "job".to_string(),
+            operators: vec![
+                FilterOperator {
+                    typ: FilterType::Regexp,
+                    params: vec!["api|web".to_string()],
+                },
+                FilterOperator {
+                    typ: FilterType::NotLiteralOr,
+                    params: vec!["web".to_string()],
+                },
+            ],
+        });
+
+        let job = || col("job");
+        assert_eq!(
+            job()
+                .in_list(vec![lit("api"), lit("web")], false)
+                .and(job().is_null().or(job().not_eq(lit("web")))),
+            expr
+        );
+    }
+
+    #[test]
+    fn test_literal_alternatives() {
+        let alternatives = literal_alternatives;
+
+        assert_eq!(Some(vec!["api".to_string()]), alternatives("api"));
+        assert_eq!(
+            Some(vec!["a".to_string(), "".to_string(), "b~#&".to_string()]),
+            alternatives("a||b~#&")
+        );
+        assert_eq!(Some(vec!["a|b".to_string()]), alternatives(r"a\|b"));
+        assert_eq!(None, alternatives("a.*"));
+        assert_eq!(None, alternatives("(a|b)"));
+        assert_eq!(None, alternatives("[ab]"));
+        assert_eq!(None, alternatives(r"\d"));
+        assert_eq!(None, alternatives(r"a\"));
+    }
+}
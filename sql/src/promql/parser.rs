@@ -0,0 +1,1222 @@
+// Copyright 2022 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! PromQL text parser
+//!
+//! Parses the PromQL text of the http api into [Expr], the same expr as the
+//! one converted from the protobuf of the grpc api, so the query can be
+//! planned by the existing plan builders.
+
+use common_types::time::{TimeRange, Timestamp};
+use snafu::{ensure, OptionExt, Snafu};
+
+use crate::promql::{
+    convert::{
+        anchored_regex, AggrExpr, BinaryExpr, Expr, Filter, FilterOperator, FilterType, FuncExpr,
+        Operand, Selector, SubExpr, DEFAULT_LOOKBACK,
+    },
+    udf::{binary_op_fn, is_comparison_op},
+};
+
+/// Label of the metric name.
+pub const METRIC_NAME_LABEL: &str = "__name__";
+/// Label to specify the field column to query, the [DEFAULT_FIELD] is used if
+/// absent.
+pub const FIELD_LABEL: &str = "__ceresdb_field__";
+/// Default field column of the prometheus tables.
+pub const DEFAULT_FIELD: &str = "value";
+
+const AGGREGATE_OPS: [&str; 12] = [
+    "sum",
+    "min",
+    "max",
+    "avg",
+    "count",
+    "stddev",
+    "stdvar",
+    "group",
+    "topk",
+    "bottomk",
+    "quantile",
+    "count_values",
+];
+const PARAMETERIZED_AGGREGATE_OPS: [&str; 4] = ["topk", "bottomk", "quantile", "count_values"];
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Unexpected char in promql, pos:{}, char:{:?}", pos, c))]
+    UnexpectedChar { pos: usize, c: char },
+
+    #[snafu(display("Unterminated string in promql, pos:{}", pos))]
+    UnterminatedString { pos: usize },
+
+    #[snafu(display("Invalid number in promql, number:{}", number))]
+    InvalidNumber { number: String },
+
+    #[snafu(display("Invalid duration, duration:{}", duration))]
+    InvalidDuration { duration: String },
+
+    #[snafu(display("Unexpected token, expected:{}, actual:{:?}", expected, actual))]
+    UnexpectedToken { expected: String, actual: Token },
+
+    #[snafu(display("Invalid promql expr, msg:{}", msg))]
+    InvalidExpr { msg: String },
+
+    #[snafu(display("Unsupported promql expr, msg:{}", msg))]
+    Unsupported { msg: String },
+}
+
+define_result!(Error);
+
+/// Parameters to evaluate the expr, all timestamps are in milliseconds.
+#[derive(Debug, Clone, Copy)]
+pub struct EvalParams {
+    /// Inclusive start of the evaluation
+    pub start: i64,
+    /// Inclusive end of the evaluation
+    pub end: i64,
+    /// Evaluation step, must be positive
+    pub step: i64,
+    /// Look back delta of instant selectors
+    pub lookback_delta: i64,
+}
+
+impl EvalParams {
+    /// Params of an instant query evaluated at `time`.
+    pub fn instant(time: i64) -> Self {
+        Self {
+            start: time,
+            end: time,
+            // Only one step in [time, time], the step just needs to be positive.
+            step: 1,
+            lookback_delta: DEFAULT_LOOKBACK,
+        }
+    }
+
+    /// Params of a range query.
+    pub fn range(start: i64, end: i64, step: i64) -> Self {
+        Self {
+            start,
+            end,
+            step,
+            lookback_delta: DEFAULT_LOOKBACK,
+        }
+    }
+
+    /// Params to select series in [start, end], without looking back.
+    pub fn series(start: i64, end: i64) -> Self {
+        Self {
+            start,
+            end,
+            step: 1,
+            lookback_delta: 0,
+        }
+    }
+}
+
+/// Parse the promql query into [Expr].
+pub fn parse_expr(query: &str, params: EvalParams) -> Result<Expr> {
+    let tokens = tokenize(query)?;
+    let mut parser = Parser {
+        tokens,
+        index: 0,
+        params,
+    };
+
+    let expr = parser.parse_expr()?;
+    parser.expect(&Token::Eof)?;
+    ensure_not_range_vector(&expr)?;
+
+    Ok(expr)
+}
+
+/// Parse the series selector, such as the `match[]` param of the metadata
+/// apis.
+pub fn parse_selector(query: &str, params: EvalParams) -> Result<Selector> {
+    match parse_expr(query, params)? {
+        Expr::SimpleExpr(Operand::Selector(selector)) => Ok(selector),
+        expr => InvalidExpr {
+            msg: format!("expect series selector, expr:{:?}", expr),
+        }
+        .fail(),
+    }
+}
+
+/// Build a selector selecting all series of the table.
+pub fn table_selector(table: String, params: EvalParams) -> Selector {
+    build_selector(params, table, Vec::new(), DEFAULT_FIELD.to_string(), 0, 0)
+}
+
+/// Label matcher of a series selector, such as `job="api"`.
+#[derive(Debug, Clone)]
+pub struct LabelMatcher {
+    pub name: String,
+    pub typ: FilterType,
+    pub value: String,
+}
+
+/// Build a selector from the label matchers, the metric name must be matched
+/// by the `__name__` label.
+pub fn matchers_to_selector(matchers: Vec<LabelMatcher>, params: EvalParams) -> Result<Selector> {
+    let (table, filters, field) = split_matchers(None, matchers)?;
+
+    Ok(build_selector(params, table, filters, field, 0, 0))
+}
+
+/// Parse duration like `1h30m` into milliseconds.
+pub fn parse_duration(duration: &str) -> Result<i64> {
+    let chars = duration.chars().collect::<Vec<_>>();
+    let mut pos = 0;
+    let millis = lex_duration(&chars, &mut pos).context(InvalidDuration { duration })?;
+    ensure!(pos == chars.len(), InvalidDuration { duration });
+
+    Ok(millis)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    Ident(String),
+    Number(f64),
+    /// Duration in milliseconds
+    Duration(i64),
+    String(String),
+    LeftParen,
+    RightParen,
+    LeftBrace,
+    RightBrace,
+    LeftBracket,
+    RightBracket,
+    Comma,
+    /// `=`
+    Assign,
+    /// `!=`
+    NotEq,
+    /// `=~`
+    RegexMatch,
+    /// `!~`
+    RegexNotMatch,
+    /// `==`
+    Eq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Pow,
+    Eof,
+}
+
+fn is_ident_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_' || c == ':'
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == ':'
+}
+
+fn duration_unit_millis(unit: &str) -> Option<i64> {
+    let millis = match unit {
+        "ms" => 1,
+        "s" => 1000,
+        "m" => 60 * 1000,
+        "h" => 60 * 60 * 1000,
+        "d" => 24 * 60 * 60 * 1000,
+        "w" => 7 * 24 * 60 * 60 * 1000,
+        "y" => 365 * 24 * 60 * 60 * 1000,
+        _ => return None,
+    };
+
+    Some(millis)
+}
+
+/// Lex a duration like `1h30m` starting from `pos`, returns None if it is not
+/// a valid duration.
+fn lex_duration(chars: &[char], pos: &mut usize) -> Option<i64> {
+    let mut millis: i64 = 0;
+    let mut has_part = false;
+    loop {
+        let start = *pos;
+        while *pos < chars.len() && chars[*pos].is_ascii_digit() {
+            *pos += 1;
+        }
+        if start == *pos {
+            break;
+        }
+        let num = chars[start..*pos]
+            .iter()
+            .collect::<String>()
+            .parse::<i64>()
+            .ok()?;
+
+        let unit_start = *pos;
+        while *pos < chars.len() && chars[*pos].is_ascii_alphabetic() {
+            *pos += 1;
+        }
+        let unit = chars[unit_start..*pos].iter().collect::<String>();
+        let unit_millis = duration_unit_millis(&unit)?;
+        millis = millis.checked_add(num.checked_mul(unit_millis)?)?;
+        has_part = true;
+    }
+
+    if has_part {
+        Some(millis)
+    } else {
+        None
+    }
+}
+
+fn tokenize(query: &str) -> Result<Vec<Token>> {
+    let chars = query.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+
+    while pos < chars.len() {
+        let c = chars[pos];
+        if c.is_whitespace() {
+            pos += 1;
+            continue;
+        }
+        // Comment lasts to the end of line.
+        if c == '#' {
+            while pos < chars.len() && chars[pos] != '\n' {
+                pos += 1;
+            }
+            continue;
+        }
+
+        let next = chars.get(pos + 1).copied();
+        let token = match c {
+            '(' => Token::LeftParen,
+            ')' => Token::RightParen,
+            '{' => Token::LeftBrace,
+            '}' => Token::RightBrace,
+            '[' => Token::LeftBracket,
+            ']' => Token::RightBracket,
+            ',' => Token::Comma,
+            '+' => Token::Add,
+            '-' => Token::Sub,
+            '*' => Token::Mul,
+            '/' => Token::Div,
+            '%' => Token::Mod,
+            '^' => Token::Pow,
+            '=' => match next {
+                Some('=') => {
+                    pos += 1;
+                    Token::Eq
+                }
+                Some('~') => {
+                    pos += 1;
+                    Token::RegexMatch
+                }
+                _ => Token::Assign,
+            },
+            '!' => match next {
+                Some('=') => {
+                    pos += 1;
+                    Token::NotEq
+                }
+                Some('~') => {
+                    pos += 1;
+                    Token::RegexNotMatch
+                }
+                _ => return UnexpectedChar { pos, c }.fail(),
+            },
+            '<' => {
+                if next == Some('=') {
+                    pos += 1;
+                    Token::LtEq
+                } else {
+                    Token::Lt
+                }
+            }
+            '>' => {
+                if next == Some('=') {
+                    pos += 1;
+                    Token::GtEq
+                } else {
+                    Token::Gt
+                }
+            }
+            '"' | '\'' | '`' => {
+                let (value, end) = lex_string(&chars, pos)?;
+                tokens.push(Token::String(value));
+                pos = end;
+                continue;
+            }
+            c if c.is_ascii_digit() || (c == '.' && next.map_or(false, |n| n.is_ascii_digit())) => {
+                let (token, end) = lex_number_or_duration(&chars, pos)?;
+                tokens.push(token);
+                pos = end;
+                continue;
+            }
+            c if is_ident_start(c) => {
+                let start = pos;
+                while pos < chars.len() && is_ident_char(chars[pos]) {
+                    pos += 1;
+                }
+                tokens.push(Token::Ident(chars[start..pos].iter().collect()));
+                continue;
+            }
+            c => return UnexpectedChar { pos, c }.fail(),
+        };
+
+        tokens.push(token);
+        pos += 1;
+    }
+    tokens.push(Token::Eof);
+
+    Ok(tokens)
+}
+
+/// Lex the quoted string starting at `start`, returns the unescaped string and
+/// the position after the closing quote.
+fn lex_string(chars: &[char], start: usize) -> Result<(String, usize)> {
+    let quote = chars[start];
+    let mut value = String::new();
+    let mut pos = start + 1;
+
+    while pos < chars.len() {
+        let c = chars[pos];
+        if c == quote {
+            return Ok((value, pos + 1));
+        }
+        // No escaping in raw string.
+        if c == '\\' && quote != '`' {
+            pos += 1;
+            let escaped = *chars.get(pos).context(UnterminatedString { pos: start })?;
+            let unescaped = match escaped {
+                'n' => '\n',
+                't' => '\t',
+                'r' => '\r',
+                c => c,
+            };
+            value.push(unescaped);
+        } else {
+            value.push(c);
+        }
+        pos += 1;
+    }
+
+    UnterminatedString { pos: start }.fail()
+}
+
+fn lex_number_or_duration(chars: &[char], start: usize) -> Result<(Token, usize)> {
+    let mut pos = start;
+    if let Some(millis) = lex_duration(chars, &mut pos) {
+        return Ok((Token::Duration(millis), pos));
+    }
+
+    pos = start;
+    while pos < chars.len() && (chars[pos].is_ascii_digit() || chars[pos] == '.') {
+        pos += 1;
+    }
+    if pos < chars.len() && (chars[pos] == 'e' || chars[pos] == 'E') {
+        pos += 1;
+        if pos < chars.len() && (chars[pos] == '+' || chars[pos] == '-') {
+            pos += 1;
+        }
+        while pos < chars.len() && chars[pos].is_ascii_digit() {
+            pos += 1;
+        }
+    }
+
+    let number = chars[start..pos].iter().collect::<String>();
+    let value = number
+        .parse::<f64>()
+        .ok()
+        .context(InvalidNumber { number })?;
+
+    Ok((Token::Number(value), pos))
+}
+
+/// Binary operators ordered by precedence from low to high, `^` is handled
+/// separately as it is right associative.
+const BINARY_OP_LEVELS: [&[&str]; 5] = [
+    &["or"],
+    &["and", "unless"],
+    &["==", "!=", "<", "<=", ">", ">="],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+fn binary_op_of_token(token: &Token) -> Option<&'static str> {
+    let op = match token {
+        Token::Ident(ident) => match ident.to_lowercase().as_str() {
+            "or" => "or",
+            "and" => "and",
+            "unless" => "unless",
+            _ => return None,
+        },
+        Token::Eq => "==",
+        Token::NotEq => "!=",
+        Token::Lt => "<",
+        Token::LtEq => "<=",
+        Token::Gt => ">",
+        Token::GtEq => ">=",
+        Token::Add => "+",
+        Token::Sub => "-",
+        Token::Mul => "*",
+        Token::Div => "/",
+        Token::Mod => "%",
+        Token::Pow => "^",
+        _ => return None,
+    };
+
+    Some(op)
+}
+
+fn is_grouping_keyword(token: &Token) -> bool {
+    match token {
+        Token::Ident(v) => v.eq_ignore_ascii_case("by") || v.eq_ignore_ascii_case("without"),
+        _ => false,
+    }
+}
+
+fn is_range_vector(expr: &Expr) -> bool {
+    matches!(expr, Expr::SimpleExpr(Operand::Selector(selector)) if selector.range > 0)
+}
+
+fn ensure_not_range_vector(expr: &Expr) -> Result<()> {
+    ensure!(
+        !is_range_vector(expr),
+        InvalidExpr {
+            msg: "range vector is only allowed as function argument",
+        }
+    );
+
+    Ok(())
+}
+
+/// Split the matchers into the table, the filters of tags and the field.
+fn split_matchers(
+    metric_name: Option<String>,
+    matchers: Vec<LabelMatcher>,
+) -> Result<(String, Vec<Filter>, String)> {
+    let mut table = metric_name;
+    let mut field = None;
+    let mut filters = Vec::with_capacity(matchers.len());
+    for LabelMatcher { name, typ, value } in matchers {
+        if name == METRIC_NAME_LABEL || name == FIELD_LABEL {
+            ensure!(
+                matches!(typ, FilterType::LiteralOr),
+                Unsupported {
+                    msg: format!("only equal matcher is supported by label {}", name),
+                }
+            );
+            let target = if name == METRIC_NAME_LABEL {
+                &mut table
+            } else {
+                &mut field
+            };
+            ensure!(
+                target.is_none(),
+                InvalidExpr {
+                    msg: format!("label {} is specified more than once", name),
+                }
+            );
+            *target = Some(value);
+        } else {
+            if matches!(typ, FilterType::Regexp | FilterType::NotRegexpMatch) {
+                if let Err(e) = regex::Regex::new(&anchored_regex(&value)) {
+                    return InvalidExpr {
+                        msg: format!("invalid regex in label matcher, regex:{}, err:{}", value, e),
+                    }
+                    .fail();
+                }
+            }
+            filters.push(Filter {
+                tag_key: name,
+                operators: vec![FilterOperator {
+                    typ,
+                    params: vec![value],
+                }],
+            });
+        }
+    }
+    let table = table.context(InvalidExpr {
+        msg: "metric name is required in selector",
+    })?;
+
+    Ok((
+        table,
+        filters,
+        field.unwrap_or_else(|| DEFAULT_FIELD.to_string()),
+    ))
+}
+
+fn build_selector(
+    params: EvalParams,
+    table: String,
+    filters: Vec<Filter>,
+    field: String,
+    range: i64,
+    offset: i64,
+) -> Selector {
+    let lookback = if range > 0 {
+        range
+    } else {
+        params.lookback_delta
+    };
+    // Sample at `t` is shifted to `t + offset` when aligning, so samples in
+    // [start - offset - lookback, end - offset] are required.
+    let query_start = params.start.saturating_sub(offset).saturating_sub(lookback);
+    let query_end = params.end.saturating_sub(offset).saturating_add(1);
+
+    Selector {
+        query_range: TimeRange::new_unchecked(
+            Timestamp::new(query_start),
+            Timestamp::new(query_end),
+        ),
+        table,
+        filters,
+        field,
+        align_range: TimeRange::new_unchecked(
+            Timestamp::new(params.start),
+            Timestamp::new(params.end.saturating_add(1)),
+        ),
+        step: params.step,
+        range,
+        offset,
+    }
+}
+
+fn eval_scalar_binary(op: &str, lhs: f64, rhs: f64, return_bool: bool) -> Result<f64> {
+    let func = binary_op_fn(op).with_context(|| InvalidExpr {
+        msg: format!("operator {} not allowed between scalars", op),
+    })?;
+    ensure!(
+        return_bool || !is_comparison_op(op),
+        InvalidExpr {
+            msg: "comparisons between scalars must use bool modifier",
+        }
+    );
+
+    Ok(func(lhs, rhs))
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    index: usize,
+    params: EvalParams,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.index]
+    }
+
+    fn peek_nth(&self, n: usize) -> &Token {
+        self.tokens
+            .get(self.index + n)
+            .unwrap_or_else(|| self.tokens.last().expect("at least eof"))
+    }
+
+    fn next_token(&mut self) -> Token {
+        let token = self.tokens[self.index].clone();
+        if token != Token::Eof {
+            self.index += 1;
+        }
+        token
+    }
+
+    fn consume(&mut self, expected: &Token) -> bool {
+        if self.peek() == expected {
+            self.next_token();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn consume_keyword(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            Token::Ident(ident) if ident.eq_ignore_ascii_case(keyword) => {
+                self.next_token();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn expect(&mut self, expected: &Token) -> Result<()> {
+        if self.consume(expected) {
+            Ok(())
+        } else {
+            UnexpectedToken {
+                expected: format!("{:?}", expected),
+                actual: self.peek().clone(),
+            }
+            .fail()
+        }
+    }
+
+    fn expect_ident(&mut self) -> Result<String> {
+        match self.next_token() {
+            Token::Ident(ident) => Ok(ident),
+            actual => UnexpectedToken {
+                expected: "identifier",
+                actual,
+            }
+            .fail(),
+        }
+    }
+
+    fn expect_duration(&mut self) -> Result<i64> {
+        match self.next_token() {
+            Token::Duration(millis) => Ok(millis),
+            actual => UnexpectedToken {
+                expected: "duration",
+                actual,
+            }
+            .fail(),
+        }
+    }
+
+    fn parse_expr(&mut self) -> Result<Expr> {
+        self.parse_binary_expr(0)
+    }
+
+    fn parse_binary_expr(&mut self, level: usize) -> Result<Expr> {
+        if level == BINARY_OP_LEVELS.len() {
+            return self.parse_pow_expr();
+        }
+
+        let mut lhs = self.parse_binary_expr(level + 1)?;
+        loop {
+            let op = match binary_op_of_token(self.peek()) {
+                Some(op) if BINARY_OP_LEVELS[level].contains(&op) => op,
+                _ => break,
+            };
+            self.next_token();
+            let return_bool = is_comparison_op(op) && self.consume_keyword("bool");
+            self.ensure_no_vector_matching()?;
+            let rhs = self.parse_binary_expr(level + 1)?;
+            lhs = self.build_binary_expr(op, lhs, rhs, return_bool)?;
+        }
+
+        Ok(lhs)
+    }
+
+    /// `^` is right associative and has higher precedence than unary operators.
+    fn parse_pow_expr(&mut self) -> Result<Expr> {
+        if self.consume(&Token::Sub) {
+            let expr = self.parse_pow_expr()?;
+            return self.build_binary_expr(
+                "*",
+                Expr::SimpleExpr(Operand::Float(-1.0)),
+                expr,
+                false,
+            );
+        }
+        if self.consume(&Token::Add) {
+            return self.parse_pow_expr();
+        }
+
+        let lhs = self.parse_primary_expr()?;
+        if self.consume(&Token::Pow) {
+            self.ensure_no_vector_matching()?;
+            let rhs = self.parse_pow_expr()?;
+            return self.build_binary_expr("^", lhs, rhs, false);
+        }
+
+        Ok(lhs)
+    }
+
+    fn ensure_no_vector_matching(&self) -> Result<()> {
+        match self.peek() {
+            Token::Ident(ident)
+                if ["on", "ignoring", "group_left", "group_right"]
+                    .iter()
+                    .any(|keyword| ident.eq_ignore_ascii_case(keyword))
+                    && self.peek_nth(1) == &Token::LeftParen =>
+            {
+                Unsupported {
+                    msg: format!("vector matching {}", ident),
+                }
+                .fail()
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn build_binary_expr(&self, op: &str, lhs: Expr, rhs: Expr, return_bool: bool) -> Result<Expr> {
+        ensure_not_range_vector(&lhs)?;
+        ensure_not_range_vector(&rhs)?;
+
+        // Fold binary expr between scalars.
+        if let (Expr::SimpleExpr(Operand::Float(l)), Expr::SimpleExpr(Operand::Float(r))) =
+            (&lhs, &rhs)
+        {
+            let value = eval_scalar_binary(op, *l, *r, return_bool)?;
+            return Ok(Expr::SimpleExpr(Operand::Float(value)));
+        }
+        ensure!(
+            !matches!(lhs, Expr::SimpleExpr(Operand::String(_)))
+                && !matches!(rhs, Expr::SimpleExpr(Operand::String(_))),
+            InvalidExpr {
+                msg: format!("operator {} not allowed on string", op),
+            }
+        );
+
+        let is_scalar = |e: &Expr| matches!(e, Expr::SimpleExpr(Operand::Float(_)));
+        ensure!(
+            is_scalar(&lhs) || is_scalar(&rhs),
+            Unsupported {
+                msg: format!("binary operator {} between instant vectors", op),
+            }
+        );
+        ensure!(
+            binary_op_fn(op).is_some(),
+            InvalidExpr {
+                msg: format!("operator {} not allowed between vector and scalar", op),
+            }
+        );
+
+        Ok(Expr::RecursiveExpr(SubExpr::Binary(BinaryExpr {
+            op: op.to_string(),
+            operands: vec![lhs, rhs],
+            return_bool,
+        })))
+    }
+
+    fn parse_primary_expr(&mut self) -> Result<Expr> {
+        match self.peek().clone() {
+            Token::Number(value) => {
+                self.next_token();
+                Ok(Expr::SimpleExpr(Operand::Float(value)))
+            }
+            Token::String(value) => {
+                self.next_token();
+                Ok(Expr::SimpleExpr(Operand::String(value)))
+            }
+            Token::LeftParen => {
+                self.next_token();
+                let expr = self.parse_expr()?;
+                self.expect(&Token::RightParen)?;
+                ensure!(
+                    self.peek() != &Token::LeftBracket,
+                    Unsupported { msg: "subquery" }
+                );
+                Ok(expr)
+            }
+            Token::LeftBrace => self.parse_vector_selector(None),
+            Token::Ident(ident) => {
+                let next = self.peek_nth(1);
+                let lower = ident.to_lowercase();
+                if AGGREGATE_OPS.contains(&lower.as_str())
+                    && (next == &Token::LeftParen || is_grouping_keyword(next))
+                {
+                    self.next_token();
+                    self.parse_aggregate_expr(lower)
+                } else if next == &Token::LeftParen {
+                    self.next_token();
+                    self.parse_function_call(ident)
+                } else if lower == "inf" || lower == "nan" {
+                    self.next_token();
+                    let value = if lower == "inf" {
+                        f64::INFINITY
+                    } else {
+                        f64::NAN
+                    };
+                    Ok(Expr::SimpleExpr(Operand::Float(value)))
+                } else {
+                    self.next_token();
+                    self.parse_vector_selector(Some(ident))
+                }
+            }
+            actual => UnexpectedToken {
+                expected: "expression",
+                actual,
+            }
+            .fail(),
+        }
+    }
+
+    fn parse_grouping_labels(&mut self) -> Result<Vec<String>> {
+        self.expect(&Token::LeftParen)?;
+        let mut labels = Vec::new();
+        if self.consume(&Token::RightParen) {
+            return Ok(labels);
+        }
+        loop {
+            labels.push(self.expect_ident()?);
+            if self.consume(&Token::RightParen) {
+                return Ok(labels);
+            }
+            self.expect(&Token::Comma)?;
+            // Trailing comma is allowed.
+            if self.consume(&Token::RightParen) {
+                return Ok(labels);
+            }
+        }
+    }
+
+    /// Parse the optional `by (...)` or `without (...)` clause, returns
+    /// `Some((labels, without))` if present.
+    fn parse_grouping(&mut self) -> Result<Option<(Vec<String>, bool)>> {
+        if self.consume_keyword("by") {
+            return Ok(Some((self.parse_grouping_labels()?, false)));
+        }
+        if self.consume_keyword("without") {
+            return Ok(Some((self.parse_grouping_labels()?, true)));
+        }
+
+        Ok(None)
+    }
+
+    fn parse_aggregate_expr(&mut self, op: String) -> Result<Expr> {
+        let mut grouping = self.parse_grouping()?;
+
+        self.expect(&Token::LeftParen)?;
+        let mut operands = Vec::with_capacity(2);
+        if PARAMETERIZED_AGGREGATE_OPS.contains(&op.as_str()) {
+            let param = self.parse_expr()?;
+            self.expect(&Token::Comma)?;
+            operands.push(self.parse_expr()?);
+            operands.push(param);
+        } else {
+            operands.push(self.parse_expr()?);
+        }
+        self.expect(&Token::RightParen)?;
+        ensure_not_range_vector(&operands[0])?;
+
+        if grouping.is_none() {
+            grouping = self.parse_grouping()?;
+        }
+        let (group_by, without) = grouping.unwrap_or_default();
+
+        Ok(Expr::RecursiveExpr(SubExpr::Aggr(AggrExpr {
+            op,
+            operands,
+            group_by,
+            without,
+        })))
+    }
+
+    fn parse_function_call(&mut self, name: String) -> Result<Expr> {
+        self.expect(&Token::LeftParen)?;
+        let mut operands = Vec::new();
+        if !self.consume(&Token::RightParen) {
+            loop {
+                operands.push(self.parse_expr()?);
+                if self.consume(&Token::RightParen) {
+                    break;
+                }
+                self.expect(&Token::Comma)?;
+            }
+        }
+        ensure!(
+            !operands.is_empty(),
+            InvalidExpr {
+                msg: format!("function {} requires arguments", name),
+            }
+        );
+
+        Ok(Expr::RecursiveExpr(SubExpr::Func(FuncExpr {
+            op: name,
+            operands,
+        })))
+    }
+
+    fn parse_label_matchers(&mut self) -> Result<Vec<LabelMatcher>> {
+        self.expect(&Token::LeftBrace)?;
+        let mut matchers = Vec::new();
+        loop {
+            if self.consume(&Token::RightBrace) {
+                return Ok(matchers);
+            }
+            let name = self.expect_ident()?;
+            let typ = match self.next_token() {
+                Token::Assign => FilterType::LiteralOr,
+                Token::NotEq => FilterType::NotLiteralOr,
+                Token::RegexMatch => FilterType::Regexp,
+                Token::RegexNotMatch => FilterType::NotRegexpMatch,
+                actual => {
+                    return UnexpectedToken {
+                        expected: "label match operator",
+                        actual,
+                    }
+                    .fail()
+                }
+            };
+            let value = match self.next_token() {
+                Token::String(value) => value,
+                actual => {
+                    return UnexpectedToken {
+                        expected: "label value",
+                        actual,
+                    }
+                    .fail()
+                }
+            };
+            matchers.push(LabelMatcher { name, typ, value });
+
+            if !self.consume(&Token::Comma) {
+                self.expect(&Token::RightBrace)?;
+                return Ok(matchers);
+            }
+        }
+    }
+
+    fn parse_vector_selector(&mut self, metric_name: Option<String>) -> Result<Expr> {
+        let matchers = if self.peek() == &Token::LeftBrace {
+            self.parse_label_matchers()?
+        } else {
+            Vec::new()
+        };
+
+        let (table, filters, field) = split_matchers(metric_name, matchers)?;
+
+        let range = if self.consume(&Token::LeftBracket) {
+            let range = self.expect_duration()?;
+            // The `:` of subquery is lexed as the start of an identifier.
+            ensure!(
+                !matches!(self.peek(), Token::Ident(v) if v.starts_with(':')),
+                Unsupported { msg: "subquery" }
+            );
+            self.expect(&Token::RightBracket)?;
+            ensure!(
+                range > 0,
+                InvalidExpr {
+                    msg: "range must be positive",
+                }
+            );
+            range
+        } else {
+            0
+        };
+
+        let offset = if self.consume_keyword("offset") {
+            if self.consume(&Token::Sub) {
+                -self.expect_duration()?
+            } else {
+                self.expect_duration()?
+            }
+        } else {
+            0
+        };
+
+        let selector = build_selector(self.params, table, filters, field, range, offset);
+
+        Ok(Expr::SimpleExpr(Operand::Selector(selector)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(query: &str) -> Expr {
+        parse_expr(query, EvalParams::range(1_000_000, 2_000_000, 10_000)).unwrap()
+    }
+
+    fn unwrap_selector(expr: &Expr) -> &Selector {
+        match expr {
+            Expr::SimpleExpr(Operand::Selector(selector)) => selector,
+            expr => panic!("Expect selector, expr:{:?}", expr),
+        }
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(500, parse_duration("500ms").unwrap());
+        assert_eq!(15_000, parse_duration("15s").unwrap());
+        assert_eq!(5_400_000, parse_duration("1h30m").unwrap());
+        assert!(parse_duration("15").is_err());
+        assert!(parse_duration("15x").is_err());
+        assert!(parse_duration("").is_err());
+    }
+
+    #[test]
+    fn test_parse_selector() {
+        let expr =
+            parse(r#"http_requests{job="api", code!="500", path=~"/a.*", __ceresdb_field__="v2"}"#);
+        let selector = unwrap_selector(&expr);
+        assert_eq!("http_requests", selector.table);
+        assert_eq!("v2", selector.field);
+        assert_eq!(3, selector.filters.len());
+        assert_eq!("path", selector.filters[2].tag_key);
+        assert!(matches!(
+            selector.filters[2].operators[0].typ,
+            FilterType::Regexp
+        ));
+        assert_eq!(0, selector.range);
+        assert_eq!(
+            1_000_000 - DEFAULT_LOOKBACK,
+            selector.query_range.inclusive_start().as_i64()
+        );
+        assert_eq!(2_000_001, selector.query_range.exclusive_end().as_i64());
+
+        let expr = parse(r#"{__name__="up"} offset 1m"#);
+        let selector = unwrap_selector(&expr);
+        assert_eq!("up", selector.table);
+        assert_eq!(DEFAULT_FIELD, selector.field);
+        assert_eq!(60_000, selector.offset);
+        assert_eq!(
+            1_000_000 - 60_000 - DEFAULT_LOOKBACK,
+            selector.query_range.inclusive_start().as_i64()
+        );
+
+        // Metric name is required.
+        assert!(parse_expr(r#"{job="api"}"#, EvalParams::instant(0)).is_err());
+        // Range vector is not allowed at top level.
+        assert!(parse_expr("up[5m]", EvalParams::instant(0)).is_err());
+        assert!(parse_selector("rate(up[5m])", EvalParams::series(0, 1)).is_err());
+        assert!(parse_selector("up", EvalParams::series(0, 1)).is_ok());
+        // Invalid regex.
+        assert!(parse_expr(r#"up{job=~"(api"}"#, EvalParams::instant(0)).is_err());
+        assert!(parse_expr(r#"up{job!~"api|web"}"#, EvalParams::instant(0)).is_ok());
+    }
+
+    #[test]
+    fn test_matchers_to_selector() {
+        let matcher = |name: &str, typ, value: &str| LabelMatcher {
+            name: name.to_string(),
+            typ,
+            value: value.to_string(),
+        };
+
+        let matchers = vec![
+            matcher(METRIC_NAME_LABEL, FilterType::LiteralOr, "up"),
+            matcher("job", FilterType::NotRegexpMatch, "api.*"),
+        ];
+        let selector = matchers_to_selector(matchers, EvalParams::series(1000, 2000)).unwrap();
+        assert_eq!("up", selector.table);
+        assert_eq!(DEFAULT_FIELD, selector.field);
+        assert_eq!(1, selector.filters.len());
+        assert_eq!("job", selector.filters[0].tag_key);
+        assert_eq!(1000, selector.query_range.inclusive_start().as_i64());
+        assert_eq!(2001, selector.query_range.exclusive_end().as_i64());
+
+        // Metric name is required.
+        let matchers = vec![matcher("job", FilterType::LiteralOr, "api")];
+        assert!(matchers_to_selector(matchers, EvalParams::series(0, 1)).is_err());
+        // Metric name only supports equal matcher.
+        let matchers = vec![matcher(METRIC_NAME_LABEL, FilterType::Regexp, "up.*")];
+        assert!(matchers_to_selector(matchers, EvalParams::series(0, 1)).is_err());
+    }
+
+    #[test]
+    fn test_parse_func_and_aggr() {
+        let expr = parse("sum by (job) (rate(http_requests[5m]))");
+        match expr {
+            Expr::RecursiveExpr(SubExpr::Aggr(AggrExpr {
+                op,
+                operands,
+                group_by,
+                without,
+            })) => {
+                assert_eq!("sum", op);
+                assert_eq!(vec!["job".to_string()], group_by);
+                assert!(!without);
+                match &operands[0] {
+                    Expr::RecursiveExpr(SubExpr::Func(FuncExpr { op, operands })) => {
+                        assert_eq!("rate", op);
+                        assert_eq!(300_000, unwrap_selector(&operands[0]).range);
+                    }
+                    expr => panic!("Expect func, expr:{:?}", expr),
+                }
+            }
+            expr => panic!("Expect aggr, expr:{:?}", expr),
+        }
+
+        let expr = parse("max(up) without (instance)");
+        assert!(matches!(
+            expr,
+            Expr::RecursiveExpr(SubExpr::Aggr(AggrExpr { without: true, .. }))
+        ));
+
+        let expr = parse("topk(3, up)");
+        match expr {
+            Expr::RecursiveExpr(SubExpr::Aggr(AggrExpr { operands, .. })) => {
+                assert!(operands[0].is_selector());
+                assert!(matches!(
+                    operands[1],
+                    Expr::SimpleExpr(Operand::Float(v)) if v == 3.0
+                ));
+            }
+            expr => panic!("Expect aggr, expr:{:?}", expr),
+        }
+    }
+
+    #[test]
+    fn test_parse_binary() {
+        let expr = parse("1 + 2 * 3 ^ 2 ^ 0.5 - -1");
+        let expected = 1.0 + 2.0 * 3f64.powf(2f64.powf(0.5)) + 1.0;
+        assert!(matches!(expr, Expr::SimpleExpr(Operand::Float(v)) if v == expected));
+
+        let expr = parse("2 > bool 1");
+        assert!(matches!(expr, Expr::SimpleExpr(Operand::Float(v)) if v == 1.0));
+        assert!(parse_expr("2 > 1", EvalParams::instant(0)).is_err());
+
+        match parse("up / 100") {
+            Expr::RecursiveExpr(SubExpr::Binary(BinaryExpr {
+                op,
+                operands,
+                return_bool,
+            })) => {
+                assert_eq!("/", op);
+                assert!(operands[0].is_selector());
+                assert!(matches!(operands[1], Expr::SimpleExpr(Operand::Float(v)) if v == 100.0));
+                assert!(!return_bool);
+            }
+            expr => panic!("Expect binary, expr:{:?}", expr),
+        }
+        match parse("8 * rate(up[5m])") {
+            Expr::RecursiveExpr(SubExpr::Binary(BinaryExpr { operands, .. })) => {
+                assert!(matches!(operands[0], Expr::SimpleExpr(Operand::Float(v)) if v == 8.0));
+                assert!(matches!(
+                    &operands[1],
+                    Expr::RecursiveExpr(SubExpr::Func(FuncExpr { op, .. })) if op == "rate"
+                ));
+            }
+            expr => panic!("Expect binary, expr:{:?}", expr),
+        }
+        assert!(matches!(
+            parse("up > bool 0"),
+            Expr::RecursiveExpr(SubExpr::Binary(BinaryExpr {
+                return_bool: true,
+                ..
+            }))
+        ));
+        assert!(matches!(
+            parse("-up"),
+            Expr::RecursiveExpr(SubExpr::Binary(BinaryExpr { .. }))
+        ));
+
+        assert!(matches!(
+            parse_expr("up / up", EvalParams::instant(0)),
+            Err(Error::Unsupported { .. })
+        ));
+        assert!(matches!(
+            parse_expr("up / on(job) up", EvalParams::instant(0)),
+            Err(Error::Unsupported { .. })
+        ));
+        assert!(parse_expr("up and 1", EvalParams::instant(0)).is_err());
+        assert!(parse_expr("up[5m] * 8", EvalParams::instant(0)).is_err());
+        assert!(parse_expr(r#""a" + 1"#, EvalParams::instant(0)).is_err());
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        for query in [
+            "",
+            "up{",
+            "up{job=}",
+            r#"up{job="a}"#,
+            "rate(up[5x])",
+            "sum(up",
+            "(up)[5m:1m]",
+            "up offset",
+        ] {
+            assert!(
+                parse_expr(query, EvalParams::instant(0)).is_err(),
+                "query:{}",
+                query
+            );
+        }
+    }
+}
@@ -2,13 +2,28 @@
 
 use std::convert::TryFrom;
 
+use arrow_deps::datafusion::logical_plan::{lit, Expr as DataFusionExpr};
 use common_types::time::{TimeRange, Timestamp};
-use snafu::Snafu;
+use snafu::{ensure, Snafu};
+
+use crate::promql::convert::Filter;
 
 #[derive(Debug, Snafu)]
 pub enum Error {
     #[snafu(display("Func {} is not supported yet", func))]
     NotSupportedFunc { func: String },
+
+    #[snafu(display(
+        "Invalid number of args for func {}, expected:{}, actual:{}",
+        func,
+        expected,
+        actual
+    ))]
+    InvalidArgs {
+        func: String,
+        expected: usize,
+        actual: usize,
+    },
 }
 
 define_result!(Error);
@@ -21,25 +36,79 @@ pub enum Func {
     Delta,
     Idelta,
     Increase,
+    AvgOverTime,
+    SumOverTime,
+    MinOverTime,
+    MaxOverTime,
+    CountOverTime,
+    /// φ-quantile of the values, φ is expected in [0, 1]
+    QuantileOverTime(f64),
+    StddevOverTime,
+    LastOverTime,
+    Changes,
+    Resets,
+    Deriv,
+    /// Predict the value after the given seconds
+    PredictLinear(f64),
 }
 
-impl TryFrom<&str> for Func {
-    type Error = Error;
+impl Func {
+    /// Create the func by its name and scalar args, the range vector arg is
+    /// excluded from `args`.
+    pub fn try_new(op: &str, args: &[f64]) -> Result<Self> {
+        let single_arg = || {
+            ensure!(
+                args.len() == 1,
+                InvalidArgs {
+                    func: op,
+                    expected: 1usize,
+                    actual: args.len(),
+                }
+            );
+            Ok(args[0])
+        };
 
-    fn try_from(op: &str) -> Result<Self> {
         let t = match op {
             "rate" => Func::Rate,
             "delta" => Func::Delta,
             "irate" => Func::Irate,
             "idelta" => Func::Idelta,
             "increase" => Func::Increase,
+            "avg_over_time" => Func::AvgOverTime,
+            "sum_over_time" => Func::SumOverTime,
+            "min_over_time" => Func::MinOverTime,
+            "max_over_time" => Func::MaxOverTime,
+            "count_over_time" => Func::CountOverTime,
+            "quantile_over_time" => return Ok(Func::QuantileOverTime(single_arg()?)),
+            "stddev_over_time" => Func::StddevOverTime,
+            "last_over_time" => Func::LastOverTime,
+            "changes" => Func::Changes,
+            "resets" => Func::Resets,
+            "deriv" => Func::Deriv,
+            "predict_linear" => return Ok(Func::PredictLinear(single_arg()?)),
             func => return NotSupportedFunc { func }.fail(),
         };
+        ensure!(
+            args.is_empty(),
+            InvalidArgs {
+                func: op,
+                expected: 0usize,
+                actual: args.len(),
+            }
+        );
 
         Ok(t)
     }
 }
 
+impl TryFrom<&str> for Func {
+    type Error = Error;
+
+    fn try_from(op: &str) -> Result<Self> {
+        Func::try_new(op, &[])
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct AlignParameter {
     pub align_range: TimeRange,
@@ -48,3 +117,73 @@ pub struct AlignParameter {
     /// 0 for no look back
     pub lookback_delta: Timestamp,
 }
+
+/// Translate the label matchers into the exprs pushed down to the table scan,
+/// the labels not in `tag_keys` are absent so their matchers are evaluated
+/// against the empty string.
+///
+/// The equality matchers are translated into `=` or `IN` exprs, which are
+/// answered by the inverted index of the tag columns to prune the ssts and row
+/// groups.
+pub fn tag_filter_exprs(filters: Vec<Filter>, tag_keys: &[String]) -> Vec<DataFusionExpr> {
+    filters
+        .into_iter()
+        .map(|f| {
+            if tag_keys.contains(&f.tag_key) {
+                DataFusionExpr::from(f)
+            } else {
+                lit(f.matches_empty())
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_deps::datafusion::logical_plan::col;
+
+    use super::*;
+    use crate::promql::convert::{FilterOperator, FilterType};
+
+    fn filter(tag_key: &str, typ: FilterType, param: &str) -> Filter {
+        Filter {
+            tag_key: tag_key.to_string(),
+            operators: vec![FilterOperator {
+                typ,
+                params: vec![param.to_string()],
+            }],
+        }
+    }
+
+    #[test]
+    fn test_tag_filter_exprs() {
+        let tag_keys = vec!["job".to_string()];
+        let filters = vec![
+            filter("job", FilterType::LiteralOr, "api"),
+            // A stored empty string is the same as the absent label.
+            filter("job", FilterType::LiteralOr, ""),
+            filter("job", FilterType::NotLiteralOr, ""),
+            // Matchers of the absent labels are evaluated against empty string.
+            filter("host", FilterType::LiteralOr, "a"),
+            filter("host", FilterType::NotLiteralOr, "a"),
+            filter("host", FilterType::Regexp, ".*"),
+            filter("host", FilterType::Regexp, ".+"),
+            filter("host", FilterType::NotRegexpMatch, ".+"),
+        ];
+
+        let exprs = tag_filter_exprs(filters, &tag_keys);
+        assert_eq!(
+            vec![
+                col("job").eq(lit("api")),
+                col("job").is_null().or(col("job").eq(lit(""))),
+                col("job").is_not_null().and(col("job").not_eq(lit(""))),
+                lit(false),
+                lit(true),
+                lit(true),
+                lit(false),
+                lit(true),
+            ],
+            exprs
+        );
+    }
+}
@@ -7,7 +7,7 @@ use std::sync::Arc;
 
 use arrow_deps::{
     arrow::{
-        array::{ArrayRef, BooleanArray, StringArray, UInt64Array},
+        array::{ArrayRef, BooleanArray, Float64Array, StringArray, UInt64Array},
         datatypes::DataType,
     },
     datafusion::{
@@ -25,6 +25,44 @@ use common_util::codec::{compact::MemCompactEncoder, Encoder};
 /// The name of the regex_match UDF given to DataFusion.
 pub const REGEX_MATCH_UDF_NAME: &str = "RegexMatch";
 pub const REGEX_NOT_MATCH_UDF_NAME: &str = "RegexNotMatch";
+/// The name of the UDF evaluating binary operators between a vector and a
+/// scalar.
+pub const SCALAR_BINARY_UDF_NAME: &str = "ScalarBinary";
+
+/// Returns whether `op` is a comparison operator.
+pub fn is_comparison_op(op: &str) -> bool {
+    matches!(op, "==" | "!=" | "<" | "<=" | ">" | ">=")
+}
+
+/// Returns the function to evaluate the arithmetic or comparison operator
+/// `op` between two floats, comparisons return 1.0 if true, otherwise 0.0.
+pub fn binary_op_fn(op: &str) -> Option<fn(f64, f64) -> f64> {
+    fn to_float(v: bool) -> f64 {
+        if v {
+            1.0
+        } else {
+            0.0
+        }
+    }
+
+    let func: fn(f64, f64) -> f64 = match op {
+        "+" => |l, r| l + r,
+        "-" => |l, r| l - r,
+        "*" => |l, r| l * r,
+        "/" => |l, r| l / r,
+        "%" => |l, r| l % r,
+        "^" => f64::powf,
+        "==" => |l, r| to_float(l == r),
+        "!=" => |l, r| to_float(l != r),
+        "<" => |l, r| to_float(l < r),
+        "<=" => |l, r| to_float(l <= r),
+        ">" => |l, r| to_float(l > r),
+        ">=" => |l, r| to_float(l >= r),
+        _ => return None,
+    };
+
+    Some(func)
+}
 
 /// Given a column containing string values and a single regex pattern,
 /// `regex_match_expr` determines which values satisfy the pattern and which do
@@ -41,12 +79,15 @@ pub fn regex_match_expr(input: Expr, pattern: String, matches: bool) -> Expr {
     // N.B., this function does not utilise the Arrow regexp compute kernel because
     // in order to act as a filter it needs to return a boolean array of comparison
     // results, not an array of strings as the regex compute kernel does.
+    // The regex is compiled only once, and the error is returned when the udf
+    // is evaluated.
+    let regex = regex::Regex::new(&pattern);
     let func = move |args: &[ArrayRef]| {
         assert_eq!(args.len(), 1); // only works over a single column at a time.
 
         let input_arr = &args[0].as_any().downcast_ref::<StringArray>().unwrap();
 
-        let pattern = regex::Regex::new(&pattern).map_err(|e| {
+        let pattern = regex.as_ref().map_err(|e| {
             DataFusionError::Internal(format!("error compiling regex pattern: {}", e))
         })?;
 
@@ -84,6 +125,49 @@ pub fn regex_match_expr(input: Expr, pattern: String, matches: bool) -> Expr {
     udf.call(vec![input])
 }
 
+/// Given a column containing float values, `scalar_binary_expr` evaluates
+/// `func` on every value and the `scalar`, the scalar is the left operand if
+/// `scalar_on_left` is true. Null values stay null.
+pub fn scalar_binary_expr(
+    input: Expr,
+    func: fn(f64, f64) -> f64,
+    scalar: f64,
+    scalar_on_left: bool,
+) -> Expr {
+    let func = move |args: &[ArrayRef]| {
+        assert_eq!(args.len(), 1);
+
+        let input_arr = args[0]
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .ok_or_else(|| DataFusionError::Execution("field column not f64".to_string()))?;
+        let results = input_arr
+            .iter()
+            .map(|row| {
+                row.map(|v| {
+                    if scalar_on_left {
+                        func(scalar, v)
+                    } else {
+                        func(v, scalar)
+                    }
+                })
+            })
+            .collect::<Float64Array>();
+
+        Ok(Arc::new(results) as ArrayRef)
+    };
+
+    let udf = create_udf(
+        SCALAR_BINARY_UDF_NAME,
+        vec![DataType::Float64],
+        Arc::new(DataType::Float64),
+        Volatility::Stable,
+        make_scalar_function(func),
+    );
+
+    udf.call(vec![input])
+}
+
 pub fn create_unique_id(input_len: usize) -> ScalarUDF {
     let func = move |args: &[ArrayRef]| {
         if args.is_empty() {
@@ -0,0 +1,498 @@
+// Copyright 2022 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Rewrite of queries by rollups
+//!
+//! An aggregate over the source table of a rollup is routed to the target
+//! table of the rollup if:
+//! - it is grouped by one `time_bucket(timestamp, period)` whose period is a
+//!   multiple of the period of the rollup, and the tags of the rollup
+//! - all the aggregates could be computed from the partial aggregates of the
+//!   rollup
+//! - the timestamp is bounded on both ends by values aligned to the period of
+//!   the rollup, and other predicates only refer to the tags of the rollup
+//! - the upper bound of the timestamp is no greater than the rollup watermark
+//!   of the source table
+//!
+//! Rows are rolled up after they are flushed, so the rows not rolled up yet are
+//! invisible to the rewritten query. The rollup watermark reported by the
+//! source table guarantees all the rows before it are rolled up, so only the
+//! range queries ending before the watermark are routed to rollups.
+
+use std::{collections::HashSet, sync::Arc, time::Duration};
+
+use arrow_deps::{
+    arrow::compute::kernels::cast_utils::string_to_timestamp_nanos,
+    datafusion::{
+        arrow::datatypes::DataType,
+        catalog::TableReference,
+        datasource::TableProvider,
+        error::Result,
+        logical_plan::{
+            plan::{Aggregate, Explain, Filter},
+            Column, Expr, LogicalPlan, LogicalPlanBuilder, Operator, TableScan,
+        },
+        optimizer::utils,
+        physical_plan::aggregates::AggregateFunction as DataFusionAggregateFunction,
+        scalar::ScalarValue,
+        sql::planner::ContextProvider,
+    },
+};
+use log::debug;
+use table_engine::{
+    provider::TableProviderAdapter,
+    rollup::{self, AggregateFunction, PartialAggregate, Rollup},
+    OPTION_KEY_ROLLUPS,
+};
+
+/// Name of the udf to compute time bucket.
+const TIME_BUCKET: &str = "time_bucket";
+
+/// Rewrite the aggregates in the `plan` to read the target tables of rollups,
+/// returns `None` if nothing is rewritten.
+pub fn rewrite_by_rollups(
+    plan: &LogicalPlan,
+    provider: &dyn ContextProvider,
+) -> Result<Option<LogicalPlan>> {
+    match plan {
+        LogicalPlan::Aggregate(aggregate) => {
+            if let Some(new_plan) = try_rewrite_aggregate(aggregate, provider)? {
+                return Ok(Some(new_plan));
+            }
+        }
+        // Explain can't be built by `from_plan()`.
+        LogicalPlan::Explain(explain) => {
+            let new_plan = rewrite_by_rollups(&explain.plan, provider)?;
+            return Ok(new_plan.map(|v| {
+                LogicalPlan::Explain(Explain {
+                    plan: Arc::new(v),
+                    ..explain.clone()
+                })
+            }));
+        }
+        _ => (),
+    }
+
+    let inputs = plan.inputs();
+    let mut rewritten = false;
+    let mut new_inputs = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        match rewrite_by_rollups(input, provider)? {
+            Some(new_input) => {
+                rewritten = true;
+                new_inputs.push(new_input);
+            }
+            None => new_inputs.push(input.clone()),
+        }
+    }
+    if !rewritten {
+        return Ok(None);
+    }
+
+    utils::from_plan(plan, &plan.expressions(), &new_inputs).map(Some)
+}
+
+fn try_rewrite_aggregate(
+    aggregate: &Aggregate,
+    provider: &dyn ContextProvider,
+) -> Result<Option<LogicalPlan>> {
+    let (predicate, scan) = match aggregate.input.as_ref() {
+        LogicalPlan::Filter(Filter { predicate, input }) => match input.as_ref() {
+            LogicalPlan::TableScan(scan) => (predicate, scan),
+            _ => return Ok(None),
+        },
+        _ => return Ok(None),
+    };
+    if !scan.filters.is_empty() || scan.limit.is_some() {
+        return Ok(None);
+    }
+
+    let table = match scan.source.as_any().downcast_ref::<TableProviderAdapter>() {
+        Some(v) => v.as_table_ref(),
+        None => return Ok(None),
+    };
+    let rollups = match table.options().get(OPTION_KEY_ROLLUPS) {
+        Some(v) => match rollup::parse_rollups(v) {
+            Ok(v) => v,
+            Err(_) => return Ok(None),
+        },
+        None => return Ok(None),
+    };
+    let watermark = match table.stats().rollup_watermark {
+        Some(v) => v.as_i64(),
+        None => return Ok(None),
+    };
+    let schema = table.schema();
+    let timestamp_name = schema.timestamp_name();
+
+    // The coarsest rollup is preferred, which has the fewest rows to read.
+    let mut candidates: Vec<_> = rollups
+        .iter()
+        .filter(|rollup| is_applicable(rollup, timestamp_name, aggregate, predicate, watermark))
+        .collect();
+    candidates.sort_by_key(|rollup| std::cmp::Reverse(rollup.period));
+    for rollup in candidates {
+        if let Some(target) = find_target_table(rollup, timestamp_name, provider) {
+            debug!(
+                "Rewrite aggregate by rollup, table:{}, rollup:{}",
+                table.name(),
+                rollup
+            );
+
+            return build_rollup_plan(rollup, aggregate, predicate, scan, target).map(Some);
+        }
+    }
+
+    Ok(None)
+}
+
+fn is_applicable(
+    rollup: &Rollup,
+    timestamp_name: &str,
+    aggregate: &Aggregate,
+    predicate: &Expr,
+    watermark: i64,
+) -> bool {
+    let mut num_time_buckets = 0;
+    for expr in &aggregate.group_expr {
+        match expr {
+            Expr::Column(column) if rollup.tags.contains(&column.name) => (),
+            Expr::ScalarUDF { fun, args } if fun.name == TIME_BUCKET => {
+                match time_bucket_period(args, timestamp_name) {
+                    Some(period) if is_aligned(period.as_millis() as i64, rollup.period) => {
+                        num_time_buckets += 1
+                    }
+                    _ => return false,
+                }
+            }
+            _ => return false,
+        }
+    }
+    if num_time_buckets != 1 {
+        return false;
+    }
+
+    let has_partials = aggregate
+        .aggr_expr
+        .iter()
+        .all(|expr| match split_aggregate(expr) {
+            Some((function, column)) => function
+                .partials()
+                .iter()
+                .all(|partial| rollup.partial_column(column, *partial).is_some()),
+            None => false,
+        });
+
+    if !has_partials {
+        return false;
+    }
+
+    match predicate_upper_bound(rollup, timestamp_name, predicate) {
+        Some(upper_bound) => upper_bound <= watermark,
+        None => false,
+    }
+}
+
+/// Returns the exclusive upper bound of the timestamp if the `predicate` only
+/// refers to the tags of the rollup and bounds the timestamp on both ends.
+fn predicate_upper_bound(rollup: &Rollup, timestamp_name: &str, predicate: &Expr) -> Option<i64> {
+    let mut exprs = Vec::new();
+    split_conjunction(predicate, &mut exprs);
+
+    let (mut has_lower_bound, mut upper_bound) = (false, None);
+    for expr in exprs {
+        if let Expr::BinaryExpr { left, op, right } = expr {
+            if let (Expr::Column(column), Expr::Literal(value)) = (left.as_ref(), right.as_ref()) {
+                if column.name == timestamp_name {
+                    let value = timestamp_value(value).filter(|v| is_aligned(*v, rollup.period));
+                    match (op, value) {
+                        (Operator::GtEq, Some(_)) => has_lower_bound = true,
+                        (Operator::Lt, Some(v)) => {
+                            upper_bound = Some(upper_bound.unwrap_or(i64::MAX).min(v))
+                        }
+                        _ => return None,
+                    }
+                    continue;
+                }
+            }
+        }
+
+        let mut columns = HashSet::new();
+        if utils::expr_to_columns(expr, &mut columns).is_err()
+            || !columns.iter().all(|v| rollup.tags.contains(&v.name))
+        {
+            return None;
+        }
+    }
+
+    if has_lower_bound {
+        upper_bound
+    } else {
+        None
+    }
+}
+
+fn split_conjunction<'a>(expr: &'a Expr, exprs: &mut Vec<&'a Expr>) {
+    match expr {
+        Expr::BinaryExpr {
+            left,
+            op: Operator::And,
+            right,
+        } => {
+            split_conjunction(left, exprs);
+            split_conjunction(right, exprs);
+        }
+        other => exprs.push(other),
+    }
+}
+
+/// Returns the target table of the rollup if it contains all the columns of
+/// the rollup.
+fn find_target_table(
+    rollup: &Rollup,
+    timestamp_name: &str,
+    provider: &dyn ContextProvider,
+) -> Option<Arc<dyn TableProvider>> {
+    let target = provider.get_table_provider(TableReference::Bare {
+        table: &rollup.table,
+    })?;
+    let target_schema = target
+        .as_any()
+        .downcast_ref::<TableProviderAdapter>()?
+        .as_table_ref()
+        .schema();
+
+    let has_columns = target_schema.timestamp_name() == timestamp_name
+        && rollup
+            .tags
+            .iter()
+            .chain(rollup.partial_columns().iter().map(|v| &v.name))
+            .all(|name| target_schema.index_of(name).is_some());
+
+    has_columns.then(|| target)
+}
+
+/// Build the plan to aggregate the partial columns in the target table, the
+/// output schema is the same as the schema of the original aggregate.
+fn build_rollup_plan(
+    rollup: &Rollup,
+    aggregate: &Aggregate,
+    predicate: &Expr,
+    scan: &TableScan,
+    target: Arc<dyn TableProvider>,
+) -> Result<LogicalPlan> {
+    // The partial aggregates may be shared by the aggregates, such as `avg(v)`
+    // and `sum(v)`, so the indexes of the partial aggregates of each aggregate
+    // are recorded.
+    let mut aggr_exprs: Vec<Expr> = Vec::new();
+    let mut partial_indexes = Vec::with_capacity(aggregate.aggr_expr.len());
+    for expr in &aggregate.aggr_expr {
+        // Checked by `is_applicable()`.
+        let (function, column) = split_aggregate(expr).unwrap();
+        let mut indexes = Vec::with_capacity(function.partials().len());
+        for partial in function.partials() {
+            let name = rollup.partial_column(column, *partial).unwrap();
+            let fun = match partial {
+                PartialAggregate::Sum | PartialAggregate::Count => DataFusionAggregateFunction::Sum,
+                PartialAggregate::Min => DataFusionAggregateFunction::Min,
+                PartialAggregate::Max => DataFusionAggregateFunction::Max,
+            };
+            let partial_expr = Expr::AggregateFunction {
+                fun,
+                args: vec![Expr::Column(Column {
+                    relation: Some(scan.table_name.clone()),
+                    name,
+                })],
+                distinct: false,
+            };
+            let index = match aggr_exprs.iter().position(|v| *v == partial_expr) {
+                Some(v) => v,
+                None => {
+                    aggr_exprs.push(partial_expr);
+                    aggr_exprs.len() - 1
+                }
+            };
+            indexes.push(index);
+        }
+        partial_indexes.push((function, indexes));
+    }
+
+    let rollup_aggregate = LogicalPlanBuilder::scan(&scan.table_name, target, None)?
+        .filter(predicate.clone())?
+        .aggregate(aggregate.group_expr.clone(), aggr_exprs)?
+        .build()?;
+
+    // Compute the original aggregates from the partial aggregates.
+    let schema = rollup_aggregate.schema().clone();
+    let num_groups = aggregate.group_expr.len();
+    let column_expr = |index: usize| Expr::Column(schema.field(index).qualified_column());
+    let mut exprs: Vec<_> = (0..num_groups).map(column_expr).collect();
+    for (i, (function, indexes)) in partial_indexes.into_iter().enumerate() {
+        let partial_expr = |n: usize| column_expr(num_groups + indexes[n]);
+        let value = match function {
+            AggregateFunction::Avg => {
+                let count = Expr::Cast {
+                    expr: Box::new(partial_expr(1)),
+                    data_type: DataType::Float64,
+                };
+                Expr::BinaryExpr {
+                    left: Box::new(partial_expr(0)),
+                    op: Operator::Divide,
+                    right: Box::new(count),
+                }
+            }
+            _ => partial_expr(0),
+        };
+
+        let field = aggregate.schema.field(num_groups + i);
+        exprs.push(Expr::Alias(
+            Box::new(Expr::Cast {
+                expr: Box::new(value),
+                data_type: field.data_type().clone(),
+            }),
+            field.name().clone(),
+        ));
+    }
+
+    LogicalPlanBuilder::from(rollup_aggregate)
+        .project(exprs)?
+        .build()
+}
+
+/// Split the aggregate expr into the aggregate function and the column name.
+fn split_aggregate(expr: &Expr) -> Option<(AggregateFunction, &str)> {
+    match expr {
+        Expr::AggregateFunction {
+            fun,
+            args,
+            distinct: false,
+        } => {
+            let function = match fun {
+                DataFusionAggregateFunction::Avg => AggregateFunction::Avg,
+                DataFusionAggregateFunction::Sum => AggregateFunction::Sum,
+                DataFusionAggregateFunction::Count => AggregateFunction::Count,
+                DataFusionAggregateFunction::Min => AggregateFunction::Min,
+                DataFusionAggregateFunction::Max => AggregateFunction::Max,
+                _ => return None,
+            };
+            match args.as_slice() {
+                [Expr::Column(column)] => Some((function, column.name.as_str())),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Returns the period of `time_bucket(timestamp, period)`.
+fn time_bucket_period(args: &[Expr], timestamp_name: &str) -> Option<Duration> {
+    match args {
+        [Expr::Column(column), Expr::Literal(ScalarValue::Utf8(Some(period)))]
+            if column.name == timestamp_name =>
+        {
+            rollup::parse_period(period).ok()
+        }
+        _ => None,
+    }
+}
+
+fn timestamp_value(value: &ScalarValue) -> Option<i64> {
+    match value {
+        ScalarValue::Int64(Some(v)) | ScalarValue::TimestampMillisecond(Some(v), _) => Some(*v),
+        ScalarValue::Utf8(Some(v)) => string_to_timestamp_nanos(v)
+            .ok()
+            .map(|nanos| nanos / 1_000_000),
+        _ => None,
+    }
+}
+
+fn is_aligned(millis: i64, period: Duration) -> bool {
+    millis % period.as_millis() as i64 == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use common_types::request_id::RequestId;
+
+    use super::*;
+    use crate::{parser::Parser, plan::Plan, planner::Planner, tests::MockMetaProvider};
+
+    /// Returns the names of the tables scanned by the query.
+    fn scanned_tables(sql: &str) -> Vec<String> {
+        let provider = MockMetaProvider::default();
+        let planner = Planner::new(&provider, RequestId::next_id(), 1);
+        let mut statements = Parser::parse_sql(sql).unwrap();
+        let df_plan = match planner.statement_to_plan(statements.remove(0)).unwrap() {
+            Plan::Query(v) => v.df_plan,
+            _ => panic!("Expect a query plan"),
+        };
+
+        let mut tables = Vec::new();
+        collect_scanned_tables(&df_plan, &mut tables);
+        tables
+    }
+
+    fn collect_scanned_tables(plan: &LogicalPlan, tables: &mut Vec<String>) {
+        if let LogicalPlan::TableScan(scan) = plan {
+            let table = scan
+                .source
+                .as_any()
+                .downcast_ref::<TableProviderAdapter>()
+                .unwrap()
+                .as_table_ref();
+            tables.push(table.name().to_string());
+        }
+
+        for input in plan.inputs() {
+            collect_scanned_tables(input, tables);
+        }
+    }
+
+    #[test]
+    fn test_rewrite_by_rollups() {
+        let sql =
+            "SELECT time_bucket(ts, 'PT1H') AS t, host, avg(usage), count(usage), max(usage) \
+                   FROM rollup_source WHERE ts >= 1640995200000 AND ts < 1641081600000 \
+                   AND host = 'h1' GROUP BY time_bucket(ts, 'PT1H'), host";
+        assert_eq!(vec!["rollup_target"], scanned_tables(sql));
+
+        let sql = "EXPLAIN SELECT time_bucket(ts, 'PT1M'), count(usage) FROM rollup_source \
+                   WHERE ts >= 1640995200000 AND ts < 1641081600000 \
+                   GROUP BY time_bucket(ts, 'PT1M')";
+        assert_eq!(vec!["rollup_target"], scanned_tables(sql));
+
+        let not_routed_predicates = [
+            // The time range is not bounded.
+            "ts >= 1640995200000",
+            // The time range is not aligned to the period of rollup.
+            "ts >= 1640995200000 AND ts < 1641081600001",
+            "ts > 1640995200000 AND ts < 1641081600000",
+            // Predicate of the column not in rollup.
+            "ts >= 1640995200000 AND ts < 1641081600000 AND usage > 1.0",
+            // The time range ends after the rollup watermark.
+            "ts >= 1640995200000 AND ts < 1641085200000",
+        ];
+        for predicate in not_routed_predicates {
+            let sql = format!(
+                "SELECT time_bucket(ts, 'PT1H'), avg(usage) FROM rollup_source WHERE {} \
+                 GROUP BY time_bucket(ts, 'PT1H')",
+                predicate
+            );
+            assert_eq!(vec!["rollup_source"], scanned_tables(&sql), "sql:{}", sql);
+        }
+
+        let not_routed_queries = [
+            // The time bucket is finer than the rollup.
+            "SELECT time_bucket(ts, 'PT30S'), avg(usage) FROM rollup_source \
+             WHERE ts >= 1640995200000 AND ts < 1641081600000 GROUP BY time_bucket(ts, 'PT30S')",
+            // The aggregate is not maintained by the rollup.
+            "SELECT time_bucket(ts, 'PT1H'), min(usage) FROM rollup_source \
+             WHERE ts >= 1640995200000 AND ts < 1641081600000 GROUP BY time_bucket(ts, 'PT1H')",
+            // Not grouped by time bucket.
+            "SELECT host, avg(usage) FROM rollup_source \
+             WHERE ts >= 1640995200000 AND ts < 1641081600000 GROUP BY host",
+        ];
+        for sql in not_routed_queries {
+            assert_eq!(vec!["rollup_source"], scanned_tables(sql), "sql:{}", sql);
+        }
+    }
+}
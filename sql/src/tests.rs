@@ -1,18 +1,68 @@
 // Copyright 2022 CeresDB Project Authors. Licensed under Apache-2.0.
 
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
 use arrow_deps::datafusion::catalog::TableReference;
 use catalog::consts::{DEFAULT_CATALOG, DEFAULT_SCHEMA};
-use common_types::tests::build_schema;
+use common_types::{
+    column_schema,
+    datum::DatumKind,
+    schema::{self, Schema, TSID_COLUMN},
+    tests::build_schema,
+    time::Timestamp,
+};
+use snafu::ResultExt;
 use table_engine::{
     memory::MemoryTable,
-    table::{Table, TableId, TableRef},
-    ANALYTIC_ENGINE_TYPE,
+    table::{Table, TableId, TableRef, TableStats},
+    ANALYTIC_ENGINE_TYPE, OPTION_KEY_ROLLUPS,
+};
+use udf::{
+    registry::{FunctionRegistry, FunctionRegistryImpl},
+    scalar::ScalarUdf,
+    udaf::AggregateUdf,
 };
-use udf::{scalar::ScalarUdf, udaf::AggregateUdf};
 
-use crate::provider::MetaProvider;
+use crate::provider::{FindUdf, MetaProvider};
+
+/// Rollup of `rollup_source` to `rollup_target`.
+pub const ROLLUP: &str = "rollup_target:PT1M:host:avg(usage),max(usage)";
+/// Rows of `rollup_source` before this timestamp are rolled up.
+pub const ROLLUP_WATERMARK: i64 = 1641081600000;
+
+/// Build a schema with primary key (ts, tsid), the tag columns and the normal
+/// columns.
+fn build_tsid_schema(tags: &[&str], fields: &[(&str, DatumKind)]) -> Schema {
+    let mut builder = schema::Builder::new()
+        .auto_increment_column_id(true)
+        .enable_tsid_primary_key(true);
+    for (name, kind) in [
+        ("ts", DatumKind::Timestamp),
+        (TSID_COLUMN, DatumKind::UInt64),
+    ] {
+        let column_schema = column_schema::Builder::new(name.to_string(), kind)
+            .is_nullable(false)
+            .build()
+            .unwrap();
+        builder = builder.add_key_column(column_schema).unwrap();
+    }
+    for tag in tags {
+        let column_schema = column_schema::Builder::new(tag.to_string(), DatumKind::String)
+            .is_tag(true)
+            .build()
+            .unwrap();
+        builder = builder.add_normal_column(column_schema).unwrap();
+    }
+    for (name, kind) in fields {
+        let column_schema = column_schema::Builder::new(name.to_string(), *kind)
+            .is_nullable(true)
+            .build()
+            .unwrap();
+        builder = builder.add_normal_column(column_schema).unwrap();
+    }
+
+    builder.build().unwrap()
+}
 
 pub struct MockMetaProvider {
     tables: Vec<Arc<MemoryTable>>,
@@ -34,6 +84,41 @@ impl Default for MockMetaProvider {
                     build_schema(),
                     ANALYTIC_ENGINE_TYPE.to_string(),
                 )),
+                Arc::new(
+                    MemoryTable::new(
+                        "rollup_source".to_string(),
+                        TableId::from(102),
+                        build_tsid_schema(&["host"], &[("usage", DatumKind::Double)]),
+                        ANALYTIC_ENGINE_TYPE.to_string(),
+                    )
+                    .with_options(HashMap::from([(
+                        OPTION_KEY_ROLLUPS.to_string(),
+                        ROLLUP.to_string(),
+                    )]))
+                    .with_stats(TableStats {
+                        rollup_watermark: Some(Timestamp::new(ROLLUP_WATERMARK)),
+                        ..Default::default()
+                    }),
+                ),
+                Arc::new(MemoryTable::new(
+                    "rollup_target".to_string(),
+                    TableId::from(103),
+                    build_tsid_schema(
+                        &["host"],
+                        &[
+                            ("usage_sum", DatumKind::Double),
+                            ("usage_count", DatumKind::UInt64),
+                            ("usage_max", DatumKind::Double),
+                        ],
+                    ),
+                    ANALYTIC_ENGINE_TYPE.to_string(),
+                )),
+                Arc::new(MemoryTable::new(
+                    "prom_metric".to_string(),
+                    TableId::from(104),
+                    build_tsid_schema(&["host"], &[("value", DatumKind::Double)]),
+                    ANALYTIC_ENGINE_TYPE.to_string(),
+                )),
             ],
         }
     }
@@ -59,8 +144,11 @@ impl MetaProvider for MockMetaProvider {
         Ok(None)
     }
 
-    fn scalar_udf(&self, _name: &str) -> crate::provider::Result<Option<ScalarUdf>> {
-        todo!()
+    fn scalar_udf(&self, name: &str) -> crate::provider::Result<Option<ScalarUdf>> {
+        let mut registry = FunctionRegistryImpl::new();
+        registry.load_functions().context(FindUdf)?;
+
+        registry.find_udf(name).context(FindUdf)
     }
 
     fn aggregate_udf(&self, _name: &str) -> crate::provider::Result<Option<AggregateUdf>> {
@@ -103,17 +103,27 @@ pub fn run_server(config: Config) {
         // Create query executor
         let query_executor = ExecutorImpl::new();
 
+        // Open the store to backup tables if configured
+        let backup_store = match config.backup_storage.clone() {
+            Some(opts) => Some(setup::open_object_store(opts).await.unwrap_or_else(|e| {
+                panic!("Failed to open backup store, err:{}", e);
+            })),
+            None => None,
+        };
+
         // Build and start server
-        let mut server = Builder::new(config)
+        let mut builder = Builder::new(config)
             .runtimes(runtimes.clone())
             .catalog_manager(catalog_manager)
             .query_executor(query_executor)
             .table_engine(engine_proxy)
-            .function_registry(function_registry)
-            .build()
-            .unwrap_or_else(|e| {
-                panic!("Failed to create server, err:{}", e);
-            });
+            .function_registry(function_registry);
+        if let Some(backup_store) = backup_store {
+            builder = builder.backup_store(backup_store);
+        }
+        let mut server = builder.build().unwrap_or_else(|e| {
+            panic!("Failed to create server, err:{}", e);
+        });
         server.start().await.unwrap_or_else(|e| {
             panic!("Failed to start server,, err:{}", e);
         });
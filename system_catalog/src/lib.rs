@@ -18,11 +18,13 @@ use common_types::{
 };
 use futures::Stream;
 use table_engine::{
+    partition::PartitionInfo,
     stream,
     stream::{PartitionedStreams, RecordBatchStream, SendableRecordBatchStream},
     table::{
-        AlterSchemaRequest, FlushRequest, GetRequest, ReadRequest, SchemaId, Table, TableId,
-        TableSeq, TableStats, WriteRequest,
+        AlterSchemaRequest, DeleteRequest, FlushRequest, GetRequest, ReadRequest, RestoreRequest,
+        SchemaId, SnapshotRequest, SnapshotStats, Table, TableId, TableSeq, TableStats,
+        UnsupportedMethod, WriteRequest,
     },
 };
 
@@ -102,6 +104,10 @@ impl Table for SystemTableAdapter {
         HashMap::new()
     }
 
+    fn partition_info(&self) -> Option<PartitionInfo> {
+        None
+    }
+
     fn engine_type(&self) -> &str {
         "system"
     }
@@ -114,6 +120,14 @@ impl Table for SystemTableAdapter {
         Ok(0)
     }
 
+    async fn delete(&self, _request: DeleteRequest) -> table_engine::table::Result<()> {
+        UnsupportedMethod {
+            table: self.name(),
+            method: "delete",
+        }
+        .fail()
+    }
+
     async fn read(
         &self,
         request: ReadRequest,
@@ -163,6 +177,28 @@ impl Table for SystemTableAdapter {
     async fn compact(&self) -> table_engine::table::Result<()> {
         Ok(())
     }
+
+    async fn snapshot(
+        &self,
+        _request: SnapshotRequest,
+    ) -> table_engine::table::Result<SnapshotStats> {
+        UnsupportedMethod {
+            table: self.name(),
+            method: "snapshot",
+        }
+        .fail()
+    }
+
+    async fn restore(
+        &self,
+        _request: RestoreRequest,
+    ) -> table_engine::table::Result<SnapshotStats> {
+        UnsupportedMethod {
+            table: self.name(),
+            method: "restore",
+        }
+        .fail()
+    }
 }
 
 pub struct OneRecordBatchStream {
@@ -10,11 +10,14 @@ pub mod memory;
 pub mod partition;
 pub mod predicate;
 pub mod provider;
+pub mod rollup;
 pub mod stream;
 pub mod table;
 
 /// Enable ttl key
 pub const OPTION_KEY_ENABLE_TTL: &str = "enable_ttl";
+/// Rollups key, see [rollup] for the format of the value
+pub const OPTION_KEY_ROLLUPS: &str = "rollups";
 
 pub const MEMORY_ENGINE_TYPE: &str = "Memory";
 pub const ANALYTIC_ENGINE_TYPE: &str = "Analytic";
@@ -22,13 +22,15 @@ use futures::stream::Stream;
 use snafu::{OptionExt, ResultExt};
 
 use crate::{
+    partition::PartitionInfo,
     stream::{
         self, ErrNoSource, ErrWithSource, PartitionedStreams, RecordBatchStream,
         SendableRecordBatchStream,
     },
     table::{
-        AlterSchemaRequest, FlushRequest, GetRequest, ReadRequest, Result, Table, TableId,
-        TableStats, UnsupportedMethod, WriteRequest,
+        AlterSchemaRequest, DeleteRequest, FlushRequest, GetRequest, ReadRequest, RestoreRequest,
+        Result, SnapshotRequest, SnapshotStats, Table, TableId, TableStats, UnsupportedMethod,
+        WriteRequest,
     },
 };
 
@@ -49,6 +51,10 @@ pub struct MemoryTable {
     row_groups: Arc<RwLock<RowGroupVec>>,
     /// Engine type
     engine_type: String,
+    /// Table options
+    options: HashMap<String, String>,
+    /// Table stats
+    stats: TableStats,
 }
 
 impl MemoryTable {
@@ -59,8 +65,22 @@ impl MemoryTable {
             schema,
             row_groups: Arc::new(RwLock::new(Vec::new())),
             engine_type,
+            options: HashMap::new(),
+            stats: TableStats::default(),
         }
     }
+
+    /// Set the options of the table.
+    pub fn with_options(mut self, options: HashMap<String, String>) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Set the stats of the table.
+    pub fn with_stats(mut self, stats: TableStats) -> Self {
+        self.stats = stats;
+        self
+    }
 }
 
 impl fmt::Debug for MemoryTable {
@@ -85,7 +105,11 @@ impl Table for MemoryTable {
     }
 
     fn options(&self) -> HashMap<String, String> {
-        HashMap::new()
+        self.options.clone()
+    }
+
+    fn partition_info(&self) -> Option<PartitionInfo> {
+        None
     }
 
     fn schema(&self) -> Schema {
@@ -97,7 +121,7 @@ impl Table for MemoryTable {
     }
 
     fn stats(&self) -> TableStats {
-        TableStats::default()
+        self.stats
     }
 
     async fn write(&self, request: WriteRequest) -> Result<usize> {
@@ -109,6 +133,14 @@ impl Table for MemoryTable {
         Ok(n)
     }
 
+    async fn delete(&self, _request: DeleteRequest) -> Result<()> {
+        UnsupportedMethod {
+            table: self.name(),
+            method: "delete",
+        }
+        .fail()
+    }
+
     // batch_size is ignored now
     async fn read(&self, request: ReadRequest) -> Result<SendableRecordBatchStream> {
         let scan = MemoryScan {
@@ -162,6 +194,22 @@ impl Table for MemoryTable {
         }
         .fail()
     }
+
+    async fn snapshot(&self, _request: SnapshotRequest) -> Result<SnapshotStats> {
+        UnsupportedMethod {
+            table: self.name(),
+            method: "snapshot",
+        }
+        .fail()
+    }
+
+    async fn restore(&self, _request: RestoreRequest) -> Result<SnapshotStats> {
+        UnsupportedMethod {
+            table: self.name(),
+            method: "restore",
+        }
+        .fail()
+    }
 }
 
 #[derive(Debug)]
@@ -4,9 +4,9 @@
 
 use std::ops::Deref;
 
-use common_types::datum::Datum;
+use common_types::{datum::Datum, hash::hash64};
 use common_util::define_result;
-use snafu::{Backtrace, OptionExt, Snafu};
+use snafu::{ensure, Backtrace, Snafu};
 
 use crate::partition::PartitionInfo;
 
@@ -26,7 +26,7 @@ pub enum Expression {
 
 impl Expression {
     pub fn new(partition_info: &PartitionInfo) -> Self {
-        Self::parse_expr(partition_info.expr.to_string())
+        Expression::ColumnExpr(ColumnExpr::new(partition_info.columns.clone()))
     }
 
     /// Extract column name in expression
@@ -36,36 +36,42 @@ impl Expression {
         }
     }
 
-    fn parse_expr(expr_str: String) -> Expression {
-        Expression::ColumnExpr(ColumnExpr::new(expr_str))
-    }
-
     pub fn eval_uint<T: Deref<Target = Datum>>(&self, datums: &[T]) -> Result<u64> {
+        ensure!(!datums.is_empty(), EmptyDatums);
+
         match self {
-            Expression::ColumnExpr(column_expr) => {
-                column_expr.eval_uint(datums.get(0).context(EmptyDatums)?)
-            }
+            Expression::ColumnExpr(column_expr) => column_expr.eval_uint(datums),
         }
     }
 }
 
-/// Column
+/// Columns
 #[derive(Debug)]
 pub struct ColumnExpr {
-    column_name: String,
+    column_names: Vec<String>,
 }
 
 impl ColumnExpr {
-    fn new(column_name: String) -> Self {
-        Self { column_name }
+    fn new(column_names: Vec<String>) -> Self {
+        Self { column_names }
     }
 
     fn extract_column_name(&self) -> impl Iterator<Item = &str> {
-        std::iter::once(self.column_name.as_str())
+        self.column_names.iter().map(|name| name.as_str())
     }
 
     // TODO: handle error
-    fn eval_uint(&self, datum: &Datum) -> Result<u64> {
-        Ok(datum.convert_to_uint64())
+    fn eval_uint<T: Deref<Target = Datum>>(&self, datums: &[T]) -> Result<u64> {
+        if let [datum] = datums {
+            return Ok(datum.convert_to_uint64());
+        }
+
+        // Combine the values of all the columns into one hash value.
+        let mut buf = Vec::with_capacity(datums.len() * 8);
+        for datum in datums {
+            buf.extend_from_slice(&datum.convert_to_uint64().to_le_bytes());
+        }
+
+        Ok(hash64(&buf))
     }
 }
@@ -3,25 +3,334 @@
 //! Partitioned table supports
 
 mod expression;
+mod pruning;
 pub mod rule;
 
+use std::convert::TryFrom;
+
+use common_types::{
+    datum::{Datum, DatumKind},
+    schema::Schema,
+};
+use common_util::{
+    codec::{
+        compact::{MemCompactDecoder, MemCompactEncoder},
+        DecodeTo, Encoder,
+    },
+    define_result,
+};
+use proto::common as common_pb;
+use snafu::{ensure, Backtrace, OptionExt, ResultExt, Snafu};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Failed to decode partition value, err:{}", source))]
+    DecodeValue {
+        source: common_util::codec::compact::Error,
+    },
+
+    #[snafu(display(
+        "Partition column not found in schema, column:{}.\nBacktrace:\n{}",
+        column,
+        backtrace
+    ))]
+    ColumnNotFound {
+        column: String,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "Partition of type {:?} must have exactly one column.\nBacktrace:\n{}",
+        partition_type,
+        backtrace
+    ))]
+    InvalidColumnNum {
+        partition_type: PartitionType,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "Partition num exceeds the max partition num, num:{}, max:{}.\nBacktrace:\n{}",
+        num,
+        PartitionNum::MAX,
+        backtrace
+    ))]
+    TooManyPartitions { num: usize, backtrace: Backtrace },
+}
+
+define_result!(Error);
+
 /// Partition type of table
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum PartitionType {
     None = 0,
     Hash = 1,
+    Range = 2,
+    List = 3,
+}
+
+impl PartitionType {
+    /// Name of the partition type used in sql.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PartitionType::None => "NONE",
+            PartitionType::Hash => "HASH",
+            PartitionType::Range => "RANGE",
+            PartitionType::List => "LIST",
+        }
+    }
+}
+
+impl From<PartitionType> for common_pb::PartitionType {
+    fn from(partition_type: PartitionType) -> Self {
+        match partition_type {
+            PartitionType::None => common_pb::PartitionType::NONE,
+            PartitionType::Hash => common_pb::PartitionType::HASH,
+            PartitionType::Range => common_pb::PartitionType::RANGE,
+            PartitionType::List => common_pb::PartitionType::LIST,
+        }
+    }
+}
+
+impl From<common_pb::PartitionType> for PartitionType {
+    fn from(partition_type: common_pb::PartitionType) -> Self {
+        match partition_type {
+            common_pb::PartitionType::NONE => PartitionType::None,
+            common_pb::PartitionType::HASH => PartitionType::Hash,
+            common_pb::PartitionType::RANGE => PartitionType::Range,
+            common_pb::PartitionType::LIST => PartitionType::List,
+        }
+    }
 }
 
 /// Size type of partition num
 pub type PartitionNum = u16;
 
+/// Bound of a range or list partition
+#[derive(Debug, Clone, PartialEq)]
+pub enum PartitionBound {
+    /// Rows whose partition key is less than the value belong to the
+    /// partition, `None` stands for `MAXVALUE`.
+    LessThan(Option<Datum>),
+    /// Rows whose partition key is one of the values belong to the partition.
+    In(Vec<Datum>),
+}
+
+/// Definition of a range or list partition
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartitionDefinition {
+    /// Partition name
+    pub name: String,
+    /// Partition bound
+    pub bound: PartitionBound,
+}
+
 /// Info for how to partition table
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct PartitionInfo {
     /// Partition type
     pub partition_type: PartitionType,
-    /// Partition expression
-    pub expr: String,
+    /// Columns of the partition key
+    pub columns: Vec<String>,
     /// Partition num
     pub partition_num: PartitionNum,
+    /// Definitions of range/list partitions, empty for hash partitioning
+    pub definitions: Vec<PartitionDefinition>,
+}
+
+impl From<PartitionInfo> for common_pb::PartitionInfo {
+    fn from(partition_info: PartitionInfo) -> Self {
+        let mut target = common_pb::PartitionInfo::new();
+        target.set_partition_type(partition_info.partition_type.into());
+        target.set_columns(partition_info.columns.into());
+        target.set_partition_num(u32::from(partition_info.partition_num));
+
+        let mut definitions = Vec::with_capacity(partition_info.definitions.len());
+        for definition in partition_info.definitions {
+            let values = match &definition.bound {
+                PartitionBound::LessThan(None) => Vec::new(),
+                PartitionBound::LessThan(Some(value)) => vec![encode_value(value)],
+                PartitionBound::In(values) => values.iter().map(encode_value).collect(),
+            };
+
+            let mut definition_pb = common_pb::PartitionDefinition::new();
+            definition_pb.set_name(definition.name);
+            definition_pb.set_values(values.into());
+            definitions.push(definition_pb);
+        }
+        target.set_definitions(definitions.into());
+
+        target
+    }
+}
+
+impl PartitionInfo {
+    /// Convert from protobuf, the `schema` provides the type of the partition
+    /// column to decode the bound values.
+    pub fn try_from_pb(mut pb: common_pb::PartitionInfo, schema: &Schema) -> Result<Self> {
+        let partition_type = PartitionType::from(pb.get_partition_type());
+        let columns = pb.take_columns().into_vec();
+
+        let partition_num = to_partition_num(pb.get_partition_num() as usize)?;
+        let mut definitions = Vec::with_capacity(pb.get_definitions().len());
+        if matches!(partition_type, PartitionType::Range | PartitionType::List) {
+            to_partition_num(pb.get_definitions().len())?;
+            let (_, kind) = find_single_column(partition_type, &columns, schema)?;
+
+            for mut definition_pb in pb.take_definitions().into_iter() {
+                let values = definition_pb
+                    .get_values()
+                    .iter()
+                    .map(|buf| decode_value(&kind, buf))
+                    .collect::<Result<Vec<_>>>()?;
+                let bound = if partition_type == PartitionType::Range {
+                    PartitionBound::LessThan(values.into_iter().next())
+                } else {
+                    PartitionBound::In(values)
+                };
+
+                definitions.push(PartitionDefinition {
+                    name: definition_pb.take_name(),
+                    bound,
+                });
+            }
+        }
+
+        Ok(Self {
+            partition_type,
+            columns,
+            partition_num,
+            definitions,
+        })
+    }
+}
+
+/// Convert the number of partitions to [PartitionNum], returns error if it
+/// exceeds the max partition num.
+pub fn to_partition_num(num: usize) -> Result<PartitionNum> {
+    PartitionNum::try_from(num)
+        .ok()
+        .context(TooManyPartitions { num })
+}
+
+/// Find the index and the kind of the partition column in `schema`.
+pub(crate) fn find_column(schema: &Schema, name: &str) -> Result<(usize, DatumKind)> {
+    let index = schema
+        .index_of(name)
+        .context(ColumnNotFound { column: name })?;
+
+    Ok((index, schema.column(index).data_type))
+}
+
+/// Find the only column of range/list partitioning in `schema`, returns its
+/// index and kind.
+pub(crate) fn find_single_column(
+    partition_type: PartitionType,
+    columns: &[String],
+    schema: &Schema,
+) -> Result<(usize, DatumKind)> {
+    ensure!(columns.len() == 1, InvalidColumnNum { partition_type });
+
+    find_column(schema, &columns[0])
+}
+
+fn encode_value(value: &Datum) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(MemCompactEncoder.estimate_encoded_size(value));
+    // Writing to a vec never fails.
+    MemCompactEncoder
+        .encode(&mut buf, value)
+        .expect("Failed to encode partition value");
+
+    buf
+}
+
+fn decode_value(kind: &DatumKind, mut buf: &[u8]) -> Result<Datum> {
+    let mut value = Datum::empty(kind);
+    MemCompactDecoder
+        .decode_to(&mut buf, &mut value)
+        .context(DecodeValue)?;
+
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use common_types::{string::StringBytes, tests::build_schema, time::Timestamp};
+
+    use super::*;
+
+    fn check_pb_round_trip(partition_info: PartitionInfo, schema: &Schema) {
+        let pb = common_pb::PartitionInfo::from(partition_info.clone());
+        let decoded = PartitionInfo::try_from_pb(pb, schema).unwrap();
+        assert_eq!(partition_info, decoded);
+    }
+
+    #[test]
+    fn test_partition_info_pb() {
+        let schema = build_schema();
+
+        let hash = PartitionInfo {
+            partition_type: PartitionType::Hash,
+            columns: vec!["key1".to_string(), "key2".to_string()],
+            partition_num: 4,
+            definitions: Vec::new(),
+        };
+        check_pb_round_trip(hash, &schema);
+
+        let range = PartitionInfo {
+            partition_type: PartitionType::Range,
+            columns: vec!["key2".to_string()],
+            partition_num: 2,
+            definitions: vec![
+                PartitionDefinition {
+                    name: "p0".to_string(),
+                    bound: PartitionBound::LessThan(Some(Datum::Timestamp(Timestamp::new(1000)))),
+                },
+                PartitionDefinition {
+                    name: "p1".to_string(),
+                    bound: PartitionBound::LessThan(None),
+                },
+            ],
+        };
+        check_pb_round_trip(range, &schema);
+
+        let list = PartitionInfo {
+            partition_type: PartitionType::List,
+            columns: vec!["field2".to_string()],
+            partition_num: 2,
+            definitions: vec![
+                PartitionDefinition {
+                    name: "p0".to_string(),
+                    bound: PartitionBound::In(vec![
+                        Datum::String(StringBytes::from("a")),
+                        Datum::String(StringBytes::from("b")),
+                    ]),
+                },
+                PartitionDefinition {
+                    name: "p1".to_string(),
+                    bound: PartitionBound::In(vec![Datum::String(StringBytes::from("c"))]),
+                },
+            ],
+        };
+        check_pb_round_trip(list, &schema);
+    }
+
+    #[test]
+    fn test_too_many_partitions() {
+        let max = usize::from(PartitionNum::MAX);
+        assert_eq!(PartitionNum::MAX, to_partition_num(max).unwrap());
+        assert!(matches!(
+            to_partition_num(max + 1),
+            Err(Error::TooManyPartitions { num, .. }) if num == max + 1
+        ));
+
+        let mut pb = common_pb::PartitionInfo::new();
+        pb.set_partition_type(common_pb::PartitionType::HASH);
+        pb.set_columns(vec!["key1".to_string()].into());
+        pb.set_partition_num(max as u32 + 1);
+        assert!(matches!(
+            PartitionInfo::try_from_pb(pb, &build_schema()),
+            Err(Error::TooManyPartitions { .. })
+        ));
+    }
 }
@@ -0,0 +1,498 @@
+// Copyright 2022 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Partition pruning based on the filters of the query.
+//!
+//! The pruning is conservative: a partition is pruned only if it is sure that
+//! no row of the partition matches the filters.
+
+use std::convert::TryFrom;
+
+use arrow_deps::datafusion::{
+    logical_plan::{Expr, Operator},
+    scalar::ScalarValue,
+};
+use common_types::{
+    bytes::Bytes,
+    datum::{Datum, DatumKind},
+    string::StringBytes,
+    time::Timestamp,
+};
+use log::debug;
+
+use crate::partition::rule::{HashPartitionRule, PartitionRule};
+
+/// Max number of value combinations of the hash columns to evaluate, the
+/// hash partitions won't be pruned if the combinations exceed this limit.
+const MAX_HASH_COMBINATIONS: usize = 1024;
+
+/// Return the indexes of partitions which may contain rows matching all the
+/// `filters`, in ascending order.
+pub(crate) fn prune(rule: &PartitionRule, filters: &[Expr]) -> Vec<usize> {
+    let partition_num = rule.partition_num();
+    let mut candidates = vec![true; partition_num];
+
+    let matched_list = match rule {
+        PartitionRule::None => Vec::new(),
+        PartitionRule::Hash(rule) => prune_hash(rule, filters).into_iter().collect(),
+        PartitionRule::Range(_) | PartitionRule::List(_) => filters
+            .iter()
+            .filter_map(|filter| prune_expr(rule, filter))
+            .collect(),
+    };
+    for matched in matched_list {
+        for (candidate, matched) in candidates.iter_mut().zip(matched) {
+            *candidate = *candidate && matched;
+        }
+    }
+
+    let partitions: Vec<_> = candidates
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, candidate)| candidate.then(|| i))
+        .collect();
+
+    debug!(
+        "Partition pruned, filters:{:?}, partitions:{:?}, partition_num:{}",
+        filters, partitions, partition_num
+    );
+
+    partitions
+}
+
+/// Prune range/list partitions by `expr`, returns `None` if the expr can't be
+/// used to prune.
+fn prune_expr(rule: &PartitionRule, expr: &Expr) -> Option<Vec<bool>> {
+    match expr {
+        Expr::BinaryExpr { left, op, right } => match op {
+            Operator::And => match (prune_expr(rule, left), prune_expr(rule, right)) {
+                (Some(l), Some(r)) => Some(l.into_iter().zip(r).map(|(l, r)| l && r).collect()),
+                (Some(v), None) | (None, Some(v)) => Some(v),
+                (None, None) => None,
+            },
+            Operator::Or => {
+                let (l, r) = (prune_expr(rule, left)?, prune_expr(rule, right)?);
+                Some(l.into_iter().zip(r).map(|(l, r)| l || r).collect())
+            }
+            _ => {
+                let (column, op, value) = column_op_value(left, op, right)?;
+                prune_comparison(rule, column, &op, value)
+            }
+        },
+        Expr::InList {
+            expr,
+            list,
+            negated: false,
+        } => {
+            let column = column_name(expr)?;
+            let mut res = vec![false; rule.partition_num()];
+            for item in list {
+                let value = literal(item)?;
+                let matched = prune_comparison(rule, column, &Operator::Eq, value)?;
+                for (r, m) in res.iter_mut().zip(matched) {
+                    *r = *r || m;
+                }
+            }
+
+            Some(res)
+        }
+        Expr::Between {
+            expr,
+            negated: false,
+            low,
+            high,
+        } => {
+            let column = column_name(expr)?;
+            let low = prune_comparison(rule, column, &Operator::GtEq, literal(low)?)?;
+            let high = prune_comparison(rule, column, &Operator::LtEq, literal(high)?)?;
+
+            Some(low.into_iter().zip(high).map(|(l, h)| l && h).collect())
+        }
+        _ => None,
+    }
+}
+
+fn prune_comparison(
+    rule: &PartitionRule,
+    column: &str,
+    op: &Operator,
+    value: &ScalarValue,
+) -> Option<Vec<bool>> {
+    match rule {
+        PartitionRule::Range(rule) => {
+            let (name, kind) = rule.column();
+            if name != column {
+                return None;
+            }
+            rule.matches(op, &datum_from_scalar(&kind, value)?)
+        }
+        PartitionRule::List(rule) => {
+            let (name, kind) = rule.column();
+            if name != column {
+                return None;
+            }
+            rule.matches(op, &datum_from_scalar(&kind, value)?)
+        }
+        PartitionRule::None | PartitionRule::Hash(_) => None,
+    }
+}
+
+/// Prune hash partitions by `filters`, only the conjunction of equalities (or
+/// in lists) on all the hash columns can be used to prune.
+fn prune_hash(rule: &HashPartitionRule, filters: &[Expr]) -> Option<Vec<bool>> {
+    let mut conjunctions = Vec::new();
+    for filter in filters {
+        split_and_expr(filter, &mut conjunctions);
+    }
+
+    // Candidate values of each hash column.
+    let mut column_values = Vec::with_capacity(rule.columns().len());
+    for (name, kind) in rule.columns() {
+        let values = conjunctions
+            .iter()
+            .find_map(|expr| equal_values(expr, name, kind))?;
+        column_values.push(values);
+    }
+
+    let combinations = column_values
+        .iter()
+        .try_fold(1usize, |acc, values| acc.checked_mul(values.len()))?;
+    if combinations > MAX_HASH_COMBINATIONS {
+        return None;
+    }
+
+    let mut res = vec![false; rule.partition_num()];
+    let mut indexes = vec![0; column_values.len()];
+    for _ in 0..combinations {
+        let values: Vec<_> = indexes
+            .iter()
+            .zip(&column_values)
+            .map(|(i, values)| &values[*i])
+            .collect();
+        let partition = rule.eval_values(&values).ok()?;
+        res[partition] = true;
+
+        // Move to the next combination.
+        for (i, values) in indexes.iter_mut().zip(&column_values) {
+            *i += 1;
+            if *i < values.len() {
+                break;
+            }
+            *i = 0;
+        }
+    }
+
+    Some(res)
+}
+
+/// Extract the values of `column` from `column = value` or `column IN
+/// (values)`.
+fn equal_values(expr: &Expr, column: &str, kind: &DatumKind) -> Option<Vec<Datum>> {
+    match expr {
+        Expr::BinaryExpr { left, op, right } => {
+            let (name, op, value) = column_op_value(left, op, right)?;
+            if name != column || !matches!(op, Operator::Eq) {
+                return None;
+            }
+
+            Some(vec![datum_from_scalar(kind, value)?])
+        }
+        Expr::InList {
+            expr,
+            list,
+            negated: false,
+        } => {
+            if column_name(expr)? != column {
+                return None;
+            }
+
+            list.iter()
+                .map(|item| datum_from_scalar(kind, literal(item)?))
+                .collect()
+        }
+        _ => None,
+    }
+}
+
+/// Recursively split all "AND" expressions into smaller one.
+fn split_and_expr<'a>(expr: &'a Expr, exprs: &mut Vec<&'a Expr>) {
+    match expr {
+        Expr::BinaryExpr {
+            left,
+            op: Operator::And,
+            right,
+        } => {
+            split_and_expr(left, exprs);
+            split_and_expr(right, exprs);
+        }
+        other => exprs.push(other),
+    }
+}
+
+/// Normalize `column op literal` or `literal op column` into `(column, op,
+/// literal)`.
+fn column_op_value<'a>(
+    left: &'a Expr,
+    op: &Operator,
+    right: &'a Expr,
+) -> Option<(&'a str, Operator, &'a ScalarValue)> {
+    match (left, right) {
+        (Expr::Column(column), Expr::Literal(value)) => Some((&column.name, *op, value)),
+        (Expr::Literal(value), Expr::Column(column)) => {
+            let op = match op {
+                Operator::Lt => Operator::Gt,
+                Operator::LtEq => Operator::GtEq,
+                Operator::Gt => Operator::Lt,
+                Operator::GtEq => Operator::LtEq,
+                Operator::Eq | Operator::NotEq => *op,
+                _ => return None,
+            };
+            Some((&column.name, op, value))
+        }
+        _ => None,
+    }
+}
+
+fn column_name(expr: &Expr) -> Option<&str> {
+    match expr {
+        Expr::Column(column) => Some(&column.name),
+        _ => None,
+    }
+}
+
+fn literal(expr: &Expr) -> Option<&ScalarValue> {
+    match expr {
+        Expr::Literal(value) => Some(value),
+        _ => None,
+    }
+}
+
+/// Convert the literal `value` to datum of `kind`, returns `None` if it can't
+/// be converted without loss.
+fn datum_from_scalar(kind: &DatumKind, value: &ScalarValue) -> Option<Datum> {
+    let datum = match (kind, value) {
+        (DatumKind::String, ScalarValue::Utf8(Some(v)))
+        | (DatumKind::String, ScalarValue::LargeUtf8(Some(v))) => {
+            Datum::String(StringBytes::from(v.as_str()))
+        }
+        (DatumKind::Varbinary, ScalarValue::Binary(Some(v)))
+        | (DatumKind::Varbinary, ScalarValue::LargeBinary(Some(v))) => {
+            Datum::Varbinary(Bytes::copy_from_slice(v))
+        }
+        (DatumKind::Boolean, ScalarValue::Boolean(Some(v))) => Datum::Boolean(*v),
+        (DatumKind::Timestamp, ScalarValue::TimestampMillisecond(Some(v), _)) => {
+            Datum::Timestamp(Timestamp::new(*v))
+        }
+        (DatumKind::Double, ScalarValue::Float64(Some(v))) => Datum::Double(*v),
+        (DatumKind::Float, ScalarValue::Float32(Some(v))) => Datum::Float(*v),
+        _ => {
+            let v = integer_from_scalar(value)?;
+            match kind {
+                DatumKind::Timestamp => Datum::Timestamp(Timestamp::new(i64::try_from(v).ok()?)),
+                DatumKind::UInt64 => Datum::UInt64(u64::try_from(v).ok()?),
+                DatumKind::UInt32 => Datum::UInt32(u32::try_from(v).ok()?),
+                DatumKind::UInt16 => Datum::UInt16(u16::try_from(v).ok()?),
+                DatumKind::UInt8 => Datum::UInt8(u8::try_from(v).ok()?),
+                DatumKind::Int64 => Datum::Int64(i64::try_from(v).ok()?),
+                DatumKind::Int32 => Datum::Int32(i32::try_from(v).ok()?),
+                DatumKind::Int16 => Datum::Int16(i16::try_from(v).ok()?),
+                DatumKind::Int8 => Datum::Int8(i8::try_from(v).ok()?),
+                _ => return None,
+            }
+        }
+    };
+
+    Some(datum)
+}
+
+fn integer_from_scalar(value: &ScalarValue) -> Option<i128> {
+    match value {
+        ScalarValue::Int8(v) => v.map(i128::from),
+        ScalarValue::Int16(v) => v.map(i128::from),
+        ScalarValue::Int32(v) => v.map(i128::from),
+        ScalarValue::Int64(v) => v.map(i128::from),
+        ScalarValue::UInt8(v) => v.map(i128::from),
+        ScalarValue::UInt16(v) => v.map(i128::from),
+        ScalarValue::UInt32(v) => v.map(i128::from),
+        ScalarValue::UInt64(v) => v.map(i128::from),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_deps::datafusion::logical_plan::{col, lit};
+    use common_types::tests::build_schema;
+
+    use super::*;
+    use crate::partition::{
+        to_partition_num, PartitionBound, PartitionDefinition, PartitionInfo, PartitionType,
+    };
+
+    fn build_rule(
+        partition_type: PartitionType,
+        column: &str,
+        bounds: Vec<PartitionBound>,
+    ) -> PartitionRule {
+        let definitions: Vec<_> = bounds
+            .into_iter()
+            .enumerate()
+            .map(|(i, bound)| PartitionDefinition {
+                name: format!("p{}", i),
+                bound,
+            })
+            .collect();
+        let partition_info = PartitionInfo {
+            partition_type,
+            columns: vec![column.to_string()],
+            partition_num: to_partition_num(definitions.len()).unwrap(),
+            definitions,
+        };
+
+        PartitionRule::new(&partition_info, &build_schema()).unwrap()
+    }
+
+    fn ts(v: i64) -> Datum {
+        Datum::Timestamp(Timestamp::new(v))
+    }
+
+    fn ts_lit(v: i64) -> Expr {
+        Expr::Literal(ScalarValue::TimestampMillisecond(Some(v), None))
+    }
+
+    #[test]
+    fn test_prune_range() {
+        // p0: [MIN, 1000), p1: [1000, 2000), p2: [2000, MAX)
+        let rule = build_rule(
+            PartitionType::Range,
+            "key2",
+            vec![
+                PartitionBound::LessThan(Some(ts(1000))),
+                PartitionBound::LessThan(Some(ts(2000))),
+                PartitionBound::LessThan(None),
+            ],
+        );
+
+        let cases = vec![
+            (vec![], vec![0, 1, 2]),
+            (vec![col("key2").eq(ts_lit(1500))], vec![1]),
+            (vec![col("key2").eq(lit(1000i64))], vec![1]),
+            (vec![col("key2").lt(ts_lit(1000))], vec![0]),
+            (vec![col("key2").lt_eq(ts_lit(1000))], vec![0, 1]),
+            (vec![col("key2").gt_eq(ts_lit(2000))], vec![2]),
+            (vec![ts_lit(2000).gt(col("key2"))], vec![0, 1]),
+            (
+                vec![col("key2").gt_eq(ts_lit(500)), col("key2").lt(ts_lit(1500))],
+                vec![0, 1],
+            ),
+            (
+                vec![col("key2")
+                    .lt(ts_lit(500))
+                    .or(col("key2").gt_eq(ts_lit(2500)))],
+                vec![0, 2],
+            ),
+            (
+                vec![Expr::Between {
+                    expr: Box::new(col("key2")),
+                    negated: false,
+                    low: Box::new(ts_lit(1200)),
+                    high: Box::new(ts_lit(1800)),
+                }],
+                vec![1],
+            ),
+            (
+                vec![col("key2").in_list(vec![ts_lit(0), ts_lit(3000)], false)],
+                vec![0, 2],
+            ),
+            // Filters on other columns can't prune.
+            (vec![col("field1").eq(lit(1.0))], vec![0, 1, 2]),
+            (
+                vec![col("key2").eq(ts_lit(1500)).or(col("field1").eq(lit(1.0)))],
+                vec![0, 1, 2],
+            ),
+        ];
+
+        for (filters, expect) in cases {
+            assert_eq!(expect, rule.prune(&filters), "filters:{:?}", filters);
+        }
+    }
+
+    #[test]
+    fn test_prune_list() {
+        let rule = build_rule(
+            PartitionType::List,
+            "field2",
+            vec![
+                PartitionBound::In(vec![
+                    Datum::String(StringBytes::from("a")),
+                    Datum::String(StringBytes::from("b")),
+                ]),
+                PartitionBound::In(vec![Datum::String(StringBytes::from("c"))]),
+                PartitionBound::In(vec![Datum::String(StringBytes::from("d"))]),
+            ],
+        );
+
+        let cases = vec![
+            (vec![col("field2").eq(lit("a"))], vec![0]),
+            (vec![col("field2").eq(lit("x"))], vec![]),
+            (vec![col("field2").not_eq(lit("c"))], vec![0, 2]),
+            (vec![col("field2").gt(lit("b"))], vec![1, 2]),
+            (
+                vec![col("field2").in_list(vec![lit("b"), lit("d")], false)],
+                vec![0, 2],
+            ),
+            (
+                vec![col("field2").in_list(vec![lit("b"), lit("d")], true)],
+                vec![0, 1, 2],
+            ),
+        ];
+
+        for (filters, expect) in cases {
+            assert_eq!(expect, rule.prune(&filters), "filters:{:?}", filters);
+        }
+    }
+
+    #[test]
+    fn test_prune_hash() {
+        let partition_info = PartitionInfo {
+            partition_type: PartitionType::Hash,
+            columns: vec!["key1".to_string(), "field2".to_string()],
+            partition_num: 16,
+            definitions: Vec::new(),
+        };
+        let rule = PartitionRule::new(&partition_info, &build_schema()).unwrap();
+        let hash_rule = match &rule {
+            PartitionRule::Hash(rule) => rule,
+            _ => panic!("Unexpected rule"),
+        };
+
+        let key1 = Datum::Varbinary(Bytes::from_static(b"k"));
+        let expect = |values: &[&str]| {
+            let mut partitions: Vec<_> = values
+                .iter()
+                .map(|v| {
+                    let field2 = Datum::String(StringBytes::from(*v));
+                    hash_rule.eval_values(&[&key1, &field2]).unwrap()
+                })
+                .collect();
+            partitions.sort_unstable();
+            partitions.dedup();
+            partitions
+        };
+        let key1_lit = Expr::Literal(ScalarValue::Binary(Some(b"k".to_vec())));
+
+        let filters = vec![col("key1")
+            .eq(key1_lit.clone())
+            .and(col("field2").eq(lit("a")))];
+        assert_eq!(expect(&["a"]), rule.prune(&filters));
+
+        let filters = vec![
+            col("key1").eq(key1_lit.clone()),
+            col("field2").in_list(vec![lit("a"), lit("b")], false),
+        ];
+        assert_eq!(expect(&["a", "b"]), rule.prune(&filters));
+
+        // Not all the hash columns are restricted.
+        let filters = vec![col("key1").eq(key1_lit)];
+        assert_eq!((0..16).collect::<Vec<_>>(), rule.prune(&filters));
+    }
+}
@@ -2,12 +2,21 @@
 
 //! Partition rules
 
-use common_types::{datum::Datum, row::Row, schema::Schema};
+use std::cmp::Ordering;
+
+use arrow_deps::datafusion::logical_plan::{Expr, Operator};
+use common_types::{
+    datum::{Datum, DatumKind},
+    row::Row,
+    schema::{IndexInWriterSchema, Schema},
+};
 use common_util::define_result;
 use smallvec::SmallVec;
-use snafu::{ensure, Backtrace, ResultExt, Snafu};
+use snafu::{ensure, Backtrace, OptionExt, ResultExt, Snafu};
 
-use crate::partition::{expression::Expression, PartitionInfo, PartitionType};
+use crate::partition::{
+    self, expression::Expression, pruning, PartitionBound, PartitionInfo, PartitionType,
+};
 
 const HASH_COLUMN_NUM: usize = 1;
 
@@ -16,8 +25,30 @@ pub enum Error {
     #[snafu(display("No column for hash partitioning.\nBacktrace:\n{}", backtrace))]
     NoColumnForHash { backtrace: Backtrace },
 
-    #[snafu(display("Only support one hash column.\nBacktrace:\n{}", backtrace))]
-    TooMuchHashColumn { backtrace: Backtrace },
+    #[snafu(display("Invalid partition column, err:{}", source))]
+    InvalidColumn { source: crate::partition::Error },
+
+    #[snafu(display("Partition num must be positive.\nBacktrace:\n{}", backtrace))]
+    ZeroPartitionNum { backtrace: Backtrace },
+
+    #[snafu(display(
+        "Invalid bound of partition, partition:{}, msg:{}.\nBacktrace:\n{}",
+        name,
+        msg,
+        backtrace
+    ))]
+    InvalidBound {
+        name: String,
+        msg: String,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "No partition for value, value:{:?}.\nBacktrace:\n{}",
+        value,
+        backtrace
+    ))]
+    NoPartitionForValue { value: Datum, backtrace: Backtrace },
 
     #[snafu(display("Failed to eval partition expr, err:{}", source))]
     EvalExpr {
@@ -28,11 +59,15 @@ pub enum Error {
 define_result!(Error);
 
 /// Partition rule locate partition by input records
-// TODO(yingwen): Recreate partition rule once the schema of the table is changed
+///
+/// The rule refers to the partition columns by their indexes in the table
+/// schema, which are stable as columns are only appended to the schema.
 #[derive(Debug)]
 pub enum PartitionRule {
     None,
     Hash(HashPartitionRule),
+    Range(RangePartitionRule),
+    List(ListPartitionRule),
 }
 
 impl PartitionRule {
@@ -43,6 +78,24 @@ impl PartitionRule {
                 let rule = HashPartitionRule::new(partition_info, schema)?;
                 Ok(PartitionRule::Hash(rule))
             }
+            PartitionType::Range => {
+                let rule = RangePartitionRule::new(partition_info, schema)?;
+                Ok(PartitionRule::Range(rule))
+            }
+            PartitionType::List => {
+                let rule = ListPartitionRule::new(partition_info, schema)?;
+                Ok(PartitionRule::List(rule))
+            }
+        }
+    }
+
+    /// Return the number of partitions
+    pub fn partition_num(&self) -> usize {
+        match self {
+            PartitionRule::None => 1,
+            PartitionRule::Hash(rule) => rule.partition_num(),
+            PartitionRule::Range(rule) => rule.upper_bounds.len(),
+            PartitionRule::List(rule) => rule.values.len(),
         }
     }
 
@@ -52,8 +105,58 @@ impl PartitionRule {
             // Always return the first partition
             PartitionRule::None => Ok(0),
             PartitionRule::Hash(rule) => rule.eval_partition_index(row),
+            PartitionRule::Range(rule) => rule.eval_partition_index(row),
+            PartitionRule::List(rule) => rule.eval_partition_index(row),
+        }
+    }
+
+    /// Return the index of partition of the `row` in the schema of the writer,
+    /// `index_in_writer` maps the columns of the table schema to the writer
+    /// schema and the columns missing in the writer are regarded as null.
+    pub fn locate_partition_in_writer(
+        &self,
+        row: &Row,
+        index_in_writer: &IndexInWriterSchema,
+    ) -> Result<usize> {
+        let null = Datum::Null;
+        let value_of =
+            |index_in_table: usize| match index_in_writer.column_index_in_writer(index_in_table) {
+                Some(index) => &row[index],
+                None => &null,
+            };
+
+        match self {
+            PartitionRule::None => Ok(0),
+            PartitionRule::Hash(rule) => {
+                let col_vals: SmallVec<[&Datum; HASH_COLUMN_NUM]> =
+                    rule.column_index.iter().map(|i| value_of(*i)).collect();
+                rule.eval_values(&col_vals)
+            }
+            PartitionRule::Range(rule) => rule.eval_value(value_of(rule.column_index)),
+            PartitionRule::List(rule) => rule.eval_value(value_of(rule.column_index)),
         }
     }
+
+    /// Return the indexes of partitions which may contain rows matching all
+    /// the `filters`, in ascending order.
+    pub fn prune(&self, filters: &[Expr]) -> Vec<usize> {
+        pruning::prune(self, filters)
+    }
+}
+
+/// Find the only column of range/list partitioning.
+fn find_single_column(
+    partition_info: &PartitionInfo,
+    schema: &Schema,
+) -> Result<(String, usize, DatumKind)> {
+    let (index, kind) = partition::find_single_column(
+        partition_info.partition_type,
+        &partition_info.columns,
+        schema,
+    )
+    .context(InvalidColumn)?;
+
+    Ok((partition_info.columns[0].clone(), index, kind))
 }
 
 /// Partition rule based on hash
@@ -66,30 +169,33 @@ pub struct HashPartitionRule {
     /// Offsets of columns for evaluate
     // TODO(yingwen): The column index may be invalid after schema change (add/del column)
     column_index: SmallVec<[usize; HASH_COLUMN_NUM]>,
+    /// Names and kinds of the hash columns, in the same order as
+    /// `column_index`.
+    columns: SmallVec<[(String, DatumKind); HASH_COLUMN_NUM]>,
 }
 
 impl HashPartitionRule {
     pub fn new(partition_info: &PartitionInfo, schema: &Schema) -> Result<Self> {
+        ensure!(partition_info.partition_num > 0, ZeroPartitionNum);
+
         let expr = Expression::new(partition_info);
 
         let col_name_list = expr.extract_column_name();
         let mut column_index = SmallVec::with_capacity(col_name_list.size_hint().0);
+        let mut columns = SmallVec::with_capacity(col_name_list.size_hint().0);
         for col_name in col_name_list {
-            for (i, v) in schema.columns().iter().enumerate() {
-                if col_name == v.name {
-                    column_index.push(i);
-                    break;
-                }
-            }
+            let (index, kind) = partition::find_column(schema, col_name).context(InvalidColumn)?;
+            column_index.push(index);
+            columns.push((col_name.to_string(), kind));
         }
 
         ensure!(!column_index.is_empty(), NoColumnForHash);
-        ensure!(column_index.len() == 1, TooMuchHashColumn);
 
         Ok(Self {
             partition_num: partition_info.partition_num,
             expression: expr,
             column_index,
+            columns,
         })
     }
 
@@ -101,8 +207,427 @@ impl HashPartitionRule {
             // TODO(yingwen): Check index?
             col_vals.push(&row[*i]);
         }
-        let eval_uint = self.expression.eval_uint(&col_vals).context(EvalExpr)?;
+
+        self.eval_values(&col_vals)
+    }
+
+    pub(crate) fn partition_num(&self) -> usize {
+        self.partition_num as usize
+    }
+
+    /// Names and kinds of the hash columns.
+    pub(crate) fn columns(&self) -> &[(String, DatumKind)] {
+        &self.columns
+    }
+
+    /// Eval the partition index of the values of the hash columns.
+    pub(crate) fn eval_values(&self, values: &[&Datum]) -> Result<usize> {
+        let eval_uint = self.expression.eval_uint(values).context(EvalExpr)?;
 
         Ok((eval_uint % self.partition_num as u64) as usize)
     }
 }
+
+/// Partition rule based on ranges of the partition column
+#[derive(Debug)]
+pub struct RangePartitionRule {
+    column: String,
+    column_index: usize,
+    column_kind: DatumKind,
+    /// Exclusive upper bound of each partition in ascending order, `None`
+    /// stands for `MAXVALUE` and is only allowed for the last partition.
+    upper_bounds: Vec<Option<Datum>>,
+}
+
+impl RangePartitionRule {
+    pub fn new(partition_info: &PartitionInfo, schema: &Schema) -> Result<Self> {
+        let (column, column_index, column_kind) = find_single_column(partition_info, schema)?;
+        ensure!(!partition_info.definitions.is_empty(), ZeroPartitionNum);
+
+        let mut upper_bounds: Vec<Option<Datum>> =
+            Vec::with_capacity(partition_info.definitions.len());
+        for definition in &partition_info.definitions {
+            let upper = match &definition.bound {
+                PartitionBound::LessThan(upper) => upper,
+                PartitionBound::In(_) => {
+                    return InvalidBound {
+                        name: &definition.name,
+                        msg: "range partition requires VALUES LESS THAN",
+                    }
+                    .fail();
+                }
+            };
+
+            if let Some(value) = upper {
+                ensure!(
+                    value.kind() == column_kind,
+                    InvalidBound {
+                        name: &definition.name,
+                        msg: format!("value type mismatch, expect:{:?}", column_kind),
+                    }
+                );
+            }
+
+            if let Some(prev) = upper_bounds.last() {
+                let increasing = match (prev, upper) {
+                    (Some(prev), Some(value)) => prev < value,
+                    _ => false,
+                };
+                ensure!(
+                    increasing,
+                    InvalidBound {
+                        name: &definition.name,
+                        msg: "values must be strictly increasing",
+                    }
+                );
+            }
+
+            upper_bounds.push(upper.clone());
+        }
+
+        Ok(Self {
+            column,
+            column_index,
+            column_kind,
+            upper_bounds,
+        })
+    }
+
+    pub fn eval_partition_index(&self, row: &Row) -> Result<usize> {
+        self.eval_value(&row[self.column_index])
+    }
+
+    fn eval_value(&self, value: &Datum) -> Result<usize> {
+        // Null is treated as the minimum value.
+        self.upper_bounds
+            .iter()
+            .position(|upper| match upper {
+                Some(upper) => value.is_null() || value < upper,
+                None => true,
+            })
+            .with_context(|| NoPartitionForValue {
+                value: value.clone(),
+            })
+    }
+
+    pub(crate) fn column(&self) -> (&str, DatumKind) {
+        (&self.column, self.column_kind)
+    }
+
+    /// Returns whether partitions may contain values making `column op value`
+    /// true.
+    pub(crate) fn matches(&self, op: &Operator, value: &Datum) -> Option<Vec<bool>> {
+        let mut res = Vec::with_capacity(self.upper_bounds.len());
+        let mut lower: Option<&Datum> = None;
+        for upper in &self.upper_bounds {
+            let above_lower = |allow_equal: bool| match lower {
+                Some(lower) => match lower.partial_cmp(value) {
+                    Some(Ordering::Less) => true,
+                    Some(Ordering::Equal) => allow_equal,
+                    _ => false,
+                },
+                None => true,
+            };
+            let below_upper = match upper {
+                Some(upper) => value < upper,
+                None => true,
+            };
+
+            let matched = match op {
+                Operator::Eq => above_lower(true) && below_upper,
+                Operator::Lt => above_lower(false),
+                Operator::LtEq => above_lower(true),
+                Operator::Gt | Operator::GtEq => below_upper,
+                _ => return None,
+            };
+            res.push(matched);
+            lower = upper.as_ref();
+        }
+
+        Some(res)
+    }
+}
+
+/// Partition rule based on lists of values of the partition column
+#[derive(Debug)]
+pub struct ListPartitionRule {
+    column: String,
+    column_index: usize,
+    column_kind: DatumKind,
+    /// Values of each partition.
+    values: Vec<Vec<Datum>>,
+}
+
+impl ListPartitionRule {
+    pub fn new(partition_info: &PartitionInfo, schema: &Schema) -> Result<Self> {
+        let (column, column_index, column_kind) = find_single_column(partition_info, schema)?;
+        ensure!(!partition_info.definitions.is_empty(), ZeroPartitionNum);
+
+        let mut values: Vec<Vec<Datum>> = Vec::with_capacity(partition_info.definitions.len());
+        for definition in &partition_info.definitions {
+            let partition_values = match &definition.bound {
+                PartitionBound::In(v) => v,
+                PartitionBound::LessThan(_) => {
+                    return InvalidBound {
+                        name: &definition.name,
+                        msg: "list partition requires VALUES IN",
+                    }
+                    .fail();
+                }
+            };
+
+            ensure!(
+                !partition_values.is_empty(),
+                InvalidBound {
+                    name: &definition.name,
+                    msg: "values must not be empty",
+                }
+            );
+
+            for value in partition_values {
+                ensure!(
+                    value.kind() == column_kind,
+                    InvalidBound {
+                        name: &definition.name,
+                        msg: format!("value type mismatch, expect:{:?}", column_kind),
+                    }
+                );
+
+                let duplicate = values.iter().flatten().any(|v| v == value);
+                ensure!(
+                    !duplicate,
+                    InvalidBound {
+                        name: &definition.name,
+                        msg: format!("duplicate value:{:?}", value),
+                    }
+                );
+            }
+
+            values.push(partition_values.clone());
+        }
+
+        Ok(Self {
+            column,
+            column_index,
+            column_kind,
+            values,
+        })
+    }
+
+    pub fn eval_partition_index(&self, row: &Row) -> Result<usize> {
+        self.eval_value(&row[self.column_index])
+    }
+
+    fn eval_value(&self, value: &Datum) -> Result<usize> {
+        self.values
+            .iter()
+            .position(|values| values.contains(value))
+            .with_context(|| NoPartitionForValue {
+                value: value.clone(),
+            })
+    }
+
+    pub(crate) fn column(&self) -> (&str, DatumKind) {
+        (&self.column, self.column_kind)
+    }
+
+    /// Returns whether partitions contain values making `column op value`
+    /// true.
+    pub(crate) fn matches(&self, op: &Operator, value: &Datum) -> Option<Vec<bool>> {
+        let cmp: fn(Ordering) -> bool = match op {
+            Operator::Eq => |o| o == Ordering::Equal,
+            Operator::NotEq => |o| o != Ordering::Equal,
+            Operator::Lt => |o| o == Ordering::Less,
+            Operator::LtEq => |o| o != Ordering::Greater,
+            Operator::Gt => |o| o == Ordering::Greater,
+            Operator::GtEq => |o| o != Ordering::Less,
+            _ => return None,
+        };
+
+        let res = self
+            .values
+            .iter()
+            .map(|values| {
+                values
+                    .iter()
+                    .filter(|v| !v.is_null())
+                    .any(|v| v.partial_cmp(value).map(cmp).unwrap_or(false))
+            })
+            .collect();
+
+        Some(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common_types::{
+        bytes::Bytes,
+        string::StringBytes,
+        tests::{build_row, build_schema},
+        time::Timestamp,
+    };
+
+    use super::*;
+    use crate::partition::PartitionDefinition;
+
+    fn timestamp(v: i64) -> Datum {
+        Datum::Timestamp(Timestamp::new(v))
+    }
+
+    fn string(v: &str) -> Datum {
+        Datum::String(StringBytes::from(v))
+    }
+
+    fn range_partition_info(bounds: Vec<Option<Datum>>) -> PartitionInfo {
+        let definitions = bounds
+            .into_iter()
+            .enumerate()
+            .map(|(i, bound)| PartitionDefinition {
+                name: format!("p{}", i),
+                bound: PartitionBound::LessThan(bound),
+            })
+            .collect::<Vec<_>>();
+
+        PartitionInfo {
+            partition_type: PartitionType::Range,
+            columns: vec!["key2".to_string()],
+            partition_num: partition::to_partition_num(definitions.len()).unwrap(),
+            definitions,
+        }
+    }
+
+    fn list_partition_info(values: Vec<Vec<Datum>>) -> PartitionInfo {
+        let definitions = values
+            .into_iter()
+            .enumerate()
+            .map(|(i, values)| PartitionDefinition {
+                name: format!("p{}", i),
+                bound: PartitionBound::In(values),
+            })
+            .collect::<Vec<_>>();
+
+        PartitionInfo {
+            partition_type: PartitionType::List,
+            columns: vec!["field2".to_string()],
+            partition_num: partition::to_partition_num(definitions.len()).unwrap(),
+            definitions,
+        }
+    }
+
+    #[test]
+    fn test_multi_column_hash_rule() {
+        let schema = build_schema();
+        let partition_info = PartitionInfo {
+            partition_type: PartitionType::Hash,
+            columns: vec!["key1".to_string(), "field2".to_string()],
+            partition_num: 8,
+            definitions: Vec::new(),
+        };
+        let rule = PartitionRule::new(&partition_info, &schema).unwrap();
+        assert_eq!(8, rule.partition_num());
+
+        let row = build_row(b"a", 1000, 1.0, "b");
+        let index = rule.locate_partition(&row).unwrap();
+        assert!(index < 8);
+        // Same key columns always locate the same partition.
+        let row = build_row(b"a", 2000, 2.0, "b");
+        assert_eq!(index, rule.locate_partition(&row).unwrap());
+
+        let rule = match rule {
+            PartitionRule::Hash(rule) => rule,
+            _ => panic!("Unexpected rule"),
+        };
+        let values = [&Datum::Varbinary(Bytes::from_static(b"a")), &string("b")];
+        assert_eq!(index, rule.eval_values(&values).unwrap());
+    }
+
+    #[test]
+    fn test_hash_rule_invalid() {
+        let schema = build_schema();
+        let mut partition_info = PartitionInfo {
+            partition_type: PartitionType::Hash,
+            columns: vec!["key1".to_string(), "not_exist".to_string()],
+            partition_num: 8,
+            definitions: Vec::new(),
+        };
+        assert!(PartitionRule::new(&partition_info, &schema).is_err());
+
+        partition_info.columns = vec!["key1".to_string()];
+        partition_info.partition_num = 0;
+        assert!(PartitionRule::new(&partition_info, &schema).is_err());
+    }
+
+    #[test]
+    fn test_range_rule() {
+        let schema = build_schema();
+        let partition_info =
+            range_partition_info(vec![Some(timestamp(1000)), Some(timestamp(2000))]);
+        let rule = PartitionRule::new(&partition_info, &schema).unwrap();
+        assert_eq!(2, rule.partition_num());
+
+        let index_in_writer = IndexInWriterSchema::for_same_schema(schema.num_columns());
+        let cases = [(0, Some(0)), (999, Some(0)), (1000, Some(1)), (2000, None)];
+        for (ts, expect) in cases {
+            let row = build_row(b"a", ts, 1.0, "b");
+            let in_writer = rule.locate_partition_in_writer(&row, &index_in_writer);
+            match expect {
+                Some(index) => {
+                    assert_eq!(index, rule.locate_partition(&row).unwrap());
+                    assert_eq!(index, in_writer.unwrap());
+                }
+                None => {
+                    assert!(rule.locate_partition(&row).is_err());
+                    assert!(in_writer.is_err());
+                }
+            }
+        }
+
+        let partition_info =
+            range_partition_info(vec![Some(timestamp(1000)), Some(timestamp(2000)), None]);
+        let rule = PartitionRule::new(&partition_info, &schema).unwrap();
+        let row = build_row(b"a", 5000, 1.0, "b");
+        assert_eq!(2, rule.locate_partition(&row).unwrap());
+    }
+
+    #[test]
+    fn test_range_rule_invalid() {
+        let schema = build_schema();
+        let invalid_bounds = vec![
+            vec![],
+            vec![Some(timestamp(2000)), Some(timestamp(1000))],
+            vec![Some(timestamp(1000)), Some(timestamp(1000))],
+            vec![None, Some(timestamp(1000))],
+            vec![Some(Datum::Int64(1000))],
+        ];
+        for bounds in invalid_bounds {
+            let partition_info = range_partition_info(bounds);
+            assert!(PartitionRule::new(&partition_info, &schema).is_err());
+        }
+    }
+
+    #[test]
+    fn test_list_rule() {
+        let schema = build_schema();
+        let partition_info =
+            list_partition_info(vec![vec![string("a"), string("b")], vec![string("c")]]);
+        let rule = PartitionRule::new(&partition_info, &schema).unwrap();
+        assert_eq!(2, rule.partition_num());
+
+        let cases = [("a", Some(0)), ("b", Some(0)), ("c", Some(1)), ("d", None)];
+        for (value, expect) in cases {
+            let row = build_row(b"a", 1000, 1.0, value);
+            match expect {
+                Some(index) => assert_eq!(index, rule.locate_partition(&row).unwrap()),
+                None => assert!(rule.locate_partition(&row).is_err()),
+            }
+        }
+
+        // Duplicate values.
+        let partition_info = list_partition_info(vec![vec![string("a")], vec![string("a")]]);
+        assert!(PartitionRule::new(&partition_info, &schema).is_err());
+        // Empty values.
+        let partition_info = list_partition_info(vec![vec![string("a")], vec![]]);
+        assert!(PartitionRule::new(&partition_info, &schema).is_err());
+    }
+}
@@ -271,7 +271,8 @@ impl PredicateBuilder {
             return false;
         }
 
-        columns.len() == 1 && Self::is_primitive_binary_expr(expr)
+        columns.len() == 1
+            && (Self::is_primitive_binary_expr(expr) || Self::is_primitive_in_list_expr(expr))
     }
 
     /// Recursively split all "AND" expressions into smaller one
@@ -311,6 +312,63 @@ impl PredicateBuilder {
             _ => false,
         }
     }
+
+    /// Return true if the given expression is in the form: `column IN
+    /// (constants)`.
+    fn is_primitive_in_list_expr(expr: &Expr) -> bool {
+        match expr {
+            Expr::InList {
+                expr,
+                list,
+                negated: false,
+            } => {
+                matches!(&**expr, Expr::Column(_))
+                    && list.iter().all(|v| matches!(v, Expr::Literal(_)))
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Extract the column name and literals from the `column = literal` or
+/// `column IN (literals)` expression.
+///
+/// These exprs can be answered by the filters and indexes built on the exact
+/// values of a column, e.g. the bloom filters and inverted index of the tag
+/// columns in the sst.
+pub fn extract_column_literals(expr: &Expr) -> Option<(&str, Vec<&ScalarValue>)> {
+    match expr {
+        Expr::BinaryExpr {
+            left,
+            op: Operator::Eq,
+            right,
+        } => match (left.as_ref(), right.as_ref()) {
+            (Expr::Column(column), Expr::Literal(v)) | (Expr::Literal(v), Expr::Column(column)) => {
+                Some((&column.name, vec![v]))
+            }
+            _ => None,
+        },
+        Expr::InList {
+            expr,
+            list,
+            negated: false,
+        } => {
+            let column = match expr.as_ref() {
+                Expr::Column(v) => v,
+                _ => return None,
+            };
+            let literals = list
+                .iter()
+                .map(|v| match v {
+                    Expr::Literal(v) => Some(v),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>()?;
+
+            Some((&column.name, literals))
+        }
+        _ => None,
+    }
 }
 
 struct TimeRangeExtractor<'a> {
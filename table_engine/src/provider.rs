@@ -2,10 +2,22 @@
 
 //! Datafusion `TableProvider` adapter
 
-use std::{any::Any, fmt, sync::Arc};
+use std::{
+    any::Any,
+    fmt,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+};
 
 use arrow_deps::{
-    arrow::datatypes::SchemaRef,
+    arrow::{
+        datatypes::SchemaRef, error::Result as ArrowResult,
+        record_batch::RecordBatch as ArrowRecordBatch,
+    },
     datafusion::{
         datasource::datasource::{TableProvider, TableProviderFilterPushDown},
         error::{DataFusionError, Result},
@@ -13,12 +25,14 @@ use arrow_deps::{
         logical_plan::Expr,
         physical_plan::{
             DisplayFormatType, ExecutionPlan, Partitioning,
+            RecordBatchStream as DfRecordBatchStream,
             SendableRecordBatchStream as DfSendableRecordBatchStream, Statistics,
         },
     },
 };
 use async_trait::async_trait;
 use common_types::{projected_schema::ProjectedSchema, request_id::RequestId, schema::Schema};
+use futures::stream::Stream;
 use log::debug;
 use tokio::sync::Mutex;
 
@@ -41,6 +55,8 @@ pub struct TableProviderAdapter {
     read_schema: Schema,
     request_id: RequestId,
     read_parallelism: usize,
+    /// Rows scanned from the table by all the scans of this adapter.
+    scanned_rows: Arc<AtomicUsize>,
 }
 
 impl TableProviderAdapter {
@@ -53,6 +69,7 @@ impl TableProviderAdapter {
             read_schema,
             request_id,
             read_parallelism,
+            scanned_rows: Arc::new(AtomicUsize::new(0)),
         }
     }
 
@@ -60,6 +77,11 @@ impl TableProviderAdapter {
         &self.table
     }
 
+    /// Returns the rows scanned from the table so far.
+    pub fn scanned_rows(&self) -> usize {
+        self.scanned_rows.load(Ordering::Relaxed)
+    }
+
     pub fn scan_table(
         &self,
         projection: &Option<Vec<usize>>,
@@ -98,6 +120,7 @@ impl TableProviderAdapter {
             read_order,
             read_parallelism,
             predicate,
+            scanned_rows: self.scanned_rows.clone(),
             stream_state: Mutex::new(ScanStreamState::default()),
         }))
     }
@@ -169,6 +192,7 @@ struct ScanTable {
     read_order: ReadOrder,
     read_parallelism: usize,
     predicate: PredicateRef,
+    scanned_rows: Arc<AtomicUsize>,
 
     stream_state: Mutex<ScanStreamState>,
 }
@@ -243,7 +267,10 @@ impl ExecutionPlan for ScanTable {
         let mut stream_state = self.stream_state.lock().await;
         let stream = stream_state.take_stream(partition)?;
 
-        Ok(Box::pin(ToDfStream(stream)))
+        Ok(Box::pin(CountRowsStream {
+            stream: ToDfStream(stream),
+            scanned_rows: self.scanned_rows.clone(),
+        }))
     }
 
     fn fmt_as(&self, _t: DisplayFormatType, f: &mut fmt::Formatter) -> fmt::Result {
@@ -273,3 +300,28 @@ impl fmt::Debug for ScanTable {
             .finish()
     }
 }
+
+/// Stream counting the rows scanned from the table.
+struct CountRowsStream {
+    stream: ToDfStream,
+    scanned_rows: Arc<AtomicUsize>,
+}
+
+impl Stream for CountRowsStream {
+    type Item = ArrowResult<ArrowRecordBatch>;
+
+    fn poll_next(mut self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let poll = Pin::new(&mut self.stream).poll_next(ctx);
+        if let Poll::Ready(Some(Ok(record_batch))) = &poll {
+            self.scanned_rows
+                .fetch_add(record_batch.num_rows(), Ordering::Relaxed);
+        }
+        poll
+    }
+}
+
+impl DfRecordBatchStream for CountRowsStream {
+    fn schema(&self) -> SchemaRef {
+        self.stream.schema()
+    }
+}
@@ -0,0 +1,532 @@
+// Copyright 2022 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Rollup definitions
+//!
+//! A rollup aggregates the rows of a source table into time buckets of a fixed
+//! period, grouped by some tag columns of the source table. The aggregated
+//! rows are written into a target table as partial aggregates, so they can be
+//! merged again by queries with coarser time buckets:
+//! - `avg(v)` is stored as `v_sum` and `v_count`
+//! - `sum(v)` is stored as `v_sum`
+//! - `count(v)` is stored as `v_count`
+//! - `min(v)` is stored as `v_min`
+//! - `max(v)` is stored as `v_max`
+
+use std::{fmt, time::Duration};
+
+use proto::common as common_pb;
+use snafu::{ensure, Backtrace, OptionExt, ResultExt, Snafu};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display(
+        "Invalid rollup period, only seconds, minutes and hours are supported, period:{}.\nBacktrace:\n{}",
+        period,
+        backtrace
+    ))]
+    InvalidPeriod {
+        period: String,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("Invalid number of rollup period, period:{}, err:{}", period, source))]
+    InvalidPeriodNumber {
+        period: String,
+        source: std::num::ParseIntError,
+    },
+
+    #[snafu(display(
+        "Unsupported aggregate function of rollup, name:{}.\nBacktrace:\n{}",
+        name,
+        backtrace
+    ))]
+    UnsupportedAggregate { name: String, backtrace: Backtrace },
+
+    #[snafu(display("Invalid rollup, rollup:{}.\nBacktrace:\n{}", rollup, backtrace))]
+    InvalidRollup {
+        rollup: String,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("Invalid name in rollup, name:{}.\nBacktrace:\n{}", name, backtrace))]
+    InvalidName { name: String, backtrace: Backtrace },
+
+    #[snafu(display("Rollup requires at least one aggregate.\nBacktrace:\n{}", backtrace))]
+    EmptyAggregates { backtrace: Backtrace },
+}
+
+define_result!(Error);
+
+/// Separator of rollups in the table option.
+const ROLLUP_SEPARATOR: char = ';';
+/// Separator of the parts of a rollup in the table option.
+const PART_SEPARATOR: char = ':';
+/// Separator of the tags or aggregates of a rollup in the table option.
+const LIST_SEPARATOR: char = ',';
+/// Chars not allowed in the names of a rollup.
+const RESERVED_CHARS: [char; 5] = [ROLLUP_SEPARATOR, PART_SEPARATOR, LIST_SEPARATOR, '(', ')'];
+
+const SECOND_MILLIS: u64 = 1000;
+const MINUTE_MILLIS: u64 = 60 * SECOND_MILLIS;
+const HOUR_MILLIS: u64 = 60 * MINUTE_MILLIS;
+
+/// Aggregate functions supported by rollup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AggregateFunction {
+    Avg,
+    Sum,
+    Count,
+    Min,
+    Max,
+}
+
+impl AggregateFunction {
+    pub fn parse_from(name: &str) -> Result<Self> {
+        let function = match name.to_lowercase().as_str() {
+            "avg" => AggregateFunction::Avg,
+            "sum" => AggregateFunction::Sum,
+            "count" => AggregateFunction::Count,
+            "min" => AggregateFunction::Min,
+            "max" => AggregateFunction::Max,
+            _ => return UnsupportedAggregate { name }.fail(),
+        };
+
+        Ok(function)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AggregateFunction::Avg => "avg",
+            AggregateFunction::Sum => "sum",
+            AggregateFunction::Count => "count",
+            AggregateFunction::Min => "min",
+            AggregateFunction::Max => "max",
+        }
+    }
+
+    /// Partial aggregates required to compute this function.
+    pub fn partials(&self) -> &'static [PartialAggregate] {
+        match self {
+            AggregateFunction::Avg => &[PartialAggregate::Sum, PartialAggregate::Count],
+            AggregateFunction::Sum => &[PartialAggregate::Sum],
+            AggregateFunction::Count => &[PartialAggregate::Count],
+            AggregateFunction::Min => &[PartialAggregate::Min],
+            AggregateFunction::Max => &[PartialAggregate::Max],
+        }
+    }
+}
+
+impl From<AggregateFunction> for common_pb::AggregateFunction {
+    fn from(function: AggregateFunction) -> Self {
+        match function {
+            AggregateFunction::Avg => common_pb::AggregateFunction::AVG,
+            AggregateFunction::Sum => common_pb::AggregateFunction::SUM,
+            AggregateFunction::Count => common_pb::AggregateFunction::COUNT,
+            AggregateFunction::Min => common_pb::AggregateFunction::MIN,
+            AggregateFunction::Max => common_pb::AggregateFunction::MAX,
+        }
+    }
+}
+
+impl From<common_pb::AggregateFunction> for AggregateFunction {
+    fn from(function: common_pb::AggregateFunction) -> Self {
+        match function {
+            common_pb::AggregateFunction::AVG => AggregateFunction::Avg,
+            common_pb::AggregateFunction::SUM => AggregateFunction::Sum,
+            common_pb::AggregateFunction::COUNT => AggregateFunction::Count,
+            common_pb::AggregateFunction::MIN => AggregateFunction::Min,
+            common_pb::AggregateFunction::MAX => AggregateFunction::Max,
+        }
+    }
+}
+
+/// Partial aggregate stored in the target table of rollup.
+///
+/// Sum, min and max are stored as double and count is stored as uint64.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartialAggregate {
+    Sum,
+    Count,
+    Min,
+    Max,
+}
+
+impl PartialAggregate {
+    /// Name of the column to store this partial aggregate of `column`.
+    pub fn column_name(&self, column: &str) -> String {
+        let suffix = match self {
+            PartialAggregate::Sum => "sum",
+            PartialAggregate::Count => "count",
+            PartialAggregate::Min => "min",
+            PartialAggregate::Max => "max",
+        };
+
+        format!("{}_{}", column, suffix)
+    }
+}
+
+/// Aggregate of a field column in the rollup.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RollupAggregate {
+    pub function: AggregateFunction,
+    /// Name of the aggregated column in the source table.
+    pub column: String,
+}
+
+impl fmt::Display for RollupAggregate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}({})", self.function.as_str(), self.column)
+    }
+}
+
+/// Partial aggregate column in the target table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartialColumn {
+    /// Name of the column in the target table.
+    pub name: String,
+    /// Name of the aggregated column in the source table.
+    pub column: String,
+    pub partial: PartialAggregate,
+}
+
+/// Definition of a rollup.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rollup {
+    /// Name of the target table.
+    pub table: String,
+    /// Period of the time bucket.
+    pub period: Duration,
+    /// Tag columns of the source table to group by.
+    pub tags: Vec<String>,
+    pub aggregates: Vec<RollupAggregate>,
+}
+
+impl Rollup {
+    /// Partial aggregate columns of the target table, in the order of the
+    /// aggregates.
+    pub fn partial_columns(&self) -> Vec<PartialColumn> {
+        let mut columns: Vec<PartialColumn> = Vec::with_capacity(self.aggregates.len() * 2);
+        for aggregate in &self.aggregates {
+            for partial in aggregate.function.partials() {
+                let name = partial.column_name(&aggregate.column);
+                if columns.iter().all(|column| column.name != name) {
+                    columns.push(PartialColumn {
+                        name,
+                        column: aggregate.column.clone(),
+                        partial: *partial,
+                    });
+                }
+            }
+        }
+
+        columns
+    }
+
+    /// Returns the name of the column storing the `partial` aggregate of
+    /// `column`, or `None` if the rollup doesn't maintain it.
+    pub fn partial_column(&self, column: &str, partial: PartialAggregate) -> Option<String> {
+        self.aggregates
+            .iter()
+            .filter(|aggregate| aggregate.column == column)
+            .any(|aggregate| aggregate.function.partials().contains(&partial))
+            .then(|| partial.column_name(column))
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        ensure!(!self.aggregates.is_empty(), EmptyAggregates);
+        validate_period(self.period)?;
+
+        let names = std::iter::once(&self.table)
+            .chain(self.tags.iter())
+            .chain(self.aggregates.iter().map(|aggregate| &aggregate.column));
+        for name in names {
+            ensure!(
+                !name.is_empty() && !name.contains(&RESERVED_CHARS[..]),
+                InvalidName { name }
+            );
+        }
+
+        Ok(())
+    }
+
+    fn parse_from(s: &str) -> Result<Self> {
+        let parts: Vec<_> = s.split(PART_SEPARATOR).collect();
+        ensure!(parts.len() == 4, InvalidRollup { rollup: s });
+
+        let aggregates = split_list(parts[3])
+            .map(|v| {
+                parse_aggregate(v)
+                    .context(InvalidRollup { rollup: s })
+                    .and_then(|v| v)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let rollup = Rollup {
+            table: parts[0].to_string(),
+            period: parse_period(parts[1])?,
+            tags: split_list(parts[2]).map(str::to_string).collect(),
+            aggregates,
+        };
+        rollup.validate()?;
+
+        Ok(rollup)
+    }
+}
+
+/// The rollup is formatted as `table:period:tags:aggregates`, e.g.
+/// `cpu_1m:PT1M:host,region:avg(usage),max(usage)`.
+impl fmt::Display for Rollup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let aggregates: Vec<_> = self.aggregates.iter().map(|v| v.to_string()).collect();
+        write!(
+            f,
+            "{table}{sep}{period}{sep}{tags}{sep}{aggregates}",
+            table = self.table,
+            period = format_period(self.period),
+            tags = self.tags.join(&LIST_SEPARATOR.to_string()),
+            aggregates = aggregates.join(&LIST_SEPARATOR.to_string()),
+            sep = PART_SEPARATOR,
+        )
+    }
+}
+
+impl From<Rollup> for common_pb::Rollup {
+    fn from(rollup: Rollup) -> Self {
+        let aggregates: Vec<_> = rollup
+            .aggregates
+            .into_iter()
+            .map(|aggregate| {
+                let mut aggregate_pb = common_pb::RollupAggregate::new();
+                aggregate_pb.set_function(aggregate.function.into());
+                aggregate_pb.set_column(aggregate.column);
+                aggregate_pb
+            })
+            .collect();
+
+        let mut target = common_pb::Rollup::new();
+        target.set_table(rollup.table);
+        target.set_period(rollup.period.as_millis() as u64);
+        target.set_tags(rollup.tags.into());
+        target.set_aggregates(aggregates.into());
+
+        target
+    }
+}
+
+impl From<common_pb::Rollup> for Rollup {
+    fn from(mut rollup: common_pb::Rollup) -> Self {
+        let aggregates = rollup
+            .take_aggregates()
+            .into_iter()
+            .map(|mut aggregate| RollupAggregate {
+                function: aggregate.get_function().into(),
+                column: aggregate.take_column(),
+            })
+            .collect();
+
+        Self {
+            table: rollup.take_table(),
+            period: Duration::from_millis(rollup.get_period()),
+            tags: rollup.take_tags().into_vec(),
+            aggregates,
+        }
+    }
+}
+
+/// Parse the rollups from the value of table option
+/// [OPTION_KEY_ROLLUPS](crate::OPTION_KEY_ROLLUPS).
+pub fn parse_rollups(s: &str) -> Result<Vec<Rollup>> {
+    s.split(ROLLUP_SEPARATOR)
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(Rollup::parse_from)
+        .collect()
+}
+
+/// Format the rollups as the value of table option
+/// [OPTION_KEY_ROLLUPS](crate::OPTION_KEY_ROLLUPS).
+pub fn format_rollups(rollups: &[Rollup]) -> String {
+    let rollups: Vec<_> = rollups.iter().map(|v| v.to_string()).collect();
+    rollups.join(&ROLLUP_SEPARATOR.to_string())
+}
+
+/// Parse the period of rollup, both the ISO 8601 format used by `time_bucket`
+/// (e.g. `PT1M`) and the short format (e.g. `1m`) are supported.
+///
+/// Only periods of seconds, minutes and hours are supported because the time
+/// buckets of them are aligned to the unix epoch.
+pub fn parse_period(period: &str) -> Result<Duration> {
+    let number_unit = if let Some(v) = period.strip_prefix("PT") {
+        v
+    } else {
+        ensure!(
+            period
+                .chars()
+                .all(|c| c.is_ascii_digit() || c.is_lowercase()),
+            InvalidPeriod { period }
+        );
+        period
+    };
+
+    let unit = number_unit
+        .chars()
+        .last()
+        .context(InvalidPeriod { period })?;
+    let unit_millis = match unit.to_ascii_uppercase() {
+        'S' => SECOND_MILLIS,
+        'M' => MINUTE_MILLIS,
+        'H' => HOUR_MILLIS,
+        _ => return InvalidPeriod { period }.fail(),
+    };
+    let number = number_unit[..number_unit.len() - 1]
+        .parse::<u64>()
+        .context(InvalidPeriodNumber { period })?;
+    let duration = Duration::from_millis(number * unit_millis);
+    validate_period(duration)?;
+
+    Ok(duration)
+}
+
+/// Format the period in the ISO 8601 format used by `time_bucket`.
+pub fn format_period(period: Duration) -> String {
+    let millis = period.as_millis() as u64;
+    if millis % HOUR_MILLIS == 0 {
+        format!("PT{}H", millis / HOUR_MILLIS)
+    } else if millis % MINUTE_MILLIS == 0 {
+        format!("PT{}M", millis / MINUTE_MILLIS)
+    } else {
+        format!("PT{}S", millis / SECOND_MILLIS)
+    }
+}
+
+fn validate_period(period: Duration) -> Result<()> {
+    let millis = period.as_millis() as u64;
+    ensure!(
+        millis > 0 && millis % SECOND_MILLIS == 0,
+        InvalidPeriod {
+            period: format!("{:?}", period),
+        }
+    );
+
+    Ok(())
+}
+
+fn split_list(s: &str) -> impl Iterator<Item = &str> {
+    s.split(LIST_SEPARATOR)
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+}
+
+/// Parse aggregate like `avg(value)`.
+fn parse_aggregate(s: &str) -> Option<Result<RollupAggregate>> {
+    let (name, rest) = s.split_once('(')?;
+    let column = rest.strip_suffix(')')?;
+
+    Some(
+        AggregateFunction::parse_from(name.trim()).map(|function| RollupAggregate {
+            function,
+            column: column.trim().to_string(),
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_rollup() -> Rollup {
+        Rollup {
+            table: "cpu_1m".to_string(),
+            period: Duration::from_secs(60),
+            tags: vec!["host".to_string(), "region".to_string()],
+            aggregates: vec![
+                RollupAggregate {
+                    function: AggregateFunction::Avg,
+                    column: "usage".to_string(),
+                },
+                RollupAggregate {
+                    function: AggregateFunction::Sum,
+                    column: "usage".to_string(),
+                },
+                RollupAggregate {
+                    function: AggregateFunction::Max,
+                    column: "load".to_string(),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_parse_period() {
+        let cases = [
+            ("PT1S", 1),
+            ("PT5M", 5 * 60),
+            ("PT1H", 3600),
+            ("10s", 10),
+            ("1m", 60),
+            ("2h", 2 * 3600),
+        ];
+        for (period, secs) in cases {
+            assert_eq!(Duration::from_secs(secs), parse_period(period).unwrap());
+        }
+
+        for period in ["P1D", "PT0M", "1d", "1M", "m", "PTxM", ""] {
+            assert!(parse_period(period).is_err(), "period:{}", period);
+        }
+
+        assert_eq!("PT30S", format_period(Duration::from_secs(30)));
+        assert_eq!("PT90M", format_period(Duration::from_secs(90 * 60)));
+        assert_eq!("PT24H", format_period(Duration::from_secs(24 * 3600)));
+    }
+
+    #[test]
+    fn test_partial_columns() {
+        let rollup = build_rollup();
+        let names: Vec<_> = rollup
+            .partial_columns()
+            .into_iter()
+            .map(|column| column.name)
+            .collect();
+        assert_eq!(vec!["usage_sum", "usage_count", "load_max"], names);
+
+        assert_eq!(
+            Some("usage_count".to_string()),
+            rollup.partial_column("usage", PartialAggregate::Count)
+        );
+        assert_eq!(None, rollup.partial_column("load", PartialAggregate::Min));
+    }
+
+    #[test]
+    fn test_format_then_parse() {
+        let rollup = build_rollup();
+        let mut other = build_rollup();
+        other.table = "cpu_1h".to_string();
+        other.period = Duration::from_secs(3600);
+        other.tags.clear();
+        let rollups = vec![rollup, other];
+
+        let formatted = format_rollups(&rollups);
+        assert_eq!(
+            "cpu_1m:PT1M:host,region:avg(usage),sum(usage),max(load);cpu_1h:PT1H::avg(usage),sum(usage),max(load)",
+            formatted
+        );
+        assert_eq!(rollups, parse_rollups(&formatted).unwrap());
+        assert!(parse_rollups("").unwrap().is_empty());
+
+        for invalid in [
+            "cpu_1m:PT1M:host",
+            "cpu_1m:PT1M:host:",
+            "cpu_1m:P1D:host:avg(usage)",
+            "cpu_1m:PT1M:host:median(usage)",
+            "cpu_1m:PT1M:host:usage",
+        ] {
+            assert!(parse_rollups(invalid).is_err(), "rollup:{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_rollup_pb() {
+        let rollup = build_rollup();
+        let pb = common_pb::Rollup::from(rollup.clone());
+        assert_eq!(rollup, Rollup::from(pb));
+    }
+}
@@ -20,14 +20,16 @@ use common_types::{
     request_id::RequestId,
     row::{Row, RowGroup},
     schema::{RecordSchemaWithKey, Schema, Version},
-    time::Timestamp,
+    time::{TimeRange, Timestamp},
 };
+use object_store::ObjectStoreRef;
 use proto::sys_catalog::{TableEntry, TableState as TableStatePb};
 use serde_derive::Deserialize;
 use snafu::{Backtrace, Snafu};
 
 use crate::{
     engine::{TableRequestType, TableState},
+    partition::PartitionInfo,
     predicate::PredicateRef,
     stream::{PartitionedStreams, SendableRecordBatchStream},
 };
@@ -90,6 +92,12 @@ pub enum Error {
         source: Box<dyn std::error::Error + Send + Sync>,
     },
 
+    #[snafu(display("Failed to delete from table, table:{}, err:{}", table, source))]
+    Delete {
+        table: String,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+
     #[snafu(display("Failed to scan table, table:{}, err:{}", table, source))]
     Scan {
         table: String,
@@ -125,6 +133,18 @@ pub enum Error {
         table: String,
         source: Box<dyn std::error::Error + Send + Sync>,
     },
+
+    #[snafu(display("Failed to snapshot table, table:{}, err:{}", table, source))]
+    Snapshot {
+        table: String,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+
+    #[snafu(display("Failed to restore table, table:{}, err:{}", table, source))]
+    Restore {
+        table: String,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
 }
 
 define_result!(Error);
@@ -294,13 +314,26 @@ impl fmt::Display for TableId {
     }
 }
 
-// TODO(yingwen): Support DELETE/UPDATE... , a mutation type is needed.
+// TODO(yingwen): Support UPDATE... , a mutation type is needed.
 #[derive(Debug)]
 pub struct WriteRequest {
     /// rows to write
     pub row_group: RowGroup,
 }
 
+/// Request to delete rows from the table.
+///
+/// A series is identified by the values of the primary key columns except the
+/// timestamp column.
+#[derive(Debug)]
+pub struct DeleteRequest {
+    /// Only rows in this time range are deleted.
+    pub time_range: TimeRange,
+    /// Series to delete, each key contains the values of the series columns
+    /// in the order of the schema. `None` means all series.
+    pub series_keys: Option<Vec<Vec<Datum>>>,
+}
+
 #[derive(Debug)]
 pub struct ReadOptions {
     pub batch_size: usize,
@@ -402,6 +435,48 @@ impl Default for FlushRequest {
     }
 }
 
+/// Request to snapshot the table into the object store.
+#[derive(Clone)]
+pub struct SnapshotRequest {
+    /// The object store to write the snapshot to.
+    pub store: ObjectStoreRef,
+    /// Path prefix of the snapshot in the store, e.g. `backup/20220601/t1`.
+    pub prefix: String,
+}
+
+impl fmt::Debug for SnapshotRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SnapshotRequest")
+            .field("prefix", &self.prefix)
+            .finish()
+    }
+}
+
+/// Request to restore the table from a snapshot in the object store.
+#[derive(Clone)]
+pub struct RestoreRequest {
+    /// The object store to read the snapshot from.
+    pub store: ObjectStoreRef,
+    /// Path prefix of the snapshot in the store.
+    pub prefix: String,
+}
+
+impl fmt::Debug for RestoreRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RestoreRequest")
+            .field("prefix", &self.prefix)
+            .finish()
+    }
+}
+
+/// Statistics of the files in a snapshot.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SnapshotStats {
+    pub num_files: usize,
+    pub num_bytes: u64,
+    pub num_rows: u64,
+}
+
 /// Table abstraction
 ///
 /// We do not let Table trait extends datafusion's TableProvider, since
@@ -421,6 +496,10 @@ pub trait Table: std::fmt::Debug {
     /// Options of this table.
     fn options(&self) -> HashMap<String, String>;
 
+    /// Partition info of this table, returns `None` if the table is not
+    /// partitioned.
+    fn partition_info(&self) -> Option<PartitionInfo>;
+
     /// Engine type of this table.
     fn engine_type(&self) -> &str;
 
@@ -430,6 +509,9 @@ pub trait Table: std::fmt::Debug {
     /// Write to table.
     async fn write(&self, request: WriteRequest) -> Result<usize>;
 
+    /// Delete rows from table.
+    async fn delete(&self, request: DeleteRequest) -> Result<()>;
+
     /// Read from table.
     async fn read(&self, request: ReadRequest) -> Result<SendableRecordBatchStream>;
 
@@ -458,6 +540,14 @@ pub trait Table: std::fmt::Debug {
 
     /// Compact this table and wait until compaction completes.
     async fn compact(&self) -> Result<()>;
+
+    /// Flush this table and copy all its sst files together with a manifest
+    /// snapshot to the store in [SnapshotRequest].
+    async fn snapshot(&self, request: SnapshotRequest) -> Result<SnapshotStats>;
+
+    /// Restore the snapshot in [RestoreRequest] into this table, the table
+    /// must be empty and have a compatible schema.
+    async fn restore(&self, request: RestoreRequest) -> Result<SnapshotStats>;
 }
 
 /// Basic statistics of table.
@@ -469,6 +559,9 @@ pub struct TableStats {
     pub num_read: u64,
     /// Total flush request
     pub num_flush: u64,
+    /// Rows before this timestamp are aggregated into the rollups of the
+    /// table, None if the table has no rollup.
+    pub rollup_watermark: Option<Timestamp>,
 }
 
 /// A reference-counted pointer to Table
@@ -0,0 +1,779 @@
+// Copyright 2022 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! WalManager implementation based on segmented append-only files
+
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    fmt,
+    fmt::Formatter,
+    fs,
+    io::ErrorKind,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, RwLock, Weak,
+    },
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use common_types::{SequenceNumber, MIN_SEQUENCE_NUMBER};
+use common_util::runtime::Runtime;
+use log::{debug, info, warn};
+use snafu::ResultExt;
+
+use crate::{
+    file_impl::segment::{self, RegionMeta, SegmentReader, SegmentWriter},
+    log_batch::{LogEntry, LogWriteBatch, Payload, PayloadDecoder},
+    manager::{
+        error::*, LogIterator, LogReader, LogWriter, ReadContext, ReadRequest, RegionId,
+        WalManager, WriteContext,
+    },
+};
+
+/// Default max size of a segment, a new segment will be created once the
+/// size of the current segment exceeds it.
+pub const DEFAULT_SEGMENT_SIZE: usize = 64 * 1024 * 1024;
+/// Default interval to sync the segments if they are not synced on every
+/// write.
+pub const DEFAULT_SYNC_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy)]
+struct Options {
+    segment_size: u64,
+    sync_on_write: bool,
+    sync_interval: Duration,
+}
+
+/// Mutable states of the segment being written.
+struct WriterState {
+    writer: SegmentWriter,
+    next_sequence_num: SequenceNumber,
+    /// Whether some records are appended after the last sync
+    dirty: bool,
+    last_sync: Instant,
+}
+
+impl WriterState {
+    fn sync(&mut self) -> Result<()> {
+        self.writer
+            .sync()
+            .map_err(|e| Box::new(e) as _)
+            .context(Write)?;
+        self.dirty = false;
+        self.last_sync = Instant::now();
+
+        Ok(())
+    }
+}
+
+/// Shared part of the [Region] which may be accessed in the blocking
+/// threads.
+struct RegionInner {
+    /// Directory of the region
+    dir: PathBuf,
+    options: Options,
+    /// Max sequence number of the entries written and synced, entries with
+    /// larger sequence number are invisible to the readers
+    last_sequence_num: AtomicU64,
+    /// Entries whose sequence number is not greater than it are deleted
+    deleted_sequence_num: AtomicU64,
+    /// All the segments of the region: first sequence number -> segment path.
+    ///
+    /// The last segment is the one being written.
+    segments: RwLock<BTreeMap<SequenceNumber, PathBuf>>,
+    /// Ensure the writes to be sequential
+    writer_state: Mutex<WriterState>,
+}
+
+impl RegionInner {
+    fn open(dir: PathBuf, options: Options) -> Result<Self> {
+        fs::create_dir_all(&dir)
+            .map_err(|e| Box::new(e) as _)
+            .context(Initialization)?;
+
+        let meta = RegionMeta::load(&dir)
+            .map_err(|e| Box::new(e) as _)
+            .context(Initialization)?;
+        let mut segments: BTreeMap<_, _> = segment::list_segments(&dir)
+            .map_err(|e| Box::new(e) as _)
+            .context(Initialization)?
+            .into_iter()
+            .collect();
+
+        let last_segment = segments
+            .iter()
+            .next_back()
+            .map(|(first_sequence, path)| (*first_sequence, path.clone()));
+        let (writer, last_sequence_num) = match last_segment {
+            Some((first_sequence, path)) => {
+                // Only the last segment may contain the torn records.
+                let (valid_size, last_sequence) = segment::scan_segment(&path)
+                    .map_err(|e| Box::new(e) as _)
+                    .context(Initialization)?;
+                let writer = SegmentWriter::open(path, valid_size)
+                    .map_err(|e| Box::new(e) as _)
+                    .context(Initialization)?;
+                let last_sequence =
+                    last_sequence.unwrap_or_else(|| first_sequence.saturating_sub(1));
+
+                (writer, last_sequence.max(meta.max_sequence))
+            }
+            None => {
+                let first_sequence = meta.max_sequence + 1;
+                let path = dir.join(segment::segment_file_name(first_sequence));
+                let writer = SegmentWriter::create(path.clone())
+                    .map_err(|e| Box::new(e) as _)
+                    .context(Initialization)?;
+                segments.insert(first_sequence, path);
+
+                (writer, meta.max_sequence)
+            }
+        };
+
+        Ok(Self {
+            dir,
+            options,
+            last_sequence_num: AtomicU64::new(last_sequence_num),
+            deleted_sequence_num: AtomicU64::new(meta.deleted_sequence),
+            segments: RwLock::new(segments),
+            writer_state: Mutex::new(WriterState {
+                writer,
+                next_sequence_num: last_sequence_num + 1,
+                dirty: false,
+                last_sync: Instant::now(),
+            }),
+        })
+    }
+
+    /// Append the encoded `payloads` and returns the max sequence number of
+    /// them.
+    ///
+    /// All the payloads are written into the same segment and the segment is
+    /// synced only once for them if `sync_on_write` is set. Otherwise the
+    /// segment is synced if it hasn't been synced for the `sync_interval`, so
+    /// the writes during the interval are synced as a group.
+    fn append(&self, payloads: &[Vec<u8>]) -> Result<SequenceNumber> {
+        let mut state = self.writer_state.lock().unwrap();
+        if state.writer.size() >= self.options.segment_size {
+            self.rotate(&mut state)?;
+        }
+
+        let mut buf = Vec::new();
+        let mut next_sequence_num = state.next_sequence_num;
+        for payload in payloads {
+            segment::encode_record(&mut buf, next_sequence_num, payload)
+                .map_err(|e| Box::new(e) as _)
+                .context(Write)?;
+            next_sequence_num += 1;
+        }
+
+        state
+            .writer
+            .append(&buf)
+            .map_err(|e| Box::new(e) as _)
+            .context(Write)?;
+        state.dirty = true;
+        if self.options.sync_on_write || state.last_sync.elapsed() >= self.options.sync_interval {
+            state.sync()?;
+        }
+
+        let max_sequence_num = next_sequence_num - 1;
+        state.next_sequence_num = next_sequence_num;
+        self.last_sequence_num
+            .store(max_sequence_num, Ordering::Release);
+
+        Ok(max_sequence_num)
+    }
+
+    /// Sync the segment being written if it has records not synced for the
+    /// `sync_interval`.
+    fn sync_if_elapsed(&self) -> Result<()> {
+        let mut state = self.writer_state.lock().unwrap();
+        if state.dirty && state.last_sync.elapsed() >= self.options.sync_interval {
+            state.sync()?;
+        }
+
+        Ok(())
+    }
+
+    /// Seal the current segment and start a new one.
+    fn rotate(&self, state: &mut WriterState) -> Result<()> {
+        state.sync()?;
+
+        let first_sequence = state.next_sequence_num;
+        let path = self.dir.join(segment::segment_file_name(first_sequence));
+        debug!("Wal region rotate segment, new segment:{:?}", path);
+
+        state.writer = SegmentWriter::create(path.clone())
+            .map_err(|e| Box::new(e) as _)
+            .context(Write)?;
+        self.segments.write().unwrap().insert(first_sequence, path);
+
+        Ok(())
+    }
+
+    /// Mark the entries in the range `[0, sequence_num]` deleted and remove
+    /// the segments whose entries are all deleted.
+    ///
+    /// The segment being written is never removed.
+    fn delete_entries_up_to(&self, mut sequence_num: SequenceNumber) -> Result<()> {
+        let max_seq = self.last_sequence_num.load(Ordering::Acquire);
+        if sequence_num > max_seq {
+            warn!(
+                "Try to delete entries up to sequence number({}) greater than current max sequence \
+                number({})",
+                sequence_num,
+                max_seq
+            );
+            sequence_num = max_seq;
+        }
+        if sequence_num <= self.deleted_sequence_num.load(Ordering::Acquire) {
+            return Ok(());
+        }
+
+        let meta = RegionMeta {
+            max_sequence: max_seq,
+            deleted_sequence: sequence_num,
+        };
+        meta.store(&self.dir)
+            .map_err(|e| Box::new(e) as _)
+            .context(Delete)?;
+        self.deleted_sequence_num
+            .store(sequence_num, Ordering::Release);
+
+        let removed_segments = {
+            let mut segments = self.segments.write().unwrap();
+            // A segment can be removed if all the entries before the next segment are
+            // deleted.
+            let removable: Vec<_> = segments
+                .keys()
+                .zip(segments.keys().skip(1))
+                .take_while(|(_, next_first)| **next_first - 1 <= sequence_num)
+                .map(|(first, _)| *first)
+                .collect();
+
+            removable
+                .into_iter()
+                .filter_map(|first| segments.remove(&first))
+                .collect::<Vec<_>>()
+        };
+
+        for path in removed_segments {
+            debug!("Wal region remove segment, segment:{:?}", path);
+
+            if let Err(e) = fs::remove_file(&path) {
+                if e.kind() != ErrorKind::NotFound {
+                    return Err(Box::new(e) as _).context(Delete);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for RegionInner {
+    fn drop(&mut self) {
+        let state = match self.writer_state.get_mut() {
+            Ok(v) => v,
+            Err(_) => return,
+        };
+        if state.dirty {
+            if let Err(e) = state.sync() {
+                warn!(
+                    "Wal region failed to sync segment on drop, dir:{:?}, err:{}",
+                    self.dir, e
+                );
+            }
+        }
+    }
+}
+
+/// Region in the Wal.
+struct Region {
+    /// id of the Region
+    id: RegionId,
+    inner: Arc<RegionInner>,
+    /// Runtime for write requests
+    runtime: Arc<Runtime>,
+    /// Ensure the delete procedure to be sequential
+    delete_lock: tokio::sync::Mutex<()>,
+}
+
+impl Region {
+    /// Returns the current sequence number.
+    fn sequence_num(&self) -> Result<u64> {
+        Ok(self.inner.last_sequence_num.load(Ordering::Acquire))
+    }
+
+    /// Delete entries in the range `[0, sequence_num]`.
+    ///
+    /// The delete procedure is ensured to be sequential.
+    async fn delete_entries_up_to(&self, sequence_num: SequenceNumber) -> Result<()> {
+        debug!(
+            "Wal Region delete entries begin deleting, region_id:{}, sequence_num:{:?}",
+            self.id, sequence_num
+        );
+
+        let _delete_guard = self.delete_lock.lock().await;
+        let inner = self.inner.clone();
+        self.runtime
+            .spawn_blocking(move || inner.delete_entries_up_to(sequence_num))
+            .await
+            .map_err(|e| Box::new(e) as _)
+            .context(Delete)?
+    }
+
+    /// Sync the segment being written if it has records not synced for the
+    /// `sync_interval`.
+    async fn sync_if_elapsed(&self) -> Result<()> {
+        let inner = self.inner.clone();
+        self.runtime
+            .spawn_blocking(move || inner.sync_if_elapsed())
+            .await
+            .map_err(|e| Box::new(e) as _)
+            .context(Write)?
+    }
+
+    fn read(&self, ctx: &ReadContext, req: &ReadRequest) -> Result<FileLogIterator> {
+        debug!("Wal region begin reading, ctx:{:?}, req:{:?}", ctx, req);
+
+        let start_sequence = match req.start.as_start_sequence_number() {
+            Some(n) => n,
+            None => return Ok(FileLogIterator::new_empty()),
+        };
+        let end_sequence = match req.end.as_end_sequence_number() {
+            Some(n) => n,
+            None => return Ok(FileLogIterator::new_empty()),
+        };
+
+        // Deleted entries and entries being written are invisible.
+        let deleted_sequence = self.inner.deleted_sequence_num.load(Ordering::Acquire);
+        let last_sequence = self.inner.last_sequence_num.load(Ordering::Acquire);
+        let start_sequence = start_sequence.max(deleted_sequence.saturating_add(1));
+        let end_sequence = end_sequence.min(last_sequence);
+        if start_sequence > end_sequence {
+            return Ok(FileLogIterator::new_empty());
+        }
+
+        let segments = {
+            let segments = self.inner.segments.read().unwrap();
+            let next_firsts = segments
+                .keys()
+                .skip(1)
+                .map(Some)
+                .chain(std::iter::once(None));
+            segments
+                .iter()
+                .zip(next_firsts)
+                .filter(|((first, _), next_first)| {
+                    **first <= end_sequence
+                        && next_first.map_or(true, |next| *next - 1 >= start_sequence)
+                })
+                .map(|((_, path), _)| path.clone())
+                .collect()
+        };
+
+        Ok(FileLogIterator::with_data(
+            segments,
+            start_sequence,
+            end_sequence,
+        ))
+    }
+
+    async fn write<P: Payload>(&self, ctx: &WriteContext, batch: &LogWriteBatch<P>) -> Result<u64> {
+        debug!(
+            "Wal region begin writing, ctx:{:?}, log_entries_num:{}",
+            ctx,
+            batch.entries.len()
+        );
+
+        if batch.is_empty() {
+            return self.sequence_num();
+        }
+
+        let mut payloads = Vec::with_capacity(batch.len());
+        for entry in &batch.entries {
+            let mut buf = Vec::with_capacity(entry.payload.encode_size());
+            entry
+                .payload
+                .encode_to(&mut buf)
+                .map_err(|e| Box::new(e) as _)
+                .context(Encoding)?;
+            payloads.push(buf);
+        }
+
+        let inner = self.inner.clone();
+        self.runtime
+            .spawn_blocking(move || inner.append(&payloads))
+            .await
+            .map_err(|e| Box::new(e) as _)
+            .context(Write)?
+    }
+}
+
+/// [WalManager] implementation based on segmented append-only files.
+///
+/// Every [Region] owns a directory under the `wal_path`, in which the log
+/// entries are appended to the segment files. A segment is sealed once its
+/// size exceeds the `segment_size` and it is removed after all the entries in
+/// it are marked deleted.
+///
+/// If `sync_on_write` is disabled, the segments are synced every
+/// `sync_interval` in the background, and the writes in the last interval
+/// before a crash may be lost.
+pub struct FileWalImpl {
+    /// Wal data path
+    wal_path: PathBuf,
+    options: Options,
+    /// Runtime for read/write log entries
+    runtime: Arc<Runtime>,
+    /// Regions
+    regions: Arc<RwLock<HashMap<RegionId, Arc<Region>>>>,
+}
+
+/// Sync the segments of the regions every `sync_interval` until the wal is
+/// dropped.
+async fn sync_regions_periodically(
+    regions: Weak<RwLock<HashMap<RegionId, Arc<Region>>>>,
+    sync_interval: Duration,
+) {
+    loop {
+        tokio::time::sleep(sync_interval).await;
+
+        let regions: Vec<_> = match regions.upgrade() {
+            Some(regions) => regions.read().unwrap().values().cloned().collect(),
+            None => return,
+        };
+        for region in regions {
+            if let Err(e) = region.sync_if_elapsed().await {
+                warn!(
+                    "Wal region failed to sync segment, region_id:{}, err:{}",
+                    region.id, e
+                );
+            }
+        }
+    }
+}
+
+impl Drop for FileWalImpl {
+    fn drop(&mut self) {
+        // Clear all regions.
+        {
+            let mut regions = self.regions.write().unwrap();
+            regions.clear();
+        }
+
+        info!("FileWalImpl dropped, wal_path:{:?}", self.wal_path);
+    }
+}
+
+impl FileWalImpl {
+    fn region_dir(&self, region_id: RegionId) -> PathBuf {
+        self.wal_path.join(region_id.to_string())
+    }
+
+    fn open_region(&self, region_id: RegionId) -> Result<Region> {
+        let inner = RegionInner::open(self.region_dir(region_id), self.options)?;
+
+        Ok(Region {
+            id: region_id,
+            inner: Arc::new(inner),
+            runtime: self.runtime.clone(),
+            delete_lock: tokio::sync::Mutex::new(()),
+        })
+    }
+
+    /// Open all the regions found under the `wal_path`.
+    fn build_regions(&self) -> Result<()> {
+        let entries = fs::read_dir(&self.wal_path)
+            .map_err(|e| Box::new(e) as _)
+            .context(Initialization)?;
+
+        let mut regions = self.regions.write().unwrap();
+        for entry in entries {
+            let entry = entry
+                .map_err(|e| Box::new(e) as _)
+                .context(Initialization)?;
+            let is_dir = entry
+                .file_type()
+                .map_err(|e| Box::new(e) as _)
+                .context(Initialization)?
+                .is_dir();
+            let region_id = match entry.file_name().to_str().map(|s| s.parse::<RegionId>()) {
+                Some(Ok(v)) if is_dir => v,
+                _ => continue,
+            };
+
+            let region = self.open_region(region_id)?;
+            info!(
+                "FileWalImpl open region, wal_path:{:?}, region_id:{}, sequence_num:{}",
+                self.wal_path,
+                region_id,
+                region.sequence_num()?
+            );
+            regions.insert(region_id, Arc::new(region));
+        }
+
+        Ok(())
+    }
+
+    /// Get the region and create it if not found.
+    fn get_or_create_region(&self, region_id: RegionId) -> Result<Arc<Region>> {
+        {
+            let regions = self.regions.read().unwrap();
+            if let Some(region) = regions.get(&region_id) {
+                return Ok(region.clone());
+            }
+        }
+
+        let mut regions = self.regions.write().unwrap();
+        if let Some(region) = regions.get(&region_id) {
+            return Ok(region.clone());
+        }
+
+        info!(
+            "FileWalImpl create new region, wal_path:{:?}, region_id:{}",
+            self.wal_path, region_id
+        );
+
+        let region = Arc::new(self.open_region(region_id)?);
+        regions.insert(region_id, region.clone());
+
+        Ok(region)
+    }
+
+    /// Get the region
+    fn region(&self, region_id: RegionId) -> Option<Arc<Region>> {
+        let regions = self.regions.read().unwrap();
+        regions.get(&region_id).cloned()
+    }
+}
+
+/// Builder for `FileWalImpl`.
+pub struct Builder {
+    wal_path: PathBuf,
+    runtime: Arc<Runtime>,
+    segment_size: usize,
+    sync_on_write: bool,
+    sync_interval: Duration,
+}
+
+impl Builder {
+    pub fn new(wal_path: impl Into<PathBuf>, runtime: Arc<Runtime>) -> Self {
+        Self {
+            wal_path: wal_path.into(),
+            runtime,
+            segment_size: DEFAULT_SEGMENT_SIZE,
+            sync_on_write: true,
+            sync_interval: DEFAULT_SYNC_INTERVAL,
+        }
+    }
+
+    /// Set the max size of a segment.
+    pub fn segment_size(mut self, segment_size: usize) -> Self {
+        self.segment_size = segment_size;
+        self
+    }
+
+    /// Set whether to sync the segment after every write.
+    ///
+    /// The entries of a write batch are always synced together.
+    pub fn sync_on_write(mut self, sync_on_write: bool) -> Self {
+        self.sync_on_write = sync_on_write;
+        self
+    }
+
+    /// Set the interval to sync the segment if it is not synced after every
+    /// write, the writes in the last interval may be lost on crash.
+    pub fn sync_interval(mut self, sync_interval: Duration) -> Self {
+        self.sync_interval = sync_interval;
+        self
+    }
+
+    pub fn build(self) -> Result<FileWalImpl> {
+        fs::create_dir_all(&self.wal_path)
+            .map_err(|e| Box::new(e) as _)
+            .context(Open {
+                wal_path: self.wal_path.to_string_lossy().into_owned(),
+            })?;
+
+        let file_wal = FileWalImpl {
+            wal_path: self.wal_path,
+            options: Options {
+                // Avoid to create empty segments.
+                segment_size: self.segment_size.max(1) as u64,
+                sync_on_write: self.sync_on_write,
+                sync_interval: self.sync_interval,
+            },
+            runtime: self.runtime,
+            regions: Arc::new(RwLock::new(HashMap::new())),
+        };
+        file_wal.build_regions()?;
+
+        if !file_wal.options.sync_on_write {
+            let regions = Arc::downgrade(&file_wal.regions);
+            file_wal
+                .runtime
+                .spawn(sync_regions_periodically(regions, self.sync_interval));
+        }
+
+        Ok(file_wal)
+    }
+}
+
+/// Iterator over log entries in the segments.
+pub struct FileLogIterator {
+    /// Segments to read, ordered by their first sequence numbers
+    segments: VecDeque<PathBuf>,
+    /// The segment being read
+    current: Option<SegmentReader>,
+    min_sequence: SequenceNumber,
+    max_sequence: SequenceNumber,
+    /// denotes no more data to iterate
+    no_more_data: bool,
+}
+
+impl FileLogIterator {
+    /// Create iterator maybe containing data.
+    fn with_data(
+        segments: VecDeque<PathBuf>,
+        min_sequence: SequenceNumber,
+        max_sequence: SequenceNumber,
+    ) -> Self {
+        Self {
+            segments,
+            current: None,
+            min_sequence,
+            max_sequence,
+            no_more_data: false,
+        }
+    }
+
+    /// Create empty iterator.
+    fn new_empty() -> Self {
+        Self {
+            segments: VecDeque::new(),
+            current: None,
+            min_sequence: MIN_SEQUENCE_NUMBER,
+            max_sequence: MIN_SEQUENCE_NUMBER,
+            no_more_data: true,
+        }
+    }
+
+    /// Read the next record in the range `[min_sequence, max_sequence]`.
+    fn next_record(&mut self) -> Result<Option<(SequenceNumber, Vec<u8>)>> {
+        while !self.no_more_data {
+            let reader = match &mut self.current {
+                Some(v) => v,
+                None => {
+                    let path = match self.segments.pop_front() {
+                        Some(v) => v,
+                        None => {
+                            self.no_more_data = true;
+                            break;
+                        }
+                    };
+                    // The segment may have been removed after all its entries are deleted.
+                    self.current = SegmentReader::open(&path)
+                        .map_err(|e| Box::new(e) as _)
+                        .context(Read)?;
+                    continue;
+                }
+            };
+
+            match reader
+                .next_record(self.max_sequence)
+                .map_err(|e| Box::new(e) as _)
+                .context(Read)?
+            {
+                Some((sequence, _)) if sequence < self.min_sequence => continue,
+                Some(record) => {
+                    self.no_more_data = record.0 >= self.max_sequence;
+                    return Ok(Some(record));
+                }
+                None => self.current = None,
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+impl LogIterator for FileLogIterator {
+    fn next_log_entry<D: PayloadDecoder>(
+        &mut self,
+        decoder: &D,
+    ) -> Result<Option<LogEntry<D::Target>>> {
+        let (sequence, payload) = match self.next_record()? {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+
+        let payload = decoder
+            .decode(&mut payload.as_slice())
+            .map_err(|e| Box::new(e) as _)
+            .context(Decoding)?;
+
+        Ok(Some(LogEntry { sequence, payload }))
+    }
+}
+
+impl LogReader for FileWalImpl {
+    type Iterator = FileLogIterator;
+
+    fn read(&self, ctx: &ReadContext, req: &ReadRequest) -> Result<Self::Iterator> {
+        if let Some(region) = self.region(req.region_id) {
+            region.read(ctx, req)
+        } else {
+            Ok(FileLogIterator::new_empty())
+        }
+    }
+}
+
+#[async_trait]
+impl LogWriter for FileWalImpl {
+    async fn write<P: Payload>(
+        &self,
+        ctx: &WriteContext,
+        batch: &LogWriteBatch<P>,
+    ) -> Result<SequenceNumber> {
+        let region = self.get_or_create_region(batch.region_id)?;
+        region.write(ctx, batch).await
+    }
+}
+
+#[async_trait]
+impl WalManager for FileWalImpl {
+    fn sequence_num(&self, region_id: RegionId) -> Result<u64> {
+        if let Some(region) = self.region(region_id) {
+            return region.sequence_num();
+        }
+
+        Ok(MIN_SEQUENCE_NUMBER)
+    }
+
+    async fn mark_delete_entries_up_to(
+        &self,
+        region_id: RegionId,
+        sequence_num: SequenceNumber,
+    ) -> Result<()> {
+        if let Some(region) = self.region(region_id) {
+            return region.delete_entries_up_to(sequence_num).await;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Debug for FileWalImpl {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FileWalImpl")
+            .field("wal_path", &self.wal_path)
+            .field("segment_size", &self.options.segment_size)
+            .field("sync_on_write", &self.options.sync_on_write)
+            .field("sync_interval", &self.options.sync_interval)
+            .finish()
+    }
+}
@@ -0,0 +1,606 @@
+// Copyright 2022 CeresDB Project Authors. Licensed under Apache-2.0.
+
+//! Segment files and region meta of the file based Wal
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufReader, ErrorKind, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use common_types::{
+    bytes::{self, MemBuf, MemBufMut},
+    SequenceNumber, MIN_SEQUENCE_NUMBER,
+};
+use common_util::define_result;
+use snafu::{ensure, Backtrace, ResultExt, Snafu};
+
+/// Suffix of the segment file name.
+const SEGMENT_FILE_SUFFIX: &str = ".wal";
+/// Name of the file to persist the meta of a region.
+const META_FILE_NAME: &str = "META";
+/// Name of the temporary file used to replace the meta file atomically.
+const META_TMP_FILE_NAME: &str = "META.tmp";
+
+/// Size of the record header, refer to [encode_record] for the format.
+const RECORD_HEADER_SIZE: usize = 4 + 4 + 8;
+/// Size of the encoded meta, refer to [RegionMeta::encode] for the format.
+const META_SIZE: usize = 8 + 8 + 4;
+/// Max size of the payload of a record, a larger length read from the segment
+/// must be corrupted.
+pub const MAX_RECORD_SIZE: usize = 256 * 1024 * 1024;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Failed to create segment, path:{:?}, err:{}", path, source))]
+    CreateSegment { path: PathBuf, source: io::Error },
+
+    #[snafu(display("Failed to open segment, path:{:?}, err:{}", path, source))]
+    OpenSegment { path: PathBuf, source: io::Error },
+
+    #[snafu(display("Failed to append segment, path:{:?}, err:{}", path, source))]
+    AppendSegment { path: PathBuf, source: io::Error },
+
+    #[snafu(display("Failed to sync segment, path:{:?}, err:{}", path, source))]
+    SyncSegment { path: PathBuf, source: io::Error },
+
+    #[snafu(display("Failed to truncate segment, path:{:?}, err:{}", path, source))]
+    TruncateSegment { path: PathBuf, source: io::Error },
+
+    #[snafu(display("Failed to read segment, err:{}", source))]
+    ReadSegment { source: io::Error },
+
+    #[snafu(display(
+        "Record checksum mismatch, sequence:{}, expect:{}, given:{}.\nBacktrace:\n{}",
+        sequence,
+        expect,
+        given,
+        backtrace
+    ))]
+    ChecksumMismatch {
+        sequence: SequenceNumber,
+        expect: u32,
+        given: u32,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "Record is too large, size:{}, max size:{}.\nBacktrace:\n{}",
+        size,
+        max_size,
+        backtrace
+    ))]
+    RecordTooLarge {
+        size: usize,
+        max_size: usize,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "Invalid record length, sequence:{}, length:{}.\nBacktrace:\n{}",
+        sequence,
+        length,
+        backtrace
+    ))]
+    InvalidRecordLength {
+        sequence: SequenceNumber,
+        length: u32,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("Failed to list dir, path:{:?}, err:{}", path, source))]
+    ListDir { path: PathBuf, source: io::Error },
+
+    #[snafu(display("Failed to sync dir, path:{:?}, err:{}", path, source))]
+    SyncDir { path: PathBuf, source: io::Error },
+
+    #[snafu(display("Failed to read meta, path:{:?}, err:{}", path, source))]
+    ReadMeta { path: PathBuf, source: io::Error },
+
+    #[snafu(display("Failed to write meta, path:{:?}, err:{}", path, source))]
+    WriteMeta { path: PathBuf, source: io::Error },
+
+    #[snafu(display("Failed to decode meta, err:{}", source))]
+    DecodeMeta { source: bytes::Error },
+
+    #[snafu(display("Meta is corrupted, path:{:?}.\nBacktrace:\n{}", path, backtrace))]
+    CorruptedMeta { path: PathBuf, backtrace: Backtrace },
+}
+
+define_result!(Error);
+
+/// Name of the segment whose first sequence number is `first_sequence`.
+///
+/// The sequence number is padded so the lexicographic order of the names is
+/// the same as the order of the sequence numbers.
+pub fn segment_file_name(first_sequence: SequenceNumber) -> String {
+    format!("{:020}{}", first_sequence, SEGMENT_FILE_SUFFIX)
+}
+
+/// Parse the first sequence number from the name of the segment.
+///
+/// Returns `None` if the name is not a valid segment file name.
+pub fn parse_segment_file_name(name: &str) -> Option<SequenceNumber> {
+    name.strip_suffix(SEGMENT_FILE_SUFFIX)
+        .and_then(|s| s.parse().ok())
+}
+
+/// List all the segments under `dir`, ordered by their first sequence
+/// number.
+pub fn list_segments(dir: &Path) -> Result<Vec<(SequenceNumber, PathBuf)>> {
+    let mut segments = Vec::new();
+    for entry in fs::read_dir(dir).context(ListDir { path: dir })? {
+        let entry = entry.context(ListDir { path: dir })?;
+        let first_sequence = match entry.file_name().to_str().and_then(parse_segment_file_name) {
+            Some(v) => v,
+            None => continue,
+        };
+        segments.push((first_sequence, entry.path()));
+    }
+    segments.sort_unstable_by_key(|(first_sequence, _)| *first_sequence);
+
+    Ok(segments)
+}
+
+/// Make the creation/removal of files under the `dir` durable.
+pub fn sync_dir(dir: &Path) -> Result<()> {
+    File::open(dir)
+        .and_then(|f| f.sync_all())
+        .context(SyncDir { path: dir })
+}
+
+/// Append a record into the `buf`.
+///
+/// Record format:
+///
+/// ```text
+/// +-------------+------------+---------------+---------------+
+/// | length(u32) | crc32(u32) | sequence(u64) | payload bytes |
+/// +-------------+------------+---------------+---------------+
+/// ```
+///
+/// The `length` is the length of the payload and the checksum covers both the
+/// sequence and the payload. The payload must not be larger than
+/// [MAX_RECORD_SIZE].
+pub fn encode_record(buf: &mut Vec<u8>, sequence: SequenceNumber, payload: &[u8]) -> Result<()> {
+    ensure!(
+        payload.len() <= MAX_RECORD_SIZE,
+        RecordTooLarge {
+            size: payload.len(),
+            max_size: MAX_RECORD_SIZE,
+        }
+    );
+
+    buf.reserve(RECORD_HEADER_SIZE + payload.len());
+    // Writing to a vector never fails.
+    buf.write_u32(payload.len() as u32).unwrap();
+    buf.write_u32(record_checksum(sequence, payload)).unwrap();
+    buf.write_u64(sequence).unwrap();
+    buf.write_slice(payload).unwrap();
+
+    Ok(())
+}
+
+fn record_checksum(sequence: SequenceNumber, payload: &[u8]) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(&sequence.to_be_bytes());
+    hasher.update(payload);
+    hasher.finalize()
+}
+
+/// Writer appending records to the end of a segment.
+pub struct SegmentWriter {
+    path: PathBuf,
+    file: File,
+    /// Bytes of the valid records in the segment
+    size: u64,
+}
+
+impl SegmentWriter {
+    /// Create a new empty segment at `path`.
+    pub fn create(path: PathBuf) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create_new(true)
+            .append(true)
+            .open(&path)
+            .context(CreateSegment { path: &path })?;
+        if let Some(dir) = path.parent() {
+            sync_dir(dir)?;
+        }
+
+        Ok(Self {
+            path,
+            file,
+            size: 0,
+        })
+    }
+
+    /// Open an existing segment at `path` and drop all the bytes after
+    /// `valid_size`.
+    pub fn open(path: PathBuf, valid_size: u64) -> Result<Self> {
+        let file = OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .context(OpenSegment { path: &path })?;
+        let file_size = file.metadata().context(OpenSegment { path: &path })?.len();
+        if file_size > valid_size {
+            file.set_len(valid_size)
+                .context(TruncateSegment { path: &path })?;
+            file.sync_all().context(SyncSegment { path: &path })?;
+        }
+
+        Ok(Self {
+            path,
+            file,
+            size: valid_size,
+        })
+    }
+
+    #[inline]
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Append the encoded records to the segment.
+    ///
+    /// The segment is truncated to its previous size on failure so that no
+    /// partial records are left in it.
+    pub fn append(&mut self, buf: &[u8]) -> Result<()> {
+        if let Err(e) = self.file.write_all(buf) {
+            // Best effort to remove the partial written records.
+            let _ = self.file.set_len(self.size);
+            return Err(e).context(AppendSegment { path: &self.path });
+        }
+        self.size += buf.len() as u64;
+
+        Ok(())
+    }
+
+    pub fn sync(&self) -> Result<()> {
+        self.file
+            .sync_data()
+            .context(SyncSegment { path: &self.path })
+    }
+}
+
+/// Reader over the records of a segment.
+pub struct SegmentReader {
+    reader: BufReader<File>,
+    header_buf: [u8; RECORD_HEADER_SIZE],
+    /// Size of the segment when it is opened, the records appended later are
+    /// invisible to the reader
+    file_size: u64,
+    /// Offset of the next record to read
+    offset: u64,
+}
+
+impl SegmentReader {
+    /// Open the segment at `path`.
+    ///
+    /// Returns `None` if the segment doesn't exist, which means it has been
+    /// deleted.
+    pub fn open(path: &Path) -> Result<Option<Self>> {
+        let file = match File::open(path) {
+            Ok(v) => v,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).context(OpenSegment { path }),
+        };
+        let file_size = file.metadata().context(OpenSegment { path })?.len();
+
+        Ok(Some(Self {
+            reader: BufReader::new(file),
+            header_buf: [0; RECORD_HEADER_SIZE],
+            file_size,
+            offset: 0,
+        }))
+    }
+
+    /// Read the header of the next record and returns the sequence number
+    /// and the length of the payload.
+    ///
+    /// Returns `None` if the end of the segment is reached (an incomplete
+    /// header is also regarded as the end).
+    fn next_header(&mut self) -> Result<Option<(SequenceNumber, u32, u32)>> {
+        if self.file_size - self.offset < RECORD_HEADER_SIZE as u64
+            || !read_exact_or_eof(&mut self.reader, &mut self.header_buf)?
+        {
+            return Ok(None);
+        }
+        self.offset += RECORD_HEADER_SIZE as u64;
+
+        let mut buf = &self.header_buf[..];
+        // The header buffer has the exact size to decode.
+        let length = buf.read_u32().unwrap();
+        let checksum = buf.read_u32().unwrap();
+        let sequence = buf.read_u64().unwrap();
+
+        Ok(Some((sequence, length, checksum)))
+    }
+
+    /// Read the next record whose sequence number is not greater than
+    /// `max_sequence`.
+    ///
+    /// Returns `None` if the end of the segment is reached or a record with a
+    /// larger sequence number is found. A record whose payload is incomplete
+    /// is regarded as the end of the segment, and a record whose length
+    /// exceeds the [MAX_RECORD_SIZE] is regarded as corrupted.
+    pub fn next_record(
+        &mut self,
+        max_sequence: SequenceNumber,
+    ) -> Result<Option<(SequenceNumber, Vec<u8>)>> {
+        let (sequence, length, checksum) = match self.next_header()? {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+        if sequence > max_sequence {
+            return Ok(None);
+        }
+        ensure!(
+            length as usize <= MAX_RECORD_SIZE,
+            InvalidRecordLength { sequence, length }
+        );
+        // Check the length before allocating the payload, a torn header may
+        // give a length far beyond the segment.
+        if u64::from(length) > self.file_size - self.offset {
+            return Ok(None);
+        }
+
+        let mut payload = vec![0; length as usize];
+        if !read_exact_or_eof(&mut self.reader, &mut payload)? {
+            return Ok(None);
+        }
+        self.offset += u64::from(length);
+        let actual = record_checksum(sequence, &payload);
+        ensure!(
+            actual == checksum,
+            ChecksumMismatch {
+                sequence,
+                expect: checksum,
+                given: actual,
+            }
+        );
+
+        Ok(Some((sequence, payload)))
+    }
+}
+
+/// Fill the `buf` from the `reader`.
+///
+/// Returns false if the reader reaches its end before the `buf` is filled.
+fn read_exact_or_eof(reader: &mut impl Read, buf: &mut [u8]) -> Result<bool> {
+    match reader.read_exact(buf) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e).context(ReadSegment),
+    }
+}
+
+/// Scan the segment at `path` to find out the valid records.
+///
+/// Returns the size of the valid records and the sequence of the last valid
+/// record (`None` if no valid record). The scan stops at the first incomplete
+/// or corrupted record, which is usually left by a crash during writing.
+pub fn scan_segment(path: &Path) -> Result<(u64, Option<SequenceNumber>)> {
+    let mut reader = match SegmentReader::open(path)? {
+        Some(v) => v,
+        None => return Ok((0, None)),
+    };
+
+    let mut valid_size = 0;
+    let mut last_sequence = None;
+    loop {
+        match reader.next_record(SequenceNumber::MAX) {
+            Ok(Some((sequence, payload))) => {
+                valid_size += (RECORD_HEADER_SIZE + payload.len()) as u64;
+                last_sequence = Some(sequence);
+            }
+            Ok(None)
+            | Err(Error::ChecksumMismatch { .. })
+            | Err(Error::InvalidRecordLength { .. }) => break,
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok((valid_size, last_sequence))
+}
+
+/// Meta of a region which is required to survive the deletion of segments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegionMeta {
+    /// Max sequence number ever written when the meta is updated
+    pub max_sequence: SequenceNumber,
+    /// Entries whose sequence number is not greater than it are deleted
+    pub deleted_sequence: SequenceNumber,
+}
+
+impl Default for RegionMeta {
+    fn default() -> Self {
+        Self {
+            max_sequence: MIN_SEQUENCE_NUMBER,
+            deleted_sequence: MIN_SEQUENCE_NUMBER,
+        }
+    }
+}
+
+impl RegionMeta {
+    /// Meta format:
+    ///
+    /// ```text
+    /// +-------------------+------------------------+------------+
+    /// | max_sequence(u64) | deleted_sequence(u64)  | crc32(u32) |
+    /// +-------------------+------------------------+------------+
+    /// ```
+    fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(META_SIZE);
+        // Writing to a vector never fails.
+        buf.write_u64(self.max_sequence).unwrap();
+        buf.write_u64(self.deleted_sequence).unwrap();
+        let checksum = crc32fast::hash(&buf);
+        buf.write_u32(checksum).unwrap();
+        buf
+    }
+
+    fn decode(path: &Path, mut buf: &[u8]) -> Result<Self> {
+        ensure!(buf.len() == META_SIZE, CorruptedMeta { path });
+        let expect_checksum = crc32fast::hash(&buf[..META_SIZE - 4]);
+
+        let max_sequence = buf.read_u64().context(DecodeMeta)?;
+        let deleted_sequence = buf.read_u64().context(DecodeMeta)?;
+        let checksum = buf.read_u32().context(DecodeMeta)?;
+        ensure!(checksum == expect_checksum, CorruptedMeta { path });
+
+        Ok(Self {
+            max_sequence,
+            deleted_sequence,
+        })
+    }
+
+    /// Load the meta from the `dir` of the region, returns the default meta
+    /// if it doesn't exist.
+    pub fn load(dir: &Path) -> Result<Self> {
+        let path = dir.join(META_FILE_NAME);
+        match fs::read(&path) {
+            Ok(buf) => Self::decode(&path, &buf),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).context(ReadMeta { path }),
+        }
+    }
+
+    /// Persist the meta to the `dir` of the region.
+    ///
+    /// The meta is written to a temporary file first and then renamed to
+    /// replace the old one, so a crash never leaves a partial meta.
+    pub fn store(&self, dir: &Path) -> Result<()> {
+        let tmp_path = dir.join(META_TMP_FILE_NAME);
+        {
+            let mut file = File::create(&tmp_path).context(WriteMeta { path: &tmp_path })?;
+            file.write_all(&self.encode())
+                .context(WriteMeta { path: &tmp_path })?;
+            file.sync_all().context(WriteMeta { path: &tmp_path })?;
+        }
+
+        let path = dir.join(META_FILE_NAME);
+        fs::rename(&tmp_path, &path).context(WriteMeta { path })?;
+        sync_dir(dir)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn append_records(writer: &mut SegmentWriter, sequences: std::ops::Range<u64>) {
+        let mut buf = Vec::new();
+        for sequence in sequences {
+            encode_record(&mut buf, sequence, &sequence.to_be_bytes()).unwrap();
+        }
+        writer.append(&buf).unwrap();
+        writer.sync().unwrap();
+    }
+
+    #[test]
+    fn test_segment_file_name() {
+        let name = segment_file_name(42);
+        assert_eq!("00000000000000000042.wal", name);
+        assert_eq!(Some(42), parse_segment_file_name(&name));
+        assert_eq!(None, parse_segment_file_name("META"));
+        assert_eq!(None, parse_segment_file_name("abc.wal"));
+    }
+
+    #[test]
+    fn test_segment_read_write() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(segment_file_name(1));
+        let mut writer = SegmentWriter::create(path.clone()).unwrap();
+        append_records(&mut writer, 1..11);
+
+        let mut reader = SegmentReader::open(&path).unwrap().unwrap();
+        for expect in 1..6 {
+            let (sequence, payload) = reader.next_record(5).unwrap().unwrap();
+            assert_eq!(expect, sequence);
+            assert_eq!(&expect.to_be_bytes()[..], &payload[..]);
+        }
+        // Stop at the record whose sequence is greater than the max sequence.
+        assert!(reader.next_record(5).unwrap().is_none());
+
+        assert_eq!((writer.size(), Some(10)), scan_segment(&path).unwrap());
+    }
+
+    #[test]
+    fn test_segment_recover_torn_tail() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(segment_file_name(1));
+        let mut writer = SegmentWriter::create(path.clone()).unwrap();
+        append_records(&mut writer, 1..4);
+        let valid_size = writer.size();
+
+        // Append a record whose payload is incomplete.
+        let mut buf = Vec::new();
+        encode_record(&mut buf, 4, &[1, 2, 3, 4]).unwrap();
+        writer.append(&buf[..buf.len() - 2]).unwrap();
+        assert_eq!((valid_size, Some(3)), scan_segment(&path).unwrap());
+
+        // Reopen the segment would drop the torn record.
+        let mut writer = SegmentWriter::open(path.clone(), valid_size).unwrap();
+        append_records(&mut writer, 4..6);
+        assert_eq!((writer.size(), Some(5)), scan_segment(&path).unwrap());
+    }
+
+    #[test]
+    fn test_segment_checksum_mismatch() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(segment_file_name(1));
+        let mut writer = SegmentWriter::create(path.clone()).unwrap();
+        let mut buf = Vec::new();
+        encode_record(&mut buf, 1, &[1, 2, 3, 4]).unwrap();
+        // Corrupt the payload.
+        let last = buf.len() - 1;
+        buf[last] ^= 0xff;
+        writer.append(&buf).unwrap();
+
+        let mut reader = SegmentReader::open(&path).unwrap().unwrap();
+        assert!(matches!(
+            reader.next_record(SequenceNumber::MAX),
+            Err(Error::ChecksumMismatch { .. })
+        ));
+        assert_eq!((0, None), scan_segment(&path).unwrap());
+    }
+
+    #[test]
+    fn test_segment_invalid_record_length() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(segment_file_name(1));
+        let mut writer = SegmentWriter::create(path.clone()).unwrap();
+        append_records(&mut writer, 1..3);
+        let valid_size = writer.size();
+
+        // A record whose length exceeds the remaining size of the segment.
+        let mut buf = Vec::new();
+        encode_record(&mut buf, 3, &[1, 2, 3, 4]).unwrap();
+        buf[..4].copy_from_slice(&u32::MAX.to_be_bytes());
+        writer.append(&buf).unwrap();
+
+        let mut reader = SegmentReader::open(&path).unwrap().unwrap();
+        for _ in 1..3 {
+            assert!(reader.next_record(SequenceNumber::MAX).unwrap().is_some());
+        }
+        assert!(matches!(
+            reader.next_record(SequenceNumber::MAX),
+            Err(Error::InvalidRecordLength { .. })
+        ));
+        assert_eq!((valid_size, Some(2)), scan_segment(&path).unwrap());
+    }
+
+    #[test]
+    fn test_region_meta() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(RegionMeta::default(), RegionMeta::load(dir.path()).unwrap());
+
+        let meta = RegionMeta {
+            max_sequence: 100,
+            deleted_sequence: 50,
+        };
+        meta.store(dir.path()).unwrap();
+        assert_eq!(meta, RegionMeta::load(dir.path()).unwrap());
+    }
+}
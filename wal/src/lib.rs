@@ -2,6 +2,7 @@
 
 //! Write Ahead Log
 
+pub mod file_impl;
 pub mod log_batch;
 pub mod manager;
 pub mod rocks_impl;
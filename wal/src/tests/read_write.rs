@@ -7,7 +7,7 @@ use common_types::SequenceNumber;
 use crate::{
     log_batch::LogWriteBatch,
     manager::{LogReader, LogWriter, ReadBoundary, ReadRequest, RegionId, WalManager},
-    tests::util::{RocksTestEnv, TestEnv, TestPayload, WalBuilder},
+    tests::util::{FileTestEnv, RocksTestEnv, TestEnv, TestPayload, WalBuilder},
 };
 
 fn check_write_batch_with_read_request<B: WalBuilder>(
@@ -375,12 +375,18 @@ async fn sequence_increase_monotonically_delete_reopen_write<B: WalBuilder>(env:
 fn test_simple_read_write() {
     let rocks_env = RocksTestEnv::new(2);
     rocks_env.runtime.block_on(simple_read_write(&rocks_env, 0));
+
+    let file_env = FileTestEnv::new(2);
+    file_env.runtime.block_on(simple_read_write(&file_env, 0));
 }
 
 #[test]
 fn test_read_with_boundary() {
     let rocks_env = RocksTestEnv::new(2);
     rocks_env.runtime.block_on(read_with_boundary(&rocks_env));
+
+    let file_env = FileTestEnv::new(2);
+    file_env.runtime.block_on(read_with_boundary(&file_env));
 }
 
 #[test]
@@ -389,37 +395,60 @@ fn test_write_multiple_regions() {
     rocks_env
         .runtime
         .block_on(write_multiple_regions_parallelly(rocks_env.clone()));
+
+    let file_env = Arc::new(FileTestEnv::new(4));
+    file_env
+        .runtime
+        .block_on(write_multiple_regions_parallelly(file_env.clone()));
 }
 
 #[test]
 fn test_reopen() {
     let rocks_env = RocksTestEnv::new(2);
     rocks_env.runtime.block_on(reopen(&rocks_env));
+
+    let file_env = FileTestEnv::new(2);
+    file_env.runtime.block_on(reopen(&file_env));
 }
 
 #[test]
 fn test_complex_read_write() {
     let rocks_env = RocksTestEnv::new(2);
     rocks_env.runtime.block_on(complex_read_write(&rocks_env));
+
+    let file_env = FileTestEnv::new(2);
+    file_env.runtime.block_on(complex_read_write(&file_env));
 }
 
 #[test]
 fn test_simple_write_delete() {
     let rocks_env = RocksTestEnv::new(2);
     rocks_env.runtime.block_on(simple_write_delete(&rocks_env));
+
+    let file_env = FileTestEnv::new(2);
+    file_env.runtime.block_on(simple_write_delete(&file_env));
 }
 
 #[test]
 fn test_write_delete_half() {
     let rocks_env = RocksTestEnv::new(2);
     rocks_env.runtime.block_on(write_delete_half(&rocks_env));
+
+    let file_env = FileTestEnv::new(2);
+    file_env.runtime.block_on(write_delete_half(&file_env));
 }
+
 #[test]
 fn test_write_delete_multiple_regions() {
     let rocks_env = RocksTestEnv::new(2);
     rocks_env
         .runtime
         .block_on(write_delete_multiple_regions(&rocks_env));
+
+    let file_env = FileTestEnv::new(2);
+    file_env
+        .runtime
+        .block_on(write_delete_multiple_regions(&file_env));
 }
 
 #[test]
@@ -428,6 +457,11 @@ fn test_sequence_increase_monotonically_multiple_writes() {
     rocks_env
         .runtime
         .block_on(sequence_increase_monotonically_multiple_writes(&rocks_env));
+
+    let file_env = FileTestEnv::new(2);
+    file_env
+        .runtime
+        .block_on(sequence_increase_monotonically_multiple_writes(&file_env));
 }
 
 #[test]
@@ -436,6 +470,11 @@ fn test_sequence_increase_monotonically_delete_write() {
     rocks_env
         .runtime
         .block_on(sequence_increase_monotonically_delete_write(&rocks_env));
+
+    let file_env = FileTestEnv::new(2);
+    file_env
+        .runtime
+        .block_on(sequence_increase_monotonically_delete_write(&file_env));
 }
 
 #[test]
@@ -446,4 +485,11 @@ fn test_sequence_increase_monotonically_delete_reopen_write() {
         .block_on(sequence_increase_monotonically_delete_reopen_write(
             &rocks_env,
         ));
+
+    let file_env = FileTestEnv::new(2);
+    file_env
+        .runtime
+        .block_on(sequence_increase_monotonically_delete_reopen_write(
+            &file_env,
+        ));
 }
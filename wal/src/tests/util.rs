@@ -9,6 +9,7 @@ use common_util::runtime::{self, Runtime};
 use tempfile::TempDir;
 
 use crate::{
+    file_impl::{self, manager::FileWalImpl},
     log_batch::{LogWriteBatch, LogWriteEntry, Payload, PayloadDecoder},
     manager::{LogIterator, LogReader, ReadContext, RegionId, WalManager, WriteContext},
     rocks_impl::{self, manager::RocksImpl},
@@ -44,6 +45,30 @@ impl WalBuilder for RocksWalBuilder {
 
 pub type RocksTestEnv = TestEnv<RocksWalBuilder>;
 
+/// Segment size for testing, which is small enough to make the segments
+/// rotate frequently.
+const TEST_SEGMENT_SIZE: usize = 64;
+
+#[derive(Default)]
+pub struct FileWalBuilder;
+
+impl WalBuilder for FileWalBuilder {
+    type Wal = FileWalImpl;
+
+    fn build(&self, data_path: &Path, runtime: Arc<Runtime>) -> Arc<Self::Wal> {
+        let wal_builder =
+            file_impl::manager::Builder::new(data_path, runtime).segment_size(TEST_SEGMENT_SIZE);
+
+        Arc::new(
+            wal_builder
+                .build()
+                .expect("should succeed to build file wal"),
+        )
+    }
+}
+
+pub type FileTestEnv = TestEnv<FileWalBuilder>;
+
 /// The environment for testing wal.
 pub struct TestEnv<B> {
     pub dir: TempDir,